@@ -0,0 +1,181 @@
+//! Things the pet can wear: a bowtie, a scarf and a wizard hat
+//!
+//! Accessories are unlocked by spending time with the pet and then put on and
+//! taken off with `/wear`. Both the unlocked and the worn ones are part of the
+//! pet's state. `dress` layers whatever the pet wears onto any ASCII art: hats
+//! sit on top of it, and things worn around the neck go two thirds of the way
+//! down, which is where the head ends for most of the gallery. The card shows
+//! the dressed art and portraits describe the outfit.
+
+use chrono::{DateTime, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::pet::PetState;
+
+/// Chats it takes to earn the wizard hat
+const WIZARD_HAT_CHATS: usize = 50;
+/// Days in a row of chatting it takes to earn the scarf
+const SCARF_STREAK_DAYS: usize = 3;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Accessory {
+    Bowtie,
+    Scarf,
+    WizardHat,
+}
+
+/// Where an accessory is worn; one accessory per slot
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Slot {
+    Head,
+    Neck,
+}
+
+impl Accessory {
+    pub const ALL: [Accessory; 3] = [Accessory::Bowtie, Accessory::Scarf, Accessory::WizardHat];
+
+    /// The name used with `/wear`
+    pub fn name(self) -> &'static str {
+        match self {
+            Accessory::Bowtie => "bowtie",
+            Accessory::Scarf => "scarf",
+            Accessory::WizardHat => "wizard-hat",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase().replace([' ', '_'], "-");
+        Self::ALL.into_iter().find(|accessory| accessory.name() == name || (name == "hat" && *accessory == Accessory::WizardHat))
+    }
+
+    pub fn slot(self) -> Slot {
+        match self {
+            Accessory::Bowtie | Accessory::Scarf => Slot::Neck,
+            Accessory::WizardHat => Slot::Head,
+        }
+    }
+
+    /// How an image model should picture it
+    pub fn describe(self) -> &'static str {
+        match self {
+            Accessory::Bowtie => "a dapper red bowtie",
+            Accessory::Scarf => "a cozy striped scarf",
+            Accessory::WizardHat => "a tall starry wizard hat",
+        }
+    }
+
+    /// What it takes to unlock
+    pub fn requirement(self) -> String {
+        match self {
+            Accessory::Bowtie => "always available".to_string(),
+            Accessory::Scarf => format!("chat {} days in a row", SCARF_STREAK_DAYS),
+            Accessory::WizardHat => format!("chat {} times", WIZARD_HAT_CHATS),
+        }
+    }
+
+    fn is_earned<Tz: TimeZone>(self, state: &PetState, now: &DateTime<Tz>) -> bool {
+        match self {
+            Accessory::Bowtie => true,
+            Accessory::Scarf => state.chat_streak(now) >= SCARF_STREAK_DAYS,
+            Accessory::WizardHat => state.chat_history.len() >= WIZARD_HAT_CHATS,
+        }
+    }
+
+    /// The lines that draw it
+    fn sprite(self, width: usize) -> Vec<String> {
+        match self {
+            Accessory::Bowtie => vec![">-<".to_string()],
+            // Wraps the whole neck, with an end hanging down
+            Accessory::Scarf => vec!["~".repeat(width.clamp(5, 9)), "   ~".to_string()],
+            Accessory::WizardHat => vec!["/*\\".to_string(), "/_*_\\".to_string()],
+        }
+    }
+}
+
+/// Accessories the pet has just earned, in unlock order; they're added to
+/// `state.unlocked_accessories`
+pub fn unlock<Tz: TimeZone>(state: &mut PetState, now: &DateTime<Tz>) -> Vec<Accessory> {
+    let earned: Vec<Accessory> = Accessory::ALL
+        .into_iter()
+        .filter(|accessory| !state.unlocked_accessories.contains(accessory) && accessory.is_earned(state, now))
+        .collect();
+    state.unlocked_accessories.extend(&earned);
+    earned
+}
+
+/// Puts `accessory` on, replacing whatever was worn in its slot, or takes it
+/// off if it's already worn; returns whether it's worn now
+pub fn toggle(state: &mut PetState, accessory: Accessory) -> bool {
+    if state.accessories.contains(&accessory) {
+        state.accessories.retain(|&worn| worn != accessory);
+        return false;
+    }
+    state.accessories.retain(|worn| worn.slot() != accessory.slot());
+    state.accessories.push(accessory);
+    true
+}
+
+/// The outfit for a prompt, like "a tall starry wizard hat and a dapper red bowtie"
+pub fn outfit(worn: &[Accessory]) -> Option<String> {
+    let descriptions: Vec<&str> = worn.iter().map(|accessory| accessory.describe()).collect();
+    match descriptions.as_slice() {
+        [] => None,
+        [only] => Some(only.to_string()),
+        [rest @ .., last] => Some(format!("{} and {}", rest.join(", "), last)),
+    }
+}
+
+/// The art with the worn accessories layered on, centered on the art
+pub fn dress(art: &str, worn: &[Accessory]) -> String {
+    if worn.is_empty() {
+        return art.to_string();
+    }
+    let mut lines: Vec<String> = art.lines().map(str::to_string).collect();
+    let drawn = || lines.iter().filter(|line| !line.trim().is_empty());
+    let left = drawn().map(|line| line.len() - line.trim_start().len()).min().unwrap_or(0);
+    let right = drawn().map(|line| line.trim_end().chars().count()).max().unwrap_or(0);
+    let center = (left + right) / 2;
+    let centered = |sprite: Vec<String>| -> Vec<String> {
+        sprite.into_iter().map(|line| format!("{}{}", " ".repeat(center.saturating_sub(line.chars().count() / 2)), line)).collect()
+    };
+
+    // The neck first, so its position is counted before a hat adds lines on top
+    if let Some(&neck) = worn.iter().find(|accessory| accessory.slot() == Slot::Neck) {
+        let first = lines.iter().position(|line| !line.trim().is_empty()).unwrap_or(0);
+        let last = lines.iter().rposition(|line| !line.trim().is_empty()).unwrap_or(0);
+        let below = first + ((last - first + 1) * 2).div_ceil(3);
+        let sprite = centered(neck.sprite(right.saturating_sub(left) / 2));
+        lines.splice(below..below, sprite);
+    }
+    if let Some(&head) = worn.iter().find(|accessory| accessory.slot() == Slot::Head) {
+        let top = lines.iter().position(|line| !line.trim().is_empty()).unwrap_or(0);
+        lines.splice(top..top, centered(head.sprite(0)));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accessories_are_layered_onto_the_art() {
+        let cat = "\n  /\\___/\\\n (  o o  )\n (  =^=  )\n  (____)\n";
+        assert_eq!(
+            dress(cat, &[Accessory::WizardHat, Accessory::Bowtie]),
+            "\n    /*\\\n   /_*_\\\n  /\\___/\\\n (  o o  )\n (  =^=  )\n    >-<\n  (____)"
+        );
+        assert_eq!(dress(cat, &[]), cat);
+    }
+
+    #[test]
+    fn a_slot_holds_one_accessory() {
+        let mut state = PetState::default();
+        assert!(toggle(&mut state, Accessory::Bowtie));
+        assert!(toggle(&mut state, Accessory::WizardHat));
+        assert!(toggle(&mut state, Accessory::Scarf));
+        assert_eq!(state.accessories, vec![Accessory::WizardHat, Accessory::Scarf]);
+        assert!(!toggle(&mut state, Accessory::Scarf));
+        assert_eq!(outfit(&state.accessories).as_deref(), Some("a tall starry wizard hat"));
+    }
+}
@@ -0,0 +1,116 @@
+//! Adopting a pet from a randomized litter
+//!
+//! A brand-new pet (or `/adopt`) is offered a litter of candidates, each a
+//! gallery species with a color, a personality quirk and a talent it starts
+//! out with. The one picked is saved in the pet's state as its pedigree,
+//! which shapes its art, its portrait and how it talks, so no two
+//! companions are quite the same.
+
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::art;
+use crate::clock;
+
+/// Candidates in a litter
+pub const LITTER_SIZE: usize = 4;
+
+const COLORS: &[&str] = &["ginger", "silver", "midnight", "cream", "speckled", "copper", "smoky", "snowy", "tabby", "golden"];
+
+const QUIRKS: &[&str] = &[
+    "hums while you type",
+    "hoards semicolons",
+    "sneezes at merge conflicts",
+    "naps on the Enter key",
+    "collects dotfiles",
+    "chases the cursor",
+    "flinches at rm -rf",
+    "counts your keystrokes",
+    "sleeps in /tmp",
+    "purrs at green test runs",
+];
+
+/// Talents and how each shows in the pet's replies
+const TALENTS: &[(&str, &str)] = &[
+    ("git whisperer", "You love git and like to slip in a git tip when it fits."),
+    ("Vim devotee", "You can't resist pointing out a handy Vim motion."),
+    ("pipe plumber", "You love a clever shell pipeline and suggest one when it helps."),
+    ("tidy keeper", "You care about tidy directories, dotfiles and scripts."),
+    ("night owl", "You perk up in the evening and are at your most playful late at night."),
+    ("speed demon", "You love shaving keystrokes off a workflow with aliases and shortcuts."),
+    ("patient teacher", "You explain the why behind commands, step by step."),
+];
+
+/// What makes an adopted pet itself
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Pedigree {
+    /// A gallery species, like "fox"
+    pub species: String,
+    pub color: String,
+    /// A personality quirk, like "hums while you type"
+    pub quirk: String,
+    /// What it's good at from the start, like "git whisperer"
+    pub talent: String,
+}
+
+impl Pedigree {
+    /// Like "a ginger fox who hums while you type (git whisperer)"
+    pub fn describe(&self) -> String {
+        format!("a {} {} who {} ({})", self.color, self.species, self.quirk, self.talent)
+    }
+
+    /// The system prompt's line about who the pet is
+    pub fn personality(&self, name: &str) -> String {
+        let mut text = format!("You are {}, a {} {} who {}. ", name, self.color, self.species, self.quirk);
+        if let Some((_, line)) = TALENTS.iter().find(|(talent, _)| *talent == self.talent) {
+            text.push_str(line);
+            text.push(' ');
+        }
+        text.push_str("Let this color your tone now and then, without getting in the way of useful answers.");
+        text
+    }
+}
+
+/// A fresh litter, each candidate of a different species
+pub fn litter() -> Vec<Pedigree> {
+    let mut rng = clock::rng();
+    let species: Vec<&str> = art::gallery_names().into_iter().filter(|name| !name.contains('-')).collect();
+    species
+        .choose_multiple(&mut rng, LITTER_SIZE)
+        .map(|species| Pedigree {
+            species: species.to_string(),
+            color: COLORS.choose(&mut rng).unwrap_or(&COLORS[0]).to_string(),
+            quirk: QUIRKS.choose(&mut rng).unwrap_or(&QUIRKS[0]).to_string(),
+            talent: TALENTS.choose(&mut rng).map_or(TALENTS[0].0, |(talent, _)| talent).to_string(),
+        })
+        .collect()
+}
+
+/// The litter as a numbered list
+pub fn list(litter: &[Pedigree]) -> String {
+    litter.iter().enumerate().map(|(i, pet)| format!("{}. {}", i + 1, pet.describe())).collect::<Vec<_>>().join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn litters_are_gallery_species_with_known_talents() {
+        let litter = litter();
+        assert_eq!(litter.len(), LITTER_SIZE);
+        for pet in &litter {
+            assert!(art::gallery(&pet.species).is_some());
+            assert!(litter.iter().filter(|other| other.species == pet.species).count() == 1);
+            assert!(pet.personality("Whiskers").contains(TALENTS.iter().find(|(talent, _)| *talent == pet.talent).unwrap().1));
+        }
+        let fox = Pedigree {
+            species: "fox".to_string(),
+            color: "ginger".to_string(),
+            quirk: "hums while you type".to_string(),
+            talent: "retired".to_string(),
+        };
+        assert_eq!(fox.describe(), "a ginger fox who hums while you type (retired)");
+        assert!(fox.personality("Rusty").starts_with("You are Rusty, a ginger fox who hums while you type. Let this"));
+    }
+}
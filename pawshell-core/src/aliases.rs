@@ -0,0 +1,154 @@
+//! Managed alias file for PetCLI
+//!
+//! When the pet suggests aliases or shell functions (after `/analyze`, a proactive
+//! suggestion or a regular answer), `/adopt-alias` appends the chosen ones to
+//! `aliases.sh` in the config directory. The user sources that file once from
+//! their shell rc file and every adopted alias is available from then on.
+//!
+//! Adopted aliases are also recorded in the pet's state, so the same suggestion
+//! isn't offered twice and the pet can tell which of its ideas were taken up.
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use crate::clock;
+use crate::config_path;
+
+/// Multi-line functions longer than this are assumed to be mis-parsed
+const MAX_FUNCTION_LINES: usize = 40;
+
+/// An alias or shell function found in a pet response
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct AliasDefinition {
+    pub name: String,
+    /// The full definition, e.g. `alias gs='git status'`
+    pub definition: String,
+}
+
+/// A suggestion the user adopted
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AdoptedAlias {
+    #[serde(flatten)]
+    pub alias: AliasDefinition,
+    pub adopted: DateTime<Utc>,
+}
+
+pub fn aliases_file_path() -> PathBuf {
+    config_path::get_config_dir().join("aliases.sh")
+}
+
+/// The line users add to their rc file to load adopted aliases
+pub fn source_line() -> String {
+    let path = aliases_file_path();
+    format!("[ -f '{0}' ] && . '{0}'", path.display())
+}
+
+/// Finds alias and function definitions in a pet response
+///
+/// Definitions are recognized at the start of a line (including inside code
+/// blocks) or as an inline `code` span. Each name is returned once.
+pub fn extract_definitions(response: &str) -> Vec<AliasDefinition> {
+    let mut definitions: Vec<AliasDefinition> = Vec::new();
+    let mut push = |definition: AliasDefinition| {
+        if !definitions.iter().any(|d| d.name == definition.name) {
+            definitions.push(definition);
+        }
+    };
+
+    let raw_lines: Vec<&str> = response.lines().collect();
+    let lines: Vec<&str> = raw_lines.iter().map(|line| line.trim().trim_start_matches("$ ")).collect();
+    let mut i = 0;
+    while i < lines.len() {
+        if let Some(alias) = parse_alias(lines[i]) {
+            push(alias);
+        } else if let Some(name) = function_name(lines[i]) {
+            if let Some(end) = function_end(&lines[i..]) {
+                // Keep the body's indentation
+                let body = raw_lines[i + 1..=i + end].iter().map(|line| line.trim_end());
+                let definition = std::iter::once(lines[i]).chain(body).collect::<Vec<_>>().join("\n");
+                push(AliasDefinition { name, definition });
+                i += end;
+            }
+        }
+        i += 1;
+    }
+
+    for span in response.split('`').skip(1).step_by(2) {
+        if let Some(alias) = parse_alias(span.trim()) {
+            push(alias);
+        }
+    }
+    definitions
+}
+
+/// Appends a definition to the managed alias file, creating it with a header if needed
+pub fn append(alias: &AliasDefinition) -> Result<(), Box<dyn std::error::Error>> {
+    config_path::ensure_config_dir()?;
+    let path = aliases_file_path();
+    let is_new = !path.exists();
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+
+    let mut text = String::new();
+    if is_new {
+        text.push_str(&format!(
+            "# Aliases adopted from pawshell suggestions with /adopt-alias.\n\
+             # This file is appended to by pawshell; edit or remove entries freely.\n\
+             # Load it from your ~/.zshrc or ~/.bashrc:\n\
+             #   {}\n",
+            source_line()
+        ));
+    }
+    text.push_str(&format!("\n# Adopted {}\n{}\n", clock::now().format("%Y-%m-%d"), alias.definition));
+    file.write_all(text.as_bytes())
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(())
+}
+
+fn parse_alias(line: &str) -> Option<AliasDefinition> {
+    static ALIAS: OnceLock<Regex> = OnceLock::new();
+    let pattern = ALIAS.get_or_init(|| Regex::new(r"^alias\s+([A-Za-z0-9_.:-]+)=\S").expect("invalid alias pattern"));
+    let name = pattern.captures(line)?.get(1)?.as_str().to_string();
+    Some(AliasDefinition { name, definition: line.to_string() })
+}
+
+/// Returns the function name if the line starts a shell function definition
+fn function_name(line: &str) -> Option<String> {
+    static FUNCTION: OnceLock<Regex> = OnceLock::new();
+    let pattern = FUNCTION.get_or_init(|| {
+        Regex::new(r"^(?:function\s+([A-Za-z_][\w-]*)\s*(?:\(\))?|([A-Za-z_][\w-]*)\s*\(\))\s*(?:\{|$)")
+            .expect("invalid function pattern")
+    });
+    let captures = pattern.captures(line)?;
+    captures.get(1).or_else(|| captures.get(2)).map(|name| name.as_str().to_string())
+}
+
+/// Returns the index of the line that closes the function starting at `lines[0]`
+fn function_end(lines: &[&str]) -> Option<usize> {
+    let mut depth = 0i32;
+    let mut opened = false;
+    for (i, line) in lines.iter().enumerate().take(MAX_FUNCTION_LINES) {
+        for c in line.chars() {
+            match c {
+                '{' => {
+                    depth += 1;
+                    opened = true;
+                }
+                '}' => depth -= 1,
+                _ => {}
+            }
+        }
+        if opened && depth <= 0 {
+            return Some(i);
+        }
+    }
+    None
+}
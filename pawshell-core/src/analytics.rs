@@ -0,0 +1,141 @@
+//! Command usage analytics for PetCLI
+//!
+//! This module turns the full shell history into a frequency breakdown used by the
+//! `/analyze` command:
+//! - Top commands (by program name, ignoring `sudo` and `VAR=value` prefixes)
+//! - Common flag combinations (e.g. `tar -xzf`, `git commit -am`)
+//! - Long pipelines that get typed again and again
+//!
+//! The breakdown is shown to the user and also sent to the backend so it can
+//! suggest aliases and shell functions for the most repetitive patterns.
+
+use std::collections::HashMap;
+
+use crate::history::HistoryEntry;
+use crate::templates::Prompt;
+
+/// Pipelines shorter than this are cheap enough to type and not worth aliasing
+const LONG_PIPELINE_LEN: usize = 30;
+
+/// Programs whose first argument is a subcommand worth keeping in flag combos
+const SUBCOMMAND_PROGRAMS: &[&str] = &["git", "cargo", "docker", "kubectl", "npm", "yarn", "systemctl", "brew", "apt", "go"];
+
+const ALIAS_TEMPLATE: &str = "Here is a breakdown of my shell command usage:\n\n{{ usage }}\n\
+Based on this, suggest up to 5 concrete aliases or shell functions that would save me the most typing. \
+Show each as a ready-to-paste line and say in one sentence which pattern it replaces.";
+
+/// Frequency breakdown of a command history
+pub struct CommandStats {
+    pub total: usize,
+    pub top_commands: Vec<(String, usize)>,
+    pub flag_combos: Vec<(String, usize)>,
+    pub repeated_pipelines: Vec<(String, usize)>,
+}
+
+impl CommandStats {
+    /// Analyzes the given history, keeping the `top` most frequent entries per category
+    pub fn from_entries(entries: &[HistoryEntry], top: usize) -> Self {
+        let mut programs: HashMap<String, usize> = HashMap::new();
+        let mut combos: HashMap<String, usize> = HashMap::new();
+        let mut pipelines: HashMap<String, usize> = HashMap::new();
+
+        for entry in entries {
+            let command = entry.command.trim();
+            let tokens = significant_tokens(command);
+            let Some(program) = tokens.first() else {
+                continue;
+            };
+            *programs.entry(program.to_string()).or_default() += 1;
+
+            if let Some(combo) = flag_combo(&tokens) {
+                *combos.entry(combo).or_default() += 1;
+            }
+
+            if command.contains('|') && command.len() >= LONG_PIPELINE_LEN {
+                *pipelines.entry(command.to_string()).or_default() += 1;
+            }
+        }
+
+        pipelines.retain(|_, count| *count > 1);
+        combos.retain(|_, count| *count > 1);
+
+        Self {
+            total: entries.len(),
+            top_commands: most_frequent(programs, top),
+            flag_combos: most_frequent(combos, top),
+            repeated_pipelines: most_frequent(pipelines, top),
+        }
+    }
+
+    /// Renders the breakdown as a chat message
+    pub fn summary(&self) -> String {
+        let mut summary = format!("Command Analysis ({} commands):\n", self.total);
+        let sections = [
+            ("Top commands", &self.top_commands),
+            ("Common flag combos", &self.flag_combos),
+            ("Repeated long pipelines", &self.repeated_pipelines),
+        ];
+        for (title, items) in sections {
+            summary.push_str(&format!("\n{}:\n", title));
+            if items.is_empty() {
+                summary.push_str("  (none)\n");
+            }
+            for (item, count) in items {
+                summary.push_str(&format!("  {:>4}× {}\n", count, item));
+            }
+        }
+        summary
+    }
+
+    /// Builds the prompt asking the backend for alias suggestions
+    pub fn alias_prompt(&self) -> Prompt {
+        Prompt::new("analyze", ALIAS_TEMPLATE).var("usage", self.summary())
+    }
+}
+
+/// Splits a command into tokens, dropping `sudo` and leading `VAR=value` assignments
+fn significant_tokens(command: &str) -> Vec<&str> {
+    command
+        .split_whitespace()
+        .skip_while(|token| *token == "sudo" || is_assignment(token))
+        .collect()
+}
+
+fn is_assignment(token: &str) -> bool {
+    match token.split_once('=') {
+        Some((name, _)) => !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        None => false,
+    }
+}
+
+/// Returns `program [subcommand] flags...` for commands that use flags
+///
+/// Flags are sorted so `ls -la` and `ls -al` don't count separately when typed
+/// as separate flags, and only the first pipeline stage is considered.
+fn flag_combo(tokens: &[&str]) -> Option<String> {
+    let stage: Vec<&str> = tokens.iter().copied().take_while(|token| *token != "|").collect();
+    let program = *stage.first()?;
+    let mut key = vec![program];
+
+    if SUBCOMMAND_PROGRAMS.contains(&program) {
+        if let Some(subcommand) = stage.get(1).filter(|arg| !arg.starts_with('-')) {
+            key.push(subcommand);
+        }
+    }
+
+    let mut flags: Vec<&str> = stage.iter().copied().filter(|arg| arg.starts_with('-') && *arg != "-" && *arg != "--").collect();
+    if flags.is_empty() {
+        return None;
+    }
+    flags.sort_unstable();
+    flags.dedup();
+    key.extend(flags);
+    Some(key.join(" "))
+}
+
+fn most_frequent(counts: HashMap<String, usize>, top: usize) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts.truncate(top);
+    counts
+}
@@ -0,0 +1,217 @@
+//! Pet ASCII art: the built-in gallery, art files and mood poses
+//!
+//! The art shown in the pet pane comes from, in order of precedence: a pose
+//! file for the pet's current mood (`pet_ascii_happy_file`, ...), an art file
+//! (`pet_ascii_file`), a gallery pet picked with `/art` (`pet_art`), or the
+//! inline `pet_ascii` string. Art files are plain text, so they don't need
+//! TOML's multi-line escaping.
+
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::config_path;
+use crate::mood::MoodLevel;
+
+/// Widest art that fits the pet pane in a typical terminal
+pub const MAX_WIDTH: usize = 40;
+/// Tallest art the pet pane shows; anything below is cut off
+pub const MAX_HEIGHT: usize = 10;
+
+/// Built-in pets and poses selectable with `/art <name>`
+pub const GALLERY: &[(&str, &str)] = &[
+    ("cat", r#"
+  /\___/\
+ (  o o  )
+ (  =^=  )
+  (____)
+"#),
+    ("cat-happy", r#"
+  /\___/\
+ (  ^ ^  )
+ (  =w=  )
+  (____)
+"#),
+    ("cat-sleepy", r#"
+  /\___/\
+ (  - -  ) z
+ (  =^=  )  z
+  (____)
+"#),
+    ("owl", r#"
+  ,___,
+  (O,O)
+  /)_)
+   ""
+"#),
+    ("fox", r#"
+  /\   /\
+ //\\_//\\
+ \_     _/
+   (o o)
+   (   )
+    \~/
+"#),
+    ("dog", r#"
+  /^___^\
+  ( o o )
+  \  Y  /
+   `---'
+"#),
+    ("bunny", r#"
+  /\ /\
+ ( . . )
+ c(")(")
+"#),
+    ("penguin", r#"
+   __
+  /  \
+ |o o|
+ |>-<|
+ |__|
+"#),
+    ("crab", r#"
+  (\/)  (\/)
+    \ o o /
+   --(___)--
+    /     \
+"#),
+];
+
+/// Looks up a gallery pet by name
+pub fn gallery(name: &str) -> Option<&'static str> {
+    GALLERY.iter().find(|(gallery_name, _)| *gallery_name == name).map(|(_, art)| *art)
+}
+
+/// The pet's art for each mood, resolved from the config
+pub struct PetArt {
+    base: String,
+    happy: Option<String>,
+    neutral: Option<String>,
+    sad: Option<String>,
+}
+
+impl PetArt {
+    /// Resolves the configured art, along with anything wrong with it
+    ///
+    /// Files that can't be read and unknown gallery names fall back to the
+    /// next source, so the pet always has something to show.
+    pub fn load(config: &Config) -> (Self, Vec<String>) {
+        let mut problems = Vec::new();
+        let mut base = None;
+        if let Some(path) = &config.pet_ascii_file {
+            base = read_art("pet_ascii_file", path, &mut problems);
+        }
+        if let Some(name) = config.pet_art.as_deref().filter(|_| base.is_none()) {
+            match gallery(name) {
+                Some(art) => base = Some(tidy(art)),
+                None => problems.push(format!(
+                    "pet_art {:?} isn't in the gallery; pick one of: {}",
+                    name,
+                    gallery_names().join(", ")
+                )),
+            }
+        }
+        let base = base.unwrap_or_else(|| {
+            let art = tidy(&config.pet_ascii);
+            check_size("pet_ascii", &art, &mut problems);
+            art
+        });
+
+        let mut pose = |key: &str, path: &Option<String>| path.as_ref().and_then(|path| read_art(key, path, &mut problems));
+        let art = Self {
+            happy: pose("pet_ascii_happy_file", &config.pet_ascii_happy_file),
+            neutral: pose("pet_ascii_neutral_file", &config.pet_ascii_neutral_file),
+            sad: pose("pet_ascii_sad_file", &config.pet_ascii_sad_file),
+            base,
+        };
+        (art, problems)
+    }
+
+    /// The art to show at the given mood
+    pub fn for_mood(&self, mood: f32) -> &str {
+        self.pose(MoodLevel::of(mood)).unwrap_or(&self.base)
+    }
+
+    /// The art configured for a mood level, if any
+    pub fn pose(&self, level: MoodLevel) -> Option<&str> {
+        match level {
+            MoodLevel::Happy => self.happy.as_deref(),
+            MoodLevel::Content => self.neutral.as_deref(),
+            MoodLevel::Sad => self.sad.as_deref(),
+        }
+    }
+
+    /// The art used when no pose matches the mood
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+}
+
+/// Everything wrong with the configured art, for config validation
+pub fn validate(config: &Config) -> Vec<String> {
+    PetArt::load(config).1
+}
+
+/// Names of the gallery pets, in gallery order
+pub fn gallery_names() -> Vec<&'static str> {
+    GALLERY.iter().map(|(name, _)| *name).collect()
+}
+
+/// Number of lines the art takes up
+pub fn height(art: &str) -> usize {
+    art.lines().count()
+}
+
+/// Reads an art file, noting a problem if it can't be read or doesn't fit
+fn read_art(key: &str, path: &str, problems: &mut Vec<String>) -> Option<String> {
+    let resolved = resolve_path(path);
+    match std::fs::read_to_string(&resolved) {
+        Ok(contents) => {
+            let art = tidy(&contents);
+            check_size(key, &art, problems);
+            Some(art)
+        }
+        Err(e) => {
+            problems.push(format!("{} {} can't be read: {}", key, resolved.display(), e));
+            None
+        }
+    }
+}
+
+fn check_size(key: &str, art: &str, problems: &mut Vec<String>) {
+    let width = art.lines().map(|line| line.chars().count()).max().unwrap_or(0);
+    if width > MAX_WIDTH {
+        problems.push(format!("{} art is {} columns wide; the pet pane fits {}", key, width, MAX_WIDTH));
+    }
+    if height(art) > MAX_HEIGHT {
+        problems.push(format!(
+            "{} art is {} lines tall; the pet pane shows only the first {}",
+            key,
+            height(art),
+            MAX_HEIGHT
+        ));
+    }
+}
+
+/// Expands `~` and makes relative paths relative to the config directory
+fn resolve_path(path: &str) -> PathBuf {
+    // Joining an absolute path, like an expanded `~`, replaces the directory
+    config_path::get_config_dir().join(config_path::expand_home(path))
+}
+
+/// Drops blank lines around the art and pads every line to the same width
+///
+/// The pane centers each line on its own, so without the padding lines of
+/// different lengths would shift against each other.
+fn tidy(art: &str) -> String {
+    let lines: Vec<&str> = art.lines().map(str::trim_end).collect();
+    let first = lines.iter().position(|line| !line.is_empty()).unwrap_or(lines.len());
+    let last = lines.iter().rposition(|line| !line.is_empty()).map_or(first, |last| last + 1);
+    let lines = &lines[first..last];
+    let width = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+    lines
+        .iter()
+        .map(|line| format!("{:width$}", line, width = width))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,98 @@
+//! Atuin shell history integration for PetCLI
+//!
+//! Atuin (https://atuin.sh) records every command in a SQLite database along with
+//! the directory it ran in, its exit code and how long it took. This module reads
+//! that database so the pet gets much richer context than plain history files,
+//! including whether recent commands failed.
+//!
+//! The database is only ever opened read-only; Atuin owns the file.
+
+use chrono::DateTime;
+use rusqlite::{Connection, OpenFlags};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::history::HistoryEntry;
+
+/// Returns the default location of Atuin's history database
+///
+/// Honors `ATUIN_DB_PATH` when set, otherwise falls back to
+/// `<data dir>/atuin/history.db` (e.g. `~/.local/share/atuin/history.db`).
+pub fn default_db_path() -> Option<PathBuf> {
+    if let Ok(path) = std::env::var("ATUIN_DB_PATH") {
+        return Some(PathBuf::from(path));
+    }
+    dirs::data_dir().map(|dir| dir.join("atuin").join("history.db"))
+}
+
+/// Loads the most recent `limit` commands from an Atuin database, oldest first
+pub fn load_recent(db_path: &Path, limit: usize) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>> {
+    let conn = Connection::open_with_flags(
+        db_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .map_err(|e| format!("Failed to open Atuin database: {}", e))?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT command, cwd, exit, duration, timestamp FROM history
+             WHERE deleted_at IS NULL
+             ORDER BY timestamp DESC
+             LIMIT ?1",
+        )
+        .map_err(|e| format!("Failed to query Atuin database: {}", e))?;
+
+    let rows = stmt.query_map([limit as i64], |row| {
+        let duration_ns: i64 = row.get(3)?;
+        let timestamp_ns: i64 = row.get(4)?;
+        Ok(HistoryEntry {
+            command: row.get(0)?,
+            timestamp: Some(DateTime::from_timestamp_nanos(timestamp_ns)),
+            cwd: Some(row.get(1)?),
+            exit_code: Some(row.get(2)?),
+            // Atuin stores -1 when the duration is unknown
+            duration: u64::try_from(duration_ns).ok().map(Duration::from_nanos),
+        })
+    })?;
+
+    let mut entries = rows.collect::<Result<Vec<_>, _>>()?;
+    entries.reverse();
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_commands_come_with_their_context() {
+        let path = std::env::temp_dir().join(format!("pawshell-atuin-test-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let conn = Connection::open(&path).unwrap();
+        // The columns of Atuin's history table
+        conn.execute_batch(
+            "CREATE TABLE history (
+                 id TEXT PRIMARY KEY, timestamp INTEGER NOT NULL, duration INTEGER NOT NULL,
+                 exit INTEGER NOT NULL, command TEXT NOT NULL, cwd TEXT NOT NULL, session TEXT NOT NULL,
+                 hostname TEXT NOT NULL, deleted_at INTEGER
+             );
+             INSERT INTO history VALUES ('1', 1709283600000000000, 2500000000, 0, 'cargo build', '/src/pet', 's', 'h', NULL);
+             INSERT INTO history VALUES ('2', 1709283660000000000, -1, 101, 'cargo test', '/src/pet', 's', 'h', NULL);
+             INSERT INTO history VALUES ('3', 1709283720000000000, 1000000, 0, 'rm secrets.txt', '/tmp', 's', 'h', 1709283780000000000);",
+        )
+        .unwrap();
+        drop(conn);
+
+        let entries = load_recent(&path, 10).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        // Deleted commands are left out, and the rest come oldest first
+        let commands: Vec<&str> = entries.iter().map(|entry| entry.command.as_str()).collect();
+        assert_eq!(commands, ["cargo build", "cargo test"]);
+        assert_eq!(entries[0].cwd.as_deref(), Some("/src/pet"));
+        assert_eq!(entries[0].exit_code, Some(0));
+        assert_eq!(entries[0].duration, Some(Duration::from_millis(2500)));
+        assert_eq!(entries[0].timestamp, DateTime::from_timestamp(1_709_283_600, 0));
+        assert_eq!(entries[1].exit_code, Some(101));
+        assert_eq!(entries[1].duration, None);
+    }
+}
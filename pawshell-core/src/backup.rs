@@ -0,0 +1,155 @@
+//! State backups for PetCLI
+//!
+//! A crash in the middle of a save can leave the state file truncated, which
+//! resets the pet. Before saving, the current state file is copied to a
+//! timestamped file in the `backups` directory of the data directory (at most once
+//! per `backup_interval_hours`), keeping the newest `backup_count` copies.
+//!
+//! `pawshell restore --list` shows the available backups and
+//! `pawshell restore --from <backup>` puts one back in place.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::clock;
+use crate::config::Config;
+use crate::config_path;
+use crate::error::{BackupError, StorageError};
+use crate::pet::PetState;
+use crate::storage::{self, StateStore};
+
+/// Backups are named `state-<timestamp>.<ext>` so they sort chronologically
+const BACKUP_PREFIX: &str = "state-";
+
+pub fn backup_dir() -> PathBuf {
+    config_path::get_data_dir().join("backups")
+}
+
+/// Lists backups, newest first
+pub fn list() -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(backup_dir()) else {
+        return Vec::new();
+    };
+    let mut backups: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with(BACKUP_PREFIX))
+        })
+        .collect();
+    backups.sort();
+    backups.reverse();
+    backups
+}
+
+/// Whether the newest backup is older than `interval` (or there is none)
+pub fn is_due(interval: Duration) -> bool {
+    let newest = list()
+        .first()
+        .and_then(|path| std::fs::metadata(path).and_then(|meta| meta.modified()).ok());
+    match newest {
+        Some(modified) => SystemTime::now()
+            .duration_since(modified)
+            .map_or(true, |age| age >= interval),
+        None => true,
+    }
+}
+
+/// Saves `state`, backing up what's on disk first when a backup is due
+///
+/// A failed backup doesn't stop the save; it's logged and handed back so the
+/// caller can mention it.
+pub fn save_with_backup(store: &mut dyn StateStore, state: &PetState, config: &Config) -> Result<Option<BackupError>, StorageError> {
+    let interval = Duration::from_secs(config.backup_interval_hours * 3600);
+    let mut failed_backup = None;
+    if config.backup_count > 0 && is_due(interval) {
+        if let Err(e) = create(store, config.backup_count) {
+            tracing::error!("backup failed: {}", e);
+            failed_backup = Some(e);
+        }
+    }
+    let result = store.save(state);
+    match &result {
+        Ok(()) => tracing::debug!(mood = %format_args!("{:.2}", state.mood), chat_messages = state.chat_history.len(), "state saved"),
+        Err(e) => tracing::error!("saving the state failed: {}", e),
+    }
+    result.map(|()| failed_backup)
+}
+
+/// Backs up the store's file and deletes all but the newest `keep` backups
+///
+/// Returns None if there was nothing on disk to back up yet.
+pub fn create(store: &dyn StateStore, keep: usize) -> Result<Option<PathBuf>, BackupError> {
+    let dir = backup_dir();
+    std::fs::create_dir_all(&dir).map_err(BackupError::file(&dir))?;
+    let dest = backup_path(store.backup_extension(), "");
+    if !store.backup(&dest)? {
+        return Ok(None);
+    }
+    for old in list().into_iter().skip(keep) {
+        std::fs::remove_file(&old).map_err(BackupError::file(&old))?;
+    }
+    tracing::info!(path = %dest.display(), "state backed up");
+    Ok(Some(dest))
+}
+
+/// Replaces the state with a backup, given its file name or path
+///
+/// The current state file is itself backed up first, and the backup is checked
+/// to be readable before anything is overwritten. Returns the restored file.
+pub fn restore(backup: &str) -> Result<PathBuf, BackupError> {
+    let source = resolve(backup)?;
+    let target = match source.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => config_path::state_file_path(),
+        Some("db") => storage::database_path(),
+        _ => return Err(BackupError::NotABackup(source)),
+    };
+    validate(&source)?;
+
+    if target.exists() {
+        let dir = backup_dir();
+        std::fs::create_dir_all(&dir).map_err(BackupError::file(&dir))?;
+        let extension = target.extension().and_then(|ext| ext.to_str()).unwrap_or("bak");
+        let pre_restore = backup_path(extension, "-pre-restore");
+        std::fs::copy(&target, &pre_restore).map_err(BackupError::file(&pre_restore))?;
+    }
+    std::fs::read(&source)
+        .and_then(|contents| storage::write_atomic(&target, &contents))
+        .map_err(|source_error| BackupError::Restore { from: source.clone(), to: target.clone(), source: source_error })?;
+    Ok(target)
+}
+
+fn backup_path(extension: &str, suffix: &str) -> PathBuf {
+    let timestamp = clock::local_now().format("%Y%m%d-%H%M%S");
+    backup_dir().join(format!("{}{}{}.{}", BACKUP_PREFIX, timestamp, suffix, extension))
+}
+
+/// Finds a backup by file name in the backup directory, or by path
+fn resolve(backup: &str) -> Result<PathBuf, BackupError> {
+    let in_backup_dir = backup_dir().join(backup);
+    if in_backup_dir.is_file() {
+        return Ok(in_backup_dir);
+    }
+    let path = PathBuf::from(backup);
+    if path.is_file() {
+        return Ok(path);
+    }
+    Err(BackupError::NotFound(backup.to_string()))
+}
+
+/// Checks that a backup actually contains a pet
+fn validate(path: &Path) -> Result<(), BackupError> {
+    let invalid = |e: &dyn std::fmt::Display| BackupError::Damaged { path: path.to_path_buf(), message: e.to_string() };
+    if path.extension().is_some_and(|ext| ext == "db") {
+        let conn = rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|e| invalid(&e))?;
+        let state: String = conn
+            .query_row("SELECT value FROM pet WHERE key = 'state'", [], |row| row.get(0))
+            .map_err(|e| invalid(&e))?;
+        serde_json::from_str::<serde_json::Value>(&state).map_err(|e| invalid(&e))?;
+    } else {
+        let contents = std::fs::read_to_string(path).map_err(|e| invalid(&e))?;
+        toml::from_str::<PetState>(&contents).map_err(|e| invalid(&e))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,181 @@
+//! Laptop battery level and charging state
+//!
+//! Read from `/sys/class/power_supply` on Linux, `pmset` on macOS and
+//! `Win32_Battery` through PowerShell on Windows. Machines without a battery
+//! simply have none, and everything battery-related stays out of the way.
+//!
+//! With `battery_awareness` on, the level shows in the pet's title and in
+//! prompts, and below `LOW_PERCENT` the pet gets sleepy and asks to be
+//! plugged in.
+
+/// Level at which the pet gets sleepy and asks for the charger
+pub const LOW_PERCENT: u8 = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Charge {
+    Charging,
+    Discharging,
+    /// Plugged in and not charging, usually because it's full
+    Full,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Battery {
+    pub percent: u8,
+    pub charge: Charge,
+}
+
+impl Battery {
+    /// Low and running on battery power
+    pub fn is_low(&self) -> bool {
+        self.percent < LOW_PERCENT && self.charge == Charge::Discharging
+    }
+
+    /// Level and state for the title, like `🔋42%` or `🔌80%`
+    pub fn status(&self) -> String {
+        let icon = match self.charge {
+            Charge::Discharging => "🔋",
+            Charge::Charging | Charge::Full => "🔌",
+        };
+        format!("{}{}%", icon, self.percent)
+    }
+
+    /// A sentence of prompt context
+    pub fn describe(&self) -> String {
+        let state = match self.charge {
+            Charge::Charging => "charging",
+            Charge::Discharging => "on battery power",
+            Charge::Full => "plugged in",
+        };
+        format!("Laptop battery: {}%, {}.", self.percent, state)
+    }
+}
+
+/// The idle art with its eyes half closed and a `z Z` above it: any lone
+/// `o`, `O`, `0` or `^` becomes `-`
+pub fn sleepy(art: &str) -> String {
+    let lines: Vec<String> = art
+        .lines()
+        .map(|line| {
+            let chars: Vec<char> = line.chars().collect();
+            let alone = |i: usize| {
+                let before = i.checked_sub(1).map(|j| chars[j]);
+                let after = chars.get(i + 1).copied();
+                !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+            };
+            chars
+                .iter()
+                .enumerate()
+                .map(|(i, &c)| if matches!(c, 'o' | 'O' | '0' | '^') && alone(i) { '-' } else { c })
+                .collect()
+        })
+        .collect();
+    format!("      z Z\n{}", lines.join("\n"))
+}
+
+/// The machine's battery, if it has one
+#[cfg(target_os = "linux")]
+pub fn read() -> Option<Battery> {
+    let supplies = std::fs::read_dir("/sys/class/power_supply").ok()?;
+    supplies.flatten().find_map(|supply| {
+        let path = supply.path();
+        let read = |name: &str| std::fs::read_to_string(path.join(name)).ok().map(|value| value.trim().to_string());
+        if read("type")? != "Battery" || read("present").is_some_and(|present| present == "0") {
+            return None;
+        }
+        parse_sysfs(&read("capacity")?, &read("status").unwrap_or_default())
+    })
+}
+
+/// The machine's battery, if it has one
+#[cfg(target_os = "macos")]
+pub fn read() -> Option<Battery> {
+    parse_pmset(&run("pmset", &["-g", "batt"])?)
+}
+
+/// The machine's battery, if it has one
+#[cfg(windows)]
+pub fn read() -> Option<Battery> {
+    parse_win32(&run(
+        "powershell",
+        &[
+            "-NoProfile",
+            "-Command",
+            "Get-CimInstance Win32_Battery | ForEach-Object { \"$($_.EstimatedChargeRemaining) $($_.BatteryStatus)\" }",
+        ],
+    )?)
+}
+
+/// The machine's battery, if it has one
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn read() -> Option<Battery> {
+    None
+}
+
+#[cfg(any(target_os = "macos", windows))]
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program).args(args).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Parses a battery's `capacity` and `status` files
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_sysfs(capacity: &str, status: &str) -> Option<Battery> {
+    let percent = capacity.parse::<u8>().ok()?.min(100);
+    let charge = match status {
+        "Charging" => Charge::Charging,
+        "Full" | "Not charging" => Charge::Full,
+        _ => Charge::Discharging,
+    };
+    Some(Battery { percent, charge })
+}
+
+/// Parses `pmset -g batt`, e.g. `-InternalBattery-0 (id=123)<TAB>85%; charging; 1:02 remaining`
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_pmset(output: &str) -> Option<Battery> {
+    let line = output.lines().find(|line| line.contains("InternalBattery"))?;
+    let mut fields = line.split('\t').nth(1)?.split(';').map(str::trim);
+    let percent = fields.next()?.trim_end_matches('%').parse::<u8>().ok()?.min(100);
+    let charge = match fields.next()? {
+        "charging" => Charge::Charging,
+        "discharging" => Charge::Discharging,
+        _ => Charge::Full,
+    };
+    Some(Battery { percent, charge })
+}
+
+/// Parses `<EstimatedChargeRemaining> <BatteryStatus>` as printed for `Win32_Battery`
+#[cfg_attr(not(windows), allow(dead_code))]
+fn parse_win32(output: &str) -> Option<Battery> {
+    let (percent, status) = output.lines().next()?.trim().split_once(' ')?;
+    let percent = percent.parse::<u8>().ok()?.min(100);
+    // 1 is discharging, 3 is fully charged, 6 to 9 are charging
+    let charge = match status.trim() {
+        "1" | "4" | "5" => Charge::Discharging,
+        "3" => Charge::Full,
+        _ => Charge::Charging,
+    };
+    Some(Battery { percent, charge })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn platform_outputs_are_parsed() {
+        assert_eq!(parse_sysfs("12", "Discharging"), Some(Battery { percent: 12, charge: Charge::Discharging }));
+        assert_eq!(parse_sysfs("100", "Not charging"), Some(Battery { percent: 100, charge: Charge::Full }));
+        let pmset = "Now drawing from 'AC Power'\n -InternalBattery-0 (id=4653155)\t85%; charging; 1:02 remaining present: true\n";
+        assert_eq!(parse_pmset(pmset), Some(Battery { percent: 85, charge: Charge::Charging }));
+        assert_eq!(parse_pmset("Now drawing from 'AC Power'\n"), None);
+        assert_eq!(parse_win32("9 1\r\n"), Some(Battery { percent: 9, charge: Charge::Discharging }));
+        assert!(Battery { percent: 9, charge: Charge::Discharging }.is_low());
+        assert!(!Battery { percent: 9, charge: Charge::Charging }.is_low());
+    }
+
+    #[test]
+    fn sleepy_art_closes_only_the_eyes() {
+        assert_eq!(sleepy(" /\\_/\\\n( o.O )\n Hello"), "      z Z\n /\\_/\\\n( -.- )\n Hello");
+    }
+}
@@ -0,0 +1,131 @@
+//! Command bookmarks for PetCLI
+//!
+//! Favorite commands are saved in the pet's state with an optional description
+//! (`/bookmark <cmd> # what it does`), or taken from the pet's last reply with a
+//! bare `/bookmark`. `/bookmarks <query>` fuzzy-searches them, and bookmarks that
+//! share words with a question are added to its prompt so the pet can point back
+//! to commands the user already likes.
+//!
+//! Consider a dedicated overlay with live filtering once the list gets long.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::clock;
+
+/// At most this many bookmarks are added to a single prompt
+const MAX_PROMPT_BOOKMARKS: usize = 5;
+
+/// Words too common to make a bookmark relevant
+const STOP_WORDS: &[&str] = &[
+    "the", "and", "for", "how", "what", "why", "can", "you", "this", "that", "with", "from", "into", "all", "are",
+    "does", "use", "using", "get", "make", "want", "need", "there", "about", "which", "when", "where",
+];
+
+/// A saved command
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Bookmark {
+    pub command: String,
+    pub description: Option<String>,
+    pub created: DateTime<Utc>,
+}
+
+impl Bookmark {
+    /// Parses `/bookmark` arguments of the form `<cmd> [# description]`
+    pub fn parse(args: &str) -> Option<Self> {
+        let (command, description) = match args.rsplit_once(" # ") {
+            Some((command, description)) => (command, Some(description.trim().to_string())),
+            None => (args, None),
+        };
+        let command = command.trim();
+        if command.is_empty() {
+            return None;
+        }
+        Some(Self {
+            command: command.to_string(),
+            description: description.filter(|d| !d.is_empty()),
+            created: clock::now(),
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        match &self.description {
+            Some(description) => format!("{}  # {}", self.command, description),
+            None => self.command.clone(),
+        }
+    }
+}
+
+/// Adds a bookmark, replacing an existing one for the same command
+///
+/// Returns true if the command was already bookmarked.
+pub fn add(bookmarks: &mut Vec<Bookmark>, bookmark: Bookmark) -> bool {
+    match bookmarks.iter_mut().find(|b| b.command == bookmark.command) {
+        Some(existing) => {
+            if bookmark.description.is_some() {
+                existing.description = bookmark.description;
+            }
+            true
+        }
+        None => {
+            bookmarks.push(bookmark);
+            false
+        }
+    }
+}
+
+/// Returns bookmarks matching `query`, best matches first
+///
+/// A bookmark matches if the query's characters appear in order in its command or
+/// description; consecutive runs and matches at word starts rank higher.
+pub fn search<'a>(bookmarks: &'a [Bookmark], query: &str) -> Vec<&'a Bookmark> {
+    let mut scored: Vec<(i32, &Bookmark)> = bookmarks
+        .iter()
+        .filter_map(|bookmark| fuzzy_score(query, &bookmark.describe()).map(|score| (score, bookmark)))
+        .collect();
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored.into_iter().map(|(_, bookmark)| bookmark).collect()
+}
+
+/// Returns the bookmarks worth mentioning in a prompt about `message`
+///
+/// A bookmark is relevant if it shares a meaningful word of three or more letters
+/// with the message, e.g. "how do I find big files" and `fd -S +100m # find big files`.
+pub fn relevant<'a>(bookmarks: &'a [Bookmark], message: &str) -> Vec<&'a Bookmark> {
+    let message_words = words(message);
+    bookmarks
+        .iter()
+        .filter(|bookmark| words(&bookmark.describe()).iter().any(|word| message_words.contains(word)))
+        .take(MAX_PROMPT_BOOKMARKS)
+        .collect()
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.len() >= 3)
+        .map(str::to_lowercase)
+        .filter(|word| !STOP_WORDS.contains(&word.as_str()))
+        .collect()
+}
+
+/// Scores a case-insensitive subsequence match, or None if `query` doesn't match
+fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous_match: Option<usize> = None;
+    for query_char in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let index = position + text[position..].iter().position(|&c| c == query_char)?;
+        score += 1;
+        if previous_match == Some(index.wrapping_sub(1)) {
+            score += 5;
+        }
+        if index == 0 || !text[index - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous_match = Some(index);
+        position = index + 1;
+    }
+    // Prefer shorter entries among equally good matches
+    Some(score * 100 - text.len() as i32)
+}
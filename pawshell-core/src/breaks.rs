@@ -0,0 +1,137 @@
+//! Screen time and break nudges
+//!
+//! Time at the keyboard counts from the start of the session or the last
+//! break. Key presses in the pet and commands reported by the shell hook are
+//! activity, and being away from both for `BREAK_MINUTES` is a break, which
+//! is added to the pet's `break_log`, one entry per day, for `/stats`. Once
+//! `break_after_minutes` go by without one, the pet nudges you to stretch,
+//! and again every `REPEAT_MINUTES` until you take it; `/break snooze` holds
+//! it off for a while.
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Time away that counts as a break
+pub const BREAK_MINUTES: i64 = 5;
+/// Time between nudges while a break is overdue
+const REPEAT_MINUTES: i64 = 15;
+/// Longer gaps (a night, a weekend) still end the stretch but aren't logged
+const MAX_BREAK_HOURS: i64 = 3;
+
+/// Continuous activity since the last break
+pub struct BreakTimer {
+    /// When the current stretch started
+    since: DateTime<Utc>,
+    last_active: DateTime<Utc>,
+    /// No nudges before this, after a nudge or a snooze
+    quiet_until: Option<DateTime<Utc>>,
+}
+
+impl BreakTimer {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self { since: now, last_active: now, quiet_until: None }
+    }
+
+    /// Notes activity, returning how long the break was if this ends one
+    pub fn activity(&mut self, now: DateTime<Utc>) -> Option<Duration> {
+        let away = now - self.last_active;
+        self.last_active = now;
+        if away < Duration::minutes(BREAK_MINUTES) {
+            return None;
+        }
+        self.since = now;
+        self.quiet_until = None;
+        Some(away)
+    }
+
+    /// Time at the keyboard without a break
+    pub fn stretch(&self, now: DateTime<Utc>) -> Duration {
+        now - self.since
+    }
+
+    /// Whether to nudge now; a nudge holds off the next one for a while
+    ///
+    /// Nobody is nudged while they're away already.
+    pub fn nudge_due(&mut self, now: DateTime<Utc>, after: Duration) -> bool {
+        let away = now - self.last_active >= Duration::minutes(BREAK_MINUTES);
+        if away || self.stretch(now) < after || self.quiet_until.is_some_and(|until| now < until) {
+            return false;
+        }
+        self.quiet_until = Some(now + Duration::minutes(REPEAT_MINUTES));
+        true
+    }
+
+    pub fn snooze(&mut self, now: DateTime<Utc>, length: Duration) {
+        self.quiet_until = Some(now + length);
+    }
+}
+
+/// Breaks taken on one day
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BreakDay {
+    pub date: NaiveDate,
+    pub breaks: u32,
+    pub minutes: u32,
+}
+
+/// Adds a break to the day's entry, unless it was too long to be one;
+/// returns whether it was added
+pub fn record(log: &mut Vec<BreakDay>, date: NaiveDate, length: Duration) -> bool {
+    if length > Duration::hours(MAX_BREAK_HOURS) {
+        return false;
+    }
+    let minutes = length.num_minutes() as u32;
+    match log.iter_mut().find(|day| day.date == date) {
+        Some(day) => {
+            day.breaks += 1;
+            day.minutes += minutes;
+        }
+        None => log.push(BreakDay { date, breaks: 1, minutes }),
+    }
+    true
+}
+
+/// Today's breaks and the last seven days', for `/stats`
+pub fn summary(log: &[BreakDay], today: NaiveDate) -> String {
+    let week: Vec<&BreakDay> = log.iter().filter(|day| day.date <= today && day.date > today - Duration::days(7)).collect();
+    let (today_breaks, today_minutes) = week.iter().find(|day| day.date == today).map_or((0, 0), |day| (day.breaks, day.minutes));
+    let week_breaks: u32 = week.iter().map(|day| day.breaks).sum();
+    let week_minutes: u32 = week.iter().map(|day| day.minutes).sum();
+    format!(
+        "Breaks Today: {} ({} min)\nBreaks This Week: {} ({} min)",
+        today_breaks, today_minutes, week_breaks, week_minutes
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::test_time;
+
+    #[test]
+    fn nudges_come_after_a_long_stretch_until_a_break() {
+        let start = test_time(0);
+        let after = Duration::minutes(50);
+        let mut timer = BreakTimer::new(start);
+        let minute = |n| start + Duration::minutes(n);
+        for n in 1..=50 {
+            assert_eq!(timer.activity(minute(n)), None);
+        }
+        assert!(timer.nudge_due(minute(50), after));
+        assert!(!timer.nudge_due(minute(51), after));
+        timer.snooze(minute(51), Duration::minutes(30));
+        assert_eq!(timer.activity(minute(70)), Some(Duration::minutes(20)));
+        assert_eq!(timer.stretch(minute(71)), Duration::minutes(1));
+        assert!(!timer.nudge_due(minute(71), after));
+    }
+
+    #[test]
+    fn breaks_are_summed_per_day_and_week() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 8).unwrap();
+        let mut log = Vec::new();
+        assert!(record(&mut log, today, Duration::minutes(10)));
+        assert!(record(&mut log, today - Duration::days(2), Duration::minutes(6)));
+        assert!(!record(&mut log, today, Duration::hours(14)));
+        assert_eq!(summary(&log, today), "Breaks Today: 1 (10 min)\nBreaks This Week: 2 (16 min)");
+    }
+}
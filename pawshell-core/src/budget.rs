@@ -0,0 +1,235 @@
+//! Spending budgets for paid backends
+//!
+//! Each reply from a paid backend is priced from its token counts, with
+//! built-in prices for OpenAI's models that `[model_prices]` can add to or
+//! correct, and added to the day's entry in the pet's `spend_log`. With
+//! `budget_daily_usd` or `budget_monthly_usd` set, the pet warns once a
+//! budget is `WARN_AT` spent and switches to the local Ollama model once it's
+//! used up, until the day or month is over. Local models cost nothing.
+
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::config::LLMProvider;
+use crate::llm::Generation;
+
+/// Share of a budget spent that earns a warning
+pub const WARN_AT: f64 = 0.8;
+/// Days of spending kept, enough for this month and the last
+const KEEP_DAYS: i64 = 62;
+
+/// Dollars per million tokens
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Price {
+    pub input: f64,
+    pub output: f64,
+}
+
+/// OpenAI's list prices; a model matches the longest name it starts with
+const PRICES: &[(&str, Price)] = &[
+    ("gpt-3.5-turbo", Price { input: 0.5, output: 1.5 }),
+    ("gpt-4", Price { input: 30.0, output: 60.0 }),
+    ("gpt-4-turbo", Price { input: 10.0, output: 30.0 }),
+    ("gpt-4o", Price { input: 2.5, output: 10.0 }),
+    ("gpt-4o-mini", Price { input: 0.15, output: 0.6 }),
+    ("gpt-4.1", Price { input: 2.0, output: 8.0 }),
+    ("gpt-4.1-mini", Price { input: 0.4, output: 1.6 }),
+    ("gpt-4.1-nano", Price { input: 0.1, output: 0.4 }),
+    ("o1", Price { input: 15.0, output: 60.0 }),
+    ("o1-mini", Price { input: 1.1, output: 4.4 }),
+    ("o3-mini", Price { input: 1.1, output: 4.4 }),
+];
+
+/// What `model` costs, by the longest matching name in the built-in prices
+/// and `overrides`, which win a tie
+pub fn price(model: &str, overrides: &BTreeMap<String, Price>) -> Option<Price> {
+    PRICES
+        .iter()
+        .map(|(name, price)| (*name, *price))
+        .chain(overrides.iter().map(|(name, price)| (name.as_str(), *price)))
+        .filter(|(name, _)| model.starts_with(name))
+        .max_by_key(|(name, _)| name.len())
+        .map(|(_, price)| price)
+}
+
+/// Dollars a reply cost
+pub fn cost(price: Price, prompt_tokens: Option<u32>, completion_tokens: Option<u32>) -> f64 {
+    (prompt_tokens.unwrap_or(0) as f64 * price.input + completion_tokens.unwrap_or(0) as f64 * price.output) / 1_000_000.0
+}
+
+/// What was spent on one day
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SpendDay {
+    pub date: NaiveDate,
+    pub usd: f64,
+}
+
+/// Adds spending to the day's entry and forgets old days
+pub fn record(log: &mut Vec<SpendDay>, date: NaiveDate, usd: f64) {
+    match log.iter_mut().find(|day| day.date == date) {
+        Some(day) => day.usd += usd,
+        None => log.push(SpendDay { date, usd }),
+    }
+    log.retain(|day| date - day.date < Duration::days(KEEP_DAYS));
+}
+
+/// Records what a reply from `model` cost on `date`; false if its price isn't known
+pub fn charge(log: &mut Vec<SpendDay>, overrides: &BTreeMap<String, Price>, model: &str, generation: &Generation, date: NaiveDate) -> bool {
+    let Some(price) = price(model, overrides) else {
+        return false;
+    };
+    record(log, date, cost(price, generation.prompt_tokens, generation.completion_tokens));
+    true
+}
+
+/// A budget's period
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Day,
+    Month,
+}
+
+impl Period {
+    /// The day the period that `today` is in started
+    pub fn start(self, today: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => today,
+            Period::Month => today.with_day(1).unwrap_or(today),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Period::Day => "today",
+            Period::Month => "this month",
+        }
+    }
+
+    /// Spending since the period started
+    pub fn spent(self, log: &[SpendDay], today: NaiveDate) -> f64 {
+        let start = self.start(today);
+        log.iter().filter(|day| day.date >= start && day.date <= today).map(|day| day.usd).sum()
+    }
+}
+
+/// How spending stands against the budgets
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Standing {
+    Within,
+    /// At least `WARN_AT` of a budget is spent
+    Low { period: Period, spent: f64, budget: f64 },
+    /// A budget is used up
+    Exceeded { period: Period, spent: f64, budget: f64 },
+}
+
+/// The daily and monthly budgets, in dollars
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Budget {
+    pub daily: Option<f64>,
+    pub monthly: Option<f64>,
+}
+
+impl Budget {
+    /// Where spending stands; a used-up budget outweighs a low one, and the
+    /// month outweighs the day
+    pub fn standing(&self, log: &[SpendDay], today: NaiveDate) -> Standing {
+        let budgets = [(Period::Month, self.monthly), (Period::Day, self.daily)];
+        let standings: Vec<(Period, f64, f64)> = budgets
+            .iter()
+            .filter_map(|(period, budget)| budget.map(|budget| (*period, period.spent(log, today), budget)))
+            .collect();
+        if let Some(&(period, spent, budget)) = standings.iter().find(|(_, spent, budget)| spent >= budget) {
+            return Standing::Exceeded { period, spent, budget };
+        }
+        if let Some(&(period, spent, budget)) = standings.iter().find(|(_, spent, budget)| *spent >= budget * WARN_AT) {
+            return Standing::Low { period, spent, budget };
+        }
+        Standing::Within
+    }
+
+    /// The `/budget` overview
+    pub fn summary(&self, log: &[SpendDay], today: NaiveDate) -> String {
+        let line = |period: Period, budget: Option<f64>| {
+            let spent = period.spent(log, today);
+            match budget {
+                Some(budget) => format!("{}: ${:.2} of ${:.2} ({:.0}%)", period.name(), spent, budget, spent / budget * 100.0),
+                None => format!("{}: ${:.2} (no budget)", period.name(), spent),
+            }
+        };
+        let text = format!("Spent {}\nSpent {}", line(Period::Day, self.daily), line(Period::Month, self.monthly));
+        if self.daily.is_none() && self.monthly.is_none() {
+            format!("{}\nSet budget_daily_usd or budget_monthly_usd to cap it.", text)
+        } else {
+            text
+        }
+    }
+}
+
+impl Standing {
+    /// Whether the local model should answer instead: a budget is used up
+    /// and `provider` is the paid one
+    pub fn needs_local_model(&self, provider: &LLMProvider) -> bool {
+        matches!(self, Standing::Exceeded { .. }) && *provider == LLMProvider::OpenAI
+    }
+
+    /// What the pet says about it
+    pub fn describe(&self) -> Option<String> {
+        match self {
+            Standing::Within => None,
+            Standing::Low { period, spent, budget } => Some(format!(
+                "Heads up: I've used ${:.2} of your ${:.2} budget {} ({:.0}%).",
+                spent,
+                budget,
+                period.name(),
+                spent / budget * 100.0
+            )),
+            Standing::Exceeded { period, spent, budget } => Some(format!(
+                "Your ${:.2} budget {} is used up (${:.2} spent).",
+                budget,
+                period.name(),
+                spent
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spending_is_priced_and_checked_against_budgets() {
+        let overrides = BTreeMap::from([("gpt-4o".to_string(), Price { input: 5.0, output: 15.0 })]);
+        assert_eq!(price("gpt-4o-mini-2024-07-18", &overrides), Some(Price { input: 0.15, output: 0.6 }));
+        assert_eq!(price("gpt-4o-2024-08-06", &overrides), Some(Price { input: 5.0, output: 15.0 }));
+        assert_eq!(price("llama3.2", &overrides), None);
+        assert!((cost(Price { input: 2.0, output: 8.0 }, Some(1_000), Some(500)) - 0.006).abs() < 1e-9);
+
+        let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        let mut log = Vec::new();
+        record(&mut log, NaiveDate::from_ymd_opt(2024, 2, 28).unwrap(), 50.0);
+        record(&mut log, day(1), 3.0);
+        record(&mut log, day(2), 4.0);
+        record(&mut log, day(2), 4.5);
+        assert_eq!(log.len(), 3);
+
+        let budget = Budget { daily: Some(10.0), monthly: Some(20.0) };
+        assert_eq!(budget.standing(&log, day(2)), Standing::Low { period: Period::Day, spent: 8.5, budget: 10.0 });
+        assert_eq!(budget.standing(&log, day(3)), Standing::Within);
+        record(&mut log, day(3), 9.0);
+        assert_eq!(budget.standing(&log, day(3)), Standing::Exceeded { period: Period::Month, spent: 20.5, budget: 20.0 });
+        assert!(budget.summary(&log, day(3)).starts_with("Spent today: $9.00 of $10.00 (90%)\nSpent this month: $20.50"));
+        let standing = budget.standing(&log, day(3));
+        assert!(standing.needs_local_model(&LLMProvider::OpenAI));
+        assert!(!standing.needs_local_model(&LLMProvider::Ollama));
+
+        let generation = Generation { text: String::new(), prompt_tokens: Some(1_000_000), completion_tokens: Some(0), first_token: None, generation_time: None };
+        assert!(charge(&mut log, &overrides, "gpt-4o", &generation, day(3)));
+        assert!(!charge(&mut log, &overrides, "llama3.2", &generation, day(3)));
+        assert_eq!(Period::Day.spent(&log, day(3)), 14.0);
+
+        record(&mut log, day(30) + Duration::days(60), 1.0);
+        assert_eq!(log.len(), 1);
+    }
+}
@@ -0,0 +1,229 @@
+//! Pet export/import bundles for PetCLI
+//!
+//! `pawshell export-pet <file>` writes everything that makes up a pet into one
+//! JSON file: its name and ASCII art (mood poses included) from the config, and
+//! its full state (mood, complete chat history, bookmarks, adopted aliases, ...). `pawshell import-pet
+//! <file>` loads such a bundle on another machine or into another profile.
+//!
+//! Bundles are plaintext even if `encrypt_history` is on, since the key doesn't
+//! travel with them, so on unix they're only readable by their owner. Provider
+//! settings and API keys are never included.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::clock;
+use crate::art::PetArt;
+use crate::backup;
+use crate::config::Config;
+use crate::config_path;
+use crate::error::BundleError;
+use crate::mood::MoodLevel;
+use crate::pet::PetState;
+use crate::storage;
+
+/// Bumped when the bundle layout changes incompatibly
+const BUNDLE_VERSION: u32 = 2;
+
+/// A pet's complete save file
+#[derive(Serialize, Deserialize)]
+pub struct PetBundle {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub pet_name: String,
+    pub pet_ascii: String,
+    /// Mood poses; version 1 bundles had none
+    #[serde(default)]
+    pub pet_ascii_happy: Option<String>,
+    #[serde(default)]
+    pub pet_ascii_neutral: Option<String>,
+    #[serde(default)]
+    pub pet_ascii_sad: Option<String>,
+    pub state: PetState,
+}
+
+/// Config key and file each mood pose is imported to
+const POSES: [(MoodLevel, &str, &str); 3] = [
+    (MoodLevel::Happy, "pet_ascii_happy_file", "pet_ascii_happy.txt"),
+    (MoodLevel::Content, "pet_ascii_neutral_file", "pet_ascii_neutral.txt"),
+    (MoodLevel::Sad, "pet_ascii_sad_file", "pet_ascii_sad.txt"),
+];
+
+impl PetBundle {
+    fn pose(&self, level: MoodLevel) -> Option<&String> {
+        match level {
+            MoodLevel::Happy => self.pet_ascii_happy.as_ref(),
+            MoodLevel::Content => self.pet_ascii_neutral.as_ref(),
+            MoodLevel::Sad => self.pet_ascii_sad.as_ref(),
+        }
+    }
+}
+
+/// Writes the active profile's pet to `path`
+pub fn export(path: &Path) -> Result<PetBundle, BundleError> {
+    let config = Config::load();
+    let (mut store, warning) = storage::open(&config);
+    if let Some(warning) = warning {
+        return Err(BundleError::StoreUnavailable(warning));
+    }
+    let mut state = store.load_full()?;
+    state.name = config.pet_name.clone();
+
+    let (art, _) = PetArt::load(&config);
+    let bundle = PetBundle {
+        version: BUNDLE_VERSION,
+        exported_at: clock::now(),
+        pet_name: config.pet_name,
+        pet_ascii: art.base().to_string(),
+        pet_ascii_happy: art.pose(MoodLevel::Happy).map(str::to_string),
+        pet_ascii_neutral: art.pose(MoodLevel::Content).map(str::to_string),
+        pet_ascii_sad: art.pose(MoodLevel::Sad).map(str::to_string),
+        state,
+    };
+    write(path, &bundle).map_err(BundleError::file(path))?;
+    Ok(bundle)
+}
+
+/// Writes a bundle readable only by its owner, since it holds the whole chat
+fn write(path: &Path, bundle: &PetBundle) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // The mode only applies to new files
+        if path.exists() {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    let mut file = options.open(path)?;
+    file.write_all(serde_json::to_string_pretty(bundle)?.as_bytes())
+}
+
+/// Reads a bundle, refusing ones from a newer version
+fn read(path: &Path) -> Result<PetBundle, BundleError> {
+    let contents = std::fs::read_to_string(path).map_err(BundleError::file(path))?;
+    let bundle: PetBundle = serde_json::from_str(&contents)
+        .map_err(|e| BundleError::Invalid { path: path.to_path_buf(), message: e.to_string() })?;
+    if bundle.version > BUNDLE_VERSION {
+        return Err(BundleError::TooNew { path: path.to_path_buf(), version: bundle.version });
+    }
+    Ok(bundle)
+}
+
+/// Replaces the active profile's pet with the one in `path`
+///
+/// The current state is backed up first (returning the backup, if there was a
+/// state to back up), and the pet's name and art are written to the config file.
+/// Mood poses are written to files next to it, since the config only points to
+/// pose files.
+pub fn import(path: &Path) -> Result<(PetBundle, Option<PathBuf>), BundleError> {
+    let mut bundle = read(path)?;
+
+    let config = Config::load();
+    let (mut store, warning) = storage::open(&config);
+    if let Some(warning) = warning {
+        return Err(BundleError::StoreUnavailable(warning));
+    }
+    let backup = backup::create(store.as_ref(), config.backup_count.max(1))?;
+    // Load first so the store knows what is already on disk
+    store.load_full()?;
+    bundle.state.name = bundle.pet_name.clone();
+    bundle.state.adopt_legacy_timestamps();
+    store.save(&bundle.state)?;
+    // The bundle's art replaces whatever art the config pointed to
+    let config_dir = config_path::get_config_dir();
+    let pose_files = write_poses(&bundle, &config_dir).map_err(BundleError::file(&config_dir))?;
+    let mut keys = vec![
+        ("pet_name", Some(bundle.pet_name.as_str())),
+        ("pet_ascii", Some(bundle.pet_ascii.as_str())),
+        ("pet_art", None),
+        ("pet_ascii_file", None),
+    ];
+    keys.extend(pose_files.iter().map(|(key, file)| (*key, *file)));
+    Config::set_keys(&keys).map_err(|e| BundleError::Config(e.to_string()))?;
+    Ok((bundle, backup))
+}
+
+/// Writes the bundle's mood poses into `dir`, returning the pose file config
+/// keys to set (None for the poses the bundle doesn't have)
+fn write_poses(bundle: &PetBundle, dir: &Path) -> std::io::Result<Vec<(&'static str, Option<&'static str>)>> {
+    let mut keys = Vec::new();
+    for (level, key, file) in POSES {
+        match bundle.pose(level) {
+            Some(art) => {
+                std::fs::create_dir_all(dir)?;
+                std::fs::write(dir.join(file), art)?;
+                keys.push((key, Some(file)));
+            }
+            None => keys.push((key, None)),
+        }
+    }
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pet::ChatMessage;
+
+    #[test]
+    fn bundles_round_trip_and_are_private() {
+        let path = std::env::temp_dir().join(format!("pawshell-bundle-test-{}.json", std::process::id()));
+        let mut state = PetState { name: "Biscuit".to_string(), mood: 0.75, ..PetState::default() };
+        state.chat_history.push(ChatMessage::new("hi".to_string(), "meow".to_string()));
+        let bundle = PetBundle {
+            version: BUNDLE_VERSION,
+            exported_at: clock::now(),
+            pet_name: "Biscuit".to_string(),
+            pet_ascii: "=^.^=".to_string(),
+            pet_ascii_happy: Some("=^o^=".to_string()),
+            pet_ascii_neutral: None,
+            pet_ascii_sad: Some("=;.;=".to_string()),
+            state,
+        };
+        write(&path, &bundle).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        let read_back = read(&path).unwrap();
+        assert_eq!((read_back.pet_name.as_str(), read_back.pet_ascii.as_str()), ("Biscuit", "=^.^="));
+        assert_eq!(read_back.state.mood, 0.75);
+        assert_eq!(read_back.state.chat_history[0].response, "meow");
+        assert_eq!(read_back.pet_ascii_happy.as_deref(), Some("=^o^="));
+
+        // Poses are written next to the config, and the missing one unset
+        let dir = std::env::temp_dir().join(format!("pawshell-bundle-poses-test-{}", std::process::id()));
+        let keys = write_poses(&read_back, &dir).unwrap();
+        assert_eq!(
+            keys,
+            [
+                ("pet_ascii_happy_file", Some("pet_ascii_happy.txt")),
+                ("pet_ascii_neutral_file", None),
+                ("pet_ascii_sad_file", Some("pet_ascii_sad.txt")),
+            ]
+        );
+        assert_eq!(std::fs::read_to_string(dir.join("pet_ascii_sad.txt")).unwrap(), "=;.;=");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // Bundles from before poses were exported still load
+        let mut old: serde_json::Value = serde_json::to_value(&bundle).unwrap();
+        for key in ["pet_ascii_happy", "pet_ascii_neutral", "pet_ascii_sad"] {
+            old.as_object_mut().unwrap().remove(key);
+        }
+        old["version"] = 1.into();
+        std::fs::write(&path, old.to_string()).unwrap();
+        assert!(read(&path).unwrap().pet_ascii_happy.is_none());
+
+        let newer = PetBundle { version: BUNDLE_VERSION + 1, ..bundle };
+        write(&path, &newer).unwrap();
+        assert!(read(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
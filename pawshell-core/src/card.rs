@@ -0,0 +1,251 @@
+//! Shareable pet cards for `/card`
+//!
+//! A card is a small framed picture of the pet: its art, name, mood level,
+//! chat streak and favorite command. It is written as an ANSI snippet (24-bit
+//! colors, for `cat` in a terminal or a code block) and, if a monospace font
+//! can be found, as a PNG for chats and READMEs. The PNG is drawn from the same
+//! colored character grid as the ANSI text, so both look alike.
+//!
+//! No font is bundled: `card_font` in the config picks one, otherwise a few
+//! common system monospace fonts are tried.
+
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+use chrono::{DateTime, Local};
+use std::path::{Path, PathBuf};
+
+use crate::analytics::CommandStats;
+use crate::config::Config;
+use crate::config_path;
+use crate::history::HistoryEntry;
+use crate::mood::MoodLevel;
+use crate::pet::PetState;
+
+type Rgb = (u8, u8, u8);
+
+const BACKGROUND: Rgb = (30, 30, 46);
+const TEXT: Rgb = (205, 214, 244);
+const MUTED: Rgb = (147, 153, 178);
+const STREAK: Rgb = (250, 179, 135);
+/// Font size of the PNG in pixels
+const FONT_SIZE: f32 = 22.0;
+/// Space around the text in the PNG, in pixels
+const MARGIN: usize = 24;
+
+/// Monospace fonts tried when `card_font` isn't set
+const FONT_CANDIDATES: &[&str] = &[
+    "/usr/share/fonts/truetype/dejavu/DejaVuSansMono.ttf",
+    "/usr/share/fonts/TTF/DejaVuSansMono.ttf",
+    "/usr/share/fonts/dejavu/DejaVuSansMono.ttf",
+    "/usr/share/fonts/truetype/liberation/LiberationMono-Regular.ttf",
+    "/usr/share/fonts/liberation-mono/LiberationMono-Regular.ttf",
+    "/System/Library/Fonts/Menlo.ttc",
+    "/System/Library/Fonts/Monaco.ttf",
+    "C:\\Windows\\Fonts\\consola.ttf",
+    "C:\\Windows\\Fonts\\cour.ttf",
+];
+
+/// One run of same-colored text on a card line
+struct Span {
+    text: String,
+    color: Rgb,
+}
+
+/// Everything shown on a card
+pub struct PetCard {
+    pub name: String,
+    pub art: String,
+    pub mood: f32,
+    pub streak: usize,
+    /// The most used program in the shell history and how often it ran
+    pub favorite_command: Option<(String, usize)>,
+}
+
+impl PetCard {
+    pub fn new(state: &PetState, art: &str, history: &[HistoryEntry], now: &DateTime<Local>) -> Self {
+        let favorite_command = CommandStats::from_entries(history, 1).top_commands.into_iter().next();
+        Self {
+            name: state.name.clone(),
+            art: art.to_string(),
+            mood: state.mood,
+            streak: state.chat_streak(now),
+            favorite_command,
+        }
+    }
+
+    /// The card as a framed grid of colored text
+    fn lines(&self) -> Vec<Vec<Span>> {
+        let (level, mood_color) = match MoodLevel::of(self.mood) {
+            MoodLevel::Happy => ("Happy", (166, 227, 161)),
+            MoodLevel::Content => ("Content", (249, 226, 175)),
+            MoodLevel::Sad => ("Sad", (243, 139, 168)),
+        };
+        let streak = match self.streak {
+            0 => "No chat streak yet".to_string(),
+            1 => "1-day chat streak".to_string(),
+            days => format!("{}-day chat streak", days),
+        };
+        let favorite = match &self.favorite_command {
+            Some((command, count)) => format!("Favorite command: {} ({}×)", command, count),
+            None => "No favorite command yet".to_string(),
+        };
+
+        let mut content: Vec<(String, Rgb)> = self.art.lines().map(|line| (line.to_string(), mood_color)).collect();
+        content.push((String::new(), TEXT));
+        content.push((self.name.clone(), TEXT));
+        content.push((format!("{} · {:.0}% mood", level, self.mood * 100.0), mood_color));
+        content.push((streak, STREAK));
+        content.push((favorite, MUTED));
+
+        let width = content.iter().map(|(text, _)| text.chars().count()).max().unwrap_or(0) + 2;
+        let border = |text: String| Span { text, color: mood_color };
+        let mut lines = vec![vec![border(format!("╭{}╮", "─".repeat(width)))]];
+        for (text, color) in content {
+            let padding = width - 1 - text.chars().count();
+            lines.push(vec![
+                border("│ ".to_string()),
+                Span { text, color },
+                Span { text: " ".repeat(padding), color: TEXT },
+                border("│".to_string()),
+            ]);
+        }
+        lines.push(vec![border(format!("╰{}╯", "─".repeat(width)))]);
+        lines
+    }
+
+    /// The card without colors
+    pub fn to_plain(&self) -> String {
+        let lines: Vec<String> =
+            self.lines().iter().map(|line| line.iter().map(|span| span.text.as_str()).collect()).collect();
+        lines.join("\n")
+    }
+
+    /// The card with 24-bit ANSI colors
+    pub fn to_ansi(&self) -> String {
+        let mut ansi = String::new();
+        for line in self.lines() {
+            for span in line {
+                let (r, g, b) = span.color;
+                ansi.push_str(&format!("\x1b[38;2;{};{};{}m{}", r, g, b, span.text));
+            }
+            ansi.push_str("\x1b[0m\n");
+        }
+        ansi
+    }
+
+    /// Draws the card in `font` and encodes it as a PNG
+    pub fn to_png(&self, font: &FontVec) -> Result<Vec<u8>, String> {
+        let scale = PxScale::from(FONT_SIZE);
+        let scaled = font.as_scaled(scale);
+        let cell_width = scaled.h_advance(font.glyph_id('M')).ceil() as usize;
+        let line_height = (scaled.ascent() - scaled.descent()).ceil() as usize;
+        let lines = self.lines();
+        let columns = lines
+            .iter()
+            .map(|line| line.iter().map(|span| span.text.chars().count()).sum::<usize>())
+            .max()
+            .unwrap_or(0);
+        let width = columns * cell_width + 2 * MARGIN;
+        let height = lines.len() * line_height + 2 * MARGIN;
+
+        let mut pixels: Vec<u8> = [BACKGROUND.0, BACKGROUND.1, BACKGROUND.2].repeat(width * height);
+        for (row, line) in lines.iter().enumerate() {
+            let baseline = (MARGIN + row * line_height) as f32 + scaled.ascent();
+            let mut column = 0;
+            for span in line {
+                for c in span.text.chars() {
+                    let x = (MARGIN + column * cell_width) as f32;
+                    column += 1;
+                    let glyph = font.glyph_id(c).with_scale_and_position(scale, point(x, baseline));
+                    let Some(outline) = font.outline_glyph(glyph) else {
+                        continue;
+                    };
+                    let bounds = outline.px_bounds();
+                    outline.draw(|gx, gy, coverage| {
+                        let px = bounds.min.x as i64 + gx as i64;
+                        let py = bounds.min.y as i64 + gy as i64;
+                        if px < 0 || py < 0 || px as usize >= width || py as usize >= height {
+                            return;
+                        }
+                        let i = (py as usize * width + px as usize) * 3;
+                        let (r, g, b) = span.color;
+                        for (channel, value) in pixels[i..i + 3].iter_mut().zip([r, g, b]) {
+                            let blended = *channel as f32 + (value as f32 - *channel as f32) * coverage.min(1.0);
+                            *channel = blended.round() as u8;
+                        }
+                    });
+                }
+            }
+        }
+
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+        writer.write_image_data(&pixels).map_err(|e| e.to_string())?;
+        writer.finish().map_err(|e| e.to_string())?;
+        Ok(png)
+    }
+}
+
+pub fn cards_dir() -> PathBuf {
+    config_path::get_data_dir().join("cards")
+}
+
+/// Loads `card_font`, or the first system monospace font that exists
+pub fn load_font(config: &Config) -> Result<FontVec, String> {
+    let path = match &config.card_font {
+        Some(path) => PathBuf::from(path),
+        None => FONT_CANDIDATES
+            .iter()
+            .map(PathBuf::from)
+            .find(|path| path.exists())
+            .ok_or("no monospace font found; set card_font in config.toml")?,
+    };
+    let bytes = std::fs::read(&path).map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+    FontVec::try_from_vec_and_index(bytes, 0).map_err(|e| format!("{} isn't a usable font: {}", path.display(), e))
+}
+
+/// Writes the card to the cards directory as `.ans` and, with a font, `.png`
+///
+/// Returns the paths written; a PNG that couldn't be made is reported as the
+/// second element instead, since the ANSI card is still useful.
+pub fn save(card: &PetCard, config: &Config, now: &DateTime<Local>) -> Result<(PathBuf, Result<PathBuf, String>), String> {
+    let dir = cards_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| format!("couldn't create {}: {}", dir.display(), e))?;
+    let stem = format!("{}-{}", card.name.to_lowercase().replace(char::is_whitespace, "-"), now.format("%Y-%m-%d"));
+    let ansi_path = dir.join(format!("{}.ans", stem));
+    write(&ansi_path, card.to_ansi().as_bytes())?;
+    let png = load_font(config).and_then(|font| card.to_png(&font)).and_then(|png| {
+        let path = dir.join(format!("{}.png", stem));
+        write(&path, &png).map(|()| path)
+    });
+    Ok((ansi_path, png))
+}
+
+fn write(path: &Path, contents: &[u8]) -> Result<(), String> {
+    std::fs::write(path, contents).map_err(|e| format!("couldn't write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn card_lines_share_one_width() {
+        let card = PetCard {
+            name: "Whiskers".to_string(),
+            art: " /\\_/\\\n( o.o )".to_string(),
+            mood: 0.85,
+            streak: 3,
+            favorite_command: Some(("git".to_string(), 412)),
+        };
+        let plain = card.to_plain();
+        let widths: Vec<usize> = plain.lines().map(|line| line.chars().count()).collect();
+        assert!(widths.iter().all(|&width| width == widths[0]));
+        assert!(plain.contains("Happy · 85% mood"));
+        assert!(plain.contains("3-day chat streak"));
+        assert!(plain.contains("Favorite command: git (412×)"));
+        assert_eq!(card.to_ansi().lines().count(), plain.lines().count());
+    }
+}
@@ -0,0 +1,160 @@
+//! Chat sessions and the `/sessions` browser
+//!
+//! Each run of the TUI that says anything is a session. Its exchanges are
+//! tagged with the session's id, and the session itself (when it started, its
+//! title and how many exchanges it has) is kept in the pet's state apart from
+//! the chat history. Once a session has `TITLE_AFTER` exchanges the backend is
+//! asked for a short title. `/sessions` lists the sessions newest first, and
+//! opening one brings its conversation back, in the chat window and as the
+//! backend's context, so new messages continue it.
+
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::pet::ChatMessage;
+use crate::templates::Prompt;
+
+/// Exchanges a session needs before it gets a title
+pub const TITLE_AFTER: u32 = 2;
+/// Titles are cut to this many characters
+const MAX_TITLE_CHARS: usize = 48;
+/// Characters of each exchange given to the title prompt
+const EXCHANGE_CHARS: usize = 400;
+
+const TITLE_TEMPLATE: &str = "Give this conversation between me and my terminal pet a short title of at most \
+6 words, like \"Fixing a Docker build\". Reply with the title only, without quotes.\n\n{{ exchanges }}";
+
+/// A run of the TUI and what was said in it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChatSession {
+    pub id: u64,
+    pub started: DateTime<Utc>,
+    /// None until the backend has named it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Exchanges said in it, including any no longer loaded
+    pub exchanges: u32,
+}
+
+impl ChatSession {
+    pub fn label(&self) -> &str {
+        self.title.as_deref().unwrap_or("Untitled")
+    }
+}
+
+/// Adds a session starting now and returns its id
+pub fn start(sessions: &mut Vec<ChatSession>, now: DateTime<Utc>) -> u64 {
+    let id = sessions.iter().map(|session| session.id + 1).max().unwrap_or(1);
+    sessions.push(ChatSession { id, started: now, title: None, exchanges: 0 });
+    id
+}
+
+/// The part of `conversation` said in `session`, or all of it without one
+pub fn messages(conversation: Vec<&ChatMessage>, session: Option<u64>) -> Vec<&ChatMessage> {
+    match session {
+        Some(id) => conversation.into_iter().filter(|message| message.session == Some(id)).collect(),
+        None => conversation,
+    }
+}
+
+/// Asks for a title for a session's first exchanges
+pub fn title_prompt(exchanges: &[&ChatMessage]) -> Prompt {
+    let shorten = |text: &str| text.chars().take(EXCHANGE_CHARS).collect::<String>();
+    let text = exchanges
+        .iter()
+        .map(|message| format!("Me: {}\nPet: {}", shorten(&message.user_message), shorten(&message.response)))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    Prompt::new("session_title", TITLE_TEMPLATE).var("exchanges", text)
+}
+
+/// The title in a reply to `title_prompt`, without quotes or a label
+pub fn clean_title(reply: &str) -> Option<String> {
+    let line = reply.lines().map(str::trim).find(|line| !line.is_empty())?;
+    let line = line.strip_prefix("Title:").unwrap_or(line);
+    let title = line.trim().trim_matches(|c| matches!(c, '"' | '\'' | '*' | '#' | '`')).trim().trim_end_matches('.');
+    if title.is_empty() {
+        return None;
+    }
+    if title.chars().count() > MAX_TITLE_CHARS {
+        let cut: String = title.chars().take(MAX_TITLE_CHARS).collect();
+        return Some(format!("{}…", cut.trim_end()));
+    }
+    Some(title.to_string())
+}
+
+/// The `/sessions` overlay: the sessions newest first, one of them selected
+#[derive(Debug, Clone)]
+pub struct Browser {
+    pub sessions: Vec<ChatSession>,
+    pub selected: usize,
+    /// The session new messages go to, marked in the list
+    pub current: Option<u64>,
+}
+
+impl Browser {
+    /// A browser of `sessions`, with the current one selected; none without sessions
+    pub fn new(sessions: &[ChatSession], current: Option<u64>) -> Option<Self> {
+        if sessions.is_empty() {
+            return None;
+        }
+        let mut sessions = sessions.to_vec();
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.started));
+        let selected = sessions.iter().position(|session| Some(session.id) == current).unwrap_or(0);
+        Some(Self { sessions, selected, current })
+    }
+
+    pub fn up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn down(&mut self) {
+        self.selected = (self.selected + 1).min(self.sessions.len() - 1);
+    }
+
+    pub fn selected(&self) -> &ChatSession {
+        &self.sessions[self.selected]
+    }
+
+    /// A session's line in the list: when it started, its title and its length
+    pub fn line(session: &ChatSession) -> String {
+        format!(
+            "{}  {}  ({} exchange{})",
+            session.started.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+            session.label(),
+            session.exchanges,
+            if session.exchanges == 1 { "" } else { "s" }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::test_time;
+
+    #[test]
+    fn sessions_keep_their_own_exchanges_and_get_titles() {
+        let mut sessions = Vec::new();
+        assert_eq!(start(&mut sessions, test_time(0)), 1);
+        assert_eq!(start(&mut sessions, test_time(60)), 2);
+
+        let message = |text: &str, session| ChatMessage { session, ..ChatMessage::new(text.to_string(), String::new()) };
+        let history = [message("before sessions", None), message("docker", Some(1)), message("git", Some(2)), message("compose", Some(1))];
+        let texts = |session| messages(history.iter().collect(), session).iter().map(|message| message.user_message.as_str()).collect::<Vec<_>>();
+        assert_eq!(texts(Some(1)), ["docker", "compose"]);
+        assert_eq!(texts(None).len(), 4);
+
+        assert_eq!(clean_title("Title: \"Fixing a Docker build.\"\n"), Some("Fixing a Docker build".to_string()));
+        assert_eq!(clean_title(&"word ".repeat(20)).unwrap().chars().count(), MAX_TITLE_CHARS + 1);
+        assert_eq!(clean_title("  \n\"\""), None);
+
+        let mut browser = Browser::new(&sessions, Some(1)).unwrap();
+        assert_eq!((browser.selected().id, browser.selected), (1, 1));
+        browser.down();
+        browser.up();
+        browser.up();
+        assert_eq!(browser.selected().id, 2);
+        assert!(Browser::new(&[], None).is_none());
+    }
+}
@@ -0,0 +1,135 @@
+//! The time and randomness the pet runs on
+//!
+//! Everything that reads the wall clock (mood decay, timestamps, cooldowns,
+//! script hooks) goes through [`now`] instead of `Utc::now()`, so a
+//! [`FakeClock`] can stand in for it. Tests inject one into the parts they
+//! exercise; `--freeze-time` installs one for the whole process, which along
+//! with `--seed` makes the TUI render the same way on every run for snapshot
+//! tests.
+//!
+//! [`rng`] hands out random number generators for the pet's behavior. They
+//! draw from the OS unless a seed is set, in which case the sequence of
+//! generators is the same on every run. Key material never comes from here.
+
+use chrono::{DateTime, Duration, Local, Utc};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// A source of the current time
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The real time
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when told to
+///
+/// Clones share the same time, so a test can keep one and advance the copy it
+/// handed to the code under test.
+#[derive(Clone)]
+pub struct FakeClock {
+    now: Arc<Mutex<DateTime<Utc>>>,
+}
+
+impl FakeClock {
+    pub fn at(now: DateTime<Utc>) -> Self {
+        Self { now: Arc::new(Mutex::new(now)) }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) += by;
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) = now;
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// `minutes` after 09:00 UTC on 2024-03-01, the time tests start from
+#[cfg(test)]
+pub(crate) fn test_time(minutes: i64) -> DateTime<Utc> {
+    "2024-03-01T09:00:00Z".parse::<DateTime<Utc>>().unwrap() + Duration::minutes(minutes)
+}
+
+/// The process-wide clock; None is the system clock
+static CLOCK: RwLock<Option<Arc<dyn Clock>>> = RwLock::new(None);
+/// Seed for `rng`, if set
+static SEED: RwLock<Option<u64>> = RwLock::new(None);
+/// How many generators `rng` has handed out, so each seeded one differs
+static RNG_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Replaces the process-wide clock, e.g. with a frozen one for `--freeze-time`
+pub fn set(clock: Arc<dyn Clock>) {
+    *CLOCK.write().unwrap_or_else(|e| e.into_inner()) = Some(clock);
+}
+
+/// The process-wide clock
+pub fn current() -> Arc<dyn Clock> {
+    CLOCK.read().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_else(|| Arc::new(SystemClock))
+}
+
+pub fn now() -> DateTime<Utc> {
+    match CLOCK.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        Some(clock) => clock.now(),
+        None => Utc::now(),
+    }
+}
+
+/// `now` in the local time zone
+pub fn local_now() -> DateTime<Local> {
+    now().with_timezone(&Local)
+}
+
+/// Makes `rng` deterministic
+pub fn set_seed(seed: u64) {
+    *SEED.write().unwrap_or_else(|e| e.into_inner()) = Some(seed);
+    RNG_COUNT.store(0, Ordering::SeqCst);
+}
+
+/// A random number generator for the pet's behavior, seeded if `set_seed` was called
+pub fn rng() -> StdRng {
+    match *SEED.read().unwrap_or_else(|e| e.into_inner()) {
+        Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(RNG_COUNT.fetch_add(1, Ordering::SeqCst))),
+        None => StdRng::from_entropy(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn fake_clocks_share_their_time_between_clones() {
+        let start = test_time(0);
+        let clock = FakeClock::at(start);
+        let handed_out = clock.clone();
+        clock.advance(Duration::hours(2));
+        assert_eq!(handed_out.now(), start + Duration::hours(2));
+    }
+
+    #[test]
+    fn seeded_generators_repeat_across_runs() {
+        set_seed(7);
+        let first: Vec<u32> = (0..3).map(|_| rng().gen()).collect();
+        set_seed(7);
+        let second: Vec<u32> = (0..3).map(|_| rng().gen()).collect();
+        assert_eq!(first, second);
+        assert_ne!(first[0], first[1]);
+    }
+}
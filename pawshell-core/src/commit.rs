@@ -0,0 +1,84 @@
+//! Commit message drafts for `/commitmsg`
+//!
+//! Reads the staged changes with `git diff --cached`, builds a prompt asking
+//! for a Conventional Commits message, and writes the (possibly edited) draft
+//! to the repository's `COMMIT_EDITMSG`, where `git commit -eF` can pick it up.
+//! Large diffs are cut off so the prompt stays within the model's context.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::templates::Prompt;
+
+/// Diff text beyond this many characters is left out of the prompt
+const MAX_DIFF_CHARS: usize = 12_000;
+
+const TEMPLATE: &str = "Write a commit message for the staged changes below, following Conventional Commits: \
+a subject line like `fix(parser): handle empty input` (type, optional scope, imperative mood, \
+at most 72 characters, no trailing period), then a blank line and a short body explaining \
+what changed and why, wrapped at 72 characters. Leave out the body if the subject says it all. \
+Reply with the commit message only, without code fences or commentary.\n\n{{ diff }}";
+
+/// The staged changes of the repository containing `dir`: a `--stat` summary
+/// followed by the (possibly truncated) diff
+pub fn staged_diff(dir: &Path) -> Result<String, String> {
+    let stat = git(dir, &["diff", "--cached", "--stat"])?;
+    if stat.trim().is_empty() {
+        return Err("Nothing is staged; `git add` the changes to describe first.".to_string());
+    }
+    let diff = git(dir, &["diff", "--cached", "--no-color", "--no-ext-diff"])?;
+    let mut text = format!("{}\n", stat.trim_end());
+    if diff.chars().count() > MAX_DIFF_CHARS {
+        text.extend(diff.chars().take(MAX_DIFF_CHARS));
+        text.push_str("\n… (diff truncated)\n");
+    } else {
+        text.push_str(&diff);
+    }
+    Ok(text)
+}
+
+/// Asks for a Conventional Commits message describing `diff`
+pub fn prompt(diff: &str) -> Prompt {
+    Prompt::new("commit", TEMPLATE).var("diff", diff)
+}
+
+/// The commit message in a reply, without code fences or surrounding blank lines
+pub fn clean_reply(reply: &str) -> String {
+    let lines: Vec<&str> = reply.trim().lines().filter(|line| !line.trim_start().starts_with("```")).collect();
+    lines.join("\n").trim().to_string()
+}
+
+/// Writes `message` to the repository's `COMMIT_EDITMSG`, returning its path
+pub fn write_editmsg(dir: &Path, message: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(git(dir, &["rev-parse", "--git-path", "COMMIT_EDITMSG"])?.trim());
+    let path = dir.join(path);
+    std::fs::write(&path, format!("{}\n", message.trim_end()))
+        .map_err(|e| format!("Couldn't write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Runs a git command in `dir`, returning stdout, or git's complaint on failure
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Couldn't run git: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replies_lose_code_fences() {
+        let reply = "```text\nfeat(ui): show mood in the title\n\nSo it's visible without /stats.\n```\n";
+        assert_eq!(clean_reply(reply), "feat(ui): show mood in the title\n\nSo it's visible without /stats.");
+        assert_eq!(clean_reply("  fix: typo  \n"), "fix: typo");
+    }
+}
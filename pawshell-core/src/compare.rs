@@ -0,0 +1,118 @@
+//! Side-by-side answers from two models for `/compare`
+//!
+//! `/compare <a> <b> [question]` sends the same chat prompt, for the question
+//! or else the last message, to two models at once. A model is an Ollama
+//! model name, or `openai/<model>` for one of OpenAI's. The answers are shown
+//! in two columns, and picking the better one counts as a 👍 for it and a 👎
+//! for the other, so what the winner does well shapes later prompts.
+
+use crate::config::Config;
+use crate::error::LlmError;
+use crate::feedback::Reaction;
+use crate::llm::{self, LLMBackend, OpenAIBackend};
+use crate::ollama::OllamaBackend;
+use crate::perf::Speed;
+
+/// Marks a model as one of OpenAI's rather than Ollama's
+pub const OPENAI_PREFIX: &str = "openai/";
+
+/// A backend for `model`, otherwise set up like the configured one
+pub fn backend(config: &Config, model: &str) -> Result<Box<dyn LLMBackend>, LlmError> {
+    Ok(match model.strip_prefix(OPENAI_PREFIX) {
+        Some(model) => {
+            let api_key = std::env::var("OPENAI_API_KEY")
+                .map_err(|_| LlmError::MissingKey { var: "OPENAI_API_KEY".to_string() })?;
+            Box::new(OpenAIBackend::new(api_key, llm::system_prompt(config), config.openai_options()).with_model(model))
+        }
+        None => Box::new(OllamaBackend::new(
+            config.ollama_url.clone(),
+            model.to_string(),
+            llm::system_prompt(config),
+            config.ollama_options(),
+        )),
+    })
+}
+
+/// Reads `/compare`'s arguments: the two models and the question, if any
+pub fn parse(args: &str) -> Result<(String, String, Option<String>), String> {
+    let mut words = args.splitn(3, char::is_whitespace);
+    let (Some(a), Some(b)) = (words.next().filter(|a| !a.is_empty()), words.next().filter(|b| !b.is_empty())) else {
+        return Err("Usage: /compare <model> <model> [question], like /compare llama3.2 openai/gpt-4o-mini".to_string());
+    };
+    if a == b {
+        return Err(format!("Comparing {} with itself won't tell you much; pick two different models.", a));
+    }
+    let question = words.next().map(str::trim).filter(|question| !question.is_empty()).map(str::to_string);
+    Ok((a.to_string(), b.to_string(), question))
+}
+
+/// One model's side of a comparison
+#[derive(Debug, Clone)]
+pub struct Answer {
+    pub model: String,
+    /// The reply, or why there isn't one; none while waiting
+    pub reply: Option<Result<String, String>>,
+    pub speed: Option<Speed>,
+}
+
+/// The same question put to two models
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    /// Tells its answers apart from those of an earlier comparison
+    pub id: u64,
+    pub question: String,
+    pub answers: [Answer; 2],
+    pub winner: Option<usize>,
+    /// Lines both answers are scrolled down by
+    pub scroll: u16,
+}
+
+impl Comparison {
+    pub fn new(id: u64, question: String, a: String, b: String) -> Self {
+        let answer = |model| Answer { model, reply: None, speed: None };
+        Self { id, question, answers: [answer(a), answer(b)], winner: None, scroll: 0 }
+    }
+
+    /// Whether both models have answered (or failed to)
+    pub fn finished(&self) -> bool {
+        self.answers.iter().all(|answer| answer.reply.is_some())
+    }
+
+    /// Makes `winner` (0 or 1) the better answer and returns the reactions
+    /// that amounts to; none until both models have answered
+    pub fn pick(&mut self, winner: usize) -> Option<[Reaction; 2]> {
+        if winner > 1 || self.winner.is_some() {
+            return None;
+        }
+        let [Some(Ok(first)), Some(Ok(second))] = [&self.answers[0].reply, &self.answers[1].reply] else {
+            return None;
+        };
+        self.winner = Some(winner);
+        Some([Reaction::new(first, None, winner == 0), Reaction::new(second, None, winner == 1)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picking_a_winner_likes_one_answer_and_dislikes_the_other() {
+        assert_eq!(
+            parse("llama3.2 openai/gpt-4o-mini how do I   find big files?"),
+            Ok(("llama3.2".to_string(), "openai/gpt-4o-mini".to_string(), Some("how do I   find big files?".to_string())))
+        );
+        assert_eq!(parse("llama3.2 mistral").unwrap().2, None);
+        assert!(parse("llama3.2").is_err() && parse("mistral mistral hi").is_err());
+
+        let mut comparison = Comparison::new(1, "find big files".to_string(), "llama3.2".to_string(), "mistral".to_string());
+        comparison.answers[0].reply = Some(Ok("Try `du -sh * | sort -h`".to_string()));
+        assert!(!comparison.finished() && comparison.pick(0).is_none());
+        comparison.answers[1].reply = Some(Ok("Use `find . -size +100M`".to_string()));
+        let [first, second] = comparison.pick(1).unwrap();
+        assert!(!first.liked && second.liked);
+        assert_eq!(second.tools, ["find"]);
+        assert_eq!(comparison.winner, Some(1));
+        assert!(comparison.pick(0).is_none());
+    }
+}
@@ -0,0 +1,718 @@
+//! Configuration management for PetCLI
+//!
+//! This module handles the application's configuration settings, including:
+//! - LLM provider selection and settings
+//! - Pet customization options
+//! - Command history limits
+//!
+//! The configuration is stored in TOML format and supports multiple LLM backends.
+//! Every key can be overridden with a `PAWSHELL_<KEY>` environment variable, e.g.
+//! `PAWSHELL_LLM_PROVIDER=Ollama`, which takes precedence over the file.
+//! Consider splitting the pet-specific configuration into a separate module if
+//! pet customization options grow more complex.
+
+use chrono::NaiveTime;
+use figment::providers::{Env, Format, Serialized, Toml};
+use figment::Figment;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::Path;
+
+use crate::art;
+use crate::budget::{Budget, Price};
+use crate::config_path;
+use crate::few_shot::{self, Example};
+use crate::history_filter::HistoryFilter;
+use crate::llm::{self, OpenAIOptions};
+use crate::ollama::{KeepAlive, OllamaOptions};
+use crate::project;
+use crate::storage;
+use crate::templates;
+
+/// Supported Language Model providers
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub enum LLMProvider {
+    OpenAI,
+    Ollama,
+}
+
+/// Where the pet's state and chat history are stored
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub enum StorageBackend {
+    /// A single TOML state file
+    Confy,
+    /// An indexed SQLite database (`petcli.db`)
+    Sqlite,
+    /// Per-machine append-only files that can be synced between machines
+    Synced,
+    /// One locked state file at `shared_state_path` that several users share
+    Shared,
+}
+
+/// Where the weather comes from
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum WeatherProvider {
+    /// open-meteo.com, which needs coordinates
+    OpenMeteo,
+    /// wttr.in, which guesses the location from the IP address without coordinates
+    WttrIn,
+}
+
+/// What turns recorded speech into text
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum SttEngine {
+    /// whisper.cpp on this machine
+    WhisperCpp,
+    /// OpenAI's transcription API (needs `OPENAI_API_KEY`)
+    OpenAI,
+}
+
+/// What paints `/portrait`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum ImageEngine {
+    /// DALL·E through OpenAI's image API (needs `OPENAI_API_KEY`)
+    OpenAI,
+    /// A local Stable Diffusion web UI's API at `stable_diffusion_url`
+    StableDiffusion,
+}
+
+/// How pictures are drawn in the terminal
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum TerminalGraphics {
+    /// Kitty's graphics protocol or sixel, guessed from the terminal
+    Auto,
+    Kitty,
+    Sixel,
+    /// Only save pictures
+    Off,
+}
+
+/// What reads replies out loud
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum TtsEngine {
+    /// `tts_command`, or the platform's own voice (say, espeak-ng, SAPI)
+    System,
+    /// OpenAI's speech API (needs `OPENAI_API_KEY`)
+    OpenAI,
+}
+
+/// How the input line is edited
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum EditingMode {
+    /// readline's Emacs bindings, like Ctrl+A, Alt+F and Ctrl+K
+    #[serde(alias = "emacs")]
+    Emacs,
+    /// vi's insert and normal modes (see `vi_mode`)
+    #[serde(alias = "vi")]
+    Vi,
+}
+
+/// Main configuration structure for the application
+/// 
+/// Handles both application-level settings and pet customization.
+/// If pet customization options grow, consider moving them to a dedicated
+/// PetConfig struct in the pet module.
+///
+/// Missing keys fall back to their defaults so that config files written by
+/// older versions keep loading after new options are added.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    pub command_history_limit: usize,
+    pub pet_name: String,
+    pub pet_ascii: String,
+    /// Read the art from this text file instead (relative to the config directory)
+    pub pet_ascii_file: Option<String>,
+    /// Use a pet from the built-in gallery (see `/art`)
+    pub pet_art: Option<String>,
+    /// What kind of animal the pet is, for `/portrait`; taken from `pet_art` if unset
+    pub pet_species: Option<String>,
+    /// Art shown while the pet is happy (mood above 80%)
+    pub pet_ascii_happy_file: Option<String>,
+    /// Art shown while the pet is content (mood above 40%)
+    pub pet_ascii_neutral_file: Option<String>,
+    /// Art shown while the pet is sad
+    pub pet_ascii_sad_file: Option<String>,
+    pub llm_provider: LLMProvider,
+    /// Who the pet should act as, e.g. "a patient Rust mentor"; added to the system prompt
+    pub persona: Option<String>,
+    /// Replaces the built-in system prompt
+    pub system_prompt: Option<String>,
+    /// Replaces built-in prompts by template name, like "commit" or "chat" (see `templates`)
+    pub prompt_templates: BTreeMap<String, String>,
+    /// Example exchanges showing the pet how to answer (see `few_shot`)
+    pub few_shot: Vec<Example>,
+    /// Language the pet replies in, e.g. "German"; also used for its built-in phrases
+    pub language: Option<String>,
+    /// OpenAI's API, or another region of it like "https://eu.api.openai.com/v1"
+    pub openai_base_url: String,
+    /// OpenAI organization ID for keys that belong to several
+    pub openai_organization: Option<String>,
+    /// OpenAI project ID usage is billed to
+    pub openai_project: Option<String>,
+    /// Dollars paid backends may cost per day before the pet switches to Ollama
+    pub budget_daily_usd: Option<f64>,
+    /// Dollars paid backends may cost per calendar month before the pet switches to Ollama
+    pub budget_monthly_usd: Option<f64>,
+    /// Dollars per million input and output tokens by model name prefix, for models
+    /// without a built-in price or whose price changed (see `budget`)
+    pub model_prices: BTreeMap<String, Price>,
+    pub ollama_url: String,
+    pub ollama_model: String,
+    /// How long Ollama keeps the model loaded after a request, like "10m" or -1 for good
+    pub ollama_keep_alive: Option<KeepAlive>,
+    /// Ollama's context window in tokens (the model's default if unset)
+    pub ollama_num_ctx: Option<u32>,
+    /// Most tokens Ollama generates per reply; -1 for no limit
+    pub ollama_num_predict: Option<i32>,
+    /// Fixed seed so Ollama gives the same reply to the same prompt
+    pub ollama_seed: Option<i64>,
+    /// Sequences that end an Ollama reply
+    pub ollama_stop: Vec<String>,
+    /// Read command context from Atuin's history database instead of plain history files
+    pub atuin_history: bool,
+    /// Overrides the Atuin database location (defaults to Atuin's own default)
+    pub atuin_db_path: Option<String>,
+    /// Combine all history files (zsh, bash, PowerShell) instead of using the first one found
+    pub merge_history_files: bool,
+    /// Give prompts only the latest run of each command
+    pub history_dedupe: bool,
+    /// Commands (with any arguments) left out of prompts unless they failed
+    pub history_ignore: Vec<String>,
+    /// Commands given to prompts at most, picked by how recent and frequent they are
+    pub history_prompt_commands: usize,
+    /// Let the pet offer shortcuts for repetitive command patterns unprompted
+    pub proactive_suggestions: bool,
+    /// Minimum minutes between unprompted suggestions
+    pub suggestion_cooldown_minutes: i64,
+    /// Kill-switch for running commands via `/run` and `!cmd`
+    pub allow_command_execution: bool,
+    /// Commands are killed after this many seconds
+    pub exec_timeout_secs: u64,
+    /// Run commands in this directory instead of the current one
+    pub exec_working_dir: Option<String>,
+    /// Run commands with a minimal environment so secrets aren't inherited
+    pub exec_clear_env: bool,
+    /// Captured stdout/stderr beyond this size is truncated
+    pub exec_max_output_bytes: usize,
+    /// Regexes of commands the pet may suggest but never offers to run
+    pub exec_denylist: Vec<String>,
+    /// Tell the pet about the launch directory and its git branch/status
+    pub include_workspace_context: bool,
+    /// Tell the pet about the OS, shell, terminal and which CLI tools are installed
+    pub include_system_context: bool,
+    /// Tell the pet about your most visited directories from zoxide's database
+    pub zoxide_context: bool,
+    /// Give the pet the tldr or man page of a command a message asks about
+    pub docs_context: bool,
+    /// Where to keep the pet's state; existing confy state is imported into SQLite on first use
+    pub storage_backend: StorageBackend,
+    /// Directory for the `Synced` storage backend (`sync` in the data directory if unset)
+    pub sync_dir: Option<String>,
+    /// State file for the `Shared` storage backend, somewhere all of its users can write
+    pub shared_state_path: Option<String>,
+    /// Archive chat exchanges beyond this many (unlimited if unset)
+    pub chat_retention_messages: Option<usize>,
+    /// Archive chat exchanges older than this many days (kept forever if unset)
+    pub chat_retention_days: Option<i64>,
+    /// Number of state backups to keep (0 disables backups)
+    pub backup_count: usize,
+    /// Minimum hours between backups (0 backs up before every save)
+    pub backup_interval_hours: u64,
+    /// Also write each session's conversation to a Markdown file in the data directory
+    pub write_transcripts: bool,
+    /// Encrypt chat messages at rest (key from the OS keyring or $PAWSHELL_PASSPHRASE)
+    pub encrypt_history: bool,
+    /// Redact secrets from prompts sent to remote backends
+    pub privacy_filter: bool,
+    /// Extra regexes to redact; a `(?P<secret>...)` group limits what gets replaced
+    pub redaction_patterns: Vec<String>,
+    /// Load WebAssembly plugins from the `plugins` directory next to the config
+    pub load_plugins: bool,
+    /// Load Rhai scripts from the `scripts` directory next to the config
+    pub load_scripts: bool,
+    /// Send OS notifications when the pet needs attention while you're away from it
+    pub desktop_notifications: bool,
+    /// Notify when the mood drops below this (0.0 to 1.0)
+    pub notify_mood_below: f32,
+    /// Notify when a reply that took at least this long arrives
+    pub notify_slow_reply_secs: u64,
+    /// Local times without notifications, like "22:00-08:00"
+    pub quiet_hours: Option<String>,
+    /// Monospace font for `/card` images; common system fonts are tried if unset
+    pub card_font: Option<String>,
+    /// Fetch the local weather for greetings, idle art and outdoor questions
+    pub weather: bool,
+    pub weather_provider: WeatherProvider,
+    pub weather_latitude: Option<f64>,
+    pub weather_longitude: Option<f64>,
+    /// How long fetched weather is reused before asking the provider again
+    pub weather_refresh_minutes: u64,
+    /// Watch CPU, memory and disk usage; the pet comments when they run out
+    pub system_monitor: bool,
+    pub system_monitor_interval_secs: u64,
+    /// Show the battery level; the pet gets sleepy and asks for the charger when it runs low
+    pub battery_awareness: bool,
+    /// Seconds between backend reachability checks (0 turns the offline switch off)
+    pub connectivity_check_secs: u64,
+    /// Read replies out loud from the start; `/speak on|off` toggles it
+    pub speak_replies: bool,
+    pub tts_engine: TtsEngine,
+    /// Command that reads text from stdin and speaks it, for the `System` engine
+    pub tts_command: Option<String>,
+    /// Voice for the `OpenAI` engine
+    pub tts_voice: String,
+    /// Record questions with `voice_key` and transcribe them into the input line
+    pub voice_input: bool,
+    /// Key that starts and stops recording, like "F9" or "ctrl+space"
+    pub voice_key: String,
+    pub stt_engine: SttEngine,
+    /// whisper.cpp's command-line program
+    pub whisper_command: String,
+    /// ggml model file for whisper.cpp
+    pub whisper_model: Option<String>,
+    /// Records from the microphone into `{file}` until interrupted; detected if unset
+    pub record_command: Option<String>,
+    pub image_engine: ImageEngine,
+    /// Base URL of the Stable Diffusion web UI (started with `--api`)
+    pub stable_diffusion_url: String,
+    pub terminal_graphics: TerminalGraphics,
+    /// Write a weekly habits report by itself on Monday morning (`/report` makes one any time)
+    pub weekly_report: bool,
+    /// Say once a day when `/flashcards` are due for review
+    pub flashcard_reminders: bool,
+    /// Point out chatting in the middle of the night
+    pub late_night_nudges: bool,
+    /// Show how long each reply took, its time to first token and tokens per second under it
+    pub show_reply_speed: bool,
+    /// Nudge to stretch after this many minutes at the keyboard without a
+    /// break; 0 turns the nudges off
+    pub break_after_minutes: u64,
+    /// How long `/break snooze` holds off the next nudge
+    pub break_snooze_minutes: u64,
+    /// Length of a `/focus` session started without one
+    pub focus_minutes: u64,
+    /// Greet with what the launch directory's project is and what was
+    /// failing there last time
+    pub project_greeting: bool,
+    /// Directory levels scanned for source files to tell the language
+    pub project_scan_depth: usize,
+    /// Directories that are never scanned or remembered (`~` is expanded)
+    pub project_scan_exclude: Vec<String>,
+    /// Key bindings of the input line
+    pub editing_mode: EditingMode,
+    /// Undoes the last edit of the input line; suspends like Ctrl+Z when there's none
+    pub undo_key: String,
+    /// Makes the last undone edit again
+    pub redo_key: String,
+    /// Starts recording a macro (followed by its letter) and stops it
+    pub macro_record_key: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            command_history_limit: 50,
+            pet_name: String::from("Whiskers"),
+            pet_ascii: String::from(r#"
+  /\___/\
+ (  o o  )
+ (  =^=  )
+  (____)
+"#),
+            pet_ascii_file: None,
+            pet_art: None,
+            pet_species: None,
+            pet_ascii_happy_file: None,
+            pet_ascii_neutral_file: None,
+            pet_ascii_sad_file: None,
+            llm_provider: LLMProvider::OpenAI,
+            persona: None,
+            system_prompt: None,
+            prompt_templates: BTreeMap::new(),
+            few_shot: Vec::new(),
+            language: None,
+            openai_base_url: String::from(llm::OPENAI_BASE_URL),
+            openai_organization: None,
+            openai_project: None,
+            budget_daily_usd: None,
+            budget_monthly_usd: None,
+            model_prices: BTreeMap::new(),
+            ollama_url: String::from("http://localhost:11434"),
+            ollama_model: String::from("llama2"),
+            ollama_keep_alive: None,
+            ollama_num_ctx: None,
+            ollama_num_predict: None,
+            ollama_seed: None,
+            ollama_stop: Vec::new(),
+            atuin_history: false,
+            atuin_db_path: None,
+            merge_history_files: false,
+            history_dedupe: true,
+            history_ignore: ["ls", "ll", "cd", "clear", "pwd", "exit", "history"].map(String::from).to_vec(),
+            history_prompt_commands: 30,
+            proactive_suggestions: true,
+            suggestion_cooldown_minutes: 30,
+            allow_command_execution: true,
+            exec_timeout_secs: 30,
+            exec_working_dir: None,
+            exec_clear_env: true,
+            exec_max_output_bytes: 4096,
+            exec_denylist: Vec::new(),
+            include_workspace_context: true,
+            include_system_context: false,
+            zoxide_context: false,
+            docs_context: true,
+            storage_backend: StorageBackend::Confy,
+            sync_dir: None,
+            shared_state_path: None,
+            chat_retention_messages: None,
+            chat_retention_days: None,
+            backup_count: 7,
+            backup_interval_hours: 24,
+            write_transcripts: false,
+            encrypt_history: false,
+            privacy_filter: true,
+            redaction_patterns: Vec::new(),
+            load_plugins: true,
+            load_scripts: true,
+            desktop_notifications: false,
+            notify_mood_below: 0.3,
+            notify_slow_reply_secs: 20,
+            quiet_hours: None,
+            card_font: None,
+            weather: false,
+            weather_provider: WeatherProvider::OpenMeteo,
+            weather_latitude: None,
+            weather_longitude: None,
+            weather_refresh_minutes: 30,
+            system_monitor: false,
+            system_monitor_interval_secs: 5,
+            battery_awareness: true,
+            connectivity_check_secs: 30,
+            speak_replies: false,
+            tts_engine: TtsEngine::System,
+            tts_command: None,
+            tts_voice: String::from("alloy"),
+            voice_input: false,
+            voice_key: String::from("F9"),
+            stt_engine: SttEngine::WhisperCpp,
+            whisper_command: String::from("whisper-cli"),
+            whisper_model: None,
+            record_command: None,
+            image_engine: ImageEngine::OpenAI,
+            stable_diffusion_url: String::from("http://127.0.0.1:7860"),
+            terminal_graphics: TerminalGraphics::Auto,
+            weekly_report: true,
+            flashcard_reminders: true,
+            late_night_nudges: true,
+            show_reply_speed: true,
+            break_after_minutes: 50,
+            break_snooze_minutes: 10,
+            focus_minutes: 25,
+            project_greeting: true,
+            project_scan_depth: 2,
+            project_scan_exclude: Vec::new(),
+            editing_mode: EditingMode::Emacs,
+            undo_key: String::from("ctrl+z"),
+            redo_key: String::from("ctrl+y"),
+            macro_record_key: String::from("ctrl+q"),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the active profile's config file, writing the defaults if there is none
+    ///
+    /// Failing to write the defaults (e.g. on a read-only filesystem) isn't an
+    /// error, so the pet can be configured through the environment alone.
+    pub fn load() -> Self {
+        Self::load_checked().0
+    }
+
+    /// Like `load`, but also returns everything wrong with the config
+    ///
+    /// If the file can't be read, the defaults (and environment overrides) are
+    /// used instead, but the file itself is left alone.
+    pub fn load_checked() -> (Self, Vec<String>) {
+        let config_path = config_path::get_config_file_path(None);
+        if !config_path.exists() {
+            let toml = toml::to_string(&Config::default()).expect("Failed to serialize config");
+            let _ = config_path::ensure_config_dir().and_then(|_| std::fs::write(&config_path, toml));
+        }
+        match Self::read(&config_path) {
+            Ok(config) => {
+                let problems = config.validate();
+                (config, problems)
+            }
+            Err(e) => {
+                let config = Figment::from(Serialized::defaults(Config::default()))
+                    .merge(Env::prefixed("PAWSHELL_"))
+                    .extract()
+                    .unwrap_or_default();
+                (config, vec![e.to_string()])
+            }
+        }
+    }
+
+    /// Where OpenAI requests go and which account they count against
+    pub fn openai_options(&self) -> OpenAIOptions {
+        OpenAIOptions {
+            base_url: self.openai_base_url.clone(),
+            organization: self.openai_organization.clone(),
+            project: self.openai_project.clone(),
+        }
+    }
+
+    /// The `budget_*_usd` budgets
+    pub fn budget(&self) -> Budget {
+        Budget { daily: self.budget_daily_usd, monthly: self.budget_monthly_usd }
+    }
+
+    /// The `history_*` settings, for the commands given to prompts
+    pub fn history_filter(&self) -> HistoryFilter {
+        HistoryFilter {
+            dedupe: self.history_dedupe,
+            ignore: self.history_ignore.clone(),
+            slots: self.history_prompt_commands,
+        }
+    }
+
+    /// The `ollama_*` generation settings, for the Ollama backend
+    pub fn ollama_options(&self) -> OllamaOptions {
+        OllamaOptions {
+            keep_alive: self.ollama_keep_alive.clone(),
+            num_ctx: self.ollama_num_ctx,
+            num_predict: self.ollama_num_predict,
+            seed: self.ollama_seed,
+            stop: self.ollama_stop.clone(),
+        }
+    }
+
+    /// Checks values that parse but can't work, saying how to fix each
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.pet_name.trim().is_empty() {
+            problems.push("pet_name is empty; give your pet a name".to_string());
+        }
+        if self.persona.as_deref().is_some_and(|persona| persona.trim().is_empty()) {
+            problems.push("persona is empty; describe who the pet should be, or remove it".to_string());
+        }
+        if self.system_prompt.as_deref().is_some_and(|prompt| prompt.trim().is_empty()) {
+            problems.push("system_prompt is empty; remove it to use the built-in prompt".to_string());
+        }
+        problems.extend(templates::check(&self.prompt_templates));
+        problems.extend(few_shot::check(&self.few_shot));
+        if self.language.as_deref().is_some_and(|language| language.trim().is_empty()) {
+            problems.push("language is empty; set it to e.g. \"Spanish\", or remove it for English".to_string());
+        }
+        if self.command_history_limit == 0 {
+            problems.push("command_history_limit is 0, so the pet can't see any commands; try 50".to_string());
+        }
+        if self.history_prompt_commands == 0 {
+            problems.push("history_prompt_commands is 0, so prompts get no commands; try 30".to_string());
+        }
+        if self.history_ignore.iter().any(|command| command.trim().is_empty()) {
+            problems.push("history_ignore has an empty entry; remove it".to_string());
+        }
+        match reqwest::Url::parse(&self.openai_base_url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+            _ => problems.push(format!(
+                "openai_base_url {:?} is not an http(s) URL, e.g. \"{}\"",
+                self.openai_base_url,
+                llm::OPENAI_BASE_URL
+            )),
+        }
+        for (key, value) in [("openai_organization", &self.openai_organization), ("openai_project", &self.openai_project)] {
+            if value.as_deref().is_some_and(|id| id.trim().is_empty()) {
+                problems.push(format!("{} is empty; set it to an ID from your OpenAI settings, or remove it", key));
+            }
+        }
+        match reqwest::Url::parse(&self.ollama_url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+            _ => problems.push(format!(
+                "ollama_url {:?} is not an http(s) URL, e.g. \"http://localhost:11434\"",
+                self.ollama_url
+            )),
+        }
+        if self.llm_provider == LLMProvider::Ollama && self.ollama_model.trim().is_empty() {
+            problems.push("ollama_model is empty; set it to a model from `ollama list`".to_string());
+        }
+        problems.extend(self.ollama_options().check());
+        for (key, budget) in [("budget_daily_usd", self.budget_daily_usd), ("budget_monthly_usd", self.budget_monthly_usd)] {
+            if budget.is_some_and(|budget| budget.is_nan() || budget <= 0.0) {
+                problems.push(format!("{} must be more than 0 dollars; remove it for no budget", key));
+            }
+        }
+        for (model, price) in &self.model_prices {
+            if price.input.is_nan() || price.output.is_nan() || price.input < 0.0 || price.output < 0.0 {
+                problems.push(format!("model_prices.{} has a negative price; give dollars per million tokens", model));
+            }
+        }
+        if self.suggestion_cooldown_minutes < 0 {
+            problems.push("suggestion_cooldown_minutes is negative; use 0 or more".to_string());
+        }
+        if self.exec_timeout_secs == 0 {
+            problems.push("exec_timeout_secs is 0, so every command would be killed at once".to_string());
+        }
+        if self.chat_retention_messages == Some(0) {
+            problems.push("chat_retention_messages is 0, which archives every message; remove it to keep all".to_string());
+        }
+        if self.chat_retention_days.is_some_and(|days| days <= 0) {
+            problems.push("chat_retention_days must be at least 1; remove it to keep messages forever".to_string());
+        }
+        if self.atuin_history {
+            if let Some(path) = self.atuin_db_path.as_deref().filter(|path| !config_path::expand_home(path).exists()) {
+                problems.push(format!("atuin_db_path {} doesn't exist", path));
+            }
+        }
+        if self.sync_dir.is_some() && self.storage_backend != StorageBackend::Synced {
+            problems.push("sync_dir is ignored unless storage_backend = \"Synced\"".to_string());
+        }
+        match (&self.storage_backend, &self.shared_state_path) {
+            (StorageBackend::Shared, None) => problems.push("storage_backend = \"Shared\" needs shared_state_path".to_string()),
+            (StorageBackend::Shared, Some(_)) => {}
+            (_, Some(_)) => problems.push("shared_state_path is ignored unless storage_backend = \"Shared\"".to_string()),
+            (_, None) => {}
+        }
+        if self.voice_input && self.stt_engine == SttEngine::WhisperCpp {
+            match self.whisper_model.as_deref() {
+                None => problems.push("voice_input with stt_engine = \"WhisperCpp\" needs whisper_model".to_string()),
+                Some(model) if !Path::new(model).exists() => problems.push(format!("whisper_model {} doesn't exist", model)),
+                Some(_) => {}
+            }
+        }
+        if self.record_command.as_deref().is_some_and(|command| !command.contains("{file}")) {
+            problems.push("record_command must contain {file}, where the recording goes".to_string());
+        }
+        if self.image_engine == ImageEngine::StableDiffusion {
+            match reqwest::Url::parse(&self.stable_diffusion_url) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+                _ => problems.push(format!(
+                    "stable_diffusion_url {:?} is not an http(s) URL, e.g. \"http://127.0.0.1:7860\"",
+                    self.stable_diffusion_url
+                )),
+            }
+        }
+        for pattern in &self.redaction_patterns {
+            if let Err(e) = regex::Regex::new(pattern) {
+                problems.push(format!("redaction_patterns entry {:?} is not a valid regex: {}", pattern, e));
+            }
+        }
+        for pattern in &self.exec_denylist {
+            if let Err(e) = regex::Regex::new(pattern) {
+                problems.push(format!("exec_denylist entry {:?} is not a valid regex: {}", pattern, e));
+            }
+        }
+        if !(0.0..=1.0).contains(&self.notify_mood_below) {
+            problems.push("notify_mood_below must be between 0.0 and 1.0".to_string());
+        }
+        if let Some(hours) = self.quiet_hours.as_deref().filter(|hours| parse_quiet_hours(hours).is_none()) {
+            problems.push(format!("quiet_hours {:?} isn't a range like \"22:00-08:00\"", hours));
+        }
+        if let Some(path) = self.card_font.as_deref().filter(|path| !Path::new(path).exists()) {
+            problems.push(format!("card_font {} doesn't exist", path));
+        }
+        if self.weather {
+            match (self.weather_latitude, self.weather_longitude) {
+                (None, None) if self.weather_provider == WeatherProvider::OpenMeteo => problems.push(
+                    "weather with the OpenMeteo provider needs weather_latitude and weather_longitude".to_string(),
+                ),
+                (None, None) => {}
+                (Some(latitude), Some(longitude)) => {
+                    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+                        problems.push("weather_latitude must be within ±90 and weather_longitude within ±180".to_string());
+                    }
+                }
+                _ => problems.push("set both weather_latitude and weather_longitude, or neither".to_string()),
+            }
+            if self.weather_refresh_minutes == 0 {
+                problems.push("weather_refresh_minutes must be at least 1".to_string());
+            }
+        }
+        if self.system_monitor && self.system_monitor_interval_secs == 0 {
+            problems.push("system_monitor_interval_secs must be at least 1".to_string());
+        }
+        problems.extend(art::validate(self));
+        problems
+    }
+
+    /// Whether `time` falls within `quiet_hours`, which may span midnight
+    pub fn is_quiet_time(&self, time: NaiveTime) -> bool {
+        let Some((start, end)) = self.quiet_hours.as_deref().and_then(parse_quiet_hours) else {
+            return false;
+        };
+        if start <= end {
+            start <= time && time < end
+        } else {
+            time >= start || time < end
+        }
+    }
+
+    /// Reads a config file with the project's `.pawshell.toml` and then
+    /// `PAWSHELL_*` overrides on top, reporting the key and source of any error
+    ///
+    /// Keys missing from all of them fall back to their defaults, and a missing
+    /// file is treated as empty.
+    pub fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut figment = Figment::from(Serialized::defaults(Config::default())).merge(Toml::file(path));
+        if let Some(project_file) = project::find() {
+            figment = figment.merge(Serialized::defaults(project::read(&project_file)?));
+        }
+        figment
+            .merge(Env::prefixed("PAWSHELL_"))
+            .extract()
+            .map_err(|e| e.to_string().into())
+    }
+
+    /// Sets (or, given `None`, removes) keys in the config file, keeping its comments
+    ///
+    /// A running pet picks the change up like any other edit.
+    pub fn set_keys(values: &[(&str, Option<&str>)]) -> Result<(), Box<dyn Error>> {
+        let path = config_path::get_config_file_path(None);
+        let contents = std::fs::read_to_string(&path).unwrap_or_default();
+        let mut document: toml_edit::DocumentMut =
+            contents.parse().map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+        for (key, value) in values {
+            match value {
+                Some(value) => document[key] = toml_edit::value(*value),
+                None => {
+                    document.remove(key);
+                }
+            }
+        }
+        storage::write_atomic(&path, document.to_string().as_bytes())
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok(())
+    }
+}
+
+/// Parses a range of local times like "22:00-08:00"
+fn parse_quiet_hours(hours: &str) -> Option<(NaiveTime, NaiveTime)> {
+    let (start, end) = hours.split_once('-')?;
+    let parse = |time: &str| NaiveTime::parse_from_str(time.trim(), "%H:%M").ok();
+    Some((parse(start)?, parse(end)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quiet_hours_can_span_midnight() {
+        let at = |time: &str| NaiveTime::parse_from_str(time, "%H:%M").unwrap();
+        let config = Config { quiet_hours: Some("22:00 - 08:00".to_string()), ..Config::default() };
+        assert!(config.is_quiet_time(at("23:30")));
+        assert!(config.is_quiet_time(at("07:59")));
+        assert!(!config.is_quiet_time(at("08:00")));
+        assert!(!config.is_quiet_time(at("12:00")));
+
+        let config = Config { quiet_hours: Some("12:00-13:00".to_string()), ..Config::default() };
+        assert!(config.is_quiet_time(at("12:30")));
+        assert!(!config.is_quiet_time(at("23:30")));
+        assert!(config.validate().is_empty());
+        let config = Config { quiet_hours: Some("after dinner".to_string()), ..Config::default() };
+        assert_eq!(config.validate().len(), 1);
+    }
+}
@@ -0,0 +1,256 @@
+//! Configuration path management for PetCLI
+//!
+//! This module handles the management of configuration file paths and directories,
+//! ensuring proper setup of the application's configuration structure. It provides
+//! functionality for:
+//! - Locating and creating the configuration directory
+//! - Managing configuration file paths
+//! - Ensuring configuration directory exists
+//! - Selecting the active profile
+//! - Locating the data and cache directories
+//!
+//! Only configuration lives in `~/.config/petcli`. The pet's state, chat archive,
+//! backups and other files that change while the pet runs go in the data
+//! directory (`$XDG_DATA_HOME/pawshell`), and anything that can be regenerated
+//! in the cache directory (`$XDG_CACHE_HOME/pawshell`); on macOS and Windows the
+//! platform equivalents are used. Files from older versions that kept everything
+//! in the config directory are moved over on startup.
+//!
+//! Profiles (`--profile work`, `/profile work`) get their own directory under
+//! `profiles/` in each of these, with an independent config, state and history.
+//! Without a profile the top-level directories are used.
+//!
+//! Consider moving this into a broader configuration management module if the
+//! configuration system becomes more complex.
+
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// The active profile; None is the default profile
+static PROFILE: RwLock<Option<String>> = RwLock::new(None);
+
+/// Name shown for the top-level (unnamed) profile
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Checks that a profile name is usable as a directory name
+pub fn validate_profile_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("Invalid profile name \"{}\": use letters, digits, - and _", name));
+    }
+    Ok(())
+}
+
+/// Selects the profile all paths are resolved against
+pub fn set_profile(name: &str) -> Result<(), String> {
+    validate_profile_name(name)?;
+    let profile = (name != DEFAULT_PROFILE).then(|| name.to_string());
+    *PROFILE.write().unwrap_or_else(|e| e.into_inner()) = profile;
+    Ok(())
+}
+
+/// The active profile's name, or None for the default profile
+pub fn profile() -> Option<String> {
+    PROFILE.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Names of all profiles that have been used, the default first
+pub fn list_profiles() -> Vec<String> {
+    let mut profiles: Vec<String> = std::fs::read_dir(base_config_dir().join("profiles"))
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().is_dir())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .collect()
+        })
+        .unwrap_or_default();
+    profiles.sort();
+    profiles.insert(0, DEFAULT_PROFILE.to_string());
+    profiles
+}
+
+/// Returns the configuration directory of the active profile
+pub fn get_config_dir() -> PathBuf {
+    match profile() {
+        Some(name) => base_config_dir().join("profiles").join(name),
+        None => base_config_dir(),
+    }
+}
+
+/// Returns the data directory of the active profile, for state and history
+pub fn get_data_dir() -> PathBuf {
+    let data_dir = dirs::data_dir().expect("Could not find data directory").join("pawshell");
+    with_profile(data_dir)
+}
+
+/// Returns the cache directory of the active profile, for regenerable files
+pub fn get_cache_dir() -> PathBuf {
+    let cache_dir = dirs::cache_dir().expect("Could not find cache directory").join("pawshell");
+    with_profile(cache_dir)
+}
+
+/// Expands a leading `~` in a path from the config to the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    if let Some(rest) = path.strip_prefix("~/").or_else(|| path.strip_prefix("~\\")) {
+        if let Some(home) = dirs::home_dir() {
+            return home.join(rest);
+        }
+    }
+    PathBuf::from(path)
+}
+
+fn with_profile(dir: PathBuf) -> PathBuf {
+    match profile() {
+        Some(name) => dir.join("profiles").join(name),
+        None => dir,
+    }
+}
+
+/// Ensures the data directory exists, creating it if necessary
+pub fn ensure_data_dir() -> std::io::Result<PathBuf> {
+    let data_dir = get_data_dir();
+    std::fs::create_dir_all(&data_dir)?;
+    Ok(data_dir)
+}
+
+/// Returns the path of the pet's state file for the active profile
+pub fn state_file_path() -> PathBuf {
+    get_data_dir().join("state.toml")
+}
+
+/// Moves state written by older versions from the config directory to the data directory
+///
+/// Files already present in the data directory are left alone.
+pub fn migrate_to_data_dir() -> std::io::Result<()> {
+    let config_dir = get_config_dir();
+    let legacy_state = match profile() {
+        Some(_) => config_dir.join("state.toml"),
+        None => confy_state_file().unwrap_or_else(|| config_dir.join("default-config.toml")),
+    };
+    let candidates = [
+        (legacy_state, state_file_path()),
+        (config_dir.join("petcli.db"), get_data_dir().join("petcli.db")),
+        (config_dir.join("chat_archive.jsonl"), get_data_dir().join("chat_archive.jsonl")),
+        (config_dir.join("history.salt"), get_data_dir().join("history.salt")),
+        (config_dir.join("backups"), get_data_dir().join("backups")),
+    ];
+    move_missing(candidates)
+}
+
+/// Moves each `from` to its `to`, unless there's nothing to move or `to` exists
+fn move_missing(candidates: impl IntoIterator<Item = (PathBuf, PathBuf)>) -> std::io::Result<()> {
+    for (from, to) in candidates {
+        if !from.exists() || to.exists() {
+            continue;
+        }
+        if let Some(dir) = to.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        move_path(&from, &to)?;
+    }
+    Ok(())
+}
+
+/// confy's standard location, where the state was kept before profiles and the
+/// data directory
+fn confy_state_file() -> Option<PathBuf> {
+    confy::get_configuration_file_path("petcli", None).ok()
+}
+
+/// Renames a file or flat directory, copying if it is on another filesystem
+fn move_path(from: &Path, to: &Path) -> std::io::Result<()> {
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    if from.is_dir() {
+        std::fs::create_dir_all(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            std::fs::copy(entry.path(), to.join(entry.file_name()))?;
+        }
+        std::fs::remove_dir_all(from)
+    } else {
+        std::fs::copy(from, to)?;
+        std::fs::remove_file(from)
+    }
+}
+
+/// Returns the path to the top-level PetCLI configuration directory
+///
+/// On Unix-like systems this is `~/.config/petcli`. On Windows the roaming
+/// application data folder (`%APPDATA%\petcli`) is used instead, since there is
+/// no `~/.config` convention there.
+#[cfg(not(windows))]
+pub(crate) fn base_config_dir() -> PathBuf {
+    let home = dirs::home_dir().expect("Could not find home directory");
+    home.join(".config").join("petcli")
+}
+
+/// Returns the path to the top-level PetCLI configuration directory
+#[cfg(windows)]
+pub(crate) fn base_config_dir() -> PathBuf {
+    let app_data = dirs::config_dir().expect("Could not find %APPDATA% directory");
+    app_data.join("petcli")
+}
+
+/// Ensures the configuration directory exists, creating it if necessary
+pub fn ensure_config_dir() -> std::io::Result<PathBuf> {
+    let config_dir = get_config_dir();
+    if !config_dir.exists() {
+        std::fs::create_dir_all(&config_dir)?
+    }
+    Ok(config_dir)
+}
+
+/// Returns the path to a configuration file
+///
+/// If a name is provided, returns the path to that specific configuration file.
+/// Otherwise, returns the path to the default config.toml file.
+pub fn get_config_file_path(name: Option<&str>) -> PathBuf {
+    let config_dir = get_config_dir();
+    match name {
+        Some(name) => config_dir.join(name).with_extension("toml"),
+        None => config_dir.join("config.toml"),
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// confy 0.5 wrote the state through `directories` 4, and confy 0.6 goes
+    /// through `directories` 5; the migration needs both to agree on the path
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn legacy_state_is_found_where_confy_wrote_it() {
+        let expected = dirs::config_dir().unwrap().join("petcli").join("default-config.toml");
+        assert_eq!(confy_state_file(), Some(expected));
+    }
+
+    #[test]
+    fn old_files_move_to_the_data_directory_without_overwriting() {
+        let root = std::env::temp_dir().join(format!("pawshell-migrate-test-{}", std::process::id()));
+        let (config, data) = (root.join("config"), root.join("data"));
+        std::fs::create_dir_all(config.join("backups")).unwrap();
+        std::fs::write(config.join("state.toml"), "name = \"Old\"").unwrap();
+        std::fs::write(config.join("petcli.db"), "old database").unwrap();
+        std::fs::write(config.join("backups").join("state-1.toml"), "backup").unwrap();
+        std::fs::create_dir_all(&data).unwrap();
+        std::fs::write(data.join("petcli.db"), "new database").unwrap();
+
+        let pair = |name: &str| (config.join(name), data.join(name));
+        move_missing([pair("state.toml"), pair("petcli.db"), pair("chat_archive.jsonl")]).unwrap();
+        assert!(!config.join("state.toml").exists());
+        assert_eq!(std::fs::read_to_string(data.join("state.toml")).unwrap(), "name = \"Old\"");
+        // What's already in the data directory wins, and the old copy stays put
+        assert_eq!(std::fs::read_to_string(data.join("petcli.db")).unwrap(), "new database");
+        assert!(config.join("petcli.db").exists());
+        assert!(!data.join("chat_archive.jsonl").exists());
+
+        // Renaming into a directory that doesn't exist fails, so this copies
+        let nested = root.join("elsewhere").join("backups");
+        move_path(&config.join("backups"), &nested).unwrap();
+        assert!(!config.join("backups").exists());
+        assert_eq!(std::fs::read_to_string(nested.join("state-1.toml")).unwrap(), "backup");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,38 @@
+//! Backend reachability
+//!
+//! The TUI probes the configured backend every `connectivity_check_secs`.
+//! While it can't be reached, chat messages get the pet's own offline replies
+//! right away instead of each one waiting for a request to time out, and the
+//! title says so. The first probe that gets through switches back.
+
+use std::time::Duration;
+
+use crate::config::{Config, LLMProvider};
+
+/// How long a probe waits; a backend this slow to answer anything counts as offline
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The backend's base URL, which probes ask for
+pub fn endpoint(config: &Config) -> String {
+    match config.llm_provider {
+        LLMProvider::OpenAI => config.openai_base_url.clone(),
+        LLMProvider::Ollama => config.ollama_url.clone(),
+    }
+}
+
+/// Whether the backend at `url` answers at all
+///
+/// Any HTTP response counts, even an error status: only the network matters
+/// here, and a rejected key or missing model is reported when chatting.
+pub async fn probe(url: &str) -> bool {
+    let Ok(client) = reqwest::Client::builder().timeout(PROBE_TIMEOUT).build() else {
+        return true;
+    };
+    match client.get(url).send().await {
+        Ok(_) => true,
+        Err(e) => {
+            tracing::debug!("backend unreachable: {}", e);
+            false
+        }
+    }
+}
@@ -0,0 +1,327 @@
+//! Crontab lines and systemd timers for `/cron`
+//!
+//! The backend writes the line or unit from a description, but its schedule
+//! is checked here before it's shown: crontab's five fields (with names,
+//! ranges, steps and macros like `@daily`) and systemd's `OnCalendar=`
+//! expressions (`Mon..Fri *-*-* 09:00`, `weekly`, ...) are parsed into the
+//! same `Schedule`, and the next few times it fires are worked out from it.
+//! A schedule that doesn't parse or never fires is pointed out instead.
+//!
+//! Times are to the minute; systemd's seconds are accepted but not used.
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+
+use crate::templates::Prompt;
+
+/// How far ahead firing times are looked for, enough for February 29th
+const SEARCH_DAYS: usize = 366 * 5;
+/// Firing times shown
+const NEXT_RUNS: usize = 3;
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+const CRONTAB_TEMPLATE: &str = "Write a crontab line for this: {{ description }}. Use the five standard \
+fields (minute, hour, day of month, month, day of week) or a macro like @daily, and absolute paths or \
+~ for scripts. Reply with the line in a ```crontab block, then one sentence on when it runs.";
+
+const TIMER_TEMPLATE: &str = "Write a systemd timer for this: {{ description }}. Reply with the .timer unit \
+using OnCalendar= (with Persistent=true if missed runs should catch up) in one ```ini block and the \
+matching .service unit in a second ```ini block, then one sentence on when it runs and how to enable \
+it with systemctl --user.";
+
+/// Which values of a schedule field mean what
+struct Field {
+    name: &'static str,
+    min: u32,
+    max: u32,
+    names: &'static [&'static str],
+    /// The value of the first name
+    first_name: u32,
+}
+
+const MINUTE: Field = Field { name: "minute", min: 0, max: 59, names: &[], first_name: 0 };
+const HOUR: Field = Field { name: "hour", min: 0, max: 23, names: &[], first_name: 0 };
+const DAY: Field = Field { name: "day of month", min: 1, max: 31, names: &[], first_name: 0 };
+const MONTH: Field = Field { name: "month", min: 1, max: 12, names: &MONTHS, first_name: 1 };
+/// 7 is Sunday too in crontabs
+const WEEKDAY: Field = Field { name: "day of week", min: 0, max: 7, names: &WEEKDAYS, first_name: 0 };
+
+/// When a schedule fires, as a bit per allowed value of each field
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Years it's limited to, for systemd dates with a year
+    years: Option<Vec<i32>>,
+    /// Crontabs fire when either the day of month or the weekday matches if
+    /// both are restricted; systemd needs both
+    either_day: bool,
+}
+
+impl Schedule {
+    /// Parses a crontab schedule: five fields or a macro like `@daily`
+    pub fn parse_crontab(expression: &str) -> Result<Self, String> {
+        let expression = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            macro_name if macro_name.starts_with('@') => return Err(format!("{} isn't a schedule I know", macro_name)),
+            fields => fields,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("a schedule has 5 fields, this one has {}", fields.len()));
+        };
+        Ok(Self {
+            minutes: parse_field(minute, &MINUTE, "-")?,
+            hours: parse_field(hour, &HOUR, "-")?,
+            days: parse_field(day, &DAY, "-")?,
+            months: parse_field(month, &MONTH, "-")?,
+            weekdays: fold_sunday(parse_field(weekday, &WEEKDAY, "-")?),
+            years: None,
+            either_day: !day.starts_with('*') && !weekday.starts_with('*'),
+        })
+    }
+
+    /// Parses a systemd `OnCalendar=` expression, like `Mon..Fri *-*-* 09:00`
+    pub fn parse_on_calendar(expression: &str) -> Result<Self, String> {
+        let expression = match expression.trim().to_lowercase().as_str() {
+            "minutely" => "*-*-* *:*:00".to_string(),
+            "hourly" => "*-*-* *:00:00".to_string(),
+            "daily" => "*-*-* 00:00:00".to_string(),
+            "weekly" => "Mon *-*-* 00:00:00".to_string(),
+            "monthly" => "*-*-01 00:00:00".to_string(),
+            "yearly" | "annually" => "*-01-01 00:00:00".to_string(),
+            "quarterly" => "*-01,04,07,10-01 00:00:00".to_string(),
+            "semiannually" => "*-01,07-01 00:00:00".to_string(),
+            _ => expression.trim().to_string(),
+        };
+        let (mut weekdays, mut date, mut time) = (None, None, None);
+        for part in expression.split_whitespace() {
+            let slot = if part.contains(':') {
+                &mut time
+            } else if part.contains('-') && part.starts_with(|c: char| c.is_ascii_digit() || c == '*') {
+                &mut date
+            } else if part.starts_with(char::is_alphabetic) {
+                &mut weekdays
+            } else {
+                return Err(format!("I can't read \"{}\"", part));
+            };
+            if slot.replace(part).is_some() {
+                return Err(format!("\"{}\" is the second of its kind", part));
+            }
+        }
+        if expression.contains('~') {
+            return Err("counting days from the end of the month (~) isn't something I can check".to_string());
+        }
+
+        let date: Vec<&str> = date.unwrap_or("*-*-*").split('-').collect();
+        let (years, month, day) = match date[..] {
+            [year, month, day] => (parse_years(year)?, month, day),
+            [month, day] => (None, month, day),
+            _ => return Err(format!("\"{}\" isn't a date like *-*-01", date.join("-"))),
+        };
+        let time: Vec<&str> = time.unwrap_or("00:00:00").split(':').collect();
+        let (hour, minute) = match time[..] {
+            [hour, minute] | [hour, minute, _] => (hour, minute),
+            _ => return Err(format!("\"{}\" isn't a time like 09:30", time.join(":"))),
+        };
+        Ok(Self {
+            minutes: parse_field(minute, &MINUTE, "..")?,
+            hours: parse_field(hour, &HOUR, "..")?,
+            days: parse_field(day, &DAY, "..")?,
+            months: parse_field(month, &MONTH, "..")?,
+            weekdays: fold_sunday(parse_field(weekdays.unwrap_or("*"), &WEEKDAY, "..")?),
+            years,
+            either_day: false,
+        })
+    }
+
+    fn fires_on(&self, date: NaiveDate) -> bool {
+        let day = has(self.days, date.day());
+        let weekday = has(self.weekdays, date.weekday().num_days_from_sunday());
+        has(self.months, date.month())
+            && self.years.as_ref().is_none_or(|years| years.contains(&date.year()))
+            && if self.either_day { day || weekday } else { day && weekday }
+    }
+
+    /// The next `count` times it fires after `after`, within the next few years
+    pub fn next_runs(&self, after: NaiveDateTime, count: usize) -> Vec<NaiveDateTime> {
+        let mut runs = Vec::new();
+        for date in after.date().iter_days().take(SEARCH_DAYS).filter(|&date| self.fires_on(date)) {
+            for hour in (0..24).filter(|&hour| has(self.hours, hour)) {
+                for minute in (0..60).filter(|&minute| has(self.minutes, minute)) {
+                    let Some(time) = date.and_hms_opt(hour, minute, 0) else {
+                        continue;
+                    };
+                    if time > after {
+                        runs.push(time);
+                        if runs.len() == count {
+                            return runs;
+                        }
+                    }
+                }
+            }
+        }
+        runs
+    }
+
+    /// When it fires next, or why it never does
+    pub fn describe(&self, now: NaiveDateTime) -> String {
+        let runs = self.next_runs(now, NEXT_RUNS);
+        if runs.is_empty() {
+            return "⚠ It never fires in the next five years; is that day in that month?".to_string();
+        }
+        let times: Vec<String> = runs
+            .iter()
+            .map(|run| run.format(if run.year() == now.year() { "%a %b %-d %H:%M" } else { "%a %b %-d %Y %H:%M" }).to_string())
+            .collect();
+        format!("Fires next on {}.", times.join(", "))
+    }
+}
+
+fn has(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+/// Makes weekday 7 the Sunday it stands for
+fn fold_sunday(bits: u64) -> u64 {
+    (bits | (bits >> 7)) & 0x7f
+}
+
+/// One field: `*`, values, names, ranges and steps separated by commas
+fn parse_field(text: &str, field: &Field, range_separator: &str) -> Result<u64, String> {
+    let value = |text: &str| -> Result<u32, String> {
+        let name = text.to_lowercase();
+        let named = name.get(..3).and_then(|prefix| field.names.iter().position(|known| *known == prefix));
+        let value = match named {
+            Some(i) => i as u32 + field.first_name,
+            None => text.parse().map_err(|_| format!("\"{}\" isn't a {}", text, field.name))?,
+        };
+        if value < field.min || value > field.max {
+            return Err(format!("{} {} is out of range ({}-{})", field.name, value, field.min, field.max));
+        }
+        Ok(value)
+    };
+    let mut bits = 0;
+    for item in text.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<usize>().ok().filter(|&step| step > 0).ok_or(format!("\"{}\" isn't a step", step))?),
+            None => (item, 1),
+        };
+        let (low, high) = if range == "*" {
+            (field.min, field.max)
+        } else if let Some((low, high)) = range.split_once(range_separator) {
+            (value(low)?, value(high)?)
+        } else {
+            let low = value(range)?;
+            (low, if item.contains('/') { field.max } else { low })
+        };
+        if low > high {
+            return Err(format!("the {} range {} runs backwards", field.name, range));
+        }
+        for value in (low..=high).step_by(step) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+fn parse_years(text: &str) -> Result<Option<Vec<i32>>, String> {
+    if text == "*" {
+        return Ok(None);
+    }
+    let mut years = Vec::new();
+    for item in text.split(',') {
+        let year = |text: &str| text.parse::<i32>().map_err(|_| format!("\"{}\" isn't a year", text));
+        match item.split_once("..") {
+            Some((first, last)) => years.extend(year(first)?..=year(last)?),
+            None => years.push(year(item)?),
+        }
+    }
+    Ok(Some(years))
+}
+
+/// Asks for a crontab line, or a systemd timer
+pub fn prompt(description: &str, timer: bool) -> Prompt {
+    let prompt = if timer { Prompt::new("timer", TIMER_TEMPLATE) } else { Prompt::new("cron", CRONTAB_TEMPLATE) };
+    prompt.var("description", description)
+}
+
+/// Splits a crontab line into its schedule and command
+pub fn split_crontab_line(line: &str) -> Option<(String, String)> {
+    let line = line.trim();
+    if line.starts_with('@') {
+        let (schedule, command) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        return Some((schedule.to_string(), command.trim().to_string()));
+    }
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    (fields.len() >= 5).then(|| (fields[..5].join(" "), fields[5..].join(" ")))
+}
+
+/// Checks the schedules in a reply: the crontab line, or every `OnCalendar=`
+/// of a timer
+pub fn check_reply(reply: &str, timer: bool, now: NaiveDateTime) -> String {
+    if timer {
+        let calendars: Vec<&str> = reply.lines().filter_map(|line| line.trim().strip_prefix("OnCalendar=")).collect();
+        if calendars.is_empty() {
+            return "There's no OnCalendar= in it to check; timers that count from boot or the last run don't have one.".to_string();
+        }
+        let checked: Vec<String> = calendars
+            .iter()
+            .map(|calendar| match Schedule::parse_on_calendar(calendar) {
+                Ok(schedule) => format!("OnCalendar={}: {}", calendar, schedule.describe(now)),
+                Err(e) => format!("⚠ OnCalendar={} doesn't check out: {}.", calendar, e),
+            })
+            .collect();
+        return checked.join("\n");
+    }
+    let line = reply
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with("```"))
+        .find(|line| split_crontab_line(line).is_some_and(|(schedule, _)| Schedule::parse_crontab(&schedule).is_ok()))
+        .or_else(|| reply.lines().map(str::trim).find(|line| line.starts_with(|c: char| c.is_ascii_digit() || c == '*' || c == '@')));
+    let Some((schedule, _)) = line.and_then(split_crontab_line) else {
+        return "⚠ I couldn't find a crontab line in that.".to_string();
+    };
+    match Schedule::parse_crontab(&schedule) {
+        Ok(parsed) => format!("`{}`: {}", schedule, parsed.describe(now)),
+        Err(e) => format!("⚠ The schedule `{}` doesn't check out: {}.", schedule, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 3, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn crontab_schedules_fire_when_expected() {
+        // March 1st 2024 was a Friday
+        let weekdays = Schedule::parse_crontab("*/30 9-10 * * mon-fri").unwrap();
+        assert_eq!(weekdays.next_runs(at(1, 10, 15), 3), [at(1, 10, 30), at(4, 9, 0), at(4, 9, 30)]);
+        let either = Schedule::parse_crontab("0 0 13 * 5").unwrap();
+        assert_eq!(either.next_runs(at(2, 0, 0), 2), [at(8, 0, 0), at(13, 0, 0)]);
+        assert_eq!(Schedule::parse_crontab("@weekly"), Schedule::parse_crontab("0 0 * * 7"));
+        assert_eq!(Schedule::parse_crontab("61 * * * *").unwrap_err(), "minute 61 is out of range (0-59)");
+        assert!(Schedule::parse_crontab("0 0 30 feb *").unwrap().next_runs(at(1, 0, 0), 1).is_empty());
+    }
+
+    #[test]
+    fn systemd_calendars_parse_into_the_same_schedules() {
+        assert_eq!(Schedule::parse_on_calendar("Mon..Fri *-*-* 09:00"), Schedule::parse_crontab("0 9 * * 1-5").map(|s| Schedule { either_day: false, ..s }));
+        assert_eq!(Schedule::parse_on_calendar("weekly").unwrap().next_runs(at(1, 12, 0), 1), [at(4, 0, 0)]);
+        assert_eq!(Schedule::parse_on_calendar("2025-*-01 06:30").unwrap().next_runs(at(1, 12, 0), 1)[0].year(), 2025);
+        assert!(Schedule::parse_on_calendar("every day").is_err());
+        assert_eq!(check_reply("```crontab\nMAILTO=me\n15 3 * * * ~/backup.sh\n```\nRuns nightly.", false, at(1, 12, 0)), "`15 3 * * *`: Fires next on Sat Mar 2 03:15, Sun Mar 3 03:15, Mon Mar 4 03:15.");
+    }
+}
@@ -0,0 +1,185 @@
+//! Encryption of chat history at rest for PetCLI
+//!
+//! With `encrypt_history = true`, every chat message is encrypted with
+//! ChaCha20-Poly1305 before it is written to the state file or database, and so
+//! are archived exchanges. The key comes from one of:
+//! - the `PAWSHELL_PASSPHRASE` environment variable, stretched with Argon2 and a
+//!   random salt stored in the data directory
+//! - otherwise a random key kept in the OS keyring (Secret Service, macOS
+//!   Keychain or Windows Credential Manager), created on first use
+//!
+//! Encrypted values are stored as `enc:v1:<base64 nonce + ciphertext>`, so
+//! plaintext messages saved before encryption was enabled can still be read.
+
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::io::{self, Write};
+use std::path::Path;
+use crate::config_path;
+use crate::error::CryptoError;
+
+const PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+const PASSPHRASE_VAR: &str = "PAWSHELL_PASSPHRASE";
+const KEYRING_SERVICE: &str = "pawshell";
+const KEYRING_USER: &str = "chat-history-key";
+
+/// A random URL-safe secret, e.g. for authenticating API clients
+pub fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Encrypts and decrypts persisted chat messages
+pub struct Cipher {
+    cipher: ChaCha20Poly1305,
+}
+
+impl Cipher {
+    /// Loads the key from the passphrase variable or the OS keyring
+    pub fn unlock() -> Result<Self, CryptoError> {
+        let key = match std::env::var(PASSPHRASE_VAR) {
+            Ok(passphrase) => key_from_passphrase(&passphrase)?,
+            Err(_) => key_from_keyring()?,
+        };
+        Ok(Self::with_key(&key))
+    }
+
+    pub(crate) fn with_key(key: &Key) -> Self {
+        Self { cipher: ChaCha20Poly1305::new(key) }
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> String {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .expect("ChaCha20-Poly1305 encryption cannot fail for in-memory data");
+        let mut payload = nonce.to_vec();
+        payload.extend(ciphertext);
+        format!("{}{}", PREFIX, BASE64.encode(payload))
+    }
+
+    /// Decrypts a value, passing through values that were never encrypted
+    pub fn decrypt(&self, text: &str) -> Result<String, CryptoError> {
+        let Some(encoded) = text.strip_prefix(PREFIX) else {
+            return Ok(text.to_string());
+        };
+        let payload = BASE64.decode(encoded).map_err(|e| CryptoError::Corrupted(e.to_string()))?;
+        if payload.len() < NONCE_LEN {
+            return Err(CryptoError::Corrupted("too short".to_string()));
+        }
+        let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| CryptoError::WrongKey)?;
+        String::from_utf8(plaintext).map_err(|e| CryptoError::Corrupted(e.to_string()))
+    }
+}
+
+fn key_from_passphrase(passphrase: &str) -> Result<Key, CryptoError> {
+    let data_dir = config_path::ensure_data_dir()
+        .map_err(|source| CryptoError::File { path: config_path::get_data_dir(), source })?;
+    let salt = load_or_create_salt(&data_dir.join("history.salt"))?;
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+        .map_err(|e| CryptoError::Passphrase(e.to_string()))?;
+    Ok(key)
+}
+
+/// Reads the passphrase salt, creating it the first time
+///
+/// Only a missing salt is replaced: a new salt would lock away everything
+/// encrypted with the one that merely couldn't be read.
+fn load_or_create_salt(path: &Path) -> Result<Vec<u8>, CryptoError> {
+    let file_error = |source| CryptoError::File { path: path.to_path_buf(), source };
+    let encoded = match std::fs::read_to_string(path) {
+        Ok(encoded) => encoded,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let mut salt = vec![0u8; 16];
+            OsRng.fill_bytes(&mut salt);
+            match std::fs::OpenOptions::new().write(true).create_new(true).open(path) {
+                Ok(mut file) => {
+                    file.write_all(BASE64.encode(&salt).as_bytes()).map_err(file_error)?;
+                    return Ok(salt);
+                }
+                // Another pet starting at the same time created it first
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => std::fs::read_to_string(path).map_err(file_error)?,
+                Err(e) => return Err(file_error(e)),
+            }
+        }
+        Err(e) => return Err(file_error(e)),
+    };
+    BASE64
+        .decode(encoded.trim())
+        .map_err(|e| CryptoError::InvalidSalt { path: path.to_path_buf(), message: e.to_string() })
+}
+
+fn key_from_keyring() -> Result<Key, CryptoError> {
+    let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)?;
+    match entry.get_password() {
+        Ok(encoded) => {
+            let bytes = BASE64.decode(encoded.trim()).map_err(|e| CryptoError::InvalidKey(e.to_string()))?;
+            if bytes.len() != 32 {
+                return Err(CryptoError::InvalidKey("wrong length".to_string()));
+            }
+            Ok(*Key::from_slice(&bytes))
+        }
+        Err(keyring::Error::NoEntry) => {
+            let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+            entry.set_password(&BASE64.encode(key))?;
+            Ok(key)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_round_trip_and_need_the_right_key() {
+        let cipher = Cipher::with_key(&Key::from([7; 32]));
+        let encrypted = cipher.encrypt("git push --force? 🙀");
+        assert!(encrypted.starts_with(PREFIX));
+        assert!(!encrypted.contains("push"));
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), "git push --force? 🙀");
+        // A fresh nonce every time
+        assert_ne!(cipher.encrypt("same"), cipher.encrypt("same"));
+        // Values from before encryption was enabled pass through
+        assert_eq!(cipher.decrypt("plain old message").unwrap(), "plain old message");
+
+        let other = Cipher::with_key(&Key::from([8; 32]));
+        assert!(matches!(other.decrypt(&encrypted), Err(CryptoError::WrongKey)));
+        assert!(matches!(cipher.decrypt("enc:v1:not base64!"), Err(CryptoError::Corrupted(_))));
+        assert!(matches!(cipher.decrypt("enc:v1:AAAA"), Err(CryptoError::Corrupted(_))));
+    }
+
+    #[test]
+    fn salt_is_only_created_when_missing() {
+        let dir = std::env::temp_dir().join(format!("pawshell-salt-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("history.salt");
+        let _ = std::fs::remove_file(&path);
+
+        let salt = load_or_create_salt(&path).unwrap();
+        assert_eq!(salt.len(), 16);
+        assert_eq!(load_or_create_salt(&path).unwrap(), salt);
+
+        // A salt that can't be read is an error, not a reason to make a new one
+        let unreadable = dir.join("unreadable.salt");
+        std::fs::create_dir_all(&unreadable).unwrap();
+        assert!(matches!(load_or_create_salt(&unreadable), Err(CryptoError::File { .. })));
+        assert!(unreadable.is_dir());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,173 @@
+//! Voice input: recording a question and transcribing it
+//!
+//! With `voice_input = true`, pressing `voice_key` starts recording from the
+//! microphone and pressing it again stops; the transcript lands in the input
+//! line to be edited or sent. (Terminals don't report key releases, so
+//! push-to-talk is press to start, press to stop.)
+//!
+//! Audio is recorded as 16 kHz mono WAV by `record_command` if set, or else
+//! the first of sox's `rec`, `arecord`, `pw-record` and `parecord` that is
+//! installed. It is transcribed locally by whisper.cpp (`whisper_command`
+//! with the `whisper_model` ggml file) or by OpenAI's transcription API.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use crate::config::{Config, SttEngine};
+use crate::config_path;
+use crate::environment::is_installed;
+use crate::llm::OpenAIOptions;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Where the recording goes
+pub fn recording_path() -> PathBuf {
+    config_path::get_cache_dir().join("dictation.wav")
+}
+
+/// The command that records from the microphone into `path` until it's interrupted
+pub fn recorder(config: &Config, path: &Path) -> Option<Command> {
+    let file = path.to_string_lossy();
+    if let Some(command_line) = &config.record_command {
+        let command_line = command_line.replace("{file}", &quote(&file));
+        #[cfg(windows)]
+        let mut command = {
+            let mut command = Command::new("cmd");
+            command.arg("/C");
+            command
+        };
+        #[cfg(not(windows))]
+        let mut command = {
+            let mut command = Command::new("sh");
+            command.arg("-c");
+            command
+        };
+        command.arg(command_line);
+        return Some(command);
+    }
+    let candidates: [(&str, Vec<&str>); 4] = [
+        ("rec", vec!["-q", "-c", "1", "-r", "16000", "-b", "16", &file]),
+        ("arecord", vec!["-q", "-f", "S16_LE", "-c", "1", "-r", "16000", &file]),
+        ("pw-record", vec!["--rate", "16000", "--channels", "1", &file]),
+        ("parecord", vec!["--rate=16000", "--channels=1", "--file-format=wav", &file]),
+    ];
+    let (program, args) = candidates.into_iter().find(|(program, _)| is_installed(program))?;
+    let mut command = Command::new(program);
+    command.args(args);
+    Some(command)
+}
+
+/// Quotes a path for the shell that runs `record_command`
+fn quote(path: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", path)
+    } else {
+        format!("'{}'", path.replace('\'', r"'\''"))
+    }
+}
+
+/// How recordings are turned into text, as configured
+#[derive(Clone)]
+pub struct Transcriber {
+    engine: SttEngine,
+    whisper_command: String,
+    whisper_model: Option<String>,
+    openai: OpenAIOptions,
+}
+
+impl Transcriber {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            engine: config.stt_engine,
+            whisper_command: config.whisper_command.clone(),
+            whisper_model: config.whisper_model.clone(),
+            openai: config.openai_options(),
+        }
+    }
+
+    /// What was said in the recording at `path`
+    pub async fn transcribe(&self, path: &Path) -> Result<String, String> {
+        let text = match self.engine {
+            SttEngine::WhisperCpp => {
+                let model = self.whisper_model.as_deref().ok_or("set whisper_model to a whisper.cpp ggml model file")?;
+                let output = tokio::process::Command::new(&self.whisper_command)
+                    .args(["-m", model, "-f"])
+                    .arg(path)
+                    .args(["-nt", "-np"])
+                    .stdin(Stdio::null())
+                    .output()
+                    .await
+                    .map_err(|e| format!("couldn't run {}: {}", self.whisper_command, e))?;
+                if !output.status.success() {
+                    return Err(format!(
+                        "{} failed: {}",
+                        self.whisper_command,
+                        String::from_utf8_lossy(&output.stderr).lines().last().unwrap_or_default()
+                    ));
+                }
+                String::from_utf8_lossy(&output.stdout).into_owned()
+            }
+            SttEngine::OpenAI => openai_transcription(&self.openai, path).await?,
+        };
+        Ok(clean(&text))
+    }
+}
+
+async fn openai_transcription(openai: &OpenAIOptions, path: &Path) -> Result<String, String> {
+    let api_key = std::env::var("OPENAI_API_KEY").map_err(|_| "OPENAI_API_KEY is not set")?;
+    let audio = std::fs::read(path).map_err(|e| format!("couldn't read the recording: {}", e))?;
+    let boundary = format!("pawshell-{:016x}", rand::random::<u64>());
+    let mut body = Vec::new();
+    body.extend_from_slice(
+        format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\nwhisper-1\r\n\
+             --{b}\r\nContent-Disposition: form-data; name=\"response_format\"\r\n\r\ntext\r\n\
+             --{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"dictation.wav\"\r\nContent-Type: audio/wav\r\n\r\n",
+            b = boundary
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(&audio);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+    let client = reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build().map_err(|e| e.to_string())?;
+    let response = openai
+        .authorize(client.post(openai.url("audio/transcriptions")), &api_key)
+        .header(reqwest::header::CONTENT_TYPE, format!("multipart/form-data; boundary={}", boundary))
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| format!("couldn't reach OpenAI: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("OpenAI answered {}", response.status()));
+    }
+    response.text().await.map_err(|e| e.to_string())
+}
+
+/// One line of text, without whisper's markers for silence and noise like
+/// `[BLANK_AUDIO]` or `(wind blowing)`
+fn clean(transcript: &str) -> String {
+    let mut text = String::new();
+    let mut depth = 0;
+    for c in transcript.chars() {
+        match c {
+            '[' | '(' => depth += 1,
+            ']' | ')' if depth > 0 => depth -= 1,
+            _ if depth == 0 => text.push(c),
+            _ => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transcripts_lose_noise_markers_and_line_breaks() {
+        assert_eq!(clean("\n How do I list\n open ports? [BLANK_AUDIO]\n"), "How do I list open ports?");
+        assert_eq!(clean(" (keyboard clicking) "), "");
+    }
+}
@@ -0,0 +1,129 @@
+//! Improvement suggestions for shell and editor dotfiles, for `/dotfiles`
+//!
+//! The files are only read after the user agreed to it, and always go
+//! through the redactor before they're part of a prompt, whatever the
+//! backend. The model answers with suggestions as small diffs. The dotfile
+//! itself is never edited: `/dotfiles save` writes a copy next to it, with the
+//! suggestions as comments above the original contents, to merge by hand.
+
+use chrono::NaiveDate;
+use std::path::{Path, PathBuf};
+
+use crate::templates::Prompt;
+
+/// Dotfiles looked for, relative to the home directory
+const KNOWN: &[&str] = &[".zshrc", ".bashrc", ".bash_profile", ".profile", ".vimrc", ".config/nvim/init.vim"];
+/// Larger files are probably generated and left out
+const MAX_FILE_BYTES: u64 = 64 * 1024;
+/// Appended to the name of an annotated copy
+const COPY_SUFFIX: &str = ".pawshell";
+
+const TEMPLATE: &str = "Here is my {{ name }}. Suggest concrete improvements: startup time, outdated or \
+duplicated settings, safer defaults, and useful options I'm missing. Keep it to the 3-6 changes that \
+matter most. For each, write one sentence on why, then the change as a unified diff in a ```diff \
+block with a few lines of context. Values shown as [REDACTED:...] were removed on purpose; leave \
+them alone.\n\n{{ name }}:\n{{ text }}";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dotfile {
+    /// How the file is referred to, e.g. `~/.zshrc`
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl Dotfile {
+    pub fn read(&self) -> Result<String, String> {
+        let size = std::fs::metadata(&self.path).map_err(|e| format!("Couldn't read {}: {}", self.name, e))?.len();
+        if size > MAX_FILE_BYTES {
+            return Err(format!("{} is {} KB; I only look at files up to {} KB.", self.name, size / 1024, MAX_FILE_BYTES / 1024));
+        }
+        let bytes = std::fs::read(&self.path).map_err(|e| format!("Couldn't read {}: {}", self.name, e))?;
+        String::from_utf8(bytes).map_err(|_| format!("{} isn't a text file.", self.name))
+    }
+
+    /// Where the annotated copy goes, next to the file
+    pub fn copy_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(COPY_SUFFIX);
+        PathBuf::from(path)
+    }
+
+    /// The line comment of the file's language
+    fn comment(&self) -> &'static str {
+        if self.name.ends_with("vimrc") || self.name.ends_with(".vim") {
+            "\""
+        } else {
+            "#"
+        }
+    }
+}
+
+/// The known dotfiles that exist in `home`
+pub fn find(home: &Path) -> Vec<Dotfile> {
+    KNOWN
+        .iter()
+        .map(|name| Dotfile { name: format!("~/{}", name), path: home.join(name) })
+        .filter(|file| file.path.is_file())
+        .collect()
+}
+
+/// Asks for suggestions on a dotfile's (redacted) contents
+pub fn prompt(file: &Dotfile, text: &str) -> Prompt {
+    Prompt::new("dotfiles", TEMPLATE).var("name", &file.name).var("text", text)
+}
+
+/// Suggestions the backend made for a dotfile, with the contents they're about
+pub struct Advice {
+    pub file: Dotfile,
+    /// The file as it was read, before redaction
+    pub original: String,
+    pub suggestions: String,
+}
+
+impl Advice {
+    /// The original contents with the suggestions commented out above them
+    pub fn annotated(&self, today: NaiveDate) -> String {
+        let comment = self.file.comment();
+        let mut text = format!(
+            "{c} Suggestions for {} from pawshell, {}. They're commented out;\n{c} merge the ones you like into {} by hand.\n{c}\n",
+            self.file.name,
+            today,
+            self.file.name,
+            c = comment
+        );
+        for line in self.suggestions.trim().lines().filter(|line| !line.starts_with("```")) {
+            text.push_str(format!("{} {}", comment, line).trim_end());
+            text.push('\n');
+        }
+        text.push_str(&format!("{c}\n{c} Original {} below.\n\n{}", self.file.name, self.original, c = comment));
+        text
+    }
+
+    /// Writes the annotated copy; returns where it went
+    pub fn save(&self, today: NaiveDate) -> Result<PathBuf, String> {
+        let path = self.file.copy_path();
+        std::fs::write(&path, self.annotated(today)).map_err(|e| format!("Couldn't write {}: {}", path.display(), e))?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn annotated_copies_comment_out_the_suggestions() {
+        let advice = Advice {
+            file: Dotfile { name: "~/.vimrc".to_string(), path: PathBuf::from("/home/me/.vimrc") },
+            original: "set nocompatible\n".to_string(),
+            suggestions: "Search as you type.\n```diff\n set nocompatible\n+set incsearch\n```".to_string(),
+        };
+        let today = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        assert_eq!(
+            advice.annotated(today),
+            "\" Suggestions for ~/.vimrc from pawshell, 2024-03-04. They're commented out;\n\" merge the ones you like into ~/.vimrc by hand.\n\"\n\
+            \" Search as you type.\n\"  set nocompatible\n\" +set incsearch\n\"\n\" Original ~/.vimrc below.\n\nset nocompatible\n"
+        );
+        assert_eq!(advice.file.copy_path(), PathBuf::from("/home/me/.vimrc.pawshell"));
+    }
+}
@@ -0,0 +1,116 @@
+//! System environment detection for PetCLI
+//!
+//! Describes the machine the pet lives on (OS and distro, shell, terminal) and
+//! which popular command-line tools are installed, so suggestions match the
+//! user's actual environment instead of recommending tools they don't have.
+//!
+//! Detection runs once at startup and only looks at environment variables,
+//! `/etc/os-release` and the directories on `PATH`.
+
+use std::path::Path;
+
+/// Tools worth knowing about when suggesting commands, with what they replace
+const KNOWN_TOOLS: &[(&str, &str)] = &[
+    ("eza", "ls"),
+    ("exa", "ls"),
+    ("bat", "cat"),
+    ("rg", "grep"),
+    ("fd", "find"),
+    ("fzf", "fuzzy finder"),
+    ("zoxide", "cd"),
+    ("jq", "JSON processor"),
+    ("delta", "git diff pager"),
+    ("tldr", "man pages"),
+    ("btop", "top"),
+    ("htop", "top"),
+    ("dust", "du"),
+    ("nvim", "editor"),
+    ("vim", "editor"),
+    ("tmux", "terminal multiplexer"),
+    ("git", "version control"),
+    ("docker", "containers"),
+];
+
+/// The user's operating system, shell and installed tools
+pub struct SystemEnvironment {
+    pub os: String,
+    pub distro: Option<String>,
+    pub shell: Option<String>,
+    pub term: Option<String>,
+    pub installed_tools: Vec<&'static str>,
+    pub missing_tools: Vec<&'static str>,
+}
+
+impl SystemEnvironment {
+    pub fn detect() -> Self {
+        let (installed_tools, missing_tools) = KNOWN_TOOLS
+            .iter()
+            .map(|(tool, _)| *tool)
+            .partition(|tool| is_installed(tool));
+
+        Self {
+            os: format!("{} ({})", std::env::consts::OS, std::env::consts::ARCH),
+            distro: read_distro(),
+            shell: std::env::var("SHELL")
+                .ok()
+                .and_then(|shell| Path::new(&shell).file_name().map(|name| name.to_string_lossy().into_owned())),
+            term: std::env::var("TERM").ok(),
+            installed_tools,
+            missing_tools,
+        }
+    }
+
+    /// Formats the environment as prompt context
+    pub fn describe(&self) -> String {
+        let mut text = format!("Operating system: {}\n", self.os);
+        if let Some(distro) = &self.distro {
+            text.push_str(&format!("Distribution: {}\n", distro));
+        }
+        if let Some(shell) = &self.shell {
+            text.push_str(&format!("Shell: {}\n", shell));
+        }
+        if let Some(term) = &self.term {
+            text.push_str(&format!("Terminal: {}\n", term));
+        }
+        let describe_tools = |tools: &[&str]| {
+            tools
+                .iter()
+                .map(|tool| {
+                    let replaces = KNOWN_TOOLS.iter().find(|(name, _)| name == tool).map_or("", |(_, r)| r);
+                    format!("{} ({})", tool, replaces)
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        if !self.installed_tools.is_empty() {
+            text.push_str(&format!("Installed tools: {}\n", describe_tools(&self.installed_tools)));
+        }
+        if !self.missing_tools.is_empty() {
+            text.push_str(&format!(
+                "Not installed (mention these only as optional installs): {}\n",
+                describe_tools(&self.missing_tools)
+            ));
+        }
+        text
+    }
+}
+
+/// Reads the distribution name from `/etc/os-release`
+fn read_distro() -> Option<String> {
+    let os_release = std::fs::read_to_string("/etc/os-release").ok()?;
+    os_release
+        .lines()
+        .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+        .map(|name| name.trim_matches('"').to_string())
+}
+
+/// Checks whether an executable with this name is on `PATH`
+pub(crate) fn is_installed(tool: &str) -> bool {
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
+    };
+    std::env::split_paths(&path).any(|dir| {
+        let candidate = dir.join(tool);
+        candidate.is_file() || (cfg!(windows) && candidate.with_extension("exe").is_file())
+    })
+}
@@ -0,0 +1,476 @@
+//! Typed errors for the backends, the state store, backups and bundles
+//!
+//! Failures that users can do something about are categorized, so the UI can
+//! say what went wrong (an expired API key isn't a network problem) and
+//! suggest a fix instead of the pet quietly falling back to a canned phrase.
+//! Every error type here implements [`Remedy`]; [`diagnose`] finds it behind a
+//! `Box<dyn Error>`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+use std::path::PathBuf;
+use thiserror::Error;
+
+use crate::config_path;
+
+/// What kind of failure an error is and what the user can do about it
+pub trait Remedy {
+    /// A short label like "authentication" or "network"
+    fn category(&self) -> &'static str;
+    fn remedy(&self) -> Option<String>;
+
+    /// The error, its category and remedy, for showing to the user
+    fn report(&self) -> String
+    where
+        Self: std::fmt::Display,
+    {
+        let mut report = format!("⚠ {} error: {}", self.category(), self);
+        if let Some(remedy) = self.remedy() {
+            report.push_str(&format!("\n→ {}", remedy));
+        }
+        report
+    }
+}
+
+/// The category and remedy of a boxed error, if it is one of ours
+pub fn diagnose(error: &(dyn Error + 'static)) -> Option<(&'static str, Option<String>)> {
+    if let Some(error) = error.downcast_ref::<LlmError>() {
+        return Some((error.category(), error.remedy()));
+    }
+    if let Some(error) = error.downcast_ref::<StorageError>() {
+        return Some((error.category(), error.remedy()));
+    }
+    if let Some(error) = error.downcast_ref::<CryptoError>() {
+        return Some((error.category(), error.remedy()));
+    }
+    if let Some(error) = error.downcast_ref::<BackupError>() {
+        return Some((error.category(), error.remedy()));
+    }
+    if let Some(error) = error.downcast_ref::<BundleError>() {
+        return Some((error.category(), error.remedy()));
+    }
+    if let Some(error) = error.downcast_ref::<SessionError>() {
+        return Some((error.category(), error.remedy()));
+    }
+    None
+}
+
+/// Like [`Remedy::report`] for a boxed error, falling back to its message
+pub fn report(error: &(dyn Error + 'static)) -> String {
+    let Some((category, remedy)) = diagnose(error) else {
+        return error.to_string();
+    };
+    let mut report = format!("⚠ {} error: {}", category, error);
+    if let Some(remedy) = remedy {
+        report.push_str(&format!("\n→ {}", remedy));
+    }
+    report
+}
+
+/// Why a backend couldn't answer
+///
+/// Serializable so the daemon can pass the category on to its clients.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Error)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LlmError {
+    #[error("{var} is not set")]
+    MissingKey { var: String },
+    #[error("{provider} rejected the credentials: {message}")]
+    Auth { provider: String, message: String },
+    #[error("couldn't reach {provider} at {url}: {message}")]
+    Network { provider: String, url: String, message: String },
+    #[error("{provider} doesn't have the model {model}: {message}")]
+    ModelNotFound { provider: String, model: String, message: String },
+    #[error("{provider} is limiting requests: {message}")]
+    RateLimited { provider: String, message: String },
+    #[error("{provider} answered with HTTP {status}: {message}")]
+    Http { provider: String, status: u16, message: String },
+    #[error("{provider} sent a reply I couldn't read: {message}")]
+    InvalidResponse { provider: String, message: String },
+    /// Talking to `pawshell daemon` failed
+    #[error("{message}")]
+    Daemon { message: String },
+}
+
+impl LlmError {
+    /// Classifies an unsuccessful HTTP response by its status and error body
+    pub fn from_response(provider: &str, model: &str, status: u16, body: &str) -> Self {
+        let provider = provider.to_string();
+        let message = error_message(body);
+        match status {
+            401 | 403 => LlmError::Auth { provider, message },
+            404 => LlmError::ModelNotFound { provider, model: model.to_string(), message },
+            429 => LlmError::RateLimited { provider, message },
+            _ => LlmError::Http { provider, status, message },
+        }
+    }
+
+    /// A request that never got an HTTP response
+    pub fn network(provider: &str, url: &str, error: reqwest::Error) -> Self {
+        let message = if error.is_timeout() { "the request timed out".to_string() } else { error.to_string() };
+        LlmError::Network { provider: provider.to_string(), url: url.to_string(), message }
+    }
+
+    pub fn invalid_response(provider: &str, message: impl std::fmt::Display) -> Self {
+        LlmError::InvalidResponse { provider: provider.to_string(), message: message.to_string() }
+    }
+
+    fn is_ollama(&self) -> bool {
+        match self {
+            LlmError::Auth { provider, .. }
+            | LlmError::Network { provider, .. }
+            | LlmError::ModelNotFound { provider, .. }
+            | LlmError::RateLimited { provider, .. }
+            | LlmError::Http { provider, .. }
+            | LlmError::InvalidResponse { provider, .. } => provider == "Ollama",
+            LlmError::MissingKey { .. } | LlmError::Daemon { .. } => false,
+        }
+    }
+}
+
+impl Remedy for LlmError {
+    fn category(&self) -> &'static str {
+        match self {
+            LlmError::MissingKey { .. } => "setup",
+            LlmError::Auth { .. } => "authentication",
+            LlmError::Network { .. } => "network",
+            LlmError::ModelNotFound { .. } => "model not found",
+            LlmError::RateLimited { .. } => "rate limit",
+            LlmError::Http { .. } => "backend",
+            LlmError::InvalidResponse { .. } => "response",
+            LlmError::Daemon { .. } => "daemon",
+        }
+    }
+
+    fn remedy(&self) -> Option<String> {
+        let ollama = self.is_ollama();
+        Some(match self {
+            LlmError::MissingKey { var } => format!(
+                "Export {} or add it to a .env file, or set llm_provider = \"Ollama\" in config.toml to use a local model.",
+                var
+            ),
+            LlmError::Auth { .. } if ollama => "Check the credentials of the proxy in front of ollama_url.".to_string(),
+            LlmError::Auth { .. } => {
+                "Your API key may have expired or been revoked; create a new one at https://platform.openai.com/api-keys and update OPENAI_API_KEY.".to_string()
+            }
+            LlmError::Network { url, .. } if ollama => {
+                format!("Start Ollama with `ollama serve`, or fix ollama_url in config.toml (now {}).", url)
+            }
+            LlmError::Network { .. } => "Check your internet connection and proxy settings.".to_string(),
+            LlmError::ModelNotFound { model, .. } if ollama => {
+                format!("Run `ollama pull {}`, or set ollama_model to one that `ollama list` shows.", model)
+            }
+            LlmError::ModelNotFound { model, .. } => format!("Check that your account has access to {}.", model),
+            LlmError::RateLimited { .. } => {
+                "Wait a little before asking again; if it keeps happening, check your plan's limits and billing.".to_string()
+            }
+            LlmError::Http { status, .. } if *status >= 500 => "The service is having trouble; try again later.".to_string(),
+            LlmError::Http { .. } | LlmError::InvalidResponse { .. } => {
+                "Run `pawshell doctor` to check the backend setup.".to_string()
+            }
+            LlmError::Daemon { .. } => {
+                "Check `pawshell daemon --status`, or restart the daemon.".to_string()
+            }
+        })
+    }
+}
+
+/// The most useful part of an API error body: OpenAI's `error.message`,
+/// Ollama's `error`, or the start of the body itself
+fn error_message(body: &str) -> String {
+    let value: Value = serde_json::from_str(body).unwrap_or_default();
+    match value["error"]["message"].as_str().or(value["error"].as_str()) {
+        Some(message) => message.to_string(),
+        None if body.trim().is_empty() => "no details given".to_string(),
+        None => body.trim().chars().take(200).collect(),
+    }
+}
+
+/// Why the pet's state couldn't be loaded or saved
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error("{}: {source}", path.display())]
+    File { path: PathBuf, source: std::io::Error },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    /// The saved state doesn't parse, or can't be serialized
+    #[error("malformed state: {0}")]
+    Format(String),
+    /// The state file was damaged and couldn't be recovered
+    #[error("{0}")]
+    Damaged(String),
+    #[error(transparent)]
+    Encryption(#[from] CryptoError),
+}
+
+impl StorageError {
+    /// Adds the path to an I/O error, for use with `map_err`
+    pub fn file(path: &std::path::Path) -> impl FnOnce(std::io::Error) -> Self + '_ {
+        move |source| StorageError::File { path: path.to_path_buf(), source }
+    }
+}
+
+impl From<toml::de::Error> for StorageError {
+    fn from(error: toml::de::Error) -> Self {
+        StorageError::Format(error.to_string())
+    }
+}
+
+impl From<toml::ser::Error> for StorageError {
+    fn from(error: toml::ser::Error) -> Self {
+        StorageError::Format(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(error: serde_json::Error) -> Self {
+        StorageError::Format(error.to_string())
+    }
+}
+
+impl Remedy for StorageError {
+    fn category(&self) -> &'static str {
+        match self {
+            StorageError::File { .. } | StorageError::Io(_) => "file access",
+            StorageError::Database(_) => "database",
+            StorageError::Format(_) | StorageError::Damaged(_) => "damaged state",
+            StorageError::Encryption(error) => error.category(),
+        }
+    }
+
+    fn remedy(&self) -> Option<String> {
+        Some(match self {
+            StorageError::Encryption(error) => return error.remedy(),
+            StorageError::File { .. } | StorageError::Io(_) => format!(
+                "Check that {} is writable and the disk isn't full.",
+                config_path::get_data_dir().display()
+            ),
+            StorageError::Database(_) => {
+                "Another program may have the database locked; close other pets, or switch storage_backend in config.toml.".to_string()
+            }
+            StorageError::Format(_) | StorageError::Damaged(_) => {
+                "Restore a backup with `pawshell restore --list`.".to_string()
+            }
+        })
+    }
+}
+
+/// Why the chat history key couldn't be loaded or a message decrypted
+#[derive(Debug, Error)]
+pub enum CryptoError {
+    #[error("{}: {source}", path.display())]
+    File { path: PathBuf, source: std::io::Error },
+    #[error("invalid salt in {}: {message}", path.display())]
+    InvalidSalt { path: PathBuf, message: String },
+    #[error("couldn't derive a key from the passphrase: {0}")]
+    Passphrase(String),
+    #[error("OS keyring unavailable: {0}")]
+    Keyring(#[from] keyring::Error),
+    #[error("invalid key in the OS keyring: {0}")]
+    InvalidKey(String),
+    #[error("corrupted encrypted message: {0}")]
+    Corrupted(String),
+    #[error("couldn't decrypt the chat history (wrong passphrase or key?)")]
+    WrongKey,
+}
+
+impl Remedy for CryptoError {
+    fn category(&self) -> &'static str {
+        match self {
+            CryptoError::File { .. } => "file access",
+            CryptoError::Corrupted(_) => "damaged state",
+            CryptoError::InvalidSalt { .. }
+            | CryptoError::Passphrase(_)
+            | CryptoError::Keyring(_)
+            | CryptoError::InvalidKey(_)
+            | CryptoError::WrongKey => "encryption",
+        }
+    }
+
+    fn remedy(&self) -> Option<String> {
+        Some(match self {
+            CryptoError::File { path, .. } => format!("Check that {} is writable and the disk isn't full.", path.display()),
+            CryptoError::InvalidSalt { path, .. } => {
+                format!("Restore {} from a backup; the passphrase can't unlock the history without it.", path.display())
+            }
+            CryptoError::Passphrase(_) | CryptoError::WrongKey => {
+                "Check PAWSHELL_PASSPHRASE, or that the OS keyring holds the key the history was encrypted with.".to_string()
+            }
+            CryptoError::Keyring(_) | CryptoError::InvalidKey(_) => {
+                "Unlock the OS keyring, or set PAWSHELL_PASSPHRASE to use a passphrase instead.".to_string()
+            }
+            CryptoError::Corrupted(_) => "Restore a backup with `pawshell restore --list`.".to_string(),
+        })
+    }
+}
+
+/// Why a state backup couldn't be made or restored
+#[derive(Debug, Error)]
+pub enum BackupError {
+    #[error("{}: {source}", path.display())]
+    File { path: PathBuf, source: std::io::Error },
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+    #[error("no backup named {0}")]
+    NotFound(String),
+    #[error("{} is not a state backup", .0.display())]
+    NotABackup(PathBuf),
+    #[error("{} is damaged: {message}", path.display())]
+    Damaged { path: PathBuf, message: String },
+    #[error("couldn't restore {} to {}: {source}", from.display(), to.display())]
+    Restore { from: PathBuf, to: PathBuf, source: std::io::Error },
+}
+
+impl BackupError {
+    /// Adds the path to an I/O error, for use with `map_err`
+    pub fn file(path: &std::path::Path) -> impl FnOnce(std::io::Error) -> Self + '_ {
+        move |source| BackupError::File { path: path.to_path_buf(), source }
+    }
+}
+
+impl Remedy for BackupError {
+    fn category(&self) -> &'static str {
+        match self {
+            BackupError::File { .. } | BackupError::Restore { .. } => "file access",
+            BackupError::Storage(error) => error.category(),
+            BackupError::NotFound(_) | BackupError::NotABackup(_) | BackupError::Damaged { .. } => "backup",
+        }
+    }
+
+    fn remedy(&self) -> Option<String> {
+        Some(match self {
+            BackupError::File { path, .. } => format!("Check that {} is writable and the disk isn't full.", path.display()),
+            BackupError::Restore { to, .. } => format!("Check that {} is writable and the disk isn't full.", to.display()),
+            BackupError::Storage(error) => return error.remedy(),
+            BackupError::NotFound(_) | BackupError::NotABackup(_) => {
+                "Run `pawshell restore --list` to see the backups.".to_string()
+            }
+            BackupError::Damaged { .. } => "Pick another backup from `pawshell restore --list`.".to_string(),
+        })
+    }
+}
+
+/// Why a pet couldn't be exported to or imported from a bundle
+#[derive(Debug, Error)]
+pub enum BundleError {
+    #[error("{}: {source}", path.display())]
+    File { path: PathBuf, source: std::io::Error },
+    #[error("{} is not a pet bundle: {message}", path.display())]
+    Invalid { path: PathBuf, message: String },
+    #[error("{} was exported by a newer version of pawshell (bundle version {version})", path.display())]
+    TooNew { path: PathBuf, version: u32 },
+    /// The configured store couldn't be opened, so the bundle would hold a
+    /// different pet than the one in use
+    #[error("{0}")]
+    StoreUnavailable(String),
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+    #[error(transparent)]
+    Backup(#[from] BackupError),
+    #[error("couldn't update config.toml: {0}")]
+    Config(String),
+}
+
+impl BundleError {
+    /// Adds the path to an I/O error, for use with `map_err`
+    pub fn file(path: &std::path::Path) -> impl FnOnce(std::io::Error) -> Self + '_ {
+        move |source| BundleError::File { path: path.to_path_buf(), source }
+    }
+}
+
+impl Remedy for BundleError {
+    fn category(&self) -> &'static str {
+        match self {
+            BundleError::File { .. } | BundleError::Config(_) => "file access",
+            BundleError::Invalid { .. } | BundleError::TooNew { .. } => "bundle",
+            BundleError::StoreUnavailable(_) => "storage",
+            BundleError::Storage(error) => error.category(),
+            BundleError::Backup(error) => error.category(),
+        }
+    }
+
+    fn remedy(&self) -> Option<String> {
+        Some(match self {
+            BundleError::File { path, .. } => format!("Check that {} is readable and writable.", path.display()),
+            BundleError::Config(_) => format!(
+                "Check that {} is writable and still valid TOML.",
+                config_path::get_config_file_path(None).display()
+            ),
+            BundleError::Invalid { .. } => "Check that the file was written by `pawshell export-pet`.".to_string(),
+            BundleError::TooNew { .. } => "Update pawshell, then import the bundle again.".to_string(),
+            BundleError::StoreUnavailable(_) => {
+                "Fix the storage problem first, so the bundle holds the pet you actually use.".to_string()
+            }
+            BundleError::Storage(error) => return error.remedy(),
+            BundleError::Backup(error) => return error.remedy(),
+        })
+    }
+}
+
+/// Why a [`PetSession`](crate::session::PetSession) couldn't open or answer
+#[derive(Debug, Error)]
+pub enum SessionError {
+    #[error(transparent)]
+    Llm(#[from] LlmError),
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
+impl Remedy for SessionError {
+    fn category(&self) -> &'static str {
+        match self {
+            SessionError::Llm(error) => error.category(),
+            SessionError::Storage(error) => error.category(),
+        }
+    }
+
+    fn remedy(&self) -> Option<String> {
+        match self {
+            SessionError::Llm(error) => error.remedy(),
+            SessionError::Storage(error) => error.remedy(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn responses_are_categorized_by_status() {
+        let openai = r#"{"error": {"message": "Incorrect API key provided", "code": "invalid_api_key"}}"#;
+        let error = LlmError::from_response("OpenAI", "gpt-4o", 401, openai);
+        assert_eq!(error.category(), "authentication");
+        assert!(error.to_string().contains("Incorrect API key provided"));
+        assert!(error.remedy().unwrap().contains("OPENAI_API_KEY"));
+
+        let ollama = r#"{"error": "model \"llama9\" not found, try pulling it first"}"#;
+        let error = LlmError::from_response("Ollama", "llama9", 404, ollama);
+        assert_eq!(error.category(), "model not found");
+        assert!(error.remedy().unwrap().contains("ollama pull llama9"));
+
+        assert_eq!(LlmError::from_response("OpenAI", "gpt-4o", 429, "").category(), "rate limit");
+        assert_eq!(LlmError::from_response("OpenAI", "gpt-4o", 503, "<html>").category(), "backend");
+    }
+
+    #[test]
+    fn boxed_errors_can_still_be_diagnosed() {
+        let error: Box<dyn Error> = Box::new(LlmError::MissingKey { var: "OPENAI_API_KEY".to_string() });
+        assert_eq!(diagnose(error.as_ref()).map(|(category, _)| category), Some("setup"));
+        assert!(report(error.as_ref()).contains("→ Export OPENAI_API_KEY"));
+
+        // Wrapped errors keep the category and remedy of what they wrap
+        let error: Box<dyn Error> = Box::new(SessionError::from(StorageError::from(CryptoError::WrongKey)));
+        assert_eq!(diagnose(error.as_ref()).map(|(category, _)| category), Some("encryption"));
+        assert!(report(error.as_ref()).contains("→ Check PAWSHELL_PASSPHRASE"));
+        let error: Box<dyn Error> = Box::new(BundleError::from(BackupError::NotFound("state-x.toml".to_string())));
+        assert!(report(error.as_ref()).contains("→ Run `pawshell restore --list`"));
+
+        let error: Box<dyn Error> = "something else".into();
+        assert!(diagnose(error.as_ref()).is_none());
+        assert_eq!(report(error.as_ref()), "something else");
+    }
+}
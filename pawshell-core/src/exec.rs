@@ -0,0 +1,177 @@
+//! Command execution for PetCLI
+//!
+//! Lets the user run a command the pet suggested (`/run`) or one they type
+//! themselves (`!cmd`). Every command needs an explicit confirmation in the UI
+//! before it gets here, and execution can be disabled entirely in the config.
+//!
+//! Commands run in a subshell with a few sandboxing knobs:
+//! - a hard timeout, after which the process is killed
+//! - an optional fixed working directory
+//! - an optionally cleared environment, so API keys and tokens aren't inherited
+//! - no stdin, and captured output capped in size
+
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::config::Config;
+
+/// Environment variables kept when the environment is cleared
+#[cfg(not(windows))]
+const KEPT_ENV_VARS: &[&str] = &["PATH", "HOME", "USER", "LANG", "TERM", "SHELL"];
+
+/// Environment variables kept when the environment is cleared; PowerShell
+/// doesn't start without `SystemRoot`, and `PATHEXT` is how `cargo` finds `cargo.exe`
+#[cfg(windows)]
+const KEPT_ENV_VARS: &[&str] =
+    &["PATH", "PATHEXT", "SystemRoot", "SystemDrive", "COMSPEC", "TEMP", "TMP", "USERPROFILE", "USERNAME", "APPDATA", "LOCALAPPDATA"];
+
+/// How a command is run, derived from the config
+pub struct ExecOptions {
+    pub timeout: Duration,
+    pub working_dir: Option<PathBuf>,
+    pub clear_env: bool,
+    pub max_output_bytes: usize,
+}
+
+impl ExecOptions {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            timeout: Duration::from_secs(config.exec_timeout_secs),
+            working_dir: config.exec_working_dir.as_ref().map(PathBuf::from),
+            clear_env: config.exec_clear_env,
+            max_output_bytes: config.exec_max_output_bytes,
+        }
+    }
+}
+
+/// The captured result of running a command
+pub struct ExecOutput {
+    pub stdout: String,
+    pub stderr: String,
+    /// None if the process was killed by a signal or timed out
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+}
+
+impl ExecOutput {
+    /// Formats the output as a chat message
+    pub fn summary(&self, command: &str) -> String {
+        let status = match (self.timed_out, self.exit_code) {
+            (true, _) => "timed out".to_string(),
+            (false, Some(code)) => format!("exited with {}", code),
+            (false, None) => "was killed".to_string(),
+        };
+        let mut summary = format!("`{}` {}", command, status);
+        if !self.stdout.trim().is_empty() {
+            summary.push_str(&format!("\nstdout:\n{}", self.stdout.trim_end()));
+        }
+        if !self.stderr.trim().is_empty() {
+            summary.push_str(&format!("\nstderr:\n{}", self.stderr.trim_end()));
+        }
+        summary
+    }
+}
+
+/// Runs a command line in the platform shell
+pub async fn run(command: &str, options: &ExecOptions) -> Result<ExecOutput, Box<dyn std::error::Error>> {
+    let mut cmd = shell_command(command);
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    if let Some(dir) = &options.working_dir {
+        cmd.current_dir(dir);
+    }
+    if options.clear_env {
+        let kept: Vec<(String, String)> = KEPT_ENV_VARS
+            .iter()
+            .filter_map(|name| std::env::var(name).ok().map(|value| (name.to_string(), value)))
+            .collect();
+        cmd.env_clear().envs(kept);
+    }
+
+    let child = cmd.spawn().map_err(|e| format!("Failed to start command: {}", e))?;
+    match tokio::time::timeout(options.timeout, child.wait_with_output()).await {
+        Ok(output) => {
+            let output = output.map_err(|e| format!("Failed to run command: {}", e))?;
+            Ok(ExecOutput {
+                stdout: truncate_output(&output.stdout, options.max_output_bytes),
+                stderr: truncate_output(&output.stderr, options.max_output_bytes),
+                exit_code: output.status.code(),
+                timed_out: false,
+            })
+        }
+        // Dropping the future kills the child thanks to kill_on_drop
+        Err(_) => Ok(ExecOutput {
+            stdout: String::new(),
+            stderr: String::new(),
+            exit_code: None,
+            timed_out: true,
+        }),
+    }
+}
+
+#[cfg(not(windows))]
+fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+#[cfg(windows)]
+fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("powershell");
+    cmd.arg("-NoProfile").arg("-Command").arg(command);
+    cmd
+}
+
+fn truncate_output(bytes: &[u8], max: usize) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    if text.len() <= max {
+        return text.into_owned();
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}\n… (output truncated)", &text[..end])
+}
+
+/// Extracts the shell commands suggested in a pet response
+///
+/// Every line of a fenced code block counts as a command; if there are no code
+/// blocks, inline `code` spans are used instead. Prompt markers like `$ ` are
+/// stripped.
+pub fn extract_commands(response: &str) -> Vec<String> {
+    let mut commands = Vec::new();
+    let mut in_block = false;
+    for line in response.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            in_block = !in_block;
+            continue;
+        }
+        if in_block {
+            commands.extend(block_command(trimmed));
+        }
+    }
+    if !commands.is_empty() {
+        return commands;
+    }
+
+    response
+        .split('`')
+        .skip(1)
+        .step_by(2)
+        .map(|span| span.trim().trim_start_matches("$ ").to_string())
+        .filter(|span| !span.is_empty())
+        .collect()
+}
+
+/// The command on a (trimmed) line of a fenced code block, unless it's blank or a comment
+pub(crate) fn block_command(line: &str) -> Option<String> {
+    (!line.is_empty() && !line.starts_with('#')).then(|| line.trim_start_matches("$ ").to_string())
+}
@@ -0,0 +1,133 @@
+//! 👍/👎 reactions to the pet's replies and what they say about the user
+//!
+//! Alt+Up and Alt+Down react to the last reply. Each reaction keeps how long
+//! the reply was and which tools its commands used, and the reactions taken
+//! together become a few lines of preferences ("keep answers short", "loves
+//! fzf tips") added to every prompt. Reacting again to the same reply
+//! replaces the earlier reaction.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::exec;
+
+/// Reactions kept; older ones are forgotten
+const MAX_REACTIONS: usize = 200;
+/// Reactions needed before anything is concluded about reply length
+const MIN_LENGTH_REACTIONS: usize = 3;
+/// Replies longer than this many characters count as long
+const LONG_REPLY_CHARS: usize = 600;
+/// How much more a tool must be liked than disliked (or the reverse) to matter
+const TOOL_MARGIN: i32 = 2;
+
+/// A 👍 or 👎 on one reply
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Reaction {
+    pub liked: bool,
+    /// When the reply was written, to recognize it when reacted to again
+    pub reply_at: Option<DateTime<Utc>>,
+    pub reply_chars: usize,
+    /// Tools the reply's commands used, like "fzf" or "git"
+    pub tools: Vec<String>,
+}
+
+impl Reaction {
+    pub fn new(reply: &str, reply_at: Option<DateTime<Utc>>, liked: bool) -> Self {
+        Self { liked, reply_at, reply_chars: reply.chars().count(), tools: tools(reply) }
+    }
+}
+
+/// The programs a reply's commands run, in order of first use
+fn tools(reply: &str) -> Vec<String> {
+    let mut tools: Vec<String> = Vec::new();
+    for command in exec::extract_commands(reply) {
+        for segment in command.split(['|', ';']).flat_map(|segment| segment.split("&&")) {
+            let program = segment
+                .split_whitespace()
+                .find(|word| *word != "sudo" && !word.contains('='))
+                .map(|word| word.rsplit('/').next().unwrap_or(word).to_lowercase());
+            if let Some(program) = program.filter(|program| program.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')) {
+                if !tools.contains(&program) {
+                    tools.push(program);
+                }
+            }
+        }
+    }
+    tools
+}
+
+/// Adds a reaction, replacing an earlier one to the same reply
+pub fn record(reactions: &mut Vec<Reaction>, reaction: Reaction) {
+    if reaction.reply_at.is_some() {
+        reactions.retain(|earlier| earlier.reply_at != reaction.reply_at);
+    }
+    reactions.push(reaction);
+    if reactions.len() > MAX_REACTIONS {
+        reactions.drain(..reactions.len() - MAX_REACTIONS);
+    }
+}
+
+/// What the reactions say the user likes, as prompt context
+pub fn preferences(reactions: &[Reaction]) -> Option<String> {
+    let mut lines = Vec::new();
+
+    let (long_disliked, long_liked) = reactions
+        .iter()
+        .filter(|reaction| reaction.reply_chars > LONG_REPLY_CHARS)
+        .fold((0, 0), |(down, up), reaction| if reaction.liked { (down, up + 1) } else { (down + 1, up) });
+    let (short_disliked, short_liked) = reactions
+        .iter()
+        .filter(|reaction| reaction.reply_chars <= LONG_REPLY_CHARS)
+        .fold((0, 0), |(down, up), reaction| if reaction.liked { (down, up + 1) } else { (down + 1, up) });
+    if long_disliked >= MIN_LENGTH_REACTIONS && long_disliked > long_liked && short_liked >= short_disliked {
+        lines.push("They dislike long answers, so keep replies short and to the point.".to_string());
+    } else if short_disliked >= MIN_LENGTH_REACTIONS && short_disliked > short_liked && long_liked >= long_disliked {
+        lines.push("They find short answers too thin and like thorough explanations.".to_string());
+    }
+
+    let mut scores: BTreeMap<&str, i32> = BTreeMap::new();
+    for reaction in reactions {
+        for tool in &reaction.tools {
+            *scores.entry(tool).or_default() += if reaction.liked { 1 } else { -1 };
+        }
+    }
+    let loved: Vec<&str> = scores.iter().filter(|(_, &score)| score >= TOOL_MARGIN).map(|(tool, _)| *tool).collect();
+    let disliked: Vec<&str> = scores.iter().filter(|(_, &score)| score <= -TOOL_MARGIN).map(|(tool, _)| *tool).collect();
+    if !loved.is_empty() {
+        lines.push(format!("They love tips that use {}.", loved.join(", ")));
+    }
+    if !disliked.is_empty() {
+        lines.push(format!("They aren't keen on suggestions that use {}; prefer alternatives.", disliked.join(", ")));
+    }
+
+    (!lines.is_empty()).then(|| format!("What the user's 👍/👎 on earlier replies say:\n- {}", lines.join("\n- ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::test_time;
+
+    #[test]
+    fn reactions_become_preferences() {
+        let long = "x".repeat(LONG_REPLY_CHARS + 1);
+        let mut reactions = Vec::new();
+        for _ in 0..3 {
+            record(&mut reactions, Reaction::new(&long, None, false));
+        }
+        let at = Some(test_time(0));
+        record(&mut reactions, Reaction::new("Try `git log | fzf`", at, false));
+        record(&mut reactions, Reaction::new("Try `git log | fzf`", at, true));
+        record(&mut reactions, Reaction::new("```\nsudo FOO=1 /usr/bin/fzf --multi\n```", None, true));
+        assert_eq!(reactions.len(), 5);
+        assert_eq!(reactions[3].tools, ["git", "fzf"]);
+        assert_eq!(
+            preferences(&reactions).unwrap(),
+            "What the user's 👍/👎 on earlier replies say:\n\
+             - They dislike long answers, so keep replies short and to the point.\n\
+             - They love tips that use fzf."
+        );
+        assert_eq!(preferences(&reactions[..2]), None);
+    }
+}
@@ -0,0 +1,118 @@
+//! Few-shot examples from the config
+//!
+//! `[[few_shot]]` entries pair a user message with the answer the pet should
+//! ideally give, and are put ahead of the conversation in chat prompts to
+//! show the style wanted. An example with a `persona` is only used while that
+//! persona is active. Examples get at most `MAX_CHARS` of the prompt and less
+//! when the rest of it is already long; what fits is picked by how much each
+//! example has in common with the message being answered.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Characters of examples in a prompt at most
+const MAX_CHARS: usize = 3_000;
+/// Examples are cut down so the whole chat prompt stays around this size
+const PROMPT_CHARS: usize = 16_000;
+
+/// An example exchange
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Example {
+    pub user: String,
+    /// The answer the pet should give
+    pub pet: String,
+    /// Only used while `persona` is set to this
+    #[serde(default)]
+    pub persona: Option<String>,
+}
+
+impl Example {
+    fn chars(&self) -> usize {
+        self.user.chars().count() + self.pet.chars().count()
+    }
+}
+
+/// The examples that apply with `persona` active
+pub fn for_persona<'a>(examples: &'a [Example], persona: Option<&str>) -> Vec<&'a Example> {
+    let active = persona.map(|persona| persona.trim().trim_end_matches('.').to_lowercase());
+    examples
+        .iter()
+        .filter(|example| match &example.persona {
+            Some(wanted) => active.as_deref() == Some(wanted.trim().trim_end_matches('.').to_lowercase().as_str()),
+            None => true,
+        })
+        .collect()
+}
+
+/// The examples that fit next to `used` characters of prompt, those most
+/// like `message` first, in their configured order
+pub fn fit<'a>(examples: &[&'a Example], message: &str, used: usize) -> Vec<&'a Example> {
+    let mut room = MAX_CHARS.min(PROMPT_CHARS.saturating_sub(used));
+    let message = words(message);
+    let mut ranked: Vec<(usize, &Example)> = examples.iter().copied().enumerate().collect();
+    ranked.sort_by_key(|(i, example)| (std::cmp::Reverse(words(&example.user).intersection(&message).count()), *i));
+    let mut picked = Vec::new();
+    for (i, example) in ranked {
+        if example.chars() <= room {
+            room -= example.chars();
+            picked.push((i, example));
+        }
+    }
+    picked.sort_by_key(|(i, _)| *i);
+    picked.into_iter().map(|(_, example)| example).collect()
+}
+
+fn words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|word| word.len() > 2).map(str::to_lowercase).collect()
+}
+
+/// The examples as prompt text, ahead of the conversation
+pub fn format(examples: &[&Example]) -> String {
+    if examples.is_empty() {
+        return String::new();
+    }
+    let mut text = "Example exchanges showing how to answer:\n\n".to_string();
+    for example in examples {
+        text.push_str(&format!("User: {}\nAssistant: {}\n\n", example.user.trim(), example.pet.trim()));
+    }
+    text
+}
+
+/// Problems with the `[[few_shot]]` entries, for config validation
+pub fn check(examples: &[Example]) -> Vec<String> {
+    examples
+        .iter()
+        .enumerate()
+        .filter(|(_, example)| example.user.trim().is_empty() || example.pet.trim().is_empty())
+        .map(|(i, _)| format!("few_shot example {} needs both a user message and the pet's answer", i + 1))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn examples_are_picked_by_persona_relevance_and_room() {
+        let example = |user: &str, pet: &str, persona: Option<&str>| Example {
+            user: user.to_string(),
+            pet: pet.to_string(),
+            persona: persona.map(str::to_string),
+        };
+        let examples = vec![
+            example("how do I list files?", "`ls -la`", None),
+            example("find big files", &"x".repeat(MAX_CHARS - 20), None),
+            example("undo a commit", "`git reset --soft HEAD~1`, matey", Some("a pirate")),
+        ];
+        assert_eq!(for_persona(&examples, None).len(), 2);
+        let pirate = for_persona(&examples, Some("A pirate."));
+        assert_eq!(pirate.len(), 3);
+
+        let users = |picked: Vec<&Example>| picked.iter().map(|example| example.user.clone()).collect::<Vec<_>>();
+        assert_eq!(users(fit(&pirate, "where are the big files?", 0)), ["find big files"]);
+        assert_eq!(users(fit(&pirate, "hi", 0)), ["how do I list files?", "undo a commit"]);
+        assert_eq!(users(fit(&pirate, "hi", PROMPT_CHARS - 50)), ["how do I list files?"]);
+        assert!(format(&fit(&pirate, "hi", PROMPT_CHARS)).is_empty());
+        assert_eq!(check(&[example(" ", "x", None)]).len(), 1);
+    }
+}
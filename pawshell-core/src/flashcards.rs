@@ -0,0 +1,220 @@
+//! Spaced-repetition flashcards for `/flashcards`
+//!
+//! Cards are made from the pet's own tips, with a question the user asked on
+//! the front and the command the pet answered with on the back, and from
+//! commands the user struggled with: one that failed, followed shortly by the
+//! same program succeeding. At most `NEW_CARDS_PER_DAY` are added a day, the
+//! newest first, so a long history turns into a steady trickle.
+//!
+//! Reviews are scheduled with SM-2: every answer is graded 0 to 5, a grade
+//! below 3 starts the card over, and the interval grows with the card's ease
+//! otherwise. Cards live in the pet's state.
+
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::exec;
+use crate::history::HistoryEntry;
+use crate::pet::ChatMessage;
+
+/// Cards added at most per day
+const NEW_CARDS_PER_DAY: usize = 10;
+/// How many commands after a failure still count as fixing it
+const FIX_WINDOW: usize = 3;
+/// Questions longer than this make poor card fronts
+const MAX_QUESTION_CHARS: usize = 120;
+/// First words of messages that ask how to do something
+const QUESTION_WORDS: &[&str] = &["how", "what", "which", "why", "where", "can", "could", "is", "should", "show", "list", "find"];
+/// Ease factors as SM-2 defines them
+const INITIAL_EASE: f32 = 2.5;
+const MIN_EASE: f32 = 1.3;
+/// A grade below this starts the card over
+const PASSING_GRADE: u8 = 3;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Flashcard {
+    pub front: String,
+    /// The command that answers it
+    pub back: String,
+    pub added: NaiveDate,
+    pub due: NaiveDate,
+    /// Days until the next review after a passing grade
+    pub interval_days: u32,
+    pub ease: f32,
+    /// Passing grades in a row
+    pub repetitions: u32,
+}
+
+impl Flashcard {
+    fn new(front: String, back: String, today: NaiveDate) -> Self {
+        Self { front, back, added: today, due: today, interval_days: 0, ease: INITIAL_EASE, repetitions: 0 }
+    }
+
+    /// Schedules the next review after an answer graded 0 (blackout) to 5 (perfect)
+    pub fn grade(&mut self, grade: u8, today: NaiveDate) {
+        let grade = grade.min(5);
+        if grade < PASSING_GRADE {
+            self.repetitions = 0;
+            self.interval_days = 1;
+        } else {
+            self.interval_days = match self.repetitions {
+                0 => 1,
+                1 => 6,
+                _ => (self.interval_days as f32 * self.ease).round() as u32,
+            };
+            self.repetitions += 1;
+        }
+        let miss = f32::from(5 - grade);
+        self.ease = (self.ease + 0.1 - miss * (0.08 + miss * 0.02)).max(MIN_EASE);
+        self.due = today + Duration::days(i64::from(self.interval_days));
+    }
+
+    /// Whether a typed answer is the card's command, give or take spacing
+    pub fn is_answered_by(&self, answer: &str) -> bool {
+        normalize(answer) == normalize(&self.back)
+    }
+}
+
+fn normalize(command: &str) -> String {
+    command.trim().trim_matches('`').trim_start_matches('$').split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// A grade typed as 0-5 or as again, hard, good or easy
+pub fn parse_grade(text: &str) -> Option<u8> {
+    match text.trim().to_lowercase().as_str() {
+        "again" => Some(1),
+        "hard" => Some(3),
+        "good" => Some(4),
+        "easy" => Some(5),
+        number => number.parse().ok().filter(|&grade| grade <= 5),
+    }
+}
+
+/// Adds cards for new tips and struggles, up to the day's limit; returns how
+/// many were added
+pub fn add_new(cards: &mut Vec<Flashcard>, chats: &[ChatMessage], history: &[HistoryEntry], today: NaiveDate) -> usize {
+    let room = NEW_CARDS_PER_DAY.saturating_sub(cards.iter().filter(|card| card.added == today).count());
+    let mut added = 0;
+    for (front, back) in tips(chats).into_iter().chain(struggles(history)) {
+        if added == room {
+            break;
+        }
+        if cards.iter().any(|card| normalize(&card.back) == normalize(&back)) {
+            continue;
+        }
+        cards.push(Flashcard::new(front, back, today));
+        added += 1;
+    }
+    added
+}
+
+/// Questions the pet answered with a command, newest first
+fn tips(chats: &[ChatMessage]) -> Vec<(String, String)> {
+    chats
+        .iter()
+        .rev()
+        .filter(|chat| is_question(&chat.user_message) && chat.user_message.chars().count() <= MAX_QUESTION_CHARS)
+        .filter_map(|chat| {
+            let command = exec::extract_commands(&chat.response).into_iter().next()?;
+            Some((chat.user_message.trim().to_string(), command))
+        })
+        .collect()
+}
+
+fn is_question(message: &str) -> bool {
+    let first = message.split_whitespace().next().unwrap_or_default().to_lowercase();
+    message.trim_end().ends_with('?') || QUESTION_WORDS.contains(&first.as_str())
+}
+
+/// Failed commands and what worked instead, newest first
+fn struggles(history: &[HistoryEntry]) -> Vec<(String, String)> {
+    let program = |entry: &HistoryEntry| entry.command.split_whitespace().next().map(str::to_string);
+    let mut found = Vec::new();
+    for (i, failed) in history.iter().enumerate().rev().filter(|(_, entry)| entry.failed()) {
+        let fix = history[i + 1..]
+            .iter()
+            .take(FIX_WINDOW)
+            .find(|entry| entry.exit_code == Some(0) && program(entry) == program(failed) && entry.command != failed.command);
+        if let Some(fix) = fix {
+            found.push((format!("`{}` failed. What works instead?", failed.command), fix.command.clone()));
+        }
+    }
+    found
+}
+
+/// Indices of the cards due by `today`, most overdue first
+pub fn due(cards: &[Flashcard], today: NaiveDate) -> Vec<usize> {
+    let mut due: Vec<usize> = (0..cards.len()).filter(|&i| cards[i].due <= today).collect();
+    due.sort_by_key(|&i| cards[i].due);
+    due
+}
+
+/// When the next card comes up, if there are any
+pub fn next_due(cards: &[Flashcard]) -> Option<NaiveDate> {
+    cards.iter().map(|card| card.due).min()
+}
+
+/// A review session going through the due cards
+pub struct Review {
+    queue: VecDeque<usize>,
+    /// Whether the current card's back was shown and it waits for a grade
+    pub revealed: bool,
+}
+
+impl Review {
+    pub fn start(cards: &[Flashcard], today: NaiveDate) -> Option<Self> {
+        let queue: VecDeque<usize> = due(cards, today).into();
+        (!queue.is_empty()).then_some(Self { queue, revealed: false })
+    }
+
+    /// The card being reviewed, unless the session is over
+    pub fn current<'a>(&self, cards: &'a [Flashcard]) -> Option<&'a Flashcard> {
+        self.queue.front().and_then(|&i| cards.get(i))
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Grades the current card and moves on; a failed card comes back at the
+    /// end of the session
+    pub fn grade(&mut self, cards: &mut [Flashcard], grade: u8, today: NaiveDate) {
+        let Some(i) = self.queue.pop_front() else {
+            return;
+        };
+        if let Some(card) = cards.get_mut(i) {
+            card.grade(grade, today);
+            if grade < PASSING_GRADE {
+                self.queue.push_back(i);
+            }
+        }
+        self.revealed = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intervals_follow_sm2() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        let mut card = Flashcard::new("List open ports?".to_string(), "ss -tlnp".to_string(), today);
+        card.grade(5, today);
+        card.grade(4, today);
+        card.grade(4, today);
+        assert_eq!((card.interval_days, card.repetitions), (16, 3));
+        assert_eq!(card.due, today + Duration::days(16));
+        card.grade(1, today);
+        assert_eq!((card.interval_days, card.repetitions), (1, 0));
+        assert!((card.ease - 2.06).abs() < 0.001);
+    }
+
+    #[test]
+    fn struggles_pair_failures_with_their_fix() {
+        let entry = |command: &str, exit_code| HistoryEntry { exit_code: Some(exit_code), ..HistoryEntry::new(command, None) };
+        let history = [entry("tar -xf a.tgz -C", 2), entry("ls", 0), entry("tar -xzf a.tgz", 0), entry("git pul", 1)];
+        assert_eq!(struggles(&history), [("`tar -xf a.tgz -C` failed. What works instead?".to_string(), "tar -xzf a.tgz".to_string())]);
+    }
+}
@@ -0,0 +1,75 @@
+//! Do-not-disturb focus sessions for `/focus`
+//!
+//! `/focus [duration]` keeps the pet quiet for a while (`focus_minutes`
+//! unless given): it naps instead of chatting on its own, suggesting
+//! workflows or sending notifications, and the TUI dims its chrome. The
+//! session ends when the time is up or with `/focus off`. Like a pomodoro,
+//! it only lives as long as the session.
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::reminders;
+
+/// Longest focus session `/focus` accepts
+const MAX_HOURS: i64 = 12;
+
+/// A running focus session
+pub struct FocusSession {
+    started: DateTime<Utc>,
+    until: DateTime<Utc>,
+}
+
+impl FocusSession {
+    pub fn new(now: DateTime<Utc>, length: Duration) -> Self {
+        Self { started: now, until: now + length }
+    }
+
+    /// Parses `/focus` arguments like `45m` or `1h30m`; no arguments means
+    /// `default`
+    pub fn parse_length(args: &str, default: Duration) -> Option<Duration> {
+        let length = match args.trim() {
+            "" => default,
+            args => reminders::parse_duration(args)?,
+        };
+        (length <= Duration::hours(MAX_HOURS)).then_some(length)
+    }
+
+    pub fn remaining(&self, now: DateTime<Utc>) -> Duration {
+        (self.until - now).max(Duration::zero())
+    }
+
+    pub fn is_over(&self, now: DateTime<Utc>) -> bool {
+        now >= self.until
+    }
+
+    /// How long it's been on
+    pub fn elapsed(&self, now: DateTime<Utc>) -> Duration {
+        (now.min(self.until) - self.started).max(Duration::zero())
+    }
+
+    /// The countdown for the title, like `💤 focus 24:10`
+    pub fn status(&self, now: DateTime<Utc>) -> String {
+        let remaining = self.remaining(now).num_seconds();
+        format!("💤 focus {}:{:02}", remaining / 60, remaining % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::test_time;
+
+    #[test]
+    fn sessions_count_down_and_end() {
+        let start = test_time(0);
+        let length = FocusSession::parse_length("", Duration::minutes(25)).unwrap();
+        let session = FocusSession::new(start, length);
+        assert_eq!(session.status(start + Duration::seconds(50)), "💤 focus 24:10");
+        assert!(!session.is_over(start + Duration::minutes(24)));
+        assert!(session.is_over(start + Duration::minutes(25)));
+        assert_eq!(session.elapsed(start + Duration::hours(2)), Duration::minutes(25));
+        assert_eq!(FocusSession::parse_length("1h30m", Duration::minutes(25)), Some(Duration::minutes(90)));
+        assert_eq!(FocusSession::parse_length("forever", Duration::minutes(25)), None);
+        assert_eq!(FocusSession::parse_length("13h", Duration::minutes(25)), None);
+    }
+}
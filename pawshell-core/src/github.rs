@@ -0,0 +1,106 @@
+//! Pull request and issue summaries for `/gh`
+//!
+//! Fetches a pull request or issue with the GitHub CLI (`gh`), which takes care
+//! of authentication and figures out the repository from the current
+//! directory, and builds a prompt asking for a short, action-focused summary.
+
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::templates::Prompt;
+
+/// How long `gh` gets to fetch an item
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+/// Fetched text beyond this many characters is left out of the prompt
+const MAX_CONTENT_CHARS: usize = 16_000;
+
+const SUMMARY_TEMPLATE: &str = "Summarize GitHub {{ item }} {{ number }} for me in a few short lines: what it \
+is about, where it stands (state, reviews, open questions), and what, if anything, I should do next. \
+Keep it brief and concrete.\n\n{{ content }}";
+
+/// What `/gh` can summarize
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GhItem {
+    PullRequest,
+    Issue,
+}
+
+impl GhItem {
+    /// Parses `/gh`'s arguments, like `pr 42` or `issue #7`, into the item
+    /// kind and the number (or URL) to hand to `gh`
+    pub fn parse(args: &str) -> Option<(Self, String)> {
+        let (kind, number) = args.trim().split_once(char::is_whitespace)?;
+        let kind = match kind {
+            "pr" => GhItem::PullRequest,
+            "issue" => GhItem::Issue,
+            _ => return None,
+        };
+        let number = number.trim().trim_start_matches('#');
+        (!number.is_empty() && !number.contains(char::is_whitespace)).then(|| (kind, number.to_string()))
+    }
+
+    fn subcommand(self) -> &'static str {
+        match self {
+            GhItem::PullRequest => "pr",
+            GhItem::Issue => "issue",
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            GhItem::PullRequest => "pull request",
+            GhItem::Issue => "issue",
+        }
+    }
+}
+
+/// Fetches an item with its comments as `gh ... view --comments` prints it
+pub async fn fetch(item: GhItem, number: &str, dir: &Path) -> Result<String, String> {
+    let mut command = Command::new("gh");
+    command
+        .args([item.subcommand(), "view", number, "--comments"])
+        .current_dir(dir)
+        .env("GH_PROMPT_DISABLED", "1")
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+    let output = match tokio::time::timeout(FETCH_TIMEOUT, command.output()).await {
+        Err(_) => return Err("gh took too long to answer".to_string()),
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err("The GitHub CLI isn't installed; get it from https://cli.github.com".to_string())
+        }
+        Ok(Err(e)) => return Err(format!("Couldn't run gh: {}", e)),
+        Ok(Ok(output)) => output,
+    };
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    if text.chars().count() > MAX_CONTENT_CHARS {
+        let mut truncated: String = text.chars().take(MAX_CONTENT_CHARS).collect();
+        truncated.push_str("\n… (truncated)");
+        return Ok(truncated);
+    }
+    Ok(text.into_owned())
+}
+
+/// Asks for a summary of a fetched item that leads with what to do about it
+pub fn summary_prompt(item: GhItem, number: &str, content: &str) -> Prompt {
+    Prompt::new("github", SUMMARY_TEMPLATE).var("item", item.describe()).var("number", number).var("content", content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_kind_and_number() {
+        assert_eq!(GhItem::parse("pr 42"), Some((GhItem::PullRequest, "42".to_string())));
+        assert_eq!(GhItem::parse("issue  #7 "), Some((GhItem::Issue, "7".to_string())));
+        assert_eq!(GhItem::parse("pr"), None);
+        assert_eq!(GhItem::parse("release 1"), None);
+        assert_eq!(GhItem::parse("pr 1 2"), None);
+    }
+}
@@ -0,0 +1,499 @@
+//! Shell history parsing for PetCLI
+//!
+//! This module locates the user's shell history file and parses it into individual
+//! commands. Each shell stores history differently, so there is a dedicated parser
+//! per format:
+//! - zsh: optional `extended_history` headers (`: <start>:<elapsed>;<command>`),
+//!   backslash-continued multi-line commands and metafied bytes
+//! - bash: optional `#<epoch>` timestamp lines (written when `HISTTIMEFORMAT` is
+//!   set), which also delimit multi-line commands
+//! - PowerShell: PSReadLine's `ConsoleHost_history.txt` with backtick continuations
+//! - cmd.exe: Clink's `clink_history`, with optional `|\ttime=<epoch>` lines
+//!
+//! Consider adding parsers for other shells (fish, nushell) here as well.
+
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::atuin;
+use crate::config::Config;
+use crate::config_path;
+
+/// zsh's Meta byte; the byte following it has been XORed with 0x20
+const ZSH_META: u8 = 0x83;
+
+/// A single executed command
+///
+/// Plain history files only record the command line (and sometimes when it ran);
+/// Atuin and the shell hook also know where it ran and whether it succeeded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub command: String,
+    /// When the command was run, if the history source records it
+    pub timestamp: Option<DateTime<Utc>>,
+    pub cwd: Option<String>,
+    pub exit_code: Option<i32>,
+    pub duration: Option<Duration>,
+}
+
+impl HistoryEntry {
+    pub fn new(command: &str, timestamp: Option<DateTime<Utc>>) -> Self {
+        Self {
+            command: command.trim().to_string(),
+            timestamp,
+            cwd: None,
+            exit_code: None,
+            duration: None,
+        }
+    }
+
+    /// Whether the command is known to have exited with a non-zero status
+    pub fn failed(&self) -> bool {
+        self.exit_code.is_some_and(|code| code != 0)
+    }
+
+    /// Formats the entry as a single line of prompt context
+    ///
+    /// e.g. `tar xf archive.tgz  [cwd: ~/dl, exit: 2, took: 120ms]`
+    pub fn describe(&self) -> String {
+        let mut details = Vec::new();
+        if let Some(cwd) = &self.cwd {
+            details.push(format!("cwd: {}", cwd));
+        }
+        if let Some(exit_code) = self.exit_code {
+            details.push(format!("exit: {}", exit_code));
+        }
+        if let Some(duration) = self.duration {
+            details.push(format!("took: {}ms", duration.as_millis()));
+        }
+        if details.is_empty() {
+            self.command.clone()
+        } else {
+            format!("{}  [{}]", self.command, details.join(", "))
+        }
+    }
+}
+
+/// Counts how many of the most recent commands failed in a row
+pub fn failure_streak(entries: &[HistoryEntry]) -> usize {
+    entries.iter().rev().take_while(|entry| entry.failed()).count()
+}
+
+/// Supported history file formats
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HistoryFormat {
+    Zsh,
+    Bash,
+    PowerShell,
+    Clink,
+}
+
+impl HistoryFormat {
+    /// Guesses the format of a history file from its name and contents
+    ///
+    /// The file name decides for well-known files; anything else (e.g. `~/.history`)
+    /// is treated as zsh if its first entry carries an `extended_history` header.
+    pub fn detect(path: &Path, contents: &[u8]) -> Self {
+        let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or("");
+        match file_name {
+            ".zsh_history" | ".zhistory" => HistoryFormat::Zsh,
+            ".bash_history" => HistoryFormat::Bash,
+            "ConsoleHost_history.txt" => HistoryFormat::PowerShell,
+            "clink_history" => HistoryFormat::Clink,
+            _ => {
+                let first_line = contents.split(|&b| b == b'\n').find(|line| !line.is_empty());
+                match first_line {
+                    Some(line) if parse_zsh_header(&String::from_utf8_lossy(line)).is_some() => {
+                        HistoryFormat::Zsh
+                    }
+                    _ => HistoryFormat::Bash,
+                }
+            }
+        }
+    }
+}
+
+/// Returns the shell history files to try, in order of preference
+///
+/// `$HISTFILE` comes first when it is exported. PSReadLine's
+/// `ConsoleHost_history.txt` and Clink's history for cmd.exe are preferred on
+/// Windows, where the Unix shell history files normally don't exist. On other
+/// platforms PSReadLine's file is still checked last for users running `pwsh`.
+pub fn candidate_files() -> Vec<PathBuf> {
+    let mut candidates: Vec<PathBuf> =
+        std::env::var_os("HISTFILE").filter(|path| !path.is_empty()).map(PathBuf::from).into_iter().collect();
+    let psreadline = psreadline_history_path();
+
+    #[cfg(windows)]
+    {
+        candidates.extend(psreadline.clone());
+        candidates.extend(dirs::data_local_dir().map(|dir| dir.join("clink").join("clink_history")));
+    }
+
+    if let Some(home_dir) = dirs::home_dir() {
+        candidates.push(home_dir.join(".zsh_history"));
+        candidates.push(home_dir.join(".bash_history"));
+        candidates.push(home_dir.join(".history"));
+    }
+
+    #[cfg(not(windows))]
+    candidates.extend(psreadline);
+
+    candidates
+}
+
+/// Returns the location of PSReadLine's history file for the current platform
+fn psreadline_history_path() -> Option<PathBuf> {
+    let data_dir = dirs::data_dir()?;
+    let psreadline_dir = if cfg!(windows) {
+        data_dir.join("Microsoft").join("Windows").join("PowerShell").join("PSReadLine")
+    } else {
+        data_dir.join("powershell").join("PSReadLine")
+    };
+    Some(psreadline_dir.join("ConsoleHost_history.txt"))
+}
+
+/// Reads and parses a history file, detecting its format
+pub fn load_file(path: &Path) -> std::io::Result<Vec<HistoryEntry>> {
+    let contents = std::fs::read(path)?;
+    Ok(parse(HistoryFormat::detect(path, &contents), &contents))
+}
+
+/// Reads every existing history file and merges them into one timeline
+pub fn load_merged(paths: &[PathBuf]) -> Vec<HistoryEntry> {
+    let sources = paths.iter().filter_map(|path| load_file(path).ok()).collect();
+    merge(sources)
+}
+
+/// Reads the last `limit` commands from the configured source: Atuin if enabled
+/// and available, otherwise the history files
+pub fn read_recent(config: &Config, limit: usize) -> Vec<HistoryEntry> {
+    if config.atuin_history {
+        let db_path = config.atuin_db_path.as_deref().map(config_path::expand_home).or_else(atuin::default_db_path);
+        match db_path.map(|path| atuin::load_recent(&path, limit)) {
+            Some(Ok(entries)) => return entries,
+            Some(Err(e)) => tracing::warn!("reading Atuin's history failed, using the history files: {}", e),
+            None => tracing::warn!("couldn't find Atuin's database, using the history files"),
+        }
+    }
+
+    let entries = if config.merge_history_files {
+        load_merged(&candidate_files())
+    } else {
+        candidate_files()
+            .iter()
+            .find_map(|history_file| load_file(history_file).ok())
+            .unwrap_or_default()
+    };
+    let skip = entries.len().saturating_sub(limit);
+    entries.into_iter().skip(skip).collect()
+}
+
+/// Merges history from several sources, ordered by time where known
+///
+/// Entries without a timestamp take the timestamp of the closest earlier entry in
+/// their own source (sources without any timestamps sort first), so each source
+/// keeps its internal order. Consecutive repeats of the same command are collapsed.
+pub fn merge(sources: Vec<Vec<HistoryEntry>>) -> Vec<HistoryEntry> {
+    let mut keyed = Vec::new();
+    for source in sources {
+        let mut last_seen = None;
+        for entry in source {
+            if entry.timestamp.is_some() {
+                last_seen = entry.timestamp;
+            }
+            keyed.push((last_seen, entry));
+        }
+    }
+    // Stable sort, so ties keep their source order
+    keyed.sort_by_key(|(timestamp, _)| *timestamp);
+
+    let mut merged: Vec<HistoryEntry> = Vec::with_capacity(keyed.len());
+    for (_, entry) in keyed {
+        if merged.last().is_some_and(|last| last.command == entry.command) {
+            continue;
+        }
+        merged.push(entry);
+    }
+    merged
+}
+
+/// Parses raw history file contents in the given format
+pub fn parse(format: HistoryFormat, contents: &[u8]) -> Vec<HistoryEntry> {
+    match format {
+        HistoryFormat::Zsh => parse_zsh(contents),
+        HistoryFormat::Bash => parse_bash(&String::from_utf8_lossy(contents)),
+        HistoryFormat::PowerShell => parse_powershell(&String::from_utf8_lossy(contents)),
+        HistoryFormat::Clink => parse_clink(&String::from_utf8_lossy(contents)),
+    }
+}
+
+/// Reverses zsh's metafication of bytes that would otherwise be special
+fn unmetafy(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut iter = bytes.iter();
+    while let Some(&byte) = iter.next() {
+        if byte == ZSH_META {
+            if let Some(&next) = iter.next() {
+                out.push(next ^ 0x20);
+            }
+        } else {
+            out.push(byte);
+        }
+    }
+    out
+}
+
+/// Splits an `extended_history` header off a zsh history line
+///
+/// Returns the start timestamp and the remainder of the line (the command).
+fn parse_zsh_header(line: &str) -> Option<(DateTime<Utc>, &str)> {
+    let rest = line.strip_prefix(": ")?;
+    let (header, command) = rest.split_once(';')?;
+    let (start, elapsed) = header.split_once(':')?;
+    if elapsed.is_empty() || !elapsed.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let timestamp = DateTime::from_timestamp(start.trim().parse().ok()?, 0)?;
+    Some((timestamp, command))
+}
+
+fn parse_zsh(contents: &[u8]) -> Vec<HistoryEntry> {
+    let text = String::from_utf8_lossy(&unmetafy(contents)).into_owned();
+    let mut entries = Vec::new();
+    let mut current = String::new();
+    let mut timestamp = None;
+
+    for line in text.lines() {
+        if current.is_empty() {
+            match parse_zsh_header(line) {
+                Some((start, command)) => {
+                    timestamp = Some(start);
+                    current.push_str(command);
+                }
+                None => {
+                    timestamp = None;
+                    current.push_str(line);
+                }
+            }
+        } else {
+            current.push_str(line);
+        }
+
+        // zsh writes embedded newlines as a backslash at the end of the line
+        if current.ends_with('\\') {
+            current.pop();
+            current.push('\n');
+            continue;
+        }
+
+        let entry = HistoryEntry::new(&current, timestamp);
+        if !entry.command.is_empty() {
+            entries.push(entry);
+        }
+        current.clear();
+    }
+
+    if !current.trim().is_empty() {
+        entries.push(HistoryEntry::new(&current, timestamp));
+    }
+    entries
+}
+
+/// Parses a bash `#<epoch>` timestamp line
+fn parse_bash_timestamp(line: &str) -> Option<DateTime<Utc>> {
+    let digits = line.strip_prefix('#')?;
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    DateTime::from_timestamp(digits.parse().ok()?, 0)
+}
+
+fn parse_bash(text: &str) -> Vec<HistoryEntry> {
+    let has_timestamps = text.lines().any(|line| parse_bash_timestamp(line).is_some());
+    if !has_timestamps {
+        return text
+            .lines()
+            .map(|line| HistoryEntry::new(line, None))
+            .filter(|entry| !entry.command.is_empty())
+            .collect();
+    }
+
+    // With timestamps, everything up to the next timestamp line is one command,
+    // which is how bash itself reads back multi-line entries.
+    let mut entries = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut timestamp = None;
+    let mut flush = |lines: &mut Vec<&str>, timestamp: Option<DateTime<Utc>>| {
+        let entry = HistoryEntry::new(&lines.join("\n"), timestamp);
+        if !entry.command.is_empty() {
+            entries.push(entry);
+        }
+        lines.clear();
+    };
+
+    for line in text.lines() {
+        match parse_bash_timestamp(line) {
+            Some(next) => {
+                flush(&mut current, timestamp);
+                timestamp = Some(next);
+            }
+            None => current.push(line),
+        }
+    }
+    flush(&mut current, timestamp);
+    entries
+}
+
+/// Parses PSReadLine history, joining lines continued with a trailing backtick
+fn parse_powershell(text: &str) -> Vec<HistoryEntry> {
+    let mut entries = Vec::new();
+    let mut current = String::new();
+    for line in text.lines() {
+        match line.strip_suffix('`') {
+            Some(continued) => {
+                current.push_str(continued);
+                current.push('\n');
+            }
+            None => {
+                current.push_str(line);
+                let entry = HistoryEntry::new(&current, None);
+                if !entry.command.is_empty() {
+                    entries.push(entry);
+                }
+                current.clear();
+            }
+        }
+    }
+    if !current.trim().is_empty() {
+        entries.push(HistoryEntry::new(&current, None));
+    }
+    entries
+}
+
+/// Parses Clink's history for cmd.exe
+///
+/// Clink writes one command per line. Lines starting with `|` are its own
+/// bookkeeping: `|\ttime=<epoch>` gives the time of the next command when
+/// `history.time_stamp` is on, and the rest (deleted entries, flags) are skipped.
+fn parse_clink(text: &str) -> Vec<HistoryEntry> {
+    let mut entries = Vec::new();
+    let mut timestamp = None;
+    for line in text.lines() {
+        if let Some(meta) = line.strip_prefix('|') {
+            if let Some(epoch) = meta.trim_start().strip_prefix("time=") {
+                timestamp = epoch.trim().parse().ok().and_then(|secs| DateTime::from_timestamp(secs, 0));
+            }
+            continue;
+        }
+        let entry = HistoryEntry::new(line, timestamp.take());
+        if !entry.command.is_empty() {
+            entries.push(entry);
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(entries: &[HistoryEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.command.as_str()).collect()
+    }
+
+    #[test]
+    fn zsh_plain_lines_keep_whole_command() {
+        let entries = parse_zsh(b"git commit -m \"fix bug\"\nls -la\n");
+        assert_eq!(commands(&entries), ["git commit -m \"fix bug\"", "ls -la"]);
+        assert_eq!(entries[0].timestamp, None);
+    }
+
+    #[test]
+    fn zsh_extended_history_strips_header() {
+        let entries = parse_zsh(b": 1700000000:3;cargo build; cargo test\n");
+        assert_eq!(commands(&entries), ["cargo build; cargo test"]);
+        assert_eq!(entries[0].timestamp, DateTime::from_timestamp(1_700_000_000, 0));
+    }
+
+    #[test]
+    fn zsh_joins_backslash_continuations() {
+        let entries = parse_zsh(b": 1700000000:0;for f in *.rs; do\\\n  wc -l $f\\\ndone\n: 1700000001:0;ls\n");
+        assert_eq!(commands(&entries), ["for f in *.rs; do\n  wc -l $f\ndone", "ls"]);
+    }
+
+    #[test]
+    fn zsh_unmetafies_bytes() {
+        // "→" is E2 86 92; zsh stores both trailing bytes as Meta + (byte ^ 0x20)
+        let raw = [b"echo \xe2".as_slice(), &[ZSH_META, 0x86 ^ 0x20, ZSH_META, 0x92 ^ 0x20], b"\n"].concat();
+        let entries = parse_zsh(&raw);
+        assert_eq!(commands(&entries), ["echo →"]);
+    }
+
+    #[test]
+    fn bash_without_timestamps_is_line_per_command() {
+        let entries = parse_bash("ls -la\n\ngrep -r foo .\n");
+        assert_eq!(commands(&entries), ["ls -la", "grep -r foo ."]);
+    }
+
+    #[test]
+    fn bash_timestamps_group_multiline_commands() {
+        let entries = parse_bash("#1700000000\nfor i in 1 2; do\n  echo $i\ndone\n#1700000005\nls\n");
+        assert_eq!(commands(&entries), ["for i in 1 2; do\n  echo $i\ndone", "ls"]);
+        assert_eq!(entries[1].timestamp, DateTime::from_timestamp(1_700_000_005, 0));
+    }
+
+    #[test]
+    fn bash_comment_lines_are_not_timestamps() {
+        let entries = parse_bash("# just a comment\nls\n");
+        assert_eq!(commands(&entries), ["# just a comment", "ls"]);
+    }
+
+    #[test]
+    fn powershell_joins_backtick_continuations() {
+        let entries = parse_powershell("Get-ChildItem `\n  -Recurse\nGet-Process\n");
+        assert_eq!(commands(&entries), ["Get-ChildItem \n  -Recurse", "Get-Process"]);
+    }
+
+    #[test]
+    fn clink_reads_timestamps_and_skips_bookkeeping() {
+        let entries = parse_clink("|\tflags=1\r\ndir /b\r\n|\ttime=1700000000\r\ncd C:\\Users\\pat\r\n|deleted\r\n");
+        assert_eq!(commands(&entries), ["dir /b", "cd C:\\Users\\pat"]);
+        assert_eq!(entries[0].timestamp, None);
+        assert_eq!(entries[1].timestamp, DateTime::from_timestamp(1_700_000_000, 0));
+    }
+
+    #[test]
+    fn merge_orders_by_timestamp_and_dedupes_repeats() {
+        let zsh = parse_zsh(b": 100:0;git status\n: 300:0;cargo test\n: 301:0;cargo test\n");
+        let bash = parse_bash("#200\nls\n#400\ngit status\n");
+        let merged = merge(vec![zsh, bash]);
+        assert_eq!(commands(&merged), ["git status", "ls", "cargo test", "git status"]);
+    }
+
+    #[test]
+    fn merge_keeps_untimestamped_sources_in_order() {
+        let plain = parse_bash("vim notes.md\nmake\n");
+        let zsh = parse_zsh(b": 100:0;ls\n");
+        let merged = merge(vec![zsh, plain]);
+        assert_eq!(commands(&merged), ["vim notes.md", "make", "ls"]);
+    }
+
+    #[test]
+    fn detects_zsh_format_in_generic_history_file() {
+        let path = Path::new("/home/user/.history");
+        assert_eq!(HistoryFormat::detect(path, b": 1700000000:0;ls\n"), HistoryFormat::Zsh);
+        assert_eq!(HistoryFormat::detect(path, b"ls\n"), HistoryFormat::Bash);
+    }
+
+    #[test]
+    fn detects_history_files_by_name_with_native_separators() {
+        let appdata: PathBuf = ["C:", "Users", "pat", "AppData"].iter().collect();
+        let psreadline = appdata.join("Roaming").join("Microsoft").join("Windows").join("PowerShell");
+        let psreadline = psreadline.join("PSReadLine").join("ConsoleHost_history.txt");
+        let clink = appdata.join("Local").join("clink").join("clink_history");
+        assert_eq!(HistoryFormat::detect(&psreadline, b"ls\n"), HistoryFormat::PowerShell);
+        assert_eq!(HistoryFormat::detect(&clink, b"dir\n"), HistoryFormat::Clink);
+    }
+}
@@ -0,0 +1,97 @@
+//! Which of the loaded commands reach prompts
+//!
+//! The history is full of noise: the same `git status` twenty times, and
+//! `ls`, `cd` and `clear` in between everything else. Before commands are put
+//! in a prompt, those on the `history_ignore` list are dropped (unless they
+//! failed, which is worth hearing about), repeats are collapsed into their
+//! latest run, and if more than `history_prompt_commands` are left, the ones
+//! to keep are picked by how recent and how frequent they are. The commands
+//! the pet watches for patterns and habits are not filtered.
+
+use std::collections::HashMap;
+
+use crate::history::HistoryEntry;
+
+/// How many places newer each extra run of a command makes it count as
+const FREQUENCY_WEIGHT: usize = 3;
+
+/// The `history_*` filter settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryFilter {
+    /// Keep only the latest run of each command
+    pub dedupe: bool,
+    /// Commands that are dropped, with any arguments
+    pub ignore: Vec<String>,
+    /// Commands given to prompts at most
+    pub slots: usize,
+}
+
+impl HistoryFilter {
+    /// Whether `command` is on the ignore list: it is one of the entries, or
+    /// starts with one followed by arguments
+    pub fn ignores(&self, command: &str) -> bool {
+        let command = command.trim();
+        self.ignore.iter().map(|ignored| ignored.trim()).filter(|ignored| !ignored.is_empty()).any(|ignored| {
+            command
+                .strip_prefix(ignored)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+        })
+    }
+
+    /// The commands worth a prompt's limited space, oldest first
+    pub fn apply(&self, entries: &[HistoryEntry]) -> Vec<HistoryEntry> {
+        let kept: Vec<&HistoryEntry> = entries.iter().filter(|entry| entry.failed() || !self.ignores(&entry.command)).collect();
+
+        // The latest run of each command and how often it ran
+        let mut picked: Vec<(usize, usize)> = if self.dedupe {
+            let mut runs: HashMap<&str, (usize, usize)> = HashMap::new();
+            for (index, entry) in kept.iter().enumerate() {
+                let run = runs.entry(entry.command.trim()).or_insert((index, 0));
+                *run = (index, run.1 + 1);
+            }
+            runs.into_values().collect()
+        } else {
+            (0..kept.len()).map(|index| (index, 1)).collect()
+        };
+
+        if picked.len() > self.slots {
+            picked.sort_by_key(|&(latest, runs)| std::cmp::Reverse(latest + FREQUENCY_WEIGHT * (runs - 1)));
+            picked.truncate(self.slots);
+        }
+        picked.sort_by_key(|&(latest, _)| latest);
+        picked.into_iter().map(|(latest, _)| kept[latest].clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn noise_is_dropped_and_frequent_commands_keep_their_place() {
+        let filter = HistoryFilter { dedupe: true, ignore: vec!["ls".to_string(), "git status".to_string()], slots: 3 };
+        assert!(filter.ignores("ls") && filter.ignores("ls -la") && filter.ignores("git status -s"));
+        assert!(!filter.ignores("lsblk") && !filter.ignores("git stash"));
+
+        let entry = |command: &str, exit_code| HistoryEntry { exit_code, ..HistoryEntry::new(command, None) };
+        let history = [
+            entry("cargo test", None),
+            entry("ls", None),
+            entry("vim src/main.rs", None),
+            entry("cargo test", None),
+            entry("git status", None),
+            entry("cargo test", None),
+            entry("ls missing/", Some(2)),
+            entry("git add .", None),
+            entry("git commit", None),
+        ];
+        let commands = |filter: &HistoryFilter| filter.apply(&history).into_iter().map(|entry| entry.command).collect::<Vec<_>>();
+        assert_eq!(commands(&filter), ["cargo test", "git add .", "git commit"]);
+
+        let roomy = HistoryFilter { slots: 10, ..filter.clone() };
+        assert_eq!(commands(&roomy), ["vim src/main.rs", "cargo test", "ls missing/", "git add .", "git commit"]);
+
+        let everything = HistoryFilter { dedupe: false, ignore: Vec::new(), slots: 10 };
+        assert_eq!(commands(&everything).len(), history.len());
+    }
+}
@@ -0,0 +1,146 @@
+//! Fuzzy search of the shell history for the Ctrl+R overlay
+//!
+//! Ctrl+R lists the commands in the shell history, newest first and each only
+//! once, narrowed down as you type by nucleo's fuzzy matcher. Enter puts the
+//! selected command in the input as `$ <command>`, for the pet to comment on
+//! like any command you ran; Ctrl+E asks the pet to explain it and Ctrl+F to
+//! improve it.
+
+use nucleo_matcher::pattern::{CaseMatching, Normalization, Pattern};
+use nucleo_matcher::{Config, Matcher, Utf32Str};
+use std::collections::HashSet;
+
+use crate::history::HistoryEntry;
+
+/// History entries read for a search
+pub const LIMIT: usize = 5_000;
+/// Matches listed at most, best first
+const MAX_MATCHES: usize = 200;
+
+/// The overlay's state: the query and what it matches
+pub struct HistorySearch {
+    pub query: String,
+    /// Distinct commands, newest first
+    commands: Vec<String>,
+    /// Indices into `commands` of the matches, best first, with the
+    /// positions of the matched characters
+    pub matches: Vec<(usize, Vec<u32>)>,
+    pub selected: usize,
+    matcher: Matcher,
+}
+
+impl HistorySearch {
+    pub fn new(history: &[HistoryEntry]) -> Self {
+        let mut seen = HashSet::new();
+        let commands = history
+            .iter()
+            .rev()
+            .map(|entry| entry.command.trim())
+            .filter(|command| !command.is_empty() && seen.insert(*command))
+            .map(str::to_string)
+            .collect();
+        let mut search = Self { query: String::new(), commands, matches: Vec::new(), selected: 0, matcher: Matcher::new(Config::DEFAULT) };
+        search.refresh();
+        search
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.refresh();
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.refresh();
+    }
+
+    pub fn up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn down(&mut self) {
+        self.selected = (self.selected + 1).min(self.matches.len().saturating_sub(1));
+    }
+
+    pub fn command(&self, index: usize) -> &str {
+        &self.commands[index]
+    }
+
+    pub fn selected(&self) -> Option<&str> {
+        self.matches.get(self.selected).map(|(index, _)| self.command(*index))
+    }
+
+    /// How many distinct commands are searched
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    fn refresh(&mut self) {
+        self.selected = 0;
+        if self.query.trim().is_empty() {
+            self.matches = (0..self.commands.len().min(MAX_MATCHES)).map(|index| (index, Vec::new())).collect();
+            return;
+        }
+        let pattern = Pattern::parse(&self.query, CaseMatching::Smart, Normalization::Smart);
+        let mut buffer = Vec::new();
+        let mut matches: Vec<(u32, usize, Vec<u32>)> = Vec::new();
+        for (index, command) in self.commands.iter().enumerate() {
+            let mut indices = Vec::new();
+            if let Some(score) = pattern.indices(Utf32Str::new(command, &mut buffer), &mut self.matcher, &mut indices) {
+                indices.sort_unstable();
+                indices.dedup();
+                matches.push((score, index, indices));
+            }
+        }
+        // Stable, so newer commands come first among equal scores
+        matches.sort_by_key(|(score, _, _)| std::cmp::Reverse(*score));
+        self.matches = matches.into_iter().take(MAX_MATCHES).map(|(_, index, indices)| (index, indices)).collect();
+    }
+}
+
+/// What to ask the pet about `command`: to explain it or to improve it
+pub fn question(command: &str, improve: bool) -> String {
+    if improve {
+        format!("How could I improve this command? Suggest a better version if there is one: `{}`", command)
+    } else {
+        format!("Explain what this command does, part by part: `{}`", command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_fuzzy_matched_newest_first() {
+        let history: Vec<HistoryEntry> = ["git status", "docker compose up -d", "git stash", "ls -la", "git status"]
+            .iter()
+            .map(|command| HistoryEntry::new(command, None))
+            .collect();
+        let mut search = HistorySearch::new(&history);
+        assert_eq!(search.len(), 4);
+        assert_eq!(search.selected(), Some("git status"));
+
+        for c in "dcu".chars() {
+            search.push(c);
+        }
+        assert_eq!(search.matches.len(), 1);
+        assert_eq!(search.selected(), Some("docker compose up -d"));
+        assert_eq!(search.matches[0].1, [0, 7, 15]);
+
+        search.pop();
+        search.pop();
+        search.pop();
+        search.push('s');
+        search.push('t');
+        assert_eq!(search.selected(), Some("git status"));
+        search.down();
+        assert_eq!(search.selected(), Some("git stash"));
+        search.push('z');
+        assert_eq!(search.selected(), None);
+    }
+}
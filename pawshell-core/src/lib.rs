@@ -0,0 +1,173 @@
+//! pawshell-core - the terminal pet companion without the terminal
+//!
+//! Everything that makes up the pet apart from its TUI: its state and mood,
+//! the LLM backends it talks through, shell history parsing, configuration and
+//! persistence. The `pawshell` binary is a thin ratatui front end on top of
+//! this crate, and other tools can embed the companion the same way:
+//!
+//! ```no_run
+//! use pawshell_core::config::Config;
+//! use pawshell_core::storage;
+//!
+//! let config = Config::load();
+//! let (mut store, _warning) = storage::open(&config);
+//! let mut state = store.load().unwrap_or_default();
+//! state.cheer_up(0.1);
+//! store.save(&state).unwrap();
+//! ```
+//!
+//! Modules:
+//!
+//! - pet: Pet state and chat history
+//! - threads: Conversation threads forked with `/fork`
+//! - chat_sessions: Titled chat sessions and the `/sessions` browser
+//! - mood: How the pet's mood rises and falls
+//! - usage: Session stats, reply times and the hours the pet is used
+//! - perf: Time to first token and tokens per second, per reply and per model
+//! - compare: Two models answering the same question side by side for `/compare`
+//! - budget: Estimated spending on paid backends and daily/monthly budgets
+//! - llm: Language model interface, prompt building and the OpenAI backend
+//! - ollama: Ollama LLM backend implementation
+//! - templates: Named prompt templates, overridable in the config
+//! - few_shot: Example exchanges from the config that show the pet how to answer
+//! - config: Configuration management
+//! - config_path: Configuration and data directory handling, profiles
+//! - project: Per-project settings from `.pawshell.toml`
+//! - project_scan: What the launch directory holds, for project-aware greetings
+//! - locale: Built-in pet phrases in the configured language
+//! - art: Pet ASCII art gallery, art files and mood poses
+//! - adoption: Randomized litters to adopt a pet from, and its pedigree
+//! - history: Shell history file parsing (zsh, bash, PowerShell)
+//! - history_search: Fuzzy search of the shell history for the Ctrl+R overlay
+//! - history_filter: Dedupe and noise filtering of the commands given to prompts
+//! - atuin: Atuin shell history database integration
+//! - analytics: Command frequency analysis for `/analyze`
+//! - patterns: Detection of repetitive workflows for proactive suggestions
+//! - exec: Confirmed execution of suggested or typed commands
+//! - safety: Detection of destructive command patterns
+//! - workspace: Working directory and git context for prompts
+//! - commit: Commit message drafts from the staged diff
+//! - review: Chunked code reviews of a file and excerpt highlighting
+//! - github: Pull request and issue summaries through the GitHub CLI
+//! - translate: Translations of text or the last reply for `/translate`
+//! - dotfiles: Suggestions for shell and editor dotfiles, saved as annotated copies
+//! - shell_script: Shell scripts written, refined and saved with `/script`
+//! - cron: Crontab and systemd timer schedules, checked locally for `/cron`
+//! - regex_lab: Live regex matching and explanations for `/regex`
+//! - playground: Running jq and awk expressions on sample input for `/jq` and `/awk`
+//! - card: Shareable pet cards as ANSI text and PNG images
+//! - environment: OS, shell and installed tool detection for prompts
+//! - zoxide: Most visited directories from zoxide for prompts
+//! - weather: Local weather for greetings, idle art and prompts
+//! - tldr: Offline tldr pages and man page excerpts for command questions
+//! - sysmon: CPU, memory and disk load monitoring
+//! - battery: Laptop battery level and charging state
+//! - connectivity: Backend reachability probes for the offline switch
+//! - speech: Text-to-speech for replies
+//! - dictation: Recording and transcribing spoken questions
+//! - portrait: Painted pet portraits and drawing pictures in the terminal
+//! - accessories: Unlockable things the pet wears, layered onto its art
+//! - report: Weekly terminal habits reports
+//! - skills: Learning goals, the skill tree and quizzes
+//! - flashcards: Spaced-repetition flashcards from tips and struggles
+//! - privacy: Redaction of secrets from prompts sent to remote backends
+//! - bookmarks: Saved favorite commands
+//! - feedback: 👍/👎 reactions to replies and the preferences they add to prompts
+//! - macros: Recorded input macros replayed with one key
+//! - line_editor: The chat input line with a movable cursor
+//! - vi_mode: vi's normal mode for the input line
+//! - reminders: Timed reminders and the to-do list
+//! - pomodoro: Pomodoro work/break timers and the daily focus log
+//! - breaks: Screen time, break nudges and the daily break log
+//! - focus: Do-not-disturb focus sessions for `/focus`
+//! - aliases: Managed alias file for adopted suggestions
+//! - storage: Pet state persistence (state file or SQLite database)
+//! - sync_store: Sync-friendly per-machine state files
+//! - shared_store: One locked state file shared by a team on one machine
+//! - retention: Archival of old chat history
+//! - backup: Rotating state backups and `pawshell restore`
+//! - transcript: Markdown transcripts of each session
+//! - crypto: Encryption of chat history at rest
+//! - bundle: Pet export/import bundles
+//! - plugins: WebAssembly plugins with message, command, tick and context hooks
+//! - scripting: Rhai scripts for custom reactions and commands
+//! - session: A pet without a UI, for the daemon and one-off questions
+//! - error: Categorized backend and storage errors with suggested fixes
+//! - clock: The current time and random numbers, fakeable for tests
+
+pub mod pet;
+pub mod threads;
+pub mod chat_sessions;
+pub mod mood;
+pub mod usage;
+pub mod perf;
+pub mod compare;
+pub mod budget;
+pub mod llm;
+pub mod ollama;
+pub mod templates;
+pub mod few_shot;
+pub mod config;
+pub mod config_path;
+pub mod project;
+pub mod project_scan;
+pub mod locale;
+pub mod art;
+pub mod adoption;
+pub mod history;
+pub mod history_filter;
+pub mod history_search;
+pub mod atuin;
+pub mod analytics;
+pub mod patterns;
+pub mod exec;
+pub mod safety;
+pub mod workspace;
+pub mod commit;
+pub mod review;
+pub mod github;
+pub mod translate;
+pub mod dotfiles;
+pub mod shell_script;
+pub mod cron;
+pub mod regex_lab;
+pub mod playground;
+pub mod card;
+pub mod environment;
+pub mod zoxide;
+pub mod weather;
+pub mod tldr;
+pub mod sysmon;
+pub mod battery;
+pub mod connectivity;
+pub mod speech;
+pub mod dictation;
+pub mod portrait;
+pub mod accessories;
+pub mod report;
+pub mod skills;
+pub mod flashcards;
+pub mod privacy;
+pub mod bookmarks;
+pub mod feedback;
+pub mod macros;
+pub mod line_editor;
+pub mod vi_mode;
+pub mod reminders;
+pub mod pomodoro;
+pub mod breaks;
+pub mod focus;
+pub mod aliases;
+pub mod storage;
+pub mod sync_store;
+pub mod shared_store;
+pub mod retention;
+pub mod backup;
+pub mod transcript;
+pub mod crypto;
+pub mod bundle;
+pub mod plugins;
+pub mod scripting;
+pub mod session;
+pub mod error;
+pub mod clock;
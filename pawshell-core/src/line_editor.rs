@@ -0,0 +1,568 @@
+//! The chat input line and the cursor in it
+//!
+//! The input is a single line of text with a cursor that can be anywhere in
+//! it, counted in characters rather than bytes. Besides typing and deleting,
+//! it knows the two kinds of words the editing modes move by: Emacs words are
+//! runs of letters and digits, and vi words are runs of word characters or of
+//! punctuation (or, for vi's WORDs, of anything but whitespace).
+//!
+//! Edits can be undone and redone. A run of typed characters is one undo
+//! step up to the next space or cursor move, and a vi command together with
+//! the insert it starts is one step too (see `begin_group`).
+//!
+//! Text cut with the kill keys goes to a `KillRing`, kept across lines, so it
+//! can be yanked back like in readline: kills in a row collect into one
+//! entry, and a yank right after a yank swaps in the kill before it.
+
+use std::ops::Range;
+
+/// Undo steps kept per line
+const UNDO_LIMIT: usize = 100;
+/// Kills kept for yanking
+const KILL_RING_SIZE: usize = 20;
+
+/// The text and cursor before an edit
+type Snapshot = (String, usize);
+
+/// The input line
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LineEditor {
+    text: String,
+    /// Characters before the cursor
+    cursor: usize,
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    /// The last edit was typing that the next character joins
+    typing: bool,
+    /// Where a group of edits started; they're undone together
+    group: Option<Snapshot>,
+}
+
+impl LineEditor {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Characters in the line
+    pub fn len(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Replaces the line, with the cursor at its end
+    pub fn set(&mut self, text: impl Into<String>) {
+        self.record(false);
+        self.text = text.into();
+        self.cursor = self.len();
+    }
+
+    /// Empties the line for the next message, forgetting its undo history
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn chars(&self) -> Vec<char> {
+        self.text.chars().collect()
+    }
+
+    /// The character under the cursor, if it isn't at the end
+    pub fn current(&self) -> Option<char> {
+        self.text.chars().nth(self.cursor)
+    }
+
+    /// Byte offset of a character position
+    fn byte(&self, position: usize) -> usize {
+        self.text.char_indices().nth(position).map_or(self.text.len(), |(index, _)| index)
+    }
+
+    /// Moves the cursor, at most to the end
+    pub fn move_to(&mut self, position: usize) {
+        self.cursor = position.min(self.len());
+        self.typing = false;
+    }
+
+    pub fn left(&mut self) {
+        self.move_to(self.cursor.saturating_sub(1));
+    }
+
+    pub fn right(&mut self) {
+        self.move_to(self.cursor + 1);
+    }
+
+    pub fn home(&mut self) {
+        self.move_to(0);
+    }
+
+    pub fn end(&mut self) {
+        self.move_to(self.len());
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.record(!c.is_whitespace());
+        let at = self.byte(self.cursor);
+        self.text.insert(at, c);
+        self.cursor += 1;
+    }
+
+    pub fn insert_str(&mut self, text: &str) {
+        self.record(false);
+        let at = self.byte(self.cursor);
+        self.text.insert_str(at, text);
+        self.cursor += text.chars().count();
+    }
+
+    /// Adds text at the end, like dictation does
+    pub fn append(&mut self, text: &str) {
+        self.end();
+        self.insert_str(text);
+    }
+
+    /// Removes the characters in `range` and returns them; the cursor stays
+    /// on the same character, or goes to where the range was
+    pub fn delete(&mut self, range: Range<usize>) -> String {
+        let end = range.end.min(self.len());
+        let start = range.start.min(end);
+        if start == end {
+            return String::new();
+        }
+        self.record(false);
+        let removed: String = self.text.drain(self.byte(start)..self.byte(end)).collect();
+        if self.cursor >= end {
+            self.cursor -= end - start;
+        } else if self.cursor > start {
+            self.cursor = start;
+        }
+        removed
+    }
+
+    /// Deletes the character before the cursor
+    pub fn backspace(&mut self) -> String {
+        self.delete(self.cursor.saturating_sub(1)..self.cursor)
+    }
+
+    /// Deletes the character under the cursor
+    pub fn delete_char(&mut self) -> String {
+        self.delete(self.cursor..self.cursor + 1)
+    }
+
+    /// Swaps the characters around the cursor, or the last two at the end
+    pub fn transpose(&mut self) {
+        let mut chars = self.chars();
+        if chars.len() < 2 || self.cursor == 0 {
+            return;
+        }
+        let at = self.cursor.min(chars.len() - 1);
+        self.record(false);
+        chars.swap(at - 1, at);
+        self.text = chars.into_iter().collect();
+        self.cursor = at + 1;
+    }
+
+    /// Replaces the characters in `range` with what `change` makes of them
+    pub fn map(&mut self, range: Range<usize>, change: impl Fn(&str) -> String) {
+        let cursor = self.cursor;
+        let start = range.start;
+        let own_group = self.group.is_none();
+        self.begin_group();
+        let text = change(&self.delete(range));
+        self.cursor = start;
+        self.insert_str(&text);
+        self.cursor = cursor.min(self.len());
+        if own_group {
+            self.end_group();
+        }
+    }
+
+    /// Remembers the line before an edit, unless the edit is typing that
+    /// continues the last one or part of a group
+    fn record(&mut self, typing: bool) {
+        if self.group.is_none() && !(typing && self.typing) {
+            let snapshot = (self.text.clone(), self.cursor);
+            self.push_undo(snapshot);
+        }
+        self.typing = typing;
+    }
+
+    fn push_undo(&mut self, snapshot: Snapshot) {
+        self.undo.push(snapshot);
+        if self.undo.len() > UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// Starts collecting edits into one undo step, until `end_group`
+    pub fn begin_group(&mut self) {
+        if self.group.is_none() {
+            self.group = Some((self.text.clone(), self.cursor));
+            self.typing = false;
+        }
+    }
+
+    /// Makes the edits since `begin_group` one undo step, if they changed anything
+    pub fn end_group(&mut self) {
+        if let Some(snapshot) = self.group.take() {
+            if snapshot.0 != self.text {
+                self.push_undo(snapshot);
+            }
+        }
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty() || self.group.as_ref().is_some_and(|(text, _)| *text != self.text)
+    }
+
+    /// Goes back to before the last edit, returning whether there was one
+    pub fn undo(&mut self) -> bool {
+        self.end_group();
+        let Some((text, cursor)) = self.undo.pop() else {
+            return false;
+        };
+        self.redo.push((std::mem::replace(&mut self.text, text), self.cursor));
+        self.cursor = cursor;
+        self.typing = false;
+        true
+    }
+
+    /// Makes the last undone edit again, returning whether there was one
+    pub fn redo(&mut self) -> bool {
+        let Some((text, cursor)) = self.redo.pop() else {
+            return false;
+        };
+        self.undo.push((std::mem::replace(&mut self.text, text), self.cursor));
+        self.cursor = cursor;
+        self.typing = false;
+        true
+    }
+}
+
+/// Text cut from the input line, newest last
+#[derive(Debug, Clone, Default)]
+pub struct KillRing {
+    kills: Vec<String>,
+    /// The line right after the last kill or yank, to tell whether the
+    /// next one follows straight on
+    after: Option<Snapshot>,
+    /// Where the last yank put its text, and which kill it was
+    yanked: Option<(Range<usize>, usize)>,
+}
+
+impl KillRing {
+    fn follows(&self, editor: &LineEditor) -> bool {
+        self.after.as_ref().is_some_and(|(text, cursor)| text == editor.text() && *cursor == editor.cursor())
+    }
+
+    fn done(&mut self, editor: &LineEditor, yanked: Option<(Range<usize>, usize)>) {
+        self.after = Some((editor.text().to_string(), editor.cursor()));
+        self.yanked = yanked;
+    }
+
+    /// Cuts `range` from the line onto the ring; a kill right after another
+    /// adds to its entry, in front of it if it was before the cursor
+    pub fn kill(&mut self, editor: &mut LineEditor, range: Range<usize>) {
+        let backwards = range.end <= editor.cursor();
+        let joins = self.follows(editor) && self.yanked.is_none();
+        let text = editor.delete(range);
+        if text.is_empty() {
+            return;
+        }
+        match self.kills.last_mut().filter(|_| joins) {
+            Some(newest) if backwards => newest.insert_str(0, &text),
+            Some(newest) => newest.push_str(&text),
+            None => {
+                self.kills.push(text);
+                if self.kills.len() > KILL_RING_SIZE {
+                    self.kills.remove(0);
+                }
+            }
+        }
+        self.done(editor, None);
+    }
+
+    /// Puts the newest kill in at the cursor, returning whether there was one
+    pub fn yank(&mut self, editor: &mut LineEditor) -> bool {
+        let Some(text) = self.kills.last() else {
+            return false;
+        };
+        let start = editor.cursor();
+        editor.insert_str(text);
+        let yanked = (start..editor.cursor(), self.kills.len() - 1);
+        self.done(editor, Some(yanked));
+        true
+    }
+
+    /// Right after a yank, swaps what it put in for the kill before that one
+    pub fn yank_pop(&mut self, editor: &mut LineEditor) -> bool {
+        let Some((range, index)) = self.yanked.clone().filter(|_| self.follows(editor)) else {
+            return false;
+        };
+        let index = (index + self.kills.len() - 1) % self.kills.len();
+        editor.begin_group();
+        editor.delete(range.clone());
+        editor.move_to(range.start);
+        editor.insert_str(&self.kills[index]);
+        editor.end_group();
+        let yanked = (range.start..editor.cursor(), index);
+        self.done(editor, Some(yanked));
+        true
+    }
+}
+
+/// How vi sorts characters into words
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Space,
+    Word,
+    Punctuation,
+}
+
+fn class(c: char, big: bool) -> Class {
+    if c.is_whitespace() {
+        Class::Space
+    } else if big || c.is_alphanumeric() || c == '_' {
+        Class::Word
+    } else {
+        Class::Punctuation
+    }
+}
+
+/// Where the next vi word starts after `position` (vi's `w`, or `W` if `big`)
+pub fn next_word_start(chars: &[char], position: usize, big: bool) -> usize {
+    let mut i = position;
+    if let Some(&c) = chars.get(i) {
+        let start = class(c, big);
+        while i < chars.len() && start != Class::Space && class(chars[i], big) == start {
+            i += 1;
+        }
+    }
+    while i < chars.len() && chars[i].is_whitespace() {
+        i += 1;
+    }
+    i
+}
+
+/// Where the vi word before `position` starts (`b` or `B`)
+pub fn prev_word_start(chars: &[char], position: usize, big: bool) -> usize {
+    let mut i = position.min(chars.len());
+    while i > 0 && chars[i - 1].is_whitespace() {
+        i -= 1;
+    }
+    if i == 0 {
+        return 0;
+    }
+    let word = class(chars[i - 1], big);
+    while i > 0 && class(chars[i - 1], big) == word {
+        i -= 1;
+    }
+    i
+}
+
+/// The last character of the vi word after `position` (`e` or `E`)
+pub fn word_end(chars: &[char], position: usize, big: bool) -> usize {
+    let mut i = position + 1;
+    while i < chars.len() && chars[i].is_whitespace() {
+        i += 1;
+    }
+    if i >= chars.len() {
+        return chars.len().saturating_sub(1);
+    }
+    let word = class(chars[i], big);
+    while i + 1 < chars.len() && class(chars[i + 1], big) == word {
+        i += 1;
+    }
+    i
+}
+
+/// The vi word (or run of whitespace) around `position`, for `iw`; with
+/// `around`, the whitespace after it too, or else before it, for `aw`
+pub fn word_at(chars: &[char], position: usize, big: bool, around: bool) -> Option<Range<usize>> {
+    let here = class(*chars.get(position)?, big);
+    let mut start = position;
+    while start > 0 && class(chars[start - 1], big) == here {
+        start -= 1;
+    }
+    let mut end = position + 1;
+    while end < chars.len() && class(chars[end], big) == here {
+        end += 1;
+    }
+    if around && here != Class::Space {
+        if end < chars.len() && chars[end].is_whitespace() {
+            while end < chars.len() && chars[end].is_whitespace() {
+                end += 1;
+            }
+        } else {
+            while start > 0 && chars[start - 1].is_whitespace() {
+                start -= 1;
+            }
+        }
+    }
+    Some(start..end)
+}
+
+/// Where the Emacs word after `position` ends (`M-f`)
+pub fn emacs_word_right(chars: &[char], position: usize) -> usize {
+    let mut i = position.min(chars.len());
+    while i < chars.len() && !chars[i].is_alphanumeric() {
+        i += 1;
+    }
+    while i < chars.len() && chars[i].is_alphanumeric() {
+        i += 1;
+    }
+    i
+}
+
+/// Where the Emacs word before `position` starts (`M-b`)
+pub fn emacs_word_left(chars: &[char], position: usize) -> usize {
+    let mut i = position.min(chars.len());
+    while i > 0 && !chars[i - 1].is_alphanumeric() {
+        i -= 1;
+    }
+    while i > 0 && chars[i - 1].is_alphanumeric() {
+        i -= 1;
+    }
+    i
+}
+
+/// Where the whitespace-separated word before `position` starts (`C-w`)
+pub fn shell_word_left(chars: &[char], position: usize) -> usize {
+    let mut i = position.min(chars.len());
+    while i > 0 && chars[i - 1].is_whitespace() {
+        i -= 1;
+    }
+    while i > 0 && !chars[i - 1].is_whitespace() {
+        i -= 1;
+    }
+    i
+}
+
+/// What's between the pair of `open` and `close` around `position`, for vi's
+/// `i(` and `i"`; with `around`, the pair itself too
+pub fn pair_at(chars: &[char], position: usize, open: char, close: char, around: bool) -> Option<Range<usize>> {
+    let (start, end) = if open == close {
+        // Quotes don't nest, so they pair up from the start of the line; the
+        // first pair that doesn't end before the cursor is the one
+        let quotes: Vec<usize> = chars.iter().enumerate().filter(|(_, &c)| c == open).map(|(i, _)| i).collect();
+        let pair = quotes.chunks_exact(2).find(|pair| pair[1] >= position)?;
+        (pair[0], pair[1])
+    } else {
+        let mut depth = 0;
+        let mut start = None;
+        for i in (0..=position.min(chars.len().checked_sub(1)?)).rev() {
+            if chars[i] == close && i != position {
+                depth += 1;
+            } else if chars[i] == open {
+                if depth == 0 {
+                    start = Some(i);
+                    break;
+                }
+                depth -= 1;
+            }
+        }
+        let start = start?;
+        let mut depth = 0;
+        let mut end = None;
+        for (i, &c) in chars.iter().enumerate().skip(start + 1) {
+            if c == open {
+                depth += 1;
+            } else if c == close {
+                if depth == 0 {
+                    end = Some(i);
+                    break;
+                }
+                depth -= 1;
+            }
+        }
+        (start, end?)
+    };
+    Some(if around { start..end + 1 } else { start + 1..end })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_cursor_moves_and_edits_by_characters_and_words() {
+        let mut editor = LineEditor::default();
+        editor.set("grep -rn 'foo' src");
+        editor.move_to(5);
+        editor.insert_str("-i ");
+        assert_eq!(editor.text(), "grep -i -rn 'foo' src");
+        assert_eq!(editor.cursor(), 8);
+        assert_eq!(editor.delete(0..5), "grep ");
+        assert_eq!(editor.cursor(), 3);
+        editor.set("añb");
+        editor.move_to(2);
+        editor.backspace();
+        editor.transpose();
+        assert_eq!(editor.text(), "ba");
+
+        editor.clear();
+        for c in "ls -la".chars() {
+            editor.insert(c);
+        }
+        editor.home();
+        editor.delete_char();
+        editor.insert_str("eza");
+        assert!(editor.undo() && editor.undo());
+        assert_eq!(editor.text(), "ls -la");
+        assert!(editor.undo());
+        assert_eq!(editor.text(), "ls ");
+        assert!(editor.redo());
+        assert_eq!((editor.text(), editor.cursor()), ("ls -la", 0));
+        editor.begin_group();
+        editor.delete(0..2);
+        editor.insert_str("exa");
+        editor.end_group();
+        assert!(editor.undo());
+        assert_eq!(editor.text(), "ls -la");
+        assert!(editor.redo());
+        assert_eq!(editor.text(), "exa -la");
+        editor.insert('!');
+        assert!(!editor.redo());
+
+        let mut ring = KillRing::default();
+        editor.set("docker run --rm alpine sh");
+        editor.move_to(11);
+        ring.kill(&mut editor, 11..25);
+        let word = shell_word_left(&editor.chars(), 11);
+        ring.kill(&mut editor, word..11);
+        assert_eq!(editor.text(), "docker ");
+        editor.insert_str("compose up ");
+        ring.kill(&mut editor, 0..6);
+        editor.home();
+        assert!(ring.yank(&mut editor));
+        assert_eq!(editor.text(), "docker compose up ");
+        assert!(ring.yank_pop(&mut editor));
+        assert_eq!(editor.text(), "run --rm alpine sh compose up ");
+        editor.left();
+        assert!(!ring.yank_pop(&mut editor));
+
+        let chars: Vec<char> = "git commit -m \"fix(ui): wrap\"".chars().collect();
+        assert_eq!(next_word_start(&chars, 0, false), 4);
+        assert_eq!(next_word_start(&chars, 11, false), 12);
+        assert_eq!(next_word_start(&chars, 14, true), 24);
+        assert_eq!(next_word_start(&chars, 24, true), chars.len());
+        assert_eq!(prev_word_start(&chars, 14, false), 12);
+        assert_eq!(word_end(&chars, 4, false), 9);
+        assert_eq!(word_at(&chars, 5, false, true), Some(4..11));
+        assert_eq!(emacs_word_left(&chars, 11), 4);
+        assert_eq!(emacs_word_right(&chars, 10), 13);
+        assert_eq!(shell_word_left(&chars, 14), 11);
+        assert_eq!(pair_at(&chars, 20, '(', ')', false), Some(19..21));
+        assert_eq!(pair_at(&chars, 20, '"', '"', true), Some(14..29));
+        assert_eq!(pair_at(&chars, 28, '"', '"', false), Some(15..28));
+        assert_eq!(pair_at(&chars, 2, '(', ')', false), None);
+    }
+}
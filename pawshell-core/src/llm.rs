@@ -0,0 +1,561 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use std::future::Future;
+use std::time::Duration;
+
+use crate::bookmarks::Bookmark;
+use crate::config::{Config, LLMProvider};
+use crate::error::LlmError;
+use crate::few_shot::{self, Example};
+use crate::history::{self, HistoryEntry};
+use crate::environment::SystemEnvironment;
+use crate::workspace::WorkspaceContext;
+use crate::zoxide::FrequentDirectories;
+use crate::weather::Weather;
+use crate::battery::Battery;
+use crate::ollama::OllamaBackend;
+use crate::pet::{ChatMessage, PetState};
+use crate::templates::Prompt;
+use crate::{bookmarks, feedback, skills, tldr};
+
+/// Instructions every conversation starts with, unless `system_prompt` replaces them
+pub const DEFAULT_SYSTEM_PROMPT: &str = "You are a knowledgeable terminal companion with a friendly personality. You understand that your user is an experienced developer who is newer to Linux and interested in learning Vim. As an expert in shell commands and workflows, your primary focus is providing practical, intelligent suggestions for improving terminal usage. When analyzing command history, suggest optimizations like:
+- More efficient command combinations using pipes and redirections
+- Modern alternatives to traditional tools
+- Helpful aliases or shell functions
+- Better workflows and time-saving techniques
+- Beginner-friendly Vim tips and Linux command explanations when relevant
+
+Keep responses concise and focused on technical value, while maintaining a light, approachable tone. You can occasionally use cat-themed expressions or emojis when appropriate, but prioritize delivering useful terminal insights. Balance between general workflow improvements and specific Linux/Vim learning opportunities based on the context. If you notice patterns in command usage that could be improved, share your expertise in a clear, professional way.";
+
+/// The system prompt for the configured (or project's) prompt, persona and language
+pub fn system_prompt(config: &Config) -> String {
+    let mut prompt = config.system_prompt.as_deref().unwrap_or(DEFAULT_SYSTEM_PROMPT).to_string();
+    if let Some(persona) = &config.persona {
+        prompt.push_str(&format!("\n\nIn this context, act as {}.", persona.trim_end_matches('.')));
+    }
+    if let Some(language) = &config.language {
+        prompt.push_str(&format!(
+            "\n\nAlways reply in {}, even when the user, their commands or this prompt use another language. Keep commands, code and file names unchanged.",
+            language
+        ));
+    }
+    prompt
+}
+
+/// Earlier exchanges replayed into a new backend's conversation
+const RESUMED_EXCHANGES: usize = 5;
+
+/// Replays the last few exchanges written by a backend (not the pet's
+/// offline replies) into a new backend's conversation
+pub fn resume(llm: &mut dyn LLMBackend, messages: &[&ChatMessage]) {
+    let replies: Vec<&&ChatMessage> = messages.iter().filter(|message| message.provider.is_some()).collect();
+    for message in &replies[replies.len().saturating_sub(RESUMED_EXCHANGES)..] {
+        llm.add_to_history(message.user_message.clone(), message.response.clone());
+    }
+}
+
+/// Creates the backend the config asks for
+pub fn build(config: &Config) -> Result<Box<dyn LLMBackend>, LlmError> {
+    Ok(match config.llm_provider {
+        LLMProvider::OpenAI => {
+            let api_key = std::env::var("OPENAI_API_KEY")
+                .map_err(|_| LlmError::MissingKey { var: "OPENAI_API_KEY".to_string() })?;
+            Box::new(OpenAIBackend::new(api_key, system_prompt(config), config.openai_options()))
+        }
+        LLMProvider::Ollama => Box::new(OllamaBackend::new(
+            config.ollama_url.clone(),
+            config.ollama_model.clone(),
+            system_prompt(config),
+            config.ollama_options(),
+        )),
+    })
+}
+
+/// The configured backend, or the local model while a budget is used up
+pub fn build_within_budget(config: &Config, over_budget: bool) -> Result<Box<dyn LLMBackend>, LlmError> {
+    if over_budget {
+        Ok(build_local(config))
+    } else {
+        build(config)
+    }
+}
+
+/// An Ollama backend with the configured model, whatever the provider
+pub fn build_local(config: &Config) -> Box<dyn LLMBackend> {
+    Box::new(OllamaBackend::new(
+        config.ollama_url.clone(),
+        config.ollama_model.clone(),
+        system_prompt(config),
+        config.ollama_options(),
+    ))
+}
+
+/// Everything besides the conversation itself that goes into a prompt
+pub struct PromptContext<'a> {
+    /// Who the pet is, from its pedigree
+    pub personality: Option<&'a str>,
+    /// What the user's reactions to earlier replies say they like
+    pub preferences: Option<&'a str>,
+    /// Few-shot examples for the active persona, of which those that fit go ahead of the conversation
+    pub examples: Vec<&'a Example>,
+    pub recent_commands: &'a [HistoryEntry],
+    pub workspace: Option<&'a WorkspaceContext>,
+    pub environment: Option<&'a SystemEnvironment>,
+    pub frequent_directories: Option<&'a FrequentDirectories>,
+    /// The local weather, for messages about going outside
+    pub weather: Option<&'a Weather>,
+    pub battery: Option<&'a Battery>,
+    /// tldr or man page of the command the message asks about
+    pub docs: Option<&'a str>,
+    /// Bookmarked commands related to the current message
+    pub bookmarks: Vec<&'a Bookmark>,
+    /// Text from plugins' context providers
+    pub plugins: Option<&'a str>,
+    /// The skill the user is learning next, from `skills::context`
+    pub learning: Option<&'a str>,
+}
+
+/// The part of a chat prompt's context that comes from the pet, the config
+/// and the command history, gathered the same way wherever the pet is asked
+pub struct ChatContext {
+    personality: Option<String>,
+    preferences: Option<String>,
+    docs: Option<String>,
+    learning: Option<String>,
+    recent_commands: Vec<HistoryEntry>,
+}
+
+impl ChatContext {
+    /// `docs` comes from [`look_up_docs`](Self::look_up_docs), which callers
+    /// await away from anything that has to stay responsive
+    pub fn gather(config: &Config, state: &PetState, recent_commands: &[HistoryEntry], docs: Option<String>) -> Self {
+        Self {
+            personality: state.pedigree.as_ref().map(|pedigree| pedigree.personality(&state.name)),
+            preferences: feedback::preferences(&state.reactions),
+            docs,
+            learning: skills::context(state),
+            recent_commands: config.history_filter().apply(recent_commands),
+        }
+    }
+
+    /// Docs for the command a message mentions, when `docs_context` is on
+    pub fn look_up_docs(config: &Config, user_message: &str) -> impl Future<Output = Option<String>> + Send + 'static {
+        let lookup = config.docs_context.then(|| tldr::context_for(user_message.to_string()));
+        async move {
+            match lookup {
+                Some(lookup) => lookup.await,
+                None => None,
+            }
+        }
+    }
+
+    /// The recent commands the prompt may mention, after `history_filter`
+    pub fn recent_commands(&self) -> &[HistoryEntry] {
+        &self.recent_commands
+    }
+
+    /// A prompt context with just these parts; callers fill in whatever else
+    /// they know, like the workspace
+    pub fn prompt_context<'a>(&'a self, config: &'a Config, state: &'a PetState, user_message: &str) -> PromptContext<'a> {
+        PromptContext {
+            personality: self.personality.as_deref(),
+            preferences: self.preferences.as_deref(),
+            examples: few_shot::for_persona(&config.few_shot, config.persona.as_deref()),
+            recent_commands: &self.recent_commands,
+            workspace: None,
+            environment: None,
+            frequent_directories: None,
+            weather: None,
+            battery: None,
+            docs: self.docs.as_deref(),
+            bookmarks: bookmarks::relevant(&state.bookmarks, user_message),
+            plugins: None,
+            learning: self.learning.as_deref(),
+        }
+    }
+}
+
+/// A response along with what the backend reported about producing it
+#[derive(Debug)]
+pub struct Generation {
+    pub text: String,
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    /// Time until the first token, where the backend reports it
+    pub first_token: Option<Duration>,
+    /// Time spent generating after that, where the backend reports it
+    pub generation_time: Option<Duration>,
+}
+
+impl Generation {
+    /// Summarizes the reply in the log, with its text only at debug level
+    pub(crate) fn log(&self) {
+        tracing::info!(
+            reply_chars = self.text.len(),
+            prompt_tokens = self.prompt_tokens,
+            completion_tokens = self.completion_tokens,
+            "reply received"
+        );
+        tracing::debug!(reply = %self.text);
+    }
+}
+
+/// A language model the pet talks through
+///
+/// Backends keep a short conversation history of their own, which
+/// `format_prompt` includes along with the command and workspace context,
+/// ready to be rendered with the `chat` template.
+#[async_trait]
+pub trait LLMBackend: Send + Sync {
+    async fn generate(&self, prompt: &str) -> Result<Generation, LlmError>;
+    /// Provider and model name recorded with each chat message
+    fn provider(&self) -> &'static str;
+    fn model(&self) -> &str;
+    fn format_prompt(&self, user_input: &str, context: &PromptContext) -> Prompt;
+    fn add_to_history(&mut self, user_message: String, assistant_response: String);
+    /// Whether prompts leave this machine, in which case they are privacy-filtered
+    fn is_remote(&self) -> bool;
+    /// A copy that can answer a prompt on another task while this one keeps its history
+    fn boxed_clone(&self) -> Box<dyn LLMBackend>;
+}
+
+/// The built-in `chat` template
+const CHAT_TEMPLATE: &str = "{{ examples }}{{ history }}{{ context }}Current user message: {{ message }}";
+
+/// Where OpenAI's API is unless `openai_base_url` says otherwise
+pub const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// Which OpenAI endpoint and account requests go to
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpenAIOptions {
+    /// Like "https://api.openai.com/v1", or "https://eu.api.openai.com/v1" to keep data in the EU
+    pub base_url: String,
+    /// Sent as `OpenAI-Organization`, for keys that belong to several organizations
+    pub organization: Option<String>,
+    /// Sent as `OpenAI-Project`, for usage billed to a project
+    pub project: Option<String>,
+}
+
+impl Default for OpenAIOptions {
+    fn default() -> Self {
+        Self { base_url: OPENAI_BASE_URL.to_string(), organization: None, project: None }
+    }
+}
+
+impl OpenAIOptions {
+    /// The URL of an API path like "chat/completions"
+    pub fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url.trim_end_matches('/'), path)
+    }
+
+    /// `request` with the API key and the organization and project headers
+    pub fn authorize(&self, request: reqwest::RequestBuilder, api_key: &str) -> reqwest::RequestBuilder {
+        let mut request = request.header("Authorization", format!("Bearer {}", api_key));
+        if let Some(organization) = &self.organization {
+            request = request.header("OpenAI-Organization", organization);
+        }
+        if let Some(project) = &self.project {
+            request = request.header("OpenAI-Project", project);
+        }
+        request
+    }
+}
+
+/// Whether `url` points at this machine
+pub(crate) fn is_loopback(url: &str) -> bool {
+    match reqwest::Url::parse(url) {
+        Ok(url) => matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]" | "::1")),
+        Err(_) => false,
+    }
+}
+
+/// The OpenAI model asked unless another one is picked
+const OPENAI_MODEL: &str = "gpt-3.5-turbo";
+
+/// OpenAI's chat completions API (needs `OPENAI_API_KEY`)
+#[derive(Clone)]
+pub struct OpenAIBackend {
+    api_key: String,
+    model: String,
+    system_prompt: String,
+    options: OpenAIOptions,
+    conversation_history: Vec<(String, String)>,
+}
+
+impl OpenAIBackend {
+    pub fn new(api_key: String, system_prompt: String, options: OpenAIOptions) -> Self {
+        Self {
+            api_key,
+            model: String::from(OPENAI_MODEL),
+            system_prompt,
+            options,
+            conversation_history: Vec::new(),
+        }
+    }
+
+    /// Uses `model` instead of the default one
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = model.to_string();
+        self
+    }
+}
+
+#[async_trait]
+impl LLMBackend for OpenAIBackend {
+    #[tracing::instrument(name = "generate", skip_all, fields(provider = "openai", model = %self.model, prompt_chars = prompt.len()), err(level = "warn"))]
+    async fn generate(&self, prompt: &str) -> Result<Generation, LlmError> {
+        let url = self.options.url("chat/completions");
+        let client = reqwest::Client::new();
+        let response = self.options.authorize(client.post(&url), &self.api_key)
+            .header("Content-Type", "application/json")
+            .json(&serde_json::json!({
+                "model": self.model,
+                "messages": [{
+                    "role": "system",
+                    "content": self.system_prompt
+                }, {
+                    "role": "user",
+                    "content": prompt
+                }]
+            }))
+            .send()
+            .await
+            .map_err(|e| LlmError::network("OpenAI", &url, e))?;
+
+        let status = response.status();
+        let response_text = response.text().await
+            .map_err(|e| LlmError::network("OpenAI", &url, e))?;
+        if !status.is_success() {
+            return Err(LlmError::from_response("OpenAI", &self.model, status.as_u16(), &response_text));
+        }
+
+        let response_data: Value = serde_json::from_str(&response_text)
+            .map_err(|e| LlmError::invalid_response("OpenAI", e))?;
+
+        let text = response_data["choices"][0]["message"]["content"]
+            .as_str()
+            .ok_or_else(|| LlmError::invalid_response("OpenAI", "the reply has no message"))?
+            .to_string();
+        let generation = Generation {
+            text,
+            prompt_tokens: token_count(&response_data["usage"]["prompt_tokens"]),
+            completion_tokens: token_count(&response_data["usage"]["completion_tokens"]),
+            first_token: None,
+            generation_time: None,
+        };
+        generation.log();
+        Ok(generation)
+    }
+
+    fn provider(&self) -> &'static str {
+        "openai"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn format_prompt(&self, user_input: &str, context: &PromptContext) -> Prompt {
+        chat_prompt(&self.conversation_history, user_input, context)
+    }
+
+    fn add_to_history(&mut self, user_message: String, assistant_response: String) {
+        self.conversation_history.push((user_message, assistant_response));
+        // Keep only last 5 exchanges
+        if self.conversation_history.len() > 5 {
+            self.conversation_history.remove(0);
+        }
+    }
+
+    fn is_remote(&self) -> bool {
+        !is_loopback(&self.options.base_url)
+    }
+
+    fn boxed_clone(&self) -> Box<dyn LLMBackend> {
+        Box::new(self.clone())
+    }
+}
+
+/// Stands in for a backend that couldn't be created, failing every request
+/// with the reason so the pet answers by itself until the config is fixed
+#[derive(Clone)]
+pub struct UnavailableBackend {
+    provider: &'static str,
+    model: String,
+    error: LlmError,
+    conversation_history: Vec<(String, String)>,
+}
+
+impl UnavailableBackend {
+    pub fn new(config: &Config, error: LlmError) -> Self {
+        let (provider, model) = match config.llm_provider {
+            LLMProvider::OpenAI => ("openai", OPENAI_MODEL.to_string()),
+            LLMProvider::Ollama => ("ollama", config.ollama_model.clone()),
+        };
+        Self { provider, model, error, conversation_history: Vec::new() }
+    }
+}
+
+#[async_trait]
+impl LLMBackend for UnavailableBackend {
+    async fn generate(&self, _prompt: &str) -> Result<Generation, LlmError> {
+        Err(self.error.clone())
+    }
+
+    fn provider(&self) -> &'static str {
+        self.provider
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn format_prompt(&self, user_input: &str, context: &PromptContext) -> Prompt {
+        chat_prompt(&self.conversation_history, user_input, context)
+    }
+
+    fn add_to_history(&mut self, user_message: String, assistant_response: String) {
+        self.conversation_history.push((user_message, assistant_response));
+    }
+
+    fn is_remote(&self) -> bool {
+        false
+    }
+
+    fn boxed_clone(&self) -> Box<dyn LLMBackend> {
+        Box::new(self.clone())
+    }
+}
+
+/// Reads a token count from a backend's JSON response
+pub fn token_count(value: &Value) -> Option<u32> {
+    value.as_u64().and_then(|count| u32::try_from(count).ok())
+}
+
+/// The chat prompt shared by all backends: the few-shot examples that fit,
+/// the last few exchanges, the context and the message, for the `chat` template
+pub fn chat_prompt(history: &[(String, String)], user_input: &str, context: &PromptContext) -> Prompt {
+    let history: String = history
+        .iter()
+        .rev()
+        .take(3)
+        .map(|(user_msg, assistant_msg)| format!("User: {}\nAssistant: {}\n\n", user_msg, assistant_msg))
+        .collect();
+    let formatted_context = format_context(context);
+    let used = history.chars().count() + formatted_context.chars().count() + user_input.chars().count();
+    let examples = few_shot::fit(&context.examples, user_input, used);
+    Prompt::new("chat", CHAT_TEMPLATE)
+        .var("examples", few_shot::format(&examples))
+        .var("history", history)
+        .var("context", formatted_context)
+        .var("message", user_input)
+}
+
+/// Formats the prompt context shared by all backends
+pub fn format_context(context: &PromptContext) -> String {
+    let mut text = String::new();
+    if let Some(personality) = context.personality {
+        text.push_str(personality);
+        text.push_str("\n\n");
+    }
+    if let Some(preferences) = context.preferences {
+        text.push_str(preferences);
+        text.push_str("\n\n");
+    }
+    if let Some(environment) = context.environment {
+        text.push_str(&environment.describe());
+        text.push('\n');
+    }
+    if let Some(workspace) = context.workspace {
+        text.push_str(&workspace.describe());
+        text.push('\n');
+    }
+    if let Some(weather) = context.weather {
+        text.push_str(&weather.describe());
+        text.push_str("\n\n");
+    }
+    if let Some(battery) = context.battery {
+        text.push_str(&battery.describe());
+        text.push_str("\n\n");
+    }
+    if let Some(directories) = context.frequent_directories {
+        text.push_str(&directories.describe());
+        text.push('\n');
+    }
+    if !context.bookmarks.is_empty() {
+        let lines: Vec<String> = context.bookmarks.iter().map(|bookmark| bookmark.describe()).collect();
+        text.push_str(&format!(
+            "The user's bookmarked commands (prefer these when they fit the question):\n{}\n\n",
+            lines.join("\n")
+        ));
+    }
+    if let Some(docs) = context.docs {
+        text.push_str(docs);
+        text.push_str("\n\n");
+    }
+    if let Some(plugins) = context.plugins {
+        text.push_str(plugins);
+        text.push_str("\n\n");
+    }
+    if let Some(learning) = context.learning {
+        text.push_str(learning);
+        text.push_str("\n\n");
+    }
+    text.push_str(&format_command_context(context.recent_commands));
+    text
+}
+
+/// Formats recent commands as prompt context
+///
+/// When exit codes are known, failed commands are repeated in their own section
+/// so the model comments on what went wrong before anything else.
+fn format_command_context(commands: &[HistoryEntry]) -> String {
+    if commands.is_empty() {
+        return String::new();
+    }
+
+    let lines: Vec<String> = commands.iter().map(|entry| entry.describe()).collect();
+    let mut context = format!("Recent commands:\n{}\n\n", lines.join("\n"));
+
+    // Only the last few failures are worth pointing out
+    let failed: Vec<String> = commands
+        .iter()
+        .rev()
+        .take(10)
+        .filter(|entry| entry.failed())
+        .map(|entry| entry.describe())
+        .collect();
+    if !failed.is_empty() {
+        context.push_str(&format!(
+            "Recently failed commands (prioritize explaining these failures and suggesting a fix, e.g. the correct flags):\n{}\n\n",
+            failed.into_iter().rev().collect::<Vec<_>>().join("\n")
+        ));
+        let streak = history::failure_streak(commands);
+        if streak > 1 {
+            context.push_str(&format!("The user's last {} commands all failed.\n\n", streak));
+        }
+    }
+
+    context
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn openai_requests_carry_the_account_headers() {
+        let options = OpenAIOptions {
+            base_url: "https://eu.api.openai.com/v1/".to_string(),
+            organization: Some("org-123".to_string()),
+            project: None,
+        };
+        assert_eq!(options.url("chat/completions"), "https://eu.api.openai.com/v1/chat/completions");
+        let request = options.authorize(reqwest::Client::new().get(options.url("models")), "sk-test").build().unwrap();
+        assert_eq!(request.headers()["Authorization"], "Bearer sk-test");
+        assert_eq!(request.headers()["OpenAI-Organization"], "org-123");
+        assert!(!request.headers().contains_key("OpenAI-Project"));
+        assert!(is_loopback("http://localhost:1234/v1") && !is_loopback(OPENAI_BASE_URL));
+    }
+}
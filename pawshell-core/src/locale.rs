@@ -0,0 +1,125 @@
+//! Built-in pet phrases in the configured `language`
+//!
+//! The LLM is told to reply in `language` through the system prompt; this
+//! module covers what the pet says without it: the welcome and goodbye
+//! messages and the replies it falls back to when the backend is unavailable.
+//! Languages without a translation here get the English phrases.
+
+/// The pet's built-in phrases in one language
+pub struct Strings {
+    /// Language names and ISO 639-1 code this translation is picked for, lowercase
+    names: &'static [&'static str],
+    pub welcome: &'static str,
+    pub goodbye: &'static str,
+    pub treat: &'static str,
+    pub play: &'static str,
+    pub content: &'static str,
+    pub curious: &'static str,
+    pub distant: &'static str,
+    pub no_aliases: &'static str,
+    /// Words in a message that count as offering a treat or asking to play
+    pub treat_words: &'static [&'static str],
+    pub play_words: &'static [&'static str],
+}
+
+const ENGLISH: Strings = Strings {
+    names: &["en", "english"],
+    welcome: "Welcome back! Type your message and press Enter to chat.",
+    goodbye: "Goodbye! Take care! 👋",
+    treat: "*purrs happily* Thank you for the treat! 😊",
+    play: "*bounces around excitedly* I love to play! 🐱",
+    content: "*purrs contentedly* 😊",
+    curious: "*looks at you curiously* Meow?",
+    distant: "*seems a bit distant* ...",
+    no_aliases: "*tilts head* I can't think of any aliases right now. Try again later!",
+    treat_words: &["treat"],
+    play_words: &["play"],
+};
+
+const TRANSLATIONS: &[Strings] = &[
+    ENGLISH,
+    Strings {
+        names: &["es", "spanish", "español", "espanol"],
+        welcome: "¡Bienvenido de nuevo! Escribe tu mensaje y pulsa Enter para charlar.",
+        goodbye: "¡Adiós! ¡Cuídate! 👋",
+        treat: "*ronronea feliz* ¡Gracias por el premio! 😊",
+        play: "*salta emocionado* ¡Me encanta jugar! 🐱",
+        content: "*ronronea contento* 😊",
+        curious: "*te mira con curiosidad* ¿Miau?",
+        distant: "*parece un poco distante* ...",
+        no_aliases: "*ladea la cabeza* Ahora mismo no se me ocurre ningún alias. ¡Inténtalo más tarde!",
+        treat_words: &["premio", "golosina", "chuche"],
+        play_words: &["jugar", "juego"],
+    },
+    Strings {
+        names: &["fr", "french", "français", "francais"],
+        welcome: "Bon retour ! Tape ton message et appuie sur Entrée pour discuter.",
+        goodbye: "Au revoir ! Prends soin de toi ! 👋",
+        treat: "*ronronne joyeusement* Merci pour la friandise ! 😊",
+        play: "*bondit partout* J'adore jouer ! 🐱",
+        content: "*ronronne, satisfait* 😊",
+        curious: "*te regarde avec curiosité* Miaou ?",
+        distant: "*semble un peu distant* ...",
+        no_aliases: "*penche la tête* Aucun alias ne me vient à l'esprit. Réessaie plus tard !",
+        treat_words: &["friandise", "gâterie"],
+        play_words: &["jouer", "jeu"],
+    },
+    Strings {
+        names: &["de", "german", "deutsch"],
+        welcome: "Willkommen zurück! Schreib deine Nachricht und drück Enter zum Chatten.",
+        goodbye: "Tschüss! Pass auf dich auf! 👋",
+        treat: "*schnurrt glücklich* Danke für das Leckerli! 😊",
+        play: "*hüpft aufgeregt herum* Ich liebe es zu spielen! 🐱",
+        content: "*schnurrt zufrieden* 😊",
+        curious: "*schaut dich neugierig an* Miau?",
+        distant: "*wirkt etwas abwesend* ...",
+        no_aliases: "*legt den Kopf schief* Mir fallen gerade keine Aliase ein. Versuch es später noch mal!",
+        treat_words: &["leckerli", "leckerchen"],
+        play_words: &["spielen", "spiel"],
+    },
+    Strings {
+        names: &["pt", "portuguese", "português", "portugues"],
+        welcome: "Bem-vindo de volta! Digite sua mensagem e pressione Enter para conversar.",
+        goodbye: "Tchau! Se cuida! 👋",
+        treat: "*ronrona feliz* Obrigado pelo petisco! 😊",
+        play: "*pula animado* Eu adoro brincar! 🐱",
+        content: "*ronrona satisfeito* 😊",
+        curious: "*olha para você curioso* Miau?",
+        distant: "*parece um pouco distante* ...",
+        no_aliases: "*inclina a cabeça* Não consigo pensar em nenhum alias agora. Tente mais tarde!",
+        treat_words: &["petisco", "guloseima"],
+        play_words: &["brincar", "jogar"],
+    },
+    Strings {
+        names: &["it", "italian", "italiano"],
+        welcome: "Bentornato! Scrivi il tuo messaggio e premi Invio per chiacchierare.",
+        goodbye: "Ciao! Abbi cura di te! 👋",
+        treat: "*fa le fusa felice* Grazie per il premio! 😊",
+        play: "*salta eccitato* Adoro giocare! 🐱",
+        content: "*fa le fusa contento* 😊",
+        curious: "*ti guarda incuriosito* Miao?",
+        distant: "*sembra un po' distante* ...",
+        no_aliases: "*inclina la testa* Ora non mi viene in mente nessun alias. Riprova più tardi!",
+        treat_words: &["premio", "bocconcino"],
+        play_words: &["giocare", "gioco"],
+    },
+];
+
+/// The phrases for `language`, e.g. "German", "Deutsch", "de" or "de_DE"
+pub fn strings(language: Option<&str>) -> &'static Strings {
+    language.and_then(translation).unwrap_or(&ENGLISH)
+}
+
+/// Whether the pet has its own phrases for `language`
+pub fn is_translated(language: &str) -> bool {
+    translation(language).is_some()
+}
+
+fn translation(language: &str) -> Option<&'static Strings> {
+    let language = language.trim().to_lowercase();
+    // Region and encoding suffixes like "pt_BR" or "de-DE.UTF-8" don't matter here
+    let code = language.split(['_', '-', '.']).next().unwrap_or_default();
+    TRANSLATIONS
+        .iter()
+        .find(|strings| strings.names.iter().any(|name| *name == language || *name == code))
+}
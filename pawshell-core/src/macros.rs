@@ -0,0 +1,66 @@
+//! Recorded input macros, replayed with one key
+//!
+//! Like Vim's `q`, `macro_record_key` followed by a letter starts recording
+//! into that register, and pressing `macro_record_key` again stops. Every
+//! line sent while recording, chat messages and slash commands alike, is
+//! kept, and Alt+<letter> sends them again in order. Macros live in the pet's
+//! state, so each profile has its own.
+
+use std::collections::BTreeMap;
+
+/// Lines kept per macro at most
+const MAX_INPUTS: usize = 50;
+
+/// A macro being recorded
+pub struct Recording {
+    pub register: char,
+    pub inputs: Vec<String>,
+}
+
+impl Recording {
+    pub fn new(register: char) -> Self {
+        Self { register, inputs: Vec::new() }
+    }
+
+    /// Keeps a sent line; returns false once the macro is full
+    pub fn record(&mut self, input: &str) -> bool {
+        if self.inputs.len() >= MAX_INPUTS {
+            return false;
+        }
+        self.inputs.push(input.to_string());
+        true
+    }
+}
+
+/// Registers are the letters a to z
+pub fn is_register(c: char) -> bool {
+    c.is_ascii_lowercase()
+}
+
+/// The `/macros` list
+pub fn list(macros: &BTreeMap<String, Vec<String>>, record_key: &str) -> String {
+    if macros.is_empty() {
+        return format!("No macros yet. Press {} and a letter to record one, and {} again to stop.", record_key, record_key);
+    }
+    let mut text = "Macros (Alt+<letter> replays one):".to_string();
+    for (register, inputs) in macros {
+        text.push_str(&format!("\n{}: {}", register, inputs.join(" ⏎ ")));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recordings_stop_growing_when_full() {
+        let mut recording = Recording::new('a');
+        for _ in 0..MAX_INPUTS {
+            assert!(recording.record("/feed"));
+        }
+        assert!(!recording.record("/quiz git"));
+        assert_eq!(recording.inputs.len(), MAX_INPUTS);
+        assert!(is_register('q') && !is_register('Q') && !is_register('1'));
+    }
+}
@@ -0,0 +1,212 @@
+//! How the pet's mood rises and falls
+//!
+//! Mood is a number between `MIN_MOOD` and `MAX_MOOD`. Chatting, treats and
+//! play raise it; time without attention and failing commands lower it. The
+//! UI and the art poses group it into three levels.
+//!
+//! The TUI writes the mood down every half hour for a week, and `/stats`
+//! graphs the last day and week from those samples.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::pet::PetState;
+use crate::sysmon;
+
+/// The pet is never completely miserable
+pub const MIN_MOOD: f32 = 0.1;
+pub const MAX_MOOD: f32 = 1.0;
+
+/// Mood lost per hour without interaction
+const DECAY_PER_HOUR: f32 = 0.1;
+/// Mood lost per failed command, times the length of the failure streak
+const FAILURE_PENALTY: f32 = 0.02;
+/// Streaks longer than this don't hurt any more than this one
+const MAX_PENALIZED_STREAK: usize = 5;
+/// Minutes between mood samples
+const SAMPLE_MINUTES: i64 = 30;
+/// Days of mood samples kept
+const HISTORY_DAYS: i64 = 7;
+
+/// The mood at one point in time, for the history graph
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct MoodSample {
+    pub at: DateTime<Utc>,
+    pub mood: f32,
+}
+
+/// Coarse mood for choosing colors, art poses and fallback replies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoodLevel {
+    /// Mood above 80%
+    Happy,
+    /// Mood above 40%
+    Content,
+    Sad,
+}
+
+impl MoodLevel {
+    pub fn of(mood: f32) -> Self {
+        match mood {
+            m if m > 0.8 => MoodLevel::Happy,
+            m if m > 0.4 => MoodLevel::Content,
+            _ => MoodLevel::Sad,
+        }
+    }
+}
+
+impl PetState {
+    /// Raises the mood, e.g. by 0.1 for a message or 0.2 for a treat
+    pub fn cheer_up(&mut self, amount: f32) {
+        self.mood = (self.mood + amount).min(MAX_MOOD);
+    }
+
+    /// Lowers the mood for the hours since the last interaction
+    pub fn decay_mood(&mut self, now: DateTime<Utc>) {
+        let hours_since_last = (now - self.last_interaction).num_hours() as f32;
+        self.mood = (self.mood - hours_since_last * DECAY_PER_HOUR).clamp(MIN_MOOD, MAX_MOOD);
+    }
+
+    /// Lowers the mood after a failed command; each failure in a row stings a little more
+    pub fn react_to_failure(&mut self, streak: usize) {
+        let penalty = FAILURE_PENALTY * streak.min(MAX_PENALIZED_STREAK) as f32;
+        self.mood = (self.mood - penalty).max(MIN_MOOD);
+    }
+
+    /// Raises or lowers the mood, e.g. at a script's request
+    pub fn change_mood(&mut self, delta: f32) {
+        self.mood = (self.mood + delta).clamp(MIN_MOOD, MAX_MOOD);
+    }
+
+    pub fn mood_level(&self) -> MoodLevel {
+        MoodLevel::of(self.mood)
+    }
+
+    /// Writes the mood down if the last sample is old enough, forgetting
+    /// samples older than a week; returns whether it did
+    pub fn sample_mood(&mut self, now: DateTime<Utc>) -> bool {
+        if self.mood_log.last().is_some_and(|sample| now - sample.at < Duration::minutes(SAMPLE_MINUTES)) {
+            return false;
+        }
+        self.mood_log.retain(|sample| now - sample.at < Duration::days(HISTORY_DAYS));
+        self.mood_log.push(MoodSample { at: now, mood: self.mood });
+        true
+    }
+}
+
+/// Graphs of the mood over the last day and week, for `/stats`
+pub fn history_summary(log: &[MoodSample], now: DateTime<Utc>) -> String {
+    if log.is_empty() {
+        return "Mood History: nothing recorded yet".to_string();
+    }
+    let line = |label: &str, span: Duration, columns: i32| {
+        let (graph, lowest) = graph(log, now, span, columns);
+        format!("{}: {} (lowest {:.0}%)", label, graph, lowest * 100.0)
+    };
+    format!("{}\n{}", line("Mood, Last 24h", Duration::days(1), 24), line("Mood, Last 7d", Duration::days(7), 28))
+}
+
+/// The mood over `span` before `now` as a sparkline of `columns` columns, and
+/// its lowest point
+///
+/// Each column averages its samples. Columns without any, while pawshell
+/// wasn't running, show where decay would have taken the last known mood, and
+/// columns before the first sample stay blank.
+fn graph(log: &[MoodSample], now: DateTime<Utc>, span: Duration, columns: i32) -> (String, f32) {
+    let width = span / columns;
+    let mut lowest = MAX_MOOD;
+    let graph = (0..columns)
+        .map(|column| {
+            let start = now - span + width * column;
+            let end = start + width;
+            let samples: Vec<f32> = log.iter().filter(|sample| sample.at >= start && sample.at < end).map(|sample| sample.mood).collect();
+            let mood = if samples.is_empty() {
+                let last = log.iter().rev().find(|sample| sample.at < start)?;
+                let hours = (start + width / 2 - last.at).num_minutes() as f32 / 60.0;
+                (last.mood - hours * DECAY_PER_HOUR).max(MIN_MOOD)
+            } else {
+                samples.iter().sum::<f32>() / samples.len() as f32
+            };
+            lowest = lowest.min(mood);
+            Some(mood)
+        })
+        .map(|mood| mood.map_or_else(|| " ".to_string(), |mood| sysmon::sparkline(std::iter::once(mood * 100.0))))
+        .collect();
+    (graph, lowest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, FakeClock};
+    use chrono::Duration;
+
+    fn pet(mood: f32) -> PetState {
+        PetState { mood, ..PetState::default() }
+    }
+
+    #[test]
+    fn cheering_up_stops_at_the_maximum() {
+        let mut state = pet(0.95);
+        state.cheer_up(0.2);
+        assert_eq!(state.mood, MAX_MOOD);
+    }
+
+    #[test]
+    fn mood_decays_per_hour_without_interaction() {
+        let mut state = pet(0.8);
+        let now = state.last_interaction + Duration::hours(3);
+        state.decay_mood(now);
+        assert!((state.mood - 0.5).abs() < 1e-6);
+
+        state.decay_mood(now + Duration::days(7));
+        assert_eq!(state.mood, MIN_MOOD);
+    }
+
+    #[test]
+    fn only_time_since_the_last_interaction_counts() {
+        let clock = FakeClock::at("2024-03-01T18:00:00Z".parse().unwrap());
+        let mut state = PetState { mood: 0.9, last_interaction: clock.now(), ..PetState::default() };
+
+        clock.advance(Duration::hours(4));
+        state.last_interaction = clock.now();
+        clock.advance(Duration::hours(3));
+        state.decay_mood(clock.now());
+        assert!((state.mood - 0.6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn failure_penalty_grows_with_the_streak_up_to_a_cap() {
+        let mut state = pet(0.8);
+        state.react_to_failure(2);
+        assert!((state.mood - 0.76).abs() < 1e-6);
+
+        let (mut long, mut capped) = (pet(0.8), pet(0.8));
+        long.react_to_failure(50);
+        capped.react_to_failure(MAX_PENALIZED_STREAK);
+        assert_eq!(long.mood, capped.mood);
+    }
+
+    #[test]
+    fn mood_history_is_sampled_and_graphed() {
+        let now: DateTime<Utc> = "2024-03-08T12:00:00Z".parse().unwrap();
+        let mut state = pet(1.0);
+        state.mood_log.push(MoodSample { at: now - Duration::days(8), mood: 0.5 });
+        assert!(state.sample_mood(now - Duration::hours(4)));
+        assert!(!state.sample_mood(now - Duration::hours(4) + Duration::minutes(10)));
+        assert_eq!(state.mood_log.len(), 1);
+
+        // Full mood four hours ago, then decay while nobody was around
+        let (graph, lowest) = graph(&state.mood_log, now, Duration::hours(6), 6);
+        assert_eq!(graph, "  █▇▆▆");
+        assert!((lowest - 0.65).abs() < 1e-6);
+    }
+
+    #[test]
+    fn levels_follow_the_thresholds() {
+        assert_eq!(MoodLevel::of(0.9), MoodLevel::Happy);
+        assert_eq!(MoodLevel::of(0.8), MoodLevel::Content);
+        assert_eq!(MoodLevel::of(0.41), MoodLevel::Content);
+        assert_eq!(MoodLevel::of(0.4), MoodLevel::Sad);
+    }
+}
@@ -0,0 +1,293 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+use crate::error::LlmError;
+use crate::llm::{self, Generation, LLMBackend, PromptContext};
+use crate::templates::Prompt;
+
+/// How long Ollama keeps the model loaded after a request
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum KeepAlive {
+    /// Seconds; negative keeps it loaded for good, 0 unloads it right away
+    Seconds(i64),
+    /// A duration like "10m" or "1h30m"
+    Duration(String),
+}
+
+/// Generation settings passed along with every request; unset ones are left
+/// to the model's defaults
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OllamaOptions {
+    pub keep_alive: Option<KeepAlive>,
+    /// Context window in tokens
+    pub num_ctx: Option<u32>,
+    /// Most tokens to generate; -1 for no limit
+    pub num_predict: Option<i32>,
+    /// Makes replies to the same prompt repeatable
+    pub seed: Option<i64>,
+    /// Generation stops at any of these
+    pub stop: Vec<String>,
+}
+
+impl OllamaOptions {
+    /// Problems with the options, for config validation
+    pub fn check(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if let Some(KeepAlive::Duration(duration)) = &self.keep_alive {
+            let go_duration = regex::Regex::new(r"^-?(\d+(\.\d*)?(ns|us|µs|ms|s|m|h))+$").expect("valid regex");
+            if !go_duration.is_match(duration) && duration != "0" {
+                problems.push(format!(
+                    "ollama_keep_alive {:?} is not a duration like \"10m\" or \"1h30m\"; use a number for seconds, -1 to keep the model loaded",
+                    duration
+                ));
+            }
+        }
+        if self.num_ctx == Some(0) {
+            problems.push("ollama_num_ctx is 0, which leaves no room for the prompt; try 8192".to_string());
+        }
+        if self.num_predict.is_some_and(|tokens| tokens < -2) {
+            problems.push("ollama_num_predict must be a number of tokens, or -1 for no limit".to_string());
+        }
+        if self.stop.iter().any(String::is_empty) {
+            problems.push("ollama_stop has an empty entry, which would stop every reply before it starts".to_string());
+        }
+        problems
+    }
+}
+
+/// A local or remote Ollama server
+#[derive(Clone)]
+pub struct OllamaBackend {
+    url: String,
+    model: String,
+    system_prompt: String,
+    options: OllamaOptions,
+    conversation_history: Vec<(String, String)>,
+}
+
+impl OllamaBackend {
+    pub fn new(url: String, model: String, system_prompt: String, options: OllamaOptions) -> Self {
+        Self {
+            url,
+            model,
+            system_prompt,
+            options,
+            conversation_history: Vec::new(),
+        }
+    }
+
+    /// The `/api/generate` request for `prompt`
+    fn request_body(&self, prompt: &str) -> Value {
+        let mut body = serde_json::json!({
+            "model": self.model,
+            "prompt": format!("{}\n{}", self.system_prompt, prompt),
+            "stream": false
+        });
+        if let Some(keep_alive) = &self.options.keep_alive {
+            body["keep_alive"] = serde_json::json!(keep_alive);
+        }
+        let mut options = serde_json::Map::new();
+        if let Some(num_ctx) = self.options.num_ctx {
+            options.insert("num_ctx".to_string(), num_ctx.into());
+        }
+        if let Some(num_predict) = self.options.num_predict {
+            options.insert("num_predict".to_string(), num_predict.into());
+        }
+        if let Some(seed) = self.options.seed {
+            options.insert("seed".to_string(), seed.into());
+        }
+        if !self.options.stop.is_empty() {
+            options.insert("stop".to_string(), serde_json::json!(self.options.stop));
+        }
+        if !options.is_empty() {
+            body["options"] = Value::Object(options);
+        }
+        body
+    }
+}
+
+#[async_trait]
+impl LLMBackend for OllamaBackend {
+    #[tracing::instrument(name = "generate", skip_all, fields(provider = "ollama", model = %self.model, prompt_chars = prompt.len()), err(level = "warn"))]
+    async fn generate(&self, prompt: &str) -> Result<Generation, LlmError> {
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{}/api/generate", self.url))
+            .header("Content-Type", "application/json")
+            .json(&self.request_body(prompt))
+            .send()
+            .await
+            .map_err(|e| LlmError::network("Ollama", &self.url, e))?;
+
+        let status = response.status();
+        let response_text = response.text().await
+            .map_err(|e| LlmError::network("Ollama", &self.url, e))?;
+        if !status.is_success() {
+            return Err(LlmError::from_response("Ollama", &self.model, status.as_u16(), &response_text));
+        }
+
+        let response_data: Value = serde_json::from_str(&response_text)
+            .map_err(|e| LlmError::invalid_response("Ollama", e))?;
+
+        let text = response_data["response"]
+            .as_str()
+            .ok_or_else(|| LlmError::invalid_response("Ollama", "the reply has no response text"))?
+            .to_string();
+        let generation = Generation {
+            text,
+            prompt_tokens: llm::token_count(&response_data["prompt_eval_count"]),
+            completion_tokens: llm::token_count(&response_data["eval_count"]),
+            first_token: match (nanos(&response_data["load_duration"]), nanos(&response_data["prompt_eval_duration"])) {
+                (None, None) => None,
+                (load, prompt) => Some(load.unwrap_or_default() + prompt.unwrap_or_default()),
+            },
+            generation_time: nanos(&response_data["eval_duration"]),
+        };
+        generation.log();
+        Ok(generation)
+    }
+
+    fn provider(&self) -> &'static str {
+        "ollama"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn format_prompt(&self, user_input: &str, context: &PromptContext) -> Prompt {
+        llm::chat_prompt(&self.conversation_history, user_input, context)
+    }
+
+    fn add_to_history(&mut self, user_message: String, assistant_response: String) {
+        self.conversation_history.push((user_message, assistant_response));
+        // Keep only last 5 exchanges
+        if self.conversation_history.len() > 5 {
+            self.conversation_history.remove(0);
+        }
+    }
+
+    fn is_remote(&self) -> bool {
+        // Anything but a loopback address means prompts leave this machine
+        !llm::is_loopback(&self.url)
+    }
+
+    fn boxed_clone(&self) -> Box<dyn LLMBackend> {
+        Box::new(self.clone())
+    }
+}
+/// Reads one of the nanosecond timings in Ollama's reply
+fn nanos(value: &Value) -> Option<Duration> {
+    value.as_u64().map(Duration::from_nanos)
+}
+
+/// How far a `/model pull` has got, as Ollama's `/api/pull` reports it
+#[derive(Debug, Clone, PartialEq)]
+pub struct PullProgress {
+    /// Like "pulling manifest" or "verifying sha256 digest"
+    pub status: String,
+    /// Bytes of the layer being downloaded so far, and its size; 0 between layers
+    pub completed: u64,
+    pub total: u64,
+}
+
+impl PullProgress {
+    /// The downloaded part of the current layer, from 0 to 1, if it's downloading
+    pub fn ratio(&self) -> Option<f64> {
+        (self.total > 0).then(|| (self.completed as f64 / self.total as f64).min(1.0))
+    }
+}
+
+/// Reads one line of `/api/pull`'s streamed reply
+fn parse_pull_line(line: &str) -> Result<Option<PullProgress>, String> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    let value: Value = serde_json::from_str(line).map_err(|e| format!("Ollama sent a progress line I couldn't read: {}", e))?;
+    if let Some(error) = value["error"].as_str() {
+        return Err(error.to_string());
+    }
+    Ok(Some(PullProgress {
+        status: value["status"].as_str().unwrap_or_default().to_string(),
+        completed: value["completed"].as_u64().unwrap_or(0),
+        total: value["total"].as_u64().unwrap_or(0),
+    }))
+}
+
+/// Downloads `model` onto the Ollama server at `url`, calling `progress` for
+/// every update until it's done
+pub async fn pull(url: &str, model: &str, mut progress: impl FnMut(PullProgress) + Send) -> Result<(), String> {
+    let mut response = reqwest::Client::new()
+        .post(format!("{}/api/pull", url))
+        .json(&serde_json::json!({ "model": model, "stream": true }))
+        .send()
+        .await
+        .map_err(|e| LlmError::network("Ollama", url, e).to_string())?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(LlmError::from_response("Ollama", model, status.as_u16(), &body).to_string());
+    }
+    let mut buffer = Vec::new();
+    let mut succeeded = false;
+    while let Some(chunk) = response.chunk().await.map_err(|e| LlmError::network("Ollama", url, e).to_string())? {
+        buffer.extend_from_slice(&chunk);
+        while let Some(end) = buffer.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=end).collect();
+            if let Some(update) = parse_pull_line(&String::from_utf8_lossy(&line))? {
+                succeeded |= update.status == "success";
+                progress(update);
+            }
+        }
+    }
+    if let Some(update) = parse_pull_line(&String::from_utf8_lossy(&buffer))? {
+        succeeded |= update.status == "success";
+        progress(update);
+    }
+    if succeeded {
+        Ok(())
+    } else {
+        Err(format!("Ollama stopped sending {} before it was done", model))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pull_progress_lines_are_read() {
+        let update = parse_pull_line(r#"{"status":"pulling 6a0746a1ec1a","digest":"sha256:6a07","total":400,"completed":100}"#).unwrap().unwrap();
+        assert_eq!(update.status, "pulling 6a0746a1ec1a");
+        assert_eq!(update.ratio(), Some(0.25));
+        assert_eq!(parse_pull_line(r#"{"status":"pulling manifest"}"#).unwrap().unwrap().ratio(), None);
+        assert_eq!(parse_pull_line("  \n"), Ok(None));
+        assert_eq!(parse_pull_line(r#"{"error":"pull model manifest: file does not exist"}"#), Err("pull model manifest: file does not exist".to_string()));
+    }
+
+    #[test]
+    fn options_go_in_the_request_only_when_set() {
+        let plain = OllamaBackend::new("http://localhost:11434".to_string(), "llama3.2".to_string(), "Be nice.".to_string(), OllamaOptions::default());
+        assert_eq!(plain.request_body("hi"), serde_json::json!({ "model": "llama3.2", "prompt": "Be nice.\nhi", "stream": false }));
+
+        let options = OllamaOptions {
+            keep_alive: Some(KeepAlive::Seconds(-1)),
+            num_ctx: Some(8192),
+            seed: Some(42),
+            stop: vec!["User:".to_string()],
+            ..OllamaOptions::default()
+        };
+        let tuned = OllamaBackend::new("http://localhost:11434".to_string(), "llama3.2".to_string(), String::new(), options.clone());
+        let body = tuned.request_body("hi");
+        assert_eq!(body["keep_alive"], -1);
+        assert_eq!(body["options"], serde_json::json!({ "num_ctx": 8192, "seed": 42, "stop": ["User:"] }));
+        assert!(options.check().is_empty());
+
+        let bad = OllamaOptions { keep_alive: Some(KeepAlive::Duration("10 minutes".to_string())), num_ctx: Some(0), ..OllamaOptions::default() };
+        assert_eq!(bad.check().len(), 2);
+        assert!(OllamaOptions { keep_alive: Some(KeepAlive::Duration("1h30m".to_string())), ..options }.check().is_empty());
+    }
+}
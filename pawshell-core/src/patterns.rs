@@ -0,0 +1,168 @@
+//! Workflow pattern detection for PetCLI
+//!
+//! The system prompt promises workflow suggestions, so this module looks for
+//! repetitive habits in the recent command history that an alias, shell function
+//! or a more modern tool could replace:
+//! - `cd` immediately followed by `ls`
+//! - the `git add` → `git commit` → `git push` dance
+//! - searching with `grep`/`rg` and then opening an editor on the result
+//!
+//! The detector only decides *what* to suggest and *when*; the wording comes from
+//! the LLM backend. Each pattern is suggested at most once per session.
+
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::clock::{self, Clock};
+use crate::history::HistoryEntry;
+use crate::templates::Prompt;
+
+/// How many of the most recent commands are scanned for patterns
+const WINDOW: usize = 50;
+
+const EDITORS: &[&str] = &["vim", "nvim", "vi", "nano", "emacs", "hx", "code", "micro"];
+
+const SUGGESTION_TEMPLATE: &str = "{{ habit }} (seen {{ count }} times in my recent commands.) Offer one short, \
+friendly suggestion: an alias, shell function or modern tool that would streamline this. Include the \
+ready-to-paste snippet and keep it under 5 lines.";
+
+/// A repetitive workflow the pet can offer a shortcut for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pattern {
+    CdThenLs,
+    GitAddCommitPush,
+    GrepThenEdit,
+}
+
+impl Pattern {
+    const ALL: [Pattern; 3] = [Pattern::CdThenLs, Pattern::GitAddCommitPush, Pattern::GrepThenEdit];
+
+    /// How many occurrences in the window make the habit worth mentioning
+    fn threshold(self) -> usize {
+        match self {
+            Pattern::CdThenLs => 3,
+            Pattern::GitAddCommitPush => 2,
+            Pattern::GrepThenEdit => 3,
+        }
+    }
+
+    /// Counts occurrences of the pattern in consecutive commands
+    fn count(self, commands: &[&str]) -> usize {
+        match self {
+            Pattern::CdThenLs => commands
+                .windows(2)
+                .filter(|pair| program(pair[0]) == "cd" && program(pair[1]) == "ls")
+                .count(),
+            Pattern::GitAddCommitPush => commands
+                .windows(3)
+                .filter(|run| {
+                    run[0].starts_with("git add") && run[1].starts_with("git commit") && run[2].starts_with("git push")
+                })
+                .count(),
+            Pattern::GrepThenEdit => commands
+                .windows(2)
+                .filter(|pair| {
+                    matches!(program(pair[0]), "grep" | "rg" | "ag") && EDITORS.contains(&program(pair[1]))
+                })
+                .count(),
+        }
+    }
+
+    /// Describes the observed habit for the suggestion prompt
+    fn description(self) -> &'static str {
+        match self {
+            Pattern::CdThenLs => "I keep running `ls` right after every `cd`.",
+            Pattern::GitAddCommitPush => "I keep typing `git add`, `git commit` and `git push` one after another.",
+            Pattern::GrepThenEdit => "I keep searching with grep/rg and then opening the matching file in my editor.",
+        }
+    }
+
+    /// Builds the prompt asking the backend for a shortcut
+    pub fn prompt(self, count: usize) -> Prompt {
+        Prompt::new("suggestion", SUGGESTION_TEMPLATE).var("habit", self.description()).var("count", count.to_string())
+    }
+}
+
+/// Decides when a detected pattern is worth an unprompted suggestion
+pub struct PatternDetector {
+    cooldown: Duration,
+    last_suggestion: DateTime<Utc>,
+    suggested: HashSet<Pattern>,
+    clock: Arc<dyn Clock>,
+}
+
+impl PatternDetector {
+    /// Creates a detector; the first suggestion waits a full cooldown after startup
+    pub fn new(cooldown_minutes: i64) -> Self {
+        Self::with_clock(cooldown_minutes, clock::current())
+    }
+
+    /// Like `new`, timing the cooldown with `clock`
+    pub fn with_clock(cooldown_minutes: i64, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            cooldown: Duration::minutes(cooldown_minutes),
+            last_suggestion: clock.now(),
+            suggested: HashSet::new(),
+            clock,
+        }
+    }
+
+    pub fn set_cooldown(&mut self, cooldown_minutes: i64) {
+        self.cooldown = Duration::minutes(cooldown_minutes);
+    }
+
+    /// Returns a pattern to suggest now, along with its occurrence count
+    ///
+    /// Returns None while the cooldown is running or when nothing new was found.
+    /// A returned pattern is marked as suggested and won't be offered again.
+    pub fn poll(&mut self, recent_commands: &[HistoryEntry]) -> Option<(Pattern, usize)> {
+        let now = self.clock.now();
+        if now - self.last_suggestion < self.cooldown {
+            return None;
+        }
+
+        let start = recent_commands.len().saturating_sub(WINDOW);
+        let commands: Vec<&str> = recent_commands[start..].iter().map(|entry| entry.command.trim()).collect();
+
+        let (pattern, count) = Pattern::ALL
+            .into_iter()
+            .filter(|pattern| !self.suggested.contains(pattern))
+            .map(|pattern| (pattern, pattern.count(&commands)))
+            .find(|(pattern, count)| *count >= pattern.threshold())?;
+
+        self.suggested.insert(pattern);
+        self.last_suggestion = now;
+        Some((pattern, count))
+    }
+}
+
+fn program(command: &str) -> &str {
+    command.split_whitespace().next().unwrap_or("")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{test_time, FakeClock};
+
+    fn history(commands: &[&str]) -> Vec<HistoryEntry> {
+        commands.iter().map(|command| HistoryEntry::new(command, None)).collect()
+    }
+
+    #[test]
+    fn suggestions_wait_for_the_cooldown_and_are_made_once() {
+        let clock = FakeClock::at(test_time(0));
+        let mut detector = PatternDetector::with_clock(30, Arc::new(clock.clone()));
+        let commands = history(&["cd src", "ls", "cd ..", "ls", "cd docs", "ls"]);
+
+        assert_eq!(detector.poll(&commands), None);
+        clock.advance(Duration::minutes(29));
+        assert_eq!(detector.poll(&commands), None);
+        clock.advance(Duration::minutes(1));
+        assert_eq!(detector.poll(&commands), Some((Pattern::CdThenLs, 3)));
+
+        clock.advance(Duration::hours(2));
+        assert_eq!(detector.poll(&commands), None);
+    }
+}
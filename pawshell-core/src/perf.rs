@@ -0,0 +1,144 @@
+//! How fast replies come
+//!
+//! Replies arrive in one piece rather than streamed, so the time to the first
+//! token and the generation speed come from the backend where it reports
+//! them: Ollama says how long it took to load the model and read the prompt,
+//! and how long it then spent generating. For other backends the speed is
+//! measured over the whole wait. Each reply can get a dimmed footer with its
+//! numbers, and `/stats` compares the models used over the last month.
+
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::llm::Generation;
+use crate::pet::ChatMessage;
+
+/// Starts the footer line under a reply, which the chat shows dimmed
+pub const FOOTER_MARK: &str = "⏱ ";
+
+/// The numbers of one reply
+#[derive(Debug, Clone, PartialEq)]
+pub struct Speed {
+    pub total_ms: u64,
+    pub first_token_ms: Option<u64>,
+    pub tokens_per_sec: Option<f64>,
+}
+
+impl Speed {
+    /// The speed of a reply that just came in after `latency`
+    pub fn measure(generation: &Generation, latency: Duration) -> Self {
+        Self::new(
+            latency.as_millis() as u64,
+            generation.first_token.map(|time| time.as_millis() as u64),
+            generation.generation_time.map(|time| time.as_millis() as u64),
+            generation.completion_tokens,
+        )
+    }
+
+    /// The speed of a recorded reply, if it came from a backend
+    pub fn of(message: &ChatMessage) -> Option<Self> {
+        Some(Self::new(message.latency_ms?, message.first_token_ms, message.generation_ms, message.completion_tokens))
+    }
+
+    fn new(total_ms: u64, first_token_ms: Option<u64>, generation_ms: Option<u64>, tokens: Option<u32>) -> Self {
+        let generation_ms = generation_ms.unwrap_or(total_ms);
+        let tokens_per_sec = tokens.filter(|_| generation_ms > 0).map(|tokens| tokens as f64 * 1000.0 / generation_ms as f64);
+        Self { total_ms, first_token_ms, tokens_per_sec }
+    }
+
+    /// Like "⏱ 3.2s · first token 0.4s · 41.0 tok/s"
+    pub fn footer(&self) -> String {
+        let mut parts = vec![format!("{}{:.1}s", FOOTER_MARK, self.total_ms as f64 / 1000.0)];
+        if let Some(first_token_ms) = self.first_token_ms {
+            parts.push(format!("first token {:.1}s", first_token_ms as f64 / 1000.0));
+        }
+        if let Some(tokens_per_sec) = self.tokens_per_sec {
+            parts.push(format!("{:.1} tok/s", tokens_per_sec));
+        }
+        parts.join(" · ")
+    }
+}
+
+/// Sums of one model's replies
+#[derive(Default)]
+struct ModelTotals {
+    replies: u32,
+    total_ms: u64,
+    first_token_ms: u64,
+    first_tokens: u32,
+    tokens: u64,
+    generation_ms: u64,
+}
+
+/// How fast each model answered since `since`, for `/stats`
+pub fn by_model(chats: &[ChatMessage], since: DateTime<Utc>) -> Option<String> {
+    let mut models: BTreeMap<&str, ModelTotals> = BTreeMap::new();
+    for chat in chats.iter().filter(|chat| chat.timestamp.is_some_and(|timestamp| timestamp >= since)) {
+        let (Some(model), Some(total_ms)) = (chat.model.as_deref(), chat.latency_ms) else {
+            continue;
+        };
+        let totals = models.entry(model).or_default();
+        totals.replies += 1;
+        totals.total_ms += total_ms;
+        if let Some(first_token_ms) = chat.first_token_ms {
+            totals.first_token_ms += first_token_ms;
+            totals.first_tokens += 1;
+        }
+        if let Some(tokens) = chat.completion_tokens {
+            totals.tokens += tokens as u64;
+            totals.generation_ms += chat.generation_ms.unwrap_or(total_ms);
+        }
+    }
+    if models.is_empty() {
+        return None;
+    }
+    let lines: Vec<String> = models
+        .iter()
+        .map(|(model, totals)| {
+            let mut line = format!("- {}: {:.1}s per reply", model, totals.total_ms as f64 / totals.replies as f64 / 1000.0);
+            if totals.first_tokens > 0 {
+                line.push_str(&format!(", first token {:.1}s", totals.first_token_ms as f64 / totals.first_tokens as f64 / 1000.0));
+            }
+            if totals.generation_ms > 0 {
+                line.push_str(&format!(", {:.1} tok/s", totals.tokens as f64 * 1000.0 / totals.generation_ms as f64));
+            }
+            line.push_str(&format!(" ({} repl{})", totals.replies, if totals.replies == 1 { "y" } else { "ies" }));
+            line
+        })
+        .collect();
+    Some(format!("Speed by Model:\n{}", lines.join("\n")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn speed_is_measured_per_reply_and_per_model() {
+        let reply = |model: &str, latency_ms, first_token_ms, generation_ms, tokens| ChatMessage {
+            model: Some(model.to_string()),
+            latency_ms: Some(latency_ms),
+            first_token_ms,
+            generation_ms,
+            completion_tokens: Some(tokens),
+            ..ChatMessage::new(String::new(), String::new())
+        };
+        let chats = vec![
+            reply("llama3.2", 3_000, Some(500), Some(2_000), 80),
+            reply("llama3.2", 1_000, Some(300), Some(500), 20),
+            reply("gpt-4o", 2_000, None, None, 50),
+            ChatMessage::new("offline".to_string(), "*purrs*".to_string()),
+        ];
+        assert_eq!(Speed::of(&chats[0]).unwrap().footer(), "⏱ 3.0s · first token 0.5s · 40.0 tok/s");
+        assert_eq!(Speed::of(&chats[2]).unwrap().footer(), "⏱ 2.0s · 25.0 tok/s");
+        assert_eq!(Speed::of(&chats[3]), None);
+        assert_eq!(
+            by_model(&chats, Utc::now() - chrono::Duration::days(1)).unwrap(),
+            "Speed by Model:\n\
+             - gpt-4o: 2.0s per reply, 25.0 tok/s (1 reply)\n\
+             - llama3.2: 2.0s per reply, first token 0.4s, 40.0 tok/s (2 replies)"
+        );
+        assert_eq!(by_model(&chats, Utc::now() + chrono::Duration::days(1)), None);
+    }
+}
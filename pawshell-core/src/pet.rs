@@ -0,0 +1,327 @@
+//! Pet state and behavior management for PetCLI
+//!
+//! This module defines the core pet-related functionality, including:
+//! - Pet state tracking (mood, interaction history)
+//! - Pet behavior traits and implementations
+//! - Chat history management
+//!
+//! Consider splitting this module if pet behaviors become more complex:
+//! - Move chat history to a dedicated ChatHistory module
+//! - Create separate modules for different pet personalities/behaviors
+//!
+//! How mood changes over time lives in the `mood` module.
+
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use std::collections::BTreeMap;
+
+use crate::clock;
+use crate::accessories::Accessory;
+use crate::adoption::Pedigree;
+use crate::mood::MoodSample;
+use crate::usage::SessionStats;
+use crate::aliases::AdoptedAlias;
+use crate::bookmarks::Bookmark;
+use crate::feedback::Reaction;
+use crate::flashcards::Flashcard;
+use crate::pomodoro::FocusDay;
+use crate::breaks::BreakDay;
+use crate::budget::SpendDay;
+use crate::project_scan::ProjectMemory;
+use crate::reminders::{Reminder, Todo};
+use crate::shared_store::Feeding;
+use crate::skills::Track;
+use crate::threads::Thread;
+use crate::chat_sessions::ChatSession;
+
+/// Represents the current state of the pet, including mood and interaction history
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PetState {
+    pub name: String,
+    pub mood: f32,          // 0.0 to 1.0
+    pub last_interaction: DateTime<Utc>,
+    #[serde(deserialize_with = "deserialize_chat_history")]
+    pub chat_history: Vec<ChatMessage>,
+    /// Exchange times saved by older versions, aligned with the end of
+    /// `chat_history`; moved into the messages by `adopt_legacy_timestamps`
+    #[serde(default, skip_serializing)]
+    pub chat_timestamps: Vec<DateTime<Utc>>,
+    #[serde(default)]
+    pub bookmarks: Vec<Bookmark>,
+    /// 👍/👎 on replies, which tune later prompts
+    #[serde(default)]
+    pub reactions: Vec<Reaction>,
+    /// Alias suggestions the user added to their alias file
+    #[serde(default)]
+    pub adopted_aliases: Vec<AdoptedAlias>,
+    #[serde(default)]
+    pub reminders: Vec<Reminder>,
+    #[serde(default)]
+    pub todos: Vec<Todo>,
+    /// Finished pomodoros per day
+    #[serde(default)]
+    pub focus_log: Vec<FocusDay>,
+    /// Breaks taken per day
+    #[serde(default)]
+    pub break_log: Vec<BreakDay>,
+    /// Estimated spending on paid backends per day
+    #[serde(default)]
+    pub spend_log: Vec<SpendDay>,
+    /// When each user was last around, for a pet shared by a team
+    #[serde(default)]
+    pub visitors: BTreeMap<String, DateTime<Utc>>,
+    /// Who gave the pet its last treat
+    #[serde(default)]
+    pub fed_by: Option<Feeding>,
+    /// Accessories earned so far
+    #[serde(default)]
+    pub unlocked_accessories: Vec<Accessory>,
+    /// Accessories being worn, drawn onto the art
+    #[serde(default)]
+    pub accessories: Vec<Accessory>,
+    /// The mood every half hour over the last week
+    #[serde(default)]
+    pub mood_log: Vec<MoodSample>,
+    /// When the last automatic weekly report was made
+    #[serde(default)]
+    pub last_weekly_report: Option<NaiveDate>,
+    /// Learning tracks picked with `/learn`
+    #[serde(default)]
+    pub learning_tracks: Vec<Track>,
+    /// IDs of the skills learned so far
+    #[serde(default)]
+    pub learned_skills: Vec<String>,
+    /// `/flashcards` and where each is in its review schedule
+    #[serde(default)]
+    pub flashcards: Vec<Flashcard>,
+    /// When the pet last said that flashcards are due
+    #[serde(default)]
+    pub last_flashcard_reminder: Option<NaiveDate>,
+    /// Finished TUI sessions of the last month
+    #[serde(default)]
+    pub sessions: Vec<SessionStats>,
+    /// Projects the pet was started in, by their root directory
+    #[serde(default)]
+    pub projects: BTreeMap<String, ProjectMemory>,
+    /// Recorded input macros by register letter
+    #[serde(default)]
+    pub macros: BTreeMap<String, Vec<String>>,
+    /// Species, color, quirk and talent picked from a litter with `/adopt`
+    #[serde(default)]
+    pub pedigree: Option<Pedigree>,
+    /// Threads forked off the conversation with `/fork`
+    #[serde(default)]
+    pub threads: Vec<Thread>,
+    /// The thread being talked in; none for the main conversation
+    #[serde(default)]
+    pub current_thread: Option<String>,
+    /// TUI sessions and their titles, for `/sessions`
+    #[serde(default)]
+    pub chat_sessions: Vec<ChatSession>,
+    /// What was typed but not sent when the TUI quit, put back in the input next time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_draft: Option<String>,
+}
+
+/// One exchange of the conversation and how the response came about
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ChatMessage {
+    pub user_message: String,
+    pub response: String,
+    /// Missing for exchanges saved before timestamps were recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Backend and model that wrote the response (none for the pet's offline replies)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completion_tokens: Option<u32>,
+    /// How long the backend took to respond
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    /// Of that, how long until the first token and how long generating, where the backend says
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_token_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Revision::is_original")]
+    pub revision: Revision,
+    /// The `/fork` thread it was said in; none for the main conversation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread: Option<String>,
+    /// The TUI session it was said in; none outside the TUI and before sessions were kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<u64>,
+}
+
+/// Whether a message is as first written
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum Revision {
+    #[default]
+    Original,
+    /// The user edited their message
+    Edited,
+    /// The response was generated again
+    Regenerated,
+}
+
+impl Revision {
+    fn is_original(&self) -> bool {
+        *self == Revision::Original
+    }
+}
+
+impl ChatMessage {
+    /// An exchange that happened just now, without backend details
+    pub fn new(user_message: String, response: String) -> Self {
+        Self {
+            user_message,
+            response,
+            timestamp: Some(clock::now()),
+            provider: None,
+            model: None,
+            prompt_tokens: None,
+            completion_tokens: None,
+            latency_ms: None,
+            first_token_ms: None,
+            generation_ms: None,
+            revision: Revision::Original,
+            thread: None,
+            session: None,
+        }
+    }
+}
+
+/// Reads chat history saved either as messages or, by older versions, as
+/// `(user_message, response)` pairs
+fn deserialize_chat_history<'de, D>(deserializer: D) -> Result<Vec<ChatMessage>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Stored {
+        Message(ChatMessage),
+        Pair(String, String),
+    }
+    let stored: Vec<Stored> = Vec::deserialize(deserializer)?;
+    Ok(stored
+        .into_iter()
+        .map(|stored| match stored {
+            Stored::Message(message) => message,
+            Stored::Pair(user_message, response) => ChatMessage { timestamp: None, ..ChatMessage::new(user_message, response) },
+        })
+        .collect())
+}
+
+impl PetState {
+    /// Removes the `count` oldest exchanges
+    pub fn remove_oldest(&mut self, count: usize) {
+        let count = count.min(self.chat_history.len());
+        self.chat_history.drain(..count);
+    }
+
+    /// Moves `chat_timestamps` from a state saved by an older version into
+    /// the messages they belong to
+    pub fn adopt_legacy_timestamps(&mut self) {
+        let timestamps = std::mem::take(&mut self.chat_timestamps);
+        let offset = self.chat_history.len().saturating_sub(timestamps.len());
+        let skipped = timestamps.len().saturating_sub(self.chat_history.len());
+        for (message, timestamp) in self.chat_history[offset..].iter_mut().zip(timestamps.into_iter().skip(skipped)) {
+            message.timestamp.get_or_insert(timestamp);
+        }
+    }
+
+    /// Days in a row, up to today in `now`'s time zone, with at least one chat
+    ///
+    /// A streak that ended yesterday still counts, so it doesn't reset before
+    /// the first chat of the day.
+    pub fn chat_streak<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> usize {
+        let mut days: Vec<_> = self
+            .chat_history
+            .iter()
+            .filter_map(|message| message.timestamp)
+            .map(|timestamp| timestamp.with_timezone(&now.timezone()).date_naive())
+            .collect();
+        days.sort_unstable();
+        days.dedup();
+        let today = now.date_naive();
+        let mut day = match days.last() {
+            Some(&last) if last == today || last == today - Duration::days(1) => last,
+            _ => return 0,
+        };
+        let mut streak = 0;
+        for &chatted in days.iter().rev() {
+            if chatted != day {
+                break;
+            }
+            streak += 1;
+            day -= Duration::days(1);
+        }
+        streak
+    }
+}
+
+impl Default for PetState {
+    fn default() -> Self {
+        Self {
+            name: String::from("Whiskers"),
+            mood: 0.8,
+            last_interaction: clock::now(),
+            chat_history: Vec::new(),
+            chat_timestamps: Vec::new(),
+            bookmarks: Vec::new(),
+            adopted_aliases: Vec::new(),
+            reminders: Vec::new(),
+            todos: Vec::new(),
+            focus_log: Vec::new(),
+            break_log: Vec::new(),
+            spend_log: Vec::new(),
+            visitors: BTreeMap::new(),
+            fed_by: None,
+            unlocked_accessories: Vec::new(),
+            accessories: Vec::new(),
+            mood_log: Vec::new(),
+            last_weekly_report: None,
+            learning_tracks: Vec::new(),
+            learned_skills: Vec::new(),
+            flashcards: Vec::new(),
+            last_flashcard_reminder: None,
+            sessions: Vec::new(),
+            projects: BTreeMap::new(),
+            macros: BTreeMap::new(),
+            pedigree: None,
+            threads: Vec::new(),
+            current_thread: None,
+            chat_sessions: Vec::new(),
+            input_draft: None,
+            reactions: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chatted_at(times: &[&str]) -> PetState {
+        let chat_history = times
+            .iter()
+            .map(|time| ChatMessage { timestamp: Some(time.parse().unwrap()), ..ChatMessage::new(String::new(), String::new()) })
+            .collect();
+        PetState { chat_history, ..PetState::default() }
+    }
+
+    #[test]
+    fn chat_streak_counts_consecutive_days_up_to_yesterday() {
+        let state = chatted_at(&["2024-03-01T09:00:00Z", "2024-03-03T09:00:00Z", "2024-03-04T23:00:00Z", "2024-03-04T08:00:00Z"]);
+        let now: DateTime<Utc> = "2024-03-05T10:00:00Z".parse().unwrap();
+        assert_eq!(state.chat_streak(&now), 2);
+        assert_eq!(state.chat_streak(&(now + Duration::days(2))), 0);
+        assert_eq!(PetState::default().chat_streak(&now), 0);
+    }
+}
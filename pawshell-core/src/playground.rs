@@ -0,0 +1,151 @@
+//! The jq and awk playgrounds opened with `/jq` and `/awk`
+//!
+//! Like the regex lab, an expression and some sample input are edited side
+//! by side. After every change the expression is run on the input with the
+//! real tool, if it's installed, and its output or error is shown next to
+//! the input. When it errors, the backend can be asked for a fixed
+//! expression, which replaces the broken one.
+//!
+//! The input is written to a file in the cache directory for each run rather
+//! than piped in, so a tool that doesn't read it all can't block.
+
+use std::process::Stdio;
+use std::time::Duration;
+
+use crate::config_path;
+use crate::templates::Prompt;
+
+/// A run that takes longer is stopped
+const RUN_TIMEOUT: Duration = Duration::from_secs(3);
+/// Output beyond this is cut off
+const MAX_OUTPUT_BYTES: usize = 32 * 1024;
+/// Input beyond this many characters is left out of fix prompts
+const MAX_SAMPLE_CHARS: usize = 2_000;
+
+const FIX_TEMPLATE: &str = "This {{ tool }} expression fails:\n\n{{ expression }}\n\nwith this error:\n\n\
+{{ error }}\n\nThe input looks like this:\n\n{{ input }}\n\nFix the expression so it does what it was \
+probably meant to. Reply with the fixed expression alone in a ``` block, then one sentence on what \
+was wrong.";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tool {
+    Jq,
+    Awk,
+}
+
+impl Tool {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tool::Jq => "jq",
+            Tool::Awk => "awk",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Playground {
+    pub tool: Tool,
+    pub expression: String,
+    pub input: String,
+    /// Whether typing goes to the input rather than the expression
+    pub editing_input: bool,
+    /// What the last finished run printed, or why it failed
+    pub output: Option<Result<String, String>>,
+    /// Counts edits, so results of runs for older versions can be ignored
+    pub revision: u64,
+}
+
+impl Playground {
+    pub fn new(tool: Tool, expression: &str) -> Self {
+        Self { tool, expression: expression.to_string(), input: String::new(), editing_input: false, output: None, revision: 0 }
+    }
+
+    /// The text being edited
+    pub fn focused(&mut self) -> &mut String {
+        if self.editing_input {
+            &mut self.input
+        } else {
+            &mut self.expression
+        }
+    }
+
+    /// Asks for a fixed expression, with the error it gave
+    pub fn fix_prompt(&self, error: &str) -> Prompt {
+        let input: String = self.input.chars().take(MAX_SAMPLE_CHARS).collect();
+        Prompt::new("playground", FIX_TEMPLATE)
+            .var("tool", self.tool.name())
+            .var("expression", &self.expression)
+            .var("error", error.trim())
+            .var("input", input)
+    }
+}
+
+/// Runs `expression` on `input` with the tool; `revision` keeps the input
+/// files of runs in flight apart
+pub async fn run(tool: Tool, expression: &str, input: &str, revision: u64) -> Result<String, String> {
+    let dir = config_path::get_cache_dir();
+    let path = dir.join(format!("playground-{}-{}", std::process::id(), revision));
+    std::fs::create_dir_all(&dir)
+        .and_then(|()| std::fs::write(&path, input))
+        .map_err(|e| format!("Couldn't write the input to {}: {}", path.display(), e))?;
+    let child = tokio::process::Command::new(tool.name())
+        .arg(expression)
+        .arg(&path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn();
+    let result = match child {
+        Ok(child) => match tokio::time::timeout(RUN_TIMEOUT, child.wait_with_output()).await {
+            Ok(Ok(output)) if output.status.success() => Ok(truncate(&output.stdout)),
+            Ok(Ok(output)) => Err(truncate(&output.stderr).replace(&path.display().to_string(), "<input>")),
+            Ok(Err(e)) => Err(format!("Couldn't run {}: {}", tool.name(), e)),
+            Err(_) => Err(format!("{} was still running after {} seconds, so I stopped it.", tool.name(), RUN_TIMEOUT.as_secs())),
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(format!("{} isn't installed, so I can't run it here.", tool.name())),
+        Err(e) => Err(format!("Couldn't start {}: {}", tool.name(), e)),
+    };
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+fn truncate(bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(&bytes[..bytes.len().min(MAX_OUTPUT_BYTES)]).into_owned();
+    if bytes.len() > MAX_OUTPUT_BYTES {
+        format!("{}\n… (cut off)", text)
+    } else {
+        text
+    }
+}
+
+/// The fixed expression in a reply: its first code block, or inline code
+pub fn extract_fix(reply: &str) -> Option<String> {
+    let mut block: Option<Vec<&str>> = None;
+    for line in reply.lines() {
+        match (&mut block, line.trim_start().starts_with("```")) {
+            (None, true) => block = Some(Vec::new()),
+            (Some(_), true) => break,
+            (Some(lines), false) => lines.push(line),
+            (None, false) => {}
+        }
+    }
+    let fix = match block {
+        Some(lines) => lines.join("\n"),
+        None => reply.split('`').nth(1)?.to_string(),
+    };
+    let fix = fix.trim().to_string();
+    (!fix.is_empty()).then_some(fix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixes_are_taken_from_the_code_block() {
+        assert_eq!(extract_fix("```jq\n.items[] | .name\n```\nThe array needed iterating.").as_deref(), Some(".items[] | .name"));
+        assert_eq!(extract_fix("Use `{ print $2 }` instead.").as_deref(), Some("{ print $2 }"));
+        assert_eq!(extract_fix("No idea."), None);
+    }
+}
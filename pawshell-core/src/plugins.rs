@@ -0,0 +1,339 @@
+//! WebAssembly plugins from `~/.config/petcli/plugins/`
+//!
+//! Every `*.wasm` file in the plugins directory is loaded at startup and runs
+//! sandboxed: it can't touch files, the network or the pet's state, each call
+//! gets a fixed instruction budget, and its memory is capped. A plugin that
+//! traps or runs out of budget is disabled for the rest of the session.
+//!
+//! # Plugin ABI (version 1)
+//!
+//! A plugin exports its `memory` and `pawshell_alloc(len: i32) -> i32`, which
+//! returns a buffer the host writes strings into. Strings are UTF-8 and passed
+//! as a pointer and a length; a returned string is packed into an `i64` as
+//! `(ptr << 32) | len`, with `0` meaning "nothing to say". It may export any of
+//! these hooks:
+//!
+//! - `on_message(ptr, len) -> i64`: the user sent a chat message
+//! - `on_command(ptr, len) -> i64`: a command ran; the argument is JSON with
+//!   `command`, `cwd` and `exit_code` (the latter two may be null)
+//! - `on_tick() -> i64`: called about once a second
+//! - `context() -> i64`: extra text for the prompt (a context provider)
+//!
+//! Returned text from the first three is shown as a message from the pet. An
+//! optional `pawshell_api_version() -> i32` export must return 1.
+
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use wasmi::{Engine, Instance, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc};
+
+use crate::config_path;
+use crate::history::HistoryEntry;
+
+/// The ABI version this host implements
+pub const API_VERSION: i32 = 1;
+/// Instructions a plugin may execute per call before it is stopped
+const FUEL_PER_CALL: u64 = 10_000_000;
+/// Longest string a plugin may return
+const MAX_RESULT_BYTES: usize = 64 * 1024;
+/// Most linear memory a plugin may have; growing past it fails
+const MAX_MEMORY_BYTES: usize = 16 * 1024 * 1024;
+/// Most elements a plugin's tables may hold
+const MAX_TABLE_ELEMENTS: u32 = 10_000;
+
+/// Where plugins are loaded from (shared by all profiles)
+pub fn plugins_dir() -> PathBuf {
+    config_path::base_config_dir().join("plugins")
+}
+
+/// A loaded plugin and the hooks it exports
+struct Plugin {
+    name: String,
+    store: Store<StoreLimits>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    on_message: Option<TypedFunc<(i32, i32), i64>>,
+    on_command: Option<TypedFunc<(i32, i32), i64>>,
+    on_tick: Option<TypedFunc<(), i64>>,
+    context: Option<TypedFunc<(), i64>>,
+}
+
+impl Plugin {
+    fn load(engine: &Engine, path: &Path) -> Result<Self, String> {
+        let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let wasm = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let module = Module::new(engine, &wasm).map_err(|e| format!("{} is not a valid WebAssembly module: {}", name, e))?;
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(MAX_MEMORY_BYTES)
+            .table_elements(MAX_TABLE_ELEMENTS)
+            .instances(1)
+            .build();
+        let mut store = Store::new(engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(FUEL_PER_CALL).map_err(|e| e.to_string())?;
+        let instance = Linker::<StoreLimits>::new(engine)
+            .instantiate(&mut store, &module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(|e| format!("{} couldn't start (plugins can't import anything): {}", name, e))?;
+
+        if let Ok(version) = instance.get_typed_func::<(), i32>(&store, "pawshell_api_version") {
+            let version = version.call(&mut store, ()).map_err(|e| format!("{}: {}", name, e))?;
+            if version != API_VERSION {
+                return Err(format!("{} needs plugin API version {}, but this pawshell has {}", name, version, API_VERSION));
+            }
+        }
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or_else(|| format!("{} doesn't export its memory", name))?;
+        let alloc = instance
+            .get_typed_func(&store, "pawshell_alloc")
+            .map_err(|_| format!("{} doesn't export pawshell_alloc(i32) -> i32", name))?;
+        Ok(Self {
+            on_message: hook(&instance, &store, "on_message"),
+            on_command: hook(&instance, &store, "on_command"),
+            on_tick: hook(&instance, &store, "on_tick"),
+            context: hook(&instance, &store, "context"),
+            name,
+            store,
+            memory,
+            alloc,
+        })
+    }
+
+    /// Names of the hooks this plugin exports
+    fn hooks(&self) -> Vec<&'static str> {
+        [
+            ("on_message", self.on_message.is_some()),
+            ("on_command", self.on_command.is_some()),
+            ("on_tick", self.on_tick.is_some()),
+            ("context", self.context.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(name, exported)| exported.then_some(name))
+        .collect()
+    }
+
+    /// Calls a hook that takes a string
+    fn call_with(&mut self, hook: TypedFunc<(i32, i32), i64>, argument: &str) -> Result<Option<String>, String> {
+        self.store.set_fuel(FUEL_PER_CALL).map_err(|e| e.to_string())?;
+        let len = i32::try_from(argument.len()).map_err(|_| "argument too long".to_string())?;
+        let ptr = self.alloc.call(&mut self.store, len).map_err(|e| e.to_string())?;
+        self.memory
+            .write(&mut self.store, ptr as u32 as usize, argument.as_bytes())
+            .map_err(|e| format!("pawshell_alloc returned a bad buffer: {}", e))?;
+        let packed = hook.call(&mut self.store, (ptr, len)).map_err(|e| e.to_string())?;
+        self.read_result(packed)
+    }
+
+    /// Calls a hook without arguments
+    fn call(&mut self, hook: TypedFunc<(), i64>) -> Result<Option<String>, String> {
+        self.store.set_fuel(FUEL_PER_CALL).map_err(|e| e.to_string())?;
+        let packed = hook.call(&mut self.store, ()).map_err(|e| e.to_string())?;
+        self.read_result(packed)
+    }
+
+    fn read_result(&self, packed: i64) -> Result<Option<String>, String> {
+        let (ptr, len) = ((packed as u64 >> 32) as usize, (packed as u64 & 0xffff_ffff) as usize);
+        if len == 0 {
+            return Ok(None);
+        }
+        if len > MAX_RESULT_BYTES {
+            return Err(format!("returned {} bytes; the limit is {}", len, MAX_RESULT_BYTES));
+        }
+        let mut buffer = vec![0; len];
+        self.memory
+            .read(&self.store, ptr, &mut buffer)
+            .map_err(|e| format!("returned a string outside its memory: {}", e))?;
+        let text = String::from_utf8(buffer).map_err(|_| "returned text that isn't UTF-8".to_string())?;
+        Ok(Some(text).filter(|text| !text.trim().is_empty()))
+    }
+}
+
+fn hook<Params: wasmi::WasmParams, Results: wasmi::WasmResults>(
+    instance: &Instance,
+    store: &Store<StoreLimits>,
+    name: &str,
+) -> Option<TypedFunc<Params, Results>> {
+    instance.get_typed_func(store, name).ok()
+}
+
+/// All loaded plugins
+///
+/// Hooks return what the plugins said, one line per plugin prefixed with its
+/// name, and a line about each plugin that failed and was disabled.
+#[derive(Default)]
+pub struct PluginHost {
+    plugins: Vec<Plugin>,
+}
+
+impl PluginHost {
+    /// Loads every `*.wasm` file in `dir`, returning the host and a problem per plugin that didn't load
+    pub fn load_dir(dir: &Path) -> (Self, Vec<String>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return (Self::default(), Vec::new());
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "wasm"))
+            .collect();
+        paths.sort();
+
+        let mut config = wasmi::Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let mut host = Self::default();
+        let mut problems = Vec::new();
+        for path in paths {
+            match Plugin::load(&engine, &path) {
+                Ok(plugin) => host.plugins.push(plugin),
+                Err(e) => problems.push(e),
+            }
+        }
+        (host, problems)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    /// Each plugin's name with the hooks it exports
+    pub fn describe(&self) -> Vec<String> {
+        self.plugins
+            .iter()
+            .map(|plugin| format!("{} ({})", plugin.name, plugin.hooks().join(", ")))
+            .collect()
+    }
+
+    pub fn on_message(&mut self, message: &str) -> Vec<String> {
+        self.run(|plugin| match plugin.on_message {
+            Some(hook) => plugin.call_with(hook, message),
+            None => Ok(None),
+        })
+    }
+
+    pub fn on_command(&mut self, entry: &HistoryEntry) -> Vec<String> {
+        let argument = json!({ "command": entry.command, "cwd": entry.cwd, "exit_code": entry.exit_code }).to_string();
+        self.run(|plugin| match plugin.on_command {
+            Some(hook) => plugin.call_with(hook, &argument),
+            None => Ok(None),
+        })
+    }
+
+    pub fn on_tick(&mut self) -> Vec<String> {
+        self.run(|plugin| match plugin.on_tick {
+            Some(hook) => plugin.call(hook),
+            None => Ok(None),
+        })
+    }
+
+    /// Prompt context from every context provider, or None if they had nothing to add
+    ///
+    /// Failures are dropped here; the plugin is still disabled.
+    pub fn context(&mut self) -> Option<String> {
+        let mut sections = Vec::new();
+        self.plugins.retain_mut(|plugin| match plugin.context {
+            Some(hook) => match plugin.call(hook) {
+                Ok(text) => {
+                    sections.extend(text.map(|text| format!("From the {} plugin:\n{}", plugin.name, text)));
+                    true
+                }
+                Err(_) => false,
+            },
+            None => true,
+        });
+        (!sections.is_empty()).then(|| sections.join("\n\n"))
+    }
+
+    /// Runs a hook on every plugin, disabling the ones that fail
+    fn run(&mut self, mut call: impl FnMut(&mut Plugin) -> Result<Option<String>, String>) -> Vec<String> {
+        let mut output = Vec::new();
+        self.plugins.retain_mut(|plugin| match call(plugin) {
+            Ok(text) => {
+                output.extend(text.map(|text| format!("[{}] {}", plugin.name, text)));
+                true
+            }
+            Err(e) => {
+                tracing::warn!(plugin = %plugin.name, "plugin disabled: {}", e);
+                output.push(format!("The {} plugin failed and is disabled until restart: {}", plugin.name, e));
+                false
+            }
+        });
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Echoes chat messages back and loops forever on ticks
+    const ECHO_PLUGIN: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (func (export "pawshell_alloc") (param i32) (result i32) (i32.const 1024))
+          (func (export "on_message") (param $ptr i32) (param $len i32) (result i64)
+            (i64.or
+              (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+              (i64.extend_i32_u (local.get $len))))
+          (func (export "on_tick") (result i64) (loop $forever (br $forever)) (i64.const 0)))
+    "#;
+
+    fn host_with(wat_source: &str) -> (PluginHost, Vec<String>) {
+        let dir = std::env::temp_dir().join(format!("pawshell-plugins-test-{}-{}", std::process::id(), wat_source.len()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("echo.wasm"), wat::parse_str(wat_source).unwrap()).unwrap();
+        let loaded = PluginHost::load_dir(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        loaded
+    }
+
+    #[test]
+    fn hooks_pass_strings_both_ways() {
+        let (mut host, problems) = host_with(ECHO_PLUGIN);
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(host.describe(), vec!["echo (on_message, on_tick)"]);
+        assert_eq!(host.on_message("héllo"), vec!["[echo] héllo"]);
+        assert!(host.on_message("   ").is_empty());
+        assert_eq!(host.context(), None);
+    }
+
+    #[test]
+    fn runaway_plugins_are_stopped_and_disabled() {
+        let (mut host, _) = host_with(ECHO_PLUGIN);
+        let output = host.on_tick();
+        assert_eq!(output.len(), 1);
+        assert!(output[0].contains("disabled"));
+        assert!(host.is_empty());
+    }
+
+    #[test]
+    fn plugin_memory_is_capped() {
+        let pages = (MAX_MEMORY_BYTES / 65536) as i32;
+        // Starting out over the limit
+        let (host, problems) = host_with(&format!(r#"(module (memory (export "memory") {}))"#, pages + 1));
+        assert!(host.is_empty());
+        assert_eq!(problems.len(), 1);
+        // Growing past it fails, which `memory.grow` reports as -1
+        let (mut host, problems) = host_with(&format!(
+            r#"
+            (module
+              (memory (export "memory") 1)
+              (data (i32.const 0) "ok")
+              (func (export "pawshell_alloc") (param i32) (result i32) (i32.const 1024))
+              (func (export "on_tick") (result i64)
+                (if (i32.ne (memory.grow (i32.const {})) (i32.const -1)) (then (unreachable)))
+                (i64.const 2)))
+            "#,
+            pages
+        ));
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(host.on_tick(), vec!["[echo] ok"]);
+    }
+
+    #[test]
+    fn plugins_without_alloc_are_rejected() {
+        let (host, problems) = host_with(r#"(module (memory (export "memory") 1))"#);
+        assert!(host.is_empty());
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("pawshell_alloc"));
+    }
+}
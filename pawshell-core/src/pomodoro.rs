@@ -0,0 +1,161 @@
+//! Pomodoro timers and the focus log
+//!
+//! `/pomodoro [work/break]` alternates work and break phases (25 and 5
+//! minutes unless given) until stopped. The timer itself only lives as long as
+//! the session, but every finished work phase is added to the pet's
+//! `focus_log`, one entry per day, which `/stats` sums up.
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Which part of the cycle a pomodoro is in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    Work,
+    Break,
+}
+
+/// A running work/break cycle
+pub struct Pomodoro {
+    pub work: Duration,
+    pub rest: Duration,
+    pub phase: Phase,
+    /// When the current phase started
+    started: DateTime<Utc>,
+    /// Work phases finished since the timer was started
+    pub completed: u32,
+}
+
+impl Pomodoro {
+    pub fn new(work: Duration, rest: Duration, now: DateTime<Utc>) -> Self {
+        Self { work, rest, phase: Phase::Work, started: now, completed: 0 }
+    }
+
+    /// Parses `/pomodoro` arguments like `25/5` or `50`, in minutes; no
+    /// arguments means 25/5
+    pub fn parse_lengths(args: &str) -> Option<(Duration, Duration)> {
+        let args = args.trim();
+        if args.is_empty() {
+            return Some((Duration::minutes(25), Duration::minutes(5)));
+        }
+        let (work, rest) = args.split_once('/').unwrap_or((args, "5"));
+        let minutes = |text: &str| text.trim().trim_end_matches('m').parse::<i64>().ok().filter(|&m| (1..=24 * 60).contains(&m));
+        Some((Duration::minutes(minutes(work)?), Duration::minutes(minutes(rest)?)))
+    }
+
+    fn length(&self) -> Duration {
+        match self.phase {
+            Phase::Work => self.work,
+            Phase::Break => self.rest,
+        }
+    }
+
+    pub fn remaining(&self, now: DateTime<Utc>) -> Duration {
+        (self.started + self.length() - now).max(Duration::zero())
+    }
+
+    /// Moves on to the next phase if the current one is over, returning the
+    /// phase that just ended
+    ///
+    /// A phase that ended long ago (e.g. while the machine slept) still only
+    /// advances the cycle by one step; the next phase starts now.
+    pub fn advance(&mut self, now: DateTime<Utc>) -> Option<Phase> {
+        if now < self.started + self.length() {
+            return None;
+        }
+        let finished = self.phase;
+        if finished == Phase::Work {
+            self.completed += 1;
+        }
+        self.phase = match finished {
+            Phase::Work => Phase::Break,
+            Phase::Break => Phase::Work,
+        };
+        self.started = now;
+        Some(finished)
+    }
+
+    /// The countdown for the status bar, like `🍅 12:04` or `☕ 3:59`
+    pub fn status(&self, now: DateTime<Utc>) -> String {
+        let remaining = self.remaining(now).num_seconds();
+        let icon = match self.phase {
+            Phase::Work => "🍅",
+            Phase::Break => "☕",
+        };
+        format!("{} {}:{:02}", icon, remaining / 60, remaining % 60)
+    }
+}
+
+/// Focused time on one day
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FocusDay {
+    pub date: NaiveDate,
+    pub sessions: u32,
+    pub minutes: u32,
+}
+
+/// Adds a finished work phase to the day's entry
+pub fn record(log: &mut Vec<FocusDay>, date: NaiveDate, minutes: u32) {
+    match log.iter_mut().find(|day| day.date == date) {
+        Some(day) => {
+            day.sessions += 1;
+            day.minutes += minutes;
+        }
+        None => log.push(FocusDay { date, sessions: 1, minutes }),
+    }
+}
+
+/// Today's focus and the last seven days', for `/stats`
+pub fn summary(log: &[FocusDay], today: NaiveDate) -> String {
+    let week: Vec<&FocusDay> = log.iter().filter(|day| day.date <= today && day.date > today - Duration::days(7)).collect();
+    let (today_sessions, today_minutes) = week
+        .iter()
+        .find(|day| day.date == today)
+        .map_or((0, 0), |day| (day.sessions, day.minutes));
+    let week_sessions: u32 = week.iter().map(|day| day.sessions).sum();
+    let week_minutes: u32 = week.iter().map(|day| day.minutes).sum();
+    format!(
+        "Focus Today: {} pomodoros ({} min)\nFocus This Week: {} pomodoros ({} min)",
+        today_sessions, today_minutes, week_sessions, week_minutes
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::test_time;
+
+    #[test]
+    fn phases_alternate_and_count_work() {
+        let start = test_time(0);
+        let mut pomodoro = Pomodoro::new(Duration::minutes(25), Duration::minutes(5), start);
+        assert_eq!(pomodoro.advance(start + Duration::minutes(24)), None);
+        assert_eq!(pomodoro.status(start + Duration::seconds(61)), "🍅 23:59");
+        assert_eq!(pomodoro.advance(start + Duration::minutes(25)), Some(Phase::Work));
+        assert_eq!(pomodoro.phase, Phase::Break);
+        assert_eq!(pomodoro.completed, 1);
+        assert_eq!(pomodoro.advance(start + Duration::hours(3)), Some(Phase::Break));
+        assert_eq!(pomodoro.remaining(start + Duration::hours(3)), Duration::minutes(25));
+    }
+
+    #[test]
+    fn lengths_default_to_25_5() {
+        assert_eq!(Pomodoro::parse_lengths(""), Some((Duration::minutes(25), Duration::minutes(5))));
+        assert_eq!(Pomodoro::parse_lengths("50/10"), Some((Duration::minutes(50), Duration::minutes(10))));
+        assert_eq!(Pomodoro::parse_lengths("45"), Some((Duration::minutes(45), Duration::minutes(5))));
+        assert_eq!(Pomodoro::parse_lengths("0/5"), None);
+        assert_eq!(Pomodoro::parse_lengths("soon"), None);
+    }
+
+    #[test]
+    fn focus_is_summed_per_day_and_week() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 8).unwrap();
+        let mut log = Vec::new();
+        record(&mut log, today, 25);
+        record(&mut log, today, 25);
+        record(&mut log, today - Duration::days(3), 50);
+        record(&mut log, today - Duration::days(9), 25);
+        assert_eq!(log.len(), 3);
+        assert_eq!(summary(&log, today), "Focus Today: 2 pomodoros (50 min)\nFocus This Week: 3 pomodoros (100 min)");
+    }
+}
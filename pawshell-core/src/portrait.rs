@@ -0,0 +1,326 @@
+//! Painted portraits of the pet for `/portrait`
+//!
+//! An image model paints the pet from a prompt built out of its name, species,
+//! mood and accessories: DALL·E through OpenAI's image API, or a local Stable
+//! Diffusion web UI (AUTOMATIC1111 or compatible, started with `--api`).
+//! Portraits are saved as PNGs in the data directory's `portraits` folder.
+//!
+//! Terminals that can draw pictures get to see them right away, through
+//! kitty's graphics protocol (kitty, WezTerm, Ghostty) or sixel (foot, mlterm,
+//! xterm with sixel, ...). `terminal_graphics` overrides the guess.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Local};
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::accessories;
+use crate::config::{Config, ImageEngine, TerminalGraphics};
+use crate::config_path;
+use crate::llm::OpenAIOptions;
+use crate::mood::MoodLevel;
+use crate::pet::PetState;
+
+/// Painting takes a while, especially on a local GPU
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+/// Base64 bytes per kitty graphics escape sequence, the protocol's limit
+const KITTY_CHUNK: usize = 4096;
+
+pub fn portraits_dir() -> PathBuf {
+    config_path::get_data_dir().join("portraits")
+}
+
+/// What kind of animal the pet is: `pet_species`, the gallery pet or a cat
+pub fn species(config: &Config) -> String {
+    if let Some(species) = config.pet_species.as_deref().map(str::trim).filter(|species| !species.is_empty()) {
+        return species.to_string();
+    }
+    match config.pet_art.as_deref() {
+        // Gallery poses are named like "cat-happy"
+        Some(art) => art.split('-').next().unwrap_or(art).to_string(),
+        None => "cat".to_string(),
+    }
+}
+
+/// The image model's prompt for the pet as it is now
+pub fn prompt(state: &PetState, species: &str) -> String {
+    let mood = match MoodLevel::of(state.mood) {
+        MoodLevel::Happy => "beaming, bright-eyed and playful",
+        MoodLevel::Content => "calm and content, relaxing",
+        MoodLevel::Sad => "a little droopy and lonely, hoping for attention",
+    };
+    let outfit = accessories::outfit(&state.accessories).map(|outfit| format!(" It is wearing {}.", outfit)).unwrap_or_default();
+    let color = state.pedigree.as_ref().map(|pedigree| format!("{} ", pedigree.color)).unwrap_or_default();
+    format!(
+        "A portrait of {}, a small cartoon {}{} who lives in a programmer's terminal. \
+         It looks {}.{} Cozy storybook illustration, soft lighting, warm colors, \
+         simple background, no text.",
+        state.name, color, species, mood, outfit
+    )
+}
+
+/// The configured image model
+#[derive(Clone)]
+pub struct Painter {
+    engine: ImageEngine,
+    stable_diffusion_url: String,
+    openai: OpenAIOptions,
+}
+
+impl Painter {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            engine: config.image_engine,
+            stable_diffusion_url: config.stable_diffusion_url.clone(),
+            openai: config.openai_options(),
+        }
+    }
+
+    /// Paints `prompt`, returning the picture as PNG
+    pub async fn paint(&self, prompt: &str) -> Result<Vec<u8>, String> {
+        let client = reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build().map_err(|e| e.to_string())?;
+        let encoded = match self.engine {
+            ImageEngine::OpenAI => {
+                let api_key = std::env::var("OPENAI_API_KEY").map_err(|_| "OPENAI_API_KEY is not set")?;
+                let response: serde_json::Value = request(
+                    self.openai.authorize(client.post(self.openai.url("images/generations")), &api_key).json(&serde_json::json!({
+                        "model": "dall-e-3",
+                        "prompt": prompt,
+                        "n": 1,
+                        "size": "1024x1024",
+                        "response_format": "b64_json",
+                    })),
+                    "OpenAI",
+                )
+                .await?;
+                response["data"][0]["b64_json"].as_str().map(str::to_string)
+            }
+            ImageEngine::StableDiffusion => {
+                let url = format!("{}/sdapi/v1/txt2img", self.stable_diffusion_url.trim_end_matches('/'));
+                let response: serde_json::Value = request(
+                    client.post(&url).json(&serde_json::json!({
+                        "prompt": prompt,
+                        "negative_prompt": "text, watermark, signature, blurry, deformed",
+                        "steps": 25,
+                        "width": 512,
+                        "height": 512,
+                    })),
+                    &self.stable_diffusion_url,
+                )
+                .await?;
+                response["images"][0].as_str().map(str::to_string)
+            }
+        };
+        let encoded = encoded.ok_or("the answer had no image in it")?;
+        BASE64.decode(encoded.trim()).map_err(|e| format!("the image isn't valid base64: {}", e))
+    }
+}
+
+async fn request(request: reqwest::RequestBuilder, backend: &str) -> Result<serde_json::Value, String> {
+    let response = request.send().await.map_err(|e| format!("couldn't reach {}: {}", backend, e))?;
+    if !response.status().is_success() {
+        return Err(format!("{} answered {}", backend, response.status()));
+    }
+    response.json().await.map_err(|e| format!("{} sent something unexpected: {}", backend, e))
+}
+
+/// Saves a portrait PNG, named after the pet and the time
+pub fn save(name: &str, png: &[u8], now: &DateTime<Local>) -> Result<PathBuf, String> {
+    let dir = portraits_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| format!("couldn't create {}: {}", dir.display(), e))?;
+    let stem = name.to_lowercase().replace(char::is_whitespace, "-");
+    let path = dir.join(format!("{}-{}.png", stem, now.format("%Y-%m-%d-%H%M%S")));
+    std::fs::write(&path, png).map_err(|e| format!("couldn't write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// The most recently saved portrait
+pub fn latest() -> Option<PathBuf> {
+    std::fs::read_dir(portraits_dir())
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "png"))
+        .max_by_key(|path| std::fs::metadata(path).and_then(|meta| meta.modified()).ok())
+}
+
+/// The picture protocol to use: the configured one, or a guess from the
+/// terminal's environment for `Auto`
+///
+/// Inside tmux pictures don't get through to the outer terminal, so the guess
+/// is `Off` there.
+pub fn graphics(setting: TerminalGraphics) -> TerminalGraphics {
+    if setting != TerminalGraphics::Auto {
+        return setting;
+    }
+    let var = |name: &str| std::env::var(name).unwrap_or_default();
+    let term = var("TERM");
+    let program = var("TERM_PROGRAM");
+    if std::env::var_os("TMUX").is_some() || term.starts_with("screen") {
+        TerminalGraphics::Off
+    } else if std::env::var_os("KITTY_WINDOW_ID").is_some()
+        || term == "xterm-kitty"
+        || term == "xterm-ghostty"
+        || matches!(program.as_str(), "WezTerm" | "ghostty")
+    {
+        TerminalGraphics::Kitty
+    } else if term.starts_with("foot") || term.starts_with("mlterm") || term.contains("sixel") || program == "iTerm.app" {
+        TerminalGraphics::Sixel
+    } else {
+        TerminalGraphics::Off
+    }
+}
+
+/// Escape sequences that draw a PNG `columns` cells wide with kitty's
+/// graphics protocol, without the terminal answering back
+pub fn kitty(png: &[u8], columns: u16) -> String {
+    let encoded = BASE64.encode(png);
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(KITTY_CHUNK).collect();
+    let mut out = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+        if i == 0 {
+            let _ = write!(out, "\x1b_Ga=T,f=100,q=2,c={},m={};{}\x1b\\", columns, more, chunk);
+        } else {
+            let _ = write!(out, "\x1b_Gm={};{}\x1b\\", more, chunk);
+        }
+    }
+    out
+}
+
+/// A PNG as sixel graphics at most `max_width` pixels wide, in 252 colors
+pub fn sixel(png: &[u8], max_width: u32) -> Result<String, String> {
+    let image = decode(png)?;
+    let width = image.width.min(max_width.max(1));
+    let height = (image.height as u64 * width as u64 / image.width as u64).max(1) as u32;
+    // Each pixel's palette index, scaled down by sampling the nearest pixel
+    let indexes: Vec<u8> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let (r, g, b) = image.pixel(x * image.width / width, y * image.height / height);
+            palette_index(r, g, b)
+        })
+        .collect();
+
+    let mut out = format!("\x1bPq\"1;1;{};{}", width, height);
+    for index in 0..252u16 {
+        let (r, g, b) = (index / 42, index / 6 % 7, index % 6);
+        let _ = write!(out, "#{};2;{};{};{}", index, r * 20, g * 100 / 6, b * 20);
+    }
+    for top in (0..height).step_by(6) {
+        let rows = (height - top).min(6);
+        let band = |x: u32, row: u32| indexes[((top + row) * width + x) as usize];
+        let mut used = [false; 252];
+        for row in 0..rows {
+            for x in 0..width {
+                used[band(x, row) as usize] = true;
+            }
+        }
+        let mut first = true;
+        for color in (0..252u8).filter(|&color| used[color as usize]) {
+            if !first {
+                out.push('$');
+            }
+            first = false;
+            let _ = write!(out, "#{}", color);
+            let mut run: Option<(char, usize)> = None;
+            for x in 0..width {
+                let bits = (0..rows).filter(|&row| band(x, row) == color).fold(0u8, |bits, row| bits | 1 << row);
+                let c = char::from(63 + bits);
+                run = match run {
+                    Some((previous, count)) if previous == c => Some((c, count + 1)),
+                    Some((previous, count)) => {
+                        push_run(&mut out, previous, count);
+                        Some((c, 1))
+                    }
+                    None => Some((c, 1)),
+                };
+            }
+            if let Some((c, count)) = run {
+                push_run(&mut out, c, count);
+            }
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    Ok(out)
+}
+
+fn push_run(out: &mut String, c: char, count: usize) {
+    if count > 3 {
+        let _ = write!(out, "!{}{}", count, c);
+    } else {
+        out.extend(std::iter::repeat_n(c, count));
+    }
+}
+
+/// The nearest color of a 6×7×6 color cube
+fn palette_index(r: u8, g: u8, b: u8) -> u8 {
+    let level = |value: u8, steps: u16| ((value as u16 * (steps - 1) + 127) / 255) as u8;
+    level(r, 6) * 42 + level(g, 7) * 6 + level(b, 6)
+}
+
+/// A decoded PNG with 8 bits per sample
+struct Image {
+    width: u32,
+    height: u32,
+    channels: usize,
+    data: Vec<u8>,
+}
+
+impl Image {
+    fn pixel(&self, x: u32, y: u32) -> (u8, u8, u8) {
+        let i = (y as usize * self.width as usize + x as usize) * self.channels;
+        match self.channels {
+            1 | 2 => (self.data[i], self.data[i], self.data[i]),
+            _ => (self.data[i], self.data[i + 1], self.data[i + 2]),
+        }
+    }
+}
+
+fn decode(png: &[u8]) -> Result<Image, String> {
+    let mut decoder = png::Decoder::new(png);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(|e| format!("not a readable PNG: {}", e))?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).map_err(|e| format!("not a readable PNG: {}", e))?;
+    if info.width == 0 || info.height == 0 {
+        return Err("the picture is empty".to_string());
+    }
+    data.truncate(info.buffer_size());
+    Ok(Image { width: info.width, height: info.height, channels: info.color_type.samples(), data })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn species_comes_from_the_config_or_the_gallery() {
+        let mut config = Config::default();
+        assert_eq!(species(&config), "cat");
+        config.pet_art = Some("fox-happy".to_string());
+        assert_eq!(species(&config), "fox");
+        config.pet_species = Some("red panda".to_string());
+        assert_eq!(species(&config), "red panda");
+    }
+
+    #[test]
+    fn pngs_become_sixel_bands() {
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, 2, 7);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut pixels = vec![0u8; 2 * 7 * 3];
+        pixels[..6].copy_from_slice(&[255, 255, 255, 255, 255, 255]);
+        encoder.write_header().unwrap().write_image_data(&pixels).unwrap();
+
+        let sixel = sixel(&png, 100).unwrap();
+        assert!(sixel.starts_with("\x1bPq\"1;1;2;7#0;2;0;0;0"));
+        // A white top row over black in the first band, then one black row
+        assert!(sixel.ends_with("#0}}$#251@@-#0@@-\x1b\\"), "{}", sixel);
+    }
+}
@@ -0,0 +1,44 @@
+//! Per-project pet settings from `.pawshell.toml`
+//!
+//! A `.pawshell.toml` in the directory the pet is started from, or in one of
+//! its parents, can give the pet a persona or system prompt and change what
+//! context it sees, e.g. a Rust mentor in one repository and a Kubernetes
+//! helper in another. Only these keys are allowed, so a file checked into a
+//! repository can't change where prompts are sent or how commands are run.
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Name of the project file looked up from the current directory upwards
+pub const FILE_NAME: &str = ".pawshell.toml";
+
+/// Config keys a project file may override
+#[derive(Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub persona: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_workspace_context: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_system_context: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zoxide_context: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command_history_limit: Option<usize>,
+}
+
+/// Finds the nearest project file, starting in the current directory
+pub fn find() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    cwd.ancestors().map(|dir| dir.join(FILE_NAME)).find(|path| path.is_file())
+}
+
+/// Reads a project file, naming it in any error
+pub fn read(path: &Path) -> Result<ProjectOverrides, Box<dyn Error>> {
+    let contents = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    toml::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e).into())
+}
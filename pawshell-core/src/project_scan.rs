@@ -0,0 +1,305 @@
+//! What the pet notices about the project it was launched in, for greetings
+//!
+//! On startup the launch directory is scanned: its language (from the source
+//! files up to `project_scan_depth` levels down), frameworks (from manifests
+//! like `Cargo.toml` and `package.json`), the README's title and the git
+//! branch and dirty files. The pet remembers each project it has been started
+//! in, when, and the last command that failed there without being fixed
+//! since, so it can greet with "Back to pawshell? `cargo test` was failing
+//! yesterday".
+//!
+//! Nothing of this leaves the machine. Directories in `project_scan_exclude`
+//! (and the home directory itself) are neither scanned nor remembered.
+
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::history::HistoryEntry;
+use crate::workspace::GitInfo;
+
+/// Entries looked at while scanning for source files at most
+const MAX_SCANNED_ENTRIES: usize = 2_000;
+/// Directories that hold dependencies or build output rather than the project's code
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target", "vendor", "build", "dist", "__pycache__", "venv", "out"];
+const MAX_TITLE_CHARS: usize = 60;
+
+const LANGUAGES: &[(&str, &str)] = &[
+    ("rs", "Rust"), ("py", "Python"), ("js", "JavaScript"), ("jsx", "JavaScript"), ("ts", "TypeScript"),
+    ("tsx", "TypeScript"), ("go", "Go"), ("rb", "Ruby"), ("java", "Java"), ("kt", "Kotlin"), ("c", "C"),
+    ("h", "C"), ("cpp", "C++"), ("cc", "C++"), ("hpp", "C++"), ("cs", "C#"), ("php", "PHP"), ("swift", "Swift"),
+    ("ex", "Elixir"), ("exs", "Elixir"), ("hs", "Haskell"), ("lua", "Lua"), ("sh", "Shell"), ("zig", "Zig"),
+];
+
+/// Dependency names and the frameworks they stand for
+type Frameworks = &'static [(&'static str, &'static str)];
+
+/// Manifests, the language they stand for and the frameworks to look for in them
+const MANIFESTS: &[(&str, &str, Frameworks)] = &[
+    ("Cargo.toml", "Rust", &[("axum", "Axum"), ("actix-web", "Actix Web"), ("rocket", "Rocket"), ("tokio", "Tokio"), ("ratatui", "ratatui"), ("bevy", "Bevy"), ("tauri", "Tauri"), ("leptos", "Leptos")]),
+    ("package.json", "JavaScript", &[("next", "Next.js"), ("react", "React"), ("vue", "Vue"), ("svelte", "Svelte"), ("@angular/core", "Angular"), ("express", "Express"), ("@nestjs/core", "NestJS"), ("electron", "Electron")]),
+    ("pyproject.toml", "Python", &[("django", "Django"), ("flask", "Flask"), ("fastapi", "FastAPI"), ("torch", "PyTorch")]),
+    ("requirements.txt", "Python", &[("django", "Django"), ("flask", "Flask"), ("fastapi", "FastAPI"), ("torch", "PyTorch")]),
+    ("go.mod", "Go", &[("github.com/gin-gonic/gin", "Gin"), ("github.com/labstack/echo", "Echo"), ("github.com/gofiber/fiber", "Fiber")]),
+    ("Gemfile", "Ruby", &[("rails", "Rails"), ("sinatra", "Sinatra")]),
+    ("composer.json", "PHP", &[("laravel/framework", "Laravel"), ("symfony/framework-bundle", "Symfony")]),
+    ("mix.exs", "Elixir", &[("phoenix", "Phoenix")]),
+    ("pom.xml", "Java", &[("spring-boot", "Spring Boot")]),
+    ("build.gradle", "Java", &[("spring-boot", "Spring Boot")]),
+    ("build.gradle.kts", "Kotlin", &[("spring-boot", "Spring Boot")]),
+];
+
+/// What the launch directory looks like
+pub struct ProjectScan {
+    /// The repository root, or the launch directory outside of git
+    pub root: PathBuf,
+    pub name: String,
+    pub language: Option<&'static str>,
+    pub frameworks: Vec<&'static str>,
+    pub readme_title: Option<String>,
+    pub git: Option<GitInfo>,
+}
+
+/// What the pet remembers about a project between sessions
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ProjectMemory {
+    pub name: String,
+    pub last_visit: DateTime<Utc>,
+    /// The last command that failed there and hasn't succeeded since
+    #[serde(default)]
+    pub last_failure: Option<Failure>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Failure {
+    pub command: String,
+    pub at: DateTime<Utc>,
+}
+
+/// Whether `dir` is in one of the excluded directories (`~` is expanded)
+pub fn is_excluded(dir: &Path, excluded: &[String]) -> bool {
+    let home = dirs::home_dir();
+    if home.as_deref() == Some(dir) {
+        return true;
+    }
+    excluded.iter().any(|pattern| {
+        let path = match (pattern.strip_prefix("~/"), &home) {
+            (Some(rest), Some(home)) => home.join(rest),
+            _ => PathBuf::from(pattern),
+        };
+        dir.starts_with(path)
+    })
+}
+
+/// Looks at `dir`; None if nothing in it looks like a project
+pub fn scan(dir: &Path, depth: usize) -> Option<ProjectScan> {
+    let git = GitInfo::read(dir);
+    let root = git_root(dir).unwrap_or_else(|| dir.to_path_buf());
+    let mut language = source_language(&root, depth);
+    let mut frameworks = Vec::new();
+    let mut name = None;
+    for (file, manifest_language, known) in MANIFESTS {
+        let Ok(text) = std::fs::read_to_string(root.join(file)) else {
+            continue;
+        };
+        language.get_or_insert(manifest_language);
+        name = name.or_else(|| package_name(file, &text));
+        for (dependency, framework) in *known {
+            if mentions(&text, dependency) && !frameworks.contains(framework) {
+                frameworks.push(*framework);
+            }
+        }
+    }
+    if git.is_none() && language.is_none() {
+        return None;
+    }
+    let name = name.or_else(|| root.file_name().map(|name| name.to_string_lossy().into_owned()))?;
+    Some(ProjectScan { readme_title: readme_title(&root), root, name, language, frameworks, git })
+}
+
+fn git_root(dir: &Path) -> Option<PathBuf> {
+    let output = std::process::Command::new("git").arg("-C").arg(dir).args(["rev-parse", "--show-toplevel"]).output().ok()?;
+    output.status.success().then(|| PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+}
+
+/// The most common language among the source files
+fn source_language(root: &Path, depth: usize) -> Option<&'static str> {
+    let mut counts: HashMap<&'static str, usize> = HashMap::new();
+    let mut pending = vec![(root.to_path_buf(), 0)];
+    let mut seen = 0;
+    while let Some((dir, level)) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            seen += 1;
+            if seen > MAX_SCANNED_ENTRIES {
+                break;
+            }
+            let path = entry.path();
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            if file_name.starts_with('.') {
+                continue;
+            }
+            if path.is_dir() {
+                if level < depth && !SKIPPED_DIRS.contains(&file_name.as_str()) {
+                    pending.push((path, level + 1));
+                }
+            } else if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+                if let Some((_, language)) = LANGUAGES.iter().find(|(known, _)| *known == extension) {
+                    *counts.entry(language).or_default() += 1;
+                }
+            }
+        }
+    }
+    counts.into_iter().max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0))).map(|(language, _)| language)
+}
+
+/// Whether a manifest mentions a dependency, as a whole name
+fn mentions(text: &str, name: &str) -> bool {
+    let part_of_name = |c: char| c.is_alphanumeric() || c == '-' || c == '_';
+    text.match_indices(name).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + name.len()..].chars().next();
+        !before.is_some_and(part_of_name) && !after.is_some_and(part_of_name)
+    })
+}
+
+fn package_name(file: &str, text: &str) -> Option<String> {
+    if file == "package.json" {
+        let json: serde_json::Value = serde_json::from_str(text).ok()?;
+        return json["name"].as_str().map(str::to_string);
+    }
+    text.lines()
+        .filter_map(|line| line.trim().strip_prefix("name"))
+        .filter_map(|rest| rest.trim_start().strip_prefix('='))
+        .map(|value| value.trim().trim_matches(['"', '\'']).to_string())
+        .find(|name| !name.is_empty())
+}
+
+/// The README's first heading, or its first line
+fn readme_title(root: &Path) -> Option<String> {
+    let text = ["README.md", "README", "README.rst", "readme.md"].iter().find_map(|name| std::fs::read_to_string(root.join(name)).ok())?;
+    let mut lines = text.lines().map(str::trim);
+    let title = lines
+        .clone()
+        .find_map(|line| line.strip_prefix("# "))
+        .or_else(|| lines.find(|line| !line.is_empty() && !line.starts_with(['<', '!', '[', '='])))?
+        .trim();
+    let title: String = title.chars().take(MAX_TITLE_CHARS).collect();
+    (!title.is_empty()).then_some(title)
+}
+
+/// Updates the project's last failure with a command run in it; returns
+/// whether it changed
+pub fn remember_command(memory: &mut ProjectMemory, entry: &HistoryEntry, now: DateTime<Utc>) -> bool {
+    if entry.failed() {
+        memory.last_failure = Some(Failure { command: entry.command.clone(), at: entry.timestamp.unwrap_or(now) });
+        return true;
+    }
+    let fixed = memory.last_failure.as_ref().is_some_and(|failure| entry.exit_code == Some(0) && failure.command == entry.command);
+    if fixed {
+        memory.last_failure = None;
+    }
+    fixed
+}
+
+/// The greeting for a project, with what the pet remembers about it
+pub fn greeting(scan: &ProjectScan, memory: Option<&ProjectMemory>, now: &DateTime<Local>) -> String {
+    let mut text = match memory {
+        Some(memory) => {
+            let mut text = format!("Back to {}?", scan.name);
+            match &memory.last_failure {
+                Some(failure) => {
+                    text.push_str(&format!(" `{}` was failing {}; want to pick that up?", failure.command, when(failure.at, now)))
+                }
+                None if now.date_naive() != memory.last_visit.with_timezone(&Local).date_naive() => {
+                    text.push_str(&format!(" Last time was {}.", when(memory.last_visit, now)))
+                }
+                None => {}
+            }
+            text
+        }
+        None => {
+            let kind = match scan.language {
+                Some(language) => format!("{} {} project", article(language), language),
+                None => "a new project".to_string(),
+            };
+            let mut text = format!("*sniffs around* Ooh, {}", kind);
+            if !scan.frameworks.is_empty() {
+                text.push_str(&format!(" with {}", scan.frameworks.join(", ")));
+            }
+            match &scan.readme_title {
+                Some(title) if !title.eq_ignore_ascii_case(&scan.name) => text.push_str(&format!(": {} (\"{}\")!", scan.name, title)),
+                _ => text.push_str(&format!(": {}!", scan.name)),
+            }
+            text
+        }
+    };
+    if let Some(git) = &scan.git {
+        match git.dirty_files.len() {
+            0 => {}
+            1 => text.push_str(&format!(" You're on {} with 1 uncommitted change.", git.branch)),
+            n => text.push_str(&format!(" You're on {} with {} uncommitted changes.", git.branch, n)),
+        }
+    }
+    text
+}
+
+fn article(word: &str) -> &'static str {
+    if word.starts_with(['A', 'E', 'I', 'O', 'U']) {
+        "an"
+    } else {
+        "a"
+    }
+}
+
+/// "earlier today", "yesterday", "3 days ago" or the date
+fn when(at: DateTime<Utc>, now: &DateTime<Local>) -> String {
+    let days = (now.date_naive() - at.with_timezone(&Local).date_naive()).num_days();
+    match days {
+        ..=0 => "earlier today".to_string(),
+        1 => "yesterday".to_string(),
+        2..=6 => format!("{} days ago", days),
+        _ => format!("on {}", at.with_timezone(&Local).format("%b %-d")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn dependencies_match_whole_names() {
+        let manifest = "[dependencies]\nratatui = \"0.26\"\ntokio-util = \"0.7\"\n";
+        assert!(mentions(manifest, "ratatui"));
+        assert!(!mentions(manifest, "tokio"));
+        assert_eq!(package_name("Cargo.toml", "[package]\nname = \"pawshell\"\n").as_deref(), Some("pawshell"));
+    }
+
+    #[test]
+    fn greetings_bring_up_unfixed_failures() {
+        let now = Local.with_ymd_and_hms(2024, 3, 5, 9, 0, 0).unwrap();
+        let yesterday = (now - Duration::days(1)).with_timezone(&Utc);
+        let scan = ProjectScan {
+            root: PathBuf::from("/src/pawshell"),
+            name: "pawshell".to_string(),
+            language: Some("Rust"),
+            frameworks: vec!["ratatui"],
+            readme_title: Some("PawShell".to_string()),
+            git: None,
+        };
+        assert_eq!(greeting(&scan, None, &now), "*sniffs around* Ooh, a Rust project with ratatui: pawshell!");
+
+        let mut memory = ProjectMemory { name: "pawshell".to_string(), last_visit: yesterday, last_failure: None };
+        let mut entry = HistoryEntry::new("cargo test", Some(yesterday));
+        entry.exit_code = Some(101);
+        assert!(remember_command(&mut memory, &entry, yesterday));
+        assert_eq!(greeting(&scan, Some(&memory), &now), "Back to pawshell? `cargo test` was failing yesterday; want to pick that up?");
+        entry.exit_code = Some(0);
+        assert!(remember_command(&mut memory, &entry, yesterday));
+        assert_eq!(greeting(&scan, Some(&memory), &now), "Back to pawshell? Last time was yesterday.");
+    }
+}
@@ -0,0 +1,112 @@
+//! The regex laboratory opened with `/regex`
+//!
+//! A pattern and some sample text are edited side by side, and the matches
+//! are highlighted as they're typed, with the capture groups in their own
+//! colors. Matching is done locally with the `regex` crate, so its syntax is
+//! what's checked; explaining the pattern is the only thing that needs the
+//! backend.
+
+use regex::Regex;
+
+use crate::templates::Prompt;
+
+/// Sample text beyond this many characters is left out of explain prompts
+const MAX_SAMPLE_CHARS: usize = 2_000;
+
+const EXPLAIN_TEMPLATE: &str = "Explain this regular expression piece by piece, in plain words, \
+and point out anything that probably doesn't do what its author intended (greedy quantifiers, \
+missing anchors or escapes, catastrophic backtracking). Keep it short.\n\nPattern: {{ pattern }}\
+{% if sample %}\n\nIt's meant for text like this:\n{{ sample }}{% endif %}";
+
+#[derive(Debug, Clone, Default)]
+pub struct RegexLab {
+    pub pattern: String,
+    pub sample: String,
+    /// Whether typing goes to the sample rather than the pattern
+    pub editing_sample: bool,
+    /// The backend's explanation of the pattern, once asked for
+    pub explanation: Option<String>,
+}
+
+/// What a piece of the sample is: outside any match, in a match (0) or in
+/// a capture group (1 and up)
+pub type Segment = (Option<usize>, String);
+
+/// The sample split into lines of highlighted segments
+pub struct Highlight {
+    pub lines: Vec<Vec<Segment>>,
+    pub matches: usize,
+    /// Capture groups in the pattern
+    pub groups: usize,
+}
+
+impl RegexLab {
+    pub fn new(pattern: &str) -> Self {
+        Self { pattern: pattern.to_string(), ..Self::default() }
+    }
+
+    /// The text being edited
+    pub fn focused(&mut self) -> &mut String {
+        if self.editing_sample {
+            &mut self.sample
+        } else {
+            &mut self.pattern
+        }
+    }
+
+    /// Matches the pattern against the sample; the error is the regex's
+    pub fn highlight(&self) -> Result<Highlight, String> {
+        let regex = Regex::new(&self.pattern).map_err(|e| e.to_string())?;
+        // The innermost group covering a byte wins, so later groups are painted over earlier ones
+        let mut kinds: Vec<Option<usize>> = vec![None; self.sample.len()];
+        let mut matches = 0;
+        for captures in regex.captures_iter(&self.sample) {
+            matches += 1;
+            for (group, capture) in captures.iter().enumerate() {
+                if let Some(capture) = capture {
+                    kinds[capture.range()].fill(Some(group));
+                }
+            }
+        }
+
+        let mut lines = vec![Vec::new()];
+        let mut start = 0;
+        for (i, c) in self.sample.char_indices() {
+            let boundary = c == '\n' || kinds[i] != kinds[start];
+            if boundary && start < i {
+                lines.last_mut().unwrap().push((kinds[start], self.sample[start..i].to_string()));
+            }
+            if c == '\n' {
+                lines.push(Vec::new());
+                start = i + 1;
+            } else if boundary {
+                start = i;
+            }
+        }
+        if start < self.sample.len() {
+            lines.last_mut().unwrap().push((kinds[start], self.sample[start..].to_string()));
+        }
+        Ok(Highlight { lines, matches, groups: regex.captures_len() - 1 })
+    }
+
+    /// Asks for an explanation of the pattern, with the sample if there is one
+    pub fn explain_prompt(&self) -> Prompt {
+        let sample: String = if self.sample.trim().is_empty() { String::new() } else { self.sample.chars().take(MAX_SAMPLE_CHARS).collect() };
+        Prompt::new("regex", EXPLAIN_TEMPLATE).var("pattern", &self.pattern).var("sample", sample)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_and_groups_are_highlighted_per_line() {
+        let lab = RegexLab { sample: "id=42\nname=cat".to_string(), ..RegexLab::new(r"(\w+)=(\d+)") };
+        let highlight = lab.highlight().unwrap();
+        let segment = |kind, text: &str| (kind, text.to_string());
+        assert_eq!(highlight.lines, [vec![segment(Some(1), "id"), segment(Some(0), "="), segment(Some(2), "42")], vec![segment(None, "name=cat")]]);
+        assert_eq!((highlight.matches, highlight.groups), (1, 2));
+        assert!(RegexLab::new("(unclosed").highlight().is_err());
+    }
+}
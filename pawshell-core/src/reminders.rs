@@ -0,0 +1,187 @@
+//! Reminders and the to-do list
+//!
+//! `/remind "stand up" in 25m` (or `at 17:30`) saves a reminder in the pet's
+//! state; the TUI checks for due ones on every tick, so reminders that came due
+//! while the pet was closed go off at the next start. `/todo` keeps a plain
+//! list of tasks to tick off.
+
+use chrono::{DateTime, Duration, Local, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::clock;
+
+/// Something to bring up at a given time
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Reminder {
+    pub text: String,
+    pub due: DateTime<Utc>,
+}
+
+impl Reminder {
+    /// Parses `/remind` arguments: the text, optionally quoted, followed by
+    /// `in <duration>` (like `25m` or `1h30m`) or `at <HH:MM>` (the next time
+    /// the local clock shows it)
+    pub fn parse<Tz: TimeZone>(args: &str, now: &DateTime<Tz>) -> Result<Self, String> {
+        const USAGE: &str = "Usage: /remind \"what\" in 25m, or /remind \"what\" at 17:30";
+        let args = args.trim();
+        let (text, when) = match args.strip_prefix('"').and_then(|rest| rest.split_once('"')) {
+            Some((text, when)) => (text.trim(), when.trim()),
+            None => {
+                let split = args.rfind(" in ").max(args.rfind(" at ")).ok_or(USAGE)?;
+                (args[..split].trim(), args[split..].trim())
+            }
+        };
+        if text.is_empty() {
+            return Err(USAGE.to_string());
+        }
+        let due = if let Some(duration) = when.strip_prefix("in ") {
+            let duration = parse_duration(duration).ok_or_else(|| format!("\"{}\" isn't a duration like 25m or 1h30m", duration.trim()))?;
+            now.with_timezone(&Utc) + duration
+        } else if let Some(time) = when.strip_prefix("at ") {
+            let time = NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|_| format!("\"{}\" isn't a time like 17:30", time.trim()))?;
+            next_occurrence(now, time)
+        } else {
+            return Err(USAGE.to_string());
+        };
+        Ok(Self { text: text.to_string(), due })
+    }
+
+    /// The reminder with how long until it's due, like `stand up (in 12m)`
+    pub fn describe(&self, now: DateTime<Utc>) -> String {
+        format!("{} (in {})", self.text, format_duration(self.due - now))
+    }
+}
+
+/// A task on the to-do list
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Todo {
+    pub text: String,
+    #[serde(default)]
+    pub done: bool,
+    pub created: DateTime<Utc>,
+}
+
+/// What `/todo` was asked to do; items are numbered from 1 as listed
+#[derive(Debug, PartialEq)]
+pub enum TodoCommand {
+    List,
+    Add(String),
+    Done(usize),
+    Remove(usize),
+    /// Drops the finished items
+    Clear,
+}
+
+impl TodoCommand {
+    pub fn parse(args: &str) -> Self {
+        let args = args.trim();
+        let (verb, rest) = args.split_once(' ').unwrap_or((args, ""));
+        let number = rest.trim().trim_start_matches('#').parse::<usize>().ok().filter(|&n| n > 0);
+        match (verb, number) {
+            ("", _) => TodoCommand::List,
+            ("done", Some(n)) => TodoCommand::Done(n),
+            ("rm", Some(n)) => TodoCommand::Remove(n),
+            ("clear", _) if rest.is_empty() => TodoCommand::Clear,
+            _ => TodoCommand::Add(args.to_string()),
+        }
+    }
+}
+
+impl Todo {
+    pub fn new(text: &str) -> Self {
+        Self { text: text.to_string(), done: false, created: clock::now() }
+    }
+}
+
+/// Removes and returns the reminders due at `now`, earliest first
+pub fn take_due(reminders: &mut Vec<Reminder>, now: DateTime<Utc>) -> Vec<Reminder> {
+    let (mut due, pending) = reminders.drain(..).partition(|reminder: &Reminder| reminder.due <= now);
+    *reminders = pending;
+    due.sort_by_key(|reminder| reminder.due);
+    due
+}
+
+/// Parses durations like `90s`, `25m`, `1h30m` or `2d`
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut total = Duration::zero();
+    let mut rest = text.as_str();
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let amount: i64 = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+        let unit = rest.find(|c: char| c.is_ascii_digit()).unwrap_or(rest.len());
+        total += match &rest[..unit] {
+            "s" | "sec" | "secs" => Duration::seconds(amount),
+            "m" | "min" | "mins" => Duration::minutes(amount),
+            "h" | "hr" | "hrs" | "hour" | "hours" => Duration::hours(amount),
+            "d" | "day" | "days" => Duration::days(amount),
+            _ => return None,
+        };
+        rest = &rest[unit..];
+    }
+    (total > Duration::zero()).then_some(total)
+}
+
+/// The next time after `now` that the local clock shows `time`
+fn next_occurrence<Tz: TimeZone>(now: &DateTime<Tz>, time: NaiveTime) -> DateTime<Utc> {
+    let local = now.with_timezone(&Local);
+    let today = local.date_naive().and_time(time);
+    let candidate = if today > local.naive_local() { today } else { today + Duration::days(1) };
+    Local
+        .from_local_datetime(&candidate)
+        .earliest()
+        .map(|due| due.with_timezone(&Utc))
+        .unwrap_or_else(|| now.with_timezone(&Utc) + Duration::days(1))
+}
+
+/// Short human durations like `45s`, `12m` or `2h 5m`
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    match seconds {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m", s / 60),
+        s if s < 86_400 => format!("{}h {}m", s / 3600, s % 3600 / 60),
+        s => format!("{}d {}h", s / 86_400, s % 86_400 / 3600),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::test_time;
+
+    #[test]
+    fn reminders_parse_quoted_and_bare_text() {
+        let now = test_time(0);
+        let reminder = Reminder::parse("\"stand up\" in 25m", &now).unwrap();
+        assert_eq!(reminder, Reminder { text: "stand up".to_string(), due: now + Duration::minutes(25) });
+        let reminder = Reminder::parse("check the build in 1h 30m", &now).unwrap();
+        assert_eq!(reminder.text, "check the build");
+        assert_eq!(reminder.due, now + Duration::minutes(90));
+        assert_eq!(Reminder::parse("check in with Sam at 15:00", &now).unwrap().text, "check in with Sam");
+        assert!(Reminder::parse("\"stand up\" in a while", &now).is_err());
+        assert!(Reminder::parse("stand up", &now).is_err());
+        let at = Reminder::parse("\"lunch\" at 12:30", &now).unwrap();
+        assert!(at.due > now && at.due <= now + Duration::days(1));
+    }
+
+    #[test]
+    fn only_due_reminders_are_taken() {
+        let now = test_time(0);
+        let reminder = |text: &str, minutes| Reminder { text: text.to_string(), due: now + Duration::minutes(minutes) };
+        let mut reminders = vec![reminder("later", 5), reminder("now", 0), reminder("missed", -30)];
+        let due: Vec<String> = take_due(&mut reminders, now).into_iter().map(|r| r.text).collect();
+        assert_eq!(due, ["missed", "now"]);
+        assert_eq!(reminders, [reminder("later", 5)]);
+    }
+
+    #[test]
+    fn todo_commands_fall_back_to_adding() {
+        assert_eq!(TodoCommand::parse(""), TodoCommand::List);
+        assert_eq!(TodoCommand::parse("done 2"), TodoCommand::Done(2));
+        assert_eq!(TodoCommand::parse("rm #1"), TodoCommand::Remove(1));
+        assert_eq!(TodoCommand::parse("clear"), TodoCommand::Clear);
+        assert_eq!(TodoCommand::parse("done the dishes"), TodoCommand::Add("done the dishes".to_string()));
+    }
+}
@@ -0,0 +1,181 @@
+//! Weekly terminal habits reports for `/report`
+//!
+//! A report looks back seven days: the commands run and how many failed, what
+//! the chats were about, the chat streak, finished pomodoros and the pet's
+//! mood. Those numbers go to the backend, which writes a short summary with
+//! three concrete suggestions for the week ahead. The whole report is saved as
+//! Markdown in the data directory's `reports` folder.
+//!
+//! With `weekly_report` on, the TUI also makes one by itself on Monday
+//! morning, once per week.
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, Timelike, Utc};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::analytics::CommandStats;
+use crate::config_path;
+use crate::history::HistoryEntry;
+use crate::pet::PetState;
+use crate::pomodoro;
+use crate::templates::Prompt;
+
+/// How many commands a report looks at when the history has no timestamps
+const UNDATED_COMMANDS: usize = 1_000;
+/// Most frequent words from chat messages listed as topics
+const TOPICS: usize = 6;
+/// Automatic reports are made on Monday between these hours
+const MONDAY_MORNING: std::ops::Range<u32> = 7..12;
+
+const TEMPLATE: &str = "Here is what my week in the terminal looked like:\n\n{{ facts }}\n\
+Write my weekly report in Markdown: first a short paragraph summing up the week, then a \"Suggestions\" \
+list of exactly 3 concrete improvements for next week, each naming a specific command, alias, tool or \
+habit from the numbers above. Skip the headline and don't repeat the numbers.";
+
+/// Words too common to say anything about what a chat was about
+const STOPWORDS: &[&str] = &[
+    "about", "after", "again", "also", "because", "been", "before", "being", "could", "does", "doing", "from",
+    "have", "here", "into", "just", "know", "like", "make", "more", "much", "need", "only", "other", "please",
+    "really", "should", "some", "than", "that", "their", "them", "then", "there", "these", "they", "thing",
+    "this", "through", "what", "when", "where", "which", "while", "with", "would", "your", "want", "there's",
+    "what's", "it's", "don't", "can't", "i'm",
+];
+
+pub fn reports_dir() -> PathBuf {
+    config_path::get_data_dir().join("reports")
+}
+
+/// Whether it's Monday morning and this week's automatic report is still missing
+pub fn is_due(last: Option<NaiveDate>, now: &DateTime<Local>) -> bool {
+    let today = now.date_naive();
+    now.weekday() == chrono::Weekday::Mon
+        && MONDAY_MORNING.contains(&now.hour())
+        && last.is_none_or(|last| last.iso_week() != today.iso_week())
+}
+
+/// The week's numbers as Markdown, for the prompt and the saved report
+pub fn facts(state: &PetState, history: &[HistoryEntry], now: &DateTime<Local>) -> String {
+    let since = now.with_timezone(&Utc) - Duration::days(7);
+    let dated: Vec<HistoryEntry> =
+        history.iter().filter(|entry| entry.timestamp.is_some_and(|timestamp| timestamp >= since)).cloned().collect();
+    let (commands, note) = if history.is_empty() || history.iter().any(|entry| entry.timestamp.is_some()) {
+        (dated, "")
+    } else {
+        let start = history.len().saturating_sub(UNDATED_COMMANDS);
+        (history[start..].to_vec(), " (the history has no timestamps, so these are the latest commands)")
+    };
+    let stats = CommandStats::from_entries(&commands, 5);
+    let failed = commands.iter().filter(|entry| entry.failed()).count();
+
+    let chats: Vec<&str> = state
+        .chat_history
+        .iter()
+        .filter(|message| message.timestamp.is_some_and(|timestamp| timestamp >= since))
+        .map(|message| message.user_message.as_str())
+        .collect();
+
+    let mut facts = format!("### Commands{}\n\n- {} commands", note, stats.total);
+    if commands.iter().any(|entry| entry.exit_code.is_some()) {
+        facts.push_str(&format!(", {} of them failed", failed));
+    }
+    facts.push('\n');
+    let list = |items: &[(String, usize)]| items.iter().map(|(item, count)| format!("`{}` ({}×)", item, count)).collect::<Vec<_>>().join(", ");
+    if !stats.top_commands.is_empty() {
+        facts.push_str(&format!("- Most used: {}\n", list(&stats.top_commands)));
+    }
+    if !stats.flag_combos.is_empty() {
+        facts.push_str(&format!("- Frequent flag combos: {}\n", list(&stats.flag_combos)));
+    }
+    if !stats.repeated_pipelines.is_empty() {
+        facts.push_str(&format!("- Pipelines typed again and again: {}\n", list(&stats.repeated_pipelines)));
+    }
+
+    facts.push_str(&format!("\n### Chats\n\n- {} messages to the pet\n", chats.len()));
+    let topics = topics(&chats);
+    if !topics.is_empty() {
+        facts.push_str(&format!("- Topics: {}\n", topics.join(", ")));
+    }
+    match state.chat_streak(now) {
+        1 => facts.push_str("- Chat streak: 1 day\n"),
+        days => facts.push_str(&format!("- Chat streak: {} days\n", days)),
+    }
+    for line in pomodoro::summary(&state.focus_log, now.date_naive()).lines().skip(1) {
+        facts.push_str(&format!("- {}\n", line));
+    }
+
+    let moods: Vec<f32> = state.mood_log.iter().filter(|sample| sample.at >= since).map(|sample| sample.mood).collect();
+    if !moods.is_empty() {
+        let average = moods.iter().sum::<f32>() / moods.len() as f32;
+        let lowest = moods.iter().copied().fold(f32::MAX, f32::min);
+        facts.push_str(&format!(
+            "\n### The pet\n\n- Mood: {:.0}% on average, {:.0}% at the lowest\n",
+            average * 100.0,
+            lowest * 100.0
+        ));
+    }
+    facts
+}
+
+/// The most frequent meaningful words in the chat messages
+fn topics(messages: &[&str]) -> Vec<String> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for message in messages {
+        for word in message.split(|c: char| !c.is_alphanumeric() && c != '\'' && c != '-') {
+            let word = word.trim_matches(|c| c == '\'' || c == '-').to_lowercase();
+            if word.chars().count() >= 4 && word.chars().any(char::is_alphabetic) && !STOPWORDS.contains(&word.as_str()) {
+                *counts.entry(word).or_default() += 1;
+            }
+        }
+    }
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts.into_iter().take(TOPICS).map(|(word, _)| word).collect()
+}
+
+/// Asks for the summary and suggestions
+pub fn prompt(facts: &str) -> Prompt {
+    Prompt::new("report", TEMPLATE).var("facts", facts)
+}
+
+/// The full report: the backend's summary, if there is one, and the numbers
+pub fn to_markdown(facts: &str, summary: Option<&str>, now: &DateTime<Local>) -> String {
+    let start = now.date_naive() - Duration::days(7);
+    let mut markdown = format!("# Weekly terminal report, {} to {}\n\n", start, now.date_naive());
+    if let Some(summary) = summary {
+        markdown.push_str(&format!("{}\n\n", summary.trim()));
+    }
+    markdown.push_str(&format!("## The numbers\n\n{}", facts));
+    markdown
+}
+
+/// Writes a report into the reports folder, named after its ISO week
+pub fn save(markdown: &str, now: &DateTime<Local>) -> Result<PathBuf, String> {
+    let dir = reports_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| format!("couldn't create {}: {}", dir.display(), e))?;
+    let week = now.iso_week();
+    let path = dir.join(format!("{}-W{:02}.md", week.year(), week.week()));
+    std::fs::write(&path, markdown).map_err(|e| format!("couldn't write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn topics_skip_filler_words() {
+        let messages = ["How do I rebase onto main?", "rebase keeps failing with conflicts", "What about docker volumes"];
+        assert_eq!(topics(&messages), ["rebase", "conflicts", "docker", "failing", "keeps", "main"]);
+    }
+
+    #[test]
+    fn automatic_reports_come_once_on_monday_morning() {
+        let monday = Local.with_ymd_and_hms(2024, 3, 4, 9, 30, 0).unwrap();
+        assert!(is_due(None, &monday));
+        assert!(!is_due(Some(monday.date_naive()), &(monday + Duration::hours(1))));
+        assert!(is_due(Some(monday.date_naive() - Duration::days(7)), &monday));
+        assert!(!is_due(None, &(monday + Duration::hours(4))));
+        assert!(!is_due(None, &(monday + Duration::days(1))));
+    }
+}
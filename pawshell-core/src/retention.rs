@@ -0,0 +1,139 @@
+//! Chat history retention for PetCLI
+//!
+//! Without limits the chat history grows forever, and with the confy backend the
+//! whole of it is rewritten on every message. When `chat_retention_messages` or
+//! `chat_retention_days` is set, exchanges beyond the limit are taken out of the
+//! pet's state, summarized, and appended to `chat_archive.jsonl` in the data
+//! directory together with the summary (encrypted if `encrypt_history` is on).
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::clock;
+use crate::config::Config;
+use crate::config_path;
+use crate::crypto::Cipher;
+use crate::pet::PetState;
+use crate::templates::Prompt;
+
+/// Exchanges are archived in batches so the archive isn't touched on every message
+const MIN_ARCHIVE_BATCH: usize = 10;
+
+const SUMMARY_TEMPLATE: &str = "Summarize these earlier conversations between me and my terminal pet in at \
+most 5 short bullet points. Keep facts about my setup, projects and preferences, and any commands that \
+solved a problem.\n\n{{ exchanges }}";
+
+/// How much chat history to keep in the pet's state
+pub struct RetentionPolicy {
+    pub max_messages: Option<usize>,
+    pub max_age: Option<Duration>,
+}
+
+/// An exchange moved out of the pet's state
+#[derive(Serialize)]
+pub struct ArchivedExchange {
+    pub user_message: String,
+    pub pet_response: String,
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct ArchiveRecord<'a> {
+    archived_at: DateTime<Utc>,
+    summary: &'a str,
+    exchanges: &'a [ArchivedExchange],
+}
+
+impl RetentionPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_messages: config.chat_retention_messages,
+            max_age: config.chat_retention_days.map(Duration::days),
+        }
+    }
+
+    /// How many of the oldest exchanges fall outside the policy
+    ///
+    /// Exchanges saved before timestamps were recorded only count against the
+    /// message limit. Returns 0 until a whole batch is due.
+    pub fn expired_count(&self, state: &PetState, now: DateTime<Utc>) -> usize {
+        let total = state.chat_history.len();
+        let over_limit = self.max_messages.map_or(0, |max| total.saturating_sub(max));
+        let too_old = self.max_age.map_or(0, |max_age| {
+            (0..total)
+                .take_while(|&i| state.chat_history[i].timestamp.is_none_or(|time| now - time > max_age))
+                .filter(|&i| state.chat_history[i].timestamp.is_some())
+                .last()
+                .map_or(0, |i| i + 1)
+        });
+        let expired = over_limit.max(too_old);
+        if expired >= MIN_ARCHIVE_BATCH || (expired == total && expired > 0) {
+            expired
+        } else {
+            0
+        }
+    }
+}
+
+pub fn archive_path() -> PathBuf {
+    config_path::get_data_dir().join("chat_archive.jsonl")
+}
+
+/// Asks the backend to condense archived exchanges into a few notes
+pub fn summary_prompt(exchanges: &[ArchivedExchange]) -> Prompt {
+    let exchanges: String = exchanges
+        .iter()
+        .map(|exchange| format!("User: {}\nPet: {}\n\n", exchange.user_message, exchange.pet_response))
+        .collect();
+    Prompt::new("retention", SUMMARY_TEMPLATE).var("exchanges", exchanges)
+}
+
+/// A summary for when the backend can't write one
+pub fn fallback_summary(exchanges: &[ArchivedExchange]) -> String {
+    let times: Vec<DateTime<Utc>> = exchanges.iter().filter_map(|exchange| exchange.timestamp).collect();
+    match (times.first(), times.last()) {
+        (Some(first), Some(last)) => format!(
+            "{} exchanges from {} to {}",
+            exchanges.len(),
+            first.format("%Y-%m-%d"),
+            last.format("%Y-%m-%d")
+        ),
+        _ => format!("{} exchanges", exchanges.len()),
+    }
+}
+
+/// Appends archived exchanges and their summary as one JSON line
+pub fn append(
+    exchanges: &[ArchivedExchange],
+    summary: &str,
+    cipher: Option<&Cipher>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    config_path::ensure_data_dir()?;
+    let path = archive_path();
+    let encrypted = cipher.map(|cipher| {
+        let exchanges: Vec<ArchivedExchange> = exchanges
+            .iter()
+            .map(|exchange| ArchivedExchange {
+                user_message: cipher.encrypt(&exchange.user_message),
+                pet_response: cipher.encrypt(&exchange.pet_response),
+                timestamp: exchange.timestamp,
+            })
+            .collect();
+        (exchanges, cipher.encrypt(summary))
+    });
+    let record = match &encrypted {
+        Some((exchanges, summary)) => ArchiveRecord { archived_at: clock::now(), summary, exchanges },
+        None => ArchiveRecord { archived_at: clock::now(), summary, exchanges },
+    };
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    writeln!(file, "{}", serde_json::to_string(&record)?)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(())
+}
@@ -0,0 +1,277 @@
+//! Code reviews of a single file for `/review`
+//!
+//! The file is split into chunks of `CHUNK_LINES` lines, each sent with its
+//! line numbers and a review-oriented prompt (plus an optional focus like
+//! "error handling"). The model answers with one `L<start>-<end>: <finding>`
+//! line per finding; those are collected across chunks, sorted by line and
+//! shown with an excerpt of the lines they're about.
+//!
+//! Excerpt lines are written as `<line> │ <code>`. The TUI recognizes them
+//! with `excerpt_line` and colors the code with `tokens`, a small highlighter
+//! that knows comments, strings, numbers and the keywords of common languages.
+
+use std::path::Path;
+
+use crate::templates::Prompt;
+
+/// Lines per request
+const CHUNK_LINES: usize = 200;
+/// Larger files are refused rather than sent in dozens of requests
+const MAX_FILE_LINES: usize = 2_000;
+/// Lines of code shown under a finding at most
+const EXCERPT_LINES: usize = 6;
+/// Separates the line number from the code in excerpts
+const GUTTER: &str = " │ ";
+
+const TEMPLATE: &str = "Review lines {{ first }}-{{ last }} of {{ path }} ({{ total }} lines in total) like a \
+careful senior reviewer before a push.{% if focus %} Focus on {{ focus }}.{% endif %} Point out bugs, \
+unhandled edge cases, security problems and confusing code; skip style nitpicks and praise. Write each \
+finding on its own line as `L<start>-<end>: <finding and how to fix it>`, using the line numbers below, \
+most important first. If there's nothing worth mentioning, reply `No findings.`\n\n{{ code }}";
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "case", "catch", "class", "const", "continue", "def", "default", "defer",
+    "do", "elif", "else", "enum", "except", "export", "extends", "false", "finally", "fn", "for", "from", "func",
+    "function", "go", "if", "impl", "import", "in", "interface", "let", "loop", "match", "mod", "mut", "new",
+    "nil", "None", "null", "package", "pass", "pub", "raise", "return", "self", "Self", "static", "struct",
+    "switch", "then", "this", "throw", "trait", "true", "True", "False", "try", "type", "unsafe", "use", "var",
+    "void", "where", "while", "with", "yield", "fi", "esac", "done", "local",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// A review waiting for the replies to its chunks
+pub struct CodeReview {
+    pub path: String,
+    lines: Vec<String>,
+    /// Chunks without a reply yet
+    pending: usize,
+    findings: Vec<Finding>,
+    /// Chunks that couldn't be reviewed, and why
+    failures: Vec<String>,
+}
+
+impl CodeReview {
+    /// Reads the file and builds the prompt for each chunk; `name` is how
+    /// the file is referred to in prompts and messages
+    pub fn start(path: &Path, name: &str, focus: &str) -> Result<(Self, Vec<Prompt>), String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("Couldn't read {}: {}", name, e))?;
+        let text = String::from_utf8(bytes).map_err(|_| format!("{} isn't a text file.", name))?;
+        let lines: Vec<String> = text.lines().map(str::to_string).collect();
+        if lines.iter().all(|line| line.trim().is_empty()) {
+            return Err(format!("{} is empty.", name));
+        }
+        if lines.len() > MAX_FILE_LINES {
+            return Err(format!("{} has {} lines; I can review up to {}.", name, lines.len(), MAX_FILE_LINES));
+        }
+        let prompts: Vec<Prompt> = (0..lines.len())
+            .step_by(CHUNK_LINES)
+            .map(|first| prompt(name, &lines, first, (first + CHUNK_LINES).min(lines.len()), focus))
+            .collect();
+        let review = Self { path: name.to_string(), lines, pending: prompts.len(), findings: Vec::new(), failures: Vec::new() };
+        Ok((review, prompts))
+    }
+
+    /// Adds a chunk's reply; returns whether every chunk has answered
+    pub fn receive(&mut self, chunk: usize, reply: Result<&str, String>) -> bool {
+        match reply {
+            Ok(text) => self.findings.extend(parse_findings(text).into_iter().filter(|finding| finding.start <= self.lines.len())),
+            Err(e) => {
+                let first = chunk * CHUNK_LINES + 1;
+                let last = ((chunk + 1) * CHUNK_LINES).min(self.lines.len());
+                self.failures.push(format!("Lines {}-{} weren't reviewed: {}", first, last, e));
+            }
+        }
+        self.pending = self.pending.saturating_sub(1);
+        self.pending == 0
+    }
+
+    /// The findings in line order, each with its excerpt
+    pub fn render(&self) -> String {
+        let mut findings = self.findings.clone();
+        findings.sort_by_key(|finding| (finding.start, finding.end));
+        let mut text = match findings.len() {
+            0 => format!("Review of {}: nothing worth pointing out.", self.path),
+            1 => format!("Review of {}, 1 finding:", self.path),
+            n => format!("Review of {}, {} findings:", self.path, n),
+        };
+        let width = self.lines.len().to_string().len();
+        for finding in &findings {
+            let range = if finding.end > finding.start {
+                format!("Lines {}-{}", finding.start, finding.end)
+            } else {
+                format!("Line {}", finding.start)
+            };
+            text.push_str(&format!("\n\n{}: {}", range, finding.text));
+            let last = finding.end.min(finding.start + EXCERPT_LINES - 1).min(self.lines.len());
+            for number in finding.start.max(1)..=last {
+                text.push('\n');
+                text.push_str(&excerpt(number, width, &self.lines[number - 1]));
+            }
+        }
+        for failure in &self.failures {
+            text.push_str(&format!("\n\n{}", failure));
+        }
+        text
+    }
+}
+
+fn prompt(path: &str, lines: &[String], first: usize, last: usize, focus: &str) -> Prompt {
+    let code: Vec<String> = lines[first..last].iter().enumerate().map(|(i, line)| format!("{}: {}", first + i + 1, line)).collect();
+    Prompt::new("review", TEMPLATE)
+        .var("first", (first + 1).to_string())
+        .var("last", last.to_string())
+        .var("path", path)
+        .var("total", lines.len().to_string())
+        .var("focus", focus.trim_end_matches('.'))
+        .var("code", code.join("\n"))
+}
+
+/// Reads `L12-18: …` (or `L12: …`) lines; indented lines continue the
+/// previous finding, and anything else is left out
+fn parse_findings(reply: &str) -> Vec<Finding> {
+    let mut findings: Vec<Finding> = Vec::new();
+    for raw in reply.lines() {
+        let line = raw.trim().trim_start_matches(['-', '*', ' ']).replace('`', "");
+        if line.is_empty() {
+            continue;
+        }
+        let parsed = line.strip_prefix('L').and_then(|rest| {
+            let (range, text) = rest.split_once(':')?;
+            let (start, end) = range.split_once('-').unwrap_or((range, range));
+            let start: usize = start.trim().parse().ok()?;
+            let end: usize = end.trim().trim_start_matches('L').parse().ok()?;
+            Some(Finding { start, end: end.max(start), text: text.trim().to_string() })
+        });
+        match (parsed, findings.last_mut()) {
+            (Some(finding), _) => findings.push(finding),
+            // Wrapped findings are indented
+            (None, Some(previous)) if raw.starts_with(char::is_whitespace) => {
+                previous.text.push(' ');
+                previous.text.push_str(&line);
+            }
+            (None, _) => {}
+        }
+    }
+    findings
+}
+
+/// A line of code with its number, padded to `width`, in the form
+/// `excerpt_line` recognizes
+pub fn excerpt(number: usize, width: usize, code: &str) -> String {
+    format!("{:<width$}{}{}", number, GUTTER, code, width = width)
+}
+
+/// Splits an excerpt line into its line number and code
+pub fn excerpt_line(line: &str) -> Option<(&str, &str)> {
+    let (number, code) = line.split_once(GUTTER)?;
+    number.trim().chars().all(|c| c.is_ascii_digit()).then_some((number, code))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    Keyword,
+    String,
+    Number,
+    Comment,
+    Plain,
+}
+
+/// Splits a line of code into highlightable pieces
+pub fn tokens(code: &str) -> Vec<(TokenKind, &str)> {
+    let mut tokens = Vec::new();
+    let mut rest = code;
+    while let Some(c) = rest.chars().next() {
+        let length = if is_comment(rest) {
+            tokens.push((TokenKind::Comment, rest));
+            break;
+        } else if c == '"' || c == '\'' || c == '`' {
+            // Rust lifetimes and char literals look alike; a quote without a close is plain
+            match rest[1..].find(c) {
+                Some(end) if c != '\'' || end <= 2 => {
+                    let mut end = end + 2;
+                    while rest[..end - 1].ends_with('\\') && !rest[..end - 1].ends_with("\\\\") {
+                        match rest[end..].find(c) {
+                            Some(next) => end += next + 1,
+                            None => break,
+                        }
+                    }
+                    tokens.push((TokenKind::String, &rest[..end]));
+                    end
+                }
+                _ => {
+                    tokens.push((TokenKind::Plain, &rest[..1]));
+                    1
+                }
+            }
+        } else if c.is_alphanumeric() || c == '_' {
+            let number = c.is_ascii_digit();
+            let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_' || (number && c == '.'))).unwrap_or(rest.len());
+            let word = &rest[..end];
+            let kind = if number {
+                TokenKind::Number
+            } else if KEYWORDS.contains(&word) {
+                TokenKind::Keyword
+            } else {
+                TokenKind::Plain
+            };
+            tokens.push((kind, word));
+            end
+        } else {
+            let end = rest.find(|c: char| c.is_alphanumeric() || "_\"'`#/-".contains(c)).filter(|&end| end > 0).unwrap_or(c.len_utf8());
+            tokens.push((TokenKind::Plain, &rest[..end]));
+            end
+        };
+        rest = &rest[length..];
+    }
+    tokens
+}
+
+/// Line comments in C-like languages, Python and shell; `#[` attributes,
+/// `#!` shebangs and C preprocessor lines aren't comments
+fn is_comment(code: &str) -> bool {
+    code.starts_with("//")
+        || (code.starts_with('#') && !["#[", "#!", "#include", "#define", "#if", "#endif", "#pragma"].iter().any(|prefix| code.starts_with(prefix)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn findings_are_read_from_the_reply() {
+        let reply = "- `L12-14`: unwrap on user input panics\n  Return an error instead.\nL3: unused import\nNo other findings.";
+        assert_eq!(
+            parse_findings(reply),
+            [
+                Finding { start: 12, end: 14, text: "unwrap on user input panics Return an error instead.".to_string() },
+                Finding { start: 3, end: 3, text: "unused import".to_string() },
+            ]
+        );
+    }
+
+    #[test]
+    fn code_is_split_for_highlighting() {
+        use TokenKind::*;
+        assert_eq!(
+            tokens("let n = \"a\\\"b\" + 42; // done"),
+            [
+                (Keyword, "let"),
+                (Plain, " "),
+                (Plain, "n"),
+                (Plain, " = "),
+                (String, "\"a\\\"b\""),
+                (Plain, " + "),
+                (Number, "42"),
+                (Plain, "; "),
+                (Comment, "// done"),
+            ]
+        );
+        assert_eq!(excerpt_line("12 │ fn main() {"), Some(("12", "fn main() {")));
+    }
+}
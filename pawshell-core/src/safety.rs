@@ -0,0 +1,233 @@
+//! Dangerous command detection for PetCLI
+//!
+//! Inspects shell commands for destructive or risky patterns so the pet can warn
+//! about them before anything proceeds. Commands are checked when the user types
+//! them with `$`, when the pet suggests them, and right before they are executed.
+//!
+//! The rules are deliberately simple pattern matches; they catch the classic
+//! foot-guns (`rm -rf /`, `dd of=/dev/sda`, fork bombs, `curl | sh`) rather than
+//! trying to understand arbitrary shell code.
+//!
+//! Replies get a warning banner under each code block (or line) with a risky
+//! command. Suggested commands matching a pattern of the `exec_denylist`
+//! aren't offered to `/run` at all.
+
+use regex::Regex;
+use std::sync::OnceLock;
+
+use crate::exec;
+
+/// How bad it would be to run a flagged command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Risky or irreversible in some situations; worth a second look
+    Caution,
+    /// Likely to destroy data or take down the system
+    Danger,
+}
+
+/// A single reason a command was flagged
+#[derive(Debug, Clone)]
+pub struct Warning {
+    pub severity: Severity,
+    pub explanation: &'static str,
+}
+
+struct Rule {
+    pattern: Regex,
+    severity: Severity,
+    explanation: &'static str,
+}
+
+/// Start of a command: beginning of line, after a separator, or after sudo
+const CMD_START: &str = r"(?:^|[;&|(]\s*|\bsudo\s+)";
+
+/// Block devices that hold whole disks
+const DISK_DEVICE: &str = r"/dev/(?:sd[a-z]|hd[a-z]|vd[a-z]|xvd[a-z]|nvme\d|mmcblk\d|disk\d)";
+
+fn rules() -> &'static [Rule] {
+    static RULES: OnceLock<Vec<Rule>> = OnceLock::new();
+    RULES.get_or_init(|| {
+        let rule = |pattern: String, severity, explanation| Rule {
+            pattern: Regex::new(&pattern).expect("invalid safety rule"),
+            severity,
+            explanation,
+        };
+        vec![
+            rule(
+                format!(r"{}rm\s+(?:[^;&|]*\s)?(?:-[a-zA-Z]*[rR][a-zA-Z]*|--recursive)\s+(?:[^;&|]*\s)?(?:/\*?|~/?|\$HOME/?|\*)(?:\s|$|[;&|])", CMD_START),
+                Severity::Danger,
+                "Recursively deletes your root directory, home directory or everything in the current directory. There is no undo.",
+            ),
+            rule(
+                r"--no-preserve-root".to_string(),
+                Severity::Danger,
+                "Disables rm's last safeguard against wiping the entire filesystem.",
+            ),
+            rule(
+                format!(r"{}dd\b[^;&|]*\bof={}", CMD_START, DISK_DEVICE),
+                Severity::Danger,
+                "Writes raw data straight onto a disk, destroying its partition table and filesystems. Double-check the device name.",
+            ),
+            rule(
+                format!(r">\s*{}", DISK_DEVICE),
+                Severity::Danger,
+                "Redirects output onto a raw disk device, overwriting whatever is stored there.",
+            ),
+            rule(
+                format!(r"{}mkfs(?:\.\w+)?\b", CMD_START),
+                Severity::Danger,
+                "Formats a filesystem, erasing everything on that device or partition.",
+            ),
+            rule(
+                r":\s*\(\s*\)\s*\{\s*:\s*\|\s*:\s*&\s*\}\s*;\s*:".to_string(),
+                Severity::Danger,
+                "This is a fork bomb: it spawns processes until the system becomes unresponsive.",
+            ),
+            rule(
+                format!(r"{}(?:chmod|chown)\s+(?:-[a-zA-Z]*R[a-zA-Z]*|--recursive)\s+\S+\s+/(?:\s|$)", CMD_START),
+                Severity::Danger,
+                "Recursively changes permissions or ownership of the whole filesystem, which breaks the system.",
+            ),
+            rule(
+                format!(r"{}mv\s+(?:~|/|\$HOME)/?\s+/dev/null", CMD_START),
+                Severity::Danger,
+                "Moves your home or root directory into /dev/null, which effectively deletes it.",
+            ),
+            rule(
+                r"\b(?:curl|wget)\b[^|;&]*\|\s*(?:sudo\s+)?(?:ba|z|da|k|fi)?sh\b".to_string(),
+                Severity::Caution,
+                "Pipes a downloaded script straight into a shell without letting you read it first. Download it, inspect it, then run it.",
+            ),
+            rule(
+                format!(r"{}chmod\s+(?:-[a-zA-Z]*\s+)*0?777\b", CMD_START),
+                Severity::Caution,
+                "Makes files writable by every user on the system; there's almost always a narrower permission that works.",
+            ),
+            rule(
+                format!(r"{}git\s+push\b[^;&|]*\s(?:--force|-f)(?:\s|$)", CMD_START),
+                Severity::Caution,
+                "Force-pushing rewrites remote history and can discard other people's commits. Prefer --force-with-lease.",
+            ),
+            rule(
+                format!(r"{}git\s+(?:reset\s+--hard|clean\s+-[a-zA-Z]*f)", CMD_START),
+                Severity::Caution,
+                "Permanently discards uncommitted changes or untracked files.",
+            ),
+            rule(
+                format!(r"{}(?:shutdown|reboot|halt|poweroff)\b", CMD_START),
+                Severity::Caution,
+                "Shuts down or restarts the machine.",
+            ),
+        ]
+    })
+}
+
+/// Returns every warning that applies to a command, most severe first
+pub fn analyze(command: &str) -> Vec<Warning> {
+    let mut warnings: Vec<Warning> = rules()
+        .iter()
+        .filter(|rule| rule.pattern.is_match(command))
+        .map(|rule| Warning { severity: rule.severity, explanation: rule.explanation })
+        .collect();
+    warnings.sort_by_key(|warning| std::cmp::Reverse(warning.severity));
+    warnings
+}
+
+/// Formats warnings for a command as a chat message or dialog text
+pub fn describe(command: &str, warnings: &[Warning]) -> String {
+    let mut text = format!("⚠️ Careful with `{}`:", command);
+    for warning in warnings {
+        let label = match warning.severity {
+            Severity::Danger => "DANGER",
+            Severity::Caution => "Caution",
+        };
+        text.push_str(&format!("\n{}: {}", label, warning.explanation));
+    }
+    text
+}
+
+/// Patterns of suggested commands that are never offered to run
+pub struct Denylist {
+    patterns: Vec<Regex>,
+}
+
+impl Denylist {
+    /// Compiles `exec_denylist`, skipping invalid patterns (the config checks report them)
+    pub fn new(patterns: &[String]) -> Self {
+        Self { patterns: patterns.iter().filter_map(|pattern| Regex::new(pattern).ok()).collect() }
+    }
+
+    pub fn matches(&self, command: &str) -> bool {
+        self.patterns.iter().any(|pattern| pattern.is_match(command))
+    }
+}
+
+/// A reply with a warning banner after each code block, or each line of
+/// inline commands, holding risky or denied commands
+pub fn annotate(reply: &str, denylist: &Denylist) -> String {
+    let has_blocks = reply.lines().any(|line| line.trim().starts_with("```"));
+    let mut text = String::new();
+    let mut banners = Vec::new();
+    let mut in_block = false;
+    for line in reply.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            in_block = !in_block;
+        } else if in_block || !has_blocks {
+            let commands = if in_block { exec::block_command(trimmed).into_iter().collect() } else { exec::extract_commands(line) };
+            banners.extend(commands.iter().filter_map(|command| banner(command, denylist)));
+        }
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(line);
+        if !in_block && !banners.is_empty() {
+            text.push_str(&format!("\n{}", banners.join("\n")));
+            banners.clear();
+        }
+    }
+    // A block left open at the end of the reply
+    if !banners.is_empty() {
+        text.push_str(&format!("\n{}", banners.join("\n")));
+    }
+    text
+}
+
+fn banner(command: &str, denylist: &Denylist) -> Option<String> {
+    let warnings = analyze(command);
+    let denied = denylist.matches(command);
+    if warnings.is_empty() && !denied {
+        return None;
+    }
+    let mut text = if warnings.is_empty() { String::new() } else { describe(command, &warnings) };
+    if denied {
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(&format!("🚫 `{}` matches your exec_denylist, so /run won't run it.", command));
+    }
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn risky_and_denied_commands_get_a_banner() {
+        let denylist = Denylist::new(&["^kubectl delete".to_string()]);
+        let reply = "Try this:\n```sh\nls\ngit push --force\n```\nOr `kubectl delete pod web`.";
+        let annotated = annotate(reply, &denylist);
+        assert!(annotated.starts_with("Try this:\n```sh\nls\ngit push --force\n```\n⚠️ Careful with `git push --force`:\nCaution: Force-pushing"));
+        // With code blocks only they hold the commands, as for `/run`
+        assert!(annotated.ends_with("Prefer --force-with-lease.\nOr `kubectl delete pod web`."));
+
+        let annotated = annotate("Run `kubectl delete pod web` then `ls`.\nDone.", &denylist);
+        assert_eq!(
+            annotated,
+            "Run `kubectl delete pod web` then `ls`.\n🚫 `kubectl delete pod web` matches your exec_denylist, so /run won't run it.\nDone."
+        );
+        assert_eq!(annotate("All good: `ls`", &denylist), "All good: `ls`");
+    }
+}
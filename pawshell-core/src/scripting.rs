@@ -0,0 +1,402 @@
+//! Rhai scripts from `~/.config/petcli/scripts/`
+//!
+//! Every `*.rhai` file in the scripts directory is run once at startup, and
+//! the functions it defines are called as hooks afterwards:
+//!
+//! - `fn on_message(text)`: the user sent a chat message
+//! - `fn on_command(command, exit_code)`: a command ran; `exit_code` is `()`
+//!   when unknown
+//! - `fn on_tick()`: called about once a second
+//!
+//! Top-level code can call `register_command("name", "description")` to add a
+//! `/name` command that calls the script's `fn name(args)`. Scripts read the
+//! pet with `pet_name()`, `mood()` (0.0 to 1.0), `hour()` and `weekday()`
+//! (e.g. "Friday"), talk with `say(text)` (or `print`), nudge the mood with
+//! `change_mood(delta)`, and throttle reactions with `every("key", minutes)`,
+//! which is true at most once per interval:
+//!
+//! ```text
+//! fn on_tick() {
+//!     if mood() < 0.3 && weekday() == "Friday" && every("friday", 240) {
+//!         say("It's almost the weekend, hang in there!");
+//!     }
+//! }
+//! ```
+//!
+//! Scripts are sandboxed: Rhai has no file, network or process access, `import`
+//! is disabled, and each call is limited in operations and memory. A script
+//! that fails is disabled for the rest of the session.
+
+use chrono::Timelike;
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{CallFnOptions, Dynamic, Engine, Scope, AST};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::clock;
+use crate::config_path;
+use crate::history::HistoryEntry;
+use crate::pet::PetState;
+
+/// Operations a script may run per call (or at load) before it is stopped
+const MAX_OPERATIONS: u64 = 1_000_000;
+/// Longest string, array or map a script may build
+const MAX_SIZE: usize = 64 * 1024;
+
+/// Where scripts are loaded from (shared by all profiles)
+pub fn scripts_dir() -> PathBuf {
+    config_path::base_config_dir().join("scripts")
+}
+
+/// What scripts did during one hook call
+#[derive(Debug, Default, PartialEq)]
+pub struct ScriptOutput {
+    /// Things scripts said (prefixed with the script's name), and failures
+    pub messages: Vec<String>,
+    /// Total mood change requested with `change_mood`
+    pub mood_change: f32,
+}
+
+/// A custom slash command registered by a script
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptCommand {
+    pub name: String,
+    pub description: String,
+    /// Name of the script that handles it
+    pub script: String,
+}
+
+/// State shared with the functions registered on the engine
+#[derive(Default)]
+struct Shared {
+    pet_name: String,
+    mood: f32,
+    output: ScriptOutput,
+    /// Commands registered by the script currently loading
+    registered: Vec<(String, String)>,
+    /// When each `every` key last fired
+    last_fired: HashMap<String, Instant>,
+}
+
+struct Script {
+    name: String,
+    ast: AST,
+    scope: Scope<'static>,
+}
+
+impl Script {
+    fn defines(&self, function: &str, params: usize) -> bool {
+        self.ast.iter_functions().any(|f| f.name == function && f.params.len() == params)
+    }
+}
+
+/// All loaded scripts
+pub struct ScriptHost {
+    engine: Engine,
+    scripts: Vec<Script>,
+    commands: Vec<ScriptCommand>,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Default for ScriptHost {
+    fn default() -> Self {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        Self { engine: sandboxed_engine(&shared), scripts: Vec::new(), commands: Vec::new(), shared }
+    }
+}
+
+impl ScriptHost {
+    /// Loads every `*.rhai` file in `dir`, returning the host and a problem per script that didn't load
+    pub fn load_dir(dir: &Path) -> (Self, Vec<String>) {
+        let mut host = Self::default();
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return (host, Vec::new());
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "rhai"))
+            .collect();
+        paths.sort();
+
+        let mut problems = Vec::new();
+        for path in paths {
+            let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+            let result = std::fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+                .and_then(|source| host.add(&name, &source));
+            if let Err(e) = result {
+                problems.push(e);
+            }
+        }
+        (host, problems)
+    }
+
+    /// Compiles and runs a script's top level, keeping its hooks and commands
+    pub fn add(&mut self, name: &str, source: &str) -> Result<(), String> {
+        let ast = self.engine.compile(source).map_err(|e| format!("{}: {}", name, e))?;
+        let mut scope = Scope::new();
+        self.lock().registered.clear();
+        self.engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|e| format!("{} failed to start: {}", name, e))?;
+        let script = Script { name: name.to_string(), ast, scope };
+
+        // Anything said while loading has no place in the chat yet
+        let registered = {
+            let mut shared = self.lock();
+            shared.output = ScriptOutput::default();
+            std::mem::take(&mut shared.registered)
+        };
+        let mut commands = Vec::new();
+        for (command, description) in registered {
+            if !script.defines(&command, 1) {
+                return Err(format!("{} registers /{} but has no fn {}(args)", name, command, command));
+            }
+            if let Some(other) = self.command(&command) {
+                return Err(format!("{} registers /{}, which {} already did", name, command, other.script));
+            }
+            commands.push(ScriptCommand { name: command, description, script: name.to_string() });
+        }
+        self.commands.extend(commands);
+        self.scripts.push(script);
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scripts.is_empty()
+    }
+
+    /// Each script's name with the hooks it defines
+    pub fn describe(&self) -> Vec<String> {
+        self.scripts
+            .iter()
+            .map(|script| {
+                let hooks: Vec<&str> = [("on_message", 1), ("on_command", 2), ("on_tick", 0)]
+                    .into_iter()
+                    .filter(|(hook, params)| script.defines(hook, *params))
+                    .map(|(hook, _)| hook)
+                    .collect();
+                if hooks.is_empty() {
+                    script.name.clone()
+                } else {
+                    format!("{} ({})", script.name, hooks.join(", "))
+                }
+            })
+            .collect()
+    }
+
+    /// Commands registered by the loaded scripts
+    pub fn commands(&self) -> &[ScriptCommand] {
+        &self.commands
+    }
+
+    pub fn command(&self, name: &str) -> Option<&ScriptCommand> {
+        self.commands.iter().find(|command| command.name == name)
+    }
+
+    pub fn on_message(&mut self, pet: &PetState, message: &str) -> ScriptOutput {
+        self.run_hook(pet, "on_message", || vec![Dynamic::from(message.to_string())])
+    }
+
+    pub fn on_command(&mut self, pet: &PetState, entry: &HistoryEntry) -> ScriptOutput {
+        let exit_code = entry.exit_code.map_or(Dynamic::UNIT, |code| Dynamic::from(code as i64));
+        self.run_hook(pet, "on_command", || vec![Dynamic::from(entry.command.clone()), exit_code.clone()])
+    }
+
+    pub fn on_tick(&mut self, pet: &PetState) -> ScriptOutput {
+        self.run_hook(pet, "on_tick", Vec::new)
+    }
+
+    /// Runs a registered command with everything after its name as `args`
+    pub fn run_command(&mut self, pet: &PetState, name: &str, args: &str) -> ScriptOutput {
+        let Some(script) = self.command(name).map(|command| command.script.clone()) else {
+            return ScriptOutput::default();
+        };
+        self.prepare(pet);
+        let mut failed = None;
+        if let Some(index) = self.scripts.iter().position(|s| s.name == script) {
+            if let Err(e) = self.call(index, name, vec![Dynamic::from(args.to_string())]) {
+                failed = Some((index, e));
+            }
+        }
+        let mut output = std::mem::take(&mut self.lock().output);
+        if let Some((index, e)) = failed {
+            output.messages.push(self.disable(index, &e));
+        }
+        output
+    }
+
+    fn run_hook(&mut self, pet: &PetState, hook: &str, args: impl Fn() -> Vec<Dynamic>) -> ScriptOutput {
+        let params = args().len();
+        self.prepare(pet);
+        let mut failures = Vec::new();
+        for index in 0..self.scripts.len() {
+            if self.scripts[index].defines(hook, params) {
+                if let Err(e) = self.call(index, hook, args()) {
+                    failures.push((index, e));
+                }
+            }
+        }
+        let mut output = std::mem::take(&mut self.lock().output);
+        // Back to front so the indices stay valid
+        for (index, e) in failures.into_iter().rev() {
+            output.messages.push(self.disable(index, &e));
+        }
+        output
+    }
+
+    fn prepare(&mut self, pet: &PetState) {
+        let mut shared = self.lock();
+        shared.pet_name = pet.name.clone();
+        shared.mood = pet.mood;
+        shared.output = ScriptOutput::default();
+    }
+
+    fn call(&mut self, index: usize, function: &str, args: Vec<Dynamic>) -> Result<(), String> {
+        let script = &mut self.scripts[index];
+        let said_before = lock(&self.shared).output.messages.len();
+        let options = CallFnOptions::new().eval_ast(false).rewind_scope(true);
+        let result = self
+            .engine
+            .call_fn_with_options::<Dynamic>(options, &mut script.scope, &script.ast, function, args)
+            .map_err(|e| e.to_string());
+        // Prefix what this script said with its name
+        for message in lock(&self.shared).output.messages.iter_mut().skip(said_before) {
+            *message = format!("[{}] {}", script.name, message);
+        }
+        result.map(|_| ())
+    }
+
+    /// Removes a failed script and its commands, returning a message about it
+    fn disable(&mut self, index: usize, error: &str) -> String {
+        let script = self.scripts.remove(index);
+        self.commands.retain(|command| command.script != script.name);
+        tracing::warn!(script = %script.name, "script disabled: {}", error);
+        format!("The {} script failed and is disabled until restart: {}", script.name, error)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Shared> {
+        lock(&self.shared)
+    }
+}
+
+fn lock(shared: &Mutex<Shared>) -> MutexGuard<'_, Shared> {
+    shared.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// An engine without module loading, limited per call, with the pet API registered
+fn sandboxed_engine(shared: &Arc<Mutex<Shared>>) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_module_resolver(DummyModuleResolver::new())
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(32)
+        .set_max_expr_depths(64, 32)
+        .set_max_string_size(MAX_SIZE)
+        .set_max_array_size(MAX_SIZE)
+        .set_max_map_size(MAX_SIZE);
+
+    let state = shared.clone();
+    engine.register_fn("pet_name", move || lock(&state).pet_name.clone());
+    let state = shared.clone();
+    engine.register_fn("mood", move || lock(&state).mood as f64);
+    engine.register_fn("hour", || clock::local_now().hour() as i64);
+    engine.register_fn("weekday", || clock::local_now().format("%A").to_string());
+
+    let state = shared.clone();
+    engine.register_fn("say", move |text: &str| lock(&state).output.messages.push(text.to_string()));
+    let state = shared.clone();
+    engine.on_print(move |text| lock(&state).output.messages.push(text.to_string()));
+    let state = shared.clone();
+    engine.register_fn("change_mood", move |delta: f64| lock(&state).output.mood_change += delta as f32);
+    let state = shared.clone();
+    engine.register_fn("every", move |key: &str, minutes: i64| {
+        let mut shared = lock(&state);
+        let interval = Duration::from_secs(minutes.max(0) as u64 * 60);
+        let due = shared.last_fired.get(key).is_none_or(|fired| fired.elapsed() >= interval);
+        if due {
+            shared.last_fired.insert(key.to_string(), Instant::now());
+        }
+        due
+    });
+    let state = shared.clone();
+    engine.register_fn("register_command", move |name: &str, description: &str| {
+        let name = name.trim_start_matches('/');
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("invalid command name {:?}; use letters, digits and _", name).into());
+        }
+        lock(&state).registered.push((name.to_string(), description.to_string()));
+        Ok::<_, Box<rhai::EvalAltResult>>(())
+    });
+    engine
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pet(mood: f32) -> PetState {
+        PetState { name: "Whiskers".to_string(), mood, ..PetState::default() }
+    }
+
+    #[test]
+    fn hooks_can_read_the_pet_and_talk() {
+        let mut host = ScriptHost::default();
+        host.add(
+            "greeter",
+            r#"
+            fn on_message(text) {
+                if text.contains("hello") { say(`${pet_name()} waves back`); }
+                if mood() < 0.5 { change_mood(0.1); }
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(host.describe(), vec!["greeter (on_message)"]);
+
+        let output = host.on_message(&pet(0.3), "hello there");
+        assert_eq!(output.messages, vec!["[greeter] Whiskers waves back"]);
+        assert!((output.mood_change - 0.1).abs() < 1e-6);
+        assert_eq!(host.on_message(&pet(0.9), "bye"), ScriptOutput::default());
+    }
+
+    #[test]
+    fn registered_commands_call_the_script() {
+        let mut host = ScriptHost::default();
+        host.add(
+            "notes",
+            r#"
+            register_command("standup", "Show my standup notes");
+            fn standup(args) { print(`notes for ${args}`); }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(host.command("standup").map(|c| c.description.as_str()), Some("Show my standup notes"));
+        assert_eq!(host.run_command(&pet(0.5), "standup", "today").messages, vec!["[notes] notes for today"]);
+
+        let error = host.add("other", r#"register_command("standup", "again"); fn standup(args) {}"#).unwrap_err();
+        assert!(error.contains("already"));
+        assert!(host.add("broken", r#"register_command("nope", "no handler");"#).is_err());
+    }
+
+    #[test]
+    fn every_fires_once_per_interval() {
+        let mut host = ScriptHost::default();
+        host.add("nag", r#"fn on_tick() { if every("nag", 60) { say("stretch!"); } }"#).unwrap();
+        assert_eq!(host.on_tick(&pet(0.5)).messages.len(), 1);
+        assert!(host.on_tick(&pet(0.5)).messages.is_empty());
+    }
+
+    #[test]
+    fn runaway_scripts_are_stopped_and_disabled() {
+        let mut host = ScriptHost::default();
+        host.add("spin", r#"register_command("spin", ""); fn spin(args) { loop {} }"#).unwrap();
+        let output = host.run_command(&pet(0.5), "spin", "");
+        assert!(output.messages[0].contains("disabled"));
+        assert!(host.is_empty());
+        assert!(host.command("spin").is_none());
+        assert!(host.add("importer", r#"import "secrets" as s;"#).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+//! A pet without a user interface
+//!
+//! `PetSession` loads the config, state and backend the way the TUI does and
+//! answers questions with the same context, recording each exchange. The
+//! daemon keeps one alive so every client talks to the same pet, and one-off
+//! commands like `pawshell ask` use one when no daemon is running.
+
+use std::time::{Duration, Instant};
+
+use crate::clock;
+use crate::backup;
+use crate::budget;
+use crate::config::Config;
+use crate::error::{LlmError, SessionError, StorageError};
+use crate::history::{self, HistoryEntry};
+use crate::llm::{self, ChatContext, Generation, LLMBackend, PromptContext};
+use crate::pet::{ChatMessage, PetState};
+use crate::privacy::Redactor;
+use crate::storage::{self, StateStore};
+use crate::battery;
+use crate::threads;
+use crate::templates::Globals;
+
+/// A prompt ready to send, along with the backend to send it to
+///
+/// Generating doesn't need the session, so callers sharing one can release it
+/// while waiting for the backend.
+pub struct PendingReply {
+    pub user_message: String,
+    pub prompt: String,
+    pub llm: Box<dyn LLMBackend>,
+}
+
+impl PendingReply {
+    pub async fn generate(&self) -> (Result<Generation, LlmError>, Duration) {
+        let started = Instant::now();
+        let result = self.llm.generate(&self.prompt).await;
+        (result, started.elapsed())
+    }
+}
+
+pub struct PetSession {
+    pub config: Config,
+    pub state: PetState,
+    pub recent_commands: Vec<HistoryEntry>,
+    store: Box<dyn StateStore>,
+    llm: Box<dyn LLMBackend>,
+    redactor: Redactor,
+    failure_streak: usize,
+    /// A budget is used up and the local model answers instead of OpenAI
+    over_budget: bool,
+}
+
+impl PetSession {
+    /// Loads the pet for the current profile, along with warnings about its state
+    pub fn open() -> Result<(Self, Vec<String>), SessionError> {
+        let config = Config::load();
+        let (mut store, storage_warning) = storage::open(&config);
+        let mut state = store.load()?;
+        state.name = config.pet_name.clone();
+        state.decay_mood(clock::now());
+        let warnings = storage_warning.into_iter().chain(store.take_notice()).collect();
+
+        let mut llm = llm::build(&config)?;
+        let conversation = threads::messages(&state.chat_history, &state.threads, state.current_thread.as_deref());
+        llm::resume(llm.as_mut(), &conversation);
+        let recent_commands = history::read_recent(&config, config.command_history_limit);
+        let session = Self {
+            redactor: Redactor::new(&config.redaction_patterns),
+            failure_streak: history::failure_streak(&recent_commands),
+            recent_commands,
+            config,
+            state,
+            store,
+            llm,
+            over_budget: false,
+        };
+        Ok((session, warnings))
+    }
+
+    /// The backend answering this session's questions
+    pub fn llm(&self) -> &dyn LLMBackend {
+        self.llm.as_ref()
+    }
+
+    /// Picks up state saved by another instance, e.g. bookmarks added in the TUI
+    pub fn refresh(&mut self) {
+        if !self.store.changed_externally() {
+            return;
+        }
+        if let Ok(mut state) = self.store.load() {
+            state.name = self.config.pet_name.clone();
+            self.state = state;
+        }
+    }
+
+    /// Builds the prompt for a chat message, redacted if it leaves this machine
+    ///
+    /// `docs` is what [`ChatContext::look_up_docs`] found for the message.
+    pub fn prepare(&mut self, user_message: &str, docs: Option<String>) -> PendingReply {
+        self.check_budget();
+        self.state.last_interaction = clock::now();
+        self.state.cheer_up(0.1);
+        let battery = self.config.battery_awareness.then(battery::read).flatten();
+        let shared = ChatContext::gather(&self.config, &self.state, &self.recent_commands, docs);
+        let context = PromptContext { battery: battery.as_ref(), ..shared.prompt_context(&self.config, &self.state, user_message) };
+        let globals = Globals::new(&self.state, shared.recent_commands(), None);
+        let (prompt, problem) = self.llm.format_prompt(user_message, &context).render(&self.config.prompt_templates, &globals);
+        if let Some(problem) = problem {
+            tracing::warn!("{}", problem);
+        }
+        let prompt = if self.config.privacy_filter && self.llm.is_remote() {
+            self.redactor.redact(&prompt)
+        } else {
+            prompt
+        };
+        PendingReply { user_message: user_message.to_string(), prompt, llm: self.llm.boxed_clone() }
+    }
+
+    /// Records a reply to a prepared message and saves the state
+    pub fn record(&mut self, pending: PendingReply, generation: &Generation, latency: Duration) -> Result<(), StorageError> {
+        self.llm.add_to_history(pending.user_message.clone(), generation.text.clone());
+        let mut message = ChatMessage::new(pending.user_message, generation.text.clone());
+        message.provider = Some(pending.llm.provider().to_string());
+        message.model = Some(pending.llm.model().to_string());
+        message.prompt_tokens = generation.prompt_tokens;
+        message.completion_tokens = generation.completion_tokens;
+        message.latency_ms = Some(latency.as_millis() as u64);
+        message.first_token_ms = generation.first_token.map(|time| time.as_millis() as u64);
+        message.generation_ms = generation.generation_time.map(|time| time.as_millis() as u64);
+        message.thread = self.state.current_thread.clone();
+        self.state.chat_history.push(message);
+        if pending.llm.provider() == "openai" {
+            let today = clock::local_now().date_naive();
+            budget::charge(&mut self.state.spend_log, &self.config.model_prices, pending.llm.model(), generation, today);
+        }
+        self.save()
+    }
+
+    /// Switches to the local model while a budget is used up, and back
+    fn check_budget(&mut self) {
+        let standing = self.config.budget().standing(&self.state.spend_log, clock::local_now().date_naive());
+        let over_budget = standing.needs_local_model(&self.config.llm_provider);
+        if over_budget == self.over_budget {
+            return;
+        }
+        match llm::build_within_budget(&self.config, over_budget) {
+            Ok(mut llm) => {
+                let conversation = threads::messages(&self.state.chat_history, &self.state.threads, self.state.current_thread.as_deref());
+                llm::resume(llm.as_mut(), &conversation);
+                self.llm = llm;
+                self.over_budget = over_budget;
+                if let Some(notice) = standing.describe().filter(|_| over_budget) {
+                    tracing::warn!("{} Answering with {} on Ollama instead.", notice, self.config.ollama_model);
+                }
+            }
+            Err(e) => tracing::error!("couldn't switch backends for the budget: {}", e),
+        }
+    }
+
+    /// Asks the pet something and records the exchange
+    pub async fn ask(&mut self, user_message: &str) -> Result<String, SessionError> {
+        let docs = ChatContext::look_up_docs(&self.config, user_message).await;
+        self.refresh();
+        let pending = self.prepare(user_message, docs);
+        let (result, latency) = pending.generate().await;
+        let generation = result?;
+        self.record(pending, &generation, latency)?;
+        Ok(generation.text)
+    }
+
+    /// Adds a command reported by the shell hook; failures lower the mood
+    pub fn record_command(&mut self, entry: HistoryEntry) {
+        if entry.failed() {
+            self.failure_streak += 1;
+            self.state.react_to_failure(self.failure_streak);
+        } else if entry.exit_code.is_some() {
+            self.failure_streak = 0;
+        }
+        self.recent_commands.push(entry);
+        if self.recent_commands.len() > self.config.command_history_limit {
+            self.recent_commands.remove(0);
+        }
+    }
+
+    /// Saves the state, backing it up first when a backup is due
+    pub fn save(&mut self) -> Result<(), StorageError> {
+        // A failed backup has been logged, and there's no one else to tell
+        backup::save_with_backup(self.store.as_mut(), &self.state, &self.config).map(drop)
+    }
+}
@@ -0,0 +1,249 @@
+//! A pet shared by everyone on one machine
+//!
+//! With `storage_backend = "Shared"` the state lives in a single TOML file at
+//! `shared_state_path`, e.g. `/srv/pawshell/pet.toml` on a jump host, in a
+//! directory the whole team can write to. Loads and saves hold an exclusive
+//! lock on `<file>.lock`, so teammates saving at the same moment take turns
+//! instead of interleaving, and the file is kept group-writable. A save that
+//! finds a teammate's newer save on disk merges into it rather than replacing
+//! it: their chat is kept with ours appended.
+//!
+//! The pet tells teammates apart by `$USER`: it greets each by name, remembers
+//! when each was last around (`visitors`) and who gave it the last treat
+//! (`fed_by`). Its mood is simply the pet's, so everyone shares it.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::config::Config;
+use crate::error::StorageError;
+use crate::pet::{ChatMessage, PetState};
+use crate::reminders;
+use crate::storage::{self, StateStore};
+
+/// Who gave the pet its last treat
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Feeding {
+    pub user: String,
+    pub at: DateTime<Utc>,
+}
+
+/// The name of the person at the keyboard, from `$USER` (`%USERNAME%` on Windows)
+pub fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .ok()
+        .filter(|user| !user.is_empty())
+        .unwrap_or_else(|| "friend".to_string())
+}
+
+/// What the pet says when `user` starts a session: a welcome (back), and who
+/// fed it last if that was someone else
+pub fn greeting(state: &PetState, user: &str, now: DateTime<Utc>) -> String {
+    let mut greeting = match state.visitors.get(user) {
+        Some(&seen) => format!(
+            "*wags at {user}* Welcome back, {user}! Last time I saw you was {} ago.",
+            reminders::format_duration(now - seen)
+        ),
+        None if state.visitors.is_empty() => format!("*sniffs {user}* Hi {user}! I'm the team's pet now."),
+        None => format!("*sniffs {user}* A new face! Hi {user}, I'm the team's pet."),
+    };
+    if let Some(feeding) = state.fed_by.as_ref().filter(|feeding| feeding.user != user) {
+        greeting.push_str(&format!(
+            " {} fed me last, {} ago.",
+            feeding.user,
+            reminders::format_duration(now - feeding.at)
+        ));
+    }
+    greeting
+}
+
+/// The whole state in one locked TOML file that several users share
+pub struct SharedStore {
+    path: PathBuf,
+    /// Modification time of the state file as of our last load or save, or
+    /// None after a merge so the merged state gets loaded
+    mtime: Option<SystemTime>,
+    /// Our chat history as of our last load or save, to tell which messages are new
+    synced: Vec<ChatMessage>,
+}
+
+impl SharedStore {
+    pub fn open(config: &Config) -> Result<Self, StorageError> {
+        let path = PathBuf::from(config.shared_state_path.as_deref().ok_or_else(|| {
+            StorageError::Format("storage_backend = \"Shared\" needs shared_state_path".to_string())
+        })?);
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(StorageError::file(dir))?;
+        }
+        let mtime = file_mtime(&path);
+        Ok(Self { path, mtime, synced: Vec::new() })
+    }
+
+    /// Waits for and takes the lock, which is released when the file is dropped
+    fn lock(&self) -> Result<File, StorageError> {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".lock");
+        let path = self.path.with_file_name(name);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(StorageError::file(&path))?;
+        share_with_group(&path);
+        file.lock().map_err(StorageError::file(&path))?;
+        Ok(file)
+    }
+}
+
+impl StateStore for SharedStore {
+    fn load(&mut self) -> Result<PetState, StorageError> {
+        let _lock = self.lock()?;
+        let state = if self.path.exists() { storage::read_state_file(&self.path)? } else { PetState::default() };
+        self.mtime = file_mtime(&self.path);
+        self.synced = state.chat_history.clone();
+        Ok(state)
+    }
+
+    fn save(&mut self, state: &PetState) -> Result<(), StorageError> {
+        let _lock = self.lock()?;
+        // A teammate saved since we last looked
+        let theirs = match file_mtime(&self.path) {
+            Some(mtime) if self.mtime != Some(mtime) => Some(storage::read_state_file(&self.path)?),
+            _ => None,
+        };
+        let merged = theirs.map(|theirs| merge(theirs, state, &self.synced));
+        let saved = merged.as_ref().unwrap_or(state);
+        storage::write_atomic(&self.path, toml::to_string_pretty(saved)?.as_bytes())
+            .map_err(StorageError::file(&self.path))?;
+        share_with_group(&self.path);
+        self.mtime = if merged.is_some() { None } else { file_mtime(&self.path) };
+        self.synced = state.chat_history.clone();
+        Ok(())
+    }
+
+    fn changed_externally(&mut self) -> bool {
+        let mtime = file_mtime(&self.path);
+        mtime.is_some() && mtime != self.mtime
+    }
+
+    fn backup(&self, dest: &Path) -> Result<bool, StorageError> {
+        let _lock = self.lock()?;
+        if !self.path.exists() {
+            return Ok(false);
+        }
+        std::fs::copy(&self.path, dest).map_err(StorageError::file(&self.path))?;
+        Ok(true)
+    }
+
+    fn backup_extension(&self) -> &'static str {
+        "toml"
+    }
+}
+
+/// Folds our changes into a state a teammate saved since we last synced
+///
+/// Our new messages are appended to their chat, each visitor's latest visit
+/// and the latest feeding are kept, and everything else is ours.
+fn merge(theirs: PetState, ours: &PetState, synced: &[ChatMessage]) -> PetState {
+    let kept = synced.len() - storage::dropped_prefix(synced, &ours.chat_history);
+    let mut chat_history = theirs.chat_history;
+    chat_history.extend_from_slice(&ours.chat_history[kept..]);
+    let mut visitors = theirs.visitors;
+    for (user, &seen) in &ours.visitors {
+        visitors.entry(user.clone()).and_modify(|last| *last = (*last).max(seen)).or_insert(seen);
+    }
+    let fed_by = [theirs.fed_by, ours.fed_by.clone()].into_iter().flatten().max_by_key(|feeding| feeding.at);
+    PetState { chat_history, visitors, fed_by, ..ours.clone() }
+}
+
+fn file_mtime(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Lets the rest of the file's group write it too; a file someone else owns
+/// is left as it is
+fn share_with_group(path: &Path) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Ok(meta) = std::fs::metadata(path) {
+            let mut permissions = meta.permissions();
+            if permissions.mode() & 0o060 != 0o060 {
+                permissions.set_mode(permissions.mode() | 0o060);
+                let _ = std::fs::set_permissions(path, permissions);
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::test_time;
+    use chrono::Duration;
+
+    #[test]
+    fn teammates_are_greeted_by_name() {
+        let now = test_time(0);
+        let mut state = PetState::default();
+        assert_eq!(greeting(&state, "alice", now), "*sniffs alice* Hi alice! I'm the team's pet now.");
+        state.visitors.insert("alice".to_string(), now - Duration::hours(3));
+        state.fed_by = Some(Feeding { user: "bob".to_string(), at: now - Duration::minutes(20) });
+        assert_eq!(
+            greeting(&state, "alice", now),
+            "*wags at alice* Welcome back, alice! Last time I saw you was 3h 0m ago. bob fed me last, 20m ago."
+        );
+        assert_eq!(greeting(&state, "bob", now), "*sniffs bob* A new face! Hi bob, I'm the team's pet.");
+    }
+
+    #[test]
+    fn saves_merge_into_a_teammates_newer_save() {
+        let message = |text: &str| ChatMessage::new(text.to_string(), format!("re: {}", text));
+        let synced = vec![message("hi")];
+        let mut theirs = PetState { chat_history: vec![message("hi"), message("bob's question")], ..PetState::default() };
+        theirs.visitors.insert("bob".to_string(), test_time(10));
+        theirs.visitors.insert("alice".to_string(), test_time(0));
+        theirs.fed_by = Some(Feeding { user: "bob".to_string(), at: test_time(10) });
+        let mut ours = PetState { chat_history: vec![message("hi"), message("alice's question")], mood: 0.25, ..PetState::default() };
+        ours.visitors.insert("alice".to_string(), test_time(5));
+        ours.fed_by = Some(Feeding { user: "alice".to_string(), at: test_time(5) });
+
+        let merged = merge(theirs, &ours, &synced);
+        let texts: Vec<&str> = merged.chat_history.iter().map(|message| message.user_message.as_str()).collect();
+        assert_eq!(texts, ["hi", "bob's question", "alice's question"]);
+        assert_eq!(merged.visitors["alice"], test_time(5));
+        assert_eq!(merged.visitors["bob"], test_time(10));
+        assert_eq!(merged.fed_by.unwrap().user, "bob");
+        assert_eq!(merged.mood, 0.25);
+    }
+
+    #[test]
+    fn saving_keeps_what_a_teammate_saved_meanwhile() {
+        let path = std::env::temp_dir().join(format!("pawshell-shared-test-{}", std::process::id())).join("pet.toml");
+        let config = Config { shared_state_path: Some(path.to_string_lossy().into_owned()), ..Config::default() };
+        let message = |text: &str| ChatMessage::new(text.to_string(), String::new());
+        let mut alice = SharedStore::open(&config).unwrap();
+        let mut bob = SharedStore::open(&config).unwrap();
+        let mut alices = alice.load().unwrap();
+        let mut bobs = bob.load().unwrap();
+
+        alices.chat_history.push(message("from alice"));
+        alice.save(&alices).unwrap();
+        // Make sure the file's modification time moves on coarse clocks
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        bobs.chat_history.push(message("from bob"));
+        bob.save(&bobs).unwrap();
+        assert!(bob.changed_externally());
+
+        let texts: Vec<String> = bob.load().unwrap().chat_history.into_iter().map(|message| message.user_message).collect();
+        assert_eq!(texts, ["from alice", "from bob"]);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
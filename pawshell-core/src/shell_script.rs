@@ -0,0 +1,129 @@
+//! Shell scripts written with the backend for `/script`
+//!
+//! `/script <description>` asks for a script, and every `/script <change>`
+//! after that sends the current version back with the change to make, so a
+//! draft can be refined ("make it POSIX sh", "add a --dry-run flag") before
+//! it's saved. Drafts are shown as numbered lines, which the TUI highlights
+//! like `/review` excerpts.
+//!
+//! Saved scripts get a header comment with the description and the changes
+//! asked for, and are made executable. Existing files are never overwritten.
+//! Not to be confused with `scripting`, which runs the user's Rhai scripts.
+
+use chrono::NaiveDate;
+use std::path::{Path, PathBuf};
+
+use crate::review;
+use crate::templates::Prompt;
+
+/// Used when the reply has no shebang
+const DEFAULT_SHEBANG: &str = "#!/usr/bin/env bash";
+
+const TEMPLATE: &str = "Write a shell script that {{ description }}. Start it with a shebang (bash unless \
+I ask for another shell), quote variables, fail early on errors, and print a usage message when \
+arguments are missing. Comment only what isn't obvious. Reply with the whole script in a single \
+```sh block and nothing else.";
+
+const REVISION_TEMPLATE: &str = "Here is a shell script that {{ description }}:\n\n```sh\n{{ code }}\n```\n\n\
+Change it: {{ change }}. Reply with the whole updated script in a single ```sh block and nothing else.";
+
+/// The latest version of a script and how it came about
+pub struct ScriptDraft {
+    pub description: String,
+    /// Changes asked for since the first version, oldest first
+    pub changes: Vec<String>,
+    pub code: String,
+}
+
+impl ScriptDraft {
+    /// The numbered lines of the script, for showing it
+    pub fn render(&self) -> String {
+        let lines: Vec<&str> = self.code.lines().collect();
+        let width = lines.len().to_string().len();
+        lines.iter().enumerate().map(|(i, line)| review::excerpt(i + 1, width, line)).collect::<Vec<_>>().join("\n")
+    }
+
+    /// The script with a header comment after the shebang
+    pub fn with_header(&self, today: NaiveDate) -> String {
+        let (shebang, body) = match self.code.split_once('\n') {
+            Some((first, rest)) if first.starts_with("#!") => (first, rest),
+            _ if self.code.starts_with("#!") => (self.code.as_str(), ""),
+            _ => (DEFAULT_SHEBANG, self.code.as_str()),
+        };
+        let mut header = format!("{}\n#\n# {}\n", shebang, capitalize(&self.description));
+        if !self.changes.is_empty() {
+            header.push_str(&format!("# Changes: {}\n", self.changes.join("; ")));
+        }
+        header.push_str(&format!("# Written with pawshell on {}.\n#\n", today));
+        format!("{}{}\n", header, body.trim_end())
+    }
+
+    /// Writes the script to `path`, unless something is there already, and
+    /// makes it executable
+    pub fn save(&self, path: &Path, today: NaiveDate) -> Result<PathBuf, String> {
+        if path.exists() {
+            return Err(format!("{} already exists; pick another path.", path.display()));
+        }
+        std::fs::write(path, self.with_header(today)).map_err(|e| format!("Couldn't write {}: {}", path.display(), e))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+                .map_err(|e| format!("Saved {}, but couldn't make it executable: {}", path.display(), e))?;
+        }
+        Ok(path.to_path_buf())
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default()
+}
+
+/// Asks for the first version of a script
+pub fn prompt(description: &str) -> Prompt {
+    Prompt::new("script", TEMPLATE).var("description", description)
+}
+
+/// Asks for a change to the current version
+pub fn revision_prompt(draft: &ScriptDraft, change: &str) -> Prompt {
+    Prompt::new("script_revision", REVISION_TEMPLATE).var("description", &draft.description).var("code", &draft.code).var("change", change)
+}
+
+/// The script in a reply: its first code block, or the whole reply if it's
+/// just the script
+pub fn extract(reply: &str) -> Option<String> {
+    let mut block = None;
+    for line in reply.lines() {
+        match (&mut block, line.trim_start().starts_with("```")) {
+            (None, true) => block = Some(Vec::new()),
+            (Some(_), true) => break,
+            (Some(lines), false) => lines.push(line),
+            (None, false) => {}
+        }
+    }
+    let code = match block {
+        Some(lines) => lines.join("\n"),
+        None if reply.trim_start().starts_with("#!") => reply.to_string(),
+        None => return None,
+    };
+    let code = code.trim_matches('\n').trim_end().to_string();
+    (!code.trim().is_empty()).then_some(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_scripts_get_a_header_after_the_shebang() {
+        let code = extract("Here you go:\n```sh\n#!/bin/sh\nset -eu\necho \"$1\"\n```\nEnjoy!").unwrap();
+        let draft = ScriptDraft { description: "echoes its argument".to_string(), changes: vec!["make it POSIX sh".to_string()], code };
+        let today = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        assert_eq!(
+            draft.with_header(today),
+            "#!/bin/sh\n#\n# Echoes its argument\n# Changes: make it POSIX sh\n# Written with pawshell on 2024-03-04.\n#\nset -eu\necho \"$1\"\n"
+        );
+        assert_eq!(extract("I can't help with that."), None);
+    }
+}
@@ -0,0 +1,337 @@
+//! Learning goals: a small skill tree for Vim motions, pipes and git
+//!
+//! `/learn` picks the tracks to work on. A skill counts as learned once it
+//! shows up in a command that succeeded (pipes and git) or in the answer to a
+//! `/quiz` question (every track; Vim motions never show up in the shell
+//! history). The first unlearned skill of the chosen tracks is the next one,
+//! and it goes into prompts so the pet's tips lean toward it.
+
+use serde::{Deserialize, Serialize};
+
+use crate::history::HistoryEntry;
+use crate::pet::PetState;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Track {
+    Vim,
+    Pipes,
+    Git,
+}
+
+impl Track {
+    pub const ALL: [Track; 3] = [Track::Vim, Track::Pipes, Track::Git];
+
+    /// The name used with `/learn`
+    pub fn name(self) -> &'static str {
+        match self {
+            Track::Vim => "vim",
+            Track::Pipes => "pipes",
+            Track::Git => "git",
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Track::Vim => "Vim motions",
+            Track::Pipes => "Pipes and redirection",
+            Track::Git => "Git",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.into_iter().find(|track| track.name() == name)
+    }
+
+    /// Whether its skills can be spotted in the shell history
+    fn shows_in_history(self) -> bool {
+        self != Track::Vim
+    }
+}
+
+/// One thing to learn
+pub struct Skill {
+    pub id: &'static str,
+    pub track: Track,
+    pub name: &'static str,
+    /// How it's done, for the prompt and after a wrong answer
+    pub tip: &'static str,
+    pub question: &'static str,
+    /// Vim: the accepted answers. Otherwise: text a command using the skill contains.
+    evidence: &'static [&'static str],
+}
+
+/// Every skill, easiest first within each track
+pub const SKILLS: &[Skill] = &[
+    Skill {
+        id: "vim-word-motions",
+        track: Track::Vim,
+        name: "word motions",
+        tip: "w jumps to the start of the next word, b back to the previous one and e to the end of the word",
+        question: "In normal mode, which key jumps to the start of the next word?",
+        evidence: &["w"],
+    },
+    Skill {
+        id: "vim-file-jumps",
+        track: Track::Vim,
+        name: "jumping to the top and bottom",
+        tip: "gg jumps to the first line and G to the last; 42G goes to line 42",
+        question: "Which keys jump to the first line of the file?",
+        evidence: &["gg", "1G", ":1"],
+    },
+    Skill {
+        id: "vim-delete-word",
+        track: Track::Vim,
+        name: "deleting with motions",
+        tip: "d followed by a motion deletes what it moves over: dw deletes a word, d$ to the end of the line",
+        question: "Which keys delete from the cursor to the start of the next word?",
+        evidence: &["dw"],
+    },
+    Skill {
+        id: "vim-change-inner-word",
+        track: Track::Vim,
+        name: "text objects",
+        tip: "ciw changes the whole word under the cursor, wherever the cursor is in it; ci\" changes inside quotes",
+        question: "Which keys replace the whole word under the cursor, wherever the cursor is in it?",
+        evidence: &["ciw", "caw"],
+    },
+    Skill {
+        id: "vim-dot-repeat",
+        track: Track::Vim,
+        name: "the dot command",
+        tip: ". repeats the last change, so a change made once can be applied anywhere with one key",
+        question: "Which key repeats the last change?",
+        evidence: &["."],
+    },
+    Skill {
+        id: "vim-search-word",
+        track: Track::Vim,
+        name: "searching for the word under the cursor",
+        tip: "* searches forward for the word under the cursor and # backward; n and N go to the next and previous match",
+        question: "Which key searches for the next occurrence of the word under the cursor?",
+        evidence: &["*"],
+    },
+    Skill {
+        id: "vim-substitute",
+        track: Track::Vim,
+        name: "substitution",
+        tip: ":%s/old/new/g replaces every old with new in the whole file; add c to confirm each one",
+        question: "What command replaces every foo with bar in the whole file?",
+        evidence: &[":%s/foo/bar/g", "%s/foo/bar/g"],
+    },
+    Skill {
+        id: "pipes-grep",
+        track: Track::Pipes,
+        name: "filtering output with grep",
+        tip: "piping into grep keeps only the matching lines, like ps aux | grep ssh",
+        question: "How would you show only the lines of `ps aux` that mention ssh?",
+        evidence: &["| grep", "| rg "],
+    },
+    Skill {
+        id: "pipes-redirect-stderr",
+        track: Track::Pipes,
+        name: "redirecting errors",
+        tip: "2>&1 sends errors where the output goes, so make > build.log 2>&1 captures both; 2>/dev/null hides them",
+        question: "How would you run make and send both its output and its errors into build.log?",
+        evidence: &["2>&1", "&>", "2>/dev/null", "2> /dev/null"],
+    },
+    Skill {
+        id: "pipes-count-unique",
+        track: Track::Pipes,
+        name: "counting with sort and uniq",
+        tip: "sort | uniq -c | sort -rn counts how often each line appears, most frequent first",
+        question: "How would you count how often each line appears in access.log, most frequent first?",
+        evidence: &["| uniq -c"],
+    },
+    Skill {
+        id: "pipes-tee",
+        track: Track::Pipes,
+        name: "tee",
+        tip: "piping into tee writes to a file and still shows the output, like cargo test | tee test.log",
+        question: "How would you save the output of `cargo test` to test.log while still seeing it?",
+        evidence: &["| tee "],
+    },
+    Skill {
+        id: "pipes-xargs",
+        track: Track::Pipes,
+        name: "xargs",
+        tip: "xargs turns lines of input into arguments, like find . -name '*.tmp' | xargs rm",
+        question: "How would you delete every file that `find . -name '*.tmp'` prints, in one line?",
+        evidence: &["| xargs "],
+    },
+    Skill {
+        id: "pipes-process-substitution",
+        track: Track::Pipes,
+        name: "process substitution",
+        tip: "<(command) passes a command's output as if it were a file, like diff <(sort a.txt) <(sort b.txt)",
+        question: "How would you diff the sorted contents of a.txt and b.txt without temporary files?",
+        evidence: &["<("],
+    },
+    Skill {
+        id: "git-log-oneline",
+        track: Track::Git,
+        name: "compact logs",
+        tip: "git log --oneline --graph shows one line per commit with the branches drawn next to them",
+        question: "How would you show the commit history with one commit per line?",
+        evidence: &["git log --oneline", "git log --graph", "git log --pretty=oneline"],
+    },
+    Skill {
+        id: "git-stash",
+        track: Track::Git,
+        name: "stashing",
+        tip: "git stash sets uncommitted changes aside and git stash pop brings them back",
+        question: "How would you set your uncommitted changes aside to switch branches?",
+        evidence: &["git stash"],
+    },
+    Skill {
+        id: "git-add-patch",
+        track: Track::Git,
+        name: "staging parts of a file",
+        tip: "git add -p walks through each change and asks whether to stage it",
+        question: "How would you stage only some of the changes in a file?",
+        evidence: &["git add -p", "git add --patch"],
+    },
+    Skill {
+        id: "git-amend",
+        track: Track::Git,
+        name: "amending commits",
+        tip: "git commit --amend folds staged changes into the last commit; --no-edit keeps its message",
+        question: "How would you add a forgotten, already staged file to the last commit?",
+        evidence: &["git commit --amend"],
+    },
+    Skill {
+        id: "git-rebase-interactive",
+        track: Track::Git,
+        name: "interactive rebase",
+        tip: "git rebase -i HEAD~3 lets you squash, reword, reorder or drop the last three commits",
+        question: "How would you start squashing your last three commits into one?",
+        evidence: &["git rebase -i", "git rebase --interactive"],
+    },
+    Skill {
+        id: "git-bisect",
+        track: Track::Git,
+        name: "bisecting",
+        tip: "git bisect start, then git bisect good/bad, binary-searches the commit that introduced a bug",
+        question: "Which git command finds the commit that introduced a bug by binary search?",
+        evidence: &["git bisect"],
+    },
+];
+
+impl Skill {
+    /// Whether a command or quiz answer uses the skill
+    pub fn is_shown_by(&self, text: &str) -> bool {
+        let text = text.trim().trim_matches('`').split_whitespace().collect::<Vec<_>>().join(" ");
+        match self.track {
+            Track::Vim => self.evidence.contains(&text.as_str()),
+            _ => {
+                // Padded so that evidence ending in a space also matches at the end
+                let text = format!("{} ", text);
+                self.evidence.iter().any(|evidence| text.contains(evidence))
+            }
+        }
+    }
+
+    /// One answer that would have been right
+    pub fn example(&self) -> &'static str {
+        self.evidence[0].trim()
+    }
+
+    fn is_learned(&self, state: &PetState) -> bool {
+        state.learned_skills.iter().any(|id| id == self.id)
+    }
+}
+
+/// The skills of a track, in order
+pub fn track(track: Track) -> impl Iterator<Item = &'static Skill> {
+    SKILLS.iter().filter(move |skill| skill.track == track)
+}
+
+/// The first unlearned skill of the chosen tracks
+pub fn next(state: &PetState) -> Option<&'static Skill> {
+    SKILLS.iter().find(|skill| state.learning_tracks.contains(&skill.track) && !skill.is_learned(state))
+}
+
+/// Marks a skill as learned; returns whether it wasn't already
+pub fn learn(state: &mut PetState, skill: &Skill) -> bool {
+    if skill.is_learned(state) {
+        return false;
+    }
+    state.learned_skills.push(skill.id.to_string());
+    true
+}
+
+/// Skills of the chosen tracks that succeeded commands show for the first
+/// time; they're marked as learned
+pub fn learn_from_history(state: &mut PetState, commands: &[HistoryEntry]) -> Vec<&'static Skill> {
+    let shown: Vec<&'static Skill> = SKILLS
+        .iter()
+        .filter(|skill| skill.track.shows_in_history() && state.learning_tracks.contains(&skill.track))
+        .filter(|skill| !skill.is_learned(state))
+        .filter(|skill| commands.iter().any(|entry| !entry.failed() && skill.is_shown_by(&entry.command)))
+        .collect();
+    for skill in &shown {
+        learn(state, skill);
+    }
+    shown
+}
+
+/// The chosen tracks with what's learned in each, for `/learn`
+pub fn tree(state: &PetState) -> String {
+    let mut text = String::from("Learning goals:");
+    for track in state.learning_tracks.iter().copied() {
+        let learned = self::track(track).filter(|skill| skill.is_learned(state)).count();
+        text.push_str(&format!("\n{} ({}/{})", track.title(), learned, self::track(track).count()));
+        let next = next_in(state, track).map(|skill| skill.id);
+        for skill in self::track(track) {
+            let mark = if skill.is_learned(state) { "✓" } else { " " };
+            let arrow = if Some(skill.id) == next { "  ← next" } else { "" };
+            text.push_str(&format!("\n  [{}] {}{}", mark, skill.name, arrow));
+        }
+    }
+    text
+}
+
+fn next_in(state: &PetState, track: Track) -> Option<&'static Skill> {
+    self::track(track).find(|skill| !skill.is_learned(state))
+}
+
+/// What the model should steer tips toward
+pub fn context(state: &PetState) -> Option<String> {
+    let skill = next(state)?;
+    Some(format!(
+        "The user is learning {}. The next skill for them to learn is {} ({}). When it fits the conversation, lean your tips and examples toward it.",
+        skill.track.title(),
+        skill.name,
+        skill.tip
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(id: &str) -> &'static Skill {
+        SKILLS.iter().find(|skill| skill.id == id).unwrap()
+    }
+
+    #[test]
+    fn skills_are_recognized_in_commands_and_answers() {
+        assert!(find("pipes-xargs").is_shown_by("find . -name '*.tmp' |  xargs rm"));
+        assert!(find("pipes-tee").is_shown_by("`cargo test | tee`"));
+        assert!(!find("pipes-tee").is_shown_by("cargo test | teeth"));
+        assert!(find("vim-change-inner-word").is_shown_by(" `ciw` "));
+        assert!(!find("vim-dot-repeat").is_shown_by("press ."));
+    }
+
+    #[test]
+    fn history_teaches_the_chosen_tracks_only() {
+        let mut state = PetState { learning_tracks: vec![Track::Git, Track::Vim], ..PetState::default() };
+        let mut failed = HistoryEntry::new("git bisect start", None);
+        failed.exit_code = Some(1);
+        let commands = [HistoryEntry::new("git stash pop", None), HistoryEntry::new("ps aux | grep ssh", None), failed];
+        let learned: Vec<&str> = learn_from_history(&mut state, &commands).iter().map(|skill| skill.id).collect();
+        assert_eq!(learned, ["git-stash"]);
+        assert_eq!(next(&state).map(|skill| skill.id), Some("vim-word-motions"));
+        assert!(learn_from_history(&mut state, &commands).is_empty());
+    }
+}
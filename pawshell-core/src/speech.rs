@@ -0,0 +1,218 @@
+//! Text-to-speech for the pet's replies
+//!
+//! With `/speak on` (or `speak_replies = true`) every chat reply is read out
+//! loud. The `System` engine pipes the text into `tts_command` if set, or else
+//! the platform's own voice: `say` on macOS, espeak-ng or espeak on Linux and
+//! SAPI through PowerShell on Windows. The `OpenAI` engine asks OpenAI's speech
+//! API for a WAV file and plays it with the platform's audio player.
+//!
+//! Code blocks, markdown and emoji are left out of what gets spoken.
+
+use std::process::Stdio;
+use std::time::Duration;
+
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::config::{Config, TtsEngine};
+use crate::config_path;
+#[cfg(not(any(target_os = "macos", windows)))]
+use crate::environment::is_installed;
+use crate::llm::OpenAIOptions;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+/// Longer replies are cut off here; OpenAI takes at most 4096 characters
+const MAX_CHARS: usize = 4_000;
+
+/// How the pet talks, as configured
+#[derive(Clone)]
+pub struct Voice {
+    engine: TtsEngine,
+    command: Option<String>,
+    openai_voice: String,
+    openai: OpenAIOptions,
+}
+
+impl Voice {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            engine: config.tts_engine,
+            command: config.tts_command.clone(),
+            openai_voice: config.tts_voice.clone(),
+            openai: config.openai_options(),
+        }
+    }
+
+    /// Reads `text` out loud, returning once it has been spoken
+    ///
+    /// Dropping the future stops the speech.
+    pub async fn speak(&self, text: &str) -> Result<(), String> {
+        let text = speakable(text);
+        if text.is_empty() {
+            return Ok(());
+        }
+        match self.engine {
+            TtsEngine::System => {
+                let mut command = match &self.command {
+                    Some(command) => shell(command),
+                    None => system_engine().ok_or("no speech engine found; install espeak-ng or set tts_command")?,
+                };
+                run(&mut command, Some(&text)).await
+            }
+            TtsEngine::OpenAI => {
+                let audio = self.openai_speech(&text).await?;
+                let path = config_path::get_cache_dir().join("speech.wav");
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+                }
+                std::fs::write(&path, audio).map_err(|e| format!("couldn't write {}: {}", path.display(), e))?;
+                let mut player = audio_player(&path.to_string_lossy()).ok_or("no audio player found; install pulseaudio-utils or alsa-utils")?;
+                run(&mut player, None).await
+            }
+        }
+    }
+
+    async fn openai_speech(&self, text: &str) -> Result<Vec<u8>, String> {
+        let api_key = std::env::var("OPENAI_API_KEY").map_err(|_| "OPENAI_API_KEY is not set")?;
+        let client = reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build().map_err(|e| e.to_string())?;
+        let response = self
+            .openai
+            .authorize(client.post(self.openai.url("audio/speech")), &api_key)
+            .json(&serde_json::json!({
+                "model": "tts-1",
+                "voice": self.openai_voice,
+                "input": text,
+                "response_format": "wav",
+            }))
+            .send()
+            .await
+            .map_err(|e| format!("couldn't reach OpenAI: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("OpenAI answered {}", response.status()));
+        }
+        Ok(response.bytes().await.map_err(|e| e.to_string())?.to_vec())
+    }
+}
+
+/// The part of a reply worth saying out loud: no code blocks, markdown or
+/// emoji, and the whitespace collapsed
+pub fn speakable(text: &str) -> String {
+    let mut prose = String::new();
+    let mut in_code_block = false;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if !in_code_block {
+            prose.push_str(line);
+            prose.push('\n');
+        }
+    }
+    let cleaned: String = prose
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace() || c.is_ascii_punctuation())
+        .filter(|c| !matches!(c, '*' | '_' | '`' | '#' | '>' | '|'))
+        .collect();
+    cleaned.split_whitespace().collect::<Vec<_>>().join(" ").chars().take(MAX_CHARS).collect()
+}
+
+/// The platform's own text-to-speech, reading from stdin
+#[cfg(target_os = "macos")]
+fn system_engine() -> Option<Command> {
+    Some(Command::new("say"))
+}
+
+#[cfg(windows)]
+fn system_engine() -> Option<Command> {
+    Some(powershell(
+        "Add-Type -AssemblyName System.Speech; (New-Object System.Speech.Synthesis.SpeechSynthesizer).Speak([Console]::In.ReadToEnd())",
+    ))
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn system_engine() -> Option<Command> {
+    ["espeak-ng", "espeak"].into_iter().find(|engine| is_installed(engine)).map(|engine| {
+        let mut command = Command::new(engine);
+        command.arg("--stdin");
+        command
+    })
+}
+
+/// Something that plays a WAV file
+#[cfg(target_os = "macos")]
+fn audio_player(path: &str) -> Option<Command> {
+    let mut command = Command::new("afplay");
+    command.arg(path);
+    Some(command)
+}
+
+#[cfg(windows)]
+fn audio_player(path: &str) -> Option<Command> {
+    Some(powershell(&format!("(New-Object Media.SoundPlayer '{}').PlaySync()", path.replace('\'', "''"))))
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn audio_player(path: &str) -> Option<Command> {
+    ["paplay", "pw-play", "aplay"].into_iter().find(|player| is_installed(player)).map(|player| {
+        let mut command = Command::new(player);
+        command.arg(path);
+        command
+    })
+}
+
+#[cfg(windows)]
+fn powershell(script: &str) -> Command {
+    let mut command = Command::new("powershell");
+    command.args(["-NoProfile", "-Command", script]);
+    command
+}
+
+/// A user-configured command line, run by the shell so it can be a pipeline
+fn shell(command_line: &str) -> Command {
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = Command::new("cmd");
+        command.arg("/C");
+        command
+    };
+    #[cfg(not(windows))]
+    let mut command = {
+        let mut command = Command::new("sh");
+        command.arg("-c");
+        command
+    };
+    command.arg(command_line);
+    command
+}
+
+/// Runs a speech command to completion, feeding it `input` if given; it's
+/// killed if the future is dropped
+async fn run(command: &mut Command, input: Option<&str>) -> Result<(), String> {
+    let mut child = command
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("couldn't start the speech engine: {}", e))?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input.as_bytes()).await.map_err(|e| e.to_string())?;
+    }
+    let status = child.wait().await.map_err(|e| e.to_string())?;
+    if !status.success() {
+        return Err(format!("the speech engine exited with {}", status));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_markdown_and_emoji_are_not_spoken() {
+        let reply = "*purrs* Try **this** 😊:\n```bash\nfind . -name '*.rs'\n```\nor just `ls -la`!";
+        assert_eq!(speakable(reply), "purrs Try this : or just ls -la!");
+    }
+}
@@ -0,0 +1,877 @@
+//! Pet state persistence for PetCLI
+//!
+//! The pet's state can be stored in four ways:
+//! - confy (default): the whole state, chat history included, is one TOML file
+//!   that is rewritten on every save
+//! - SQLite: messages, sessions and mood samples live in their own indexed tables
+//!   in `petcli.db` in the data directory. Only the most recent messages are loaded at startup, and a
+//!   save only inserts what changed since the last one.
+//! - synced: per-machine append-only files that tolerate being synced between
+//!   machines, see [`crate::sync_store`]
+//! - shared: one state file that several users on one machine share, with
+//!   locking, see [`crate::shared_store`]
+//!
+//! The first time the SQLite or synced store is opened it imports the existing confy state.
+//! The confy file is left in place, so switching back loses nothing from before
+//! the migration.
+//!
+//! The state file is replaced atomically on save, and the SQLite database is
+//! protected by its journal, so a crash mid-save can't leave a half-written pet.
+//!
+//! With `encrypt_history`, either store is wrapped in an [`EncryptedStore`] that
+//! encrypts chat messages on their way to disk.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::clock;
+use crate::backup;
+use crate::chat_sessions::ChatSession;
+use crate::config::{Config, StorageBackend};
+use crate::crypto::Cipher;
+use crate::error::StorageError;
+use crate::config_path;
+use crate::pet::{ChatMessage, PetState, Revision};
+use crate::sync_store::SyncStore;
+use crate::shared_store::SharedStore;
+
+/// How many of the most recent exchanges are loaded from SQLite at startup
+const LOADED_MESSAGES: usize = 100;
+/// A save's temporary file this old belongs to a save that crashed
+const STALE_TEMP_AGE: Duration = Duration::from_secs(60);
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS pet (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS sessions (
+        id INTEGER PRIMARY KEY,
+        started_at TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS messages (
+        id INTEGER PRIMARY KEY,
+        session_id INTEGER REFERENCES sessions(id),
+        user_message TEXT NOT NULL,
+        pet_response TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        provider TEXT,
+        model TEXT,
+        prompt_tokens INTEGER,
+        completion_tokens INTEGER,
+        latency_ms INTEGER,
+        revision TEXT,
+        thread TEXT,
+        first_token_ms INTEGER,
+        generation_ms INTEGER,
+        chat_session INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS messages_created_at ON messages(created_at);
+    CREATE INDEX IF NOT EXISTS messages_session_id ON messages(session_id);
+    CREATE TABLE IF NOT EXISTS mood_samples (
+        recorded_at TEXT NOT NULL,
+        mood REAL NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS mood_samples_recorded_at ON mood_samples(recorded_at);
+    -- Created by earlier versions but never used
+    DROP TABLE IF EXISTS memories;
+";
+
+/// Columns added to `messages` after its first release, created on open if missing
+const MESSAGE_METADATA_COLUMNS: [(&str, &str); 10] = [
+    ("provider", "TEXT"),
+    ("model", "TEXT"),
+    ("prompt_tokens", "INTEGER"),
+    ("completion_tokens", "INTEGER"),
+    ("latency_ms", "INTEGER"),
+    ("revision", "TEXT"),
+    ("thread", "TEXT"),
+    ("first_token_ms", "INTEGER"),
+    ("generation_ms", "INTEGER"),
+    ("chat_session", "INTEGER"),
+];
+
+/// Loads and saves the pet's state
+pub trait StateStore {
+    fn load(&mut self) -> Result<PetState, StorageError>;
+    /// Like `load`, but with the complete chat history even if `load` trims it
+    fn load_full(&mut self) -> Result<PetState, StorageError> {
+        self.load()
+    }
+    fn save(&mut self, state: &PetState) -> Result<(), StorageError>;
+    /// Whether another instance (e.g. a popup) saved since our last load or save
+    fn changed_externally(&mut self) -> bool;
+    /// Copies the state on disk to `dest`, returning false if nothing was saved yet
+    fn backup(&self, dest: &Path) -> Result<bool, StorageError>;
+    /// File extension used for this store's backups
+    fn backup_extension(&self) -> &'static str;
+    /// The cipher chat messages are encrypted with, if encryption is enabled
+    fn cipher(&self) -> Option<&Cipher> {
+        None
+    }
+    /// Something the user should know about the last load, like a recovery
+    fn take_notice(&mut self) -> Option<String> {
+        None
+    }
+}
+
+/// Opens the configured store, falling back to something usable on failure
+///
+/// If the SQLite database can't be opened the confy file is used instead. If
+/// encrypted history can't be unlocked nothing is saved this session, so the
+/// encrypted data isn't overwritten. The returned message explains a fallback.
+pub fn open(config: &Config) -> (Box<dyn StateStore>, Option<String>) {
+    let (store, warning): (Box<dyn StateStore>, _) = match config.storage_backend {
+        StorageBackend::Confy => (Box::new(ConfyStore::new()), None),
+        StorageBackend::Sqlite => match SqliteStore::open(&database_path()) {
+            Ok(store) => (Box::new(store), None),
+            Err(e) => (
+                Box::new(ConfyStore::new()),
+                Some(format!("Couldn't open the SQLite store ({}); using the state file instead.", e)),
+            ),
+        },
+        StorageBackend::Synced => match SyncStore::open(config) {
+            Ok(store) => (Box::new(store), None),
+            Err(e) => (
+                Box::new(ConfyStore::new()),
+                Some(format!("Couldn't open the sync directory ({}); using the state file instead.", e)),
+            ),
+        },
+        StorageBackend::Shared => match SharedStore::open(config) {
+            Ok(store) => (Box::new(store), None),
+            Err(e) => (
+                Box::new(ConfyStore::new()),
+                Some(format!("Couldn't open the shared state ({}); using your own state file instead.", e)),
+            ),
+        },
+    };
+    if !config.encrypt_history {
+        return (store, warning);
+    }
+    let cipher = Cipher::unlock().map_err(StorageError::from);
+    match cipher.and_then(|cipher| EncryptedStore::new(store, cipher)) {
+        Ok(store) => (Box::new(store), warning),
+        Err(e) => (
+            Box::new(MemoryStore),
+            Some(format!("Couldn't unlock the encrypted chat history ({}). Nothing will be saved this session.", e)),
+        ),
+    }
+}
+
+pub fn database_path() -> PathBuf {
+    config_path::get_data_dir().join("petcli.db")
+}
+
+/// Encrypts chat messages before they reach the wrapped store
+pub struct EncryptedStore {
+    inner: Box<dyn StateStore>,
+    cipher: Cipher,
+    /// Ciphertext of messages already on disk, so unchanged messages aren't
+    /// re-encrypted (with a new nonce) and rewritten on every save
+    ciphertexts: HashMap<String, String>,
+}
+
+impl EncryptedStore {
+    /// Wraps a store, checking that its existing history can be decrypted
+    fn new(inner: Box<dyn StateStore>, cipher: Cipher) -> Result<Self, StorageError> {
+        let mut store = Self { inner, cipher, ciphertexts: HashMap::new() };
+        store.load()?;
+        Ok(store)
+    }
+
+    fn encrypt(&mut self, plaintext: &str) -> String {
+        if let Some(ciphertext) = self.ciphertexts.get(plaintext) {
+            return ciphertext.clone();
+        }
+        let ciphertext = self.cipher.encrypt(plaintext);
+        self.ciphertexts.insert(plaintext.to_string(), ciphertext.clone());
+        ciphertext
+    }
+
+    fn decrypt_history(&mut self, mut state: PetState) -> Result<PetState, StorageError> {
+        for message in state.chat_history.iter_mut() {
+            message.user_message = self.decrypt(&message.user_message)?;
+            message.response = self.decrypt(&message.response)?;
+        }
+        // Session titles give away what was talked about, too, and so does a draft
+        for session in state.chat_sessions.iter_mut() {
+            if let Some(title) = &session.title {
+                session.title = Some(self.decrypt(title)?);
+            }
+        }
+        if let Some(draft) = &state.input_draft {
+            state.input_draft = Some(self.decrypt(draft)?);
+        }
+        Ok(state)
+    }
+
+    fn decrypt(&mut self, stored: &str) -> Result<String, StorageError> {
+        let plaintext = self.cipher.decrypt(stored)?;
+        // Plaintext from before encryption was enabled gets encrypted on the next save
+        if stored != plaintext {
+            self.ciphertexts.insert(plaintext.clone(), stored.to_string());
+        }
+        Ok(plaintext)
+    }
+}
+
+impl StateStore for EncryptedStore {
+    fn load(&mut self) -> Result<PetState, StorageError> {
+        let state = self.inner.load()?;
+        self.ciphertexts.clear();
+        self.decrypt_history(state)
+    }
+
+    fn load_full(&mut self) -> Result<PetState, StorageError> {
+        let state = self.inner.load_full()?;
+        self.decrypt_history(state)
+    }
+
+    fn save(&mut self, state: &PetState) -> Result<(), StorageError> {
+        let chat_history: Vec<ChatMessage> = state
+            .chat_history
+            .iter()
+            .map(|message| ChatMessage {
+                user_message: self.encrypt(&message.user_message),
+                response: self.encrypt(&message.response),
+                ..message.clone()
+            })
+            .collect();
+        let chat_sessions: Vec<ChatSession> = state
+            .chat_sessions
+            .iter()
+            .map(|session| ChatSession { title: session.title.as_deref().map(|title| self.encrypt(title)), ..session.clone() })
+            .collect();
+        let input_draft = state.input_draft.as_deref().map(|draft| self.encrypt(draft));
+        self.ciphertexts.retain(|plaintext, _| {
+            state.chat_history.iter().any(|message| &message.user_message == plaintext || &message.response == plaintext)
+                || state.chat_sessions.iter().any(|session| session.title.as_ref() == Some(plaintext))
+                || state.input_draft.as_ref() == Some(plaintext)
+        });
+
+        let encrypted = PetState { chat_history, chat_sessions, input_draft, ..state.clone() };
+        self.inner.save(&encrypted)
+    }
+
+    fn changed_externally(&mut self) -> bool {
+        self.inner.changed_externally()
+    }
+
+    fn backup(&self, dest: &Path) -> Result<bool, StorageError> {
+        self.inner.backup(dest)
+    }
+
+    fn backup_extension(&self) -> &'static str {
+        self.inner.backup_extension()
+    }
+
+    fn cipher(&self) -> Option<&Cipher> {
+        Some(&self.cipher)
+    }
+
+    fn take_notice(&mut self) -> Option<String> {
+        self.inner.take_notice()
+    }
+}
+
+/// Keeps nothing; used when the real store must not be touched
+pub struct MemoryStore;
+
+impl StateStore for MemoryStore {
+    fn load(&mut self) -> Result<PetState, StorageError> {
+        Ok(PetState::default())
+    }
+
+    fn save(&mut self, _state: &PetState) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    fn changed_externally(&mut self) -> bool {
+        false
+    }
+
+    fn backup(&self, _dest: &Path) -> Result<bool, StorageError> {
+        Ok(false)
+    }
+
+    fn backup_extension(&self) -> &'static str {
+        "toml"
+    }
+}
+
+/// The whole state in a single TOML file (in the format confy used to write)
+///
+/// Saves go through [`write_atomic`]. If the file still turns out to be damaged
+/// at load, it is moved aside and the state is recovered from the newest
+/// readable backup. Temporary files left by a save that crashed are deleted
+/// rather than recovered from, since they may be cut short.
+pub struct ConfyStore {
+    /// Modification time of the state file as of our last load or save
+    mtime: Option<SystemTime>,
+    /// Explains a recovery from a damaged state file during the last load
+    notice: Option<String>,
+}
+
+impl ConfyStore {
+    fn new() -> Self {
+        Self { mtime: confy_file_mtime(), notice: None }
+    }
+
+}
+
+/// Moves a damaged state file aside and restores the newest readable backup,
+/// returning the state and a notice explaining what happened
+fn recover(path: &Path, error: &dyn std::fmt::Display, backups: Vec<PathBuf>) -> Result<(PetState, String), StorageError> {
+    let damaged = path.with_extension(format!("toml.damaged-{}", clock::now().format("%Y%m%d-%H%M%S")));
+    std::fs::rename(path, &damaged).map_err(|e| {
+        StorageError::Damaged(format!("{} is damaged ({}) and couldn't be moved aside: {}", path.display(), error, e))
+    })?;
+
+    for candidate in backups.into_iter().filter(|backup| backup.extension().is_some_and(|ext| ext == "toml")) {
+        if let Ok(state) = read_state_file(&candidate) {
+            std::fs::copy(&candidate, path).map_err(StorageError::file(path))?;
+            let notice = format!(
+                "My state file was damaged, so I recovered it from {} (the damaged file is kept as {}).",
+                candidate.display(),
+                damaged.display()
+            );
+            return Ok((state, notice));
+        }
+    }
+    Err(StorageError::Damaged(format!(
+        "{} was damaged ({}) and no readable backup was found; it is kept as {}",
+        path.display(),
+        error,
+        damaged.display()
+    )))
+}
+
+impl StateStore for ConfyStore {
+    fn load(&mut self) -> Result<PetState, StorageError> {
+        let path = config_path::state_file_path();
+        remove_stale_temps(&path);
+        let state = if path.exists() {
+            match read_state_file(&path) {
+                Ok(state) => state,
+                Err(e) => {
+                    let (state, notice) = recover(&path, &e, backup::list())?;
+                    self.notice = Some(notice);
+                    state
+                }
+            }
+        } else {
+            PetState::default()
+        };
+        self.mtime = confy_file_mtime();
+        Ok(state)
+    }
+
+    fn save(&mut self, state: &PetState) -> Result<(), StorageError> {
+        let path = config_path::state_file_path();
+        config_path::ensure_data_dir()?;
+        write_atomic(&path, toml::to_string_pretty(state)?.as_bytes()).map_err(StorageError::file(&path))?;
+        self.mtime = confy_file_mtime();
+        Ok(())
+    }
+
+    fn changed_externally(&mut self) -> bool {
+        let mtime = confy_file_mtime();
+        mtime.is_some() && mtime != self.mtime
+    }
+
+    fn backup(&self, dest: &Path) -> Result<bool, StorageError> {
+        let path = config_path::state_file_path();
+        if !path.exists() {
+            return Ok(false);
+        }
+        std::fs::copy(&path, dest).map_err(StorageError::file(&path))?;
+        Ok(true)
+    }
+
+    fn backup_extension(&self) -> &'static str {
+        "toml"
+    }
+
+    fn take_notice(&mut self) -> Option<String> {
+        self.notice.take()
+    }
+}
+
+fn confy_file_mtime() -> Option<SystemTime> {
+    let path = config_path::state_file_path();
+    std::fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Reads a TOML state file, treating an empty (truncated) file as damaged
+pub fn read_state_file(path: &Path) -> Result<PetState, StorageError> {
+    let contents = std::fs::read_to_string(path).map_err(StorageError::file(path))?;
+    if contents.trim().is_empty() {
+        return Err(StorageError::Format("the file is empty".to_string()));
+    }
+    let mut state: PetState = toml::from_str(&contents)?;
+    state.adopt_legacy_timestamps();
+    Ok(state)
+}
+
+/// Writes a file so that a crash leaves either the old or the new contents
+///
+/// The contents go to a temporary file next to `path`, which is flushed to disk
+/// and then renamed over `path`. Each write has its own temporary file, so
+/// processes saving at the same time (a popup, the daemon) can't rename each
+/// other's half-written files into place.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let temp = temp_path(path);
+    let written = std::fs::OpenOptions::new().write(true).create_new(true).open(&temp).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()?;
+        drop(file);
+        std::fs::rename(&temp, path)
+    });
+    if written.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    written?;
+    // Make the rename itself durable
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        // A bare file name's parent is "", meaning the current directory
+        let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+        std::fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// A temporary file next to `path` that no other write uses
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}-{:08x}.tmp", std::process::id(), rand::random::<u32>()));
+    path.with_file_name(name)
+}
+
+/// Deletes temporary files of saves to `path` that crashed before the rename
+///
+/// Only files older than `STALE_TEMP_AGE` go, so a save in progress in another
+/// process keeps its file.
+fn remove_stale_temps(path: &Path) {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return;
+    };
+    let prefix = format!("{}.", name.to_string_lossy());
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        let stale = entry
+            .metadata()
+            .and_then(|meta| meta.modified())
+            .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age >= STALE_TEMP_AGE));
+        if file_name.starts_with(&prefix) && file_name.ends_with(".tmp") && stale {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Messages, sessions and mood samples in a SQLite database
+pub struct SqliteStore {
+    conn: Connection,
+    session_id: i64,
+    /// The messages currently held in `chat_history`, oldest first, used to
+    /// work out what changed on save
+    synced: Vec<ChatMessage>,
+    /// Row ids of `synced`
+    synced_ids: Vec<i64>,
+    last_mood: Option<f32>,
+    /// `PRAGMA data_version` as of our last load or save
+    data_version: i64,
+}
+
+impl SqliteStore {
+    pub fn open(path: &Path) -> Result<Self, StorageError> {
+        config_path::ensure_data_dir()?;
+        Self::with_connection(Connection::open(path)?, &config_path::state_file_path())
+    }
+
+    /// Sets up the database behind `conn`, importing the state file at
+    /// `state_file` if the database is new
+    fn with_connection(conn: Connection, state_file: &Path) -> Result<Self, StorageError> {
+        // A popup instance may be writing at the same time
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.execute_batch(SCHEMA)?;
+        add_missing_columns(&conn)?;
+
+        // The session comes first: imported messages reference it
+        conn.execute("INSERT INTO sessions (started_at) VALUES (?1)", params![clock::now().to_rfc3339()])?;
+        let session_id = conn.last_insert_rowid();
+        let mut store = Self { conn, session_id, synced: Vec::new(), synced_ids: Vec::new(), last_mood: None, data_version: 0 };
+        if store.pet_value("state")?.is_none() {
+            store.import_confy_state(state_file)?;
+        }
+        store.data_version = store.data_version()?;
+        Ok(store)
+    }
+
+    /// Copies the existing confy state into a fresh database
+    fn import_confy_state(&mut self, confy_path: &Path) -> Result<(), StorageError> {
+        let state = if confy_path.exists() { read_state_file(confy_path)? } else { PetState::default() };
+        self.save(&state)
+    }
+
+    fn pet_value(&self, key: &str) -> rusqlite::Result<Option<String>> {
+        self.conn
+            .query_row("SELECT value FROM pet WHERE key = ?1", params![key], |row| row.get(0))
+            .optional()
+    }
+
+    fn data_version(&self) -> rusqlite::Result<i64> {
+        self.conn.query_row("PRAGMA data_version", [], |row| row.get(0))
+    }
+
+    /// Loads the state with the `limit` most recent messages
+    fn load_messages(&mut self, limit: i64) -> Result<PetState, StorageError> {
+        let mut state: serde_json::Value = match self.pet_value("state")? {
+            Some(json) => serde_json::from_str(&json)?,
+            None => serde_json::to_value(PetState::default())?,
+        };
+
+        let mut statement = self.conn.prepare(
+            "SELECT id, user_message, pet_response, created_at, provider, model, prompt_tokens, completion_tokens,
+                latency_ms, revision, thread, first_token_ms, generation_ms, chat_session
+             FROM messages ORDER BY id DESC LIMIT ?1",
+        )?;
+        let mut rows = statement
+            .query_map(params![limit], |row| {
+                let revision: Option<String> = row.get(9)?;
+                let message = ChatMessage {
+                    user_message: row.get(1)?,
+                    response: row.get(2)?,
+                    timestamp: row.get::<_, String>(3)?.parse().ok(),
+                    provider: row.get(4)?,
+                    model: row.get(5)?,
+                    prompt_tokens: row.get(6)?,
+                    completion_tokens: row.get(7)?,
+                    latency_ms: row.get(8)?,
+                    revision: revision
+                        .and_then(|revision| serde_json::from_value(serde_json::Value::String(revision)).ok())
+                        .unwrap_or_default(),
+                    thread: row.get(10)?,
+                    first_token_ms: row.get(11)?,
+                    generation_ms: row.get(12)?,
+                    session: row.get(13)?,
+                };
+                Ok((row.get::<_, i64>(0)?, message))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows.reverse();
+
+        let (ids, messages): (Vec<i64>, Vec<ChatMessage>) = rows.into_iter().unzip();
+        state["chat_history"] = serde_json::to_value(&messages)?;
+        let state: PetState = serde_json::from_value(state)?;
+
+        self.synced = messages;
+        self.synced_ids = ids;
+        self.last_mood = Some(state.mood);
+        self.data_version = self.data_version()?;
+        Ok(state)
+    }
+}
+
+impl StateStore for SqliteStore {
+    fn load(&mut self) -> Result<PetState, StorageError> {
+        self.load_messages(LOADED_MESSAGES as i64)
+    }
+
+    fn load_full(&mut self) -> Result<PetState, StorageError> {
+        // A negative limit means no limit in SQLite
+        self.load_messages(-1)
+    }
+
+    fn save(&mut self, state: &PetState) -> Result<(), StorageError> {
+        let mut scalars = serde_json::to_value(state)?;
+        if let Some(fields) = scalars.as_object_mut() {
+            fields.remove("chat_history");
+        }
+
+        let history = &state.chat_history;
+        let dropped = dropped_prefix(&self.synced, history);
+        let kept = self.synced.len() - dropped;
+
+        let now = clock::now();
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "INSERT INTO pet (key, value) VALUES ('state', ?1) ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![scalars.to_string()],
+        )?;
+        if dropped > 0 {
+            // Everything up to the last dropped message goes, including older
+            // messages that were never loaded
+            let last_dropped = self.synced_ids[dropped - 1];
+            tx.execute("DELETE FROM messages WHERE id <= ?1", params![last_dropped])?;
+        }
+        let mut inserted = Vec::new();
+        for message in history.iter().skip(kept) {
+            let created_at = message.timestamp.unwrap_or(now);
+            let revision = match message.revision {
+                Revision::Original => None,
+                revision => serde_json::to_value(revision)?.as_str().map(str::to_string),
+            };
+            tx.execute(
+                "INSERT INTO messages (session_id, user_message, pet_response, created_at, provider, model,
+                    prompt_tokens, completion_tokens, latency_ms, revision, thread, first_token_ms, generation_ms, chat_session)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                params![
+                    self.session_id,
+                    message.user_message,
+                    message.response,
+                    created_at.to_rfc3339(),
+                    message.provider,
+                    message.model,
+                    message.prompt_tokens,
+                    message.completion_tokens,
+                    message.latency_ms,
+                    revision,
+                    message.thread,
+                    message.first_token_ms,
+                    message.generation_ms,
+                    message.session,
+                ],
+            )?;
+            inserted.push((tx.last_insert_rowid(), message.clone()));
+        }
+        if self.last_mood != Some(state.mood) {
+            tx.execute("INSERT INTO mood_samples (recorded_at, mood) VALUES (?1, ?2)", params![now.to_rfc3339(), state.mood])?;
+        }
+        tx.commit()?;
+
+        self.synced.drain(..dropped);
+        self.synced_ids.drain(..dropped);
+        for (id, message) in inserted {
+            self.synced_ids.push(id);
+            self.synced.push(message);
+        }
+        self.last_mood = Some(state.mood);
+        self.data_version = self.data_version()?;
+        Ok(())
+    }
+
+    fn changed_externally(&mut self) -> bool {
+        // data_version only changes when another connection commits
+        self.data_version().is_ok_and(|version| version != self.data_version)
+    }
+
+    fn backup(&self, dest: &Path) -> Result<bool, StorageError> {
+        // Unlike a file copy, this produces a consistent snapshot even mid-write
+        self.conn.execute("VACUUM INTO ?1", params![dest.to_string_lossy()])?;
+        Ok(true)
+    }
+
+    fn backup_extension(&self) -> &'static str {
+        "db"
+    }
+}
+
+/// How many of the messages a store last synced are gone from `history`
+///
+/// Messages can only be appended, trimmed from the front or cleared, so the
+/// synced messages that are left start `history`; the ones before them were
+/// dropped, and everything after them in `history` is new.
+pub(crate) fn dropped_prefix(synced: &[ChatMessage], history: &[ChatMessage]) -> usize {
+    (0..=synced.len())
+        .find(|&start| {
+            let kept = &synced[start..];
+            kept.len() <= history.len()
+                && kept.iter().zip(history).all(|(synced, message)| {
+                    synced.user_message == message.user_message
+                        && synced.response == message.response
+                        && synced.revision == message.revision
+                })
+        })
+        .unwrap_or(synced.len())
+}
+
+/// Brings a `messages` table created by an older version up to date
+fn add_missing_columns(conn: &Connection) -> rusqlite::Result<()> {
+    let existing = conn
+        .prepare("SELECT name FROM pragma_table_info('messages')")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (name, kind) in MESSAGE_METADATA_COLUMNS {
+        if !existing.iter().any(|column| column == name) {
+            conn.execute(&format!("ALTER TABLE messages ADD COLUMN {} {}", name, kind), [])?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::OpenFlags;
+
+    fn message(text: &str) -> ChatMessage {
+        ChatMessage::new(text.to_string(), format!("re: {}", text))
+    }
+
+    /// Two connections to one in-memory database, like the TUI and a popup
+    fn shared_memory(name: &str) -> (Connection, Connection) {
+        let uri = format!("file:{}?mode=memory&cache=shared", name);
+        let open = || Connection::open_with_flags(&uri, OpenFlags::default() | OpenFlags::SQLITE_OPEN_URI).unwrap();
+        (open(), open())
+    }
+
+    fn texts(state: &PetState) -> Vec<&str> {
+        state.chat_history.iter().map(|message| message.user_message.as_str()).collect()
+    }
+
+    #[test]
+    fn sqlite_store_imports_the_state_file_and_saves_only_changes() {
+        let dir = std::env::temp_dir().join(format!("pawshell-sqlite-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state_file = dir.join("state.toml");
+        let legacy = PetState { name: "Biscuit".to_string(), chat_history: vec![message("one"), message("two")], ..PetState::default() };
+        std::fs::write(&state_file, toml::to_string(&legacy).unwrap()).unwrap();
+
+        let (conn, _keep_alive) = shared_memory("import");
+        let mut store = SqliteStore::with_connection(conn, &state_file).unwrap();
+        let mut state = store.load().unwrap();
+        assert_eq!(state.name, "Biscuit");
+        assert_eq!(texts(&state), ["one", "two"]);
+        let table_count = |name: &str| {
+            store.conn.query_row("SELECT count(*) FROM sqlite_master WHERE name = ?1", params![name], |row| row.get::<_, i64>(0)).unwrap()
+        };
+        assert_eq!(table_count("memories"), 0);
+
+        // Dropping the oldest message and adding one deletes and inserts a row each
+        state.chat_history.remove(0);
+        state.chat_history.push(message("three"));
+        store.save(&state).unwrap();
+        let ids: Vec<i64> = store.conn.prepare("SELECT id FROM messages").unwrap().query_map([], |row| row.get(0)).unwrap().map(Result::unwrap).collect();
+        assert_eq!(ids, [2, 3]);
+        assert_eq!(texts(&store.load_full().unwrap()), ["two", "three"]);
+
+        // A second open finds the database populated and doesn't import again
+        std::fs::remove_file(&state_file).unwrap();
+        let (conn, _) = shared_memory("import");
+        let mut reopened = SqliteStore::with_connection(conn, &state_file).unwrap();
+        assert_eq!(texts(&reopened.load().unwrap()), ["two", "three"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn the_dropped_prefix_is_found_after_trims_appends_and_clears() {
+        let messages: Vec<ChatMessage> = ["a", "b", "c", "d"].into_iter().map(message).collect();
+        assert_eq!(dropped_prefix(&messages[..3], &messages[..3]), 0);
+        assert_eq!(dropped_prefix(&messages[..3], &messages), 0);
+        // Trimmed from the front, with a new message at the end
+        assert_eq!(dropped_prefix(&messages[..3], &messages[2..]), 2);
+        assert_eq!(dropped_prefix(&messages[..3], &[]), 3);
+        assert_eq!(dropped_prefix(&messages[..3], &messages[3..]), 3);
+        assert_eq!(dropped_prefix(&[], &messages), 0);
+        // The same exchange as a different revision is a different message
+        let regenerated = ChatMessage { revision: Revision::Regenerated, ..messages[2].clone() };
+        assert_eq!(dropped_prefix(&messages[..3], &[messages[0].clone(), messages[1].clone(), regenerated]), 3);
+    }
+
+    #[test]
+    fn concurrent_atomic_writes_never_leave_a_partial_file() {
+        let dir = std::env::temp_dir().join(format!("pawshell-atomic-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.toml");
+        // Big enough that a write takes a while, with each writer's bytes distinct
+        let contents: Vec<Vec<u8>> = (b'a'..=b'd').map(|byte| vec![byte; 256 * 1024]).collect();
+        write_atomic(&path, &contents[0]).unwrap();
+
+        std::thread::scope(|scope| {
+            for content in &contents {
+                let path = &path;
+                scope.spawn(move || {
+                    for _ in 0..20 {
+                        write_atomic(path, content).unwrap();
+                    }
+                });
+            }
+            for _ in 0..200 {
+                let read = std::fs::read(&path).unwrap();
+                assert!(contents.contains(&read), "read a mixed or partial file of {} bytes", read.len());
+            }
+        });
+        let leftovers: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(leftovers, ["state.toml"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_truncated_state_file_is_recovered_from_a_backup_not_a_leftover_temp() {
+        let dir = std::env::temp_dir().join(format!("pawshell-recover-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.toml");
+        let full = PetState { name: "Biscuit".to_string(), chat_history: vec![message("one"), message("two")], ..PetState::default() };
+        let toml = toml::to_string_pretty(&full).unwrap();
+        let backup = dir.join("backup-1.toml");
+        std::fs::write(&backup, &toml).unwrap();
+
+        // Cut off mid-line, so it doesn't parse
+        std::fs::write(&path, &toml[..toml.find("user_message").unwrap() + 8]).unwrap();
+        // Cut off at a line break, so it parses but has lost a message
+        let temp = dir.join("state.toml.123-0000abcd.tmp");
+        std::fs::write(&temp, &toml[..toml.rfind("[[chat_history]]").unwrap()]).unwrap();
+        assert_eq!(read_state_file(&temp).unwrap().chat_history.len(), 1);
+
+        let Err(error) = read_state_file(&path) else { panic!("the state file should be damaged") };
+        let (state, notice) = recover(&path, &error, vec![backup.clone()]).unwrap();
+        assert_eq!(texts(&state), ["one", "two"]);
+        assert!(notice.contains(&backup.display().to_string()));
+        assert_eq!(texts(&read_state_file(&path).unwrap()), ["one", "two"]);
+        let damaged = std::fs::read_dir(&dir).unwrap().filter(|entry| entry.as_ref().unwrap().file_name().to_string_lossy().contains("damaged")).count();
+        assert_eq!(damaged, 1);
+
+        // Without a readable backup the damaged file is still kept aside
+        std::fs::write(&path, "name = ").unwrap();
+        let Err(error) = read_state_file(&path) else { panic!("the state file should be damaged") };
+        assert!(recover(&path, &error, vec![temp]).is_err());
+        assert!(!path.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn encrypted_store_writes_no_plaintext_to_disk() {
+        let path = std::env::temp_dir().join(format!("pawshell-encrypted-test-{}.db", std::process::id()));
+        let inner = SqliteStore::with_connection(Connection::open(&path).unwrap(), Path::new("/nonexistent/state.toml")).unwrap();
+        let mut store = EncryptedStore::new(Box::new(inner), Cipher::with_key(&[3; 32].into())).unwrap();
+
+        let mut state = PetState { input_draft: Some("half-typed-draft".to_string()), ..PetState::default() };
+        state.chat_history.push(message("secret-question"));
+        state.chat_sessions.push(ChatSession { id: 1, started: clock::now(), title: Some("secret-title".to_string()), exchanges: 1 });
+        store.save(&state).unwrap();
+
+        let on_disk = String::from_utf8_lossy(&std::fs::read(&path).unwrap()).into_owned();
+        for plaintext in ["secret-question", "re: secret-question", "secret-title", "half-typed-draft"] {
+            assert!(!on_disk.contains(plaintext), "{} was written in plaintext", plaintext);
+        }
+        let loaded = store.load().unwrap();
+        assert_eq!(texts(&loaded), ["secret-question"]);
+        assert_eq!(loaded.chat_sessions[0].title.as_deref(), Some("secret-title"));
+        assert_eq!(loaded.input_draft.as_deref(), Some("half-typed-draft"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn sqlite_store_notices_saves_from_another_connection() {
+        let (first, second) = shared_memory("reload");
+        let missing = Path::new("/nonexistent/state.toml");
+        let mut store = SqliteStore::with_connection(first, missing).unwrap();
+        let mut popup = SqliteStore::with_connection(second, missing).unwrap();
+        store.load().unwrap();
+        assert!(!store.changed_externally());
+
+        let mut state = popup.load().unwrap();
+        state.chat_history.push(message("from the popup"));
+        popup.save(&state).unwrap();
+        assert!(store.changed_externally());
+        assert_eq!(texts(&store.load().unwrap()), ["from the popup"]);
+        assert!(!store.changed_externally());
+    }
+}
@@ -0,0 +1,321 @@
+//! Sync-friendly pet state storage for PetCLI
+//!
+//! With `storage_backend = "Synced"` the state is kept in small files in the sync
+//! directory (`sync_dir`, by default `sync` in the data directory) that can be
+//! shared between machines with git, Syncthing or Dropbox:
+//! - `manifests/<machine>.toml`: the pet's mood, bookmarks, adopted aliases, ...
+//!   as last saved on that machine
+//! - `messages/<date>-<machine>.jsonl`: chat messages and clears, one per line,
+//!   only ever appended to
+//!
+//! Each machine only writes its own files, so a sync tool never has to reconcile
+//! two versions of one file. Loading merges everything: the most recently saved
+//! manifest wins for mood and name, bookmarks and aliases from all machines are
+//! combined, and messages are ordered by time with anything covered by a clear
+//! (a `/purge` or archival on any machine) left out. Conflict copies made by sync
+//! tools are merged the same way, and a line cut off by a crash is skipped.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::clock;
+use crate::config::Config;
+use crate::config_path;
+use crate::error::StorageError;
+use crate::pet::{ChatMessage, PetState};
+use crate::storage::{self, StateStore};
+
+/// One line of a message log
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum LogEntry {
+    /// Always has a timestamp
+    Message(Box<ChatMessage>),
+    /// Drops every message up to and including `through`
+    Clear { through: DateTime<Utc> },
+}
+
+/// A machine's view of everything but the chat history
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    updated_at: DateTime<Utc>,
+    state: PetState,
+}
+
+pub fn default_sync_dir() -> PathBuf {
+    config_path::get_data_dir().join("sync")
+}
+
+/// The state as per-machine manifests and append-only message logs
+pub struct SyncStore {
+    dir: PathBuf,
+    machine: String,
+    /// The messages currently held in `chat_history` as written to the logs,
+    /// oldest first, used to work out what changed on save
+    synced: Vec<ChatMessage>,
+    /// Sizes and modification times of all files as of our last load or save
+    fingerprint: Vec<(PathBuf, u64, Option<SystemTime>)>,
+}
+
+impl SyncStore {
+    pub fn open(config: &Config) -> Result<Self, StorageError> {
+        let dir = config.sync_dir.as_ref().map(PathBuf::from).unwrap_or_else(default_sync_dir);
+        for subdir in ["manifests", "messages"] {
+            let path = dir.join(subdir);
+            std::fs::create_dir_all(&path).map_err(StorageError::file(&path))?;
+        }
+
+        let mut store = Self { dir, machine: machine_name(), synced: Vec::new(), fingerprint: Vec::new() };
+        if store.files("manifests").is_empty() {
+            store.import_confy_state()?;
+        }
+        Ok(store)
+    }
+
+    /// Copies the existing confy state into a fresh sync directory
+    fn import_confy_state(&mut self) -> Result<(), StorageError> {
+        let confy_path = config_path::state_file_path();
+        let state = if confy_path.exists() { storage::read_state_file(&confy_path)? } else { PetState::default() };
+        self.save(&state)
+    }
+
+    fn files(&self, subdir: &str) -> Vec<PathBuf> {
+        let Ok(entries) = std::fs::read_dir(self.dir.join(subdir)) else {
+            return Vec::new();
+        };
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file())
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn fingerprint(&self) -> Vec<(PathBuf, u64, Option<SystemTime>)> {
+        let mut files = self.files("manifests");
+        files.extend(self.files("messages"));
+        files
+            .into_iter()
+            .filter_map(|path| {
+                let meta = std::fs::metadata(&path).ok()?;
+                Some((path, meta.len(), meta.modified().ok()))
+            })
+            .collect()
+    }
+
+    fn append(&self, entries: &[LogEntry]) -> Result<(), StorageError> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let name = format!("{}-{}.jsonl", clock::now().format("%Y-%m-%d"), self.machine);
+        let path = self.dir.join("messages").join(name);
+        let mut lines = String::new();
+        for entry in entries {
+            lines.push_str(&serde_json::to_string(entry)?);
+            lines.push('\n');
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(StorageError::file(&path))?;
+        file.write_all(lines.as_bytes()).map_err(StorageError::file(&path))?;
+        Ok(())
+    }
+}
+
+impl StateStore for SyncStore {
+    fn load(&mut self) -> Result<PetState, StorageError> {
+        let fingerprint = self.fingerprint();
+        let manifests = self
+            .files("manifests")
+            .iter()
+            .filter_map(|path| toml::from_str(&std::fs::read_to_string(path).ok()?).ok())
+            .collect();
+        let entries = self.files("messages").iter().flat_map(|path| read_log(path)).collect();
+        let state = merge(manifests, entries);
+
+        self.synced = state.chat_history.clone();
+        self.fingerprint = fingerprint;
+        Ok(state)
+    }
+
+    fn save(&mut self, state: &PetState) -> Result<(), StorageError> {
+        let history = &state.chat_history;
+        let dropped = storage::dropped_prefix(&self.synced, history);
+        let kept = self.synced.len() - dropped;
+
+        let now = clock::now();
+        let mut entries = Vec::new();
+        if dropped > 0 {
+            // Also clears older messages from other machines that weren't loaded
+            let through = self.synced[dropped - 1].timestamp.unwrap_or(now);
+            entries.push(LogEntry::Clear { through });
+        }
+        let mut inserted = Vec::new();
+        for message in history.iter().skip(kept) {
+            let message = ChatMessage { timestamp: Some(message.timestamp.unwrap_or(now)), ..message.clone() };
+            entries.push(LogEntry::Message(Box::new(message.clone())));
+            inserted.push(message);
+        }
+        self.append(&entries)?;
+
+        let manifest = Manifest {
+            updated_at: now,
+            state: PetState { chat_history: Vec::new(), ..state.clone() },
+        };
+        let path = self.dir.join("manifests").join(format!("{}.toml", self.machine));
+        storage::write_atomic(&path, toml::to_string(&manifest)?.as_bytes()).map_err(StorageError::file(&path))?;
+
+        self.synced.drain(..dropped);
+        self.synced.extend(inserted);
+        self.fingerprint = self.fingerprint();
+        Ok(())
+    }
+
+    fn changed_externally(&mut self) -> bool {
+        self.fingerprint() != self.fingerprint
+    }
+
+    fn backup(&self, _dest: &Path) -> Result<bool, StorageError> {
+        // The logs are only ever appended to, so a crash can't wipe out the
+        // history, and the sync tool keeps its own copies
+        Ok(false)
+    }
+
+    fn backup_extension(&self) -> &'static str {
+        "toml"
+    }
+}
+
+/// Reads a message log, skipping lines that don't parse (e.g. cut off by a crash)
+fn read_log(path: &Path) -> Vec<LogEntry> {
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    contents.lines().filter_map(|line| serde_json::from_str(line).ok()).collect()
+}
+
+/// Combines the manifests and message logs of all machines into one state
+fn merge(mut manifests: Vec<Manifest>, entries: Vec<LogEntry>) -> PetState {
+    // Newest first, so its mood, name and bookmark descriptions win
+    manifests.sort_by_key(|manifest| std::cmp::Reverse(manifest.updated_at));
+    let mut state = manifests.first().map(|manifest| manifest.state.clone()).unwrap_or_default();
+    for manifest in manifests.iter().skip(1) {
+        for bookmark in &manifest.state.bookmarks {
+            if !state.bookmarks.iter().any(|b| b.command == bookmark.command) {
+                state.bookmarks.push(bookmark.clone());
+            }
+        }
+        for adopted in &manifest.state.adopted_aliases {
+            if !state.adopted_aliases.iter().any(|a| a.alias.name == adopted.alias.name) {
+                state.adopted_aliases.push(adopted.clone());
+            }
+        }
+    }
+    state.bookmarks.sort_by_key(|bookmark| bookmark.created);
+    state.adopted_aliases.sort_by_key(|adopted| adopted.adopted);
+
+    let cleared = entries
+        .iter()
+        .filter_map(|entry| match entry {
+            LogEntry::Clear { through } => Some(*through),
+            LogEntry::Message(_) => None,
+        })
+        .max();
+    let mut messages: Vec<ChatMessage> = entries
+        .into_iter()
+        .filter_map(|entry| match entry {
+            LogEntry::Message(message) if cleared.is_none_or(|cleared| message.timestamp > Some(cleared)) => {
+                Some(*message)
+            }
+            _ => None,
+        })
+        .collect();
+    // Stable, so messages from one machine keep their order even on equal times
+    messages.sort_by_key(|message| message.timestamp);
+    // Conflict copies repeat lines of the original
+    messages.dedup();
+
+    state.chat_history = messages;
+    state
+}
+
+/// A file-name-safe name for this machine, so each writes its own files
+fn machine_name() -> String {
+    let hostname = std::fs::read_to_string("/etc/hostname")
+        .ok()
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .or_else(|| {
+            let output = std::process::Command::new("hostname").output().ok()?;
+            Some(String::from_utf8_lossy(&output.stdout).into_owned())
+        })
+        .unwrap_or_default();
+    let name: String = hostname
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    if name.is_empty() {
+        String::from("local")
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bookmarks::Bookmark;
+    use chrono::TimeZone;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000 + seconds, 0).unwrap()
+    }
+
+    fn message(seconds: i64, text: &str) -> LogEntry {
+        let message = ChatMessage::new(text.to_string(), format!("re: {}", text));
+        LogEntry::Message(Box::new(ChatMessage { timestamp: Some(at(seconds)), ..message }))
+    }
+
+    fn manifest(updated: i64, mood: f32, bookmarks: &[&str]) -> Manifest {
+        let bookmarks = bookmarks
+            .iter()
+            .map(|command| Bookmark { command: command.to_string(), description: None, created: at(updated) })
+            .collect();
+        Manifest { updated_at: at(updated), state: PetState { mood, bookmarks, ..PetState::default() } }
+    }
+
+    #[test]
+    fn merge_interleaves_messages_from_both_machines() {
+        let laptop = vec![message(1, "a"), message(5, "c")];
+        let desktop = vec![message(3, "b"), message(5, "c")];
+        let state = merge(Vec::new(), laptop.into_iter().chain(desktop).collect());
+
+        let users: Vec<&str> = state.chat_history.iter().map(|message| message.user_message.as_str()).collect();
+        assert_eq!(users, ["a", "b", "c"]);
+        let times: Vec<_> = state.chat_history.iter().filter_map(|message| message.timestamp).collect();
+        assert_eq!(times, [at(1), at(3), at(5)]);
+    }
+
+    #[test]
+    fn merge_applies_clears_from_any_machine() {
+        let entries = vec![message(1, "a"), message(2, "b"), LogEntry::Clear { through: at(2) }, message(3, "c")];
+        let state = merge(Vec::new(), entries);
+
+        let users: Vec<&str> = state.chat_history.iter().map(|message| message.user_message.as_str()).collect();
+        assert_eq!(users, ["c"]);
+    }
+
+    #[test]
+    fn merge_prefers_newest_manifest_and_combines_bookmarks() {
+        let state = merge(vec![manifest(1, 0.2, &["ls", "make"]), manifest(2, 0.9, &["ls", "git log"])], Vec::new());
+
+        assert_eq!(state.mood, 0.9);
+        let commands: Vec<&str> = state.bookmarks.iter().map(|b| b.command.as_str()).collect();
+        assert_eq!(commands, ["make", "ls", "git log"]);
+    }
+}
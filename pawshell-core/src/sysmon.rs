@@ -0,0 +1,189 @@
+//! Machine load awareness
+//!
+//! With `system_monitor = true`, the TUI samples CPU, memory and disk usage
+//! every `system_monitor_interval_secs`. The pet's title shows a sparkline of
+//! recent CPU load and its energy, which drops as the machine gets busier, and
+//! the pet speaks up (once, until things calm down) when the CPU stays pegged,
+//! memory runs out or a disk fills up.
+
+use std::collections::VecDeque;
+
+use sysinfo::{Disks, System};
+
+/// CPU samples kept for the sparkline and for telling a spike from a pegged CPU
+const HISTORY: usize = 12;
+/// CPU usage (percent) that counts as pegged once the last few samples all reach it
+const CPU_PEGGED: f32 = 90.0;
+/// Samples in a row above `CPU_PEGGED` before the pet says something
+const CPU_PEGGED_SAMPLES: usize = 6;
+const MEMORY_PEGGED: f32 = 0.9;
+const DISK_PEGGED: f32 = 0.95;
+/// Disks smaller than this (boot partitions, snaps, ...) are ignored
+const MIN_DISK_BYTES: u64 = 1 << 30;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// A resource the machine is running out of
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pressure {
+    Cpu,
+    /// Fraction of memory in use
+    Memory(f32),
+    /// Fullest disk's mount point and fraction in use
+    Disk(String, f32),
+}
+
+impl Pressure {
+    fn kind(&self) -> usize {
+        match self {
+            Pressure::Cpu => 0,
+            Pressure::Memory(_) => 1,
+            Pressure::Disk(..) => 2,
+        }
+    }
+
+    /// What the pet says about it, with a command to look into it
+    pub fn comment(&self) -> String {
+        #[cfg(target_os = "macos")]
+        let (by_cpu, by_memory) = ("ps aux -r | head", "ps aux -m | head");
+        #[cfg(not(target_os = "macos"))]
+        let (by_cpu, by_memory) = ("ps aux --sort=-%cpu | head", "ps aux --sort=-%mem | head");
+        match self {
+            Pressure::Cpu => format!("*pants* Your CPU has been maxed out for a while. Want `{}` to see who's hogging it?", by_cpu),
+            Pressure::Memory(used) => format!(
+                "*sniffs around* Something is eating your RAM ({:.0}% used). Want `{}`?",
+                used * 100.0,
+                by_memory
+            ),
+            Pressure::Disk(mount, used) => format!(
+                "*peeks at the disk* {} is {:.0}% full. `du -sh * | sort -h` shows what's taking up the space.",
+                mount,
+                used * 100.0
+            ),
+        }
+    }
+}
+
+/// Samples the machine's load and remembers recent CPU usage
+pub struct SystemMonitor {
+    system: System,
+    disks: Disks,
+    /// Recent CPU usage in percent, oldest first
+    cpu_history: VecDeque<f32>,
+    /// Pressures already commented on, by kind; re-armed once they go away
+    reported: [bool; 3],
+}
+
+impl Default for SystemMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SystemMonitor {
+    pub fn new() -> Self {
+        let mut system = System::new();
+        // CPU usage is measured between two refreshes, so take the first one now
+        system.refresh_cpu_usage();
+        Self { system, disks: Disks::new_with_refreshed_list(), cpu_history: VecDeque::new(), reported: [false; 3] }
+    }
+
+    /// Takes a sample, returning pressures that weren't reported yet
+    pub fn sample(&mut self) -> Vec<Pressure> {
+        self.system.refresh_cpu_usage();
+        self.system.refresh_memory();
+        self.disks.refresh();
+        if self.cpu_history.len() == HISTORY {
+            self.cpu_history.pop_front();
+        }
+        self.cpu_history.push_back(self.system.global_cpu_info().cpu_usage());
+
+        let memory = match self.system.total_memory() {
+            0 => 0.0,
+            total => self.system.used_memory() as f32 / total as f32,
+        };
+        let fullest_disk = self
+            .disks
+            .list()
+            .iter()
+            .filter(|disk| disk.total_space() >= MIN_DISK_BYTES)
+            .map(|disk| {
+                let used = 1.0 - disk.available_space() as f32 / disk.total_space() as f32;
+                (disk.mount_point().display().to_string(), used)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        let current = pressures(self.cpu_history.make_contiguous(), memory, fullest_disk);
+
+        let mut new = Vec::new();
+        for kind in 0..self.reported.len() {
+            match current.iter().find(|pressure| pressure.kind() == kind) {
+                Some(pressure) if !self.reported[kind] => {
+                    self.reported[kind] = true;
+                    new.push(pressure.clone());
+                }
+                Some(_) => {}
+                None => self.reported[kind] = false,
+            }
+        }
+        new
+    }
+
+    /// Average CPU usage over the recent samples, 0.0 to 1.0
+    pub fn load(&self) -> f32 {
+        if self.cpu_history.is_empty() {
+            return 0.0;
+        }
+        self.cpu_history.iter().sum::<f32>() / self.cpu_history.len() as f32 / 100.0
+    }
+
+    /// The pet's energy, which is whatever the machine has left
+    pub fn energy(&self) -> f32 {
+        1.0 - self.load()
+    }
+
+    /// Recent CPU usage, for the title
+    pub fn sparkline(&self) -> String {
+        sparkline(self.cpu_history.iter().copied())
+    }
+}
+
+/// What the machine is short of, given recent CPU usage, the memory in use and
+/// the fullest disk
+fn pressures(cpu_history: &[f32], memory: f32, fullest_disk: Option<(String, f32)>) -> Vec<Pressure> {
+    let mut pressures = Vec::new();
+    if cpu_history.len() >= CPU_PEGGED_SAMPLES && cpu_history.iter().rev().take(CPU_PEGGED_SAMPLES).all(|&cpu| cpu >= CPU_PEGGED) {
+        pressures.push(Pressure::Cpu);
+    }
+    if memory >= MEMORY_PEGGED {
+        pressures.push(Pressure::Memory(memory));
+    }
+    if let Some((mount, used)) = fullest_disk.filter(|(_, used)| *used >= DISK_PEGGED) {
+        pressures.push(Pressure::Disk(mount, used));
+    }
+    pressures
+}
+
+/// Percentages as a row of block characters
+pub(crate) fn sparkline(values: impl Iterator<Item = f32>) -> String {
+    values
+        .map(|value| SPARKS[((value.clamp(0.0, 100.0) / 100.0 * (SPARKS.len() - 1) as f32).round()) as usize])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sparklines_scale_percentages() {
+        assert_eq!(sparkline([0.0, 50.0, 100.0, 250.0].into_iter()), "▁▅██");
+    }
+
+    #[test]
+    fn only_sustained_cpu_load_counts_as_pegged() {
+        let spike = [10.0, 10.0, 10.0, 99.0, 99.0, 99.0, 99.0, 99.0];
+        assert_eq!(pressures(&spike, 0.5, None), []);
+        let pegged = [10.0, 10.0, 95.0, 99.0, 99.0, 99.0, 99.0, 99.0];
+        assert_eq!(pressures(&pegged, 0.95, Some(("/".to_string(), 0.5))), [Pressure::Cpu, Pressure::Memory(0.95)]);
+        assert_eq!(pressures(&[], 0.1, Some(("/home".to_string(), 0.97))), [Pressure::Disk("/home".to_string(), 0.97)]);
+    }
+}
@@ -0,0 +1,175 @@
+//! Prompt templates
+//!
+//! Every prompt sent to the backend is rendered from a named minijinja
+//! template: `chat` for chat messages and one per command, like `commit` or
+//! `translate`. A `[prompt_templates]` table in the config replaces any of
+//! them by name. Besides its own variables (`{{ diff }}` for `commit`,
+//! `{{ message }}` for `chat`, ...), every template can use `{{ pet_name }}`,
+//! `{{ mood }}`, `{{ recent_commands }}` and `{{ cwd }}`, and a replacement
+//! can build on the built-in prompt with `{{ default }}`.
+
+use minijinja::{Environment, UndefinedBehavior};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::history::HistoryEntry;
+use crate::mood::MoodLevel;
+use crate::pet::PetState;
+
+/// The templates `[prompt_templates]` can replace
+pub const NAMES: &[&str] = &[
+    "chat",
+    "analyze",
+    "suggestion",
+    "commit",
+    "github",
+    "review",
+    "playground",
+    "regex",
+    "cron",
+    "timer",
+    "script",
+    "script_revision",
+    "dotfiles",
+    "translate",
+    "report",
+    "retention",
+    "session_title",
+];
+
+/// Commands given to `{{ recent_commands }}`
+const RECENT_COMMANDS: usize = 10;
+
+/// The variables every template can use
+#[derive(Debug, Clone, Default)]
+pub struct Globals {
+    pub pet_name: String,
+    /// "happy", "content" or "sad"
+    pub mood: String,
+    /// The last few commands, one per line
+    pub recent_commands: String,
+    /// The directory the user works in
+    pub cwd: String,
+}
+
+impl Globals {
+    /// The globals for `state`; without a `cwd`, the directory of the latest
+    /// command (or this process's) is used
+    pub fn new(state: &PetState, recent_commands: &[HistoryEntry], cwd: Option<&Path>) -> Self {
+        let mood = match state.mood_level() {
+            MoodLevel::Happy => "happy",
+            MoodLevel::Content => "content",
+            MoodLevel::Sad => "sad",
+        };
+        let cwd = cwd
+            .map(|cwd| cwd.display().to_string())
+            .or_else(|| recent_commands.iter().rev().find_map(|entry| entry.cwd.clone()))
+            .or_else(|| std::env::current_dir().ok().map(|cwd| cwd.display().to_string()))
+            .unwrap_or_default();
+        let recent = &recent_commands[recent_commands.len().saturating_sub(RECENT_COMMANDS)..];
+        Self {
+            pet_name: state.name.clone(),
+            mood: mood.to_string(),
+            recent_commands: recent.iter().map(|entry| entry.command.as_str()).collect::<Vec<_>>().join("\n"),
+            cwd,
+        }
+    }
+}
+
+/// A prompt waiting to be rendered: which template it uses, the built-in
+/// text of that template and the prompt's own variables
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    pub name: &'static str,
+    template: &'static str,
+    vars: BTreeMap<&'static str, String>,
+}
+
+impl Prompt {
+    pub fn new(name: &'static str, template: &'static str) -> Self {
+        Self { name, template, vars: BTreeMap::new() }
+    }
+
+    pub fn var(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.vars.insert(name, value.into());
+        self
+    }
+
+    /// The prompt as the built-in template has it
+    pub fn render_default(&self, globals: &Globals) -> String {
+        let vars = self.vars(globals);
+        render(self.template, &vars).unwrap_or_else(|e| {
+            tracing::warn!(template = self.name, error = %e, "built-in template failed to render");
+            self.template.to_string()
+        })
+    }
+
+    /// The prompt from the user's template for it, if `overrides` has one;
+    /// when that doesn't render, the built-in prompt and why
+    pub fn render(&self, overrides: &BTreeMap<String, String>, globals: &Globals) -> (String, Option<String>) {
+        let default = self.render_default(globals);
+        let Some(source) = overrides.get(self.name) else {
+            return (default, None);
+        };
+        let mut vars = self.vars(globals);
+        vars.insert("default", default.clone());
+        match render(source, &vars) {
+            Ok(prompt) => (prompt, None),
+            Err(e) => (default, Some(format!("Your {} prompt template didn't work ({}), so the built-in one was used.", self.name, e))),
+        }
+    }
+
+    fn vars(&self, globals: &Globals) -> BTreeMap<&'static str, String> {
+        let mut vars = BTreeMap::from([
+            ("pet_name", globals.pet_name.clone()),
+            ("mood", globals.mood.clone()),
+            ("recent_commands", globals.recent_commands.clone()),
+            ("cwd", globals.cwd.clone()),
+        ]);
+        vars.extend(self.vars.iter().map(|(name, value)| (*name, value.clone())));
+        vars
+    }
+}
+
+fn render(source: &str, vars: &BTreeMap<&'static str, String>) -> Result<String, minijinja::Error> {
+    let mut env = Environment::new();
+    env.set_keep_trailing_newline(true);
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    env.render_str(source, vars)
+}
+
+/// Problems with the `[prompt_templates]` table, for config validation
+pub fn check(overrides: &BTreeMap<String, String>) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (name, source) in overrides {
+        if !NAMES.contains(&name.as_str()) {
+            problems.push(format!("prompt_templates.{} isn't a prompt; use one of {}", name, NAMES.join(", ")));
+        } else if let Err(e) = Environment::new().template_from_str(source) {
+            problems.push(format!("prompt_templates.{} doesn't parse: {}", name, e));
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_replace_or_build_on_the_built_in_prompt() {
+        let globals = Globals { pet_name: "Lilith".to_string(), mood: "happy".to_string(), ..Globals::default() };
+        let prompt = Prompt::new("commit", "Describe this:\n{{ diff }}{% if note %} ({{ note }}){% endif %}").var("diff", "+x").var("note", "");
+        assert_eq!(prompt.render(&BTreeMap::new(), &globals), ("Describe this:\n+x".to_string(), None));
+
+        let overrides = BTreeMap::from([("commit".to_string(), "{{ pet_name }} is {{ mood }}. {{ default }}\n".to_string())]);
+        assert_eq!(prompt.render(&overrides, &globals).0, "Lilith is happy. Describe this:\n+x\n");
+
+        let overrides = BTreeMap::from([("commit".to_string(), "{{ dif }}".to_string())]);
+        let (text, problem) = prompt.render(&overrides, &globals);
+        assert_eq!(text, "Describe this:\n+x");
+        assert!(problem.unwrap().starts_with("Your commit prompt template didn't work"));
+
+        let overrides = BTreeMap::from([("comit".to_string(), "x".to_string()), ("chat".to_string(), "{% if %}".to_string())]);
+        assert_eq!(check(&overrides).len(), 2);
+    }
+}
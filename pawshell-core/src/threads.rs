@@ -0,0 +1,106 @@
+//! Conversation threads forked with `/fork`
+//!
+//! All exchanges stay in the pet's one chat history, each tagged with the
+//! thread it was said in (none for the main conversation). A thread's
+//! conversation is its own exchanges plus its parent's up to the moment it
+//! was forked, and only that is shown and given to the backend as context
+//! while the thread is current, so a tangent explored in a fork never ends
+//! up in the main conversation's memory.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::pet::ChatMessage;
+
+/// What `/threads` calls the main conversation
+pub const MAIN: &str = "main";
+const MAX_NAME_CHARS: usize = 32;
+
+/// A named branch of the conversation
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Thread {
+    pub name: String,
+    /// The thread it was forked from; none for the main conversation
+    pub parent: Option<String>,
+    pub forked_at: DateTime<Utc>,
+}
+
+/// The exchanges making up `thread`'s conversation, oldest first
+pub fn messages<'a>(history: &'a [ChatMessage], threads: &[Thread], thread: Option<&str>) -> Vec<&'a ChatMessage> {
+    // The thread and its ancestors, each seen up to where the branch below it was forked
+    let mut lineage: Vec<(Option<&str>, Option<DateTime<Utc>>)> = vec![(thread, None)];
+    let mut current = thread;
+    while let Some(fork) = current.and_then(|name| threads.iter().find(|fork| fork.name == name)) {
+        if lineage.len() > threads.len() {
+            break;
+        }
+        let until = lineage.last().and_then(|(_, until)| *until).map_or(fork.forked_at, |until| until.min(fork.forked_at));
+        lineage.push((fork.parent.as_deref(), Some(until)));
+        current = fork.parent.as_deref();
+    }
+    history
+        .iter()
+        .filter(|message| {
+            lineage.iter().any(|(name, until)| {
+                message.thread.as_deref() == *name
+                    && until.is_none_or(|until| message.timestamp.is_none_or(|timestamp| timestamp <= until))
+            })
+        })
+        .collect()
+}
+
+/// Why `name` can't be used for a new thread, if it can't
+pub fn check_name(name: &str, threads: &[Thread]) -> Result<(), String> {
+    if name.is_empty() || name.chars().count() > MAX_NAME_CHARS || name.contains(char::is_whitespace) {
+        return Err(format!("Thread names are one word of up to {} characters, like /fork docker-idea.", MAX_NAME_CHARS));
+    }
+    if name == MAIN || threads.iter().any(|thread| thread.name == name) {
+        return Err(format!("There's already a thread called {}.", name));
+    }
+    Ok(())
+}
+
+/// The `/threads` list, with the current one marked
+pub fn list(history: &[ChatMessage], threads: &[Thread], current: Option<&str>) -> String {
+    let line = |name: Option<&str>, from: Option<&str>| {
+        let marker = if name == current { "*" } else { " " };
+        let count = history.iter().filter(|message| message.thread.as_deref() == name).count();
+        let from = from.map(|parent| format!(", forked from {}", parent)).unwrap_or_default();
+        format!("{} {} ({} own message{}{})", marker, name.unwrap_or(MAIN), count, if count == 1 { "" } else { "s" }, from)
+    };
+    let mut lines = vec![line(None, None)];
+    lines.extend(threads.iter().map(|thread| line(Some(&thread.name), Some(thread.parent.as_deref().unwrap_or(MAIN)))));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::test_time;
+
+    #[test]
+    fn forks_see_their_parents_up_to_the_fork() {
+        let message = |text: &str, thread: Option<&str>, minute| ChatMessage {
+            timestamp: Some(test_time(minute)),
+            thread: thread.map(str::to_string),
+            ..ChatMessage::new(text.to_string(), String::new())
+        };
+        let history = vec![
+            message("setup", None, 0),
+            message("tangent", Some("idea"), 2),
+            message("deeper", Some("deep"), 4),
+            message("back on track", None, 5),
+            message("more tangent", Some("idea"), 6),
+        ];
+        let threads = vec![
+            Thread { name: "idea".to_string(), parent: None, forked_at: test_time(1) },
+            Thread { name: "deep".to_string(), parent: Some("idea".to_string()), forked_at: test_time(3) },
+        ];
+        let texts = |thread| messages(&history, &threads, thread).iter().map(|message| message.user_message.as_str()).collect::<Vec<_>>();
+        assert_eq!(texts(None), ["setup", "back on track"]);
+        assert_eq!(texts(Some("idea")), ["setup", "tangent", "more tangent"]);
+        assert_eq!(texts(Some("deep")), ["setup", "tangent", "deeper"]);
+        assert!(check_name("main", &threads).is_err() && check_name("idea", &threads).is_err() && check_name("two words", &threads).is_err());
+        assert!(check_name("docker", &threads).is_ok());
+    }
+}
@@ -0,0 +1,253 @@
+//! tldr pages and man page excerpts for command questions
+//!
+//! `/tldr <cmd>` shows the [tldr-pages](https://tldr.sh) entry for a command:
+//! a few community-written examples. The pages are downloaded once (about
+//! 2 MB, English only) into the cache directory and read from disk after that,
+//! so lookups work offline. Pages already cached by tealdeer or the Node.js
+//! `tldr` client are used too.
+//!
+//! When `docs_context` is on, a chat message that mentions a command gets the
+//! command's tldr page (or, without one, the start of its man page) added to
+//! the prompt, so the model can check flags against real documentation
+//! instead of guessing.
+
+use std::io::{Cursor, Read};
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::config_path;
+
+const PAGES_URL: &str = "https://github.com/tldr-pages/tldr/releases/latest/download/tldr.zip";
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+/// Man page text beyond this many characters is left out of the prompt
+const MAX_MAN_CHARS: usize = 4_000;
+/// How long looking up docs may hold up a chat reply
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Words that are also command names but usually just English in a question
+const NOT_COMMANDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "can", "do", "does", "file", "files", "for", "from", "how", "i", "if",
+    "in", "is", "it", "last", "less", "me", "more", "my", "of", "on", "or", "time", "the", "this", "to", "true",
+    "false", "what", "which", "who", "why", "with", "yes",
+];
+
+#[cfg(target_os = "macos")]
+const PLATFORM: &str = "osx";
+#[cfg(windows)]
+const PLATFORM: &str = "windows";
+#[cfg(not(any(target_os = "macos", windows)))]
+const PLATFORM: &str = "linux";
+
+/// Where the pages downloaded by the pet live
+fn pages_dir() -> PathBuf {
+    config_path::get_cache_dir().join("tldr").join("pages")
+}
+
+/// Page directories to search: the pet's own download, then other clients' caches
+fn page_roots() -> Vec<PathBuf> {
+    let mut roots = vec![pages_dir()];
+    if let Some(cache) = dirs::cache_dir() {
+        roots.push(cache.join("tealdeer").join("tldr-pages").join("pages"));
+    }
+    if let Some(home) = dirs::home_dir() {
+        roots.push(home.join(".tldr").join("cache").join("pages"));
+    }
+    roots
+}
+
+/// Whether any tldr pages are available
+pub fn has_pages() -> bool {
+    page_roots().iter().any(|root| root.join("common").is_dir())
+}
+
+/// The raw markdown page for a command, preferring this platform's version
+///
+/// Subcommands are looked up the tldr way: `git commit` is `git-commit`.
+pub fn page(command: &str) -> Option<String> {
+    find_page(&page_roots(), command)
+}
+
+fn find_page(roots: &[PathBuf], command: &str) -> Option<String> {
+    let name = command.trim().to_lowercase().split_whitespace().collect::<Vec<_>>().join("-");
+    if name.is_empty() || name.contains(['/', '\\', '.']) {
+        return None;
+    }
+    roots.iter().find_map(|root| {
+        [PLATFORM, "common"]
+            .iter()
+            .find_map(|platform| std::fs::read_to_string(root.join(platform).join(format!("{}.md", name))).ok())
+    })
+}
+
+/// A tldr page as plain text: the description, then each example with its
+/// command indented below it and `{{placeholders}}` unwrapped
+pub fn render(page: &str) -> String {
+    let mut lines = Vec::new();
+    for line in page.lines() {
+        let line = line.trim();
+        if let Some(title) = line.strip_prefix("# ") {
+            lines.push(title.to_string());
+        } else if let Some(description) = line.strip_prefix("> ") {
+            lines.push(description.to_string());
+        } else if let Some(example) = line.strip_prefix("- ") {
+            lines.push(String::new());
+            lines.push(example.to_string());
+        } else if let Some(command) = line.strip_prefix('`').and_then(|line| line.strip_suffix('`')) {
+            lines.push(format!("    {}", command.replace("{{", "").replace("}}", "")));
+        }
+    }
+    lines.join("\n")
+}
+
+/// The start of a command's man page, if it has one
+pub fn man_excerpt(command: &str) -> Option<String> {
+    if command.contains(['/', '\\']) || command.starts_with('-') {
+        return None;
+    }
+    let output = Command::new("man").arg(command).env("MANPAGER", "cat").env("PAGER", "cat").env("MANWIDTH", "80").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    // Drop the backspace overstrikes man uses for bold and underlined text
+    let mut text = String::new();
+    for c in String::from_utf8_lossy(&output.stdout).chars() {
+        if c == '\u{8}' {
+            text.pop();
+        } else {
+            text.push(c);
+        }
+    }
+    if text.chars().count() > MAX_MAN_CHARS {
+        text = text.chars().take(MAX_MAN_CHARS).collect();
+        text.push_str("\n… (truncated)");
+    }
+    Some(text)
+}
+
+/// The command a message asks about: the first word of the first inline code
+/// span, or else the first word that has a tldr page and isn't a common
+/// English word
+pub fn mentioned_command(message: &str) -> Option<String> {
+    find_mentioned_command(&page_roots(), message)
+}
+
+fn find_mentioned_command(roots: &[PathBuf], message: &str) -> Option<String> {
+    if let Some(code) = message.split('`').nth(1) {
+        if let Some(word) = code.split_whitespace().next() {
+            return Some(word.to_string());
+        }
+    }
+    message
+        .split(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_'))
+        .filter(|word| word.len() > 1 && !NOT_COMMANDS.contains(&word.to_lowercase().as_str()))
+        .find(|word| find_page(roots, word).is_some())
+        .map(str::to_string)
+}
+
+/// Documentation for the command a message mentions, as prompt context
+///
+/// This reads tldr pages for the message's words and may run `man`, so it
+/// happens on a blocking thread, and gives up after `LOOKUP_TIMEOUT` rather
+/// than hold up the reply.
+pub async fn context_for(message: String) -> Option<String> {
+    let lookup = tokio::task::spawn_blocking(move || find_context(&message));
+    match tokio::time::timeout(LOOKUP_TIMEOUT, lookup).await {
+        Ok(Ok(context)) => context,
+        Ok(Err(e)) => {
+            tracing::warn!("looking up docs failed: {}", e);
+            None
+        }
+        Err(_) => {
+            tracing::warn!("looking up docs took too long");
+            None
+        }
+    }
+}
+
+fn find_context(message: &str) -> Option<String> {
+    let roots = page_roots();
+    let command = find_mentioned_command(&roots, message)?;
+    match find_page(&roots, &command) {
+        Some(page) => Some(format!(
+            "tldr page for `{}` (prefer the flags shown here; don't invent options):\n{}",
+            command,
+            render(&page)
+        )),
+        None => man_excerpt(&command).map(|man| {
+            format!("Start of the man page for `{}` (prefer the flags documented here; don't invent options):\n{}", command, man)
+        }),
+    }
+}
+
+/// Downloads the English tldr pages into the cache, replacing older ones;
+/// returns how many pages there are
+pub async fn update() -> Result<usize, String> {
+    let client = reqwest::Client::builder().timeout(DOWNLOAD_TIMEOUT).build().map_err(|e| e.to_string())?;
+    let response = client.get(PAGES_URL).send().await.map_err(|e| format!("couldn't download the tldr pages: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("couldn't download the tldr pages: {}", response.status()));
+    }
+    let archive = response.bytes().await.map_err(|e| format!("couldn't download the tldr pages: {}", e))?;
+    tokio::task::spawn_blocking(move || extract(&archive)).await.map_err(|e| e.to_string())?
+}
+
+/// Unpacks the `pages/` directory of the archive next to the old pages, then
+/// swaps it in so a failed update leaves the old pages alone
+fn extract(archive: &[u8]) -> Result<usize, String> {
+    let mut zip = zip::ZipArchive::new(Cursor::new(archive)).map_err(|e| format!("the tldr archive is damaged: {}", e))?;
+    let target = pages_dir();
+    let staging = target.with_file_name("pages.new");
+    let _ = std::fs::remove_dir_all(&staging);
+    let mut count = 0;
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i).map_err(|e| format!("the tldr archive is damaged: {}", e))?;
+        let Some(path) = file.enclosed_name().map(|path| path.to_path_buf()) else {
+            continue;
+        };
+        let Ok(relative) = path.strip_prefix("pages") else {
+            continue;
+        };
+        if !file.is_file() || relative.extension().is_none_or(|extension| extension != "md") {
+            continue;
+        }
+        let mut contents = String::new();
+        if file.read_to_string(&mut contents).is_err() {
+            continue;
+        }
+        let destination = staging.join(relative);
+        if let Some(dir) = destination.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("couldn't write the tldr pages: {}", e))?;
+        }
+        std::fs::write(&destination, contents).map_err(|e| format!("couldn't write the tldr pages: {}", e))?;
+        count += 1;
+    }
+    if count == 0 {
+        return Err("the tldr archive had no pages in it".to_string());
+    }
+    let _ = std::fs::remove_dir_all(&target);
+    std::fs::rename(&staging, &target).map_err(|e| format!("couldn't write the tldr pages: {}", e))?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_render_as_plain_examples() {
+        let page = "# tar\n\n> Archiving utility.\n> More information: <https://www.gnu.org/software/tar>.\n\n\
+            - [c]reate an archive from files:\n\n`tar cf {{path/to/target.tar}} {{path/to/file1}}`\n";
+        assert_eq!(
+            render(page),
+            "tar\nArchiving utility.\nMore information: <https://www.gnu.org/software/tar>.\n\n\
+            [c]reate an archive from files:\n    tar cf path/to/target.tar path/to/file1"
+        );
+    }
+
+    #[test]
+    fn code_spans_name_the_command() {
+        assert_eq!(mentioned_command("what does `rsync -avz` do?"), Some("rsync".to_string()));
+        assert_eq!(page("../../etc/passwd"), None);
+    }
+}
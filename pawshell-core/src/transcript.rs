@@ -0,0 +1,64 @@
+//! Per-session chat transcripts for PetCLI
+//!
+//! With `write_transcripts = true`, each session's conversation is appended as it
+//! happens to a Markdown file in the `transcripts` directory of the data
+//! directory, named after the time the session started. The file is created on
+//! the first message, so sessions without a conversation leave nothing behind.
+
+use chrono::{DateTime, Local};
+use std::error::Error;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::clock;
+use crate::config_path;
+
+pub fn transcripts_dir() -> PathBuf {
+    config_path::get_data_dir().join("transcripts")
+}
+
+/// The Markdown transcript of the current session
+pub struct Transcript {
+    started: DateTime<Local>,
+    path: PathBuf,
+}
+
+impl Default for Transcript {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transcript {
+    /// Starts a transcript named after the current time; the file is created on the first exchange
+    pub fn new() -> Self {
+        let started = clock::local_now();
+        let path = transcripts_dir().join(format!("{}.md", started.format("%Y-%m-%d-%H%M")));
+        Self { started, path }
+    }
+
+    /// Appends an exchange, creating the file with a heading if needed
+    pub fn append(&self, pet_name: &str, user_message: &str, response: &str) -> Result<(), Box<dyn Error>> {
+        std::fs::create_dir_all(transcripts_dir())?;
+        let mut text = String::new();
+        if !self.path.exists() {
+            text.push_str(&format!("# {} - {}\n\n", pet_name, self.started.format("%Y-%m-%d %H:%M")));
+        }
+        text.push_str(&format!(
+            "**You** ({}): {}\n\n**{}**: {}\n\n",
+            clock::local_now().format("%H:%M"),
+            user_message,
+            pet_name,
+            response
+        ));
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("Failed to open {}: {}", self.path.display(), e))?;
+        file.write_all(text.as_bytes())
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+//! Translations for `/translate`
+//!
+//! `/translate <language> <text>` translates the text, and `/translate
+//! <language>` alone the pet's last reply. Meant for things like error
+//! messages in another language, so commands, code, paths and error codes
+//! are kept as they are. The result is shown under the original.
+
+use crate::templates::Prompt;
+
+/// Text beyond this many characters is left out of the prompt
+const MAX_TEXT_CHARS: usize = 8_000;
+
+const TEMPLATE: &str = "Translate the text below into {{ language }}. Keep commands, code, file paths, \
+identifiers and error codes exactly as they are. If it's an error message, translate it the way a \
+native speaker would search for it. Reply with the translation only, without quotes or comments.\n\n\
+Text:\n{{ text }}";
+
+/// Splits `/translate`'s arguments into the language and the text, which is
+/// empty when the last reply should be translated
+pub fn parse(args: &str) -> Option<(&str, &str)> {
+    let args = args.trim();
+    if args.is_empty() {
+        return None;
+    }
+    let (language, text) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    Some((language, text.trim()))
+}
+
+/// Asks for the translation of `text` into `language`
+pub fn prompt(language: &str, text: &str) -> Prompt {
+    let text: String = text.chars().take(MAX_TEXT_CHARS).collect();
+    Prompt::new("translate", TEMPLATE).var("language", language).var("text", text)
+}
+
+/// The original with the translation under it
+pub fn render(language: &str, original: &str, translation: &str) -> String {
+    let translation = translation.trim().trim_matches('"').trim();
+    let indent = |text: &str| text.trim().lines().map(|line| format!("  {}", line)).collect::<Vec<_>>().join("\n");
+    format!("Original:\n{}\n\nIn {}:\n{}", indent(original), language, indent(translation))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arguments_name_the_language_first() {
+        assert_eq!(parse("de  Datei nicht gefunden "), Some(("de", "Datei nicht gefunden")));
+        assert_eq!(parse("Japanese"), Some(("Japanese", "")));
+        assert_eq!(parse("  "), None);
+        assert_eq!(render("English", "Zugriff verweigert", "\"Permission denied\"\n"), "Original:\n  Zugriff verweigert\n\nIn English:\n  Permission denied");
+    }
+}
@@ -0,0 +1,168 @@
+//! When and how the pet is used: sessions, reply times and the hour of day
+//!
+//! Each TUI session counts its messages and how long the backend took to
+//! answer them, and is added to the pet's state when it ends; sessions older
+//! than `KEEP_DAYS` are dropped. `/stats` compares the current session with
+//! the recent ones and draws which hours of the day the pet is talked to,
+//! from the chat timestamps.
+//!
+//! Chatting between 1 and 5 in the morning counts toward a late-night streak,
+//! which the pet gently points out.
+
+use chrono::{DateTime, Duration, Local, NaiveDate, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::pet::ChatMessage;
+use crate::reminders;
+use crate::sysmon;
+
+/// How long finished sessions are kept
+const KEEP_DAYS: i64 = 30;
+/// Sessions `/stats` compares the current one with
+const RECENT_DAYS: i64 = 7;
+/// Local hours that count as the middle of the night
+const LATE_HOURS: std::ops::Range<u32> = 1..5;
+/// How recently a message must have been sent to count as still being up
+const AWAKE_MINUTES: i64 = 10;
+
+/// One run of the TUI
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SessionStats {
+    pub started: DateTime<Utc>,
+    /// When the last message was sent
+    pub last_active: DateTime<Utc>,
+    pub messages: u32,
+    /// Replies from the backend and how long they took altogether
+    pub replies: u32,
+    pub reply_ms: u64,
+    pub slowest_reply_ms: u64,
+}
+
+impl SessionStats {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self { started: now, last_active: now, messages: 0, replies: 0, reply_ms: 0, slowest_reply_ms: 0 }
+    }
+
+    pub fn record_message(&mut self, now: DateTime<Utc>) {
+        self.messages += 1;
+        self.last_active = now;
+    }
+
+    pub fn record_reply(&mut self, latency: std::time::Duration) {
+        let ms = latency.as_millis() as u64;
+        self.replies += 1;
+        self.reply_ms += ms;
+        self.slowest_reply_ms = self.slowest_reply_ms.max(ms);
+    }
+
+    /// Whether messages are still being sent in the middle of the night
+    pub fn is_up_late(&self, now: &DateTime<Local>) -> bool {
+        self.messages > 0
+            && LATE_HOURS.contains(&now.hour())
+            && now.with_timezone(&Utc) - self.last_active <= Duration::minutes(AWAKE_MINUTES)
+    }
+
+    fn length(&self) -> Duration {
+        self.last_active - self.started
+    }
+
+    fn average_reply_secs(&self) -> Option<f64> {
+        (self.replies > 0).then(|| self.reply_ms as f64 / self.replies as f64 / 1000.0)
+    }
+}
+
+/// Adds a finished session, unless nothing was said in it, and forgets old ones
+pub fn archive(sessions: &mut Vec<SessionStats>, session: SessionStats, now: DateTime<Utc>) {
+    sessions.retain(|past| now - past.last_active <= Duration::days(KEEP_DAYS));
+    if session.messages > 0 {
+        sessions.push(session);
+    }
+}
+
+/// Chat messages per local hour of the day
+fn by_hour(chats: &[ChatMessage], since: DateTime<Utc>) -> [u32; 24] {
+    let mut hours = [0; 24];
+    for timestamp in chats.iter().filter_map(|chat| chat.timestamp).filter(|&timestamp| timestamp >= since) {
+        hours[timestamp.with_timezone(&Local).hour() as usize] += 1;
+    }
+    hours
+}
+
+/// Nights in a row, up to tonight, with chats in the middle of the night
+pub fn late_night_streak(chats: &[ChatMessage], now: &DateTime<Local>) -> usize {
+    let nights: Vec<NaiveDate> = chats
+        .iter()
+        .filter_map(|chat| chat.timestamp)
+        .map(|timestamp| timestamp.with_timezone(&Local))
+        .filter(|time| LATE_HOURS.contains(&time.hour()))
+        .map(|time| time.date_naive())
+        .collect();
+    let mut night = now.date_naive();
+    let mut streak = 0;
+    while nights.contains(&night) {
+        streak += 1;
+        night -= Duration::days(1);
+    }
+    streak
+}
+
+/// The session and usage lines for `/stats`
+pub fn summary(current: &SessionStats, past: &[SessionStats], chats: &[ChatMessage], now: DateTime<Utc>) -> String {
+    let mut text = format!(
+        "This Session: {} message{} in {}",
+        current.messages,
+        if current.messages == 1 { "" } else { "s" },
+        reminders::format_duration(now - current.started)
+    );
+    if let Some(average) = current.average_reply_secs() {
+        text.push_str(&format!(
+            ", replies in {:.1}s on average (slowest {:.1}s)",
+            average,
+            current.slowest_reply_ms as f64 / 1000.0
+        ));
+    }
+
+    let recent: Vec<&SessionStats> = past.iter().filter(|past| now - past.last_active <= Duration::days(RECENT_DAYS)).collect();
+    if !recent.is_empty() {
+        let messages: u32 = recent.iter().map(|session| session.messages).sum();
+        let minutes: i64 = recent.iter().map(|session| session.length().num_minutes()).sum();
+        let (replies, reply_ms) = recent.iter().fold((0, 0), |(n, ms), session| (n + session.replies, ms + session.reply_ms));
+        text.push_str(&format!(
+            "\nLast 7 Days: {} session{}, {:.0} messages and {} on average",
+            recent.len(),
+            if recent.len() == 1 { "" } else { "s" },
+            messages as f32 / recent.len() as f32,
+            reminders::format_duration(Duration::minutes(minutes / recent.len() as i64))
+        ));
+        if replies > 0 {
+            text.push_str(&format!(", replies in {:.1}s", reply_ms as f64 / replies as f64 / 1000.0));
+        }
+    }
+
+    let hours = by_hour(chats, now - Duration::days(KEEP_DAYS));
+    let busiest = hours.iter().copied().max().unwrap_or(0);
+    if busiest > 0 {
+        let peak = hours.iter().position(|&count| count == busiest).unwrap_or(0);
+        let sparks = sysmon::sparkline(hours.iter().map(|&count| count as f32 / busiest as f32 * 100.0));
+        text.push_str(&format!("\nBy Hour (0-23h): {} (busiest {:02}:00)", sparks, peak));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn late_night_streaks_count_consecutive_nights() {
+        let at = |day, hour| {
+            let time = Local.with_ymd_and_hms(2024, 3, day, hour, 10, 0).unwrap();
+            ChatMessage { timestamp: Some(time.with_timezone(&Utc)), ..ChatMessage::new(String::new(), String::new()) }
+        };
+        let chats = [at(1, 2), at(3, 3), at(3, 14), at(4, 1), at(5, 2)];
+        let now = Local.with_ymd_and_hms(2024, 3, 5, 2, 30, 0).unwrap();
+        assert_eq!(late_night_streak(&chats, &now), 3);
+        assert_eq!(late_night_streak(&chats[..4], &now), 0);
+    }
+}
@@ -0,0 +1,392 @@
+//! vi editing of the input line
+//!
+//! With `editing_mode = "Vi"` the input starts each line in insert mode, where
+//! keys type as usual, and Esc switches to normal mode. Normal mode has vi's
+//! motions (`h l w b e W B E 0 ^ $ f F t T ; ,`), counts, the `d`, `c` and `y`
+//! operators with those motions and the `iw aw iW aW i" a" i' a' i( a( i[ a[
+//! i{ a{` text objects, and `x X s S D C Y p P r ~ i a I A u`. Deleted and
+//! yanked text goes to a register that `p` and `P` put back. A command and the
+//! insert it starts, like `cw` and the new word, are undone together.
+//!
+//! The keys arrive as characters; the terminal maps arrows and the like to
+//! their vi equivalents before they get here.
+
+use std::ops::Range;
+
+use crate::line_editor::{self, LineEditor};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Insert,
+    Normal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+impl Operator {
+    fn from_key(c: char) -> Option<Self> {
+        match c {
+            'd' => Some(Operator::Delete),
+            'c' => Some(Operator::Change),
+            'y' => Some(Operator::Yank),
+            _ => None,
+        }
+    }
+
+    fn key(self) -> char {
+        match self {
+            Operator::Delete => 'd',
+            Operator::Change => 'c',
+            Operator::Yank => 'y',
+        }
+    }
+}
+
+/// The vi state of the input line
+#[derive(Debug, Clone)]
+pub struct Vi {
+    pub mode: Mode,
+    /// Count typed so far
+    count: Option<usize>,
+    /// An operator waiting for its motion, and its count
+    operator: Option<(Operator, usize)>,
+    /// A key waiting for the character it applies to, like `f` or `r`
+    awaiting: Option<char>,
+    /// The last `f`, `F`, `t` or `T` and its character, for `;` and `,`
+    last_find: Option<(char, char)>,
+    /// What `p` and `P` put back
+    pub register: String,
+}
+
+impl Default for Vi {
+    fn default() -> Self {
+        Self { mode: Mode::Insert, count: None, operator: None, awaiting: None, last_find: None, register: String::new() }
+    }
+}
+
+impl Vi {
+    /// Starts a new line in insert mode, forgetting anything half typed
+    pub fn reset(&mut self) {
+        self.mode = Mode::Insert;
+        self.cancel();
+    }
+
+    fn cancel(&mut self) {
+        self.count = None;
+        self.operator = None;
+        self.awaiting = None;
+    }
+
+    /// Handles Esc: leaves insert mode, or drops a half-typed command. Returns
+    /// false if there was nothing to leave, so Esc can mean what it does outside vi
+    pub fn escape(&mut self, editor: &mut LineEditor) -> bool {
+        match self.mode {
+            Mode::Insert => {
+                self.mode = Mode::Normal;
+                editor.end_group();
+                editor.left();
+                true
+            }
+            Mode::Normal => {
+                let pending = self.count.is_some() || self.operator.is_some() || self.awaiting.is_some();
+                self.cancel();
+                pending
+            }
+        }
+    }
+
+    /// The half-typed command, like `d2` or `f`, for the mode indicator
+    pub fn pending(&self) -> String {
+        let mut pending = String::new();
+        if let Some((operator, count)) = self.operator {
+            if count > 1 {
+                pending.push_str(&count.to_string());
+            }
+            pending.push(operator.key());
+        }
+        if let Some(count) = self.count {
+            pending.push_str(&count.to_string());
+        }
+        pending.extend(self.awaiting);
+        pending
+    }
+
+    /// Handles a key typed in normal mode
+    pub fn normal(&mut self, editor: &mut LineEditor, c: char) {
+        editor.begin_group();
+        self.command(editor, c);
+        if self.mode == Mode::Normal {
+            editor.end_group();
+        }
+        if self.mode == Mode::Normal && !editor.is_empty() && editor.cursor() >= editor.len() {
+            editor.move_to(editor.len() - 1);
+        }
+    }
+
+    fn command(&mut self, editor: &mut LineEditor, c: char) {
+        if let Some(key) = self.awaiting.take() {
+            let count = self.count.take().unwrap_or(1) * self.operator.map_or(1, |(_, count)| count);
+            match key {
+                'r' if self.operator.take().is_none() => self.replace(editor, c, count),
+                'r' => {}
+                'i' | 'a' => {
+                    if let Some((operator, _)) = self.operator.take() {
+                        if let Some(range) = text_object(&editor.chars(), editor.cursor(), c, key == 'a') {
+                            self.apply(editor, operator, range);
+                        }
+                    }
+                }
+                _ => {
+                    self.last_find = Some((key, c));
+                    self.motion_key(editor, key, Some(c), count);
+                }
+            }
+            return;
+        }
+
+        if c.is_ascii_digit() && (c != '0' || self.count.is_some()) {
+            let digit = c.to_digit(10).unwrap_or(0) as usize;
+            self.count = Some(self.count.unwrap_or(0).saturating_mul(10).saturating_add(digit).min(9_999));
+            return;
+        }
+
+        if matches!(c, 'f' | 'F' | 't' | 'T' | 'r') || (self.operator.is_some() && matches!(c, 'i' | 'a')) {
+            self.awaiting = Some(c);
+            return;
+        }
+
+        let count = self.count.take().unwrap_or(1);
+        if let Some((operator, operator_count)) = self.operator {
+            if c == operator.key() {
+                self.operator = None;
+                self.apply(editor, operator, 0..editor.len());
+                return;
+            }
+            self.motion_key(editor, c, None, count * operator_count);
+            return;
+        }
+
+        let len = editor.len();
+        let cursor = editor.cursor();
+        match c {
+            'd' | 'c' | 'y' => self.operator = Operator::from_key(c).map(|operator| (operator, count)),
+            'D' => self.apply(editor, Operator::Delete, cursor..len),
+            'C' => self.apply(editor, Operator::Change, cursor..len),
+            'Y' => self.apply(editor, Operator::Yank, 0..len),
+            'S' => self.apply(editor, Operator::Change, 0..len),
+            'x' => self.apply(editor, Operator::Delete, cursor..cursor + count),
+            'X' => self.apply(editor, Operator::Delete, cursor.saturating_sub(count)..cursor),
+            's' => self.apply(editor, Operator::Change, cursor..cursor + count),
+            'i' => self.mode = Mode::Insert,
+            'u' => {
+                for _ in 0..count {
+                    editor.undo();
+                }
+            }
+            'a' => {
+                editor.right();
+                self.mode = Mode::Insert;
+            }
+            'I' => {
+                editor.move_to(first_non_blank(&editor.chars()));
+                self.mode = Mode::Insert;
+            }
+            'A' => {
+                editor.end();
+                self.mode = Mode::Insert;
+            }
+            'p' | 'P' => {
+                if self.register.is_empty() {
+                    return;
+                }
+                if c == 'p' && !editor.is_empty() {
+                    editor.right();
+                }
+                editor.insert_str(&self.register.repeat(count));
+                editor.left();
+            }
+            '~' => {
+                let end = (cursor + count).min(len);
+                editor.map(cursor..end, |text| {
+                    text.chars()
+                        .flat_map(|c| if c.is_uppercase() { c.to_lowercase().collect::<Vec<_>>() } else { c.to_uppercase().collect() })
+                        .collect()
+                });
+                editor.move_to(end);
+            }
+            _ => self.motion_key(editor, c, None, count),
+        }
+    }
+
+    /// Moves by a motion, or applies the pending operator to the text it
+    /// moves over
+    fn motion_key(&mut self, editor: &mut LineEditor, key: char, target: Option<char>, count: usize) {
+        let operator = self.operator.take().map(|(operator, _)| operator);
+        let chars = editor.chars();
+        let cursor = editor.cursor();
+        // `cw` changes to the end of the word, like `ce`, rather than eating the space after it
+        let key = match (operator, key) {
+            (Some(Operator::Change), 'w') if chars.get(cursor).is_some_and(|c| !c.is_whitespace()) => 'e',
+            (Some(Operator::Change), 'W') if chars.get(cursor).is_some_and(|c| !c.is_whitespace()) => 'E',
+            _ => key,
+        };
+        let Some((to, inclusive)) = self.motion(&chars, cursor, key, target, count) else {
+            return;
+        };
+        match operator {
+            Some(operator) => {
+                let range = if to < cursor { to..cursor } else { cursor..to + usize::from(inclusive) };
+                self.apply(editor, operator, range);
+            }
+            None => editor.move_to(to),
+        }
+    }
+
+    /// Where a motion goes from `cursor`, and whether an operator takes the
+    /// character it lands on too
+    fn motion(&self, chars: &[char], cursor: usize, key: char, target: Option<char>, count: usize) -> Option<(usize, bool)> {
+        let repeat = |step: &dyn Fn(usize) -> usize| (0..count).fold(cursor, |position, _| step(position));
+        Some(match key {
+            'h' => (cursor.saturating_sub(count), false),
+            'l' | ' ' => ((cursor + count).min(chars.len()), false),
+            '0' => (0, false),
+            '^' => (first_non_blank(chars), false),
+            '$' => (chars.len().saturating_sub(1), true),
+            'w' | 'W' => (repeat(&|position| line_editor::next_word_start(chars, position, key == 'W')), false),
+            'b' | 'B' => (repeat(&|position| line_editor::prev_word_start(chars, position, key == 'B')), false),
+            'e' | 'E' => (repeat(&|position| line_editor::word_end(chars, position, key == 'E')), true),
+            'f' | 'F' | 't' | 'T' => find(chars, cursor, key, target?, count)?,
+            ';' | ',' => {
+                let (find_key, target) = self.last_find?;
+                // `,` goes the other way
+                let find_key = match (key, find_key) {
+                    (',', 'f') => 'F',
+                    (',', 'F') => 'f',
+                    (',', 't') => 'T',
+                    (',', 'T') => 't',
+                    _ => find_key,
+                };
+                find(chars, cursor, find_key, target, count)?
+            }
+            _ => return None,
+        })
+    }
+
+    fn apply(&mut self, editor: &mut LineEditor, operator: Operator, range: Range<usize>) {
+        let range = range.start.min(editor.len())..range.end.min(editor.len());
+        match operator {
+            Operator::Delete | Operator::Change => {
+                let removed = editor.delete(range.clone());
+                if !removed.is_empty() {
+                    self.register = removed;
+                }
+                editor.move_to(range.start);
+                if operator == Operator::Change {
+                    self.mode = Mode::Insert;
+                }
+            }
+            Operator::Yank => {
+                if !range.is_empty() {
+                    self.register = editor.chars()[range.clone()].iter().collect();
+                }
+                editor.move_to(range.start);
+            }
+        }
+    }
+
+    /// `r`: replaces the characters under the cursor, if there are enough
+    fn replace(&mut self, editor: &mut LineEditor, c: char, count: usize) {
+        let cursor = editor.cursor();
+        if cursor + count > editor.len() {
+            return;
+        }
+        editor.map(cursor..cursor + count, |_| c.to_string().repeat(count));
+        editor.move_to(cursor + count - 1);
+    }
+}
+
+fn first_non_blank(chars: &[char]) -> usize {
+    chars.iter().position(|c| !c.is_whitespace()).unwrap_or(chars.len())
+}
+
+/// The `count`th `target` from `cursor` for `f` (on it, forwards), `t` (just
+/// before it), `F` (on it, backwards) and `T` (just after it)
+fn find(chars: &[char], cursor: usize, key: char, target: char, count: usize) -> Option<(usize, bool)> {
+    match key {
+        'f' | 't' => {
+            let found = chars.iter().enumerate().skip(cursor + 1).filter(|(_, &c)| c == target).nth(count - 1)?.0;
+            Some((if key == 't' { found - 1 } else { found }, true))
+        }
+        _ => {
+            let found = chars[..cursor.min(chars.len())].iter().enumerate().rev().filter(|(_, &c)| c == target).nth(count - 1)?.0;
+            Some((if key == 'T' { found + 1 } else { found }, false))
+        }
+    }
+}
+
+/// The range of a text object: `w`, `W`, a quote or a bracket
+fn text_object(chars: &[char], cursor: usize, object: char, around: bool) -> Option<Range<usize>> {
+    match object {
+        'w' | 'W' => line_editor::word_at(chars, cursor, object == 'W', around),
+        '"' | '\'' | '`' => line_editor::pair_at(chars, cursor, object, object, around),
+        '(' | ')' | 'b' => line_editor::pair_at(chars, cursor, '(', ')', around),
+        '[' | ']' => line_editor::pair_at(chars, cursor, '[', ']', around),
+        '{' | '}' | 'B' => line_editor::pair_at(chars, cursor, '{', '}', around),
+        '<' | '>' => line_editor::pair_at(chars, cursor, '<', '>', around),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normal_mode_moves_and_operates_like_vi() {
+        let mut editor = LineEditor::default();
+        editor.set("git commit -m \"wip\" --amend");
+        let mut vi = Vi::default();
+        let keys = |vi: &mut Vi, editor: &mut LineEditor, keys: &str| keys.chars().for_each(|c| vi.normal(editor, c));
+
+        assert!(vi.escape(&mut editor));
+        assert_eq!((vi.mode, editor.cursor()), (Mode::Normal, 26));
+        assert!(!vi.escape(&mut editor));
+
+        keys(&mut vi, &mut editor, "0w");
+        assert_eq!(editor.current(), Some('c'));
+        keys(&mut vi, &mut editor, "ci\"");
+        assert_eq!((editor.text(), vi.mode, vi.register.as_str()), ("git commit -m \"\" --amend", Mode::Insert, "wip"));
+        editor.insert_str("fix typo");
+        vi.escape(&mut editor);
+
+        keys(&mut vi, &mut editor, "$d2b");
+        assert_eq!(editor.text(), "git commit -m \"fix typo\" d");
+        keys(&mut vi, &mut editor, "0dw");
+        assert_eq!((editor.text(), vi.register.as_str()), ("commit -m \"fix typo\" d", "git "));
+        keys(&mut vi, &mut editor, "f\"p");
+        assert_eq!(editor.text(), "commit -m \"git fix typo\" d");
+        keys(&mut vi, &mut editor, "0tm~");
+        assert_eq!(editor.text(), "cOmmit -m \"git fix typo\" d");
+        keys(&mut vi, &mut editor, "2;");
+        assert_eq!(editor.cursor(), 7);
+        assert!(vi.pending().is_empty());
+        keys(&mut vi, &mut editor, "d2");
+        assert_eq!(vi.pending(), "d2");
+        keys(&mut vi, &mut editor, "$");
+        assert_eq!(editor.text(), "cOmmit ");
+
+        keys(&mut vi, &mut editor, "u");
+        assert_eq!(editor.text(), "cOmmit -m \"git fix typo\" d");
+        keys(&mut vi, &mut editor, "S");
+        assert!(editor.is_empty() && vi.mode == Mode::Insert);
+        editor.insert_str("amend");
+        vi.escape(&mut editor);
+        keys(&mut vi, &mut editor, "u");
+        assert_eq!(editor.text(), "cOmmit -m \"git fix typo\" d");
+    }
+}
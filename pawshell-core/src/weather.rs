@@ -0,0 +1,214 @@
+//! Local weather for greetings, idle art and outdoor questions
+//!
+//! With `weather = true` the pet looks up the current weather at
+//! `weather_latitude`/`weather_longitude` from Open-Meteo (no API key needed)
+//! or wttr.in, which can also guess the location from the IP address. The
+//! result is cached in the cache directory for `weather_refresh_minutes`, so
+//! restarting the pet doesn't hit the provider again.
+//!
+//! The weather only colors the pet's behavior: a line in the greeting, rain or
+//! sunshine around its idle art, and a sentence of prompt context when a
+//! message sounds like it's about going outside.
+
+use chrono::{DateTime, Duration, Local, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+
+use crate::clock;
+use crate::config::{Config, WeatherProvider};
+use crate::config_path;
+
+/// How long a provider gets to answer
+const FETCH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Words that make a message about going outside
+const OUTDOOR_WORDS: &[&str] = &[
+    "outside", "outdoors", "weather", "umbrella", "jacket", "coat", "rain", "raining", "sunny", "sunscreen", "go out",
+    "a walk",
+];
+
+/// The kind of weather, as far as the pet cares
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Sky {
+    Clear,
+    Cloudy,
+    Fog,
+    Rain,
+    Snow,
+    Storm,
+}
+
+/// The current weather at the configured place
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Weather {
+    pub sky: Sky,
+    pub temperature_c: f32,
+    /// The provider's words for it, like "Light rain"
+    pub description: String,
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl Weather {
+    /// Whether it's older than `weather_refresh_minutes`
+    pub fn is_stale(&self, config: &Config, now: DateTime<Utc>) -> bool {
+        now - self.fetched_at >= Duration::minutes(config.weather_refresh_minutes as i64)
+    }
+
+    /// A sentence of prompt context
+    pub fn describe(&self) -> String {
+        format!(
+            "Current local weather: {}, {:.0}°C (as of {} UTC).",
+            self.description.to_lowercase(),
+            self.temperature_c,
+            self.fetched_at.format("%H:%M")
+        )
+    }
+
+    /// The pet's remark on the weather for its greeting
+    pub fn greeting(&self) -> String {
+        match self.sky {
+            Sky::Clear if self.temperature_c >= 18.0 => {
+                format!("*stretches out in a sunbeam* {:.0}°C and sunny. Don't forget to go outside today! ☀", self.temperature_c)
+            }
+            Sky::Clear => format!("*blinks at the bright window* Clear skies, {:.0}°C.", self.temperature_c),
+            Sky::Cloudy => format!("*peers at the clouds* A gray {:.0}°C out there.", self.temperature_c),
+            Sky::Fog => "*squints* It's foggy out; I can barely see the birds.".to_string(),
+            Sky::Rain => "*watches raindrops slide down the window* It's raining, a cozy day for coding. 🌧".to_string(),
+            Sky::Snow => "*paws at the snowflakes on the glass* It's snowing! ❄".to_string(),
+            Sky::Storm => "*hides under the desk* There's a thunderstorm out there, let's stay in. ⛈".to_string(),
+        }
+    }
+}
+
+/// Decorates the idle art for the weather: falling rain or snow that moves
+/// every second, or the sun to lounge in on clear days
+pub fn decorate(art: &str, sky: Sky, now: DateTime<Local>) -> String {
+    let even = now.timestamp() % 2 == 0;
+    let daytime = (7..20).contains(&now.hour());
+    let top = match sky {
+        Sky::Rain | Sky::Storm if even => " ' , ' , ' , ' ,",
+        Sky::Rain | Sky::Storm => " , ' , ' , ' , '",
+        Sky::Snow if even => " * . * . * . * .",
+        Sky::Snow => " . * . * . * . *",
+        Sky::Clear if daytime => "  \\ | /\n -- O --",
+        Sky::Clear | Sky::Cloudy | Sky::Fog => return art.to_string(),
+    };
+    format!("{}\n{}", top, art)
+}
+
+/// Whether a message sounds like a question about going outside
+pub fn is_outdoor_question(message: &str) -> bool {
+    let message = message.to_lowercase();
+    OUTDOOR_WORDS.iter().any(|word| {
+        message
+            .match_indices(word)
+            .any(|(i, _)| !message[..i].ends_with(char::is_alphanumeric) && !message[i + word.len()..].starts_with(char::is_alphanumeric))
+    })
+}
+
+fn cache_path() -> PathBuf {
+    config_path::get_cache_dir().join("weather.json")
+}
+
+/// The last fetched weather, however old
+pub fn cached() -> Option<Weather> {
+    let contents = std::fs::read_to_string(cache_path()).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+pub fn save_cache(weather: &Weather) -> std::io::Result<()> {
+    let path = cache_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, serde_json::to_string(weather)?)
+}
+
+/// Asks a provider for the current weather at `coordinates` (latitude, longitude)
+pub async fn fetch(provider: WeatherProvider, coordinates: Option<(f64, f64)>) -> Result<Weather, String> {
+    let client = reqwest::Client::builder().timeout(FETCH_TIMEOUT).build().map_err(|e| e.to_string())?;
+    let url = match provider {
+        WeatherProvider::OpenMeteo => {
+            let (latitude, longitude) = coordinates.ok_or("Open-Meteo needs weather_latitude and weather_longitude")?;
+            format!(
+                "https://api.open-meteo.com/v1/forecast?latitude={}&longitude={}&current=temperature_2m,weather_code",
+                latitude, longitude
+            )
+        }
+        WeatherProvider::WttrIn => {
+            let location = coordinates.map(|(latitude, longitude)| format!("{},{}", latitude, longitude)).unwrap_or_default();
+            format!("https://wttr.in/{}?format=j1", location)
+        }
+    };
+    let response = client.get(&url).send().await.map_err(|e| format!("couldn't reach the weather provider: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("the weather provider answered {}", response.status()));
+    }
+    let body: Value = response.json().await.map_err(|e| format!("unexpected weather response: {}", e))?;
+    let parsed = match provider {
+        WeatherProvider::OpenMeteo => parse_open_meteo(&body),
+        WeatherProvider::WttrIn => parse_wttr(&body),
+    };
+    let (sky, temperature_c, description) = parsed.ok_or("unexpected weather response")?;
+    Ok(Weather { sky, temperature_c, description, fetched_at: clock::now() })
+}
+
+fn parse_open_meteo(body: &Value) -> Option<(Sky, f32, String)> {
+    let current = &body["current"];
+    let temperature = current["temperature_2m"].as_f64()? as f32;
+    // WMO weather interpretation codes
+    let (sky, description) = match current["weather_code"].as_u64()? {
+        0 | 1 => (Sky::Clear, "Clear"),
+        2 => (Sky::Cloudy, "Partly cloudy"),
+        3 => (Sky::Cloudy, "Overcast"),
+        45 | 48 => (Sky::Fog, "Fog"),
+        51..=57 => (Sky::Rain, "Drizzle"),
+        61..=67 | 80..=82 => (Sky::Rain, "Rain"),
+        71..=77 | 85 | 86 => (Sky::Snow, "Snow"),
+        95..=99 => (Sky::Storm, "Thunderstorm"),
+        _ => (Sky::Cloudy, "Cloudy"),
+    };
+    Some((sky, temperature, description.to_string()))
+}
+
+fn parse_wttr(body: &Value) -> Option<(Sky, f32, String)> {
+    let current = &body["current_condition"][0];
+    let temperature = current["temp_C"].as_str()?.parse().ok()?;
+    let description = current["weatherDesc"][0]["value"].as_str().unwrap_or("Unknown").trim().to_string();
+    // WorldWeatherOnline condition codes
+    let sky = match current["weatherCode"].as_str()?.parse::<u32>().ok()? {
+        113 => Sky::Clear,
+        116 | 119 | 122 => Sky::Cloudy,
+        143 | 248 | 260 => Sky::Fog,
+        200 | 386..=395 => Sky::Storm,
+        179 | 227 | 230 | 323..=338 | 368..=377 => Sky::Snow,
+        _ => Sky::Rain,
+    };
+    Some((sky, temperature, description))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn providers_are_parsed() {
+        let open_meteo = serde_json::json!({ "current": { "temperature_2m": 12.4, "weather_code": 61 } });
+        assert_eq!(parse_open_meteo(&open_meteo), Some((Sky::Rain, 12.4, "Rain".to_string())));
+        let wttr = serde_json::json!({
+            "current_condition": [{ "temp_C": "21", "weatherCode": "113", "weatherDesc": [{ "value": "Sunny" }] }]
+        });
+        assert_eq!(parse_wttr(&wttr), Some((Sky::Clear, 21.0, "Sunny".to_string())));
+        assert_eq!(parse_wttr(&serde_json::json!({})), None);
+    }
+
+    #[test]
+    fn outdoor_questions_match_whole_words() {
+        assert!(is_outdoor_question("Should I go outside?"));
+        assert!(is_outdoor_question("do I need an umbrella"));
+        assert!(is_outdoor_question("is it nice enough for a walk?"));
+        assert!(!is_outdoor_question("how do I walk the git tree"));
+        assert!(!is_outdoor_question("fix the coathanger module"));
+    }
+}
@@ -0,0 +1,92 @@
+//! Working directory and git context for PetCLI
+//!
+//! Captures the directory the app was launched from and lightweight information
+//! about the git repository it is in (branch, dirty files, last commit), so that
+//! questions like "why is my branch weird" get grounded answers.
+//!
+//! Git information is read by shelling out to `git`; if git isn't installed or the
+//! directory isn't a repository, only the directory is reported.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Dirty files beyond this count are summarized instead of listed
+const MAX_DIRTY_FILES: usize = 15;
+
+/// Snapshot of the repository the user is working in
+pub struct GitInfo {
+    pub branch: String,
+    /// `git status --porcelain` lines, e.g. ` M src/app.rs`
+    pub dirty_files: Vec<String>,
+    pub last_commit: Option<String>,
+}
+
+/// Where the user is working
+pub struct WorkspaceContext {
+    pub cwd: PathBuf,
+    pub git: Option<GitInfo>,
+}
+
+impl WorkspaceContext {
+    /// Captures the current directory and its git state
+    pub fn detect() -> Option<Self> {
+        let cwd = std::env::current_dir().ok()?;
+        let git = GitInfo::read(&cwd);
+        Some(Self { cwd, git })
+    }
+
+    /// Re-reads git state, which changes as the user works
+    pub fn refresh(&mut self) {
+        self.git = GitInfo::read(&self.cwd);
+    }
+
+    /// Formats the workspace as prompt context
+    pub fn describe(&self) -> String {
+        let mut text = format!("Working directory: {}\n", self.cwd.display());
+        if let Some(git) = &self.git {
+            text.push_str(&format!("Git branch: {}\n", git.branch));
+            if let Some(commit) = &git.last_commit {
+                text.push_str(&format!("Last commit: {}\n", commit));
+            }
+            if git.dirty_files.is_empty() {
+                text.push_str("Working tree: clean\n");
+            } else {
+                text.push_str("Uncommitted changes:\n");
+                for file in git.dirty_files.iter().take(MAX_DIRTY_FILES) {
+                    text.push_str(&format!("  {}\n", file));
+                }
+                if git.dirty_files.len() > MAX_DIRTY_FILES {
+                    text.push_str(&format!("  … and {} more\n", git.dirty_files.len() - MAX_DIRTY_FILES));
+                }
+            }
+        }
+        text
+    }
+}
+
+impl GitInfo {
+    /// Reads git state for `dir`, or None if it isn't inside a repository
+    pub fn read(dir: &Path) -> Option<Self> {
+        let branch = git(dir, &["rev-parse", "--abbrev-ref", "HEAD"])?;
+        let branch = match branch.trim() {
+            // Detached HEAD: show the commit instead
+            "HEAD" => format!("(detached at {})", git(dir, &["rev-parse", "--short", "HEAD"])?.trim()),
+            name => name.to_string(),
+        };
+        let dirty_files = git(dir, &["status", "--porcelain"])
+            .map(|status| status.lines().map(str::to_string).collect())
+            .unwrap_or_default();
+        let last_commit = git(dir, &["log", "-1", "--pretty=format:%h %s (%cr)"])
+            .filter(|commit| !commit.trim().is_empty());
+        Some(Self { branch, dirty_files, last_commit })
+    }
+}
+
+/// Runs a git command in `dir`, returning stdout on success
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git").arg("-C").arg(dir).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
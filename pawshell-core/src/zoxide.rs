@@ -0,0 +1,64 @@
+//! zoxide directory frecency for PetCLI
+//!
+//! [zoxide](https://github.com/ajeetdsouza/zoxide) ranks directories by how often
+//! and how recently they are visited. Knowing the user's top directories lets the
+//! pet name their projects and suggest shortcuts for the places they jump to most
+//! (a tmux session script, a `cd` alias, ...).
+//!
+//! The database is read through `zoxide query --list --score` rather than parsed
+//! directly, since its binary format changes between zoxide versions.
+
+use std::path::Path;
+use std::process::Command;
+
+/// How many directories are included in prompts
+const MAX_DIRECTORIES: usize = 10;
+
+/// A frequently visited directory
+pub struct FrequentDirectory {
+    pub path: String,
+    pub score: f64,
+}
+
+/// The user's most visited directories, highest score first
+pub struct FrequentDirectories {
+    pub directories: Vec<FrequentDirectory>,
+}
+
+impl FrequentDirectories {
+    /// Queries zoxide, returning None if it isn't installed or has no entries
+    pub fn load() -> Option<Self> {
+        let output = Command::new("zoxide").args(["query", "--list", "--score"]).output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let home = dirs::home_dir();
+        let mut directories: Vec<FrequentDirectory> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let (score, path) = line.trim().split_once(char::is_whitespace)?;
+                let path = path.trim();
+                let path = match home.as_deref().and_then(|home| Path::new(path).strip_prefix(home).ok()) {
+                    Some(relative) => format!("~/{}", relative.display()),
+                    None => path.to_string(),
+                };
+                Some(FrequentDirectory { path, score: score.parse().ok()? })
+            })
+            .collect();
+        directories.sort_by(|a, b| b.score.total_cmp(&a.score));
+        directories.truncate(MAX_DIRECTORIES);
+        (!directories.is_empty()).then_some(Self { directories })
+    }
+
+    /// Formats the directories as prompt context
+    pub fn describe(&self) -> String {
+        let mut text = String::from(
+            "Most visited directories according to zoxide (the last path component is usually a project name; \
+             suggest shortcuts like tmux session scripts when relevant):\n",
+        );
+        for directory in &self.directories {
+            text.push_str(&format!("  {} (score {:.0})\n", directory.path, directory.score));
+        }
+        text
+    }
+}
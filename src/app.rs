@@ -6,23 +6,131 @@
 //! modules for better organization:
 //!
 //! Potential refactoring suggestions:
-//! 1. Command History Management: Move shell history loading and processing into a separate module
-//! 2. State Management: Create a dedicated module for handling pet state and persistence
-//! 3. Input Handler: Separate command processing logic into its own module
-//! 4. LLM Integration: Move LLM initialization and interaction logic to a dedicated module
-
-use chrono::Utc;
-use std::fs::File;
-use std::io::{self, BufRead};
-use std::path::PathBuf;
-
-use crate::pet::PetState;
-use crate::llm::{LLMBackend, OpenAIBackend};
-use crate::ollama::OllamaBackend;
-use crate::config::LLMProvider;
+//! 1. State Management: Create a dedicated module for handling pet state and persistence
+//! 2. Input Handler: Separate command processing logic into its own module
+//! 3. LLM Integration: Move LLM initialization and interaction logic to a dedicated module
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::time::{Duration, Instant, SystemTime};
+
+use pawshell_core::analytics::CommandStats;
+use pawshell_core::card::{self, PetCard};
+use pawshell_core::clock;
+use pawshell_core::commit;
+use pawshell_core::review::CodeReview;
+use pawshell_core::github::{self, GhItem};
+use pawshell_core::translate;
+use pawshell_core::dotfiles::{self, Advice, Dotfile};
+use pawshell_core::shell_script::{self, ScriptDraft};
+use pawshell_core::cron::{self, Schedule};
+use pawshell_core::templates::{Globals, Prompt};
+use pawshell_core::regex_lab::RegexLab;
+use pawshell_core::playground::{self, Playground, Tool};
+use pawshell_core::macros::{self, Recording};
+use pawshell_core::adoption::{self, Pedigree};
+use pawshell_core::threads::{self, Thread};
+use pawshell_core::chat_sessions;
+use pawshell_core::feedback::{self, Reaction};
+use pawshell_core::environment::SystemEnvironment;
+use pawshell_core::exec::{self, ExecOptions, ExecOutput};
+use pawshell_core::history::{self, HistoryEntry};
+use pawshell_core::history_search::{self, HistorySearch};
+use pawshell_core::vi_mode::Vi;
+use pawshell_core::patterns::PatternDetector;
+use pawshell_core::privacy::Redactor;
+use pawshell_core::safety::{self, Denylist};
+use pawshell_core::workspace::WorkspaceContext;
+use pawshell_core::project_scan::{self, ProjectMemory};
+use pawshell_core::zoxide::FrequentDirectories;
+use pawshell_core::weather::{self, Weather};
+use pawshell_core::tldr;
+use pawshell_core::sysmon::SystemMonitor;
+use pawshell_core::battery::{self, Battery, Charge};
+use pawshell_core::connectivity;
+use pawshell_core::shared_store::{self, Feeding};
+use pawshell_core::speech::Voice;
+use pawshell_core::dictation::{self, Transcriber};
+use pawshell_core::portrait::{self, Painter};
+use pawshell_core::accessories::{self, Accessory};
+use pawshell_core::report;
+use pawshell_core::skills::{self, Skill, Track};
+use pawshell_core::flashcards::{self, Review};
+use pawshell_core::storage::{self, StateStore};
+use pawshell_core::retention::{self, ArchivedExchange, RetentionPolicy};
+use pawshell_core::backup;
+use pawshell_core::art::{self, PetArt};
+use pawshell_core::transcript::Transcript;
+use pawshell_core::mood::{self, MoodLevel};
+use pawshell_core::usage::{self, SessionStats};
+use pawshell_core::perf;
+use pawshell_core::compare::{self, Comparison};
+use pawshell_core::budget::{self, Period, Standing};
+use pawshell_core::plugins::{self, PluginHost};
+use pawshell_core::scripting::{self, ScriptHost, ScriptOutput};
+use pawshell_core::pet::{ChatMessage, PetState, Revision};
+use pawshell_core::bookmarks::{self, Bookmark};
+use pawshell_core::reminders::{self, Reminder, Todo, TodoCommand};
+use pawshell_core::pomodoro::{self, Phase, Pomodoro};
+use pawshell_core::breaks::{self, BreakTimer};
+use pawshell_core::focus::FocusSession;
+use pawshell_core::aliases::{self, AdoptedAlias, AliasDefinition};
+use pawshell_core::llm::{self, ChatContext, Generation, LLMBackend, PromptContext, UnavailableBackend};
+use pawshell_core::ollama;
+use pawshell_core::error::{LlmError, Remedy, StorageError};
 use crate::ui::AppUI;
-use crate::config;
-use crate::config_path;
+use pawshell_core::config::{self, EditingMode, LLMProvider, StorageBackend, TerminalGraphics};
+use pawshell_core::config_path;
+use pawshell_core::project;
+use pawshell_core::locale;
+use crate::events::{self, AppEvent, DocsRequest, EventSender, LlmRequest};
+use crate::logging;
+use crate::notify::Notifier;
+#[cfg(unix)]
+use crate::hook::{self, HookEvent};
+#[cfg(unix)]
+use crate::daemon;
+
+/// How the app is being run
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// The regular full-screen companion
+    Full,
+    /// A compact instance summoned into a tmux popup or floating terminal
+    ///
+    /// It shares the pet's state with the main instance but leaves background
+    /// work like the shell hook socket and proactive suggestions to it.
+    Popup,
+}
+
+/// A program the terminal should hand the screen and keyboard to until it exits
+pub enum Handoff {
+    /// `/log`: the log file in a pager
+    Pager(PathBuf),
+    /// `/commitmsg edit`: the draft commit message in the user's editor
+    CommitMessage(PathBuf),
+}
+
+impl Handoff {
+    pub fn command(&self) -> Command {
+        match self {
+            Handoff::Pager(path) => logging::pager_command(path),
+            Handoff::CommitMessage(path) => editor_command(path),
+        }
+    }
+}
+
+/// Upper bound on how many commands `/analyze` reads from the history source
+const ANALYZE_HISTORY_LIMIT: usize = 100_000;
+/// How often plugins' and scripts' `on_tick` hooks run
+const PLUGIN_TICK_INTERVAL: Duration = Duration::from_secs(1);
+/// How often the battery is read
+const BATTERY_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Replaying again within this long steps back to the reply before
+const REPLAY_STEP_WINDOW: Duration = Duration::from_secs(5);
+/// Recorded as the reply to messages still unanswered when the app quit
+const NO_REPLY: &str = "(no reply: pawshell was closed before it arrived)";
 
 /// The main application struct that coordinates all components and manages the application state.
 /// 
@@ -36,138 +144,742 @@ pub struct App {
     pub ui: AppUI,
     pub state: PetState,
     llm: Box<dyn LLMBackend>,
-    pub recent_commands: Vec<String>,
+    pub recent_commands: Vec<HistoryEntry>,
     pub config: config::Config,
+    /// The pet's art for each mood, from the gallery, files or config
+    pub art: PetArt,
+    /// Number of consecutive failed commands, when exit codes are known
+    pub failure_streak: usize,
+    pattern_detector: PatternDetector,
+    /// Launch directory and its git state, if workspace context is enabled
+    workspace: Option<WorkspaceContext>,
+    /// OS, shell and installed tools, if system context is enabled
+    environment: Option<SystemEnvironment>,
+    /// Most visited directories from zoxide, if enabled and available
+    frequent_directories: Option<FrequentDirectories>,
+    /// Removes secrets from prompts sent to remote backends
+    redactor: Redactor,
+    /// Suggested commands that are never offered to run
+    denylist: Denylist,
+    /// Command waiting for the user to confirm execution
+    pending_exec: Option<String>,
+    /// Quitting waits for the user to confirm it
+    confirming_quit: bool,
+    /// Chat messages sent whose replies haven't arrived, kept if the app quits first
+    unanswered: Vec<String>,
+    /// What the next chat message is; `/edit` makes it an edit of the last one
+    next_revision: Revision,
+    /// Whether expired messages are being summarized, so they're archived once
+    archiving: bool,
+    /// Aliases and functions from the pet's latest reply that suggested any
+    alias_suggestions: Vec<AliasDefinition>,
+    mode: Mode,
+    /// Where the pet's state is persisted
+    store: Box<dyn StateStore>,
+    /// Modification times of config.toml and the project file as of the last (re)load
+    config_mtimes: (Option<SystemTime>, Option<SystemTime>),
+    /// Plain-text record of this session's conversation, if enabled
+    transcript: Option<Transcript>,
+    /// Where background work (LLM replies, command output, the shell hook) reports back
+    events: EventSender,
+    /// WebAssembly plugins, if enabled
+    plugins: PluginHost,
+    /// Chat goes through `pawshell daemon`, which records it, instead of our own backend
+    attached_to_daemon: bool,
+    /// Rhai scripts, if enabled
+    scripts: ScriptHost,
+    last_plugin_tick: Instant,
+    /// A program `/log` or `/commitmsg edit` wants to run, for the terminal to pick up
+    pending_handoff: Option<Handoff>,
+    /// The latest commit message from `/commitmsg`
+    commit_draft: Option<String>,
+    /// Opened on the first copy; on X11 the copied text only lasts as long as it does
+    clipboard: Option<arboard::Clipboard>,
+    /// Desktop notifications for when the user is away
+    pub notifier: Notifier,
+    /// The running `/pomodoro` timer
+    pomodoro: Option<Pomodoro>,
+    /// The running `/focus` session
+    focus: Option<FocusSession>,
+    /// Whether the user was already sent off during this break
+    break_nudged: bool,
+    /// The latest weather, possibly from the cache
+    weather: Option<Weather>,
+    /// Whether a weather fetch is on its way
+    weather_fetching: bool,
+    /// Whether the greeting mentioned the weather yet
+    weather_greeted: bool,
+    /// When the weather was last fetched, so a failing provider isn't asked every tick
+    last_weather_attempt: Option<Instant>,
+    /// CPU, memory and disk sampling, with `system_monitor` on
+    system_monitor: Option<SystemMonitor>,
+    last_system_sample: Instant,
+    /// The laptop battery, with `battery_awareness` on and a battery present
+    battery: Option<Battery>,
+    last_battery_check: Option<Instant>,
+    /// Whether the pet already asked to be plugged in since the battery last charged
+    battery_warned: bool,
+    /// Whether the last probe couldn't reach the backend
+    offline: bool,
+    probing_backend: bool,
+    last_backend_probe: Option<Instant>,
+    /// Whether replies are read out loud
+    speaking: bool,
+    /// The speech in progress; aborting it stops the engine
+    speech: Option<tokio::task::JoinHandle<()>>,
+    /// How many replies back the last replay went, and when
+    last_replay: Option<(usize, Instant)>,
+    /// The microphone recording in progress, stopped by the voice key
+    recording: Option<std::process::Child>,
+    /// A `/portrait` is being painted
+    painting: bool,
+    /// A picture to draw over the UI until a key is pressed
+    pending_picture: Option<PathBuf>,
+    /// The `/quiz` question waiting for an answer
+    quiz: Option<&'static Skill>,
+    /// The `/flashcards` review in progress
+    review: Option<Review>,
+    /// The `/review` waiting for replies
+    code_review: Option<CodeReview>,
+    /// Messages and reply times of this session, for `/stats`
+    session_stats: SessionStats,
+    /// Whether the pet already pointed out how late it is this session
+    late_night_nudged: bool,
+    /// Time at the keyboard since the last break
+    breaks: BreakTimer,
+    /// Root of the project the pet was launched in, whose failures it remembers
+    project_root: Option<PathBuf>,
+    /// `/dotfiles` suggestions that can be saved as annotated copies
+    dotfile_advice: Vec<Advice>,
+    /// The latest version of the `/script` being written
+    script: Option<ScriptDraft>,
+    /// Candidates offered by `/adopt`
+    litter: Vec<Pedigree>,
+    /// The Ollama model `/model pull` is downloading
+    model_pull: Option<String>,
+    /// `/compare`s started, to tell their answers apart
+    comparisons: u64,
+    /// A budget is used up and the local model answers instead of OpenAI
+    over_budget: bool,
+    /// Budget periods already warned about, by when they started
+    budget_notices: Vec<(Period, chrono::NaiveDate)>,
+    /// Models without a known price, mentioned once
+    unpriced_models: Vec<String>,
+    /// The session new exchanges are counted toward, once there's one
+    chat_session: Option<u64>,
+    /// The session reopened from `/sessions`, which the chat is limited to
+    shown_session: Option<u64>,
+    /// Sessions a title was asked for this run
+    titles_requested: Vec<u64>,
+    /// The macro being recorded
+    recording_macro: Option<Recording>,
+    /// `macro_record_key` was pressed and the next key names the macro
+    naming_macro: bool,
 }
 
 impl App {
-    pub fn new() -> Self {
-        config_path::ensure_config_dir().expect("Failed to create config directory");
-        let config_path = config_path::get_config_file_path(None);
-        let config: config::Config = if config_path.exists() {
-            std::fs::read_to_string(&config_path)
-                .and_then(|content| toml::from_str(&content).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
-                .unwrap_or_default()
-        } else {
-            let default_config = config::Config::default();
-            let toml = toml::to_string(&default_config).expect("Failed to serialize config");
-            std::fs::write(&config_path, toml).expect("Failed to write default config");
-            default_config
-        };
-        let mut state: PetState = confy::load("petcli", None).unwrap_or_default();
+    pub fn new(mode: Mode, events: EventSender) -> Self {
+        let (config, config_problems) = config::Config::load_checked();
+        let config_mtimes = config_mtimes(&config_path::get_config_file_path(None));
+        let (mut store, storage_warning) = storage::open(&config);
+        let (mut state, load_warning) = match store.load() {
+            Ok(state) => (state, store.take_notice()),
+            Err(e) => (PetState::default(), Some(format!("Couldn't load my state, so I'm starting fresh.\n{}", e.report()))),
+        };
         state.name = config.pet_name.clone();
 
-        let llm: Box<dyn LLMBackend> = match config.llm_provider {
-            LLMProvider::OpenAI => {
-                let api_key = std::env::var("OPENAI_API_KEY")
-                    .expect("OPENAI_API_KEY not found in environment variables");
-                Box::new(OpenAIBackend::new(api_key))
-            },
-            LLMProvider::Ollama => {
-                Box::new(OllamaBackend::new(
-                    config.ollama_url.clone(),
-                    config.ollama_model.clone(),
-                ))
+        // Without a backend the pet answers by itself, saying why with each reply
+        let (llm, llm_warning): (Box<dyn LLMBackend>, _) = match llm::build(&config) {
+            Ok(llm) => (llm, None),
+            Err(e) => {
+                let report = e.report();
+                (Box::new(UnavailableBackend::new(&config, e)), Some(report))
             }
         };
 
-        let mut ui = AppUI::new();
+        let mut ui = AppUI::new(locale::strings(config.language.as_deref()).welcome);
+        ui.compact = mode == Mode::Popup;
+        ui.vi = (config.editing_mode == EditingMode::Vi).then(Vi::default);
 
         // Load chat history into messages
-        for (user_msg, pet_response) in state.chat_history.iter() {
-            ui.add_message(format!("You: {}", user_msg));
-            ui.add_message(format!("{}: {}", state.name, pet_response));
+        replay_chat_history(&mut ui, &state, None);
+        if !config_problems.is_empty() {
+            ui.add_message(format!(
+                "{}: Your config has {} problem(s); run `pawshell doctor` for details.",
+                state.name,
+                config_problems.len()
+            ));
+        }
+        if let Some(project_file) = project::find() {
+            ui.add_message(format!("{}: Using the project settings in {}", state.name, project_file.display()));
+        }
+        for warning in storage_warning.into_iter().chain(load_warning).chain(llm_warning) {
+            ui.add_message(format!("{}: {}", state.name, warning));
+        }
+        // What was left unsent when the TUI last quit goes back in the input
+        if mode == Mode::Full {
+            if let Some(draft) = state.input_draft.take() {
+                ui.input.set(draft);
+            }
+        }
+
+        #[cfg(unix)]
+        let attached_to_daemon = daemon::is_running();
+        #[cfg(not(unix))]
+        let attached_to_daemon = false;
+        if attached_to_daemon {
+            ui.add_message(format!("{}: The pet daemon is running, so our chats go through it.", state.name));
+        }
+
+        // The main instance owns the hook socket (unless the daemon does); a popup must not take it over
+        #[cfg(unix)]
+        if mode == Mode::Full && attached_to_daemon {
+            daemon::subscribe(events.clone());
+        } else if mode == Mode::Full {
+            // Without the socket the pet just doesn't see commands live
+            if let Err(e) = hook::listen(events.clone()) {
+                tracing::warn!("not listening for shell hook commands: {}", e);
+            }
         }
 
-        let mut app = Self { ui, state, llm, recent_commands: Vec::new(), config };
+        let pattern_detector = PatternDetector::new(config.suggestion_cooldown_minutes);
+        let workspace = if config.include_workspace_context {
+            WorkspaceContext::detect()
+        } else {
+            None
+        };
+        let environment = config.include_system_context.then(SystemEnvironment::detect);
+        let frequent_directories = if config.zoxide_context { FrequentDirectories::load() } else { None };
+        let redactor = Redactor::new(&config.redaction_patterns);
+        let denylist = Denylist::new(&config.exec_denylist);
+        let transcript = config.write_transcripts.then(Transcript::new);
+        let (art, _) = PetArt::load(&config);
+        let plugins = load_plugins(&config, &mut ui, &state.name);
+        let scripts = load_scripts(&config, &mut ui, &state.name);
+
+        let mut app = Self {
+            ui,
+            state,
+            llm,
+            recent_commands: Vec::new(),
+            config,
+            art,
+            failure_streak: 0,
+            pattern_detector,
+            pending_exec: None,
+            confirming_quit: false,
+            unanswered: Vec::new(),
+            next_revision: Revision::Original,
+            archiving: false,
+            alias_suggestions: Vec::new(),
+            redactor,
+            denylist,
+            workspace,
+            environment,
+            frequent_directories,
+            mode,
+            store,
+            config_mtimes,
+            transcript,
+            events,
+            plugins,
+            attached_to_daemon,
+            scripts,
+            last_plugin_tick: Instant::now(),
+            pending_handoff: None,
+            commit_draft: None,
+            clipboard: None,
+            notifier: Notifier::new(),
+            pomodoro: None,
+            focus: None,
+            break_nudged: false,
+            weather: None,
+            weather_fetching: false,
+            weather_greeted: false,
+            last_weather_attempt: None,
+            system_monitor: None,
+            last_system_sample: Instant::now(),
+            battery: None,
+            last_battery_check: None,
+            battery_warned: false,
+            offline: false,
+            probing_backend: false,
+            last_backend_probe: None,
+            speaking: false,
+            speech: None,
+            last_replay: None,
+            recording: None,
+            painting: false,
+            pending_picture: None,
+            quiz: None,
+            review: None,
+            code_review: None,
+            session_stats: SessionStats::new(clock::now()),
+            late_night_nudged: false,
+            breaks: BreakTimer::new(clock::now()),
+            project_root: None,
+            dotfile_advice: Vec::new(),
+            script: None,
+            litter: Vec::new(),
+            model_pull: None,
+            comparisons: 0,
+            over_budget: false,
+            budget_notices: Vec::new(),
+            unpriced_models: Vec::new(),
+            chat_session: None,
+            shown_session: None,
+            titles_requested: Vec::new(),
+            recording_macro: None,
+            naming_macro: false,
+        };
+        app.speaking = app.config.speak_replies;
+        if app.config.system_monitor {
+            app.system_monitor = Some(SystemMonitor::new());
+        }
+        if app.config.weather {
+            app.weather = weather::cached();
+            app.greet_with_weather();
+        }
+        if app.config.storage_backend == StorageBackend::Shared {
+            app.greet_teammate();
+        }
+        if app.config.project_greeting && mode == Mode::Full {
+            app.greet_project();
+        }
+        if mode == Mode::Full && !app.attached_to_daemon && app.state.pedigree.is_none() && app.state.chat_history.is_empty() {
+            app.adopt("");
+        }
+        app.check_unlocks();
+        for (setting, key) in [("undo_key", &app.config.undo_key), ("redo_key", &app.config.redo_key)] {
+            if events::parse_key(key).is_none() {
+                app.ui.add_message(format!(
+                    "{}: *tilts head* I don't know the key \"{}\" for {}; try something like ctrl+z.",
+                    app.state.name, key, setting
+                ));
+            }
+        }
+        if app.config.voice_input && events::parse_key(&app.config.voice_key).is_none() {
+            app.ui.add_message(format!(
+                "{}: *tilts head* I don't know the key \"{}\" for voice input; try something like F9 or ctrl+space.",
+                app.state.name, app.config.voice_key
+            ));
+        }
         app.load_shell_history();
         app
     }
 
     pub fn load_shell_history(&mut self) {
-        if let Some(home_dir) = dirs::home_dir() {
-            let history_files = vec![
-                home_dir.join(".zsh_history"),
-                home_dir.join(".bash_history"),
-                home_dir.join(".history"),
-            ];
-
-            for history_file in history_files {
-                if let Ok(lines) = read_lines(history_file) {
-                    for line in lines.flatten() {
-                        let cmd = clean_history_line(&line);
-                        if !cmd.is_empty() {
-                            self.recent_commands.push(cmd);
-                            if self.recent_commands.len() > self.config.command_history_limit {
-                                self.recent_commands.remove(0);
-                            }
-                        }
-                    }
-                    break;
-                }
-            }
-        }
+        self.recent_commands = self.read_history(self.config.command_history_limit);
+        self.failure_streak = history::failure_streak(&self.recent_commands);
     }
 
-    pub async fn handle_input(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    /// Reads up to `limit` of the most recent commands from the configured history source
+    fn read_history(&self, limit: usize) -> Vec<HistoryEntry> {
+        history::read_recent(&self.config, limit)
+    }
+
+    pub fn handle_input(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         if !self.ui.input.is_empty() {
-            let user_message = self.ui.input.clone();
+            let user_message = self.ui.input.text().to_string();
             
             if user_message.trim() == "/exit" {
-                self.ui.add_message(format!("{}: Goodbye! Take care! 👋", self.state.name));
+                self.ui.add_message(format!("{}: {}", self.state.name, self.strings().goodbye));
                 self.save_state()?;
                 return Ok(());
             }
             
             self.ui.add_message(format!("You: {}", user_message));
+            if let Some(recording) = self.recording_macro.as_mut() {
+                if !recording.record(&user_message) {
+                    self.ui.show_toast("The macro is full; that wasn't recorded");
+                }
+            }
 
             if user_message.starts_with('$') {
                 if let Some(cmd) = user_message.strip_prefix('$') {
-                    self.recent_commands.push(cmd.trim().to_string());
-                    if self.recent_commands.len() > 5 {
-                        self.recent_commands.remove(0);
+                    self.warn_if_dangerous(cmd.trim());
+                    self.push_recent_command(HistoryEntry::new(cmd, Some(clock::now())));
+                }
+            }
+
+            if let Some(cmd) = user_message.strip_prefix('!') {
+                self.request_exec(cmd.trim().to_string());
+                self.ui.input.clear();
+                return Ok(());
+            }
+
+            if let Some(choice) = user_message.trim().strip_prefix("/run") {
+                if choice.is_empty() || choice.starts_with(' ') {
+                    self.run_suggested_command(choice.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+            }
+
+            let (command, args) = user_message.trim().split_once(' ').unwrap_or((user_message.trim(), ""));
+            match command {
+                "/bookmark" => {
+                    self.add_bookmark(args.trim());
+                    self.save_state()?;
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/tldr" => {
+                    self.show_tldr(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/focus" => {
+                    self.start_focus(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/break" => {
+                    self.take_break(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/pomodoro" => {
+                    self.start_pomodoro(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/remind" => {
+                    if self.add_reminder(args.trim()) {
+                        self.save_state()?;
+                    }
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/todo" => {
+                    if self.update_todos(TodoCommand::parse(args)) {
+                        self.save_state()?;
+                    }
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/bookmarks" => {
+                    self.list_bookmarks(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/profile" => {
+                    self.switch_profile(args.trim())?;
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/speak" => {
+                    self.speak_command(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/learn" => {
+                    if self.learn(args.trim()) {
+                        self.save_state()?;
+                    }
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/flashcards" => {
+                    if self.flashcards_command(args.trim()) {
+                        self.save_state()?;
+                    }
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/quiz" => {
+                    self.quiz_command(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/wear" => {
+                    if self.wear(args.trim()) {
+                        self.save_state()?;
+                    }
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/portrait" => {
+                    self.portrait_command(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/art" => {
+                    self.choose_art(args.trim())?;
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/model" => {
+                    self.manage_model(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/budget" => {
+                    self.show_budget();
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/compare" => {
+                    self.compare(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/plugins" => {
+                    self.list_plugins();
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/scripts" => {
+                    self.list_scripts();
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/log" => {
+                    match logging::latest_file() {
+                        Some(path) => self.pending_handoff = Some(Handoff::Pager(path)),
+                        None => self.ui.add_message(format!(
+                            "{}: Nothing has been logged yet ({}).",
+                            self.state.name,
+                            logging::log_dir().display()
+                        )),
                     }
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/commitmsg" => {
+                    self.commit_message(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/gh" => {
+                    self.fetch_from_github(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/review" => {
+                    self.review_file(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/translate" => {
+                    self.translate(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/feedback" => {
+                    self.show_feedback(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/retry" => {
+                    self.retry();
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/edit" => {
+                    self.edit_last_message();
+                    return Ok(());
+                }
+                "/fork" => {
+                    self.fork_thread(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/threads" => {
+                    self.switch_thread(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/sessions" => {
+                    self.browse_sessions(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/macros" => {
+                    self.manage_macros(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/regex" => {
+                    let mut lab = RegexLab::new(args.trim());
+                    lab.editing_sample = !lab.pattern.is_empty();
+                    self.ui.regex_lab = Some(lab);
+                    self.ui.input.clear();
+                    return Ok(());
                 }
+                "/jq" | "/awk" => {
+                    let tool = if command == "/jq" { Tool::Jq } else { Tool::Awk };
+                    let mut playground = Playground::new(tool, args.trim());
+                    playground.editing_input = !playground.expression.is_empty();
+                    self.ui.playground = Some(playground);
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/cron" => {
+                    self.schedule_job(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/script" => {
+                    self.write_script(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/dotfiles" => {
+                    self.advise_on_dotfiles(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/adopt" => {
+                    self.adopt(args.trim());
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                "/adopt-alias" => {
+                    self.adopt_aliases(args.trim());
+                    self.save_state()?;
+                    self.ui.input.clear();
+                    return Ok(());
+                }
+                _ => {}
             }
 
             if user_message.starts_with('/') {
                 match user_message.trim() {
                     "/stats" => {
-                        let stats = format!("Current Stats:\nMood: {:.0}%\nLast Interaction: {}\nChat History: {} messages",
+                        let mut stats = format!("Current Stats:\nMood: {:.0}%\nLast Interaction: {}\nChat History: {} messages\n{}",
                             self.state.mood * 100.0,
                             self.state.last_interaction.format("%Y-%m-%d %H:%M:%S UTC"),
-                            self.state.chat_history.len());
+                            self.state.chat_history.len(),
+                            pomodoro::summary(&self.state.focus_log, clock::local_now().date_naive()));
+                        stats.push_str(&format!("\n{}", breaks::summary(&self.state.break_log, clock::local_now().date_naive())));
+                        stats.push_str(&format!("\n{}", mood::history_summary(&self.state.mood_log, clock::now())));
+                        stats.push_str(&format!(
+                            "\n{}",
+                            usage::summary(&self.session_stats, &self.state.sessions, &self.state.chat_history, clock::now())
+                        ));
+                        if let Some(speed) = perf::by_model(&self.state.chat_history, clock::now() - chrono::Duration::days(30)) {
+                            stats.push_str(&format!("\n{}", speed));
+                        }
+                        if let Some(feeding) = &self.state.fed_by {
+                            stats.push_str(&format!("\nLast Fed: by {}, {} ago",
+                                feeding.user,
+                                reminders::format_duration(clock::now() - feeding.at)));
+                        }
                         self.ui.add_message(format!("{}: {}", self.state.name, stats));
                         self.ui.input.clear();
                         return Ok(());
                     },
+                    "/feed" => {
+                        self.give_treat();
+                        self.ui.add_message(format!("{}: {}", self.state.name, self.strings().treat));
+                        self.save_state()?;
+                        self.ui.input.clear();
+                        return Ok(());
+                    },
                     "/clear" => {
-                        self.ui.messages.clear();
+                        self.ui.clear_messages();
                         self.ui.add_message("Chat window cleared.".to_string());
                         self.ui.input.clear();
                         return Ok(());
                     },
                     "/purge" => {
                         self.state.chat_history.clear();
-                        self.ui.messages.clear();
+                        self.ui.clear_messages();
                         self.ui.add_message("Chat history has been purged from disk.".to_string());
                         self.save_state()?;
                         self.ui.input.clear();
                         return Ok(());
                     },
+                    "/analyze" => {
+                        let stats = CommandStats::from_entries(&self.read_history(ANALYZE_HISTORY_LIMIT), 10);
+                        self.ui.add_message(format!("{}: {}", self.state.name, stats.summary()));
+                        self.send_prompt(LlmRequest::Analyze, stats.alias_prompt());
+                        self.ui.input.clear();
+                        return Ok(());
+                    },
+                    "/report" => {
+                        self.weekly_report();
+                        self.ui.input.clear();
+                        return Ok(());
+                    },
+                    "/card" => {
+                        let message = self.share_card();
+                        self.ui.add_message(format!("{}: {}", self.state.name, message));
+                        self.ui.input.clear();
+                        return Ok(());
+                    },
+                    "/privacy" => {
+                        let report = self.privacy_report();
+                        self.ui.add_message(format!("{}: {}", self.state.name, report));
+                        self.ui.input.clear();
+                        return Ok(());
+                    },
                     "/help" => {
                         let help = "Available Commands:\n\
                         /stats - Display current pet statistics\n\
+                        /feed - Give me a treat\n\
                         /clear - Clear chat window\n\
                         /purge - Remove all chat history\n\
+                        /analyze - Analyze command usage and suggest aliases\n\
+                        /privacy - Show what was redacted from prompts\n\
+                        /run [n] - Run the (nth) command from the last reply\n\
+                        /bookmark [cmd # note] - Save a command (default: from the last reply)\n\
+                        /bookmarks [query] - List or search saved commands\n\
+                        /speak [on|off|stop] - Read replies out loud (alone: replay the last one, or press Alt+S)\n\
+                        /remind \"what\" in 25m|at 17:30 - Remind you of something (no arguments: list)\n\
+                        /todo [task|done n|rm n|clear] - Show or update your to-do list\n\
+                        /focus [duration|off] - Do not disturb: no suggestions, notifications or idle chatter while the pet naps\n\
+                        /break [snooze [minutes]] - How long you've been at it, or put off the next stretch nudge\n\
+                        /pomodoro [work/break|stop] - Start a pomodoro timer (default 25/5 minutes)\n\
+                        /adopt [n] - Meet a new litter of pets, or adopt one of them\n\
+                        /adopt-alias [n|name|all] - Add suggested aliases to your alias file\n\
+                        /profile [name] - List profiles or switch to another one\n\
+                        /art [name] - List the art gallery or change how I look\n\
+                        /model [pull <name>] - Show the backend and model, or download an Ollama model and switch to it\n\
+                        /budget - Show estimated OpenAI spending today and this month against your budgets\n\
+                        /compare <model> <model> [question] - Put a question, or your last message, to two models side by side (openai/<model> for OpenAI's)\n\
+                        /plugins - List loaded plugins and their hooks\n\
+                        /scripts - List loaded scripts and the commands they add\n\
+                        /log - Open the log in a pager\n\
+                        /commitmsg [edit|write|copy] - Draft a commit message for the staged changes\n\
+                        /gh pr|issue <n> - Summarize a pull request or issue\n\
+                        /translate <lang> [text] - Translate text, or my last reply\n\
+                        /review <path> [focus] - Review a file before you push it\n\
+                        /dotfiles [yes|name|save] - Get suggestions for your dotfiles\n\
+                        /script <description|change> - Write a shell script, then refine it\n\
+                        /script save <path>|new|drop - Save, restart or discard the script\n\
+                        /feedback [clear] - What your 👍/👎 (Alt+Up/Alt+Down) on replies taught the pet, or forget it\n\
+                        /retry - Ask your last message again for a new reply\n\
+                        /edit - Put your last message back in the input to change and send again\n\
+                        /fork <name> - Branch the conversation here into a thread with its own context\n\
+                        /threads [name] - List the conversation's threads, or switch to one (main is the original)\n\
+                        /sessions [new] - Browse past chat sessions to reopen and continue one, or start a new session\n\
+                        /macros [delete <letter>] - List recorded macros, or delete one\n\
+                        /regex [pattern] - Try out a regex on sample text, with live highlighting\n\
+                        /jq [expr], /awk [program] - Try out an expression on sample input\n\
+                        /cron [timer] <description|schedule> - Write a crontab line or systemd timer, or check a schedule\n\
+                        /tldr <cmd>|--update - Show examples for a command from tldr-pages\n\
+                        /card - Make a shareable card of me (ANSI text and PNG)\n\
+                        /report - Write a report of your week in the terminal (saved as Markdown)\n\
+                        /learn [vim|pipes|git] - Show your learning goals, or add or drop a track\n\
+                        /quiz [skip] - Get quizzed on the next skill to learn\n\
+                        /flashcards [stop] - Review the flashcards that are due\n\
+                        /wear [name|none] - List my accessories, or put one on or take it off\n\
+                        /portrait [last] - Have my portrait painted (last: show the latest one again)\n\
+                        !<cmd>  - Run a shell command\n\
                         /help  - Show this help message\n\
                         /exit  - Exit the application";
+                        let mut help = help.to_string();
+                        for command in self.scripts.commands() {
+                            help.push_str(&format!("\n/{} - {} (from {}.rhai)", command.name, command.description, command.script));
+                        }
                         self.ui.add_message(format!("{}: {}", self.state.name, help));
                         self.ui.input.clear();
                         return Ok(());
                     },
                     "/exit" => {
-                        self.ui.add_message(format!("{}: Goodbye! Take care! 👋", self.state.name));
+                        self.ui.add_message(format!("{}: {}", self.state.name, self.strings().goodbye));
                         self.save_state()?;
                         return Ok(());
                     },
@@ -175,64 +887,3407 @@ impl App {
                 }
             }
 
-            self.state.last_interaction = Utc::now();
-            self.state.mood = (self.state.mood + 0.1).min(1.0);
+            if let Some(name) = command.strip_prefix('/').filter(|name| self.scripts.command(name).is_some()) {
+                let output = self.scripts.run_command(&self.state, name, args.trim());
+                self.apply_script_output(output);
+                self.save_state()?;
+                self.ui.input.clear();
+                return Ok(());
+            }
 
-            let response = match self.llm.generate_response(&self.llm.format_prompt(&user_message, Some(&self.recent_commands))).await {
-                Ok(response) => {
-                    self.llm.add_to_history(user_message.clone(), response.clone());
-                    response
+            if self.review.is_some() {
+                if self.answer_flashcard(&user_message) {
+                    self.save_state()?;
                 }
-                Err(_) => {
-                    if user_message.to_lowercase().contains("treat") {
-                        self.state.mood = (self.state.mood + 0.2).min(1.0);
-                        "*purrs happily* Thank you for the treat! 😊".to_string()
-                    } else if user_message.to_lowercase().contains("play") {
-                        self.state.mood = (self.state.mood + 0.15).min(1.0);
-                        "*bounces around excitedly* I love to play! 🐱".to_string()
-                    } else if self.state.mood > 0.8 {
-                        "*purrs contentedly* 😊".to_string()
-                    } else if self.state.mood > 0.4 {
-                        "*looks at you curiously* Meow?".to_string()
-                    } else {
-                        "*seems a bit distant* ...".to_string()
-                    }
+                self.ui.input.clear();
+                return Ok(());
+            }
+
+            if let Some(skill) = self.quiz.take() {
+                if self.answer_quiz(skill, &user_message) {
+                    self.save_state()?;
                 }
-            };
+                self.ui.input.clear();
+                return Ok(());
+            }
 
-            self.ui.add_message(format!("{}: {}", self.state.name, response));
-            self.state.chat_history.push((user_message, response));
+            self.state.last_interaction = clock::now();
+            self.state.cheer_up(0.1);
+            self.session_stats.record_message(clock::now());
+            self.nudge_during_break();
+
+            let plugin_output = self.plugins.on_message(&user_message);
+            self.show_plugin_output(plugin_output);
+            let script_output = self.scripts.on_message(&self.state, &user_message);
+            self.apply_script_output(script_output);
+            if let Some(workspace) = self.workspace.as_mut() {
+                workspace.refresh();
+            }
+            let revision = std::mem::take(&mut self.next_revision);
+            self.send_chat(user_message, revision);
             self.ui.input.clear();
             self.save_state()?;
         }
         Ok(())
     }
 
-    pub fn update(&mut self) {
-        let now = Utc::now();
-        let hours_since_last = (now - self.state.last_interaction).num_hours() as f32;
-        self.state.mood = (self.state.mood - (hours_since_last * 0.1)).max(0.1).min(1.0);
+    /// Sends a chat message to the backend, or to the daemon's pet
+    fn send_chat(&mut self, user_message: String, revision: Revision) {
+        if self.attached_to_daemon {
+            self.ask_daemon(user_message);
+            return;
+        }
+        self.unanswered.push(user_message.clone());
+        self.look_up_docs(DocsRequest::Chat { user_message, revision });
+    }
+
+    /// Looks up docs for a chat prompt on its own task, since that reads files
+    /// and may run `man`; the prompt is built when `AppEvent::DocsFound` arrives
+    fn look_up_docs(&mut self, request: DocsRequest) {
+        let message = match &request {
+            DocsRequest::Chat { user_message, .. } => user_message,
+            DocsRequest::Compare { question, .. } => question,
+        };
+        let docs = ChatContext::look_up_docs(&self.config, message);
+        let events = self.events.clone();
+        self.ui.pending_replies += 1;
+        tokio::spawn(async move {
+            let docs = docs.await;
+            let _ = events.send(AppEvent::DocsFound { request, docs });
+        });
     }
 
-    pub fn save_state(&self) -> Result<(), confy::ConfyError> {
-        confy::store("petcli", None, &self.state)
+    /// The last thing said in the conversation on screen
+    fn last_user_message(&self) -> Option<String> {
+        last_exchange(&self.state, self.shown_session).map(|message| message.user_message.clone())
+    }
+
+    /// `/retry` asks the last message again for a new reply
+    ///
+    /// The old reply stays in the history, and the new one is marked as
+    /// regenerated.
+    fn retry(&mut self) {
+        let Some(user_message) = self.last_user_message() else {
+            self.ui.add_message(format!("{}: There's nothing to ask again yet.", self.state.name));
+            return;
+        };
+        self.ui.add_message(format!("You: {}", user_message));
+        self.send_chat(user_message, Revision::Regenerated);
     }
-}
 
-fn clean_history_line(line: &str) -> String {
-    if line.starts_with(':') {
-        if let Some(cmd) = line.split(';').last() {
-            return cmd.trim().to_string();
+    /// `/edit` puts the last message back in the input; what's sent from it is
+    /// marked as an edit
+    fn edit_last_message(&mut self) {
+        match self.last_user_message() {
+            Some(user_message) => {
+                self.ui.input.set(user_message);
+                self.next_revision = Revision::Edited;
+                self.ui.show_toast("Edit your last message and press Enter to ask again");
+            }
+            None => {
+                self.ui.input.clear();
+                self.ui.add_message(format!("{}: There's nothing to edit yet.", self.state.name));
+            }
         }
     }
-    if let Some(cmd) = line.split_whitespace().last() {
-        cmd.trim().to_string()
-    } else {
-        line.trim().to_string()
+
+    /// The prompt for a chat message, with the conversation so far and all
+    /// the context that applies to it
+    fn chat_prompt(&mut self, user_message: &str, docs: Option<String>) -> Prompt {
+        let plugin_context = self.plugins.context();
+        let shared = ChatContext::gather(&self.config, &self.state, &self.recent_commands, docs);
+        let context = PromptContext {
+            workspace: self.workspace.as_ref(),
+            environment: self.environment.as_ref(),
+            frequent_directories: self.frequent_directories.as_ref(),
+            weather: self.weather.as_ref().filter(|_| self.config.weather && weather::is_outdoor_question(user_message)),
+            battery: self.battery.as_ref(),
+            plugins: plugin_context.as_deref(),
+            ..shared.prompt_context(&self.config, &self.state, user_message)
+        };
+        self.llm.format_prompt(user_message, &context)
+    }
+
+    /// Reacts to everything on the bus except key presses, which `terminal` handles
+    pub async fn handle_event(&mut self, event: AppEvent) {
+        match event {
+            AppEvent::Tick => {
+                self.update();
+                self.record_mood();
+                self.notifier.check_mood(&self.config, &self.state.name, self.state.mood);
+                self.fire_reminders();
+                self.advance_pomodoro();
+                self.advance_focus();
+                self.refresh_weather();
+                self.check_battery();
+                self.probe_backend();
+                // The pet naps through /focus instead of speaking up by itself
+                if self.focus.is_none() {
+                    self.sample_system_load();
+                    self.suggest_workflow();
+                    self.make_weekly_report_if_due();
+                    self.remind_flashcards();
+                    self.notice_late_night();
+                    self.nudge_to_stretch();
+                    if self.last_plugin_tick.elapsed() >= PLUGIN_TICK_INTERVAL {
+                        self.last_plugin_tick = Instant::now();
+                        let output = self.plugins.on_tick();
+                        self.show_plugin_output(output);
+                        let output = self.scripts.on_tick(&self.state);
+                        self.apply_script_output(output);
+                    }
+                }
+            }
+            AppEvent::LlmResponse { request, result, provider, model, latency } => {
+                self.ui.pending_replies = self.ui.pending_replies.saturating_sub(1);
+                if let Ok(generation) = &result {
+                    self.charge(provider, &model, generation);
+                }
+                match request {
+                    LlmRequest::Chat { user_message, revision } => {
+                        if let Some(index) = self.unanswered.iter().position(|message| *message == user_message) {
+                            self.unanswered.remove(index);
+                        }
+                        if let Ok(generation) = &result {
+                            self.notifier.reply_arrived(&self.config, &self.state.name, latency, &generation.text);
+                        }
+                        let result = result.map(|generation| (generation, provider, model, latency));
+                        if let Err(e) = self.receive_chat_reply(user_message, revision, result) {
+                            self.ui.add_message(format!("{}: Couldn't save our chat.\n{}", self.state.name, e.report()));
+                        }
+                    }
+                    LlmRequest::Analyze => {
+                        let suggestions = match result {
+                            Ok(generation) => generation.text,
+                            Err(e) => {
+                                self.report_llm_error(&e);
+                                self.strings().no_aliases.to_string()
+                            }
+                        };
+                        self.ui.add_message(format!("{}: {}", self.state.name, suggestions));
+                        self.remember_alias_suggestions(&suggestions);
+                    }
+                    LlmRequest::CommitMessage => match result {
+                        Ok(generation) => {
+                            let draft = commit::clean_reply(&generation.text);
+                            self.ui.add_message(format!(
+                                "{}: Here's a commit message for your staged changes:\n\n{}\n\n\
+                                /commitmsg edit to change it, write to save it as COMMIT_EDITMSG, or copy to copy it.",
+                                self.state.name, draft
+                            ));
+                            self.commit_draft = Some(draft);
+                        }
+                        Err(e) => self.report_llm_error(&e),
+                    },
+                    LlmRequest::WeeklyReport { facts } => self.receive_weekly_report(&facts, result),
+                    LlmRequest::SessionTitle { session } => self.receive_session_title(session, result),
+                    LlmRequest::RetentionSummary { exchanges } => self.receive_retention_summary(exchanges, result),
+                    LlmRequest::CodeReview { chunk } => self.receive_code_review(chunk, result),
+                    LlmRequest::Translation { language, original } => match result {
+                        Ok(generation) => self.ui.add_message(format!(
+                            "{}: {}",
+                            self.state.name,
+                            translate::render(&language, &original, &generation.text)
+                        )),
+                        Err(e) => self.report_llm_error(&e),
+                    },
+                    LlmRequest::PlaygroundFix { expression } => match result {
+                        Ok(generation) => self.receive_playground_fix(&expression, &generation.text),
+                        Err(e) => self.report_llm_error(&e),
+                    },
+                    LlmRequest::RegexExplanation { pattern } => {
+                        let text = match result {
+                            Ok(generation) => generation.text.trim().to_string(),
+                            Err(e) => format!("Couldn't get an explanation: {}", e),
+                        };
+                        if let Some(lab) = self.ui.regex_lab.as_mut().filter(|lab| lab.pattern == pattern) {
+                            lab.explanation = Some(text.clone());
+                        }
+                        self.ui.add_message(format!("{}: `{}`\n{}", self.state.name, pattern, text));
+                    }
+                    LlmRequest::Cron { timer } => match result {
+                        Ok(generation) => self.ui.add_message(format!(
+                            "{}: {}\n\n{}",
+                            self.state.name,
+                            generation.text.trim(),
+                            cron::check_reply(&generation.text, timer, clock::local_now().naive_local())
+                        )),
+                        Err(e) => self.report_llm_error(&e),
+                    },
+                    LlmRequest::Script { description, changes } => match result {
+                        Ok(generation) => self.receive_script(description, changes, &generation.text),
+                        Err(e) => self.report_llm_error(&e),
+                    },
+                    LlmRequest::DotfileAdvice { file, original } => match result {
+                        Ok(generation) => self.receive_dotfile_advice(file, original, generation.text),
+                        Err(e) => self.report_llm_error(&e),
+                    },
+                    LlmRequest::GitHubSummary => match result {
+                        Ok(generation) => self.ui.add_message(format!("{}: {}", self.state.name, generation.text)),
+                        Err(e) => self.report_llm_error(&e),
+                    },
+                    // Unprompted suggestions are a nicety; stay quiet if the backend is unavailable
+                    LlmRequest::Suggestion => match result {
+                        Ok(generation) => {
+                            self.ui.add_message(format!("{}: 💡 {}", self.state.name, generation.text));
+                            self.remember_alias_suggestions(&generation.text);
+                        }
+                        Err(e) => tracing::debug!("no suggestion: {}", e),
+                    },
+                }
+            }
+            AppEvent::GitHubFetched { item, number, result } => {
+                self.ui.pending_replies = self.ui.pending_replies.saturating_sub(1);
+                match result {
+                    Ok(content) => self.send_prompt(LlmRequest::GitHubSummary, github::summary_prompt(item, &number, &content)),
+                    Err(e) => self.ui.add_message(format!("{}: Couldn't fetch {} {}: {}", self.state.name, item.describe(), number, e)),
+                }
+            }
+            AppEvent::DocsFound { request, docs } => {
+                self.ui.pending_replies = self.ui.pending_replies.saturating_sub(1);
+                match request {
+                    DocsRequest::Chat { user_message, revision } => {
+                        let prompt = self.chat_prompt(&user_message, docs);
+                        self.send_prompt(LlmRequest::Chat { user_message, revision }, prompt);
+                    }
+                    DocsRequest::Compare { question, models, backends } => self.start_comparison(question, models, backends, docs),
+                }
+            }
+            AppEvent::PlaygroundRan { revision, result } => {
+                if let Some(playground) = self.ui.playground.as_mut().filter(|playground| playground.revision == revision) {
+                    playground.output = Some(result);
+                }
+            }
+            AppEvent::ModelPullProgress(progress) => {
+                if let Some(model) = &self.model_pull {
+                    self.ui.download = Some((format!("Pulling {}: {}", model, progress.status), progress.ratio()));
+                }
+            }
+            AppEvent::ModelPulled { model, result } => self.finish_model_pull(model, result),
+            AppEvent::CompareAnswered { id, side, result, latency } => {
+                self.ui.pending_replies = self.ui.pending_replies.saturating_sub(1);
+                if let Ok(generation) = &result {
+                    let model = self.ui.comparison.as_ref().filter(|comparison| comparison.id == id).map(|comparison| comparison.answers[side].model.clone());
+                    if let Some(model) = model.as_deref().and_then(|model| model.strip_prefix(compare::OPENAI_PREFIX)) {
+                        self.charge("openai", model, generation);
+                    }
+                }
+                if let Some(answer) = self.ui.comparison.as_mut().filter(|comparison| comparison.id == id).map(|comparison| &mut comparison.answers[side]) {
+                    answer.speed = result.as_ref().ok().map(|generation| perf::Speed::measure(generation, latency));
+                    answer.reply = Some(result.map(|generation| generation.text).map_err(|e| e.to_string()));
+                }
+            }
+            AppEvent::TldrUpdated { result, command } => {
+                self.ui.pending_replies = self.ui.pending_replies.saturating_sub(1);
+                match result {
+                    Ok(count) => {
+                        self.ui.add_message(format!("{}: *licks a paw* Got {} tldr pages; they work offline now.", self.state.name, count));
+                        if let Some(command) = command {
+                            self.show_tldr(&command);
+                        }
+                    }
+                    Err(e) => self.ui.add_message(format!("{}: {}", self.state.name, e)),
+                }
+            }
+            AppEvent::BackendProbed(reachable) => {
+                self.probing_backend = false;
+                self.set_offline(!reachable);
+            }
+            AppEvent::PortraitPainted(result) => {
+                self.painting = false;
+                self.receive_portrait(result);
+            }
+            AppEvent::Transcribed(result) => {
+                self.ui.voice = None;
+                match result {
+                    Ok(text) if text.is_empty() => self.ui.show_toast("🎙 Didn't catch that"),
+                    Ok(text) => {
+                        if !self.ui.input.is_empty() && !self.ui.input.text().ends_with(' ') {
+                            self.ui.input.append(" ");
+                        }
+                        self.ui.input.append(&text);
+                    }
+                    Err(e) => {
+                        tracing::warn!("transcription failed: {}", e);
+                        self.ui.add_message(format!("{}: *flattens ears* I couldn't make that out: {}", self.state.name, e));
+                    }
+                }
+            }
+            AppEvent::WeatherFetched(result) => {
+                self.weather_fetching = false;
+                match result {
+                    Ok(weather) => {
+                        if let Err(e) = weather::save_cache(&weather) {
+                            tracing::warn!("couldn't cache the weather: {}", e);
+                        }
+                        self.weather = Some(weather);
+                        self.greet_with_weather();
+                    }
+                    Err(e) => tracing::warn!("couldn't fetch the weather: {}", e),
+                }
+            }
+            AppEvent::CommandFinished { command, result, duration, cwd } => {
+                self.receive_command_output(command, result, duration, cwd)
+            }
+            #[cfg(unix)]
+            AppEvent::CommandReported(event) => self.receive_hook_event(event),
+            AppEvent::Notification(text) => self.ui.show_toast(text),
+            AppEvent::Focus(focused) => self.notifier.focus_changed(focused),
+            #[cfg(unix)]
+            AppEvent::Suspend => {}
+            AppEvent::Key(_) | AppEvent::Resize | AppEvent::Quit => {}
+        }
+    }
+
+    /// Shows and records the pet's reply to a chat message
+    ///
+    /// Without a reply from the backend the pet explains what went wrong and
+    /// answers with one of its built-in phrases, which isn't added to the
+    /// backend's conversation.
+    fn receive_chat_reply(
+        &mut self,
+        user_message: String,
+        revision: Revision,
+        result: Result<(Generation, &'static str, String, Duration), LlmError>,
+    ) -> Result<(), StorageError> {
+        let response = match &result {
+            Ok((generation, _, _, latency)) => {
+                self.session_stats.record_reply(*latency);
+                if !self.attached_to_daemon {
+                    self.llm.add_to_history(user_message.clone(), generation.text.clone());
+                }
+                generation.text.clone()
+            }
+            Err(e) => {
+                match e {
+                    // The title already says so
+                    LlmError::Network { .. } if self.offline => {}
+                    // Check now whether the backend is gone rather than at the next probe
+                    LlmError::Network { .. } => {
+                        self.report_llm_error(e);
+                        self.last_backend_probe = None;
+                    }
+                    _ => self.report_llm_error(e),
+                }
+                let strings = self.strings();
+                let lowercase = user_message.to_lowercase();
+                let mentions = |words: &[&str]| words.iter().any(|word| lowercase.contains(word));
+                if mentions(strings.treat_words) {
+                    self.give_treat();
+                    strings.treat.to_string()
+                } else if mentions(strings.play_words) {
+                    self.state.cheer_up(0.15);
+                    strings.play.to_string()
+                } else {
+                    match self.state.mood_level() {
+                        MoodLevel::Happy => strings.content.to_string(),
+                        MoodLevel::Content => strings.curious.to_string(),
+                        MoodLevel::Sad => strings.distant.to_string(),
+                    }
+                }
+            }
+        };
+
+        let mut shown = format!("{}: {}", self.state.name, safety::annotate(&response, &self.denylist));
+        if let (Ok((generation, _, _, latency)), true) = (&result, self.config.show_reply_speed) {
+            shown.push_str(&format!("\n{}", perf::Speed::measure(generation, *latency).footer()));
+        }
+        self.ui.add_message(shown);
+        if self.speaking {
+            self.say(&response);
+        }
+        self.remember_alias_suggestions(&response);
+        if let Some(transcript) = &self.transcript {
+            if let Err(e) = transcript.append(&self.state.name, &user_message, &response) {
+                self.ui.add_message(format!("{}: Couldn't write the transcript: {}", self.state.name, e));
+                self.transcript = None;
+            }
+        }
+        // The daemon saves its replies to the shared state itself
+        if self.attached_to_daemon && result.is_ok() {
+            return Ok(());
+        }
+        let mut message = ChatMessage::new(user_message, response);
+        if let Ok((generation, provider, model, latency)) = result {
+            message.provider = Some(provider.to_string());
+            message.model = Some(model);
+            message.prompt_tokens = generation.prompt_tokens;
+            message.completion_tokens = generation.completion_tokens;
+            message.latency_ms = Some(latency.as_millis() as u64);
+            message.first_token_ms = generation.first_token.map(|time| time.as_millis() as u64);
+            message.generation_ms = generation.generation_time.map(|time| time.as_millis() as u64);
+        }
+        message.revision = revision;
+        message.thread = self.state.current_thread.clone();
+        message.session = Some(self.count_exchange());
+        self.state.chat_history.push(message);
+        self.title_session();
+        self.check_unlocks();
+        self.apply_retention();
+        self.save_state()
+    }
+
+    /// Tells the user why the backend didn't answer and how to fix it
+    fn report_llm_error(&mut self, error: &LlmError) {
+        tracing::warn!(category = error.category(), "no reply: {}", error);
+        self.ui.add_message(format!("{}: {}", self.state.name, error.report()));
+        self.ui.show_toast(format!("No reply: {} error", error.category()));
+    }
+
+    /// Periodic work: config and state reloads and mood decay
+    fn update(&mut self) {
+        self.reload_config_if_changed();
+        self.reload_state_if_changed();
+        self.check_budget();
+
+        self.state.decay_mood(clock::now());
+    }
+
+    /// Gently points out chatting in the middle of the night, once a session
+    fn notice_late_night(&mut self) {
+        let now = clock::local_now();
+        if !self.config.late_night_nudges || self.late_night_nudged || !self.session_stats.is_up_late(&now) {
+            return;
+        }
+        self.late_night_nudged = true;
+        let streak = usage::late_night_streak(&self.state.chat_history, &now);
+        let nights = match streak {
+            0 | 1 => String::new(),
+            2 => " That's two nights in a row.".to_string(),
+            n => format!(" That's {} nights in a row now.", n),
+        };
+        self.ui.add_message(format!(
+            "{}: *yawns* It's {} already.{} Whatever you're fixing will still be there after some sleep, and it'll probably look easier. 🌙",
+            self.state.name,
+            now.format("%-H:%M"),
+            nights
+        ));
+    }
+
+    /// Notes a key press or shell command, logging the break it ends if
+    /// there was one
+    pub fn note_activity(&mut self) {
+        let Some(away) = self.breaks.activity(clock::now()) else {
+            return;
+        };
+        if !breaks::record(&mut self.state.break_log, clock::local_now().date_naive(), away) || self.attached_to_daemon {
+            return;
+        }
+        if let Err(e) = self.save_state() {
+            tracing::warn!("couldn't save the break log: {}", e);
+        }
+    }
+
+    /// Nudges to stretch after `break_after_minutes` without a break, unless
+    /// a pomodoro already takes care of breaks
+    fn nudge_to_stretch(&mut self) {
+        let after = chrono::Duration::minutes(self.config.break_after_minutes as i64);
+        if self.config.break_after_minutes == 0 || self.pomodoro.is_some() || !self.breaks.nudge_due(clock::now(), after) {
+            return;
+        }
+        let message = format!(
+            "*stretches all four legs* You've been at it for {} without a break. Stand up, roll your shoulders and look at something far away for a few minutes; I'll keep your spot. (/break snooze puts me off for {} minutes.)",
+            reminders::format_duration(self.breaks.stretch(clock::now())),
+            self.config.break_snooze_minutes
+        );
+        self.ui.show_toast("🧘 Time to stretch");
+        self.notifier.break_due(&self.config, &self.state.name, &message);
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+    }
+
+    /// `/break` tells how long it's been since the last break, and `/break
+    /// snooze [minutes]` holds off the next nudge
+    fn take_break(&mut self, args: &str) {
+        let now = clock::now();
+        let message = match args.split_once(' ').unwrap_or((args, "")) {
+            ("", _) => {
+                let stretch = reminders::format_duration(self.breaks.stretch(now));
+                match self.config.break_after_minutes {
+                    0 => format!("You've been at it for {} without a break.", stretch),
+                    after => format!("You've been at it for {} without a break; I nudge you after {} minutes.", stretch, after),
+                }
+            }
+            ("snooze", minutes) => {
+                let minutes = match minutes.trim() {
+                    "" => Some(self.config.break_snooze_minutes as i64),
+                    minutes => minutes.trim_end_matches('m').parse::<i64>().ok().filter(|&m| (1..=24 * 60).contains(&m)),
+                };
+                match minutes {
+                    Some(minutes) => {
+                        self.breaks.snooze(now, chrono::Duration::minutes(minutes));
+                        format!("*curls back up* Fine, I'll ask again in {} minutes.", minutes)
+                    }
+                    None => "Usage: /break snooze [minutes]".to_string(),
+                }
+            }
+            _ => "Usage: /break [snooze [minutes]]".to_string(),
+        };
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+    }
+
+    /// Adds to the mood history every so often, saving it right away so a
+    /// crash doesn't leave a gap
+    ///
+    /// The daemon owns the state while the app is attached to it.
+    fn record_mood(&mut self) {
+        if self.attached_to_daemon || !self.state.sample_mood(clock::now()) {
+            return;
+        }
+        if let Err(e) = self.save_state() {
+            tracing::warn!("couldn't save the mood history: {}", e);
+        }
+    }
+
+    /// Picks a command from the pet's last reply and asks to run it
+    fn run_suggested_command(&mut self, choice: &str) {
+        let suggestions = last_exchange(&self.state, self.shown_session)
+            .map(|message| exec::extract_commands(&message.response))
+            .unwrap_or_default();
+        let index = match choice {
+            "" => 0,
+            n => match n.parse::<usize>() {
+                Ok(n) if n > 0 => n - 1,
+                _ => {
+                    self.ui.add_message(format!("{}: Usage: /run [n]", self.state.name));
+                    return;
+                }
+            },
+        };
+        match suggestions.into_iter().nth(index) {
+            Some(command) if self.denylist.matches(&command) => self.ui.add_message(format!(
+                "{}: `{}` matches your exec_denylist, so I won't run it. Copy it into your shell if you're sure.",
+                self.state.name, command
+            )),
+            Some(command) => self.request_exec(command),
+            None => self.ui.add_message(format!(
+                "{}: *looks around* I didn't suggest a command like that in my last reply.",
+                self.state.name
+            )),
+        }
     }
-}
 
-fn read_lines(filename: PathBuf) -> io::Result<io::Lines<io::BufReader<File>>> {
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).lines())
-}
\ No newline at end of file
+    /// Bookmarks a command, or the first command of the last reply if none is given
+    fn add_bookmark(&mut self, args: &str) {
+        let bookmark = if args.is_empty() {
+            last_exchange(&self.state, self.shown_session).and_then(|message| {
+                let command = exec::extract_commands(&message.response).into_iter().next()?;
+                Some(Bookmark { command, description: Some(message.user_message.clone()), created: clock::now() })
+            })
+        } else {
+            Bookmark::parse(args)
+        };
+        let Some(bookmark) = bookmark else {
+            self.ui.add_message(format!(
+                "{}: Usage: /bookmark <cmd> [# description], or just /bookmark after I suggest a command",
+                self.state.name
+            ));
+            return;
+        };
+        let command = bookmark.command.clone();
+        let message = if bookmarks::add(&mut self.state.bookmarks, bookmark) {
+            format!("`{}` was already bookmarked; I updated it.", command)
+        } else {
+            format!("*tucks `{}` away* Bookmarked! Find it again with /bookmarks.", command)
+        };
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+    }
+
+    /// `/remind`: saves a reminder, or lists the pending ones without
+    /// arguments; returns whether the state changed
+    fn add_reminder(&mut self, args: &str) -> bool {
+        let now = clock::now();
+        if args.is_empty() {
+            let message = if self.state.reminders.is_empty() {
+                "No reminders. Set one with /remind \"stand up\" in 25m.".to_string()
+            } else {
+                let mut reminders = self.state.reminders.clone();
+                reminders.sort_by_key(|reminder| reminder.due);
+                let lines: Vec<String> = reminders.iter().map(|reminder| reminder.describe(now)).collect();
+                format!("Reminders:\n{}", lines.join("\n"))
+            };
+            self.ui.add_message(format!("{}: {}", self.state.name, message));
+            return false;
+        }
+        match Reminder::parse(args, &clock::local_now()) {
+            Ok(reminder) => {
+                let due = reminder.due.with_timezone(&chrono::Local).format("%H:%M");
+                self.ui.add_message(format!(
+                    "{}: *nods* I'll remind you to {} at {} (in {}).",
+                    self.state.name,
+                    reminder.text,
+                    due,
+                    reminders::format_duration(reminder.due - now)
+                ));
+                self.state.reminders.push(reminder);
+                true
+            }
+            Err(e) => {
+                self.ui.add_message(format!("{}: {}", self.state.name, e));
+                false
+            }
+        }
+    }
+
+    /// Brings up reminders that came due, including ones missed while closed
+    fn fire_reminders(&mut self) {
+        let now = clock::now();
+        let due = reminders::take_due(&mut self.state.reminders, now);
+        if due.is_empty() {
+            return;
+        }
+        for reminder in &due {
+            let late = now - reminder.due;
+            let late = if late.num_minutes() >= 1 {
+                format!(" (that was due {} ago)", reminders::format_duration(late))
+            } else {
+                String::new()
+            };
+            self.ui.add_message(format!("{}: *paws at your hand* ⏰ Hey! {}{}", self.state.name, reminder.text, late));
+            self.ui.show_toast(format!("⏰ {}", reminder.text));
+            self.notifier.reminder_due(&self.config, &self.state.name, &reminder.text);
+        }
+        if let Err(e) = self.save_state() {
+            self.ui.add_message(format!("{}: Couldn't save the reminders.\n{}", self.state.name, e.report()));
+        }
+    }
+
+    /// The art for the pet pane: the mood's pose, sleepy on a low battery or
+    /// during `/focus`, and with the weather around it while idle
+    pub fn pet_ascii(&self) -> String {
+        let art = accessories::dress(self.art.for_mood(self.state.mood), &self.state.accessories);
+        if self.focus.is_some() || self.battery.is_some_and(|battery| battery.is_low()) {
+            return battery::sleepy(&art);
+        }
+        match &self.weather {
+            Some(weather) if self.config.weather && self.ui.pending_replies == 0 => weather::decorate(&art, weather.sky, clock::local_now()),
+            _ => art,
+        }
+    }
+
+    /// Updates the load shown in the title and comments on pegged resources
+    fn sample_system_load(&mut self) {
+        let interval = Duration::from_secs(self.config.system_monitor_interval_secs.max(1));
+        let Some(monitor) = self.system_monitor.as_mut().filter(|_| self.last_system_sample.elapsed() >= interval) else {
+            return;
+        };
+        self.last_system_sample = Instant::now();
+        let pressures = monitor.sample();
+        self.ui.system_load = Some(format!("{} ⚡{:.0}%", monitor.sparkline(), monitor.energy() * 100.0));
+        for pressure in pressures {
+            self.ui.add_message(format!("{}: {}", self.state.name, pressure.comment()));
+        }
+    }
+
+    /// Updates the battery shown in the title, asking for the charger once when
+    /// it runs low
+    fn check_battery(&mut self) {
+        if !self.config.battery_awareness || self.last_battery_check.is_some_and(|check| check.elapsed() < BATTERY_CHECK_INTERVAL) {
+            return;
+        }
+        self.last_battery_check = Some(Instant::now());
+        self.battery = battery::read();
+        self.ui.battery = self.battery.map(|battery| battery.status());
+        match self.battery {
+            Some(battery) if battery.is_low() && !self.battery_warned => {
+                self.battery_warned = true;
+                let text = format!("Battery's at {}%… I'm getting sleepy. Plug me in?", battery.percent);
+                self.ui.add_message(format!("{}: *yawns* {}", self.state.name, text));
+                self.ui.show_toast(format!("🪫 {}%", battery.percent));
+                self.notifier.battery_low(&self.config, &self.state.name, &text);
+            }
+            Some(battery) if battery.charge != Charge::Discharging && self.battery_warned => {
+                self.battery_warned = false;
+                self.ui.add_message(format!("{}: *perks up* Ahh, that's better. Thanks for the charge!", self.state.name));
+            }
+            _ => {}
+        }
+    }
+
+    /// Checks in the background whether the backend can be reached, every
+    /// `connectivity_check_secs`
+    fn probe_backend(&mut self) {
+        let interval = Duration::from_secs(self.config.connectivity_check_secs);
+        if interval.is_zero()
+            || self.attached_to_daemon
+            || self.probing_backend
+            || self.last_backend_probe.is_some_and(|probe| probe.elapsed() < interval)
+        {
+            return;
+        }
+        self.probing_backend = true;
+        self.last_backend_probe = Some(Instant::now());
+        let url = self.backend_url();
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            let _ = events.send(AppEvent::BackendProbed(connectivity::probe(&url).await));
+        });
+    }
+
+    /// Switches to or from the pet's offline replies, saying so when it changes
+    fn set_offline(&mut self, offline: bool) {
+        if offline == self.offline {
+            return;
+        }
+        self.offline = offline;
+        self.ui.offline = offline;
+        let backend = self.backend_url();
+        if offline {
+            tracing::warn!("{} is unreachable, answering offline", backend);
+            self.ui.add_message(format!(
+                "{}: *ears droop* I can't reach {} right now, so I'll answer by myself until it's back.",
+                self.state.name, backend
+            ));
+            self.ui.show_toast("📴 Backend offline".to_string());
+        } else {
+            tracing::info!("{} is reachable again", backend);
+            self.ui.add_message(format!("{}: *ears perk up* {} is back, I'm all ears again!", self.state.name, backend));
+            self.ui.show_toast("Backend back online".to_string());
+        }
+    }
+
+    /// `/speak on|off|stop`, or replays the latest reply without arguments
+    fn speak_command(&mut self, args: &str) {
+        let message = match args {
+            "on" => {
+                self.speaking = true;
+                "*clears throat* I'll read my replies out loud now."
+            }
+            "off" => {
+                self.speaking = false;
+                self.stop_speaking();
+                "*goes quiet* Back to text only."
+            }
+            "stop" => {
+                self.stop_speaking();
+                return;
+            }
+            "" => {
+                self.last_replay = None;
+                self.replay_speech();
+                return;
+            }
+            _ => "Usage: /speak on|off|stop, or /speak alone to hear my last reply again.",
+        };
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+    }
+
+    /// Reads a reply again: the latest one, or an earlier one each time this is
+    /// repeated within a few seconds
+    pub fn replay_speech(&mut self) {
+        let back = match self.last_replay {
+            Some((back, at)) if at.elapsed() < REPLAY_STEP_WINDOW => back + 1,
+            _ => 0,
+        };
+        let Some(response) = self.state.chat_history.iter().rev().nth(back).map(|message| message.response.clone()) else {
+            self.ui.show_toast(if back == 0 { "Nothing to replay yet" } else { "No earlier replies" });
+            return;
+        };
+        self.last_replay = Some((back, Instant::now()));
+        self.ui.show_toast(if back == 0 { "🔊 Last reply".to_string() } else { format!("🔊 {} replies back", back + 1) });
+        self.say(&response);
+    }
+
+    /// Reads text out loud in the background, cutting off anything still being said
+    fn say(&mut self, text: &str) {
+        self.stop_speaking();
+        let voice = Voice::from_config(&self.config);
+        let text = text.to_string();
+        let events = self.events.clone();
+        self.speech = Some(tokio::spawn(async move {
+            if let Err(e) = voice.speak(&text).await {
+                tracing::warn!("couldn't speak: {}", e);
+                let _ = events.send(AppEvent::Notification(format!("Couldn't speak: {}", e)));
+            }
+        }));
+    }
+
+    fn stop_speaking(&mut self) {
+        if let Some(speech) = self.speech.take() {
+            speech.abort();
+        }
+    }
+
+    /// The keys that undo and redo edits of the input line
+    pub fn undo_keys(&self) -> (&str, &str) {
+        (&self.config.undo_key, &self.config.redo_key)
+    }
+
+    /// The key that starts and stops recording a macro
+    pub fn macro_record_key(&self) -> &str {
+        &self.config.macro_record_key
+    }
+
+    /// Whether the next key names the macro to record
+    pub fn naming_macro(&self) -> bool {
+        self.naming_macro
+    }
+
+    /// Stops and saves the macro being recorded, or asks for the letter of
+    /// a new one
+    pub fn toggle_macro_recording(&mut self) {
+        let Some(recording) = self.recording_macro.take() else {
+            self.naming_macro = true;
+            self.ui.show_toast("⏺ Press a letter to name the macro (Esc cancels)");
+            return;
+        };
+        self.ui.recording_macro = None;
+        if recording.inputs.is_empty() {
+            self.ui.show_toast("Nothing was recorded");
+            return;
+        }
+        let count = recording.inputs.len();
+        self.state.macros.insert(recording.register.to_string(), recording.inputs);
+        let note = if self.attached_to_daemon {
+            " The daemon owns my state, so it lasts until you quit.".to_string()
+        } else if let Err(e) = self.save_state() {
+            format!(" Couldn't save it:\n{}", e.report())
+        } else {
+            String::new()
+        };
+        self.ui.add_message(format!(
+            "{}: Recorded {} line{} as @{}; Alt+{} replays {}.{}",
+            self.state.name,
+            count,
+            if count == 1 { "" } else { "s" },
+            recording.register,
+            recording.register,
+            if count == 1 { "it" } else { "them" },
+            note
+        ));
+    }
+
+    /// Starts recording into `register`; anything but a letter cancels
+    pub fn start_macro(&mut self, register: Option<char>) {
+        self.naming_macro = false;
+        match register.filter(|&c| macros::is_register(c)) {
+            Some(register) => {
+                self.recording_macro = Some(Recording::new(register));
+                self.ui.recording_macro = Some(register);
+            }
+            None => self.ui.show_toast("No macro recorded"),
+        }
+    }
+
+    /// The lines of the macro in `register`, to send again
+    pub fn macro_inputs(&self, register: char) -> Option<Vec<String>> {
+        self.state.macros.get(&register.to_string()).cloned()
+    }
+
+    /// Alt+Up and Alt+Down: 👍 or 👎 on the last reply in this thread
+    pub fn react(&mut self, liked: bool) {
+        let conversation = conversation(&self.state, self.shown_session);
+        let Some(reply) = conversation.into_iter().rev().find(|message| message.provider.is_some()) else {
+            self.ui.show_toast("There's no reply to react to yet");
+            return;
+        };
+        let reaction = Reaction::new(&reply.response, reply.timestamp, liked);
+        feedback::record(&mut self.state.reactions, reaction);
+        self.ui.show_toast(if liked { "👍 Noted, more like that" } else { "👎 Noted, I'll do better" });
+        if self.attached_to_daemon {
+            return;
+        }
+        if let Err(e) = self.save_state() {
+            self.ui.add_message(format!("{}: Couldn't save your reaction:\n{}", self.state.name, e.report()));
+        }
+    }
+
+    /// `/feedback` shows what the reactions add to prompts, and `/feedback
+    /// clear` forgets them
+    fn show_feedback(&mut self, args: &str) {
+        let reactions = &self.state.reactions;
+        let message = match args {
+            "" => {
+                let liked = reactions.iter().filter(|reaction| reaction.liked).count();
+                let counts = format!("{} 👍 and {} 👎 so far.", liked, reactions.len() - liked);
+                match feedback::preferences(reactions) {
+                    Some(preferences) => format!("{} Here's what I tell the backend:\n{}", counts, preferences),
+                    None => format!("{} That's not enough to go on yet; react to replies with Alt+Up and Alt+Down.", counts),
+                }
+            }
+            "clear" if self.attached_to_daemon => {
+                self.state.reactions.clear();
+                "Forgot your reactions until you quit; the daemon owns my state.".to_string()
+            }
+            "clear" => {
+                self.state.reactions.clear();
+                match self.save_state() {
+                    Ok(()) => "*shakes it off* Forgot all your reactions.".to_string(),
+                    Err(e) => format!("Forgot your reactions, but couldn't save that:\n{}", e.report()),
+                }
+            }
+            _ => "Usage: /feedback [clear]".to_string(),
+        };
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+    }
+
+    /// `/fork <name>` branches the current conversation into a new thread and
+    /// switches to it
+    fn fork_thread(&mut self, name: &str) {
+        if self.attached_to_daemon {
+            self.ui.add_message(format!("{}: The daemon owns my state, so forks have to wait until it's stopped.", self.state.name));
+            return;
+        }
+        if let Err(e) = threads::check_name(name, &self.state.threads) {
+            self.ui.add_message(format!("{}: {}", self.state.name, e));
+            return;
+        }
+        let parent = self.state.current_thread.clone();
+        let from = parent.as_deref().unwrap_or(threads::MAIN).to_string();
+        self.state.threads.push(Thread { name: name.to_string(), parent, forked_at: clock::now() });
+        self.enter_thread(Some(name.to_string()));
+        self.ui.add_message(format!(
+            "{}: *hops onto a side path* We're in {} now, forked from {}. Nothing said here reaches {}'s memory; /threads {} goes back.",
+            self.state.name, name, from, from, from
+        ));
+    }
+
+    /// `/threads` lists the threads and `/threads <name>` switches to one
+    fn switch_thread(&mut self, name: &str) {
+        if name.is_empty() {
+            self.ui.add_message(format!(
+                "{}: Threads (* is the current one):\n{}\nSwitch with /threads <name>, or branch off with /fork <name>.",
+                self.state.name,
+                threads::list(&self.state.chat_history, &self.state.threads, self.state.current_thread.as_deref())
+            ));
+            return;
+        }
+        if self.attached_to_daemon {
+            self.ui.add_message(format!("{}: The daemon owns my state, so threads have to wait until it's stopped.", self.state.name));
+            return;
+        }
+        let thread = (name != threads::MAIN).then(|| name.to_string());
+        if thread.as_ref().is_some_and(|name| !self.state.threads.iter().any(|thread| &thread.name == name)) {
+            self.ui.add_message(format!("{}: There's no thread called {}; /threads lists them.", self.state.name, name));
+            return;
+        }
+        self.enter_thread(thread);
+        self.ui.add_message(format!("{}: *pads over* Back in {}.", self.state.name, name));
+    }
+
+    /// Makes `thread` current: its conversation fills the chat window and
+    /// the backend's context
+    fn enter_thread(&mut self, thread: Option<String>) {
+        self.state.current_thread = thread;
+        self.ui.clear_messages();
+        replay_chat_history(&mut self.ui, &self.state, self.shown_session);
+        if let Err(e) = self.rebuild_llm() {
+            self.ui.add_message(format!("{}: Couldn't restart the backend for this thread: {}", self.state.name, e));
+        }
+        if let Err(e) = self.save_state() {
+            self.ui.add_message(format!("{}: Couldn't save the thread:\n{}", self.state.name, e.report()));
+        }
+    }
+
+    /// Replaces the backend with a fresh one, the configured one or the
+    /// local model while over budget, carrying the current thread's last
+    /// exchanges over
+    fn rebuild_llm(&mut self) -> Result<(), LlmError> {
+        let mut llm = llm::build_within_budget(&self.config, self.over_budget)?;
+        llm::resume(llm.as_mut(), &conversation(&self.state, self.shown_session));
+        self.llm = llm;
+        Ok(())
+    }
+
+    /// The URL the backend in use answers at, for probes and error messages
+    fn backend_url(&self) -> String {
+        if self.over_budget {
+            self.config.ollama_url.clone()
+        } else {
+            connectivity::endpoint(&self.config)
+        }
+    }
+
+    /// Adds what a reply from a paid backend cost to today's spending
+    fn charge(&mut self, provider: &str, model: &str, generation: &Generation) {
+        if provider != "openai" {
+            return;
+        }
+        let today = clock::local_now().date_naive();
+        if budget::charge(&mut self.state.spend_log, &self.config.model_prices, model, generation, today)
+            || self.unpriced_models.iter().any(|unpriced| unpriced == model)
+        {
+            return;
+        }
+        self.unpriced_models.push(model.to_string());
+        if self.config.budget_daily_usd.is_some() || self.config.budget_monthly_usd.is_some() {
+            self.ui.add_message(format!(
+                "{}: I don't know what {} costs, so its replies don't count toward your budget. Add it to [model_prices] to fix that.",
+                self.state.name, model
+            ));
+        }
+    }
+
+    /// Warns as a budget runs low, switches to the local model once one is
+    /// used up and back when a new day or month starts
+    fn check_budget(&mut self) {
+        let today = clock::local_now().date_naive();
+        let standing = self.config.budget().standing(&self.state.spend_log, today);
+        let notice = match standing {
+            Standing::Low { period, .. } | Standing::Exceeded { period, .. } => Some((period, period.start(today))),
+            Standing::Within => None,
+        };
+        let over_budget = standing.needs_local_model(&self.config.llm_provider);
+        if over_budget != self.over_budget {
+            self.over_budget = over_budget;
+            self.budget_notices.extend(notice);
+            let (message, toast) = match (over_budget, self.rebuild_llm()) {
+                (true, Ok(())) => (
+                    format!(
+                        "{} I'll answer with {} on Ollama until {}; /budget shows where things stand.",
+                        standing.describe().unwrap_or_default(),
+                        self.config.ollama_model,
+                        if notice.is_some_and(|(period, _)| period == Period::Month) { "next month" } else { "tomorrow" }
+                    ),
+                    "💸 Over budget, switched to Ollama",
+                ),
+                (false, Ok(())) if self.config.llm_provider != LLMProvider::OpenAI => return,
+                (false, Ok(())) => ("Back within budget, so I'm answering with OpenAI again.".to_string(), "💸 Back on OpenAI"),
+                (_, Err(e)) => (format!("Couldn't switch backends for the budget: {}", e), "Backend switch failed"),
+            };
+            self.ui.add_message(format!("{}: {}", self.state.name, message));
+            self.ui.show_toast(toast);
+            return;
+        }
+        if let Some(notice) = notice.filter(|notice| !self.budget_notices.contains(notice)) {
+            self.budget_notices.push(notice);
+            self.ui.add_message(format!("{}: 💸 {}", self.state.name, standing.describe().unwrap_or_default()));
+            self.ui.show_toast("💸 Budget running low");
+        }
+    }
+
+    /// `/budget` shows today's and this month's spending against the budgets
+    fn show_budget(&mut self) {
+        let mut text = self.config.budget().summary(&self.state.spend_log, clock::local_now().date_naive());
+        if self.over_budget {
+            text.push_str(&format!("\nOver budget, so I'm answering with {} on Ollama for now.", self.config.ollama_model));
+        }
+        self.ui.add_message(format!("{}: {}", self.state.name, text));
+    }
+
+    /// `/macros [delete <letter>]`
+    fn manage_macros(&mut self, args: &str) {
+        let message = match args.split_once(' ').map(|(action, register)| (action, register.trim())) {
+            None if args.is_empty() => macros::list(&self.state.macros, &self.config.macro_record_key),
+            Some(("delete", register)) => match self.state.macros.remove(register) {
+                Some(_) if self.attached_to_daemon => format!("Deleted @{} until you quit; the daemon owns my state.", register),
+                Some(_) => match self.save_state() {
+                    Ok(()) => format!("Deleted @{}.", register),
+                    Err(e) => format!("Deleted @{}, but couldn't save that:\n{}", register, e.report()),
+                },
+                None => format!("There's no macro @{}.", register),
+            },
+            _ => "Usage: /macros [delete <letter>]".to_string(),
+        };
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+    }
+
+    /// The key that starts and stops voice input, if it's on
+    pub fn voice_key(&self) -> Option<&str> {
+        self.config.voice_input.then_some(self.config.voice_key.as_str())
+    }
+
+    /// Starts recording from the microphone, or stops and transcribes the
+    /// recording into the input line
+    pub fn toggle_recording(&mut self) {
+        match self.recording.take() {
+            None => self.start_recording(),
+            Some(recording) => self.finish_recording(recording),
+        }
+    }
+
+    fn start_recording(&mut self) {
+        if self.ui.voice.is_some() {
+            self.ui.show_toast("Still transcribing…");
+            return;
+        }
+        let path = dictation::recording_path();
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let _ = std::fs::remove_file(&path);
+        let Some(mut recorder) = dictation::recorder(&self.config, &path) else {
+            self.ui.add_message(format!(
+                "{}: *cups an ear* I can't find anything to record with. Install sox or alsa-utils, or set record_command.",
+                self.state.name
+            ));
+            return;
+        };
+        let spawned = recorder
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn();
+        match spawned {
+            Ok(child) => {
+                tracing::debug!("recording to {}", path.display());
+                self.recording = Some(child);
+                self.ui.voice = Some(format!("🎙 listening… ({} to stop)", self.config.voice_key));
+            }
+            Err(e) => {
+                tracing::warn!("couldn't start recording: {}", e);
+                self.ui.add_message(format!("{}: *cups an ear* I couldn't start recording: {}", self.state.name, e));
+            }
+        }
+    }
+
+    /// Stops the recorder gently so it finishes the file, then transcribes it
+    /// in the background; the text arrives as `AppEvent::Transcribed`
+    fn finish_recording(&mut self, mut recording: std::process::Child) {
+        stop_recorder(&mut recording);
+        self.ui.voice = Some("🎙 transcribing…".to_string());
+        let transcriber = Transcriber::from_config(&self.config);
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            let _ = tokio::task::spawn_blocking(move || recording.wait()).await;
+            let path = dictation::recording_path();
+            let result = if path.exists() {
+                transcriber.transcribe(&path).await
+            } else {
+                Err("nothing was recorded".to_string())
+            };
+            let _ = events.send(AppEvent::Transcribed(result));
+        });
+    }
+
+    /// Greets whoever started this session of a shared pet and remembers they came by
+    fn greet_teammate(&mut self) {
+        let user = shared_store::current_user();
+        let now = clock::now();
+        self.ui.add_message(format!("{}: {}", self.state.name, shared_store::greeting(&self.state, &user, now)));
+        self.state.visitors.insert(user, now);
+        if let Err(e) = self.save_state() {
+            self.ui.add_message(format!("{}: Couldn't save your visit.\n{}", self.state.name, e.report()));
+        }
+    }
+
+    /// Greets with what the launch directory's project is, or what happened
+    /// there last time, and remembers the visit
+    fn greet_project(&mut self) {
+        let Ok(dir) = std::env::current_dir() else {
+            return;
+        };
+        let excluded = &self.config.project_scan_exclude;
+        let Some(scan) = project_scan::scan(&dir, self.config.project_scan_depth).filter(|scan| {
+            !project_scan::is_excluded(&dir, excluded) && !project_scan::is_excluded(&scan.root, excluded)
+        }) else {
+            return;
+        };
+        let key = scan.root.to_string_lossy().into_owned();
+        let greeting = project_scan::greeting(&scan, self.state.projects.get(&key), &clock::local_now());
+        self.ui.add_message(format!("{}: {}", self.state.name, greeting));
+        self.project_root = Some(scan.root);
+        if self.attached_to_daemon {
+            return;
+        }
+        let now = clock::now();
+        let memory = self.state.projects.entry(key).or_insert_with(|| ProjectMemory { name: scan.name.clone(), last_visit: now, last_failure: None });
+        memory.name = scan.name;
+        memory.last_visit = now;
+        if let Err(e) = self.save_state() {
+            self.ui.add_message(format!("{}: Couldn't save your visit.\n{}", self.state.name, e.report()));
+        }
+    }
+
+    /// Remembers a command that failed in the launch project, or that a
+    /// remembered failure works again
+    fn notice_project_command(&mut self, entry: &HistoryEntry) {
+        let Some(root) = &self.project_root else {
+            return;
+        };
+        if self.attached_to_daemon || entry.cwd.as_ref().is_some_and(|cwd| !Path::new(cwd).starts_with(root)) {
+            return;
+        }
+        let Some(memory) = self.state.projects.get_mut(root.to_string_lossy().as_ref()) else {
+            return;
+        };
+        if project_scan::remember_command(memory, entry, clock::now()) {
+            if let Err(e) = self.save_state() {
+                tracing::error!("couldn't save the project's last failure: {}", e);
+            }
+        }
+    }
+
+    /// Announces accessories the pet has just earned
+    ///
+    /// The daemon owns the state while the app is attached to it, so it's
+    /// left alone then.
+    fn check_unlocks(&mut self) {
+        if self.attached_to_daemon {
+            return;
+        }
+        for accessory in accessories::unlock(&mut self.state, &clock::local_now()) {
+            tracing::info!("unlocked the {}", accessory.name());
+            self.ui.add_message(format!(
+                "{}: *finds a {name}* Ooh, I earned a {name}! Put it on me with /wear {name}.",
+                self.state.name,
+                name = accessory.name()
+            ));
+        }
+    }
+
+    /// `/wear`: lists the accessories, or puts one on or takes it off;
+    /// returns whether the outfit changed
+    fn wear(&mut self, name: &str) -> bool {
+        let (message, changed) = match name {
+            "" => {
+                let mut list = "My accessories:".to_string();
+                for accessory in Accessory::ALL {
+                    let status = if self.state.accessories.contains(&accessory) {
+                        "wearing it".to_string()
+                    } else if self.state.unlocked_accessories.contains(&accessory) {
+                        "unlocked".to_string()
+                    } else {
+                        format!("locked: {}", accessory.requirement())
+                    };
+                    list.push_str(&format!("\n  {} ({})", accessory.name(), status));
+                }
+                (list, false)
+            }
+            "none" | "off" if self.state.accessories.is_empty() => ("I'm not wearing anything fancy right now.".to_string(), false),
+            "none" | "off" => {
+                self.state.accessories.clear();
+                ("*shakes off every accessory* Back to basics.".to_string(), true)
+            }
+            _ => match Accessory::parse(name) {
+                None => (
+                    format!("I don't have a {}. Try one of: {}, or none.", name, Accessory::ALL.map(Accessory::name).join(", ")),
+                    false,
+                ),
+                Some(accessory) if !self.state.unlocked_accessories.contains(&accessory) => {
+                    (format!("I haven't earned the {} yet: {}.", accessory.name(), accessory.requirement()), false)
+                }
+                Some(accessory) if accessories::toggle(&mut self.state, accessory) => {
+                    (format!("*puts on the {}* How do I look?", accessory.name()), true)
+                }
+                Some(accessory) => (format!("*takes off the {}*", accessory.name()), true),
+            },
+        };
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+        changed
+    }
+
+    /// `/learn`: shows the skill tree, or adds or drops a learning track;
+    /// returns whether the tracks changed
+    fn learn(&mut self, name: &str) -> bool {
+        let names = Track::ALL.map(Track::name).join("|");
+        let (message, changed) = match name {
+            "" if self.state.learning_tracks.is_empty() => {
+                (format!("What would you like to learn? Pick a track with /learn {}.", names), false)
+            }
+            "" => (format!("{}\n{}", skills::tree(&self.state), self.next_up()), false),
+            _ => match Track::parse(name) {
+                None => (format!("I don't know that track. Try /learn {}.", names), false),
+                Some(track) if self.state.learning_tracks.contains(&track) => {
+                    self.state.learning_tracks.retain(|&chosen| chosen != track);
+                    (format!("Dropped {}. What you've learned stays learned.", track.title()), true)
+                }
+                Some(track) => {
+                    self.state.learning_tracks.push(track);
+                    let history = self.read_history(ANALYZE_HISTORY_LIMIT);
+                    let known: Vec<&str> =
+                        skills::learn_from_history(&mut self.state, &history).iter().map(|skill| skill.name).collect();
+                    let mut message = format!("*sharpens pencil* Let's learn {}!", track.title());
+                    if !known.is_empty() {
+                        message.push_str(&format!(" Your history says you already know {}.", known.join(", ")));
+                    }
+                    (format!("{} {}", message, self.next_up()), true)
+                }
+            },
+        };
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+        changed
+    }
+
+    /// What to learn next, or that there's nothing left
+    fn next_up(&self) -> String {
+        match skills::next(&self.state) {
+            Some(skill) => format!("Next up: {} ({}). Try /quiz when you're ready.", skill.name, skill.track.title()),
+            None => "You've learned every skill in your tracks! Pick another one with /learn.".to_string(),
+        }
+    }
+
+    /// `/quiz`: asks about the next skill to learn; `/quiz skip` drops the question
+    fn quiz_command(&mut self, args: &str) {
+        let message = match args {
+            "skip" => match self.quiz.take() {
+                Some(skill) => format!("Skipped! For next time: {}.", skill.tip),
+                None => "There's no question waiting. Ask for one with /quiz.".to_string(),
+            },
+            "" if self.state.learning_tracks.is_empty() => {
+                format!("Pick something to learn first, with /learn {}.", Track::ALL.map(Track::name).join("|"))
+            }
+            "" => match skills::next(&self.state) {
+                Some(skill) => {
+                    self.quiz = Some(skill);
+                    format!("*puts on glasses* {} quiz: {}\n(Answer in your next message, or /quiz skip.)", skill.track.title(), skill.question)
+                }
+                None => self.next_up(),
+            },
+            _ => "Try /quiz, or /quiz skip.".to_string(),
+        };
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+    }
+
+    /// Checks an answer to the `/quiz` question; returns whether the skill
+    /// was learned
+    fn answer_quiz(&mut self, skill: &'static Skill, answer: &str) -> bool {
+        let answer = answer.trim().trim_start_matches('$');
+        let learned = skill.is_shown_by(answer) && skills::learn(&mut self.state, skill);
+        let message = if learned {
+            format!("*happy chirp* That's right! You've learned {}. {}", skill.name, self.next_up())
+        } else {
+            format!("Not quite: {}. One answer would be `{}`. Try again later with /quiz.", skill.tip, skill.example())
+        };
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+        learned
+    }
+
+    /// `/flashcards`: adds cards for new tips and struggles and starts
+    /// reviewing the due ones; `/flashcards stop` ends the review. Returns
+    /// whether the cards changed.
+    fn flashcards_command(&mut self, args: &str) -> bool {
+        match args {
+            "" => {}
+            "stop" => {
+                let message = match self.review.take() {
+                    Some(_) => "*closes the card box* We'll pick up where we left off.",
+                    None => "We're not reviewing any cards right now.",
+                };
+                self.ui.add_message(format!("{}: {}", self.state.name, message));
+                return false;
+            }
+            _ => {
+                self.ui.add_message(format!("{}: Usage: /flashcards, or /flashcards stop to end the review.", self.state.name));
+                return false;
+            }
+        }
+        if self.review.is_some() {
+            self.show_flashcard();
+            return false;
+        }
+        let today = clock::local_now().date_naive();
+        let history = self.read_history(ANALYZE_HISTORY_LIMIT);
+        let added = flashcards::add_new(&mut self.state.flashcards, &self.state.chat_history, &history, today);
+        if added > 0 {
+            self.ui.add_message(format!(
+                "{}: *scribbles* I made {} new flashcard{} from my tips and your tricky commands.",
+                self.state.name,
+                added,
+                if added == 1 { "" } else { "s" }
+            ));
+        }
+        self.review = Review::start(&self.state.flashcards, today);
+        match &self.review {
+            Some(review) => {
+                self.ui.add_message(format!(
+                    "{}: {} card{} to review. Type the command, or anything if you don't know it. (/flashcards stop to quit)",
+                    self.state.name,
+                    review.remaining(),
+                    if review.remaining() == 1 { "" } else { "s" }
+                ));
+                self.show_flashcard();
+            }
+            None => {
+                let message = match flashcards::next_due(&self.state.flashcards) {
+                    Some(date) => format!("Nothing to review today; the next card is due on {}.", date),
+                    None => "I don't have any flashcards yet. Ask me how to do things and they'll come!".to_string(),
+                };
+                self.ui.add_message(format!("{}: {}", self.state.name, message));
+            }
+        }
+        added > 0
+    }
+
+    fn show_flashcard(&mut self) {
+        let Some(review) = &self.review else {
+            return;
+        };
+        let message = match review.current(&self.state.flashcards) {
+            Some(card) if review.revealed => format!("It's `{}`. How did you do? 0-5, or again/hard/good/easy.", card.back),
+            Some(card) => format!("🃏 {}", card.front),
+            None => return,
+        };
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+    }
+
+    /// Takes an answer or a grade during a review; returns whether a card
+    /// was graded
+    fn answer_flashcard(&mut self, input: &str) -> bool {
+        let Some(review) = self.review.as_mut() else {
+            return false;
+        };
+        let Some(card) = review.current(&self.state.flashcards) else {
+            self.review = None;
+            return false;
+        };
+        let grade = if review.revealed {
+            match flashcards::parse_grade(input) {
+                Some(grade) => grade,
+                None => {
+                    self.ui.add_message(format!("{}: Grade it 0-5, or again, hard, good or easy.", self.state.name));
+                    return false;
+                }
+            }
+        } else if card.is_answered_by(input) {
+            self.ui.add_message(format!("{}: *purrs* Perfect!", self.state.name));
+            5
+        } else {
+            review.revealed = true;
+            self.show_flashcard();
+            return false;
+        };
+        let today = clock::local_now().date_naive();
+        review.grade(&mut self.state.flashcards, grade, today);
+        if review.remaining() > 0 {
+            self.show_flashcard();
+        } else {
+            self.review = None;
+            let next = flashcards::next_due(&self.state.flashcards).map(|date| format!(" Next review: {}.", date)).unwrap_or_default();
+            self.ui.add_message(format!("{}: *stacks the cards* All done for today!{}", self.state.name, next));
+        }
+        true
+    }
+
+    /// Says once a day that flashcards are due, counting cards for tips
+    /// and struggles since the last review
+    fn remind_flashcards(&mut self) {
+        if !self.config.flashcard_reminders || self.attached_to_daemon || self.mode == Mode::Popup || self.review.is_some() {
+            return;
+        }
+        let today = clock::local_now().date_naive();
+        if self.state.last_flashcard_reminder == Some(today) {
+            return;
+        }
+        self.state.last_flashcard_reminder = Some(today);
+        flashcards::add_new(&mut self.state.flashcards, &self.state.chat_history, &self.recent_commands, today);
+        let due = flashcards::due(&self.state.flashcards, today).len();
+        if due > 0 {
+            self.ui.add_message(format!(
+                "{}: *taps the card box* {} flashcard{} due today. Review them with /flashcards!",
+                self.state.name,
+                due,
+                if due == 1 { " is" } else { "s are" }
+            ));
+        }
+        if let Err(e) = self.save_state() {
+            tracing::warn!("couldn't save the flashcards: {}", e);
+        }
+    }
+
+    /// Announces skills a successful command showed for the first time
+    ///
+    /// The daemon owns the state while the app is attached to it, so it's
+    /// left alone then.
+    fn notice_skills(&mut self, entry: &HistoryEntry) {
+        if self.attached_to_daemon {
+            return;
+        }
+        let learned = skills::learn_from_history(&mut self.state, std::slice::from_ref(entry));
+        if learned.is_empty() {
+            return;
+        }
+        for skill in learned {
+            self.ui.add_message(format!("{}: *ears perk up* You just used {}! I'll mark it learned in {}.", self.state.name, skill.name, skill.track.title()));
+        }
+        if let Err(e) = self.save_state() {
+            self.ui.add_message(format!("{}: Couldn't save what you learned.\n{}", self.state.name, e.report()));
+        }
+    }
+
+    /// Cheers the pet up and remembers who fed it
+    fn give_treat(&mut self) {
+        self.state.cheer_up(0.2);
+        self.state.fed_by = Some(Feeding { user: shared_store::current_user(), at: clock::now() });
+    }
+
+    /// Fetches the weather in the background once the last fetch is stale;
+    /// failures wait for the next refresh interval
+    fn refresh_weather(&mut self) {
+        let now = clock::now();
+        if !self.config.weather
+            || self.weather_fetching
+            || self.weather.as_ref().is_some_and(|weather| !weather.is_stale(&self.config, now))
+            || self.last_weather_attempt.is_some_and(|attempt| attempt.elapsed() < Duration::from_secs(self.config.weather_refresh_minutes * 60))
+        {
+            return;
+        }
+        self.weather_fetching = true;
+        self.last_weather_attempt = Some(Instant::now());
+        let provider = self.config.weather_provider;
+        let coordinates = self.config.weather_latitude.zip(self.config.weather_longitude);
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            let _ = events.send(AppEvent::WeatherFetched(weather::fetch(provider, coordinates).await));
+        });
+    }
+
+    /// Mentions the weather once per session, as soon as it's known
+    fn greet_with_weather(&mut self) {
+        if let Some(weather) = self.weather.as_ref().filter(|_| !self.weather_greeted) {
+            self.weather_greeted = true;
+            self.ui.add_message(format!("{}: {}", self.state.name, weather.greeting()));
+        }
+    }
+
+    /// `/tldr <cmd>` shows a tldr page, downloading the pages first if there
+    /// are none yet; `/tldr --update` downloads them again
+    fn show_tldr(&mut self, args: &str) {
+        let (update, command) = match args {
+            "" => {
+                self.ui.add_message(format!("{}: Usage: /tldr <command>, or /tldr --update to refresh the pages", self.state.name));
+                return;
+            }
+            "--update" => (true, None),
+            command if !tldr::has_pages() => (true, Some(command.to_string())),
+            command => (false, Some(command.to_string())),
+        };
+        if update {
+            self.ui.add_message(format!("{}: *fetches the tldr pages* This takes a moment, but only once.", self.state.name));
+            self.ui.pending_replies += 1;
+            let events = self.events.clone();
+            tokio::spawn(async move {
+                let result = tldr::update().await;
+                let _ = events.send(AppEvent::TldrUpdated { result, command });
+            });
+            return;
+        }
+        let command = command.unwrap_or_default();
+        let message = match tldr::page(&command) {
+            Some(page) => tldr::render(&page),
+            None => format!("There's no tldr page for `{}`. Try `man {}`, or just ask me!", command, command),
+        };
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+    }
+
+    /// `/pomodoro [work/break]` starts a timer, `/pomodoro stop` ends it, and
+    /// a bare `/pomodoro` while one runs tells where it stands
+    fn start_pomodoro(&mut self, args: &str) {
+        let now = clock::now();
+        let message = match (args, &self.pomodoro) {
+            ("stop", Some(pomodoro)) => {
+                let message = format!("*stretches* Timer stopped after {} pomodoro(s). Nice work!", pomodoro.completed);
+                self.pomodoro = None;
+                self.ui.timer = None;
+                message
+            }
+            ("stop", None) => "There's no pomodoro running.".to_string(),
+            ("", Some(pomodoro)) => format!(
+                "{} left in this {}, {} pomodoro(s) done so far. /pomodoro stop ends it.",
+                reminders::format_duration(pomodoro.remaining(now)),
+                if pomodoro.phase == Phase::Work { "work session" } else { "break" },
+                pomodoro.completed
+            ),
+            (args, _) => match Pomodoro::parse_lengths(args) {
+                Some((work, rest)) => {
+                    let pomodoro = Pomodoro::new(work, rest, now);
+                    self.ui.timer = Some(pomodoro.status(now));
+                    self.pomodoro = Some(pomodoro);
+                    format!(
+                        "*sits up straight* {} minutes of focus, then a {}-minute break. You've got this! 🍅",
+                        work.num_minutes(),
+                        rest.num_minutes()
+                    )
+                }
+                None => "Usage: /pomodoro [work/break minutes, like 25/5] or /pomodoro stop".to_string(),
+            },
+        };
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+    }
+
+    /// Updates the countdown and reacts when a work session or break ends
+    fn advance_pomodoro(&mut self) {
+        let now = clock::now();
+        let Some(pomodoro) = self.pomodoro.as_mut() else {
+            return;
+        };
+        let finished = pomodoro.advance(now);
+        self.ui.timer = Some(pomodoro.status(now));
+        let (message, toast) = match finished {
+            None => return,
+            Some(Phase::Work) => {
+                let (minutes, completed, rest) = (pomodoro.work.num_minutes() as u32, pomodoro.completed, pomodoro.rest.num_minutes());
+                pomodoro::record(&mut self.state.focus_log, clock::local_now().date_naive(), minutes);
+                self.state.cheer_up(0.05);
+                self.break_nudged = false;
+                (
+                    format!(
+                        "*does a happy little dance* Pomodoro #{} done! Take your {}-minute break: stand up, stretch, drink some water. 🎉",
+                        completed, rest
+                    ),
+                    format!("🎉 Pomodoro #{} done, time for a break", completed),
+                )
+            }
+            Some(Phase::Break) => (
+                "*taps the timer* Break's over! Ready for another round? 🍅".to_string(),
+                "🍅 Break's over".to_string(),
+            ),
+        };
+        self.ui.show_toast(toast);
+        self.notifier.pomodoro_phase_over(&self.config, &self.state.name, &message);
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+        if let Err(e) = self.save_state() {
+            self.ui.add_message(format!("{}: Couldn't save your focus stats.\n{}", self.state.name, e.report()));
+        }
+    }
+
+    /// `/focus [duration]` starts a focus session, `/focus off` ends it, and a
+    /// bare `/focus` while one runs tells how long is left
+    fn start_focus(&mut self, args: &str) {
+        let now = clock::now();
+        let message = match (args, &self.focus) {
+            ("off", Some(_)) => {
+                self.end_focus();
+                return;
+            }
+            ("off", None) => "Focus mode isn't on.".to_string(),
+            ("", Some(focus)) => format!(
+                "*mumbles in its sleep* {} of focus left. /focus off wakes me up.",
+                reminders::format_duration(focus.remaining(now))
+            ),
+            (args, _) => match FocusSession::parse_length(args, chrono::Duration::minutes(self.config.focus_minutes as i64)) {
+                Some(length) => {
+                    let focus = FocusSession::new(now, length);
+                    self.ui.focus = Some(focus.status(now));
+                    self.focus = Some(focus);
+                    self.notifier.set_do_not_disturb(true);
+                    format!(
+                        "*curls up for a nap* Focus on, for {}. No suggestions, notifications or chatter from me until then; /focus off wakes me up early.",
+                        reminders::format_duration(length)
+                    )
+                }
+                None => "Usage: /focus [duration, like 45m or 1h30m, up to 12h] or /focus off".to_string(),
+            },
+        };
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+    }
+
+    /// Updates the countdown and wakes the pet when the focus session is over
+    fn advance_focus(&mut self) {
+        let now = clock::now();
+        let Some(focus) = &self.focus else {
+            return;
+        };
+        if focus.is_over(now) {
+            self.end_focus();
+        } else {
+            self.ui.focus = Some(focus.status(now));
+        }
+    }
+
+    fn end_focus(&mut self) {
+        let Some(focus) = self.focus.take() else {
+            return;
+        };
+        self.ui.focus = None;
+        self.notifier.set_do_not_disturb(false);
+        self.ui.show_toast("💤 Focus time's over");
+        self.ui.add_message(format!(
+            "{}: *stretches and yawns* That was {} of focus. Welcome back!",
+            self.state.name,
+            reminders::format_duration(focus.elapsed(clock::now()))
+        ));
+    }
+
+    /// Reminds the user to rest when they start chatting during a break, once per break
+    fn nudge_during_break(&mut self) {
+        let on_break = self.pomodoro.as_ref().is_some_and(|pomodoro| pomodoro.phase == Phase::Break);
+        if on_break && !self.break_nudged {
+            self.break_nudged = true;
+            self.ui.show_toast("☕ It's break time! Step away for a bit");
+        }
+    }
+
+    /// `/todo`: lists or changes the to-do list; returns whether the state changed
+    fn update_todos(&mut self, command: TodoCommand) -> bool {
+        let finishing = matches!(command, TodoCommand::Done(_));
+        let todos = &mut self.state.todos;
+        let (message, changed) = match command {
+            TodoCommand::List if todos.is_empty() => ("Your to-do list is empty. Add something with /todo <task>.".to_string(), false),
+            TodoCommand::List => {
+                let lines: Vec<String> = todos
+                    .iter()
+                    .enumerate()
+                    .map(|(i, todo)| format!("{}. [{}] {}", i + 1, if todo.done { "x" } else { " " }, todo.text))
+                    .collect();
+                (format!("To do:\n{}", lines.join("\n")), false)
+            }
+            TodoCommand::Add(text) => {
+                todos.push(Todo::new(&text));
+                (format!("*scribbles it down* #{}: {}", todos.len(), text), true)
+            }
+            TodoCommand::Done(n) => match todos.get_mut(n - 1) {
+                Some(todo) => {
+                    todo.done = true;
+                    (format!("*happy tail swish* Done: {}", todo.text), true)
+                }
+                None => (format!("There's no #{} on your list.", n), false),
+            },
+            TodoCommand::Remove(n) if n <= todos.len() => {
+                let todo = todos.remove(n - 1);
+                (format!("Removed: {}", todo.text), true)
+            }
+            TodoCommand::Remove(n) => (format!("There's no #{} on your list.", n), false),
+            TodoCommand::Clear => {
+                let before = todos.len();
+                todos.retain(|todo| !todo.done);
+                (format!("Cleared {} finished task(s).", before - todos.len()), before != todos.len())
+            }
+        };
+        if finishing && changed {
+            self.state.cheer_up(0.05);
+        }
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+        changed
+    }
+
+    /// Lists bookmarks, fuzzy-filtered by `query` if one is given
+    fn list_bookmarks(&mut self, query: &str) {
+        let matches: Vec<&Bookmark> = if query.is_empty() {
+            self.state.bookmarks.iter().collect()
+        } else {
+            bookmarks::search(&self.state.bookmarks, query)
+        };
+        let message = if self.state.bookmarks.is_empty() {
+            "No bookmarks yet. Save one with /bookmark <cmd> # description.".to_string()
+        } else if matches.is_empty() {
+            format!("No bookmarks match \"{}\".", query)
+        } else {
+            let lines: Vec<String> = matches.iter().map(|bookmark| bookmark.describe()).collect();
+            format!("Bookmarks:\n{}", lines.join("\n"))
+        };
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+    }
+
+    /// Lists profiles, or saves the current pet and switches to another profile
+    ///
+    /// Switching reloads everything (config, state, backend) from the new
+    /// profile's directory; an unknown name creates a new profile.
+    fn switch_profile(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let current = config_path::profile().unwrap_or_else(|| config_path::DEFAULT_PROFILE.to_string());
+        if name.is_empty() {
+            let profiles: Vec<String> = config_path::list_profiles()
+                .into_iter()
+                .map(|profile| if profile == current { format!("* {}", profile) } else { format!("  {}", profile) })
+                .collect();
+            self.ui.add_message(format!(
+                "{}: Profiles:\n{}\nSwitch with /profile <name>; a new name creates a new profile.",
+                self.state.name,
+                profiles.join("\n")
+            ));
+            return Ok(());
+        }
+        if name == current {
+            self.ui.add_message(format!("{}: We're already in the {} profile!", self.state.name, name));
+            return Ok(());
+        }
+        if let Err(e) = config_path::validate_profile_name(name) {
+            self.ui.add_message(format!("{}: {}", self.state.name, e));
+            return Ok(());
+        }
+
+        self.save_state()?;
+        #[cfg(unix)]
+        if self.mode == Mode::Full {
+            hook::cleanup();
+        }
+        config_path::set_profile(name)?;
+        config_path::migrate_to_data_dir()?;
+        *self = App::new(self.mode, self.events.clone());
+        self.ui.add_message(format!("{}: *stretches* Switched to the {} profile.", self.state.name, name));
+        Ok(())
+    }
+
+    /// The pet's built-in phrases in the configured language
+    fn strings(&self) -> &'static locale::Strings {
+        locale::strings(self.config.language.as_deref())
+    }
+
+    fn list_plugins(&mut self) {
+        let dir = plugins::plugins_dir();
+        let message = if !self.config.load_plugins {
+            "Plugins are turned off; set load_plugins = true to load them.".to_string()
+        } else if self.plugins.is_empty() {
+            format!("No plugins loaded. Put *.wasm plugins in {} and restart me.", dir.display())
+        } else {
+            format!("Plugins from {}:\n{}", dir.display(), self.plugins.describe().join("\n"))
+        };
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+    }
+
+    fn list_scripts(&mut self) {
+        let dir = scripting::scripts_dir();
+        let message = if !self.config.load_scripts {
+            "Scripts are turned off; set load_scripts = true to run them.".to_string()
+        } else if self.scripts.is_empty() {
+            format!("No scripts loaded. Put *.rhai scripts in {} and restart me.", dir.display())
+        } else {
+            let mut lines = self.scripts.describe();
+            lines.extend(self.scripts.commands().iter().map(|command| format!("/{} - {}", command.name, command.description)));
+            format!("Scripts from {}:\n{}", dir.display(), lines.join("\n"))
+        };
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+    }
+
+    /// Shows what scripts said, as the pet, and applies their mood changes
+    fn apply_script_output(&mut self, output: ScriptOutput) {
+        if output.mood_change != 0.0 {
+            self.state.change_mood(output.mood_change);
+        }
+        self.show_plugin_output(output.messages);
+    }
+
+    /// Shows what plugins said, as the pet
+    fn show_plugin_output(&mut self, lines: Vec<String>) {
+        for line in lines {
+            self.ui.add_message(format!("{}: {}", self.state.name, line));
+        }
+    }
+
+    /// Lists the art gallery, or switches to one of its pets by writing `pet_art` to the config
+    fn choose_art(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if name.is_empty() {
+            let current = self.config.pet_art.as_deref();
+            let names: Vec<String> = art::gallery_names()
+                .into_iter()
+                .map(|gallery_name| {
+                    if Some(gallery_name) == current { format!("* {}", gallery_name) } else { format!("  {}", gallery_name) }
+                })
+                .collect();
+            self.ui.add_message(format!(
+                "{}: Art gallery:\n{}\nTry one on with /art <name>.",
+                self.state.name,
+                names.join("\n")
+            ));
+            return Ok(());
+        }
+        if art::gallery(name).is_none() {
+            self.ui.add_message(format!(
+                "{}: I don't have a {} outfit. Pick one of: {}",
+                self.state.name,
+                name,
+                art::gallery_names().join(", ")
+            ));
+            return Ok(());
+        }
+        config::Config::set_keys(&[("pet_art", Some(name))])?;
+        let message = if self.config.pet_ascii_file.is_some() {
+            format!("Saved pet_art = \"{}\", but pet_ascii_file still takes precedence; remove it to see the change.", name)
+        } else {
+            format!("*shakes fur* How do I look as a {}?", name)
+        };
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+        Ok(())
+    }
+
+    /// `/model` names the backend and model, and `/model pull <name>` has
+    /// Ollama download a model, switching to it once it's there
+    fn manage_model(&mut self, args: &str) {
+        let Some(model) = args.strip_prefix("pull").filter(|rest| rest.is_empty() || rest.starts_with(' ')).map(str::trim) else {
+            let message = if args.is_empty() {
+                format!(
+                    "I'm talking through {} with {}. /model pull <name> downloads an Ollama model and switches to it.",
+                    self.llm.provider(),
+                    self.llm.model()
+                )
+            } else {
+                "Usage: /model [pull <name>], like /model pull llama3.2".to_string()
+            };
+            self.ui.add_message(format!("{}: {}", self.state.name, message));
+            return;
+        };
+        if model.is_empty() || model.contains(char::is_whitespace) {
+            self.ui.add_message(format!("{}: Usage: /model pull <name>, like /model pull llama3.2 or /model pull qwen2.5-coder:7b", self.state.name));
+            return;
+        }
+        if let Some(pulling) = &self.model_pull {
+            self.ui.show_toast(format!("⬇ Still pulling {}…", pulling));
+            return;
+        }
+        self.model_pull = Some(model.to_string());
+        self.ui.download = Some((format!("Pulling {}", model), None));
+        let (events, url, model) = (self.events.clone(), self.config.ollama_url.clone(), model.to_string());
+        tokio::spawn(async move {
+            let progress_events = events.clone();
+            let result = ollama::pull(&url, &model, move |progress| {
+                let _ = progress_events.send(AppEvent::ModelPullProgress(progress));
+            })
+            .await;
+            let _ = events.send(AppEvent::ModelPulled { model, result });
+        });
+    }
+
+    /// Switches to a pulled model by writing it to the config, which the
+    /// reload then picks up like any other change
+    fn finish_model_pull(&mut self, model: String, result: Result<(), String>) {
+        self.model_pull = None;
+        self.ui.download = None;
+        let message = match result {
+            Err(e) => format!("Couldn't pull {}: {}", model, e),
+            Ok(()) => match config::Config::set_keys(&[("llm_provider", Some("Ollama")), ("ollama_model", Some(&model))]) {
+                Ok(()) => format!("*stretches* {} is downloaded; switching to it now.", model),
+                Err(e) => format!("{} is downloaded, but I couldn't switch to it: {}", model, e),
+            },
+        };
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+    }
+
+    /// `/compare <a> <b> [question]` puts the question, or the last message,
+    /// to two models at once and shows their answers side by side
+    fn compare(&mut self, args: &str) {
+        let (a, b, question) = match compare::parse(args) {
+            Ok(parsed) => parsed,
+            Err(usage) => {
+                self.ui.add_message(format!("{}: {}", self.state.name, usage));
+                return;
+            }
+        };
+        let last_message = || last_exchange(&self.state, self.shown_session).map(|message| message.user_message.clone());
+        let Some(question) = question.or_else(last_message) else {
+            self.ui.add_message(format!("{}: Ask me something first, or add a question: /compare {} {} <question>", self.state.name, a, b));
+            return;
+        };
+        let backends = match (compare::backend(&self.config, &a), compare::backend(&self.config, &b)) {
+            (Ok(first), Ok(second)) => [first, second],
+            (Err(e), _) | (_, Err(e)) => {
+                self.report_llm_error(&e);
+                return;
+            }
+        };
+        self.look_up_docs(DocsRequest::Compare { question, models: (a, b), backends });
+    }
+
+    /// Sends the `/compare` question to both backends once its docs are in
+    fn start_comparison(&mut self, question: String, (a, b): (String, String), backends: [Box<dyn LLMBackend>; 2], docs: Option<String>) {
+        let prompt = self.chat_prompt(&question, docs);
+        let prompt = self.render_prompt(&prompt);
+        self.comparisons += 1;
+        let id = self.comparisons;
+        self.ui.comparison = Some(Comparison::new(id, question, a, b));
+        for (side, llm) in backends.into_iter().enumerate() {
+            let prompt = if self.config.privacy_filter && llm.is_remote() { self.redactor.redact(&prompt) } else { prompt.clone() };
+            let events = self.events.clone();
+            self.ui.pending_replies += 1;
+            tokio::spawn(async move {
+                let started = Instant::now();
+                let result = llm.generate(&prompt).await;
+                let _ = events.send(AppEvent::CompareAnswered { id, side, result, latency: started.elapsed() });
+            });
+        }
+    }
+
+    /// Ctrl+R opens a fuzzy search of the shell history
+    pub fn open_history_search(&mut self) {
+        let search = HistorySearch::new(&self.read_history(history_search::LIMIT));
+        if search.is_empty() {
+            self.ui.show_toast("There's no shell history to search");
+            return;
+        }
+        self.ui.history_search = Some(search);
+    }
+
+    /// Enter in the history search puts the selected command in the input as `$ <command>`
+    pub fn insert_history_command(&mut self) {
+        if let Some(command) = self.ui.history_search.take().and_then(|search| search.selected().map(str::to_string)) {
+            self.ui.input.set(format!("$ {}", command));
+        }
+    }
+
+    /// Ctrl+E and Ctrl+F in the history search put a question about the
+    /// selected command in the input, returning whether there is one to send
+    pub fn ask_about_history_command(&mut self, improve: bool) -> bool {
+        let Some(command) = self.ui.history_search.take().and_then(|search| search.selected().map(str::to_string)) else {
+            return false;
+        };
+        self.ui.input.set(history_search::question(&command, improve));
+        true
+    }
+
+    /// Counts an exchange toward this session, which the first one starts, and
+    /// returns the session's id
+    fn count_exchange(&mut self) -> u64 {
+        let id = *self.chat_session.get_or_insert_with(|| chat_sessions::start(&mut self.state.chat_sessions, clock::now()));
+        if let Some(session) = self.state.chat_sessions.iter_mut().find(|session| session.id == id) {
+            session.exchanges += 1;
+        }
+        id
+    }
+
+    /// Asks the backend for a title once this session has enough exchanges
+    fn title_session(&mut self) {
+        let Some(id) = self.chat_session else {
+            return;
+        };
+        let untitled = self
+            .state
+            .chat_sessions
+            .iter()
+            .any(|session| session.id == id && session.title.is_none() && session.exchanges >= chat_sessions::TITLE_AFTER);
+        if !untitled || self.offline || self.titles_requested.contains(&id) {
+            return;
+        }
+        self.titles_requested.push(id);
+        let exchanges: Vec<&ChatMessage> =
+            self.state.chat_history.iter().filter(|message| message.session == Some(id)).take(chat_sessions::TITLE_AFTER as usize * 2).collect();
+        let prompt = chat_sessions::title_prompt(&exchanges);
+        self.send_prompt(LlmRequest::SessionTitle { session: id }, prompt);
+    }
+
+    fn receive_session_title(&mut self, id: u64, result: Result<Generation, LlmError>) {
+        let title = match result {
+            Ok(generation) => chat_sessions::clean_title(&generation.text),
+            Err(e) => {
+                tracing::warn!("couldn't get a title for session {}: {}", id, e);
+                return;
+            }
+        };
+        let Some(session) = self.state.chat_sessions.iter_mut().find(|session| session.id == id) else {
+            return;
+        };
+        session.title = title;
+        if let Err(e) = self.save_state() {
+            tracing::warn!("couldn't save the session title: {}", e);
+        }
+    }
+
+    /// `/sessions` opens the session browser and `/sessions new` leaves a
+    /// reopened session for a fresh one
+    fn browse_sessions(&mut self, args: &str) {
+        if args == "new" {
+            self.chat_session = None;
+            self.shown_session = None;
+            self.ui.clear_messages();
+            replay_chat_history(&mut self.ui, &self.state, None);
+            if let Err(e) = self.rebuild_llm() {
+                self.ui.add_message(format!("{}: Couldn't restart the backend: {}", self.state.name, e));
+            }
+            self.ui.show_toast("New session");
+            return;
+        }
+        if !args.is_empty() {
+            self.ui.add_message(format!("{}: Usage: /sessions [new]", self.state.name));
+            return;
+        }
+        self.ui.session_browser = chat_sessions::Browser::new(&self.state.chat_sessions, self.chat_session);
+        if self.ui.session_browser.is_none() {
+            self.ui.add_message(format!("{}: No sessions yet; each run where we chat becomes one.", self.state.name));
+        }
+    }
+
+    /// Reopens the session selected in the browser: its conversation fills
+    /// the chat window and the backend's context, and new messages continue it
+    pub fn open_session(&mut self) {
+        let Some(browser) = self.ui.session_browser.take() else {
+            return;
+        };
+        let session = browser.selected().clone();
+        let loaded = self.state.chat_history.iter().filter(|message| message.session == Some(session.id)).count();
+        // SQLite only loads the latest messages at startup
+        if loaded < session.exchanges as usize {
+            if let Err(e) = self.save_state() {
+                self.ui.add_message(format!("{}: Couldn't save before loading the session:
+{}", self.state.name, e.report()));
+                return;
+            }
+            match self.store.load_full() {
+                Ok(mut state) => {
+                    state.name = self.config.pet_name.clone();
+                    self.state = state;
+                }
+                Err(e) => self.ui.add_message(format!("{}: Couldn't load all of the session:
+{}", self.state.name, e.report())),
+            }
+        }
+        if let Some(message) = self.state.chat_history.iter().rev().find(|message| message.session == Some(session.id)) {
+            self.state.current_thread = message.thread.clone();
+        }
+        self.chat_session = Some(session.id);
+        self.shown_session = Some(session.id);
+        self.ui.clear_messages();
+        self.ui.add_message(format!(
+            "{}: Back to \"{}\" from {}. /sessions new starts a fresh session.",
+            self.state.name,
+            session.label(),
+            session.started.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")
+        ));
+        replay_chat_history(&mut self.ui, &self.state, self.shown_session);
+        if let Err(e) = self.rebuild_llm() {
+            self.ui.add_message(format!("{}: Couldn't restart the backend for this session: {}", self.state.name, e));
+        }
+    }
+
+    /// Picks the better `/compare` answer, a 👍 for it and a 👎 for the other
+    pub fn pick_winner(&mut self, side: usize) {
+        let Some(comparison) = self.ui.comparison.as_mut() else {
+            return;
+        };
+        let Some(reactions) = comparison.pick(side) else {
+            let toast = if comparison.finished() { "Both models need to have answered to pick one" } else { "Wait for both answers first" };
+            self.ui.show_toast(toast);
+            return;
+        };
+        let Some(comparison) = self.ui.comparison.take() else {
+            return;
+        };
+        for reaction in reactions {
+            feedback::record(&mut self.state.reactions, reaction);
+        }
+        let (winner, loser) = (&comparison.answers[side], &comparison.answers[1 - side]);
+        let reply = winner.reply.as_ref().and_then(|reply| reply.as_ref().ok()).map_or("", |reply| reply.trim());
+        self.ui.add_message(format!(
+            "{}: 🏆 {} beat {} on \"{}\", so I'll lean its way. Its answer:\n{}",
+            self.state.name, winner.model, loser.model, comparison.question, reply
+        ));
+        self.ui.show_toast(format!("🏆 {} wins", winner.model));
+        if self.attached_to_daemon {
+            return;
+        }
+        if let Err(e) = self.save_state() {
+            self.ui.add_message(format!("{}: Couldn't save your pick:\n{}", self.state.name, e.report()));
+        }
+    }
+
+    /// `/adopt` shows a fresh litter and `/adopt <n>` takes one home, giving
+    /// the pet that pedigree and art
+    fn adopt(&mut self, choice: &str) {
+        if choice.is_empty() {
+            self.litter = adoption::litter();
+            let intro = match &self.state.pedigree {
+                None => "A litter is looking for a home:",
+                Some(_) => "A new litter is looking for a home (adopting replaces who I am now):",
+            };
+            self.ui.add_message(format!(
+                "{}: {}\n{}\nTake one home with /adopt <n>, or /adopt again to meet another litter.",
+                self.state.name,
+                intro,
+                adoption::list(&self.litter)
+            ));
+            return;
+        }
+        let Some(pedigree) = choice.parse::<usize>().ok().and_then(|n| n.checked_sub(1)).and_then(|i| self.litter.get(i)).cloned() else {
+            let message = if self.litter.is_empty() { "Meet a litter with /adopt first." } else { "Pick one of the litter by its number, like /adopt 2." };
+            self.ui.add_message(format!("{}: {}", self.state.name, message));
+            return;
+        };
+        self.litter.clear();
+        self.state.pedigree = Some(pedigree.clone());
+        let mut notes = Vec::new();
+        if self.attached_to_daemon {
+            notes.push("The daemon owns my state, so this only lasts until you quit.".to_string());
+        } else if let Err(e) = self.save_state() {
+            notes.push(format!("Couldn't save it:\n{}", e.report()));
+        }
+        match config::Config::set_keys(&[("pet_art", Some(&pedigree.species)), ("pet_species", None)]) {
+            Ok(()) if self.config.pet_ascii_file.is_some() => notes.push("pet_ascii_file still decides my art; remove it to see me.".to_string()),
+            Ok(()) => {}
+            Err(e) => notes.push(format!("Couldn't switch my art: {}", e)),
+        }
+        self.ui.add_message(format!(
+            "{}: *tumbles out of the litter* Hi! I'm {}, {}.{}",
+            self.state.name,
+            self.state.name,
+            pedigree.describe(),
+            notes.iter().map(|note| format!(" {}", note)).collect::<String>()
+        ));
+    }
+
+    /// Keeps the aliases a reply suggested around for `/adopt-alias`
+    fn remember_alias_suggestions(&mut self, response: &str) {
+        let found = aliases::extract_definitions(response);
+        if !found.is_empty() {
+            self.alias_suggestions = found;
+            self.ui.add_message(format!(
+                "{}: Like one of these? Add it to your alias file with /adopt-alias.",
+                self.state.name
+            ));
+        }
+    }
+
+    /// Lists the latest alias suggestions, or appends the chosen ones to the alias file
+    fn adopt_aliases(&mut self, choice: &str) {
+        if self.alias_suggestions.is_empty() {
+            self.ui.add_message(format!(
+                "{}: I haven't suggested any aliases yet. Try /analyze!",
+                self.state.name
+            ));
+            return;
+        }
+        let is_adopted = |alias: &AliasDefinition, adopted: &[AdoptedAlias]| adopted.iter().any(|a| a.alias == *alias);
+
+        if choice.is_empty() {
+            let mut list = String::from("Suggested aliases:");
+            for (i, alias) in self.alias_suggestions.iter().enumerate() {
+                let marker = if is_adopted(alias, &self.state.adopted_aliases) { " (adopted)" } else { "" };
+                list.push_str(&format!("\n{}. {}{}", i + 1, alias.definition, marker));
+            }
+            list.push_str("\nAdopt with /adopt-alias <n|name|all>.");
+            self.ui.add_message(format!("{}: {}", self.state.name, list));
+            return;
+        }
+
+        let selected: Vec<AliasDefinition> = match choice {
+            "all" => self.alias_suggestions.clone(),
+            _ => match choice.parse::<usize>() {
+                Ok(n) => self.alias_suggestions.get(n.wrapping_sub(1)).cloned().into_iter().collect(),
+                Err(_) => self.alias_suggestions.iter().filter(|a| a.name == choice).cloned().collect(),
+            },
+        };
+        if selected.is_empty() {
+            self.ui.add_message(format!(
+                "{}: *looks around* There's no suggestion \"{}\". Use /adopt-alias to list them.",
+                self.state.name, choice
+            ));
+            return;
+        }
+
+        let first_adoption = self.state.adopted_aliases.is_empty();
+        let mut added = Vec::new();
+        for alias in selected {
+            if is_adopted(&alias, &self.state.adopted_aliases) {
+                continue;
+            }
+            if let Err(e) = aliases::append(&alias) {
+                self.ui.add_message(format!("{}: {}", self.state.name, e));
+                break;
+            }
+            added.push(alias.name.clone());
+            self.state.adopted_aliases.push(AdoptedAlias { alias, adopted: clock::now() });
+        }
+
+        let mut message = if added.is_empty() {
+            "You've already adopted that.".to_string()
+        } else {
+            format!(
+                "*purrs* Added {} to {}.",
+                added.join(", "),
+                aliases::aliases_file_path().display()
+            )
+        };
+        if first_adoption && !added.is_empty() {
+            message.push_str(&format!(
+                "\nTo load it in new shells, add this line to your ~/.zshrc or ~/.bashrc:\n{}",
+                aliases::source_line()
+            ));
+        }
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+    }
+
+    /// Has the pet explain what's risky about a command, if anything
+    fn warn_if_dangerous(&mut self, command: &str) {
+        let warnings = safety::analyze(command);
+        if !warnings.is_empty() {
+            self.ui.add_message(format!("{}: {}", self.state.name, safety::describe(command, &warnings)));
+        }
+    }
+
+    /// Asks the user to confirm running a command
+    fn request_exec(&mut self, command: String) {
+        if !self.config.allow_command_execution {
+            self.ui.add_message(format!(
+                "{}: Running commands is disabled (allow_command_execution = false).",
+                self.state.name
+            ));
+            return;
+        }
+        if command.is_empty() {
+            return;
+        }
+        let mut question = format!("Run this command?\n\n{}", command);
+        let warnings = safety::analyze(&command);
+        if !warnings.is_empty() {
+            question.push_str(&format!("\n\n{}", safety::describe(&command, &warnings)));
+        }
+        self.ui.confirmation = Some((question, "Run"));
+        self.pending_exec = Some(command);
+    }
+
+    pub fn event_sender(&self) -> EventSender {
+        self.events.clone()
+    }
+
+    /// The program to hand the terminal to, if a command asked for one
+    pub fn take_handoff(&mut self) -> Option<Handoff> {
+        self.pending_handoff.take()
+    }
+
+    /// Reports how a handed-off program went and picks up its results
+    pub fn finish_handoff(&mut self, handoff: Handoff, status: io::Result<ExitStatus>) {
+        let status = match status {
+            Ok(status) => status,
+            Err(e) => {
+                let program = handoff.command().get_program().to_string_lossy().into_owned();
+                self.ui.add_message(format!("{}: Couldn't start {}: {}", self.state.name, program, e));
+                return;
+            }
+        };
+        match handoff {
+            Handoff::Pager(_) if status.success() => {}
+            Handoff::Pager(_) => self.ui.add_message(format!("The pager exited with {}", status)),
+            Handoff::CommitMessage(path) => {
+                let edited = std::fs::read_to_string(&path).map(|text| commit::clean_reply(&text));
+                let _ = std::fs::remove_file(&path);
+                match edited {
+                    Ok(draft) if status.success() && !draft.is_empty() => {
+                        self.ui.add_message(format!("{}: Got it, the message is now:\n\n{}", self.state.name, draft));
+                        self.commit_draft = Some(draft);
+                    }
+                    Ok(_) => self.ui.add_message(format!("{}: Kept the previous message.", self.state.name)),
+                    Err(e) => self.ui.add_message(format!("{}: Couldn't read the edited message: {}", self.state.name, e)),
+                }
+            }
+        }
+    }
+
+    pub fn awaiting_confirmation(&self) -> bool {
+        self.pending_exec.is_some()
+    }
+
+    /// Asks before quitting while a reply is on its way or the input holds
+    /// something unsent; returns false when there's nothing to ask about
+    pub fn ask_to_quit(&mut self) -> bool {
+        let question = if self.ui.pending_replies > 0 {
+            "A response is still arriving. Quit anyway?"
+        } else if !self.ui.input.is_empty() {
+            "You haven't sent what you typed. Quit anyway?\nIt'll be back in the input next time."
+        } else {
+            return false;
+        };
+        self.ui.confirmation = Some((question.to_string(), "Quit"));
+        self.confirming_quit = true;
+        true
+    }
+
+    pub fn confirming_quit(&self) -> bool {
+        self.confirming_quit
+    }
+
+    /// Closes the quit dialog without quitting
+    pub fn cancel_quit(&mut self) {
+        self.ui.confirmation = None;
+        self.confirming_quit = false;
+    }
+
+    /// Starts or discards the command awaiting confirmation
+    ///
+    /// The command runs on its own task and reports back with `AppEvent::CommandFinished`.
+    pub fn confirm_exec(&mut self, approved: bool) {
+        self.ui.confirmation = None;
+        let Some(command) = self.pending_exec.take() else {
+            return;
+        };
+        if !approved {
+            self.ui.add_message(format!("{}: Okay, I won't run it.", self.state.name));
+            return;
+        }
+
+        let options = ExecOptions::from_config(&self.config);
+        let cwd = options
+            .working_dir
+            .clone()
+            .or_else(|| std::env::current_dir().ok())
+            .map(|dir| dir.to_string_lossy().into_owned());
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            let started = Instant::now();
+            let result = exec::run(&command, &options).await.map_err(|e| e.to_string());
+            let _ = events.send(AppEvent::CommandFinished { command, result, duration: started.elapsed(), cwd });
+        });
+    }
+
+    /// `/commitmsg`: drafts a commit message for the staged changes, or edits,
+    /// writes or copies the latest draft
+    fn commit_message(&mut self, action: &str) {
+        let cwd = self.workspace.as_ref().map(|workspace| workspace.cwd.clone());
+        let cwd = cwd.or_else(|| std::env::current_dir().ok()).unwrap_or_default();
+        if action.is_empty() {
+            match commit::staged_diff(&cwd) {
+                Ok(diff) => self.send_prompt(LlmRequest::CommitMessage, commit::prompt(&diff)),
+                Err(e) => self.ui.add_message(format!("{}: {}", self.state.name, e)),
+            }
+            return;
+        }
+        let Some(draft) = self.commit_draft.clone() else {
+            self.ui.add_message(format!("{}: There's no message yet; /commitmsg drafts one.", self.state.name));
+            return;
+        };
+        let message = match action {
+            "edit" => {
+                let path = config_path::get_cache_dir().join("COMMIT_EDITMSG");
+                let written = std::fs::create_dir_all(config_path::get_cache_dir())
+                    .and_then(|()| std::fs::write(&path, format!("{}\n", draft)));
+                match written {
+                    Ok(()) => {
+                        self.pending_handoff = Some(Handoff::CommitMessage(path));
+                        return;
+                    }
+                    Err(e) => format!("Couldn't write {}: {}", path.display(), e),
+                }
+            }
+            "write" => match commit::write_editmsg(&cwd, &draft) {
+                Ok(path) => format!("Saved it to {}; `git commit -eF {}` commits with it.", path.display(), path.display()),
+                Err(e) => e,
+            },
+            "copy" => match self.copy_to_clipboard(&draft) {
+                Ok(()) => "Copied it to the clipboard.".to_string(),
+                Err(e) => format!("Couldn't copy it: {}", e),
+            },
+            _ => "Usage: /commitmsg [edit|write|copy]".to_string(),
+        };
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+    }
+
+    /// `/gh pr|issue <n>`: fetches the item with `gh` on its own task; the
+    /// summary is requested once it arrives as `AppEvent::GitHubFetched`
+    fn fetch_from_github(&mut self, args: &str) {
+        let Some((item, number)) = GhItem::parse(args) else {
+            self.ui.add_message(format!("{}: Usage: /gh pr <number> or /gh issue <number>", self.state.name));
+            return;
+        };
+        let cwd = self.workspace.as_ref().map(|workspace| workspace.cwd.clone());
+        let cwd = cwd.or_else(|| std::env::current_dir().ok()).unwrap_or_default();
+        let events = self.events.clone();
+        self.ui.pending_replies += 1;
+        tokio::spawn(async move {
+            let result = github::fetch(item, &number, &cwd).await;
+            let _ = events.send(AppEvent::GitHubFetched { item, number, result });
+        });
+    }
+
+    /// `/review <path> [focus]`: sends the file to the backend chunk by chunk
+    fn review_file(&mut self, args: &str) {
+        if self.code_review.is_some() {
+            self.ui.show_toast("🔍 Still reviewing…");
+            return;
+        }
+        let (path, focus) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+        if path.is_empty() {
+            self.ui.add_message(format!("{}: Usage: /review <path> [focus], like /review src/main.rs error handling", self.state.name));
+            return;
+        }
+        let cwd = self.workspace.as_ref().map(|workspace| workspace.cwd.clone());
+        let cwd = cwd.or_else(|| std::env::current_dir().ok()).unwrap_or_default();
+        match CodeReview::start(&cwd.join(path), path, focus.trim()) {
+            Ok((review, prompts)) => {
+                self.ui.add_message(format!(
+                    "{}: *adjusts glasses* Reading through {}{}…",
+                    self.state.name,
+                    path,
+                    if prompts.len() > 1 { format!(" in {} parts", prompts.len()) } else { String::new() }
+                ));
+                self.code_review = Some(review);
+                for (chunk, prompt) in prompts.into_iter().enumerate() {
+                    self.send_prompt(LlmRequest::CodeReview { chunk }, prompt);
+                }
+            }
+            Err(e) => self.ui.add_message(format!("{}: {}", self.state.name, e)),
+        }
+    }
+
+    /// Adds a chunk's findings, and shows them all once every chunk is in
+    fn receive_code_review(&mut self, chunk: usize, result: Result<Generation, LlmError>) {
+        let Some(review) = self.code_review.as_mut() else {
+            return;
+        };
+        let reply = result.as_ref().map(|generation| generation.text.as_str()).map_err(|e| e.to_string());
+        if !review.receive(chunk, reply) {
+            return;
+        }
+        let message = review.render();
+        self.code_review = None;
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+    }
+
+    /// `/translate <language> [text]`: translates the text, or the last reply
+    fn translate(&mut self, args: &str) {
+        let Some((language, text)) = translate::parse(args) else {
+            self.ui.add_message(format!(
+                "{}: Usage: /translate <language> <text>, or /translate <language> for my last reply",
+                self.state.name
+            ));
+            return;
+        };
+        let original = match text {
+            "" => match self.state.chat_history.last() {
+                Some(message) => message.response.clone(),
+                None => {
+                    self.ui.add_message(format!("{}: I haven't said anything to translate yet.", self.state.name));
+                    return;
+                }
+            },
+            text => text.to_string(),
+        };
+        let prompt = translate::prompt(language, &original);
+        self.send_prompt(LlmRequest::Translation { language: language.to_string(), original }, prompt);
+    }
+
+    /// Asks the backend to explain the regex lab's pattern; the explanation
+    /// is shown in the lab and added to the chat
+    pub fn explain_regex(&mut self) {
+        let Some(lab) = self.ui.regex_lab.as_mut() else {
+            return;
+        };
+        if lab.pattern.is_empty() {
+            self.ui.show_toast("Type a pattern first");
+            return;
+        }
+        lab.explanation = Some("*squints at the pattern*…".to_string());
+        let (pattern, prompt) = (lab.pattern.clone(), lab.explain_prompt());
+        self.send_prompt(LlmRequest::RegexExplanation { pattern }, prompt);
+    }
+
+    /// Runs the playground's expression on its input on another task; the
+    /// result arrives as `AppEvent::PlaygroundRan`
+    pub fn run_playground(&mut self) {
+        let Some(playground) = self.ui.playground.as_mut() else {
+            return;
+        };
+        playground.revision += 1;
+        if playground.expression.trim().is_empty() {
+            playground.output = None;
+            return;
+        }
+        let (tool, expression, input, revision) = (playground.tool, playground.expression.clone(), playground.input.clone(), playground.revision);
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            let result = playground::run(tool, &expression, &input, revision).await;
+            let _ = events.send(AppEvent::PlaygroundRan { revision, result });
+        });
+    }
+
+    /// Asks the backend to fix the playground's expression after it errored
+    pub fn fix_playground(&mut self) {
+        let Some(playground) = &self.ui.playground else {
+            return;
+        };
+        let Some(Err(error)) = &playground.output else {
+            self.ui.show_toast("Nothing to fix; it runs");
+            return;
+        };
+        let prompt = playground.fix_prompt(error);
+        let expression = playground.expression.clone();
+        self.ui.show_toast("*sniffs at the expression*…");
+        self.send_prompt(LlmRequest::PlaygroundFix { expression }, prompt);
+    }
+
+    /// Puts a fixed expression in the playground, unless it was changed in
+    /// the meantime, and says what was wrong in the chat
+    fn receive_playground_fix(&mut self, broken: &str, reply: &str) {
+        let Some(fix) = playground::extract_fix(reply) else {
+            self.ui.add_message(format!("{}: I couldn't come up with a fix:\n{}", self.state.name, reply.trim()));
+            return;
+        };
+        self.ui.add_message(format!("{}: `{}` → `{}`\n{}", self.state.name, broken, fix, reply.trim()));
+        if let Some(playground) = self.ui.playground.as_mut().filter(|playground| playground.expression == broken) {
+            playground.expression = fix;
+            self.run_playground();
+        }
+    }
+
+    /// `/cron [timer] <description>`: asks for a crontab line or systemd
+    /// timer; a schedule typed in directly is checked without the backend
+    fn schedule_job(&mut self, args: &str) {
+        let (timer, rest) = match args.strip_prefix("timer") {
+            Some(rest) if rest.is_empty() || rest.starts_with(char::is_whitespace) => (true, rest.trim()),
+            _ => (false, args),
+        };
+        if rest.is_empty() {
+            self.ui.add_message(format!(
+                "{}: Usage: /cron <what should run when>, or /cron timer <...> for a systemd timer. \
+                A schedule like /cron 30 2 * * 1-5 or /cron timer Mon..Fri 09:00 is checked right away.",
+                self.state.name
+            ));
+            return;
+        }
+        let schedule = if timer {
+            Schedule::parse_on_calendar(rest).ok().map(|parsed| (rest.to_string(), parsed))
+        } else {
+            cron::split_crontab_line(rest).and_then(|(schedule, _)| Schedule::parse_crontab(&schedule).ok().map(|parsed| (schedule, parsed)))
+        };
+        match schedule {
+            Some((schedule, parsed)) => {
+                let next = parsed.describe(clock::local_now().naive_local());
+                self.ui.add_message(format!("{}: `{}`: {}", self.state.name, schedule, next));
+            }
+            None => self.send_prompt(LlmRequest::Cron { timer }, cron::prompt(rest, timer)),
+        }
+    }
+
+    /// `/script`: asks for a script, or for a change to the current one, and
+    /// saves or drops it
+    fn write_script(&mut self, args: &str) {
+        let (action, rest) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+        let rest = rest.trim();
+        let message = match (action, &self.script) {
+            ("", None) => "Usage: /script <what the script should do>, like /script backs up ~/notes to a dated tarball".to_string(),
+            ("", Some(draft)) => format!("The script so far:\n\n{}", draft.render()),
+            ("save", None) | ("drop", None) => "There's no script yet; /script <description> writes one.".to_string(),
+            ("save", Some(_)) if rest.is_empty() => "Usage: /script save <path>".to_string(),
+            ("save", Some(draft)) => {
+                let path = match rest.strip_prefix("~/").zip(dirs::home_dir()) {
+                    Some((rest, home)) => home.join(rest),
+                    None => {
+                        let cwd = self.workspace.as_ref().map(|workspace| workspace.cwd.clone());
+                        cwd.or_else(|| std::env::current_dir().ok()).unwrap_or_default().join(rest)
+                    }
+                };
+                match draft.save(&path, clock::local_now().date_naive()) {
+                    Ok(path) => format!("Saved it to {} and made it executable.", path.display()),
+                    Err(e) => e,
+                }
+            }
+            ("drop", Some(_)) => {
+                self.script = None;
+                "Okay, I threw the script away.".to_string()
+            }
+            ("new", _) if rest.is_empty() => "Usage: /script new <what the script should do>".to_string(),
+            ("new", _) => {
+                self.script = None;
+                self.send_prompt(LlmRequest::Script { description: rest.to_string(), changes: Vec::new() }, shell_script::prompt(rest));
+                return;
+            }
+            (_, None) => {
+                self.send_prompt(LlmRequest::Script { description: args.to_string(), changes: Vec::new() }, shell_script::prompt(args));
+                return;
+            }
+            (_, Some(draft)) => {
+                let prompt = shell_script::revision_prompt(draft, args);
+                let mut changes = draft.changes.clone();
+                changes.push(args.to_string());
+                self.send_prompt(LlmRequest::Script { description: draft.description.clone(), changes }, prompt);
+                return;
+            }
+        };
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+    }
+
+    /// Shows a new version of the script and makes it the current one
+    fn receive_script(&mut self, description: String, changes: Vec<String>, reply: &str) {
+        let Some(code) = shell_script::extract(reply) else {
+            self.ui.add_message(format!("{}: That didn't come with a script:\n\n{}", self.state.name, reply.trim()));
+            return;
+        };
+        let draft = ScriptDraft { description, changes, code };
+        self.ui.add_message(format!(
+            "{}: Here's a script that {}:\n\n{}\n\n/script <change> to change it, /script save <path> to save it, or /script new <description> to start over.",
+            self.state.name,
+            draft.description,
+            draft.render()
+        ));
+        self.script = Some(draft);
+    }
+
+    /// `/dotfiles`: lists the dotfiles that would be read and asks before
+    /// sending them; `yes` or a file's name sends them, and `save` writes
+    /// annotated copies with the suggestions
+    fn advise_on_dotfiles(&mut self, args: &str) {
+        let found = dirs::home_dir().map(|home| dotfiles::find(&home)).unwrap_or_default();
+        let message = match args {
+            "" if found.is_empty() => "I didn't find a .zshrc, .bashrc, .profile or .vimrc in your home directory.".to_string(),
+            "" => format!(
+                "I'd read {}. Secrets are redacted before anything goes to {}, and I won't change the files. \
+                /dotfiles yes to go ahead, or /dotfiles <name> for just one of them.",
+                found.iter().map(|file| file.name.as_str()).collect::<Vec<_>>().join(", "),
+                self.llm.provider()
+            ),
+            "save" if self.dotfile_advice.is_empty() => "There are no suggestions to save yet; /dotfiles asks for some.".to_string(),
+            "save" => {
+                let today = clock::local_now().date_naive();
+                let saved: Vec<String> = self
+                    .dotfile_advice
+                    .iter()
+                    .map(|advice| match advice.save(today) {
+                        Ok(path) => format!("Saved {}, annotated, to {}.", advice.file.name, path.display()),
+                        Err(e) => e,
+                    })
+                    .collect();
+                saved.join("\n")
+            }
+            which => {
+                let chosen: Vec<Dotfile> = match which {
+                    "yes" | "all" => found,
+                    name => found.into_iter().filter(|file| file.name.trim_start_matches("~/") == name.trim_start_matches("~/")).collect(),
+                };
+                if chosen.is_empty() {
+                    format!("I don't know a dotfile called {}; /dotfiles lists the ones I found.", which)
+                } else {
+                    let mut reading = Vec::new();
+                    for file in chosen {
+                        match file.read() {
+                            Ok(original) => {
+                                let prompt = dotfiles::prompt(&file, &self.redactor.redact(&original));
+                                reading.push(file.name.clone());
+                                self.send_prompt(LlmRequest::DotfileAdvice { file, original }, prompt);
+                            }
+                            Err(e) => self.ui.add_message(format!("{}: {}", self.state.name, e)),
+                        }
+                    }
+                    if reading.is_empty() {
+                        return;
+                    }
+                    format!("*puts on reading glasses* Going through {}…", reading.join(", "))
+                }
+            }
+        };
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+    }
+
+    /// Shows the suggestions for a dotfile and keeps them for `/dotfiles save`
+    fn receive_dotfile_advice(&mut self, file: Dotfile, original: String, suggestions: String) {
+        self.ui.add_message(format!(
+            "{}: Suggestions for {}:\n\n{}\n\n/dotfiles save writes them, commented out, to {} next to your original.",
+            self.state.name,
+            file.name,
+            suggestions.trim(),
+            file.copy_path().file_name().unwrap_or_default().to_string_lossy()
+        ));
+        self.dotfile_advice.retain(|advice| advice.file != file);
+        self.dotfile_advice.push(Advice { file, original, suggestions });
+    }
+
+    fn copy_to_clipboard(&mut self, text: &str) -> Result<(), arboard::Error> {
+        let clipboard = match &mut self.clipboard {
+            Some(clipboard) => clipboard,
+            None => self.clipboard.insert(arboard::Clipboard::new()?),
+        };
+        clipboard.set_text(text)
+    }
+
+    /// Shows a finished command's output and adds it to the pet's context
+    fn receive_command_output(
+        &mut self,
+        command: String,
+        result: Result<ExecOutput, String>,
+        duration: Duration,
+        cwd: Option<String>,
+    ) {
+        let message = match result {
+            Ok(output) => {
+                tracing::info!(%command, exit_code = output.exit_code, ?duration, "command finished");
+                self.push_recent_command(HistoryEntry {
+                    command: command.clone(),
+                    timestamp: Some(clock::now()),
+                    cwd,
+                    exit_code: output.exit_code,
+                    duration: Some(duration),
+                });
+                output.summary(&command)
+            }
+            Err(e) => {
+                tracing::warn!(%command, "command failed: {}", e);
+                e
+            }
+        };
+        self.llm.add_to_history(format!("!{}", command), message.clone());
+        self.ui.add_message(format!("{}: {}", self.state.name, message));
+    }
+
+    /// Sends a prompt to the backend on its own task, redacting secrets first
+    /// if it leaves this machine
+    ///
+    /// The reply arrives as `AppEvent::LlmResponse`, tagged with `request`.
+    fn send_prompt(&mut self, request: LlmRequest, prompt: Prompt) {
+        let prompt = self.render_prompt(&prompt);
+        let prompt = if self.config.privacy_filter && self.llm.is_remote() {
+            self.redactor.redact(&prompt)
+        } else {
+            prompt
+        };
+        let llm = self.llm.boxed_clone();
+        let events = self.events.clone();
+        self.ui.pending_replies += 1;
+        if self.offline {
+            // Fail right away rather than waiting for the request to time out
+            let _ = events.send(AppEvent::LlmResponse {
+                request,
+                result: Err(LlmError::Network {
+                    provider: llm.provider().to_string(),
+                    url: self.backend_url(),
+                    message: "the backend is offline".to_string(),
+                }),
+                provider: llm.provider(),
+                model: llm.model().to_string(),
+                latency: Duration::ZERO,
+            });
+            return;
+        }
+        tokio::spawn(async move {
+            let started = Instant::now();
+            let result = llm.generate(&prompt).await;
+            let _ = events.send(AppEvent::LlmResponse {
+                request,
+                result,
+                provider: llm.provider(),
+                model: llm.model().to_string(),
+                latency: started.elapsed(),
+            });
+        });
+    }
+
+    /// Sends a chat message to the daemon's pet on its own task
+    ///
+    /// The reply arrives as `AppEvent::LlmResponse` like any other; the daemon
+    /// has already recorded the exchange.
+    #[cfg(unix)]
+    fn ask_daemon(&mut self, user_message: String) {
+        let events = self.events.clone();
+        self.ui.pending_replies += 1;
+        tokio::spawn(async move {
+            let started = Instant::now();
+            let result = daemon::ask(&user_message).await;
+            let _ = events.send(AppEvent::LlmResponse {
+                request: LlmRequest::Chat { user_message, revision: Revision::Original },
+                result: result.map(|text| Generation { text, prompt_tokens: None, completion_tokens: None, first_token: None, generation_time: None }),
+                provider: "daemon",
+                model: String::new(),
+                latency: started.elapsed(),
+            });
+        });
+    }
+
+    #[cfg(not(unix))]
+    fn ask_daemon(&mut self, _user_message: String) {}
+
+    /// Renders a prompt with the user's template for it, if they have one
+    fn render_prompt(&mut self, prompt: &Prompt) -> String {
+        let cwd = self.workspace.as_ref().map(|workspace| workspace.cwd.as_path());
+        let recent_commands = self.config.history_filter().apply(&self.recent_commands);
+        let globals = Globals::new(&self.state, &recent_commands, cwd);
+        let (text, problem) = prompt.render(&self.config.prompt_templates, &globals);
+        if let Some(problem) = problem {
+            self.ui.add_message(format!("{}: {}", self.state.name, problem));
+        }
+        text
+    }
+
+    /// Asks for a summary of chat history that falls outside the retention
+    /// policy, which is archived when it arrives
+    fn apply_retention(&mut self) {
+        if self.archiving {
+            return;
+        }
+        let expired = RetentionPolicy::from_config(&self.config).expired_count(&self.state, clock::now());
+        if expired == 0 {
+            return;
+        }
+        let exchanges: Vec<ArchivedExchange> = self.state.chat_history[..expired]
+            .iter()
+            .map(|message| ArchivedExchange {
+                user_message: message.user_message.clone(),
+                pet_response: message.response.clone(),
+                timestamp: message.timestamp,
+            })
+            .collect();
+        self.archiving = true;
+        let prompt = retention::summary_prompt(&exchanges);
+        self.send_prompt(LlmRequest::RetentionSummary { exchanges }, prompt);
+    }
+
+    /// Archives the exchanges a retention summary is about and drops them from
+    /// the history, with a plain summary if the backend didn't write one
+    fn receive_retention_summary(&mut self, exchanges: Vec<ArchivedExchange>, result: Result<Generation, LlmError>) {
+        self.archiving = false;
+        // The history may have been cleared or replaced while waiting
+        let still_oldest = exchanges.len() <= self.state.chat_history.len()
+            && exchanges.iter().zip(&self.state.chat_history).all(|(exchange, message)| {
+                exchange.user_message == message.user_message
+                    && exchange.pet_response == message.response
+                    && exchange.timestamp == message.timestamp
+            });
+        if !still_oldest {
+            return;
+        }
+        let summary = match result {
+            Ok(generation) => generation.text,
+            Err(e) => {
+                tracing::warn!("summarizing old messages failed, keeping a plain summary: {}", e);
+                retention::fallback_summary(&exchanges)
+            }
+        };
+        // Only drop history once it is safely in the archive
+        match retention::append(&exchanges, &summary, self.store.cipher()) {
+            Ok(()) => self.state.remove_oldest(exchanges.len()),
+            Err(e) => {
+                self.ui.add_message(format!("{}: Couldn't archive old messages: {}", self.state.name, e));
+                return;
+            }
+        }
+        if let Err(e) = self.save_state() {
+            self.ui.add_message(format!("{}: Couldn't save our chat.\n{}", self.state.name, e.report()));
+        }
+    }
+
+    /// `/card`: saves the pet's card and shows it with where it was written
+    fn share_card(&self) -> String {
+        let now = clock::local_now();
+        let history = self.read_history(ANALYZE_HISTORY_LIMIT);
+        let art = accessories::dress(self.art.for_mood(self.state.mood), &self.state.accessories);
+        let card = PetCard::new(&self.state, &art, &history, &now);
+        let (ansi_path, png) = match card::save(&card, &self.config, &now) {
+            Ok(saved) => saved,
+            Err(e) => return format!("Couldn't save my card: {}", e),
+        };
+        let png = match png {
+            Ok(path) => format!("Image: {}", path.display()),
+            Err(e) => format!("No image this time: {}", e),
+        };
+        format!("Here's my card!\n{}\nANSI: {} (`cat` it in a terminal)\n{}", card.to_plain(), ansi_path.display(), png)
+    }
+
+    /// `/portrait` asks the image model for a new portrait, `/portrait last`
+    /// shows the latest one again
+    fn portrait_command(&mut self, args: &str) {
+        match args {
+            "" => {}
+            "last" => {
+                let message = match portrait::latest() {
+                    Some(path) => {
+                        self.show_picture(&path);
+                        format!("*preens* My latest portrait: {}", path.display())
+                    }
+                    None => "I haven't had my portrait painted yet. Try /portrait!".to_string(),
+                };
+                self.ui.add_message(format!("{}: {}", self.state.name, message));
+                return;
+            }
+            _ => {
+                self.ui.add_message(format!("{}: Usage: /portrait, or /portrait last to see the latest one again.", self.state.name));
+                return;
+            }
+        }
+        if self.painting {
+            self.ui.show_toast("🎨 Still painting…");
+            return;
+        }
+        self.painting = true;
+        let prompt = portrait::prompt(&self.state, &portrait::species(&self.config));
+        tracing::debug!("painting a portrait: {}", prompt);
+        self.ui.add_message(format!("{}: *sits very still* Painting my portrait, this can take a minute…", self.state.name));
+        let painter = Painter::from_config(&self.config);
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            let _ = events.send(AppEvent::PortraitPainted(painter.paint(&prompt).await));
+        });
+    }
+
+    fn receive_portrait(&mut self, result: Result<Vec<u8>, String>) {
+        let saved = result.and_then(|png| portrait::save(&self.state.name, &png, &clock::local_now()));
+        match saved {
+            Ok(path) => {
+                tracing::info!("saved a portrait to {}", path.display());
+                self.ui.add_message(format!("{}: *strikes a pose* My portrait is ready: {}", self.state.name, path.display()));
+                self.ui.show_toast("🎨 Portrait ready");
+                self.show_picture(&path);
+            }
+            Err(e) => {
+                tracing::warn!("painting a portrait failed: {}", e);
+                self.ui.add_message(format!("{}: *smudges the paint* My portrait didn't work out: {}", self.state.name, e));
+            }
+        }
+    }
+
+    /// Queues a picture for the terminal to draw, if it can draw pictures
+    fn show_picture(&mut self, path: &Path) {
+        if portrait::graphics(self.config.terminal_graphics) != TerminalGraphics::Off {
+            self.pending_picture = Some(path.to_path_buf());
+        }
+    }
+
+    /// The picture to draw and how, if one is waiting
+    pub fn take_picture(&mut self) -> Option<(PathBuf, TerminalGraphics)> {
+        let path = self.pending_picture.take()?;
+        Some((path, portrait::graphics(self.config.terminal_graphics)))
+    }
+
+    /// Describes the privacy filter's configuration and recent redactions
+    fn privacy_report(&self) -> String {
+        let status = match (self.config.privacy_filter, self.llm.is_remote()) {
+            (false, _) => "Privacy filter is OFF (privacy_filter = false).",
+            (true, false) => "Privacy filter is on, but your backend is local so prompts are sent unfiltered.",
+            (true, true) => "Privacy filter is on: secrets are redacted before prompts leave this machine.",
+        };
+        let mut report = format!("{}\nActive rules: {}", status, self.redactor.rule_count());
+        for (pattern, error) in &self.redactor.invalid_patterns {
+            report.push_str(&format!("\nIgnored invalid pattern `{}`: {}", pattern, error));
+        }
+        if self.redactor.log.is_empty() {
+            report.push_str("\nNothing has been redacted this session.");
+        } else {
+            report.push_str("\nRedacted this session:");
+            for redaction in &self.redactor.log {
+                report.push_str(&format!(
+                    "\n{} [{}] {}",
+                    redaction.at.format("%H:%M:%S"),
+                    redaction.rule,
+                    redaction.preview
+                ));
+            }
+        }
+        report
+    }
+
+    /// Starts this week's report on Monday morning if it hasn't been made yet
+    fn make_weekly_report_if_due(&mut self) {
+        if !self.config.weekly_report || self.attached_to_daemon || self.mode == Mode::Popup {
+            return;
+        }
+        let now = clock::local_now();
+        if !report::is_due(self.state.last_weekly_report, &now) {
+            return;
+        }
+        self.state.last_weekly_report = Some(now.date_naive());
+        if let Err(e) = self.save_state() {
+            tracing::warn!("couldn't save the weekly report date: {}", e);
+        }
+        self.ui.add_message(format!("{}: *stretches* It's Monday! Let me look back at last week…", self.state.name));
+        self.weekly_report();
+    }
+
+    /// `/report`: gathers the week's numbers and asks the backend to write them up
+    fn weekly_report(&mut self) {
+        let facts = report::facts(&self.state, &self.read_history(ANALYZE_HISTORY_LIMIT), &clock::local_now());
+        self.ui.add_message(format!("{}: *flips through the week's notes* Writing your weekly report…", self.state.name));
+        self.send_prompt(LlmRequest::WeeklyReport { facts: facts.clone() }, report::prompt(&facts));
+    }
+
+    /// Saves the weekly report, with just the numbers if the backend didn't
+    /// answer, and shows it
+    fn receive_weekly_report(&mut self, facts: &str, result: Result<Generation, LlmError>) {
+        let summary = match result {
+            Ok(generation) => Some(generation.text),
+            Err(e) => {
+                self.report_llm_error(&e);
+                None
+            }
+        };
+        let now = clock::local_now();
+        let markdown = report::to_markdown(facts, summary.as_deref(), &now);
+        let saved = match report::save(&markdown, &now) {
+            Ok(path) => format!("Saved to {}", path.display()),
+            Err(e) => format!("Couldn't save it: {}", e),
+        };
+        self.ui.add_message(format!("{}: {}\n\n{}", self.state.name, markdown.trim_end(), saved));
+    }
+
+    /// Offers a shortcut when a repetitive command pattern shows up in recent history
+    fn suggest_workflow(&mut self) {
+        if !self.config.proactive_suggestions || self.mode == Mode::Popup {
+            return;
+        }
+        let Some((pattern, count)) = self.pattern_detector.poll(&self.recent_commands) else {
+            return;
+        };
+        self.send_prompt(LlmRequest::Suggestion, pattern.prompt(count));
+    }
+
+    /// Adds a command reported by the shell hook
+    #[cfg(unix)]
+    fn receive_hook_event(&mut self, event: HookEvent) {
+        let entry = HistoryEntry::from(event);
+        if entry.failed() {
+            self.failure_streak += 1;
+            self.state.react_to_failure(self.failure_streak);
+        } else {
+            self.failure_streak = 0;
+        }
+        self.note_activity();
+        self.push_recent_command(entry);
+    }
+
+    /// Adds a command to the prompt context, dropping the oldest beyond the history limit
+    fn push_recent_command(&mut self, entry: HistoryEntry) {
+        let output = self.plugins.on_command(&entry);
+        self.show_plugin_output(output);
+        let output = self.scripts.on_command(&self.state, &entry);
+        self.apply_script_output(output);
+        self.notice_skills(&entry);
+        self.notice_project_command(&entry);
+        self.recent_commands.push(entry);
+        if self.recent_commands.len() > self.config.command_history_limit {
+            self.recent_commands.remove(0);
+        }
+    }
+
+    /// Saves what changed since the last save, like mood decay, before the app exits
+    ///
+    /// The daemon owns the state while the app is attached to it.
+    pub fn shut_down(&mut self) -> Result<(), StorageError> {
+        if let Some(mut recording) = self.recording.take() {
+            let _ = recording.kill();
+        }
+        if self.attached_to_daemon {
+            return Ok(());
+        }
+        self.keep_unanswered();
+        if self.mode == Mode::Full {
+            self.state.input_draft = Some(self.ui.input.text().to_string()).filter(|draft| !draft.trim().is_empty());
+        }
+        self.state.decay_mood(clock::now());
+        let session = std::mem::replace(&mut self.session_stats, SessionStats::new(clock::now()));
+        usage::archive(&mut self.state.sessions, session, clock::now());
+        self.save_state()
+    }
+
+    /// Records messages still waiting for a reply at shutdown, in the chat
+    /// history and the transcript, so quitting doesn't lose them
+    fn keep_unanswered(&mut self) {
+        for user_message in std::mem::take(&mut self.unanswered) {
+            if let Some(transcript) = &self.transcript {
+                if let Err(e) = transcript.append(&self.state.name, &user_message, NO_REPLY) {
+                    tracing::warn!("couldn't write the transcript: {}", e);
+                }
+            }
+            let mut message = ChatMessage::new(user_message, NO_REPLY.to_string());
+            message.thread = self.state.current_thread.clone();
+            message.session = Some(self.count_exchange());
+            self.state.chat_history.push(message);
+        }
+    }
+
+    pub fn save_state(&mut self) -> Result<(), StorageError> {
+        if let Some(e) = backup::save_with_backup(self.store.as_mut(), &self.state, &self.config)? {
+            self.ui.add_message(format!("{}: Couldn't back up my state: {}", self.state.name, e));
+        }
+        Ok(())
+    }
+
+    /// Applies changes to config.toml or the project's `.pawshell.toml` made
+    /// while the pet is running
+    ///
+    /// A file that doesn't parse is reported and the previous settings stay in
+    /// effect. The LLM conversation carries over to a new backend.
+    fn reload_config_if_changed(&mut self) {
+        let path = config_path::get_config_file_path(None);
+        let mtimes = config_mtimes(&path);
+        if mtimes.0.is_none() || mtimes == self.config_mtimes {
+            return;
+        }
+        self.config_mtimes = mtimes;
+        let config = match config::Config::read(&path) {
+            Ok(config) => config,
+            Err(e) => {
+                tracing::warn!("config reload failed: {}", e);
+                self.ui.add_message(format!("{}: I'm keeping my previous settings because {}", self.state.name, e));
+                self.ui.show_toast("Config has errors, see chat");
+                return;
+            }
+        };
+        if config == self.config {
+            return;
+        }
+        for problem in config.validate() {
+            self.ui.add_message(format!("{}: Config problem: {}", self.state.name, problem));
+        }
+        let old = std::mem::replace(&mut self.config, config);
+        self.state.name = self.config.pet_name.clone();
+        self.art = PetArt::load(&self.config).0;
+        if (&self.config.llm_provider, &self.config.ollama_url, &self.config.ollama_model)
+            != (&old.llm_provider, &old.ollama_url, &old.ollama_model)
+            || self.config.ollama_options() != old.ollama_options()
+            || self.config.openai_options() != old.openai_options()
+            || llm::system_prompt(&self.config) != llm::system_prompt(&old)
+        {
+            if let Err(e) = self.rebuild_llm() {
+                self.ui.add_message(format!("{}: Couldn't switch backends: {}", self.state.name, e));
+            }
+        }
+        let config = &self.config;
+        if config.redaction_patterns != old.redaction_patterns {
+            let log = std::mem::take(&mut self.redactor.log);
+            self.redactor = Redactor::new(&config.redaction_patterns);
+            self.redactor.log = log;
+            for (pattern, error) in &self.redactor.invalid_patterns {
+                self.ui.add_message(format!("{}: Ignoring redaction pattern {}: {}", self.state.name, pattern, error));
+            }
+        }
+        if config.exec_denylist != old.exec_denylist {
+            self.denylist = Denylist::new(&config.exec_denylist);
+        }
+        if config.suggestion_cooldown_minutes != old.suggestion_cooldown_minutes {
+            self.pattern_detector.set_cooldown(config.suggestion_cooldown_minutes);
+        }
+        if config.include_workspace_context != old.include_workspace_context {
+            self.workspace = if config.include_workspace_context { WorkspaceContext::detect() } else { None };
+        }
+        if config.include_system_context != old.include_system_context {
+            self.environment = config.include_system_context.then(SystemEnvironment::detect);
+        }
+        if config.system_monitor != old.system_monitor {
+            self.system_monitor = config.system_monitor.then(SystemMonitor::new);
+            self.ui.system_load = None;
+        }
+        if config.connectivity_check_secs != old.connectivity_check_secs
+            || config.llm_provider != old.llm_provider
+            || config.ollama_url != old.ollama_url
+        {
+            // Start over with the new backend; the next tick probes it
+            self.last_backend_probe = None;
+            self.offline = false;
+            self.ui.offline = false;
+        }
+        if config.battery_awareness != old.battery_awareness {
+            self.battery = None;
+            self.last_battery_check = None;
+            self.ui.battery = None;
+        }
+        if config.load_plugins != old.load_plugins {
+            self.plugins = load_plugins(config, &mut self.ui, &self.state.name);
+        }
+        if config.load_scripts != old.load_scripts {
+            self.scripts = load_scripts(config, &mut self.ui, &self.state.name);
+        }
+        if config.zoxide_context != old.zoxide_context {
+            self.frequent_directories = if config.zoxide_context { FrequentDirectories::load() } else { None };
+        }
+        if config.editing_mode != old.editing_mode {
+            self.ui.vi = (config.editing_mode == EditingMode::Vi).then(Vi::default);
+        }
+        if config.write_transcripts != old.write_transcripts {
+            self.transcript = config.write_transcripts.then(Transcript::new);
+        }
+        let restart_needed = config.storage_backend != old.storage_backend
+            || config.sync_dir != old.sync_dir
+            || config.encrypt_history != old.encrypt_history;
+        if (config.command_history_limit, config.atuin_history, &config.atuin_db_path, config.merge_history_files)
+            != (old.command_history_limit, old.atuin_history, &old.atuin_db_path, old.merge_history_files)
+        {
+            self.load_shell_history();
+        }
+        if restart_needed {
+            self.ui.add_message(format!(
+                "{}: Storage and encryption changes take effect the next time I start.",
+                self.state.name
+            ));
+        }
+        self.ui.show_toast("Config reloaded");
+    }
+
+    /// Picks up state saved by another instance (e.g. a popup) since we last touched it
+    fn reload_state_if_changed(&mut self) {
+        if !self.store.changed_externally() {
+            return;
+        }
+        if let Ok(mut state) = self.store.load() {
+            state.name = self.config.pet_name.clone();
+            self.state = state;
+            self.ui.clear_messages();
+            replay_chat_history(&mut self.ui, &self.state, self.shown_session);
+            if let Some(notice) = self.store.take_notice() {
+                self.ui.add_message(format!("{}: {}", self.state.name, notice));
+            }
+        }
+    }
+}
+
+/// Creates the configured LLM backend
+/// Loads the plugins directory if plugins are enabled, reporting the ones that didn't load
+fn load_plugins(config: &config::Config, ui: &mut AppUI, pet_name: &str) -> PluginHost {
+    if !config.load_plugins {
+        return PluginHost::default();
+    }
+    let (host, problems) = PluginHost::load_dir(&plugins::plugins_dir());
+    for problem in problems {
+        ui.add_message(format!("{}: Skipping a plugin: {}", pet_name, problem));
+    }
+    host
+}
+
+/// Runs the scripts directory if scripts are enabled, reporting the ones that didn't load
+fn load_scripts(config: &config::Config, ui: &mut AppUI, pet_name: &str) -> ScriptHost {
+    if !config.load_scripts {
+        return ScriptHost::default();
+    }
+    let (host, problems) = ScriptHost::load_dir(&scripting::scripts_dir());
+    for problem in problems {
+        ui.add_message(format!("{}: Skipping a script: {}", pet_name, problem));
+    }
+    host
+}
+
+fn file_mtime(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+fn config_mtimes(config_file: &Path) -> (Option<SystemTime>, Option<SystemTime>) {
+    (file_mtime(config_file), project::find().and_then(|path| file_mtime(&path)))
+}
+
+/// The current thread's conversation, or only the part said in `session` when one was reopened
+fn conversation(state: &PetState, session: Option<u64>) -> Vec<&ChatMessage> {
+    let thread = threads::messages(&state.chat_history, &state.threads, state.current_thread.as_deref());
+    chat_sessions::messages(thread, session)
+}
+
+/// The latest exchange of the conversation on screen, which `/run`, `/bookmark`
+/// and `/retry` act on
+fn last_exchange(state: &PetState, session: Option<u64>) -> Option<&ChatMessage> {
+    conversation(state, session).last().copied()
+}
+
+/// Adds the persisted conversation of the current thread to the chat window
+fn replay_chat_history(ui: &mut AppUI, state: &PetState, session: Option<u64>) {
+    for message in conversation(state, session) {
+        ui.add_message(format!("You: {}", message.user_message));
+        ui.add_message(format!("{}: {}", state.name, message.response));
+    }
+}
+
+/// `$VISUAL` or `$EDITOR` on `path`, falling back to vi (Notepad on Windows)
+fn editor_command(path: &Path) -> Command {
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|editor| !editor.trim().is_empty()));
+    let mut words: Vec<String> = match editor {
+        Some(editor) => editor.split_whitespace().map(String::from).collect(),
+        None if cfg!(windows) => vec!["notepad".to_string()],
+        None => vec!["vi".to_string()],
+    };
+    let mut command = Command::new(words.remove(0));
+    command.args(words).arg(path);
+    command
+}
+
+/// Interrupts a recorder like Ctrl+C would, so it writes out a complete WAV file
+#[cfg(unix)]
+fn stop_recorder(recording: &mut std::process::Child) {
+    use nix::sys::signal::{kill, Signal};
+    use nix::unistd::Pid;
+    if let Err(e) = kill(Pid::from_raw(recording.id() as i32), Signal::SIGINT) {
+        tracing::warn!("couldn't stop the recorder: {}", e);
+    }
+}
+
+#[cfg(not(unix))]
+fn stop_recorder(recording: &mut std::process::Child) {
+    let _ = recording.kill();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn exchange(user_message: &str, response: &str, thread: Option<&str>, minute: u32) -> ChatMessage {
+        let mut message = ChatMessage::new(user_message.to_string(), response.to_string());
+        message.thread = thread.map(str::to_string);
+        message.timestamp = Some(Utc.with_ymd_and_hms(2024, 3, 1, 9, minute, 0).unwrap());
+        message
+    }
+
+    #[test]
+    fn run_uses_the_last_reply_of_the_shown_thread() {
+        let mut state = PetState::default();
+        state.chat_history.push(exchange("list files?", "Try `ls -la`", None, 0));
+        state.threads.push(Thread {
+            name: "side".to_string(),
+            parent: None,
+            forked_at: Utc.with_ymd_and_hms(2024, 3, 1, 9, 1, 0).unwrap(),
+        });
+        state.current_thread = Some("side".to_string());
+        state.chat_history.push(exchange("disk usage?", "Try `du -sh .`", Some("side"), 2));
+        let suggested = |state: &PetState| last_exchange(state, None).map(|message| exec::extract_commands(&message.response));
+        assert_eq!(suggested(&state), Some(vec!["du -sh .".to_string()]));
+
+        // Back in the main thread, /run offers main's reply, not the fork's newer one
+        state.current_thread = None;
+        assert_eq!(suggested(&state), Some(vec!["ls -la".to_string()]));
+    }
+}
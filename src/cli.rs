@@ -0,0 +1,115 @@
+//! Command-line interface for PetCLI
+//!
+//! Running without a subcommand starts the interactive TUI. Subcommands cover
+//! everything that runs outside of it, such as printing shell hook scripts.
+
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(name = "pawshell", version, about = "Your terminal pet companion")]
+pub struct Cli {
+    /// Use a separate config, state and history, e.g. `--profile work`
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+    /// Answer messages from stdin with JSON lines instead of starting the TUI
+    #[arg(long)]
+    pub headless: bool,
+    /// Log debug details too, and outside the TUI also print the log to stderr
+    #[arg(long, short, global = true)]
+    pub verbose: bool,
+    /// Stop the clock at TIME (RFC 3339, default 2024-01-01T12:00:00Z) and seed
+    /// randomness, so the TUI looks the same on every run; use a scratch profile
+    #[arg(long, global = true, value_name = "TIME", num_args = 0..=1, default_missing_value = "2024-01-01T12:00:00Z")]
+    pub freeze_time: Option<DateTime<Utc>>,
+    /// Seed for the pet's random behavior, for reproducible runs
+    #[arg(long, global = true)]
+    pub seed: Option<u64>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Print the shell hook script, e.g. `eval "$(pawshell init zsh)"`
+    Init {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Start a compact instance for `tmux display-popup` or floating terminals
+    Popup,
+    /// List state backups or restore one (quit the pet first)
+    Restore {
+        /// Show available backups, newest first (the default)
+        #[arg(long, conflicts_with = "from")]
+        list: bool,
+        /// Backup file name from `--list`, or a path to one
+        #[arg(long, value_name = "BACKUP")]
+        from: Option<String>,
+    },
+    /// Save the pet (state, chat history, name and art) to a single bundle file
+    ExportPet {
+        file: PathBuf,
+    },
+    /// Replace the pet with one from `export-pet` (quit the pet first)
+    ImportPet {
+        file: PathBuf,
+    },
+    /// Check the config, API keys, LLM backend and storage
+    Doctor,
+    /// Keep the pet running in the background for the TUI, shell hooks and `ask`
+    Daemon {
+        /// Stop the running daemon
+        #[arg(long, conflicts_with = "status")]
+        stop: bool,
+        /// Show whether a daemon is running and how the pet is doing
+        #[arg(long)]
+        status: bool,
+    },
+    /// Serve a local HTTP and WebSocket API for editors, status bars and extensions
+    Serve {
+        /// Port on 127.0.0.1 to listen on
+        #[arg(long, default_value_t = 7777)]
+        port: u16,
+    },
+    /// Ask the pet something and print the reply (through the daemon, if one is running)
+    Ask {
+        #[arg(required = true)]
+        message: Vec<String>,
+    },
+    /// Print the pet's mood and chat streak for a prompt or status line
+    Status {
+        #[arg(long, value_enum, default_value_t = StatusFormat::Starship)]
+        format: StatusFormat,
+    },
+    /// Report an executed command to the running pet (called by the shell hook)
+    #[command(hide = true)]
+    Hook {
+        #[arg(long)]
+        exit_code: i32,
+        #[arg(long)]
+        cwd: String,
+        /// The command line that was executed
+        #[arg(last = true)]
+        command: Vec<String>,
+    },
+}
+
+/// How `status` prints the pet's mood
+#[derive(Clone, Copy, ValueEnum)]
+pub enum StatusFormat {
+    /// Plain text, for a starship custom module or any prompt
+    Starship,
+    /// With tmux style codes, for `status-right`
+    Tmux,
+    Json,
+}
+
+/// Shells that `init` can generate hooks for
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Shell {
+    Zsh,
+    Bash,
+    Fish,
+}
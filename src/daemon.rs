@@ -0,0 +1,318 @@
+//! `pawshell daemon`: one long-lived pet for every client
+//!
+//! The daemon keeps the pet's state and LLM conversation in memory and owns
+//! the shell hook socket. Clients talk to it over a second unix socket with
+//! JSON lines: each line is a `Request`, answered by one `Response` line.
+//! A `subscribe` request instead keeps the connection open and streams every
+//! command the hooks report. When the daemon runs, the TUI sends its chat
+//! through it and `pawshell ask` uses it too, so they all share one pet.
+//!
+//! All work on the session happens in the daemon's event loop; connections
+//! only pass requests to it, and replies are generated on their own tasks.
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{broadcast, mpsc, oneshot};
+
+use crate::events::{AppEvent, EventSender};
+use crate::hook::{self, HookEvent};
+use pawshell_core::error::{LlmError, Remedy};
+use pawshell_core::exec;
+use pawshell_core::history::HistoryEntry;
+use pawshell_core::llm::{ChatContext, Generation};
+use pawshell_core::session::{PendingReply, PetSession};
+
+/// How long a client waits for an answer before giving up
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Something a client asks the daemon
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    Ping,
+    /// Chat with the pet; the exchange is recorded in its history
+    Ask { message: String },
+    Status,
+    /// Commands suggested in the pet's last reply
+    Suggestions,
+    /// Stream the commands the shell hooks report
+    Subscribe,
+    Stop,
+}
+
+/// The daemon's answer to a `Request`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    Pong,
+    Reply { text: String },
+    Status { name: String, mood: f32, chat_messages: usize, provider: String, model: String },
+    Suggestions { commands: Vec<String> },
+    /// A command reported by a shell hook, sent to subscribers
+    Command(HookEvent),
+    Stopping,
+    Error {
+        message: String,
+        /// What kind of backend failure it was, for clients that can suggest a fix
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cause: Option<LlmError>,
+    },
+}
+
+impl Response {
+    fn error(message: impl Into<String>) -> Self {
+        Response::Error { message: message.into(), cause: None }
+    }
+}
+
+/// Work for the daemon's event loop
+enum DaemonEvent {
+    Request(Request, oneshot::Sender<Response>),
+    /// Docs for a question were looked up, so its prompt can be built
+    DocsFound {
+        message: String,
+        docs: Option<String>,
+        reply: oneshot::Sender<Response>,
+    },
+    Generated {
+        pending: PendingReply,
+        result: Result<Generation, LlmError>,
+        latency: Duration,
+        reply: oneshot::Sender<Response>,
+    },
+    CommandReported(HookEvent),
+}
+
+pub fn socket_path() -> PathBuf {
+    hook::runtime_socket("petcli-daemon")
+}
+
+/// Whether a daemon is listening for this profile
+pub fn is_running() -> bool {
+    std::os::unix::net::UnixStream::connect(socket_path()).is_ok()
+}
+
+/// Runs the daemon until it is stopped with `pawshell daemon --stop`, Ctrl-C or SIGTERM
+pub async fn run() -> Result<(), Box<dyn Error>> {
+    if is_running() {
+        return Err(format!("A pet daemon is already running ({})", socket_path().display()).into());
+    }
+    let (mut session, warnings) = PetSession::open()?;
+    for warning in warnings {
+        eprintln!("{}", warning);
+    }
+
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let (commands, _) = broadcast::channel(64);
+    let listener = hook::bind(&socket_path())?;
+    tokio::spawn(accept_clients(listener, sender.clone(), commands.clone()));
+    let hook_sender = sender.clone();
+    hook::listen_with(move |event| {
+        let _ = hook_sender.send(DaemonEvent::CommandReported(event));
+    })?;
+    println!("{} is listening on {}", session.state.name, socket_path().display());
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    loop {
+        let event = tokio::select! {
+            event = receiver.recv() => event,
+            _ = tokio::signal::ctrl_c() => None,
+            _ = terminate.recv() => None,
+        };
+        let Some(event) = event else { break };
+        match event {
+            DaemonEvent::Request(Request::Ask { message }, reply) => {
+                let docs = ChatContext::look_up_docs(&session.config, &message);
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    let docs = docs.await;
+                    let _ = sender.send(DaemonEvent::DocsFound { message, docs, reply });
+                });
+            }
+            DaemonEvent::DocsFound { message, docs, reply } => {
+                session.refresh();
+                let pending = session.prepare(&message, docs);
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    let (result, latency) = pending.generate().await;
+                    let _ = sender.send(DaemonEvent::Generated { pending, result, latency, reply });
+                });
+            }
+            DaemonEvent::Generated { pending, result, latency, reply } => {
+                let response = match result {
+                    Ok(generation) => match session.record(pending, &generation, latency) {
+                        Ok(()) => Response::Reply { text: generation.text },
+                        Err(e) => Response::error(format!("Couldn't save the chat: {}", e.report())),
+                    },
+                    Err(e) => {
+                        tracing::warn!(category = e.category(), "reply failed: {}", e);
+                        Response::Error { message: e.to_string(), cause: Some(e) }
+                    }
+                };
+                let _ = reply.send(response);
+            }
+            DaemonEvent::Request(Request::Status, reply) => {
+                session.refresh();
+                let _ = reply.send(Response::Status {
+                    name: session.state.name.clone(),
+                    mood: session.state.mood,
+                    chat_messages: session.state.chat_history.len(),
+                    provider: session.llm().provider().to_string(),
+                    model: session.llm().model().to_string(),
+                });
+            }
+            DaemonEvent::Request(Request::Suggestions, reply) => {
+                session.refresh();
+                let last_reply = session.state.chat_history.last().map(|message| message.response.as_str());
+                let commands = last_reply.map(exec::extract_commands).unwrap_or_default();
+                let _ = reply.send(Response::Suggestions { commands });
+            }
+            DaemonEvent::Request(Request::Stop, reply) => {
+                let _ = reply.send(Response::Stopping);
+                break;
+            }
+            DaemonEvent::Request(Request::Ping | Request::Subscribe, reply) => {
+                let _ = reply.send(Response::Pong);
+            }
+            DaemonEvent::CommandReported(event) => {
+                let _ = commands.send(event.clone());
+                session.refresh();
+                let entry = HistoryEntry::from(event);
+                let failed = entry.failed();
+                session.record_command(entry);
+                // Only failures change the mood, and they're worth keeping
+                if failed {
+                    if let Err(e) = session.save() {
+                        eprintln!("Couldn't save the state: {}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    hook::unbind(&socket_path());
+    hook::cleanup();
+    Ok(session.save()?)
+}
+
+async fn accept_clients(
+    listener: UnixListener,
+    sender: mpsc::UnboundedSender<DaemonEvent>,
+    commands: broadcast::Sender<HookEvent>,
+) {
+    while let Ok((stream, _)) = listener.accept().await {
+        let sender = sender.clone();
+        let commands = commands.clone();
+        tokio::spawn(async move {
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let request = match serde_json::from_str::<Request>(&line) {
+                    Ok(request) => request,
+                    Err(e) => {
+                        let error = Response::error(format!("Invalid request: {}", e));
+                        if write_line(&mut writer, &error).await.is_err() {
+                            return;
+                        }
+                        continue;
+                    }
+                };
+                if let Request::Subscribe = request {
+                    let mut commands = commands.subscribe();
+                    loop {
+                        match commands.recv().await {
+                            Ok(event) => {
+                                if write_line(&mut writer, &Response::Command(event)).await.is_err() {
+                                    return;
+                                }
+                            }
+                            // A slow subscriber misses some commands rather than holding up the rest
+                            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                            Err(broadcast::error::RecvError::Closed) => return,
+                        }
+                    }
+                }
+                let (reply, response) = oneshot::channel();
+                if sender.send(DaemonEvent::Request(request, reply)).is_err() {
+                    return;
+                }
+                let Ok(response) = response.await else { return };
+                if write_line(&mut writer, &response).await.is_err() {
+                    return;
+                }
+            }
+        });
+    }
+}
+
+async fn write_line(writer: &mut (impl AsyncWriteExt + Unpin), message: &impl Serialize) -> std::io::Result<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await
+}
+
+/// Sends one request to the daemon and waits for its response
+pub async fn request(request: &Request) -> Result<Response, Box<dyn Error>> {
+    let stream = UnixStream::connect(socket_path())
+        .await
+        .map_err(|e| format!("No pet daemon is running ({}); start one with `pawshell daemon`", e))?;
+    let (reader, mut writer) = stream.into_split();
+    write_line(&mut writer, request).await?;
+    let mut lines = BufReader::new(reader).lines();
+    let line = tokio::time::timeout(REQUEST_TIMEOUT, lines.next_line())
+        .await
+        .map_err(|_| "The pet daemon didn't answer in time")??
+        .ok_or("The pet daemon closed the connection")?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+/// Asks the daemon's pet something
+pub async fn ask(message: &str) -> Result<String, LlmError> {
+    let daemon_error = |message: String| LlmError::Daemon { message };
+    match request(&Request::Ask { message: message.to_string() }).await {
+        Ok(Response::Reply { text }) => Ok(text),
+        Ok(Response::Error { cause: Some(cause), .. }) => Err(cause),
+        Ok(Response::Error { message, cause: None }) => Err(daemon_error(message)),
+        Ok(other) => Err(daemon_error(format!("Unexpected answer from the pet daemon: {:?}", other))),
+        Err(e) => Err(daemon_error(e.to_string())),
+    }
+}
+
+/// Streams the commands the daemon's hooks report, until the daemon stops
+pub async fn commands() -> Result<mpsc::UnboundedReceiver<HookEvent>, Box<dyn Error>> {
+    let stream = UnixStream::connect(socket_path()).await?;
+    let (reader, mut writer) = stream.into_split();
+    write_line(&mut writer, &Request::Subscribe).await?;
+    let (sender, receiver) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if let Ok(Response::Command(event)) = serde_json::from_str(&line) {
+                if sender.send(event).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    Ok(receiver)
+}
+
+/// Forwards commands the daemon's hooks report to the bus, as if the app had received them
+pub fn subscribe(events: EventSender) {
+    tokio::spawn(async move {
+        let Ok(mut commands) = commands().await else {
+            return;
+        };
+        while let Some(event) = commands.recv().await {
+            if events.send(AppEvent::CommandReported(event)).is_err() {
+                return;
+            }
+        }
+        let _ = events.send(AppEvent::Notification("Lost the connection to the pet daemon".to_string()));
+    });
+}
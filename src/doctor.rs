@@ -0,0 +1,157 @@
+//! `pawshell doctor`: checks that the pet is set up correctly
+//!
+//! Goes through the config file, environment overrides, plugins, scripts, API
+//! keys, the LLM backend and the state storage, printing what works and how to fix what
+//! doesn't.
+
+use std::time::Duration;
+
+use pawshell_core::config::{Config, LLMProvider};
+use pawshell_core::config_path;
+use pawshell_core::locale;
+use pawshell_core::plugins::{self, PluginHost};
+use pawshell_core::scripting::{self, ScriptHost};
+use pawshell_core::project;
+use pawshell_core::storage;
+
+/// How long to wait for the backend before calling it unreachable
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Outcome of a single check
+enum Check {
+    Ok(String),
+    Warn(String),
+    Fail(String),
+}
+
+/// Runs every check, returning whether all of them passed
+pub async fn run() -> bool {
+    let mut checks = Vec::new();
+
+    let config_path = config_path::get_config_file_path(None);
+    let (config, problems) = Config::load_checked();
+    if problems.is_empty() {
+        checks.push(Check::Ok(format!("Config {} is valid", config_path.display())));
+    }
+    checks.extend(problems.into_iter().map(Check::Fail));
+    if let Some(project_file) = project::find() {
+        checks.push(Check::Ok(format!("Project settings from {}", project_file.display())));
+    }
+
+    let overrides: Vec<String> = std::env::vars()
+        .map(|(key, _)| key)
+        .filter(|key| key.starts_with("PAWSHELL_") && key != "PAWSHELL_PASSPHRASE")
+        .collect();
+    if !overrides.is_empty() {
+        checks.push(Check::Ok(format!("Overridden by the environment: {}", overrides.join(", "))));
+    }
+
+    if let Some(language) = config.language.as_deref().filter(|language| !locale::is_translated(language)) {
+        checks.push(Check::Warn(format!(
+            "The pet replies in {}, but its built-in phrases stay in English",
+            language
+        )));
+    }
+
+    if config.load_plugins {
+        let (host, problems) = PluginHost::load_dir(&plugins::plugins_dir());
+        if !host.is_empty() {
+            checks.push(Check::Ok(format!("Plugins loaded: {}", host.describe().join(", "))));
+        }
+        checks.extend(problems.into_iter().map(Check::Fail));
+    }
+    if config.load_scripts {
+        let (host, problems) = ScriptHost::load_dir(&scripting::scripts_dir());
+        if !host.is_empty() {
+            checks.push(Check::Ok(format!("Scripts loaded: {}", host.describe().join(", "))));
+        }
+        checks.extend(problems.into_iter().map(Check::Fail));
+    }
+
+    checks.push(check_backend(&config).await);
+    checks.push(check_storage(&config));
+
+    let mut failures = 0;
+    for check in &checks {
+        match check {
+            Check::Ok(message) => println!("✓ {}", message),
+            Check::Warn(message) => println!("! {}", message),
+            Check::Fail(message) => {
+                failures += 1;
+                println!("✗ {}", message);
+            }
+        }
+    }
+    if failures > 0 {
+        println!("\n{} problem(s) found", failures);
+    }
+    failures == 0
+}
+
+async fn check_backend(config: &Config) -> Check {
+    let client = match reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => return Check::Fail(format!("Couldn't create an HTTP client: {}", e)),
+    };
+    match config.llm_provider {
+        LLMProvider::OpenAI => {
+            let Ok(api_key) = std::env::var("OPENAI_API_KEY") else {
+                return Check::Fail("OPENAI_API_KEY is not set; export it or add it to a .env file".to_string());
+            };
+            let options = config.openai_options();
+            let response = options.authorize(client.get(options.url("models")), &api_key).send().await;
+            match response {
+                Ok(response) if response.status().is_success() => Check::Ok("OpenAI accepts your API key".to_string()),
+                Ok(response) if response.status() == reqwest::StatusCode::UNAUTHORIZED => {
+                    Check::Fail("OpenAI rejected OPENAI_API_KEY; check that it is current".to_string())
+                }
+                Ok(response) => Check::Warn(format!("OpenAI answered with {}", response.status())),
+                Err(e) => Check::Fail(format!("Couldn't reach OpenAI at {}: {}", options.base_url, e)),
+            }
+        }
+        LLMProvider::Ollama => {
+            let url = format!("{}/api/tags", config.ollama_url.trim_end_matches('/'));
+            let response = match client.get(&url).send().await {
+                Ok(response) => response,
+                Err(e) => {
+                    return Check::Fail(format!(
+                        "Couldn't reach Ollama at {} ({}); is `ollama serve` running?",
+                        config.ollama_url, e
+                    ))
+                }
+            };
+            let tags: serde_json::Value = match response.json().await {
+                Ok(tags) => tags,
+                Err(e) => return Check::Fail(format!("{} didn't answer like Ollama: {}", config.ollama_url, e)),
+            };
+            let installed = tags["models"].as_array().is_some_and(|models| {
+                models.iter().filter_map(|model| model["name"].as_str()).any(|name| {
+                    name == config.ollama_model || name.strip_suffix(":latest") == Some(config.ollama_model.as_str())
+                })
+            });
+            if installed {
+                Check::Ok(format!("Ollama at {} has {}", config.ollama_url, config.ollama_model))
+            } else {
+                Check::Fail(format!(
+                    "Ollama at {} doesn't have {}; run `ollama pull {}`",
+                    config.ollama_url, config.ollama_model, config.ollama_model
+                ))
+            }
+        }
+    }
+}
+
+fn check_storage(config: &Config) -> Check {
+    let (mut store, warning) = storage::open(config);
+    if let Some(warning) = warning {
+        return Check::Fail(warning);
+    }
+    match store.load() {
+        Ok(state) => Check::Ok(format!(
+            "State in {} loads ({} chat messages)",
+            config_path::get_data_dir().display(),
+            state.chat_history.len()
+        )),
+        Err(e) => Check::Fail(format!("Couldn't load the state: {}", e)),
+    }
+}
@@ -0,0 +1,116 @@
+//! Key bindings of the chat input line
+//!
+//! Both editing modes have the arrows, Home, End, Delete, Ctrl+Left/Right by
+//! word, and readline's Ctrl+W, Ctrl+U, Ctrl+H, Ctrl+Y and Alt+Y. The Emacs
+//! mode adds the rest of readline's standard set; the vi mode sends keys typed
+//! in normal mode to `vi_mode`, with the arrows and the like turned into vi's
+//! keys. What the kill keys cut goes to the kill ring for Ctrl+Y.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use pawshell_core::line_editor::{self, KillRing, LineEditor};
+use pawshell_core::vi_mode::{Mode, Vi};
+
+/// Edits the input with a key the rest of the app didn't want
+pub fn handle_key(input: &mut LineEditor, vi: Option<&mut Vi>, kill_ring: &mut KillRing, key: &KeyEvent) {
+    match vi {
+        Some(vi) if vi.mode == Mode::Normal => normal_key(input, vi, key),
+        Some(_) => edit(input, kill_ring, key, false),
+        None => edit(input, kill_ring, key, true),
+    }
+}
+
+fn normal_key(input: &mut LineEditor, vi: &mut Vi, key: &KeyEvent) {
+    let c = match key.code {
+        KeyCode::Char(c) if !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => c,
+        KeyCode::Left | KeyCode::Backspace => 'h',
+        KeyCode::Right => 'l',
+        KeyCode::Home => '0',
+        KeyCode::End => '$',
+        KeyCode::Delete => 'x',
+        _ => return,
+    };
+    vi.normal(input, c);
+}
+
+fn edit(input: &mut LineEditor, kill_ring: &mut KillRing, key: &KeyEvent, emacs: bool) {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let alt = key.modifiers.contains(KeyModifiers::ALT);
+    let chars = input.chars();
+    let cursor = input.cursor();
+    match key.code {
+        // AltGr arrives as Ctrl+Alt, and types a character like plain keys
+        KeyCode::Char(c) if ctrl == alt => input.insert(c),
+        KeyCode::Backspace if alt && emacs => kill_ring.kill(input, line_editor::emacs_word_left(&chars, cursor)..cursor),
+        KeyCode::Backspace => {
+            input.backspace();
+        }
+        KeyCode::Delete => {
+            input.delete_char();
+        }
+        KeyCode::Left if ctrl => input.move_to(line_editor::emacs_word_left(&chars, cursor)),
+        KeyCode::Right if ctrl => input.move_to(line_editor::emacs_word_right(&chars, cursor)),
+        KeyCode::Left => input.left(),
+        KeyCode::Right => input.right(),
+        KeyCode::Home => input.home(),
+        KeyCode::End => input.end(),
+        KeyCode::Char('h') if ctrl => {
+            input.backspace();
+        }
+        KeyCode::Char('w') if ctrl => kill_ring.kill(input, line_editor::shell_word_left(&chars, cursor)..cursor),
+        KeyCode::Char('u') if ctrl => kill_ring.kill(input, 0..cursor),
+        KeyCode::Char('y') if ctrl => {
+            kill_ring.yank(input);
+        }
+        KeyCode::Char('y') if alt => {
+            kill_ring.yank_pop(input);
+        }
+        KeyCode::Char(c) if emacs && ctrl => match c {
+            'a' => input.home(),
+            'e' => input.end(),
+            'b' => input.left(),
+            'f' => input.right(),
+            'd' => {
+                input.delete_char();
+            }
+            'k' => kill_ring.kill(input, cursor..chars.len()),
+            't' => input.transpose(),
+            _ => {}
+        },
+        KeyCode::Char(c) if emacs && alt => {
+            let word_end = line_editor::emacs_word_right(&chars, cursor);
+            match c {
+                'b' => input.move_to(line_editor::emacs_word_left(&chars, cursor)),
+                'f' => input.move_to(word_end),
+                'd' => kill_ring.kill(input, cursor..word_end),
+                'u' | 'l' | 'c' => {
+                    input.map(cursor..word_end, |word| change_case(word, c));
+                    input.move_to(word_end);
+                }
+                _ => {}
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Alt+U upper-cases a word, Alt+L lower-cases it and Alt+C capitalizes it
+fn change_case(text: &str, key: char) -> String {
+    match key {
+        'u' => text.to_uppercase(),
+        'l' => text.to_lowercase(),
+        _ => {
+            let mut capitalized = false;
+            text.chars()
+                .map(|c| {
+                    if !capitalized && c.is_alphanumeric() {
+                        capitalized = true;
+                        c.to_uppercase().collect::<String>()
+                    } else {
+                        c.to_lowercase().collect()
+                    }
+                })
+                .collect()
+        }
+    }
+}
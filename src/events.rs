@@ -0,0 +1,287 @@
+//! The app's internal message bus
+//!
+//! Everything that happens to the app arrives as an `AppEvent` on one channel:
+//! key presses from a dedicated input thread, ticks from a timer task, and
+//! results of slow work (LLM replies, executed commands, commands reported by
+//! the shell hook) from the tasks doing it. The render loop in `terminal`
+//! only waits on that channel, so it never blocks on I/O.
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use pawshell_core::dotfiles::Dotfile;
+use pawshell_core::error::LlmError;
+use pawshell_core::exec::ExecOutput;
+use pawshell_core::github::GhItem;
+use pawshell_core::llm::{Generation, LLMBackend};
+use pawshell_core::ollama::PullProgress;
+use pawshell_core::weather::Weather;
+#[cfg(unix)]
+use crate::hook::HookEvent;
+use pawshell_core::pet::Revision;
+use pawshell_core::retention::ArchivedExchange;
+
+pub type EventSender = UnboundedSender<AppEvent>;
+pub type EventReceiver = UnboundedReceiver<AppEvent>;
+
+/// How often `AppEvent::Tick` fires
+const TICK_RATE: Duration = Duration::from_millis(100);
+/// How long the input thread waits for input before checking whether it should pause or stop
+const INPUT_POLL: Duration = Duration::from_millis(50);
+
+/// Something the app should react to
+pub enum AppEvent {
+    Key(KeyEvent),
+    /// The terminal was resized and needs a redraw
+    Resize,
+    /// The terminal gained (true) or lost focus, if it reports that
+    Focus(bool),
+    /// Time for periodic work like mood decay and config reloads
+    Tick,
+    /// A backend answered (or failed to answer) a prompt
+    LlmResponse {
+        request: LlmRequest,
+        result: Result<Generation, LlmError>,
+        /// The backend that answered, for the chat message's metadata
+        provider: &'static str,
+        model: String,
+        latency: Duration,
+    },
+    /// A confirmed command finished running
+    CommandFinished {
+        command: String,
+        result: Result<ExecOutput, String>,
+        duration: Duration,
+        /// Directory it ran in
+        cwd: Option<String>,
+    },
+    /// `gh` fetched a pull request or issue for `/gh`
+    GitHubFetched {
+        item: GhItem,
+        number: String,
+        result: Result<String, String>,
+    },
+    /// The tldr pages finished downloading; `command` is the page to show then
+    TldrUpdated {
+        result: Result<usize, String>,
+        command: Option<String>,
+    },
+    /// The weather provider answered
+    WeatherFetched(Result<Weather, String>),
+    /// A reachability probe of the backend finished; whether it got through
+    BackendProbed(bool),
+    /// Docs for a chat message were looked up, so its prompt can be built
+    DocsFound { request: DocsRequest, docs: Option<String> },
+    /// A `/jq` or `/awk` playground run finished, for the playground's revision
+    PlaygroundRan { revision: u64, result: Result<String, String> },
+    /// A voice recording was transcribed
+    Transcribed(Result<String, String>),
+    /// The image model finished a `/portrait`, as PNG
+    PortraitPainted(Result<Vec<u8>, String>),
+    /// Ollama reported how far a `/model pull` has got
+    ModelPullProgress(PullProgress),
+    /// One of the two models of `/compare` answered
+    CompareAnswered { id: u64, side: usize, result: Result<Generation, LlmError>, latency: Duration },
+    /// A `/model pull` finished
+    ModelPulled { model: String, result: Result<(), String> },
+    /// A command was run in a shell with the hook installed
+    #[cfg(unix)]
+    CommandReported(HookEvent),
+    /// Something worth a toast, from a background task
+    Notification(String),
+    /// The OS asked the app to stop (SIGINT, SIGTERM or SIGHUP)
+    Quit,
+    /// The OS asked the app to suspend (SIGTSTP)
+    #[cfg(unix)]
+    Suspend,
+}
+
+/// What a chat prompt waiting for its docs is for
+pub enum DocsRequest {
+    Chat { user_message: String, revision: Revision },
+    /// `/compare`, with its two backends ready
+    Compare { question: String, models: (String, String), backends: [Box<dyn LLMBackend>; 2] },
+}
+
+/// What a prompt was sent for, so its reply ends up in the right place
+pub enum LlmRequest {
+    /// A chat message typed by the user, or sent again with `/retry` or `/edit`
+    Chat { user_message: String, revision: Revision },
+    /// Alias suggestions for `/analyze`
+    Analyze,
+    /// An unprompted suggestion for a repetitive workflow
+    Suggestion,
+    /// A commit message for the staged changes, for `/commitmsg`
+    CommitMessage,
+    /// A summary of a pull request or issue, for `/gh`
+    GitHubSummary,
+    /// Findings for one chunk of the file `/review` is going through
+    CodeReview { chunk: usize },
+    /// A fixed expression for the `/jq` or `/awk` playground, for the broken one
+    PlaygroundFix { expression: String },
+    /// An explanation of the `/regex` lab's pattern
+    RegexExplanation { pattern: String },
+    /// A crontab line or, with `timer`, a systemd timer for `/cron`
+    Cron { timer: bool },
+    /// A version of the script `/script` is writing, with the changes asked for so far
+    Script { description: String, changes: Vec<String> },
+    /// Suggestions for a dotfile, for `/dotfiles`, with its unredacted contents
+    DotfileAdvice { file: Dotfile, original: String },
+    /// A translation for `/translate`, with the text it's of
+    Translation { language: String, original: String },
+    /// The written part of a weekly report, for `/report`, with the numbers it's about
+    WeeklyReport { facts: String },
+    /// A title for a chat session, for `/sessions`
+    SessionTitle { session: u64 },
+    /// Notes on the oldest exchanges, which are archived with them when it arrives
+    RetentionSummary { exchanges: Vec<ArchivedExchange> },
+}
+
+pub fn channel() -> (EventSender, EventReceiver) {
+    mpsc::unbounded_channel()
+}
+
+/// Lets the terminal hand stdin to another program, like a pager
+pub struct InputThread {
+    paused: Arc<AtomicBool>,
+}
+
+impl InputThread {
+    /// Stops reading input; returns once the thread is no longer waiting on it
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+        std::thread::sleep(INPUT_POLL * 2);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Forwards terminal input to the bus from a blocking thread
+///
+/// The thread ends once the receiving side is gone.
+pub fn spawn_input(events: EventSender) -> InputThread {
+    let paused = Arc::new(AtomicBool::new(false));
+    let thread_paused = paused.clone();
+    std::thread::spawn(move || loop {
+        if events.is_closed() {
+            break;
+        }
+        if thread_paused.load(Ordering::SeqCst) {
+            std::thread::sleep(INPUT_POLL);
+            continue;
+        }
+        match event::poll(INPUT_POLL) {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => {
+                let _ = events.send(AppEvent::Notification(format!("Couldn't read input: {}", e)));
+                break;
+            }
+        }
+        let event = match event::read() {
+            Ok(Event::Key(key)) if key.kind != KeyEventKind::Release => AppEvent::Key(key),
+            Ok(Event::Resize(..)) => AppEvent::Resize,
+            Ok(Event::FocusGained) => AppEvent::Focus(true),
+            Ok(Event::FocusLost) => AppEvent::Focus(false),
+            Ok(_) => continue,
+            Err(e) => {
+                let _ = events.send(AppEvent::Notification(format!("Couldn't read input: {}", e)));
+                break;
+            }
+        };
+        if events.send(event).is_err() {
+            break;
+        }
+    });
+    InputThread { paused }
+}
+
+/// Sends `AppEvent::Quit` for every stop signal and `AppEvent::Suspend` for
+/// SIGTSTP until the receiving side is gone
+///
+/// Raw mode turns Ctrl+C and Ctrl+Z into key presses, so these only come from
+/// outside, e.g. `kill` or a closing terminal window.
+pub fn spawn_signals(events: EventSender) {
+    tokio::spawn(async move {
+        #[cfg(unix)]
+        let (mut terminate, mut hangup, mut suspend) = {
+            use tokio::signal::unix::{signal, SignalKind};
+            let signals = (
+                signal(SignalKind::terminate()),
+                signal(SignalKind::hangup()),
+                signal(SignalKind::from_raw(nix::sys::signal::Signal::SIGTSTP as i32)),
+            );
+            let (Ok(terminate), Ok(hangup), Ok(suspend)) = signals else {
+                return;
+            };
+            (terminate, hangup, suspend)
+        };
+        loop {
+            #[cfg(unix)]
+            let event = tokio::select! {
+                _ = tokio::signal::ctrl_c() => AppEvent::Quit,
+                _ = terminate.recv() => AppEvent::Quit,
+                _ = hangup.recv() => AppEvent::Quit,
+                _ = suspend.recv() => AppEvent::Suspend,
+            };
+            #[cfg(not(unix))]
+            let event = match tokio::signal::ctrl_c().await {
+                Ok(()) => AppEvent::Quit,
+                Err(_) => return,
+            };
+            if events.send(event).is_err() {
+                return;
+            }
+        }
+    });
+}
+
+/// Sends `AppEvent::Tick` at a fixed rate until the receiving side is gone
+pub fn spawn_ticker(events: EventSender) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TICK_RATE);
+        loop {
+            interval.tick().await;
+            if events.send(AppEvent::Tick).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Parses a key binding from the config, like "F9", "ctrl+space" or "alt+v"
+pub fn parse_key(spec: &str) -> Option<(KeyCode, KeyModifiers)> {
+    let spec = spec.trim().to_lowercase();
+    let mut parts: Vec<&str> = spec.split('+').map(str::trim).collect();
+    let key = parts.pop()?;
+    let mut modifiers = KeyModifiers::NONE;
+    for modifier in parts {
+        modifiers |= match modifier {
+            "ctrl" | "control" => KeyModifiers::CONTROL,
+            "alt" | "meta" | "option" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return None,
+        };
+    }
+    let code = match key {
+        "space" => KeyCode::Char(' '),
+        "tab" => KeyCode::Tab,
+        "enter" => KeyCode::Enter,
+        "insert" => KeyCode::Insert,
+        _ if key.len() > 1 && key.starts_with('f') => KeyCode::F(key[1..].parse().ok().filter(|n| (1..=12).contains(n))?),
+        _ if key.chars().count() == 1 => KeyCode::Char(key.chars().next()?),
+        _ => return None,
+    };
+    Some((code, modifiers))
+}
+
+/// Whether a key press is the binding `spec`
+pub fn is_binding(key: &KeyEvent, spec: &str) -> bool {
+    parse_key(spec).is_some_and(|(code, modifiers)| key.code == code && key.modifiers == modifiers)
+}
+
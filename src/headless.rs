@@ -0,0 +1,125 @@
+//! `pawshell --headless`: the pet on stdin and stdout, without a TTY
+//!
+//! Every line on stdin is a message, either plain text or a JSON object like
+//! `{"message": "..."}` for text spanning several lines. Each gets exactly one
+//! JSON line on stdout:
+//!
+//! - `{"type": "reply", "message": "...", "text": "..."}`
+//! - `{"type": "error", "message": "...", "error": "...", "category": "...", "remedy": "..."}`,
+//!   where backend and storage failures have a category and suggested fix
+//! - `{"type": "status", ...}` for `/status`
+//!
+//! so `echo "question" | pawshell --headless` works in scripts, cron jobs and
+//! tests, and programs can keep it running as a REPL. Warnings go to stderr.
+//! Questions go through the daemon when one is running.
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::io::Write;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+#[cfg(unix)]
+use crate::daemon;
+use pawshell_core::error;
+use pawshell_core::session::PetSession;
+
+#[derive(Deserialize)]
+struct Input {
+    message: String,
+}
+
+/// Who answers: the daemon's pet, or one loaded just for this run
+enum Pet {
+    #[cfg(unix)]
+    Daemon,
+    Session(Box<PetSession>),
+}
+
+impl Pet {
+    fn connect() -> Result<Self, Box<dyn Error>> {
+        #[cfg(unix)]
+        if daemon::is_running() {
+            return Ok(Pet::Daemon);
+        }
+        let (session, warnings) = PetSession::open()?;
+        for warning in warnings {
+            eprintln!("{}", warning);
+        }
+        Ok(Pet::Session(Box::new(session)))
+    }
+
+    async fn ask(&mut self, message: &str) -> Result<String, Box<dyn Error>> {
+        match self {
+            #[cfg(unix)]
+            Pet::Daemon => Ok(daemon::ask(message).await?),
+            Pet::Session(session) => Ok(session.ask(message).await?),
+        }
+    }
+
+    async fn status(&mut self) -> Result<Value, Box<dyn Error>> {
+        match self {
+            #[cfg(unix)]
+            Pet::Daemon => Ok(serde_json::to_value(daemon::request(&daemon::Request::Status).await?)?),
+            Pet::Session(session) => {
+                session.refresh();
+                Ok(json!({
+                    "type": "status",
+                    "name": session.state.name,
+                    "mood": session.state.mood,
+                    "chat_messages": session.state.chat_history.len(),
+                    "provider": session.llm().provider(),
+                    "model": session.llm().model(),
+                }))
+            }
+        }
+    }
+}
+
+/// Answers stdin until it closes, returning whether every message got a reply
+pub async fn run() -> Result<bool, Box<dyn Error>> {
+    let mut pet = Pet::connect()?;
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut all_answered = true;
+    while let Some(line) = lines.next_line().await? {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let message = if line.starts_with('{') {
+            serde_json::from_str::<Input>(line).map(|input| input.message).map_err(|e| e.to_string())
+        } else {
+            Ok(line.to_string())
+        };
+        let output = match message {
+            Ok(message) if message.trim() == "/status" => pet
+                .status()
+                .await
+                .unwrap_or_else(|e| error_line(&message, e.as_ref())),
+            Ok(message) => match pet.ask(&message).await {
+                Ok(text) => json!({ "type": "reply", "message": message, "text": text }),
+                Err(e) => {
+                    all_answered = false;
+                    error_line(&message, e.as_ref())
+                }
+            },
+            Err(e) => {
+                all_answered = false;
+                json!({ "type": "error", "message": line, "error": format!("Invalid input: {}", e) })
+            }
+        };
+        let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "{}", output)?;
+        stdout.flush()?;
+    }
+    Ok(all_answered)
+}
+
+fn error_line(message: &str, e: &(dyn Error + 'static)) -> Value {
+    let mut line = json!({ "type": "error", "message": message, "error": e.to_string() });
+    if let Some((category, remedy)) = error::diagnose(e) {
+        line["category"] = json!(category);
+        line["remedy"] = json!(remedy);
+    }
+    line
+}
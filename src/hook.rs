@@ -0,0 +1,192 @@
+//! Shell hook integration for PetCLI
+//!
+//! Instead of scraping history files, users can install a small preexec/precmd hook
+//! (printed by `pawshell init <shell>`) that reports every executed command, together
+//! with its working directory and exit code, to the running app over a unix socket.
+//! This gives the pet real-time and accurate command context.
+//!
+//! The hook invokes this binary's hidden `hook` subcommand, which forwards the
+//! command as a single JSON line. Nothing happens if the app isn't running.
+
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::UnixListener;
+
+use crate::cli::Shell;
+use crate::events::{AppEvent, EventSender};
+use pawshell_core::clock;
+use pawshell_core::config_path;
+use pawshell_core::history::HistoryEntry;
+
+/// Sockets bound by this process, the only ones it may remove
+static BOUND: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// A command reported by the shell hook
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookEvent {
+    pub command: String,
+    pub cwd: String,
+    pub exit_code: i32,
+}
+
+impl From<HookEvent> for HistoryEntry {
+    fn from(event: HookEvent) -> Self {
+        Self {
+            command: event.command,
+            timestamp: Some(clock::now()),
+            cwd: Some(event.cwd),
+            exit_code: Some(event.exit_code),
+            duration: None,
+        }
+    }
+}
+
+/// Returns the path of the socket the running app listens on
+///
+/// Each profile has its own socket so their pets don't see each other's commands.
+pub fn socket_path() -> PathBuf {
+    runtime_socket("petcli")
+}
+
+/// Returns the path for a socket named `base`, with the profile appended
+pub fn runtime_socket(base: &str) -> PathBuf {
+    let name = match config_path::profile() {
+        Some(profile) => format!("{}-{}.sock", base, profile),
+        None => format!("{}.sock", base),
+    };
+    match dirs::runtime_dir() {
+        Some(dir) => dir.join(name),
+        None => config_path::get_cache_dir().join(name),
+    }
+}
+
+/// Sends a command to the running app; silently does nothing if it isn't running
+pub fn send(event: &HookEvent) -> Result<(), Box<dyn std::error::Error>> {
+    let Ok(mut stream) = UnixStream::connect(socket_path()) else {
+        return Ok(());
+    };
+    let mut line = serde_json::to_string(event)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// Listens on the hook socket, forwarding every received command to the bus
+pub fn listen(events: EventSender) -> std::io::Result<()> {
+    listen_with(move |event| {
+        let _ = events.send(AppEvent::CommandReported(event));
+    })
+}
+
+/// Listens on the hook socket, calling `on_command` for every received command
+///
+/// A stale socket left behind by a crashed instance is replaced.
+pub fn listen_with(on_command: impl Fn(HookEvent) + Clone + Send + 'static) -> std::io::Result<()> {
+    let listener = bind(&socket_path())?;
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let on_command = on_command.clone();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stream).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if let Ok(event) = serde_json::from_str::<HookEvent>(&line) {
+                        on_command(event);
+                    }
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Binds a unix socket, replacing a stale one
+///
+/// A socket another process still listens on is left alone, with an
+/// `AddrInUse` error, so a second instance can't take it over.
+pub fn bind(path: &Path) -> std::io::Result<UnixListener> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AddrInUse,
+                format!("{} is in use by another instance", path.display()),
+            ));
+        }
+        std::fs::remove_file(path)?;
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    BOUND.lock().unwrap_or_else(|e| e.into_inner()).push(path.to_path_buf());
+    Ok(listener)
+}
+
+/// Removes a socket on shutdown, if this process bound it
+pub fn unbind(path: &Path) {
+    let mut bound = BOUND.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(index) = bound.iter().position(|bound| bound == path) {
+        bound.remove(index);
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Removes the hook socket on shutdown, if this process owns it
+pub fn cleanup() {
+    unbind(&socket_path());
+}
+
+/// Returns the hook script for the given shell
+///
+/// The script calls back into `exe` in a detached subshell so the prompt never
+/// waits on the pet.
+pub fn init_script(shell: Shell, exe: &str) -> String {
+    let script = match shell {
+        Shell::Zsh => ZSH_HOOK,
+        Shell::Bash => BASH_HOOK,
+        Shell::Fish => FISH_HOOK,
+    };
+    // Profile names are restricted to characters that are safe to quote
+    let profile = config_path::profile()
+        .map(|profile| format!(" --profile '{}'", profile))
+        .unwrap_or_default();
+    script.replace("{exe}", exe).replace("{profile}", &profile)
+}
+
+const ZSH_HOOK: &str = r#"__petcli_preexec() { __petcli_cmd="$1" }
+__petcli_precmd() {
+  local exit_code=$?
+  [[ -n "$__petcli_cmd" ]] || return
+  ( '{exe}'{profile} hook --exit-code "$exit_code" --cwd "$PWD" -- "$__petcli_cmd" >/dev/null 2>&1 & )
+  unset __petcli_cmd
+}
+autoload -Uz add-zsh-hook
+add-zsh-hook preexec __petcli_preexec
+add-zsh-hook precmd __petcli_precmd
+"#;
+
+const BASH_HOOK: &str = r#"__petcli_precmd() {
+  local exit_code=$?
+  local last
+  last=$(HISTTIMEFORMAT= builtin history 1)
+  if [[ -n "$last" && "$last" != "$__petcli_last" ]]; then
+    __petcli_last="$last"
+    local cmd
+    cmd=$(sed 's/^ *[0-9]* *//' <<< "$last")
+    ( '{exe}'{profile} hook --exit-code "$exit_code" --cwd "$PWD" -- "$cmd" >/dev/null 2>&1 & )
+  fi
+  return $exit_code
+}
+__petcli_last=$(HISTTIMEFORMAT= builtin history 1)
+PROMPT_COMMAND="__petcli_precmd${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+"#;
+
+const FISH_HOOK: &str = r#"function __petcli_postexec --on-event fish_postexec
+    set -l exit_code $status
+    '{exe}'{profile} hook --exit-code $exit_code --cwd $PWD -- $argv[1] >/dev/null 2>&1 &
+    disown 2>/dev/null
+end
+"#;
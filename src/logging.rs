@@ -0,0 +1,78 @@
+//! Logging to rotating files in the data directory
+//!
+//! Backend requests and replies, failures, state saves and executed commands
+//! are logged to `logs/pawshell.YYYY-MM-DD.log` in the data directory, with a
+//! new file every day and a week of files kept. `/log` opens the current one
+//! in a pager. The default level is `info`; `--verbose` switches to `debug`
+//! and, outside the TUI, also logs to stderr. `RUST_LOG` overrides both.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::EnvFilter;
+
+use pawshell_core::config_path;
+
+const FILE_PREFIX: &str = "pawshell";
+const FILE_SUFFIX: &str = "log";
+/// Days of logs to keep
+const KEEP_FILES: usize = 7;
+
+pub fn log_dir() -> PathBuf {
+    config_path::get_data_dir().join("logs")
+}
+
+/// Starts logging; the returned guard flushes the file when dropped at exit
+///
+/// Logging is best effort: if the log directory can't be created, the pet
+/// runs without it.
+pub fn init(verbose: bool, to_stderr: bool) -> Option<WorkerGuard> {
+    let level = if verbose { "debug" } else { "info" };
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(format!("petcli={},pawshell_core={}", level, level)));
+    std::fs::create_dir_all(log_dir()).ok()?;
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(FILE_PREFIX)
+        .filename_suffix(FILE_SUFFIX)
+        .max_log_files(KEEP_FILES)
+        .build(log_dir())
+        .ok()?;
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+
+    let file = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(false)
+        .with_span_events(FmtSpan::CLOSE);
+    let stderr = (verbose && to_stderr).then(|| tracing_subscriber::fmt::layer().with_writer(std::io::stderr));
+    tracing_subscriber::registry().with(filter).with(file).with(stderr).try_init().ok()?;
+    Some(guard)
+}
+
+/// The most recent log file, if there is one
+pub fn latest_file() -> Option<PathBuf> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(log_dir())
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == FILE_SUFFIX))
+        .collect();
+    // Dates in the names sort chronologically
+    files.sort();
+    files.pop()
+}
+
+/// `$PAGER`, or a pager that starts at the end of the file
+pub fn pager_command(path: &Path) -> Command {
+    let pager = std::env::var("PAGER").ok().filter(|pager| !pager.trim().is_empty());
+    let mut words: Vec<String> = match pager {
+        Some(pager) => pager.split_whitespace().map(String::from).collect(),
+        None if cfg!(windows) => vec!["more".to_string()],
+        None => vec!["less".to_string(), "+G".to_string()],
+    };
+    let mut command = Command::new(words.remove(0));
+    command.args(words).arg(path);
+    command
+}
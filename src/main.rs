@@ -5,36 +5,253 @@
 //! modules, each handling specific functionality:
 //!
 //! - app: Core application logic and state management
-//! - pet: Pet state and behavior definitions
-//! - llm: Language model interface and implementations
 //! - ui: Terminal user interface components
-//! - config: Configuration management
-//! - config_path: Configuration file path handling
-//! - ollama: Ollama LLM backend implementation
-//! - terminal: Terminal initialization and event handling
+//! - terminal: Terminal initialization and the render loop
+//! - events: The message bus between input, background tasks and the app
+//! - cli: Command-line argument parsing
+//! - hook: Shell hook scripts and the socket they report commands to
+//! - doctor: Setup checks for `pawshell doctor`
+//! - daemon: The background pet and the socket protocol its clients use
+//! - server: Local HTTP and WebSocket API on top of the daemon
+//! - headless: JSON lines on stdin and stdout for `--headless`
+//! - logging: Rotating log files and `--verbose`
+//! - notify: Desktop notifications while the user is away
+//! - status: The pet's mood for prompts and tmux status lines
+//!
+//! The pet itself (state, mood, LLM backends, history parsing, storage, ...)
+//! lives in the `pawshell-core` library crate.
 
-mod pet;
-mod llm;
+mod doctor;
 mod ui;
-mod config;
-mod config_path;
-mod ollama;
+mod editing;
 mod app;
 mod terminal;
+mod cli;
+mod events;
+mod headless;
+mod logging;
+mod notify;
+mod status;
+#[cfg(unix)]
+mod hook;
+#[cfg(unix)]
+mod daemon;
+#[cfg(unix)]
+mod server;
 
+use clap::Parser;
+use pawshell_core::{backup, bundle, clock, config, config_path, error};
+use pawshell_core::clock::FakeClock;
+use pawshell_core::session::PetSession;
 use dotenv::dotenv;
-use crate::app::App;
+use crate::app::{App, Mode};
+use crate::cli::{Cli, Command};
 use crate::terminal::Terminal;
 use ratatui::backend::CrosstermBackend;
 use std::io;
+use std::sync::Arc;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenv().ok();
+    let cli = Cli::parse();
+    if let Some(profile) = &cli.profile {
+        config_path::set_profile(profile)?;
+    }
+    if let Some(time) = cli.freeze_time {
+        clock::set(Arc::new(FakeClock::at(time)));
+        clock::set_seed(cli.seed.unwrap_or(0));
+    } else if let Some(seed) = cli.seed {
+        clock::set_seed(seed);
+    }
+    if let Err(e) = config_path::migrate_to_data_dir() {
+        eprintln!("Couldn't move pet data to {}: {}", config_path::get_data_dir().display(), e);
+    }
+    // The shell hook and status run for every command or prompt, so they stay out of the log
+    let tui = matches!(cli.command, None | Some(Command::Popup)) && !cli.headless;
+    let log_guard = match cli.command {
+        Some(Command::Init { .. } | Command::Hook { .. } | Command::Status { .. }) => None,
+        _ => logging::init(cli.verbose, !tui),
+    };
+
+    // Commands that report their own failures set this instead of returning an error
+    let mut failed = false;
+    let result = match cli.command {
+        Some(Command::Init { shell }) => init_hook(shell),
+        Some(Command::Hook { exit_code, cwd, command }) => send_hook(exit_code, cwd, command.join(" ")),
+        Some(Command::Popup) => run_tui(Mode::Popup).await,
+        Some(Command::Restore { from: Some(backup), .. }) => restore_backup(&backup),
+        Some(Command::Restore { from: None, .. }) => list_backups(),
+        Some(Command::ExportPet { file }) => export_pet(&file),
+        Some(Command::ImportPet { file }) => import_pet(&file),
+        Some(Command::Daemon { stop: true, .. }) => stop_daemon().await,
+        Some(Command::Daemon { status: true, .. }) => daemon_status().await,
+        Some(Command::Daemon { .. }) => run_daemon().await,
+        Some(Command::Serve { port }) => serve(port).await,
+        Some(Command::Ask { message }) => ask(&message.join(" ")).await,
+        Some(Command::Status { format }) => status::print(format),
+        Some(Command::Doctor) => {
+            failed = !doctor::run().await;
+            Ok(())
+        }
+        None if cli.headless => headless::run().await.map(|answered| failed = !answered),
+        None => run_tui(Mode::Full).await,
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {}", error::report(e.as_ref()));
+        failed = true;
+    }
+    if failed {
+        // Exiting skips destructors, so flush the log first
+        drop(log_guard);
+        std::process::exit(1);
+    }
+    Ok(())
+}
 
+async fn run_tui(mode: Mode) -> Result<(), Box<dyn std::error::Error>> {
     let mut terminal = Terminal::<CrosstermBackend<io::Stdout>>::init()?;
-    let app = App::new();
-    terminal.run(app).await?;
+    let (_, problems) = config::Config::load_checked();
+    if !problems.is_empty() {
+        let config_path = config_path::get_config_file_path(None);
+        if !terminal.confirm_config_problems(&config_path.display().to_string(), &problems)? {
+            return Ok(());
+        }
+    }
+    let (events, receiver) = events::channel();
+    let app = App::new(mode, events);
+    let result = terminal.run(app, receiver).await;
+    #[cfg(unix)]
+    if mode == Mode::Full {
+        hook::cleanup();
+    }
+    result
+}
+
+/// Prints the pet's reply, asking the daemon's pet if one is running
+async fn ask(message: &str) -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(unix)]
+    if daemon::is_running() {
+        println!("{}", daemon::ask(message).await?);
+        return Ok(());
+    }
+    let (mut session, warnings) = PetSession::open()?;
+    for warning in warnings {
+        eprintln!("{}", warning);
+    }
+    println!("{}", session.ask(message).await?);
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn run_daemon() -> Result<(), Box<dyn std::error::Error>> {
+    daemon::run().await
+}
+
+#[cfg(unix)]
+async fn stop_daemon() -> Result<(), Box<dyn std::error::Error>> {
+    daemon::request(&daemon::Request::Stop).await?;
+    println!("Stopped the pet daemon");
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn daemon_status() -> Result<(), Box<dyn std::error::Error>> {
+    if !daemon::is_running() {
+        println!("No pet daemon is running");
+        return Ok(());
+    }
+    if let daemon::Response::Status { name, mood, chat_messages, provider, model } =
+        daemon::request(&daemon::Request::Status).await?
+    {
+        println!("{} is running ({:.0}% mood, {} chat messages, {} {})", name, mood * 100.0, chat_messages, provider, model);
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn serve(port: u16) -> Result<(), Box<dyn std::error::Error>> {
+    server::serve(port).await
+}
+
+#[cfg(not(unix))]
+async fn serve(_port: u16) -> Result<(), Box<dyn std::error::Error>> {
+    Err("The API server runs on top of the daemon, which needs unix sockets".into())
+}
+
+#[cfg(not(unix))]
+async fn run_daemon() -> Result<(), Box<dyn std::error::Error>> {
+    Err("The daemon needs unix sockets and isn't supported on this platform".into())
+}
+
+#[cfg(not(unix))]
+async fn stop_daemon() -> Result<(), Box<dyn std::error::Error>> {
+    run_daemon().await
+}
+
+#[cfg(not(unix))]
+async fn daemon_status() -> Result<(), Box<dyn std::error::Error>> {
+    run_daemon().await
+}
+
+fn list_backups() -> Result<(), Box<dyn std::error::Error>> {
+    let backups = backup::list();
+    if backups.is_empty() {
+        println!("No backups in {}", backup::backup_dir().display());
+    }
+    for path in backups {
+        let modified = std::fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+        let modified = modified.map(|time| chrono::DateTime::<chrono::Local>::from(time).format("%Y-%m-%d %H:%M:%S").to_string());
+        let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        println!("{}  {}", modified.unwrap_or_default(), name);
+    }
+    Ok(())
+}
+
+fn restore_backup(backup: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let target = backup::restore(backup)?;
+    println!("Restored {} (the previous state was backed up first)", target.display());
+    Ok(())
+}
+
+fn export_pet(file: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+    let bundle = bundle::export(file)?;
+    println!(
+        "Exported {} ({} chat messages) to {}",
+        bundle.pet_name,
+        bundle.state.chat_history.len(),
+        file.display()
+    );
+    Ok(())
+}
+
+fn import_pet(file: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+    let (bundle, backup) = bundle::import(file)?;
+    println!("Imported {} ({} chat messages)", bundle.pet_name, bundle.state.chat_history.len());
+    if let Some(backup) = backup {
+        println!("The previous pet was backed up to {}", backup.display());
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn init_hook(shell: cli::Shell) -> Result<(), Box<dyn std::error::Error>> {
+    let exe = std::env::current_exe()?;
+    print!("{}", hook::init_script(shell, &exe.to_string_lossy()));
+    Ok(())
+}
+
+#[cfg(unix)]
+fn send_hook(exit_code: i32, cwd: String, command: String) -> Result<(), Box<dyn std::error::Error>> {
+    hook::send(&hook::HookEvent { command, cwd, exit_code })
+}
+
+#[cfg(not(unix))]
+fn init_hook(_shell: cli::Shell) -> Result<(), Box<dyn std::error::Error>> {
+    Err("Shell hooks need unix sockets and aren't supported on this platform".into())
+}
 
+#[cfg(not(unix))]
+fn send_hook(_exit_code: i32, _cwd: String, _command: String) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
@@ -0,0 +1,125 @@
+//! Desktop notifications for when the pet needs attention
+//!
+//! With `desktop_notifications = true`, the pet sends an OS notification while
+//! you're away from it (its terminal lost focus, or nothing was typed for a few
+//! minutes) when its mood drops below `notify_mood_below`, or when a reply that
+//! took longer than `notify_slow_reply_secs` finally arrives. Reminders, the
+//! ends of pomodoro phases and break nudges are sent whether you're away or not. Nothing is sent during `quiet_hours`
+//! or while `/focus` is on.
+
+use std::time::{Duration, Instant};
+
+use pawshell_core::clock;
+use pawshell_core::config::Config;
+
+/// How long without a key press counts as being away, even with focus
+const IDLE_AFTER: Duration = Duration::from_secs(5 * 60);
+/// How much of a reply goes into the notification
+const MAX_BODY_CHARS: usize = 120;
+
+/// Tracks whether the user is around and decides what is worth a notification
+pub struct Notifier {
+    /// Terminals that don't report focus changes count as focused
+    focused: bool,
+    last_input: Instant,
+    /// Whether the low mood was already reported; re-armed once the mood recovers
+    mood_reported: bool,
+    /// `/focus` is on, so nothing is sent
+    do_not_disturb: bool,
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Notifier {
+    pub fn new() -> Self {
+        Self { focused: true, last_input: Instant::now(), mood_reported: false, do_not_disturb: false }
+    }
+
+    pub fn focus_changed(&mut self, focused: bool) {
+        self.focused = focused;
+    }
+
+    /// Notes a key press
+    pub fn input(&mut self) {
+        self.last_input = Instant::now();
+    }
+
+    pub fn set_do_not_disturb(&mut self, on: bool) {
+        self.do_not_disturb = on;
+    }
+
+    fn away(&self) -> bool {
+        !self.focused || self.last_input.elapsed() >= IDLE_AFTER
+    }
+
+    /// Reports a mood that just dropped below the threshold
+    pub fn check_mood(&mut self, config: &Config, pet_name: &str, mood: f32) {
+        if mood >= config.notify_mood_below {
+            self.mood_reported = false;
+            return;
+        }
+        if self.mood_reported || !self.away() {
+            return;
+        }
+        self.mood_reported = self.send(config, &format!("{} misses you", pet_name), &format!("Mood is down to {:.0}%", mood * 100.0));
+    }
+
+    /// Reports a reply that kept the user waiting long enough to look elsewhere
+    pub fn reply_arrived(&mut self, config: &Config, pet_name: &str, latency: Duration, text: &str) {
+        if latency.as_secs() < config.notify_slow_reply_secs || !self.away() {
+            return;
+        }
+        let mut body: String = text.chars().take(MAX_BODY_CHARS).collect();
+        if body.len() < text.len() {
+            body.push('…');
+        }
+        self.send(config, &format!("{} answered", pet_name), &body);
+    }
+
+    /// Reports a reminder that came due
+    pub fn reminder_due(&self, config: &Config, pet_name: &str, text: &str) {
+        self.send(config, &format!("{} reminds you", pet_name), text);
+    }
+
+    /// Reports the end of a pomodoro phase
+    pub fn pomodoro_phase_over(&self, config: &Config, pet_name: &str, text: &str) {
+        self.send(config, &format!("{}'s pomodoro", pet_name), text);
+    }
+
+    /// Nudges to take a break from the screen
+    pub fn break_due(&self, config: &Config, pet_name: &str, text: &str) {
+        self.send(config, &format!("{} wants you to stretch", pet_name), text);
+    }
+
+    /// Asks for the charger when the battery runs low
+    pub fn battery_low(&self, config: &Config, pet_name: &str, text: &str) {
+        self.send(config, &format!("{} is getting sleepy", pet_name), text);
+    }
+
+    fn send(&self, config: &Config, summary: &str, body: &str) -> bool {
+        !self.do_not_disturb && send(config, summary, body)
+    }
+}
+
+/// Shows a notification unless they're off or it's quiet time, returning whether it was sent
+///
+/// The notification server is talked to on a blocking thread; failures (e.g.
+/// no notification daemon) are only logged.
+fn send(config: &Config, summary: &str, body: &str) -> bool {
+    if !config.desktop_notifications || config.is_quiet_time(clock::local_now().time()) {
+        return false;
+    }
+    tracing::debug!(summary, "desktop notification");
+    let mut notification = notify_rust::Notification::new();
+    notification.appname("pawshell").summary(summary).body(body);
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = notification.show() {
+            tracing::warn!("couldn't show a desktop notification: {}", e);
+        }
+    });
+    true
+}
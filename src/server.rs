@@ -0,0 +1,186 @@
+//! `pawshell serve`: a local HTTP and WebSocket API for the pet
+//!
+//! Editors, status bars and browser extensions can chat with the pet and read
+//! its state over HTTP on 127.0.0.1:
+//!
+//! - `GET /state`: name, mood, chat length and backend
+//! - `POST /message` with `{"message": "..."}`: the pet's reply as `{"reply": "..."}`
+//! - `GET /suggestions`: commands suggested in the pet's last reply
+//! - `GET /ws`: a WebSocket speaking the daemon's JSON protocol, which also
+//!   pushes every command the shell hooks report
+//!
+//! The server is a client of the daemon, starting one in-process if none is
+//! running. Every request needs the token from the `api-token` file in the data
+//! directory, as `Authorization: Bearer <token>` or a `?token=` parameter for
+//! WebSockets, so web pages can't talk to the pet behind your back.
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Request as HttpRequest, State};
+use axum::http::StatusCode;
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response as HttpResponse};
+use axum::routing::{any, get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::json;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::daemon::{self, Request, Response};
+use pawshell_core::config_path;
+use pawshell_core::crypto;
+use pawshell_core::error::{LlmError, Remedy};
+
+#[derive(Deserialize)]
+struct MessageBody {
+    message: String,
+}
+
+/// Where the API token is kept
+pub fn token_path() -> PathBuf {
+    config_path::get_data_dir().join("api-token")
+}
+
+/// Reads the API token, creating it on first use
+fn load_or_create_token() -> std::io::Result<String> {
+    let path = token_path();
+    if let Ok(token) = std::fs::read_to_string(&path) {
+        if !token.trim().is_empty() {
+            return Ok(token.trim().to_string());
+        }
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let token = crypto::random_token();
+    std::fs::write(&path, &token)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(token)
+}
+
+/// Serves the API on `port` until stopped, running a daemon alongside if none is running
+pub async fn serve(port: u16) -> Result<(), Box<dyn Error>> {
+    let token = Arc::new(load_or_create_token()?);
+    let app = Router::new()
+        .route("/state", get(state))
+        .route("/message", post(message))
+        .route("/suggestions", get(suggestions))
+        .route("/ws", any(websocket))
+        .layer(middleware::from_fn_with_state(token, authenticate));
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", port)).await?;
+    println!("API listening on http://127.0.0.1:{} (token in {})", port, token_path().display());
+    let server = async { axum::serve(listener, app).await.map_err(Box::<dyn Error>::from) };
+
+    if daemon::is_running() {
+        server.await
+    } else {
+        tokio::select! {
+            result = daemon::run() => result,
+            result = server => result,
+        }
+    }
+}
+
+async fn authenticate(State(token): State<Arc<String>>, request: HttpRequest, next: Next) -> HttpResponse {
+    let header = request
+        .headers()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let query = request
+        .uri()
+        .query()
+        .and_then(|query| query.split('&').find_map(|pair| pair.strip_prefix("token=")));
+    if header.or(query) == Some(token.as_str()) {
+        next.run(request).await
+    } else {
+        error(StatusCode::UNAUTHORIZED, "Missing or wrong API token")
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> HttpResponse {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+/// A backend failure with its category and suggested fix
+fn backend_error(message: &str, cause: Option<LlmError>) -> HttpResponse {
+    let Some(cause) = cause else {
+        return error(StatusCode::BAD_GATEWAY, message);
+    };
+    let body = json!({ "error": message, "category": cause.category(), "remedy": cause.remedy() });
+    (StatusCode::BAD_GATEWAY, Json(body)).into_response()
+}
+
+/// Forwards a request to the daemon, turning failures into HTTP errors
+async fn forward(request: Request) -> Result<Response, HttpResponse> {
+    match daemon::request(&request).await {
+        Ok(Response::Error { message, cause }) => Err(backend_error(&message, cause)),
+        Ok(response) => Ok(response),
+        Err(e) => Err(error(StatusCode::SERVICE_UNAVAILABLE, &e.to_string())),
+    }
+}
+
+async fn state() -> HttpResponse {
+    match forward(Request::Status).await {
+        Ok(response) => Json(response).into_response(),
+        Err(response) => response,
+    }
+}
+
+async fn message(Json(body): Json<MessageBody>) -> HttpResponse {
+    match forward(Request::Ask { message: body.message }).await {
+        Ok(Response::Reply { text }) => Json(json!({ "reply": text })).into_response(),
+        Ok(other) => Json(other).into_response(),
+        Err(response) => response,
+    }
+}
+
+async fn suggestions() -> HttpResponse {
+    match forward(Request::Suggestions).await {
+        Ok(response) => Json(response).into_response(),
+        Err(response) => response,
+    }
+}
+
+async fn websocket(upgrade: WebSocketUpgrade) -> HttpResponse {
+    upgrade.on_upgrade(talk).into_response()
+}
+
+/// Answers each request frame with the daemon's response and pushes reported commands
+async fn talk(mut socket: WebSocket) {
+    let mut commands = daemon::commands().await.ok();
+    loop {
+        let outgoing = tokio::select! {
+            frame = socket.recv() => {
+                let Some(Ok(frame)) = frame else { return };
+                match frame {
+                    Message::Text(text) => answer(text.as_str()).await,
+                    Message::Close(_) => return,
+                    _ => continue,
+                }
+            }
+            Some(event) = async { commands.as_mut()?.recv().await } => Response::Command(event),
+        };
+        let Ok(text) = serde_json::to_string(&outgoing) else { continue };
+        if socket.send(Message::Text(text.into())).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn answer(frame: &str) -> Response {
+    match serde_json::from_str::<Request>(frame) {
+        Ok(Request::Subscribe | Request::Stop) => {
+            Response::Error { message: "Not available over the API".to_string(), cause: None }
+        }
+        Ok(request) => daemon::request(&request)
+            .await
+            .unwrap_or_else(|e| Response::Error { message: e.to_string(), cause: None }),
+        Err(e) => Response::Error { message: format!("Invalid request: {}", e), cause: None },
+    }
+}
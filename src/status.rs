@@ -0,0 +1,46 @@
+//! `pawshell status`: the pet's mood for prompts and tmux status lines
+//!
+//! Reads the saved state without starting the pet, so it's quick enough to run
+//! on every prompt, and prints a mood glyph, the mood and the chat streak (days
+//! in a row with a chat):
+//!
+//! - `starship`: `😺 85% 🔥3`, for a starship custom module or any prompt
+//! - `tmux`: the same with tmux style codes coloring the mood
+//! - `json`: `{"name": ..., "mood": 0.85, "level": "happy", "glyph": "😺", "streak": 3}`
+//!
+//! The mood is decayed to the current time the way the TUI would show it, but
+//! nothing is saved.
+
+use serde_json::json;
+use std::error::Error;
+
+use crate::cli::StatusFormat;
+use pawshell_core::clock;
+use pawshell_core::config::Config;
+use pawshell_core::mood::MoodLevel;
+use pawshell_core::storage;
+
+pub fn print(format: StatusFormat) -> Result<(), Box<dyn Error>> {
+    let (mut store, _) = storage::open(&Config::load());
+    let mut state = store.load()?;
+    state.decay_mood(clock::now());
+    let streak = state.chat_streak(&clock::local_now());
+
+    let level = state.mood_level();
+    let (glyph, name, color) = match level {
+        MoodLevel::Happy => ("😺", "happy", "green"),
+        MoodLevel::Content => ("🐱", "content", "yellow"),
+        MoodLevel::Sad => ("😿", "sad", "red"),
+    };
+    let percent = format!("{:.0}%", state.mood * 100.0);
+    let streak_text = if streak > 0 { format!(" 🔥{}", streak) } else { String::new() };
+    match format {
+        StatusFormat::Starship => println!("{} {}{}", glyph, percent, streak_text),
+        StatusFormat::Tmux => println!("#[fg={}]{} {}#[default]{}", color, glyph, percent, streak_text),
+        StatusFormat::Json => println!(
+            "{}",
+            json!({ "name": state.name, "mood": state.mood, "level": name, "glyph": glyph, "streak": streak })
+        ),
+    }
+    Ok(())
+}
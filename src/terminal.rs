@@ -3,21 +3,35 @@
 //! This module manages the terminal interface using the crossterm and ratatui libraries.
 //! It handles:
 //! - Terminal initialization and cleanup
-//! - Event processing (keyboard input)
-//! - UI rendering and update loop
+//! - Key handling for events from the `events` bus
+//! - UI rendering loop
 //! - Terminal state management
 //!
 //! Consider splitting the event handling logic into a separate module if the
 //! input handling becomes more complex.
 
-use crossterm::event::{self, Event, KeyCode};
-use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::event::{DisableFocusChange, EnableFocusChange};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
 use crossterm::execute;
 use ratatui::prelude::*;
-use std::io;
-use std::time::{Duration, Instant};
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+use std::time::Duration;
+
+use pawshell_core::config::TerminalGraphics;
+use pawshell_core::error;
+use pawshell_core::portrait;
 
 use crate::app::App;
+use crate::editing;
+use crate::events::{self, AppEvent, EventReceiver, InputThread};
+use crate::ui;
+
+/// How long quitting waits for replies that are still on their way
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
 
 /// Terminal wrapper that manages the terminal interface and event loop
 pub struct Terminal<B: Backend + io::Write> {
@@ -31,72 +45,407 @@ impl<B: Backend + io::Write> Terminal<B> {
     }
 
     pub fn init() -> io::Result<Terminal<CrosstermBackend<io::Stdout>>> {
+        let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+        terminal.take_over()?;
+        Ok(terminal)
+    }
+
+    /// Switches to the alternate screen in raw mode, with focus changes reported
+    fn take_over(&mut self) -> io::Result<()> {
         enable_raw_mode()?;
-        let mut stdout = io::stdout();
-        execute!(stdout, crossterm::terminal::EnterAlternateScreen)?;
-        let backend = CrosstermBackend::new(stdout);
-        Terminal::new(backend)
+        execute!(self.terminal.backend_mut(), EnterAlternateScreen, EnableFocusChange)
+    }
+
+    /// Gives the terminal back the way the shell expects it
+    fn hand_back(&mut self) -> io::Result<()> {
+        disable_raw_mode()?;
+        execute!(self.terminal.backend_mut(), DisableFocusChange, LeaveAlternateScreen, crossterm::cursor::Show)
+    }
+
+    /// Shows config problems until the user continues (true) or quits (false)
+    pub fn confirm_config_problems(&mut self, config_path: &str, problems: &[String]) -> io::Result<bool> {
+        loop {
+            self.terminal.draw(|f| ui::render_config_problems(f, config_path, problems))?;
+            // Windows also reports key releases, like the Enter that started pawshell
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Enter => return Ok(true),
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+                    _ => {}
+                }
+            }
+        }
     }
 
-    pub async fn run(&mut self, mut app: App) -> io::Result<()> {
-        let mut last_tick = Instant::now();
-        let tick_rate = Duration::from_millis(100);
+    /// Draws the app and feeds it events until the user quits, then shuts it down
+    ///
+    /// Slow work happens on other tasks, so the loop only ever waits on `events`.
+    pub async fn run(&mut self, mut app: App, mut events: EventReceiver) -> Result<(), Box<dyn Error>> {
+        let input = events::spawn_input(app.event_sender());
+        events::spawn_ticker(app.event_sender());
+        events::spawn_signals(app.event_sender());
 
+        // Quitting from the dialog means not waiting for the reply it warned about
+        let mut quit_now = false;
         loop {
+            if let Some((path, graphics)) = app.take_picture() {
+                if let Err(e) = self.show_picture(&path, graphics, &input) {
+                    tracing::warn!("couldn't show {}: {}", path.display(), e);
+                    app.ui.show_toast(format!("Couldn't show the picture: {}", e));
+                }
+            }
+
             let terminal = &mut self.terminal;
             terminal.draw(|f| {
-                app.ui.render(f, &app.state.name, app.state.mood, &app.config.pet_ascii);
+                app.ui.render(f, &app.state.name, app.state.mood, &app.pet_ascii(), app.failure_streak);
             })?;
 
-            let timeout = tick_rate
-                .checked_sub(last_tick.elapsed())
-                .unwrap_or_else(|| Duration::from_secs(0));
-
-            if event::poll(timeout)? {
-                if let Event::Key(key) = event::read()? {
-                    match key.code {
-                        KeyCode::Enter => {
-                            if let Err(e) = app.handle_input().await {
-                                eprintln!("Error handling input: {}", e);
-                            }
-                        }
-                        KeyCode::Up => app.ui.scroll_up(),
-                        KeyCode::Down => app.ui.scroll_down(),
-                        KeyCode::PageUp => {
-                            for _ in 0..5 { app.ui.scroll_up(); }
-                        }
-                        KeyCode::PageDown => {
-                            for _ in 0..5 { app.ui.scroll_down(); }
+            let Some(event) = events.recv().await else {
+                break;
+            };
+            if let AppEvent::Key(_) = event {
+                app.notifier.input();
+                app.note_activity();
+            }
+            let key = match event {
+                AppEvent::Key(key) if is_ctrl(&key, 'c') => {
+                    if app.confirming_quit() || !app.ask_to_quit() {
+                        quit_now = app.confirming_quit();
+                        break;
+                    }
+                    continue;
+                }
+                // Unless Ctrl+Z is the undo key and there's an edit to undo
+                #[cfg(unix)]
+                AppEvent::Key(key) if is_ctrl(&key, 'z') && !(events::is_binding(&key, app.undo_keys().0) && app.ui.input.can_undo()) => {
+                    self.suspend(&input)?;
+                    continue;
+                }
+                #[cfg(unix)]
+                AppEvent::Suspend => {
+                    self.suspend(&input)?;
+                    continue;
+                }
+                AppEvent::Key(key) => key,
+                AppEvent::Quit => break,
+                event => {
+                    app.handle_event(event).await;
+                    continue;
+                }
+            };
+
+            // A pending confirmation captures all input until answered
+            if app.confirming_quit() {
+                match key.code {
+                    KeyCode::Char('y') | KeyCode::Char('Y') => {
+                        quit_now = true;
+                        break;
+                    }
+                    KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => app.cancel_quit(),
+                    _ => {}
+                }
+                continue;
+            }
+            if app.awaiting_confirmation() {
+                match key.code {
+                    KeyCode::Char('y') | KeyCode::Char('Y') => app.confirm_exec(true),
+                    KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => app.confirm_exec(false),
+                    _ => {}
+                }
+                continue;
+            }
+
+            // The regex lab takes all keys until it's closed
+            if let Some(lab) = app.ui.regex_lab.as_mut() {
+                match key.code {
+                    KeyCode::Esc => app.ui.regex_lab = None,
+                    KeyCode::Tab | KeyCode::BackTab => lab.editing_sample = !lab.editing_sample,
+                    KeyCode::Char('e') if key.modifiers.contains(KeyModifiers::CONTROL) => app.explain_regex(),
+                    KeyCode::Enter if lab.editing_sample => lab.sample.push('\n'),
+                    KeyCode::Enter => lab.editing_sample = true,
+                    KeyCode::Char(c) => lab.focused().push(c),
+                    KeyCode::Backspace => {
+                        lab.focused().pop();
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            // So does a playground, which runs the expression after every change
+            if let Some(playground) = app.ui.playground.as_mut() {
+                let edited = match key.code {
+                    KeyCode::Esc => {
+                        app.ui.playground = None;
+                        false
+                    }
+                    KeyCode::Tab | KeyCode::BackTab => {
+                        playground.editing_input = !playground.editing_input;
+                        false
+                    }
+                    KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.fix_playground();
+                        false
+                    }
+                    KeyCode::Enter if playground.editing_input => {
+                        playground.input.push('\n');
+                        true
+                    }
+                    KeyCode::Enter => {
+                        playground.editing_input = true;
+                        false
+                    }
+                    KeyCode::Char(c) => {
+                        playground.focused().push(c);
+                        true
+                    }
+                    KeyCode::Backspace => playground.focused().pop().is_some(),
+                    _ => false,
+                };
+                if edited {
+                    app.run_playground();
+                }
+                continue;
+            }
+
+            // And `/compare`, until the better answer is picked
+            if let Some(comparison) = app.ui.comparison.as_mut() {
+                match key.code {
+                    KeyCode::Esc => app.ui.comparison = None,
+                    KeyCode::Left | KeyCode::Char('1') => app.pick_winner(0),
+                    KeyCode::Right | KeyCode::Char('2') => app.pick_winner(1),
+                    KeyCode::Up => comparison.scroll = comparison.scroll.saturating_sub(1),
+                    KeyCode::Down => comparison.scroll += 1,
+                    _ => {}
+                }
+                continue;
+            }
+
+            // And the session browser, until a session is picked
+            if let Some(browser) = app.ui.session_browser.as_mut() {
+                match key.code {
+                    KeyCode::Esc => app.ui.session_browser = None,
+                    KeyCode::Up => browser.up(),
+                    KeyCode::Down => browser.down(),
+                    KeyCode::Enter => app.open_session(),
+                    _ => {}
+                }
+                continue;
+            }
+
+            // And the history search, which narrows down as you type
+            if let Some(search) = app.ui.history_search.as_mut() {
+                let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                match key.code {
+                    KeyCode::Esc => app.ui.history_search = None,
+                    KeyCode::Up => search.up(),
+                    KeyCode::Down => search.down(),
+                    // Like the shell's own Ctrl+R, pressing it again goes to the next match
+                    KeyCode::Char('r') if ctrl => search.down(),
+                    KeyCode::Char(c @ ('e' | 'f')) if ctrl => {
+                        let improve = c == 'f';
+                        if app.ask_about_history_command(improve) {
+                            self.submit(&mut app, &input);
                         }
-                        KeyCode::Char(c) => app.ui.input.push(c),
-                        KeyCode::Backspace => { app.ui.input.pop(); }
-                        KeyCode::Esc => break,
-                        _ => {}
                     }
+                    KeyCode::Enter => app.insert_history_command(),
+                    KeyCode::Char(c) => search.push(c),
+                    KeyCode::Backspace => search.pop(),
+                    _ => {}
+                }
+                continue;
+            }
+
+            if app.voice_key().is_some_and(|spec| events::is_binding(&key, spec)) {
+                app.toggle_recording();
+                continue;
+            }
+
+            // After macro_record_key, the next key names the macro
+            if app.naming_macro() {
+                app.start_macro(match key.code {
+                    KeyCode::Char(c) => Some(c),
+                    _ => None,
+                });
+                continue;
+            }
+            if events::is_binding(&key, app.macro_record_key()) {
+                app.toggle_macro_recording();
+                continue;
+            }
+            // Alt+<letter> replays the macro in that register, if there is one
+            if let KeyCode::Char(c) = key.code {
+                if let Some(inputs) = key.modifiers.contains(KeyModifiers::ALT).then(|| app.macro_inputs(c)).flatten() {
+                    for line in inputs {
+                        app.ui.input.set(line);
+                        self.submit(&mut app, &input);
+                    }
+                    continue;
+                }
+            }
+
+            match key.code {
+                KeyCode::Enter => self.submit(&mut app, &input),
+                KeyCode::Up if key.modifiers.contains(KeyModifiers::ALT) => app.react(true),
+                KeyCode::Down if key.modifiers.contains(KeyModifiers::ALT) => app.react(false),
+                KeyCode::Up => app.ui.scroll_up(),
+                KeyCode::Down => app.ui.scroll_down(),
+                KeyCode::PageUp => {
+                    for _ in 0..5 { app.ui.scroll_up(); }
+                }
+                KeyCode::PageDown => {
+                    for _ in 0..5 { app.ui.scroll_down(); }
+                }
+                KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::ALT) => app.replay_speech(),
+                KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => app.open_history_search(),
+                _ if events::is_binding(&key, app.undo_keys().0) => {
+                    app.ui.input.undo();
+                }
+                // Ctrl+Y redoes right after an undo, and yanks otherwise
+                _ if events::is_binding(&key, app.undo_keys().1) && app.ui.input.can_redo() => {
+                    app.ui.input.redo();
                 }
+                // In vi's insert mode Esc goes to normal mode, and drops a half-typed command there
+                KeyCode::Esc if app.ui.vi.as_mut().is_some_and(|vi| vi.escape(&mut app.ui.input)) => {}
+                KeyCode::Esc if !app.ask_to_quit() => break,
+                KeyCode::Esc => {}
+                _ => editing::handle_key(&mut app.ui.input, app.ui.vi.as_mut(), &mut app.ui.kill_ring, &key),
             }
+        }
+
+        if !quit_now {
+            self.finish_replies(&mut app, &mut events).await?;
+        }
+        Ok(app.shut_down()?)
+    }
+
+    /// Sends the input line, handing the terminal over if it ran something
+    /// interactive
+    fn submit(&mut self, app: &mut App, input: &InputThread) {
+        if let Some(vi) = app.ui.vi.as_mut() {
+            vi.reset();
+        }
+        if let Err(e) = app.handle_input() {
+            tracing::error!("handling input failed: {}", e);
+            app.ui.add_message(format!("{}: Something went wrong.\n{}", app.state.name, error::report(e.as_ref())));
+        }
+        if let Some(handoff) = app.take_handoff() {
+            let status = self.hand_over(handoff.command(), input);
+            app.finish_handoff(handoff, status);
+        }
+    }
+
+    /// Draws a picture over the whole screen until a key is pressed
+    ///
+    /// It's sized to fit the terminal's height, assuming cells about twice as
+    /// tall as they are wide.
+    fn show_picture(&mut self, path: &Path, graphics: TerminalGraphics, input: &InputThread) -> io::Result<()> {
+        let png = std::fs::read(path)?;
+        let (columns, rows) = crossterm::terminal::size()?;
+        let width = columns.min(rows.saturating_sub(3) * 2).max(1);
+        let picture = match graphics {
+            TerminalGraphics::Kitty => portrait::kitty(&png, width),
+            TerminalGraphics::Sixel => {
+                let cell_width = crossterm::terminal::window_size()
+                    .ok()
+                    .filter(|size| size.width > 0 && size.columns > 0)
+                    .map_or(10, |size| size.width / size.columns);
+                portrait::sixel(&png, u32::from(width) * u32::from(cell_width)).map_err(io::Error::other)?
+            }
+            TerminalGraphics::Auto | TerminalGraphics::Off => return Ok(()),
+        };
 
-            if last_tick.elapsed() >= tick_rate {
-                app.update();
-                last_tick = Instant::now();
+        input.pause();
+        let stdout = self.terminal.backend_mut();
+        execute!(stdout, Clear(ClearType::All), crossterm::cursor::MoveTo(0, 0))?;
+        write!(stdout, "{}\r\n{} (press any key)", picture, path.display())?;
+        Write::flush(stdout)?;
+        let waited = loop {
+            match event::read() {
+                Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => break Ok(()),
+                Ok(_) => {}
+                Err(e) => break Err(e),
             }
+        };
+        if graphics == TerminalGraphics::Kitty {
+            // Kitty keeps pictures on screen until they're deleted
+            write!(self.terminal.backend_mut(), "\x1b_Ga=d,q=2\x1b\\")?;
         }
+        self.terminal.clear()?;
+        input.resume();
+        waited
+    }
+
+    /// Lets replies that are still on their way arrive, so they get recorded
+    /// and written to the transcript
+    ///
+    /// Gives up after `SHUTDOWN_GRACE`, or right away on Esc, Ctrl+C or another
+    /// stop signal; the requests are cancelled when the runtime shuts down.
+    async fn finish_replies(&mut self, app: &mut App, events: &mut EventReceiver) -> io::Result<()> {
+        let deadline = tokio::time::Instant::now() + SHUTDOWN_GRACE;
+        while app.ui.pending_replies > 0 {
+            app.ui.show_toast("Waiting for a reply… (Esc to quit now)");
+            self.terminal.draw(|f| {
+                app.ui.render(f, &app.state.name, app.state.mood, &app.pet_ascii(), app.failure_streak);
+            })?;
+            let event = match tokio::time::timeout_at(deadline, events.recv()).await {
+                Ok(Some(event)) => event,
+                Ok(None) | Err(_) => break,
+            };
+            match event {
+                AppEvent::Key(key) if key.code == KeyCode::Esc || is_ctrl(&key, 'c') => break,
+                AppEvent::Quit => break,
+                AppEvent::Key(_) => {}
+                // Whatever a reply can come back as is handled as usual
+                event => app.handle_event(event).await,
+            }
+        }
+        if app.ui.pending_replies > 0 {
+            tracing::warn!(pending = app.ui.pending_replies, "quit without waiting for replies");
+        }
+        Ok(())
+    }
 
+    /// Gives the terminal back to the shell and stops the process until it is
+    /// continued with `fg`, then takes the terminal over again
+    ///
+    /// SIGTSTP is caught to get here, so the process stops itself with SIGSTOP.
+    #[cfg(unix)]
+    fn suspend(&mut self, input: &InputThread) -> io::Result<()> {
+        use nix::sys::signal::{raise, Signal};
+        input.pause();
+        self.hand_back()?;
+        tracing::debug!("suspended");
+        raise(Signal::SIGSTOP).map_err(io::Error::from)?;
+        tracing::debug!("resumed");
+        self.take_over()?;
+        self.terminal.clear()?;
+        input.resume();
         Ok(())
     }
 
-    // Remove unused ui method
-    fn ui(&mut self, f: &mut Frame, app: &mut App) {
-        app.ui.render(f, &app.state.name, app.state.mood, &app.config.pet_ascii);
+    /// Hands the screen and keyboard to a program, like a pager, until it exits
+    fn hand_over(&mut self, mut command: Command, input: &InputThread) -> io::Result<ExitStatus> {
+        input.pause();
+        self.hand_back()?;
+        let status = command.status();
+        self.take_over()?;
+        self.terminal.clear()?;
+        input.resume();
+        status
     }
 }
 
+/// Raw mode delivers Ctrl+C and Ctrl+Z as keys instead of SIGINT and SIGTSTP
+fn is_ctrl(key: &KeyEvent, c: char) -> bool {
+    key.code == KeyCode::Char(c) && key.modifiers.contains(KeyModifiers::CONTROL)
+}
+
 impl<B: Backend + io::Write> Drop for Terminal<B> {
     fn drop(&mut self) {
-        let _ = disable_raw_mode();
-        let _ = execute!(
-            self.terminal.backend_mut(),
-            crossterm::terminal::LeaveAlternateScreen
-        );
+        let _ = self.hand_back();
     }
 }
\ No newline at end of file
@@ -1,23 +1,103 @@
 use ratatui::prelude::*;
-use ratatui::widgets::{Block, Borders, ListState, Paragraph, Wrap};
+use ratatui::widgets::{Block, Borders, Clear, Gauge, List, ListItem, ListState, Paragraph, Wrap};
 use ratatui::text::{Line, Span};
+use std::time::{Duration, Instant};
+
+use pawshell_core::art;
+use pawshell_core::chat_sessions::Browser;
+use pawshell_core::compare::Comparison;
+use pawshell_core::history_search::HistorySearch;
+use pawshell_core::line_editor::{KillRing, LineEditor};
+use pawshell_core::mood::MoodLevel;
+use pawshell_core::perf;
+use pawshell_core::playground::Playground;
+use pawshell_core::regex_lab::RegexLab;
+use pawshell_core::review::{self, TokenKind};
+use pawshell_core::vi_mode::{self, Vi};
 
 pub struct AppUI {
-    pub input: String,
-    pub messages: Vec<String>,
+    pub input: LineEditor,
+    /// vi's mode and register with `editing_mode = "Vi"`
+    pub vi: Option<Vi>,
+    /// Text cut from the input with Ctrl+K, Ctrl+U and the like, for Ctrl+Y
+    pub kill_ring: KillRing,
+    /// Chat messages, added with `add_message`
+    messages: Vec<String>,
+    /// Bumped whenever `messages` changes, so a frame can tell it apart cheaply
+    messages_version: u64,
     pub scroll_state: ListState,
     pub scroll_offset: usize,
+    /// Single-line pet header instead of the ASCII art, for popups
+    pub compact: bool,
+    /// Text of a yes/no dialog shown on top of everything else, and what yes does
+    pub confirmation: Option<(String, &'static str)>,
+    /// Short notice in the top right corner and when it was shown
+    pub toast: Option<(String, Instant)>,
+    /// Prompts sent to the backend that haven't been answered yet
+    pub pending_replies: usize,
+    /// Countdown of a running timer, like a pomodoro, for the title
+    pub timer: Option<String>,
+    /// Recent machine load and the pet's energy, for the title
+    pub system_load: Option<String>,
+    /// Battery level and charging state, for the title
+    pub battery: Option<String>,
+    /// The backend can't be reached and the pet answers by itself
+    pub offline: bool,
+    /// Voice input in progress, like listening or transcribing, for the title
+    pub voice: Option<String>,
+    /// The `/regex` lab, shown instead of the chat while it's open
+    pub regex_lab: Option<RegexLab>,
+    /// The `/jq` or `/awk` playground, shown instead of the chat while it's open
+    pub playground: Option<Playground>,
+    /// Register of the macro being recorded, for the title
+    pub recording_macro: Option<char>,
+    /// Countdown of a `/focus` session, which also dims the chrome
+    pub focus: Option<String>,
+    /// A download in progress, like a `/model pull`: what it's doing and how far along it is
+    pub download: Option<(String, Option<f64>)>,
+    /// Two models' answers from `/compare`, shown instead of the chat while it's open
+    pub comparison: Option<Comparison>,
+    /// The `/sessions` browser, shown instead of the chat while it's open
+    pub session_browser: Option<Browser>,
+    /// The Ctrl+R history search, shown instead of the chat while it's open
+    pub history_search: Option<HistorySearch>,
+    /// `messages` styled for the chat, kept between frames
+    message_lines: MessageLines,
 }
 
+/// How long a toast stays on screen
+const TOAST_DURATION: Duration = Duration::from_secs(3);
+
 impl AppUI {
-    pub fn new() -> Self {
+    pub fn new(welcome: &str) -> Self {
         let mut scroll_state = ListState::default();
         scroll_state.select(Some(0));
         Self {
-            input: String::new(),
-            messages: vec!["Welcome back! Type your message and press Enter to chat.".to_string()],
+            input: LineEditor::default(),
+            vi: None,
+            kill_ring: KillRing::default(),
+            messages: vec![welcome.to_string()],
+            messages_version: 0,
             scroll_state,
             scroll_offset: 0,
+            compact: false,
+            confirmation: None,
+            toast: None,
+            pending_replies: 0,
+            timer: None,
+            system_load: None,
+            battery: None,
+            offline: false,
+            voice: None,
+            regex_lab: None,
+            playground: None,
+            recording_macro: None,
+            focus: None,
+            download: None,
+            comparison: None,
+            session_browser: None,
+            history_search: None,
+            message_lines: MessageLines::default(),
         }
     }
 
@@ -61,135 +141,180 @@ impl AppUI {
         }
     }
 
+    pub fn show_toast(&mut self, text: impl Into<String>) {
+        self.toast = Some((text.into(), Instant::now()));
+    }
+
     pub fn add_message(&mut self, message: String) {
         const MAX_MESSAGES: usize = 100;
         if self.messages.len() >= MAX_MESSAGES {
             self.messages.remove(0);
         }
         self.messages.push(message);
+        self.messages_version += 1;
         self.scroll_to_bottom();
     }
 
-    pub fn render(&mut self, f: &mut Frame, pet_name: &str, pet_mood: f32, pet_ascii: &str) {
-        // Add margin around the entire UI
-        let main_area = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Length(1),  // Top margin
-                Constraint::Min(3),     // Content
-                Constraint::Length(1),  // Bottom margin
-            ])
-            .margin(1)
-            .split(f.size())[1];
-
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Length(6),    // Pet ASCII art
-                Constraint::Length(1),     // Spacing
-                Constraint::Min(5),        // Chat area
-            ])
-            .split(main_area);
+    pub fn clear_messages(&mut self) {
+        self.messages.clear();
+        self.messages_version += 1;
+    }
+
+    pub fn render(&mut self, f: &mut Frame, pet_name: &str, pet_mood: f32, pet_ascii: &str, failure_streak: usize) {
+        let (pet_area, chat_area) = if self.compact {
+            // Popups are small, so skip the margins and the art
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Length(1),  // Pet header
+                    Constraint::Min(3),     // Chat area
+                ])
+                .split(f.size());
+            (chunks[0], chunks[1])
+        } else {
+            // Add margin around the entire UI
+            let main_area = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Length(1),  // Top margin
+                    Constraint::Min(3),     // Content
+                    Constraint::Length(1),  // Bottom margin
+                ])
+                .margin(1)
+                .split(f.size())[1];
+
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    // Pet ASCII art, plus its borders
+                    Constraint::Length(art::height(pet_ascii).clamp(1, art::MAX_HEIGHT) as u16 + 2),
+                    Constraint::Length(1),     // Spacing
+                    Constraint::Min(5),        // Chat area
+                ])
+                .split(main_area);
+            (chunks[0], chunks[2])
+        };
 
         // Calculate visible lines in chat area
-        let chat_height = chunks[2].height as usize;
+        let chat_height = chat_area.height as usize;
         
         // Pet ASCII art section with modern styling
-        let mood_color = match pet_mood {
-            m if m > 0.8 => Color::LightGreen,
-            m if m > 0.4 => Color::Yellow,
-            _ => Color::LightRed,
+        let mood_color = match MoodLevel::of(pet_mood) {
+            MoodLevel::Happy => Color::LightGreen,
+            MoodLevel::Content => Color::Yellow,
+            MoodLevel::Sad => Color::LightRed,
         };
+        // Focus mode dims the chrome and stops the cursor blinking
+        let dimmed = self.focus.is_some();
+        let mood_color = if dimmed { Color::DarkGray } else { mood_color };
 
-        let pet_block = Block::default()
-            .borders(Borders::ALL)
-            .border_style(Style::default().fg(mood_color))
-            .title(Span::styled(
-                format!(" {} (Mood: {:.0}%) ", pet_name, pet_mood * 100.0),
-                Style::default().fg(mood_color).bold()
-            ))
-            .style(Style::default().bg(Color::Reset));
-        
-        let pet_text = Paragraph::new(pet_ascii)
-            .block(pet_block)
-            .alignment(Alignment::Center)
-            .style(Style::default().fg(mood_color));
-        
-        f.render_widget(pet_text, chunks[0]);
-
-        // Chat history with modern styling
-        let mut messages_text: Vec<Line> = self.messages.iter().flat_map(|msg| {
-            let mut lines = Vec::new();
-            // Extract the role and content from the message
-            let (role, content) = if msg.starts_with("user:") || msg.starts_with("assistant:") {
-                let parts: Vec<&str> = msg.splitn(2, ':').collect();
-                (parts[0], parts.get(1).map_or("", |v| v.trim()))
-            } else if msg.starts_with("You: ") {
-                ("user", &msg[5..])
-            } else {
-                let parts: Vec<&str> = msg.splitn(2, ':').collect();
-                ("assistant", parts.get(1).map_or(msg.as_str(), |v| v.trim()))
-            };
+        // Only call out failures once they form a streak
+        let streak_note = if failure_streak > 1 {
+            format!(" · {} failed commands in a row", failure_streak)
+        } else {
+            String::new()
+        };
 
-            // Format based on the role
-            match role {
-                "user" => {
-                    for (i, line) in content.split('\n').enumerate() {
-                        let line = line.trim();
-                        if !line.is_empty() {
-                            if i == 0 {
-                                lines.push(Line::from(vec![
-                                    Span::styled("You: ", Style::default().fg(Color::Cyan).bold()),
-                                    Span::styled(line, Style::default().fg(Color::White))
-                                ]));
-                            } else {
-                                lines.push(Line::from(vec![
-                                    Span::styled("     ", Style::default().fg(Color::Cyan)),
-                                    Span::styled(line, Style::default().fg(Color::White))
-                                ]));
-                            }
-                        }
-                    }
-                },
-                _ => {
-                    // Clean up content by removing extra whitespace and empty lines
-                    let content = content.lines()
-                        .map(|line| line.trim())
-                        .filter(|line| !line.is_empty())
-                        .collect::<Vec<_>>();
-
-                    for (i, line) in content.iter().enumerate() {
-                        if i == 0 {
-                            lines.push(Line::from(vec![
-                                Span::styled(format!("{}: ", pet_name), Style::default().fg(mood_color).bold()),
-                                Span::styled(*line, Style::default().fg(Color::Gray))
-                            ]));
-                        } else {
-                            lines.push(Line::from(vec![
-                                Span::styled("     ", Style::default().fg(mood_color)),
-                                Span::styled(*line, Style::default().fg(Color::Gray))
-                            ]));
-                        }
-                    }
-                    lines.push(Line::from(""));
-                }
-            }
-            lines
-        }).collect();
+        let thinking_note = if self.pending_replies > 0 { " · thinking…" } else { "" };
+        let timer_note = self.timer.as_ref().map(|timer| format!(" · {}", timer)).unwrap_or_default();
+        let load_note = self.system_load.as_ref().map(|load| format!(" · {}", load)).unwrap_or_default();
+        let battery_note = self.battery.as_ref().map(|battery| format!(" · {}", battery)).unwrap_or_default();
+        let offline_note = if self.offline { " · 📴 offline" } else { "" };
+        let voice_note = self.voice.as_ref().map(|voice| format!(" · {}", voice)).unwrap_or_default();
+        let macro_note = self.recording_macro.map(|register| format!(" · ⏺ recording @{}", register)).unwrap_or_default();
+        let focus_note = self.focus.as_ref().map(|focus| format!(" · {}", focus)).unwrap_or_default();
+        let pet_title = Span::styled(
+            format!(
+                " {} (Mood: {:.0}%{}){}{}{}{}{}{}{}{} ",
+                pet_name,
+                pet_mood * 100.0,
+                streak_note,
+                timer_note,
+                focus_note,
+                load_note,
+                battery_note,
+                offline_note,
+                voice_note,
+                macro_note,
+                thinking_note
+            ),
+            Style::default().fg(mood_color).bold()
+        );
+
+        if self.compact {
+            f.render_widget(Paragraph::new(Line::from(pet_title)), pet_area);
+        } else {
+            let pet_block = Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(mood_color))
+                .title(pet_title)
+                .style(Style::default().bg(Color::Reset));
+
+            let pet_text = Paragraph::new(pet_ascii)
+                .block(pet_block)
+                .alignment(Alignment::Center)
+                .style(Style::default().fg(mood_color));
+
+            f.render_widget(pet_text, pet_area);
+        }
+
+        if let Some(lab) = &self.regex_lab {
+            render_regex_lab(f, chat_area, lab);
+            self.render_overlays(f);
+            return;
+        }
+        if let Some(playground) = &self.playground {
+            render_playground(f, chat_area, playground);
+            self.render_overlays(f);
+            return;
+        }
+        if let Some(comparison) = &self.comparison {
+            render_comparison(f, chat_area, comparison);
+            self.render_overlays(f);
+            return;
+        }
+        if let Some(browser) = &self.session_browser {
+            render_session_browser(f, chat_area, browser);
+            self.render_overlays(f);
+            return;
+        }
+        if let Some(search) = &self.history_search {
+            render_history_search(f, chat_area, search);
+            self.render_overlays(f);
+            return;
+        }
+
+        // Chat history with modern styling, restyling only messages that changed
+        self.message_lines.update(&self.messages, self.messages_version, pet_name, mood_color);
+        let mut messages_text: Vec<Line> = self.message_lines.lines().map(borrowed).collect();
 
         // Add the current input line with cursor before creating the paragraph
-        let cursor = "█";
-        let input_line = Line::from(vec![
-            Span::styled("> ", Style::default().fg(Color::Cyan).bold()),
-            Span::styled(&self.input, Style::default().fg(Color::White)),
-            Span::styled(cursor, Style::default().fg(Color::White).add_modifier(Modifier::SLOW_BLINK))
-        ]);
-        messages_text.push(input_line);
+        let cursor_style = if dimmed { Style::default().fg(Color::Gray) } else { Style::default().fg(Color::White).add_modifier(Modifier::SLOW_BLINK) };
+        let prompt = match &self.vi {
+            Some(vi) if vi.mode == vi_mode::Mode::Normal => Span::styled("[N] ", Style::default().fg(Color::Yellow).bold()),
+            Some(_) => Span::styled("[I] ", Style::default().fg(if dimmed { Color::DarkGray } else { Color::Cyan }).bold()),
+            None => Span::styled("> ", Style::default().fg(if dimmed { Color::DarkGray } else { Color::Cyan }).bold()),
+        };
+        let chars = self.input.chars();
+        let (before, after) = chars.split_at(self.input.cursor());
+        let mut input_line = vec![prompt, Span::styled(before.iter().collect::<String>(), Style::default().fg(Color::White))];
+        // The cursor covers the character it's on, or sits after the text
+        match after.split_first() {
+            Some((current, rest)) => {
+                input_line.push(Span::styled(current.to_string(), cursor_style.add_modifier(Modifier::REVERSED)));
+                input_line.push(Span::styled(rest.iter().collect::<String>(), Style::default().fg(Color::White)));
+            }
+            None => input_line.push(Span::styled("█", cursor_style)),
+        }
+        if let Some(pending) = self.vi.as_ref().map(Vi::pending).filter(|pending| !pending.is_empty()) {
+            input_line.push(Span::styled(format!("  {}", pending), Style::default().fg(Color::DarkGray)));
+        }
+        messages_text.push(Line::from(input_line));
 
         let messages_block = Block::default()
             .borders(Borders::ALL)
             .border_style(Style::default().fg(Color::DarkGray))
-            .title(Span::styled(" Chat History ", Style::default().fg(Color::White).bold()));
+            .title(Span::styled(" Chat History ", Style::default().fg(if dimmed { Color::Gray } else { Color::White }).bold()));
 
         // Calculate total lines and adjust scroll offset to keep cursor visible
         let total_lines = messages_text.len();
@@ -205,6 +330,558 @@ impl AppUI {
             .scroll((self.scroll_offset as u16, 0))
             .alignment(Alignment::Left);
 
-        f.render_widget(messages_paragraph, chunks[2]); // Updated index
+        f.render_widget(messages_paragraph, chat_area);
+        self.render_overlays(f);
+    }
+
+    /// Draws the toast and the confirmation dialog over everything else
+    fn render_overlays(&mut self, f: &mut Frame) {
+        if self.toast.as_ref().is_some_and(|(_, shown)| shown.elapsed() >= TOAST_DURATION) {
+            self.toast = None;
+        }
+        if let Some((label, ratio)) = &self.download {
+            render_download(f, label, *ratio);
+        }
+        if let Some((text, _)) = &self.toast {
+            render_toast(f, text);
+        }
+        if let Some((question, yes)) = &self.confirmation {
+            render_confirmation(f, question, yes);
+        }
+    }
+}
+
+/// Styles a chat message, with the pet's replies in its mood color
+fn message_lines(msg: &str, pet_name: &str, mood_color: Color) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    // Extract the role and content from the message
+    let (role, content) = if msg.starts_with("user:") || msg.starts_with("assistant:") {
+        let parts: Vec<&str> = msg.splitn(2, ':').collect();
+        (parts[0], parts.get(1).map_or("", |v| v.trim()))
+    } else if let Some(content) = msg.strip_prefix("You: ") {
+        ("user", content)
+    } else {
+        let parts: Vec<&str> = msg.splitn(2, ':').collect();
+        ("assistant", parts.get(1).map_or(msg, |v| v.trim()))
+    };
+
+    // Format based on the role
+    match role {
+        "user" => {
+            for (i, line) in content.split('\n').enumerate() {
+                let line = line.trim();
+                if !line.is_empty() {
+                    if i == 0 {
+                        lines.push(Line::from(vec![
+                            Span::styled("You: ", Style::default().fg(Color::Cyan).bold()),
+                            Span::styled(line.to_string(), Style::default().fg(Color::White))
+                        ]));
+                    } else {
+                        lines.push(Line::from(vec![
+                            Span::styled("     ", Style::default().fg(Color::Cyan)),
+                            Span::styled(line.to_string(), Style::default().fg(Color::White))
+                        ]));
+                    }
+                }
+            }
+        },
+        _ => {
+            // Clean up content by removing extra whitespace and empty lines
+            let content = content.lines()
+                .map(|line| line.trim())
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>();
+
+            for (i, line) in content.iter().enumerate() {
+                if i > 0 && line.starts_with(perf::FOOTER_MARK) {
+                    lines.push(Line::from(vec![
+                        Span::styled("     ", Style::default().fg(mood_color)),
+                        Span::styled(line.to_string(), Style::default().fg(Color::DarkGray).italic())
+                    ]));
+                } else if i == 0 {
+                    lines.push(Line::from(vec![
+                        Span::styled(format!("{}: ", pet_name), Style::default().fg(mood_color).bold()),
+                        Span::styled(line.to_string(), Style::default().fg(Color::Gray))
+                    ]));
+                } else if let Some((number, code)) = review::excerpt_line(line) {
+                    let mut spans = vec![
+                        Span::styled("     ", Style::default().fg(mood_color)),
+                        Span::styled(format!("{} │ ", number), Style::default().fg(Color::DarkGray)),
+                    ];
+                    spans.extend(review::tokens(code).into_iter().map(|(kind, text)| Span::styled(text.to_string(), code_style(kind))));
+                    lines.push(Line::from(spans));
+                } else {
+                    lines.push(Line::from(vec![
+                        Span::styled("     ", Style::default().fg(mood_color)),
+                        Span::styled(line.to_string(), Style::default().fg(Color::Gray))
+                    ]));
+                }
+            }
+            lines.push(Line::from(""));
+        }
+    }
+    lines
+}
+
+/// Chat messages already styled into lines, so a frame only styles the
+/// messages that are new or changed
+///
+/// Lines aren't wrapped until they're drawn, so a resize doesn't change them;
+/// a different pet name or mood color, like in focus mode, restyles them all.
+#[derive(Default)]
+struct MessageLines {
+    styled_with: Option<(String, Color)>,
+    /// `AppUI::messages_version` the lines were styled for
+    version: Option<u64>,
+    messages: Vec<(String, Vec<Line<'static>>)>,
+}
+
+impl MessageLines {
+    fn update(&mut self, messages: &[String], version: u64, pet_name: &str, mood_color: Color) {
+        if self.styled_with.as_ref().is_some_and(|(name, color)| name == pet_name && *color == mood_color) {
+            if self.version == Some(version) {
+                return;
+            }
+        } else {
+            self.styled_with = Some((pet_name.to_string(), mood_color));
+            self.messages.clear();
+        }
+        // Messages are mostly added at the end and dropped from the start, so
+        // the cached ones are looked for in order
+        let mut cached: Vec<Option<(String, Vec<Line<'static>>)>> = std::mem::take(&mut self.messages).into_iter().map(Some).collect();
+        let mut next = 0;
+        for msg in messages {
+            let found = cached[next..].iter().position(|entry| entry.as_ref().is_some_and(|(cached, _)| cached == msg));
+            let entry = match found {
+                Some(offset) => {
+                    next += offset + 1;
+                    cached[next - 1].take()
+                }
+                None => None,
+            };
+            self.messages.push(entry.unwrap_or_else(|| (msg.clone(), message_lines(msg, pet_name, mood_color))));
+        }
+        self.version = Some(version);
+    }
+
+    fn lines(&self) -> impl Iterator<Item = &Line<'static>> {
+        self.messages.iter().flat_map(|(_, lines)| lines)
     }
-}
\ No newline at end of file
+}
+
+/// A line that borrows the text of a cached one, so drawing doesn't copy it
+fn borrowed<'a>(line: &'a Line<'static>) -> Line<'a> {
+    Line {
+        spans: line.spans.iter().map(|span| Span::styled(span.content.as_ref(), span.style)).collect(),
+        alignment: line.alignment,
+    }
+}
+
+/// Colors for code excerpts, like those of `/review`
+fn code_style(kind: TokenKind) -> Style {
+    match kind {
+        TokenKind::Keyword => Style::default().fg(Color::Magenta),
+        TokenKind::String => Style::default().fg(Color::Green),
+        TokenKind::Number => Style::default().fg(Color::Yellow),
+        TokenKind::Comment => Style::default().fg(Color::DarkGray).italic(),
+        TokenKind::Plain => Style::default().fg(Color::White),
+    }
+}
+
+/// Background colors of the capture groups in the regex lab, in turn
+const GROUP_COLORS: [Color; 5] = [Color::LightCyan, Color::LightMagenta, Color::LightGreen, Color::LightBlue, Color::LightRed];
+
+/// Draws the `/regex` lab: the pattern on top, the sample with its matches
+/// highlighted below it, and the match count or error and the explanation
+/// at the bottom
+fn render_regex_lab(f: &mut Frame, area: Rect, lab: &RegexLab) {
+    let info_height = if lab.explanation.is_some() { (area.height / 3).max(6) } else { 4 };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(3), Constraint::Length(info_height)])
+        .split(area);
+    let pattern = Line::from(vec![Span::styled(lab.pattern.as_str(), Style::default().fg(Color::White)), cursor(!lab.editing_sample)]);
+    f.render_widget(Paragraph::new(pattern).block(pane("Pattern", !lab.editing_sample)), chunks[0]);
+
+    let highlight = lab.highlight();
+    let mut sample: Vec<Line> = match &highlight {
+        Ok(highlight) => highlight
+            .lines
+            .iter()
+            .map(|segments| {
+                Line::from(
+                    segments
+                        .iter()
+                        .map(|(kind, text)| {
+                            let style = match kind {
+                                None => Style::default().fg(Color::Gray),
+                                Some(0) => Style::default().fg(Color::Black).bg(Color::Yellow),
+                                Some(group) => Style::default().fg(Color::Black).bg(GROUP_COLORS[(group - 1) % GROUP_COLORS.len()]),
+                            };
+                            Span::styled(text.as_str(), style)
+                        })
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect(),
+        Err(_) => lab.sample.split('\n').map(|line| Line::from(Span::styled(line, Style::default().fg(Color::Gray)))).collect(),
+    };
+    if let Some(last) = sample.last_mut() {
+        last.spans.push(cursor(lab.editing_sample));
+    }
+    f.render_widget(Paragraph::new(sample).block(pane("Sample Text", lab.editing_sample)).wrap(Wrap { trim: false }), chunks[1]);
+
+    let mut info = vec![match &highlight {
+        Ok(highlight) => Line::from(Span::styled(
+            format!(
+                "{} match{}, {} group{}",
+                highlight.matches,
+                if highlight.matches == 1 { "" } else { "es" },
+                highlight.groups,
+                if highlight.groups == 1 { "" } else { "s" }
+            ),
+            Style::default().fg(Color::LightGreen),
+        )),
+        // The regex crate's errors point at the problem over several lines; the last one says what it is
+        Err(e) => Line::from(Span::styled(e.lines().last().unwrap_or(e).to_string(), Style::default().fg(Color::LightRed))),
+    }];
+    if let Some(explanation) = &lab.explanation {
+        info.extend(explanation.lines().map(|line| Line::from(Span::styled(line, Style::default().fg(Color::Gray)))));
+    }
+    let info_area = chunks[2];
+    f.render_widget(
+        Paragraph::new(info).block(Block::default().borders(Borders::TOP).border_style(Style::default().fg(Color::DarkGray))).wrap(Wrap { trim: false }),
+        Rect { height: info_area.height.saturating_sub(1), ..info_area },
+    );
+    render_pane_keys(f, info_area, "[Ctrl+E]", "Explain");
+}
+
+/// Draws the `/jq` or `/awk` playground: the expression on top, and the
+/// input next to what the tool made of it
+fn render_playground(f: &mut Frame, area: Rect, playground: &Playground) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(3), Constraint::Length(1)])
+        .split(area);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(rows[1]);
+    let text = |text: &str, color: Color| -> Vec<Line> {
+        text.split('\n').map(|line| Line::from(Span::styled(line.to_string(), Style::default().fg(color)))).collect()
+    };
+    let editing_expression = !playground.editing_input;
+
+    let title = format!("{} Expression", playground.tool.name());
+    let expression = Line::from(vec![Span::styled(playground.expression.as_str(), Style::default().fg(Color::White)), cursor(editing_expression)]);
+    f.render_widget(Paragraph::new(expression).block(pane(&title, editing_expression)), rows[0]);
+
+    let mut input = text(&playground.input, Color::Gray);
+    if let Some(last) = input.last_mut() {
+        last.spans.push(cursor(playground.editing_input));
+    }
+    f.render_widget(Paragraph::new(input).block(pane("Input", playground.editing_input)).wrap(Wrap { trim: false }), columns[0]);
+
+    let (title, output, color) = match &playground.output {
+        None => ("Output", text("", Color::Gray), Color::DarkGray),
+        Some(Ok(output)) => ("Output", text(output.trim_end(), Color::White), Color::DarkGray),
+        Some(Err(e)) => ("Error", text(e.trim_end(), Color::LightRed), Color::LightRed),
+    };
+    let block = pane(title, false).border_style(Style::default().fg(color));
+    f.render_widget(Paragraph::new(output).block(block).wrap(Wrap { trim: false }), columns[1]);
+    render_pane_keys(f, rows[2], "[Ctrl+F]", "Ask me to fix it");
+}
+
+/// Draws `/compare`: the question on top and the two answers next to each
+/// other, each with its speed
+fn render_comparison(f: &mut Frame, area: Rect, comparison: &Comparison) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(3), Constraint::Length(1)])
+        .split(area);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(rows[1]);
+    let question = Paragraph::new(Span::styled(comparison.question.as_str(), Style::default().fg(Color::White)));
+    f.render_widget(question.block(pane("Question", false)), rows[0]);
+
+    for (i, answer) in comparison.answers.iter().enumerate() {
+        let won = comparison.winner == Some(i);
+        let mut lines: Vec<Line> = match &answer.reply {
+            None => vec![Line::from(Span::styled("thinking…", Style::default().fg(Color::DarkGray).italic()))],
+            Some(Ok(reply)) => reply.trim().lines().map(|line| Line::from(Span::styled(line.to_string(), Style::default().fg(Color::Gray)))).collect(),
+            Some(Err(e)) => vec![Line::from(Span::styled(e.clone(), Style::default().fg(Color::LightRed)))],
+        };
+        if let Some(speed) = &answer.speed {
+            lines.push(Line::from(""));
+            lines.push(Line::from(Span::styled(speed.footer(), Style::default().fg(Color::DarkGray).italic())));
+        }
+        let title = format!("{} · {}{}", i + 1, answer.model, if won { " 🏆" } else { "" });
+        let paragraph = Paragraph::new(lines).block(pane(&title, won)).wrap(Wrap { trim: false }).scroll((comparison.scroll, 0));
+        f.render_widget(paragraph, columns[i]);
+    }
+
+    let keys = Line::from(vec![
+        Span::styled("[←/1] [→/2]", Style::default().fg(Color::LightGreen).bold()),
+        Span::raw(" Pick the better answer   "),
+        Span::styled("[↑/↓]", Style::default().fg(Color::LightGreen).bold()),
+        Span::raw(" Scroll   "),
+        Span::styled("[Esc]", Style::default().fg(Color::LightRed).bold()),
+        Span::raw(" Back to chat"),
+    ]);
+    f.render_widget(Paragraph::new(keys), rows[2]);
+}
+
+/// Draws `/sessions`: past sessions newest first, with when they started,
+/// their title and how many exchanges they have
+fn render_session_browser(f: &mut Frame, area: Rect, browser: &Browser) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(1)])
+        .split(area);
+    let items: Vec<ListItem> = browser
+        .sessions
+        .iter()
+        .map(|session| {
+            let current = if Some(session.id) == browser.current { " (current)" } else { "" };
+            ListItem::new(Line::from(vec![
+                Span::styled(Browser::line(session), Style::default().fg(Color::Gray)),
+                Span::styled(current, Style::default().fg(Color::DarkGray).italic()),
+            ]))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(pane("Sessions", true))
+        .highlight_style(Style::default().fg(Color::Black).bg(Color::LightGreen))
+        .highlight_symbol("▶ ");
+    let mut state = ListState::default();
+    state.select(Some(browser.selected));
+    f.render_stateful_widget(list, rows[0], &mut state);
+
+    let keys = Line::from(vec![
+        Span::styled("[↑/↓]", Style::default().fg(Color::LightGreen).bold()),
+        Span::raw(" Select   "),
+        Span::styled("[Enter]", Style::default().fg(Color::LightGreen).bold()),
+        Span::raw(" Reopen and continue   "),
+        Span::styled("[Esc]", Style::default().fg(Color::LightRed).bold()),
+        Span::raw(" Back to chat"),
+    ]);
+    f.render_widget(Paragraph::new(keys), rows[1]);
+}
+
+/// Draws the Ctrl+R history search: the query on top and the matching
+/// commands under it, best first, with the matched characters highlighted
+fn render_history_search(f: &mut Frame, area: Rect, search: &HistorySearch) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(3), Constraint::Length(1)])
+        .split(area);
+    let query = Line::from(vec![Span::styled(search.query.as_str(), Style::default().fg(Color::White)), cursor(true)]);
+    let title = format!("Search History ({} commands)", search.len());
+    f.render_widget(Paragraph::new(query).block(pane(&title, true)), rows[0]);
+
+    let items: Vec<ListItem> = search
+        .matches
+        .iter()
+        .map(|(index, matched)| {
+            let spans: Vec<Span> = search
+                .command(*index)
+                .chars()
+                .enumerate()
+                .map(|(i, c)| {
+                    let style = if matched.binary_search(&(i as u32)).is_ok() {
+                        Style::default().fg(Color::Yellow).bold()
+                    } else {
+                        Style::default().fg(Color::Gray)
+                    };
+                    Span::styled(c.to_string(), style)
+                })
+                .collect();
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let title = format!("{} match{}", search.matches.len(), if search.matches.len() == 1 { "" } else { "es" });
+    let list = List::new(items)
+        .block(pane(&title, false))
+        .highlight_style(Style::default().bg(Color::DarkGray))
+        .highlight_symbol("▶ ");
+    let mut state = ListState::default();
+    state.select(Some(search.selected));
+    f.render_stateful_widget(list, rows[1], &mut state);
+
+    let keys = Line::from(vec![
+        Span::styled("[Enter]", Style::default().fg(Color::LightGreen).bold()),
+        Span::raw(" Insert as $   "),
+        Span::styled("[Ctrl+E]", Style::default().fg(Color::LightGreen).bold()),
+        Span::raw(" Explain   "),
+        Span::styled("[Ctrl+F]", Style::default().fg(Color::LightGreen).bold()),
+        Span::raw(" Improve   "),
+        Span::styled("[Esc]", Style::default().fg(Color::LightRed).bold()),
+        Span::raw(" Back to chat"),
+    ]);
+    f.render_widget(Paragraph::new(keys), rows[2]);
+}
+
+/// A bordered pane of the regex lab, a playground, `/compare`, `/sessions` or the history search, green while it's being edited
+fn pane(title: &str, focused: bool) -> Block<'static> {
+    Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(if focused { Color::LightGreen } else { Color::DarkGray }))
+        .title(Span::styled(format!(" {} ", title), Style::default().fg(Color::White).bold()))
+}
+
+fn cursor(focused: bool) -> Span<'static> {
+    Span::styled(if focused { "█" } else { "" }, Style::default().fg(Color::White).add_modifier(Modifier::SLOW_BLINK))
+}
+
+/// The key line at the bottom of `area`, with the pane's own action
+fn render_pane_keys(f: &mut Frame, area: Rect, key: &str, action: &str) {
+    let keys = Line::from(vec![
+        Span::styled("[Tab]", Style::default().fg(Color::LightGreen).bold()),
+        Span::raw(" Switch pane   "),
+        Span::styled(key.to_string(), Style::default().fg(Color::LightGreen).bold()),
+        Span::raw(format!(" {}   ", action)),
+        Span::styled("[Esc]", Style::default().fg(Color::LightRed).bold()),
+        Span::raw(" Back to chat"),
+    ]);
+    f.render_widget(Paragraph::new(keys), Rect { y: area.bottom().saturating_sub(1), height: 1.min(area.height), ..area });
+}
+
+/// Draws a one-line notice in the top right corner
+fn render_toast(f: &mut Frame, text: &str) {
+    let area = f.size();
+    let width = (text.chars().count() as u16 + 4).min(area.width);
+    let toast = Rect::new(area.x + area.width - width, area.y, width, 3.min(area.height));
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::LightGreen));
+    let paragraph = Paragraph::new(Span::styled(text, Style::default().fg(Color::White)))
+        .block(block)
+        .alignment(Alignment::Center);
+
+    f.render_widget(Clear, toast);
+    f.render_widget(paragraph, toast);
+}
+
+/// Draws a progress bar along the bottom; without a ratio, only the label
+fn render_download(f: &mut Frame, label: &str, ratio: Option<f64>) {
+    let area = f.size();
+    let width = area.width.saturating_sub(4).min(70);
+    let bar = Rect::new(area.x + (area.width - width) / 2, area.bottom().saturating_sub(4), width, 3.min(area.height));
+    let gauge = Gauge::default()
+        .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(Color::LightBlue)).title(" ⬇ Download "))
+        .gauge_style(Style::default().fg(Color::LightBlue).bg(Color::Black))
+        .ratio(ratio.unwrap_or(0.0))
+        .label(match ratio {
+            Some(ratio) => format!("{} · {:.0}%", label, ratio * 100.0),
+            None => label.to_string(),
+        });
+
+    f.render_widget(Clear, bar);
+    f.render_widget(gauge, bar);
+}
+
+/// Draws a centered yes/no dialog over the rest of the UI
+fn render_confirmation(f: &mut Frame, question: &str, yes: &str) {
+    let mut lines: Vec<Line> = question
+        .lines()
+        .map(|line| Line::from(Span::styled(line, Style::default().fg(Color::White))))
+        .collect();
+    lines.push(Line::from(""));
+    lines.push(Line::from(vec![
+        Span::styled("[y]", Style::default().fg(Color::LightGreen).bold()),
+        Span::raw(format!(" {}   ", yes)),
+        Span::styled("[n]", Style::default().fg(Color::LightRed).bold()),
+        Span::raw(" Cancel"),
+    ]));
+
+    let area = f.size();
+    let width = (area.width * 3 / 4).max(20).min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let dialog = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow))
+        .title(Span::styled(" Confirm ", Style::default().fg(Color::Yellow).bold()));
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false })
+        .alignment(Alignment::Center);
+
+    f.render_widget(Clear, dialog);
+    f.render_widget(paragraph, dialog);
+}
+
+/// Draws the startup screen listing problems found in the config
+pub fn render_config_problems(f: &mut Frame, config_path: &str, problems: &[String]) {
+    let mut lines = vec![
+        Line::from(Span::styled(
+            format!("There are problems with {}:", config_path),
+            Style::default().fg(Color::White).bold(),
+        )),
+        Line::from(""),
+    ];
+    for problem in problems {
+        for (i, line) in problem.lines().enumerate() {
+            let bullet = if i == 0 { "• " } else { "  " };
+            lines.push(Line::from(vec![
+                Span::styled(bullet, Style::default().fg(Color::LightRed)),
+                Span::styled(line, Style::default().fg(Color::Gray)),
+            ]));
+        }
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(vec![
+        Span::styled("[Enter]", Style::default().fg(Color::LightGreen).bold()),
+        Span::raw(" Continue anyway (the file is left as it is)   "),
+        Span::styled("[q]", Style::default().fg(Color::LightRed).bold()),
+        Span::raw(" Quit and fix it"),
+    ]));
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::LightRed))
+        .title(Span::styled(" Config ", Style::default().fg(Color::LightRed).bold()));
+    let paragraph = Paragraph::new(lines).block(block).wrap(Wrap { trim: false });
+    f.render_widget(paragraph, f.size());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Where a message's styled lines live, which stays put while they're reused
+    fn line_buffers(cache: &MessageLines) -> Vec<*const Line<'static>> {
+        cache.messages.iter().map(|(_, lines)| lines.as_ptr()).collect()
+    }
+
+    #[test]
+    fn message_lines_are_reused_and_restyled_when_needed() {
+        let mut messages: Vec<String> = ["You: hi", "Biscuit: meow", "You: treat?"].iter().map(|msg| msg.to_string()).collect();
+        let mut cache = MessageLines::default();
+        cache.update(&messages, 1, "Biscuit", Color::Green);
+        let before = line_buffers(&cache);
+
+        // The oldest message drops off as a new one arrives
+        messages.remove(0);
+        messages.push("Biscuit: *purrs*".to_string());
+        cache.update(&messages, 2, "Biscuit", Color::Green);
+        let after = line_buffers(&cache);
+        assert_eq!(after[..2], before[1..]);
+        assert_eq!(cache.lines().count(), 2 + 1 + 2);
+
+        // A new name or mood color restyles everything
+        cache.update(&messages, 2, "Mochi", Color::Green);
+        assert_eq!(cache.lines().next().unwrap().spans[0].content, "Mochi: ");
+        cache.update(&messages, 2, "Mochi", Color::Red);
+        assert_eq!(cache.lines().next().unwrap().spans[0].style.fg, Some(Color::Red));
+    }
+}